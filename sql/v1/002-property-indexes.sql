@@ -0,0 +1,25 @@
+-- Property indexes on the canonical node labels created by 001-create_graph.sql
+--
+-- MERGE looks nodes up by property; without these indexes every lookup scans
+-- the label table. Keep this list in sync with `age_client::PROPERTY_INDEXES`,
+-- which bulk loads drop and rebuild (see `AgeClient::bulk_load`).
+
+CREATE INDEX IF NOT EXISTS idx_fieldvalue_canonical_key
+	ON heimdall_graph."FieldValue"
+	USING btree (ag_catalog.agtype_access_operator(VARIADIC ARRAY[properties, '"canonical_key"'::agtype]));
+
+CREATE INDEX IF NOT EXISTS idx_dump_id
+	ON heimdall_graph."Dump"
+	USING btree (ag_catalog.agtype_access_operator(VARIADIC ARRAY[properties, '"id"'::agtype]));
+
+CREATE INDEX IF NOT EXISTS idx_row_dump_id
+	ON heimdall_graph."Row"
+	USING btree (ag_catalog.agtype_access_operator(VARIADIC ARRAY[properties, '"dump_id"'::agtype]));
+
+CREATE INDEX IF NOT EXISTS idx_field_name
+	ON heimdall_graph."Field"
+	USING btree (ag_catalog.agtype_access_operator(VARIADIC ARRAY[properties, '"name"'::agtype]));
+
+CREATE INDEX IF NOT EXISTS idx_rowhash_hash
+	ON heimdall_graph."RowHash"
+	USING btree (ag_catalog.agtype_access_operator(VARIADIC ARRAY[properties, '"hash"'::agtype]));
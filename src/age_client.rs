@@ -1,29 +1,213 @@
-use anyhow::Result;
 use async_trait::async_trait;
+use futures_util::stream::BoxStream;
+use futures_util::{StreamExt, TryStreamExt};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use sha2::{Digest, Sha256};
 use sqlx::PgPool;
+use sqlx::postgres::PgPoolOptions;
+use thiserror::Error;
 
-/// Sanitize a property key by replacing non-alphanumeric characters with underscores.
-/// Returns "prop" if the result would be empty.
-fn sanitize_prop_key(k: &str) -> String {
-	let mut out = String::new();
-	for c in k.chars() {
-		if c.is_ascii_alphanumeric() || c == '_' {
-			out.push(c);
+use crate::ingest::source::{SOURCE_PROP, SOURCES_PROP};
+use crate::persist::collisions::KEY_FINGERPRINT_PROP;
+use crate::persist::sightings::{Compaction, SightingCompactor};
+
+/// Errors returned by `AgeClient` and `AgeRepo` implementations.
+///
+/// Database errors are classified by SQLSTATE so callers can retry
+/// `Connection` and `Transient` failures and dead-letter the rest.
+/// Converts into `anyhow::Error` via `?` for callers that don't care.
+#[derive(Debug, Error)]
+pub enum AgeError {
+	#[error("database connection failed: {0}")]
+	Connection(String),
+
+	#[error("transient database error: {0}")]
+	Transient(String),
+
+	#[error("query failed: {0}")]
+	Query(String),
+
+	#[error("serialization failed: {0}")]
+	Serialization(String),
+
+	#[error("graph does not exist: {0}")]
+	GraphMissing(String),
+
+	#[error("AGE extension unavailable: {0}")]
+	ExtensionUnavailable(String),
+
+	#[error("property type mismatch: {0}")]
+	PropertyType(String),
+
+	#[error("invalid property key: {0:?}")]
+	InvalidPropertyKey(String),
+}
+
+impl AgeError {
+	/// Whether the operation may succeed if retried unchanged.
+	pub fn is_retryable(&self) -> bool {
+		matches!(self, AgeError::Connection(_) | AgeError::Transient(_))
+	}
+
+	/// Classify a database error by its SQLSTATE `code`.
+	fn from_sqlstate(code: &str, message: String) -> Self {
+		match code {
+			// connection_exception, admin/crash shutdown, cannot_connect_now
+			c if c.starts_with("08") => AgeError::Connection(message),
+			"57P01" | "57P02" | "57P03" => AgeError::Connection(message),
+			// transaction_rollback (serialization failure, deadlock),
+			// insufficient_resources, lock_not_available, query_canceled
+			c if c.starts_with("40") || c.starts_with("53") => AgeError::Transient(message),
+			"55P03" | "57014" => AgeError::Transient(message),
+			// invalid_schema_name: AGE reports an unknown graph this way
+			"3F000" => AgeError::GraphMissing(message),
+			_ => AgeError::Query(message),
+		}
+	}
+}
+
+impl From<sqlx::Error> for AgeError {
+	fn from(e: sqlx::Error) -> Self {
+		let message = e.to_string();
+		match e {
+			sqlx::Error::Database(db) => match db.code() {
+				Some(code) => AgeError::from_sqlstate(&code, message),
+				None => AgeError::Query(message),
+			},
+			sqlx::Error::Io(_)
+			| sqlx::Error::Tls(_)
+			| sqlx::Error::Configuration(_)
+			| sqlx::Error::PoolClosed => AgeError::Connection(message),
+			sqlx::Error::PoolTimedOut | sqlx::Error::WorkerCrashed => AgeError::Transient(message),
+			sqlx::Error::ColumnDecode { .. } | sqlx::Error::Decode(_) => {
+				AgeError::Serialization(message)
+			}
+			_ => AgeError::Query(message),
+		}
+	}
+}
+
+impl From<serde_json::Error> for AgeError {
+	fn from(e: serde_json::Error) -> Self {
+		AgeError::Serialization(e.to_string())
+	}
+}
+
+/// Result type returned by graph persistence operations.
+pub type AgeResult<T> = std::result::Result<T, AgeError>;
+
+/// Prefix of property keys encoded by [`sanitize_prop_key`].
+pub const ENCODED_PROP_KEY_PREFIX: &str = "_enc_";
+
+/// What writes do with property keys that are not plain identifiers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PropKeyPolicy {
+	/// Store them under a reversible encoding; see [`sanitize_prop_key`].
+	#[default]
+	Encode,
+	/// Fail the write with [`AgeError::InvalidPropertyKey`], so callers
+	/// learn the data was not stored.
+	Reject,
+}
+
+impl std::str::FromStr for PropKeyPolicy {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.trim().to_ascii_lowercase().as_str() {
+			"encode" => Ok(Self::Encode),
+			"reject" => Ok(Self::Reject),
+			other => Err(format!("unknown property key policy '{}'", other)),
+		}
+	}
+}
+
+impl PropKeyPolicy {
+	/// Under `Reject`, fail on the first key of `props`, at any depth,
+	/// that [`sanitize_prop_key`] would encode.
+	pub fn check(self, props: &Value) -> AgeResult<()> {
+		if self == Self::Encode {
+			return Ok(());
+		}
+		match props {
+			Value::Object(map) => map.iter().try_for_each(|(k, v)| {
+				if !is_plain_prop_key(k) {
+					return Err(AgeError::InvalidPropertyKey(k.clone()));
+				}
+				self.check(v)
+			}),
+			Value::Array(items) => items.iter().try_for_each(|v| self.check(v)),
+			_ => Ok(()),
+		}
+	}
+}
+
+/// Whether `k` is used as a property key unchanged: a non-empty run of
+/// ASCII letters, digits and `_` that does not look encoded.
+fn is_plain_prop_key(k: &str) -> bool {
+	!k.is_empty()
+		&& !k.starts_with(ENCODED_PROP_KEY_PREFIX)
+		&& k.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+/// Make a property key safe to splice into Cypher. Plain identifiers pass
+/// unchanged. Any other key becomes [`ENCODED_PROP_KEY_PREFIX`] followed by
+/// its UTF-8 bytes, ASCII letters and digits as they are and every other
+/// byte as `_` and two hex digits, so distinct keys stay distinct (`a.b`
+/// is `_enc_a_2eb`, `a_b` is kept) and [`decode_prop_key`] restores them.
+pub(crate) fn sanitize_prop_key(k: &str) -> String {
+	if is_plain_prop_key(k) {
+		return k.to_string();
+	}
+	let mut out = String::with_capacity(ENCODED_PROP_KEY_PREFIX.len() + k.len() * 3);
+	out.push_str(ENCODED_PROP_KEY_PREFIX);
+	for b in k.bytes() {
+		if b.is_ascii_alphanumeric() {
+			out.push(b as char);
 		} else {
-			out.push('_');
+			out.push_str(&format!("_{:02x}", b));
 		}
 	}
-	if out.is_empty() {
-		"prop".to_string()
-	} else {
-		out
+	out
+}
+
+/// The original of a property key stored by [`sanitize_prop_key`]; keys
+/// that are not encoded are returned as they are.
+pub fn decode_prop_key(k: &str) -> std::borrow::Cow<'_, str> {
+	let Some(encoded) = k.strip_prefix(ENCODED_PROP_KEY_PREFIX) else {
+		return std::borrow::Cow::Borrowed(k);
+	};
+	let mut bytes = Vec::with_capacity(encoded.len());
+	let mut rest = encoded.as_bytes();
+	while let Some((&b, tail)) = rest.split_first() {
+		if b != b'_' {
+			bytes.push(b);
+			rest = tail;
+			continue;
+		}
+		let byte = tail
+			.get(..2)
+			.and_then(|hex| std::str::from_utf8(hex).ok())
+			.and_then(|hex| u8::from_str_radix(hex, 16).ok());
+		match byte {
+			Some(byte) => {
+				bytes.push(byte);
+				rest = &tail[2..];
+			}
+			None => return std::borrow::Cow::Borrowed(k),
+		}
+	}
+	match String::from_utf8(bytes) {
+		Ok(decoded) => std::borrow::Cow::Owned(decoded),
+		Err(_) => std::borrow::Cow::Borrowed(k),
 	}
 }
 
 /// Sanitize a Cypher label by removing non-alphanumeric characters.
 /// Returns "FieldValue" if the result would be empty.
-fn sanitize_label(label: &str) -> String {
+pub(crate) fn sanitize_label(label: &str) -> String {
 	let mut out = String::new();
 	for c in label.chars() {
 		if c.is_ascii_alphanumeric() || c == '_' {
@@ -37,10 +221,1093 @@ fn sanitize_label(label: &str) -> String {
 	}
 }
 
+/// Render a property value as the Cypher literal of its agtype: numbers
+/// stay unquoted (integers as integers, floats with a fraction or
+/// exponent), strings are quoted and escaped, and lists and maps are
+/// rendered element by element with sanitized map keys. Integers past the
+/// signed 64-bit range agtype stores are rejected.
+fn agtype_literal(v: &Value) -> AgeResult<String> {
+	match v {
+		Value::Number(n) if n.is_u64() && n.as_i64().is_none() => Err(AgeError::Serialization(
+			format!("integer {} exceeds the agtype integer range", n),
+		)),
+		Value::Array(items) => Ok(format!(
+			"[{}]",
+			items
+				.iter()
+				.map(agtype_literal)
+				.collect::<AgeResult<Vec<_>>>()?
+				.join(", ")
+		)),
+		Value::Object(map) => Ok(format!(
+			"{{{}}}",
+			map.iter()
+				.map(|(k, v)| Ok(format!("{}: {}", sanitize_prop_key(k), agtype_literal(v)?)))
+				.collect::<AgeResult<Vec<_>>>()?
+				.join(", ")
+		)),
+		// Cypher exponents take no `+` sign.
+		Value::Number(n) if n.is_f64() => Ok(n.to_string().replace("e+", "e")),
+		_ => Ok(serde_json::to_string(v)?),
+	}
+}
+
+/// Type name of a property value as strict typing compares it; integers
+/// and floats are both numbers and null matches anything.
+fn property_type(v: &Value) -> Option<&'static str> {
+	match v {
+		Value::Null => None,
+		Value::Bool(_) => Some("boolean"),
+		Value::Number(_) => Some("number"),
+		Value::String(_) => Some("string"),
+		Value::Array(_) => Some("list"),
+		Value::Object(_) => Some("map"),
+	}
+}
+
+/// Properties in `incoming`, other than `skip`, whose type differs from the
+/// one `stored` holds under the same (sanitized) key, as
+/// `name: stored, got incoming`.
+fn property_type_mismatches(stored: &Value, incoming: &Value, skip: &[&str]) -> Vec<String> {
+	let Value::Object(map) = incoming else {
+		return Vec::new();
+	};
+	map.iter()
+		.filter_map(|(k, v)| {
+			let k = sanitize_prop_key(k);
+			if skip.contains(&k.as_str()) {
+				return None;
+			}
+			let had = property_type(stored.get(&k)?)?;
+			let got = property_type(v)?;
+			(had != got).then(|| format!("{}: {}, got {}", k, had, got))
+		})
+		.collect()
+}
+
+/// Node property nodes are merged on unless configured otherwise.
+pub const DEFAULT_KEY_PROPERTY: &str = "canonical_key";
+
+/// Properties maintained by `observe_value`; incoming props never override them.
+pub(crate) const OBSERVATION_PROPS: [&str; 4] =
+	["first_seen", "last_seen", "seen_count", "raw_samples"];
+
+/// One sighting's raw value offered to a node's `raw_samples` reservoir,
+/// with the random draws deciding whether and where it is kept.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RawSample<'a> {
+	raw: &'a str,
+	/// Most samples kept per node.
+	capacity: usize,
+	/// Uniform draw in `[0, 1)`; a full reservoir keeps the n-th sighting
+	/// when `draw * n < capacity`.
+	draw: f64,
+	/// Index replaced when a full reservoir keeps the sighting.
+	slot: usize,
+}
+
+impl<'a> RawSample<'a> {
+	/// Offer `props.raw` to a reservoir of `capacity` samples, if present.
+	fn from_props(props: &'a Value, capacity: usize) -> Option<Self> {
+		let raw = props.get("raw")?.as_str()?;
+		if raw.is_empty() || capacity == 0 {
+			return None;
+		}
+		let mut rng = rand::thread_rng();
+		Some(Self {
+			raw,
+			capacity,
+			draw: rng.gen_range(0.0..1.0),
+			slot: rng.gen_range(0..capacity),
+		})
+	}
+
+	/// `SET` items maintaining `var.raw_samples` on create and on match.
+	///
+	/// Reservoir sampling over sightings: values already sampled are kept
+	/// as-is, new values fill the reservoir up to `capacity`, and after that
+	/// the n-th sighting replaces a random sample with probability
+	/// `capacity / n`. The match item must run before `seen_count` is
+	/// incremented.
+	fn set_items(&self, var: &str) -> AgeResult<(String, String)> {
+		let raw = serde_json::to_string(self.raw)?;
+		let samples = format!("coalesce({}.raw_samples, [])", var);
+		let create = format!("{}.raw_samples = [{}]", var, raw);
+		let matched = format!(
+			"{var}.raw_samples = CASE \
+			 WHEN {raw} IN {samples} THEN {samples}[0..{cap}] \
+			 WHEN size({samples}) < {cap} THEN {samples} + [{raw}] \
+			 WHEN {draw:.9} * (coalesce({var}.seen_count, 0) + 1) < {cap} \
+			 THEN {samples}[0..{slot}] + [{raw}] + {samples}[{next}..{cap}] \
+			 ELSE {samples}[0..{cap}] END",
+			var = var,
+			raw = raw,
+			samples = samples,
+			cap = self.capacity,
+			draw = self.draw,
+			slot = self.slot,
+			next = self.slot + 1,
+		);
+		Ok((create, matched))
+	}
+}
+
+/// Build the statement merging the `(label, key)` node on the `key_prop`
+/// property and overwriting `props` on it. Leaves the node bound to `n`.
+fn merge_cypher(label: &str, key_prop: &str, key: &str, props: &Value) -> AgeResult<String> {
+	// Sanitize property keys and serialize values to JSON so they land in
+	// the query as quoted, escaped literals.
+	let mut props_kv = Vec::new();
+	if let Value::Object(map) = props {
+		for (k, v) in map.iter() {
+			props_kv.push(format!("{}: {}", sanitize_prop_key(k), agtype_literal(v)?));
+		}
+	}
+
+	Ok(format!(
+		"MERGE (n:{label} {{{prop}: {key}}}) SET n += {{{props}}}",
+		label = sanitize_label(label),
+		prop = key_prop,
+		key = serde_json::to_string(key)?,
+		props = props_kv.join(", ")
+	))
+}
+
+/// Property indexes created by `sql/v1/002-property-indexes.sql`, as
+/// `(label, property)` pairs. Bulk loads drop and rebuild these.
+pub const PROPERTY_INDEXES: &[(&str, &str)] = &[
+	("FieldValue", "canonical_key"),
+	("Dump", "id"),
+	("Row", "dump_id"),
+	("Field", "name"),
+	("RowHash", "hash"),
+];
+
+/// Name of the index on `label.property`, e.g. `idx_fieldvalue_canonical_key`.
+fn property_index_name(label: &str, property: &str) -> String {
+	format!(
+		"idx_{}_{}",
+		sanitize_label(label).to_lowercase(),
+		sanitize_prop_key(property)
+	)
+}
+
+/// Quote `ident` as a SQL identifier.
+fn quote_ident(ident: &str) -> String {
+	format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+/// `CREATE INDEX` statement for `label.property` in `graph`'s label table.
+fn create_index_sql(graph: &str, label: &str, property: &str) -> String {
+	format!(
+		"CREATE INDEX IF NOT EXISTS {} ON {}.{} USING btree \
+		 (ag_catalog.agtype_access_operator(VARIADIC ARRAY[properties, '{}'::agtype]));",
+		quote_ident(&property_index_name(label, property)),
+		quote_ident(graph),
+		quote_ident(&sanitize_label(label)),
+		serde_json::Value::String(sanitize_prop_key(property))
+	)
+}
+
+/// Build the plain `CREATE` of a `(label, key)` node with `props`, bound to
+/// `var`. Used by the bulk-load fast path, which skips the MERGE lookup and
+/// de-duplicates afterwards.
+fn create_cypher(
+	var: &str,
+	label: &str,
+	key_prop: &str,
+	key: &str,
+	props: &Value,
+) -> AgeResult<String> {
+	let mut props_kv = vec![format!("{}: {}", key_prop, serde_json::to_string(key)?)];
+	if let Value::Object(map) = props {
+		for (k, v) in map.iter() {
+			let k = sanitize_prop_key(k);
+			if k != key_prop {
+				props_kv.push(format!("{}: {}", k, agtype_literal(v)?));
+			}
+		}
+	}
+	Ok(format!(
+		"CREATE ({}:{} {{{}}})",
+		var,
+		sanitize_label(label),
+		props_kv.join(", ")
+	))
+}
+
+/// Build the statement collapsing `label` nodes sharing a `key_prop` value
+/// onto the oldest one. Properties of the removed duplicates are merged onto
+/// the kept node in creation order, matching repeated `merge_entity` calls.
+fn dedupe_cypher(label: &str, key_prop: &str) -> String {
+	format!(
+		"MATCH (n:{label}) WITH n ORDER BY id(n) \
+		 WITH n.{prop} AS k, collect(n) AS nodes WHERE size(nodes) > 1 \
+		 WITH nodes[0] AS keep, nodes[1..] AS dups UNWIND dups AS dup \
+		 SET keep += properties(dup) DETACH DELETE dup RETURN count(dup)",
+		label = sanitize_label(label),
+		prop = key_prop
+	)
+}
+
+/// Outcome of [`AgeClient::bulk_load`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BulkLoadReport {
+	/// Nodes created by the fast path.
+	pub created: u64,
+	/// Duplicate nodes removed afterwards.
+	pub duplicates_removed: u64,
+}
+
+/// Build the `MERGE ... ON CREATE SET ... ON MATCH SET ...` clause that
+/// records one observation of `(label, key)` at `timestamp`, matching on the
+/// `key_prop` property. `var` is the node variable, so several clauses can
+/// share one Cypher query.
+///
+/// On create the node gets `first_seen = last_seen = timestamp` and
+/// `seen_count = 1`; on match `first_seen` is kept, `last_seen` is updated
+/// and `seen_count` incremented. Other props are applied in both cases.
+/// With a `sample`, the raw value is offered to the `raw_samples` reservoir.
+fn observe_cypher(
+	var: &str,
+	label: &str,
+	key_prop: &str,
+	key: &str,
+	props: &Value,
+	timestamp: &str,
+	sample: Option<RawSample<'_>>,
+) -> AgeResult<String> {
+	let mut props_kv = Vec::new();
+	let mut source = None;
+	if let Value::Object(map) = props {
+		for (k, v) in map.iter() {
+			let k_s = sanitize_prop_key(k);
+			if OBSERVATION_PROPS.contains(&k_s.as_str()) {
+				continue;
+			}
+			if k_s == SOURCE_PROP {
+				source = v.as_str();
+				continue;
+			}
+			props_kv.push(format!("{}.{} = {}", var, k_s, agtype_literal(v)?));
+		}
+	}
+	let ts = serde_json::to_string(timestamp)?;
+
+	let mut on_create = vec![
+		format!("{}.first_seen = {}", var, ts),
+		format!("{}.last_seen = {}", var, ts),
+		format!("{}.seen_count = 1", var),
+	];
+	on_create.extend(props_kv.iter().cloned());
+	let mut on_match = vec![format!("{}.last_seen = {}", var, ts)];
+	if let Some(sample) = sample {
+		let (create, matched) = sample.set_items(var)?;
+		on_create.push(create);
+		on_match.push(matched);
+	}
+	on_match.push(format!(
+		"{v}.seen_count = coalesce({v}.seen_count, 0) + 1",
+		v = var
+	));
+	if let Some(source) = source {
+		let (create, matched) = source_set_items(var, source)?;
+		on_create.push(create);
+		on_match.push(matched);
+	}
+	on_match.extend(props_kv);
+
+	Ok(format!(
+		"MERGE ({var}:{label} {{{prop}: {key}}}) ON CREATE SET {create} ON MATCH SET {matched}",
+		var = var,
+		label = sanitize_label(label),
+		prop = key_prop,
+		key = serde_json::to_string(key)?,
+		create = on_create.join(", "),
+		matched = on_match.join(", "),
+	))
+}
+
+/// The `SET` items adding `source` to `var`'s [`SOURCES_PROP`] when the
+/// node is created and when it is matched.
+fn source_set_items(var: &str, source: &str) -> AgeResult<(String, String)> {
+	let source = serde_json::to_string(source)?;
+	Ok((
+		format!("{v}.{p} = [{s}]", v = var, p = SOURCES_PROP, s = source),
+		format!(
+			"{v}.{p} = CASE WHEN {v}.{p} IS NULL THEN [{s}] \
+			 WHEN {s} IN {v}.{p} THEN {v}.{p} ELSE {v}.{p} + [{s}] END",
+			v = var,
+			p = SOURCES_PROP,
+			s = source,
+		),
+	))
+}
+
+/// Build the query merging a directed `rel_type` edge from the node keyed
+/// `from_key` to the node keyed `to_key` and applying `props` to it.
+///
+/// `rel_type` is sanitized like a label; one with no usable characters is
+/// rejected rather than replaced by a default type.
+fn relate_cypher(
+	key_prop: &str,
+	from_key: &str,
+	to_key: &str,
+	rel_type: &str,
+	props: &Value,
+) -> AgeResult<String> {
+	if !rel_type
+		.chars()
+		.any(|c| c.is_ascii_alphanumeric() || c == '_')
+	{
+		return Err(AgeError::Query(format!(
+			"invalid relationship type: {:?}",
+			rel_type
+		)));
+	}
+	let mut props_kv = Vec::new();
+	if let Value::Object(map) = props {
+		for (k, v) in map.iter() {
+			props_kv.push(format!("{}: {}", sanitize_prop_key(k), agtype_literal(v)?));
+		}
+	}
+
+	Ok(format!(
+		"MATCH (a {{{prop}: {from}}}), (b {{{prop}: {to}}}) \
+		 MERGE (a)-[e:{rel}]->(b) \
+		 SET e += {{{props}}} \
+		 RETURN e",
+		prop = key_prop,
+		from = serde_json::to_string(from_key)?,
+		to = serde_json::to_string(to_key)?,
+		rel = sanitize_label(rel_type),
+		props = props_kv.join(", ")
+	))
+}
+
+/// A node to merge with [`AgeClient::merge_record_tx`], matched on its
+/// `label` and key property.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphNode {
+	pub label: String,
+	pub key: String,
+	pub props: Value,
+}
+
+impl GraphNode {
+	pub fn new(label: impl Into<String>, key: impl Into<String>, props: Value) -> Self {
+		Self {
+			label: label.into(),
+			key: key.into(),
+			props,
+		}
+	}
+}
+
+/// A directed `rel_type` edge from a record's node to the `to_label` node
+/// keyed `to_key`, which is created if missing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edge {
+	pub rel_type: String,
+	pub to_label: String,
+	pub to_key: String,
+	pub props: Value,
+}
+
+impl Edge {
+	/// An edge to the `FieldValue` node keyed `to_key`.
+	pub fn new(rel_type: impl Into<String>, to_key: impl Into<String>) -> Self {
+		Self {
+			rel_type: rel_type.into(),
+			to_label: "FieldValue".to_string(),
+			to_key: to_key.into(),
+			props: Value::Object(Default::default()),
+		}
+	}
+
+	/// Point the edge at a `to_label` node instead of a `FieldValue`.
+	pub fn with_to_label(mut self, to_label: impl Into<String>) -> Self {
+		self.to_label = to_label.into();
+		self
+	}
+
+	/// Set `props` on the edge each time it is merged.
+	pub fn with_props(mut self, props: Value) -> Self {
+		self.props = props;
+		self
+	}
+}
+
+/// Build the query merging `edge` from the `node` (which must already
+/// exist) at `timestamp`: the edge's `count` is incremented, `last_seen`
+/// moved to `timestamp` and its props applied.
+fn record_edge_cypher(
+	key_prop: &str,
+	node: &GraphNode,
+	edge: &Edge,
+	timestamp: &str,
+) -> AgeResult<String> {
+	if !edge
+		.rel_type
+		.chars()
+		.any(|c| c.is_ascii_alphanumeric() || c == '_')
+	{
+		return Err(AgeError::Query(format!(
+			"invalid relationship type: {:?}",
+			edge.rel_type
+		)));
+	}
+	let mut set = vec![
+		"e.count = coalesce(e.count, 0) + 1".to_string(),
+		format!("e.last_seen = {}", serde_json::to_string(timestamp)?),
+	];
+	if let Value::Object(map) = &edge.props {
+		for (k, v) in map.iter() {
+			let k_s = sanitize_prop_key(k);
+			if k_s == "count" || k_s == "last_seen" {
+				continue;
+			}
+			set.push(format!("e.{} = {}", k_s, agtype_literal(v)?));
+		}
+	}
+
+	Ok(format!(
+		"MATCH (n:{label} {{{prop}: {key}}}) \
+		 MERGE (m:{to_label} {{{prop}: {to}}}) \
+		 MERGE (n)-[e:{rel}]->(m) \
+		 SET {set} \
+		 RETURN e",
+		label = sanitize_label(&node.label),
+		prop = key_prop,
+		key = serde_json::to_string(&node.key)?,
+		to_label = sanitize_label(&edge.to_label),
+		to = serde_json::to_string(&edge.to_key)?,
+		rel = sanitize_label(&edge.rel_type),
+		set = set.join(", "),
+	))
+}
+
+/// Merge the `Dump` node `dump_id`, stamping `received_at` when created.
+/// Leaves the node bound to `d`.
+fn dump_clause(dump_id_json: &str, timestamp_json: &str) -> String {
+	format!(
+		"MERGE (d:Dump {{id: {}}}) ON CREATE SET d.received_at = {}",
+		dump_id_json, timestamp_json
+	)
+}
+
+/// What the retention sweeper does with a node past its label's TTL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetentionAction {
+	/// Set `tombstone = true` and `expired_at`, leaving removal to tombstone
+	/// garbage collection.
+	Tombstone,
+	/// Detach and delete the node outright.
+	Delete,
+}
+
+impl std::str::FromStr for RetentionAction {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.trim().to_ascii_lowercase().as_str() {
+			"tombstone" => Ok(Self::Tombstone),
+			"delete" => Ok(Self::Delete),
+			other => Err(format!("unknown retention action '{}'", other)),
+		}
+	}
+}
+
+/// Build the statement expiring at most `limit` `label` nodes last seen
+/// before `cutoff` (an RFC 3339 timestamp, compared as a string like the
+/// stored `last_seen`). Already tombstoned nodes are not tombstoned again.
+/// Returns the number of nodes expired.
+fn expire_cypher(
+	label: &str,
+	cutoff: &str,
+	now: &str,
+	action: RetentionAction,
+	limit: usize,
+) -> AgeResult<String> {
+	let cutoff = serde_json::to_string(cutoff)?;
+	let label = sanitize_label(label);
+	Ok(match action {
+		RetentionAction::Tombstone => format!(
+			"MATCH (n:{label}) WHERE n.last_seen < {cutoff} AND n.tombstone IS NULL \
+			 WITH n LIMIT {limit} \
+			 SET n.tombstone = true, n.expired_at = {now} RETURN count(n)",
+			now = serde_json::to_string(now)?,
+		),
+		RetentionAction::Delete => format!(
+			"MATCH (n:{label}) WHERE n.last_seen < {cutoff} \
+			 WITH n LIMIT {limit} DETACH DELETE n RETURN count(n)"
+		),
+	})
+}
+
+/// Build the query detaching and deleting up to `limit` nodes whose only
+/// ingest source is `source`, returning how many were deleted.
+fn delete_by_source_cypher(source: &str, limit: usize) -> AgeResult<String> {
+	Ok(format!(
+		"MATCH (n) WHERE n.{prop} = [{source}] WITH n LIMIT {limit} DETACH DELETE n RETURN count(n)",
+		prop = SOURCES_PROP,
+		source = serde_json::to_string(source)?,
+	))
+}
+
+/// Build the query removing `source` from the ingest sources of up to
+/// `limit` nodes, returning how many were updated.
+fn release_source_cypher(source: &str, limit: usize) -> AgeResult<String> {
+	Ok(format!(
+		"MATCH (n) WHERE {source} IN n.{prop} WITH n LIMIT {limit} \
+		 SET n.{prop} = [s IN n.{prop} WHERE s <> {source}] RETURN count(n)",
+		prop = SOURCES_PROP,
+		source = serde_json::to_string(source)?,
+	))
+}
+
+/// Build the query returning the keys of up to `limit` nodes whose
+/// canonical value `normalizer` produced at a version below `current`.
+fn outdated_normalizations_cypher(
+	key_prop: &str,
+	normalizer: &str,
+	current: u32,
+	limit: usize,
+) -> AgeResult<String> {
+	Ok(format!(
+		"MATCH (n) WHERE n.normalizer = {normalizer} AND n.normalizer_version < {current} \
+		 RETURN n.{prop} LIMIT {limit}",
+		normalizer = serde_json::to_string(normalizer)?,
+		prop = key_prop,
+	))
+}
+
+/// Query for the distinct `ip_hex` of `FieldValue` nodes in `range`.
+fn ips_in_range_cypher(range: &crate::lib::normalizers::IpRange) -> AgeResult<String> {
+	Ok(format!(
+		"MATCH (n:FieldValue) WHERE n.ip_version = {version} \
+		 AND n.ip_hex >= {first} AND n.ip_hex <= {last} \
+		 RETURN DISTINCT n.ip_hex ORDER BY n.ip_hex",
+		version = range.version,
+		first = serde_json::to_string(&range.first)?,
+		last = serde_json::to_string(&range.last)?,
+	))
+}
+
+/// Stops co-occurrence edges from piling up on hub values, such as a
+/// constant column shared by every row. See [`AgeClient::with_hub_guard`].
+#[derive(Debug, Clone)]
+pub struct HubGuard {
+	/// `CO_OCCURS` degree at which a value is a hub.
+	pub max_degree: usize,
+	/// Set `is_hub = true` on values found to be hubs.
+	pub flag: bool,
+	/// Counts co-occurrences skipped at hubs.
+	pub skipped: prometheus::IntCounter,
+}
+
+/// Build the query telling whether the `FieldValue` keyed `key` is a hub:
+/// flagged `is_hub`, or with at least `max_degree` `CO_OCCURS` edges.
+/// Edges are counted only up to `max_degree`. Returns no row when the node
+/// does not exist.
+fn hub_cypher(key_prop: &str, key: &str, max_degree: usize) -> AgeResult<String> {
+	Ok(format!(
+		"MATCH (n:FieldValue {{{prop}: {key}}}) \
+		 OPTIONAL MATCH (n)-[e:CO_OCCURS]-() \
+		 WITH n, e LIMIT {max} \
+		 WITH n, count(e) AS degree \
+		 RETURN coalesce(n.is_hub, false) OR degree >= {max}",
+		prop = key_prop,
+		key = serde_json::to_string(key)?,
+		max = max_degree,
+	))
+}
+
+/// Values whose co-occurrences [`AgeClient::rebuild_cooccurrence`]
+/// recomputes per batch.
+const COOCCURRENCE_REBUILD_BATCH: usize = 500;
+
+/// Build the query listing, in key order, up to `limit` keys after `after`
+/// of values observed in a row of `dump_id` (any row when `None`).
+fn cooccurrence_values_cypher(
+	key_prop: &str,
+	dump_id: Option<&str>,
+	after: &str,
+	limit: usize,
+) -> AgeResult<String> {
+	let row = match dump_id {
+		Some(id) => format!("(r:Row {{dump_id: {}}})", serde_json::to_string(id)?),
+		None => "(r:Row)".to_string(),
+	};
+	Ok(format!(
+		"MATCH {row}-[:HAS_SIGHTING]->(:Sighting)-[:OBSERVED_VALUE]->(a:FieldValue) \
+		 WHERE a.{prop} > {after} \
+		 RETURN DISTINCT a.{prop} AS k ORDER BY k LIMIT {limit}",
+		prop = key_prop,
+		after = serde_json::to_string(after)?,
+	))
+}
+
+/// Build the query setting the `CO_OCCURS` edge between each value in
+/// `keys` and every greater-keyed value sharing a row with it: `count` is
+/// the number of rows holding both and `last_seen` their latest sighting.
+/// With `dump_id`, only pairs sharing a row of that dump are written, still
+/// counting rows of every dump. Yields one row per edge.
+fn rebuild_cooccurrence_cypher(
+	key_prop: &str,
+	keys: &[String],
+	dump_id: Option<&str>,
+) -> AgeResult<String> {
+	let scope = match dump_id {
+		Some(id) => format!(
+			"WITH a, b, rows, last_seen, dumps WHERE {} IN dumps ",
+			serde_json::to_string(id)?
+		),
+		None => String::new(),
+	};
+	Ok(format!(
+		"MATCH (a:FieldValue)<-[:OBSERVED_VALUE]-(:Sighting)<-[:HAS_SIGHTING]-(r:Row)\
+		 -[:HAS_SIGHTING]->(s:Sighting)-[:OBSERVED_VALUE]->(b:FieldValue) \
+		 WHERE a.{prop} IN {keys} AND a.{prop} < b.{prop} \
+		 WITH a, b, count(DISTINCT r) AS rows, max(s.timestamp) AS last_seen, \
+		 collect(DISTINCT r.dump_id) AS dumps \
+		 {scope}\
+		 MERGE (a)-[co:CO_OCCURS]-(b) \
+		 SET co.count = rows, co.last_seen = last_seen \
+		 RETURN co",
+		prop = key_prop,
+		keys = serde_json::to_string(keys)?,
+	))
+}
+
+/// Rows [`AgeClient::query_stream`] reads ahead of its consumer.
+const QUERY_STREAM_BUFFER: usize = 64;
+
+/// Nodes [`AgeClient::delete_by_source`] deletes or updates per statement.
+pub const SOURCE_DELETE_BATCH: usize = 1_000;
+
+/// JSON for one `agtype` value rendered as text. Vertices, edges and paths
+/// carry a `::vertex`/`::edge`/`::path` suffix that is not JSON and is
+/// dropped; anything still unparseable is returned as a string.
+fn agtype_json(text: &str) -> Value {
+	if let Ok(value) = serde_json::from_str(text) {
+		return value;
+	}
+	let stripped = ["::vertex", "::edge", "::path"]
+		.iter()
+		.find_map(|suffix| text.strip_suffix(suffix));
+	// Vertices and edges inside a path keep their own suffixes.
+	let stripped = stripped.map(|s| s.replace("}::vertex", "}").replace("}::edge", "}"));
+	stripped
+		.and_then(|s| serde_json::from_str(&s).ok())
+		.unwrap_or_else(|| Value::String(text.to_string()))
+}
+
+/// Upper bound on the edges returned by one [`AgeRepo::neighbors`] call.
+pub const MAX_NEIGHBORS: usize = 500;
+
+/// A node and its immediate neighbors, see [`AgeRepo::neighbors`].
+///
+/// Nodes are `{id, label, properties}` objects, the looked-up node first;
+/// edges are `{id, type, start, end, properties}` objects whose `start` and
+/// `end` refer to node ids.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct GraphFragment {
+	pub nodes: Vec<Value>,
+	pub edges: Vec<Value>,
+}
+
+/// A node listed by [`AgeRepo::rekey_candidates`]: its key and the stored
+/// properties its unsalted key can be recovered from.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct RekeyCandidate {
+	pub key: String,
+	#[serde(default)]
+	pub field_type: Option<String>,
+	/// Canonical value, stored on values linked by `persist_row`.
+	#[serde(default)]
+	pub value: Option<String>,
+	/// Original value, stored by the ingest sink unless PII policy drops it.
+	#[serde(default)]
+	pub raw: Option<String>,
+}
+
+/// What [`AgeRepo::rekey`] did with a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RekeyOutcome {
+	/// The node now carries the new key.
+	Renamed,
+	/// A node with the new key already existed: the old node's edges were
+	/// moved onto it and the old node removed.
+	Merged,
+	/// No node has the old key.
+	Missing,
+}
+
+/// Build the query listing, in key order, up to `limit` `label` nodes
+/// keyed after `after` as [`RekeyCandidate`] maps.
+fn rekey_candidates_cypher(
+	label: &str,
+	key_prop: &str,
+	after: &str,
+	limit: usize,
+) -> AgeResult<String> {
+	Ok(format!(
+		"MATCH (n:{label}) WHERE n.{prop} > {after} \
+		 WITH n ORDER BY n.{prop} LIMIT {limit} \
+		 RETURN {{key: n.{prop}, field_type: n.field_type, value: n.value, raw: n.raw}}",
+		label = sanitize_label(label),
+		prop = key_prop,
+		after = serde_json::to_string(after)?,
+	))
+}
+
+/// Build the query returning `[id(old), id(new)]` for the `label` nodes
+/// keyed `old_key` and `new_key`; the second id is null when there is no
+/// `new_key` node.
+fn rekey_ids_cypher(
+	label: &str,
+	key_prop: &str,
+	old_key: &str,
+	new_key: &str,
+) -> AgeResult<String> {
+	Ok(format!(
+		"MATCH (o:{label} {{{prop}: {old}}}) \
+		 OPTIONAL MATCH (n:{label} {{{prop}: {new}}}) \
+		 RETURN [id(o), id(n)] LIMIT 1",
+		label = sanitize_label(label),
+		prop = key_prop,
+		old = serde_json::to_string(old_key)?,
+		new = serde_json::to_string(new_key)?,
+	))
+}
+
+/// Build the query returning the `label` node keyed `key` as a
+/// `{id, label, properties}` map.
+fn node_cypher(label: &str, key_prop: &str, key: &str) -> AgeResult<String> {
+	Ok(format!(
+		"MATCH (n:{label} {{{prop}: {key}}}) \
+		 RETURN {{id: id(n), label: label(n), properties: properties(n)}} LIMIT 1",
+		label = sanitize_label(label),
+		prop = key_prop,
+		key = serde_json::to_string(key)?,
+	))
+}
+
+/// Build the query returning up to `limit` (at most [`MAX_NEIGHBORS`])
+/// edges touching the `label` node keyed `key`, in either direction, with
+/// the node at their other end. Only `edge_types` are followed unless the
+/// list is empty.
+fn neighbors_cypher(
+	label: &str,
+	key_prop: &str,
+	key: &str,
+	edge_types: &[&str],
+	limit: usize,
+) -> AgeResult<String> {
+	let mut types = Vec::with_capacity(edge_types.len());
+	for edge_type in edge_types {
+		let sanitized = sanitize_label(edge_type);
+		if !edge_type
+			.chars()
+			.any(|c| c.is_ascii_alphanumeric() || c == '_')
+		{
+			return Err(AgeError::Query(format!(
+				"invalid relationship type: {:?}",
+				edge_type
+			)));
+		}
+		types.push(serde_json::to_string(&sanitized)?);
+	}
+	let filter = if types.is_empty() {
+		String::new()
+	} else {
+		format!(" WHERE label(e) IN [{}]", types.join(", "))
+	};
+
+	Ok(format!(
+		"MATCH (n:{label} {{{prop}: {key}}})-[e]-(m){filter} \
+		 RETURN {{id: id(e), type: label(e), start: start_id(e), end: end_id(e), properties: properties(e)}}, \
+		 {{id: id(m), label: label(m), properties: properties(m)}} \
+		 LIMIT {limit}",
+		label = sanitize_label(label),
+		prop = key_prop,
+		key = serde_json::to_string(key)?,
+		filter = filter,
+		limit = limit.min(MAX_NEIGHBORS),
+	))
+}
+
+/// Build the query merging the `Dump` node `dump_id` and setting `props`
+/// on it. Null properties are skipped rather than removed.
+fn dump_cypher(dump_id: &str, props: &Value, timestamp: &str) -> AgeResult<String> {
+	let mut cypher = dump_clause(
+		&serde_json::to_string(dump_id)?,
+		&serde_json::to_string(timestamp)?,
+	);
+	let mut assignments = Vec::new();
+	if let Value::Object(map) = props {
+		for (k, v) in map.iter().filter(|(_, v)| !v.is_null()) {
+			assignments.push(format!(
+				"d.{} = {}",
+				sanitize_prop_key(k),
+				agtype_literal(v)?
+			));
+		}
+	}
+	if !assignments.is_empty() {
+		cypher.push_str("\nSET ");
+		cypher.push_str(&assignments.join(", "));
+	}
+	cypher.push_str("\nRETURN d");
+	Ok(cypher)
+}
+
+/// One cell of a row: `(column, raw_value, canonical_key, canonical_value)`.
+pub type Cell = (String, String, String, String);
+
+/// Whether a computed row hash depends on the order of the row's columns.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RowHashOrder {
+	/// The same cells in a different column order hash differently.
+	#[default]
+	Columns,
+	/// Cells are sorted first, so column order does not matter.
+	Unordered,
+}
+
+/// Row hash over each cell's column and canonical value, in column order.
+/// See [`row_hash_with`].
+pub fn row_hash_for(cells: &[Cell]) -> String {
+	row_hash_with(cells, RowHashOrder::Columns)
+}
+
+/// Hex SHA-256 over each cell's column and canonical value. Raw values are
+/// left out, so rows that differ only in formatting hash alike.
+pub fn row_hash_with(cells: &[Cell], order: RowHashOrder) -> String {
+	let mut parts: Vec<(&str, &str)> = cells
+		.iter()
+		.map(|(column, _, _, value)| (column.as_str(), value.as_str()))
+		.collect();
+	if order == RowHashOrder::Unordered {
+		parts.sort_unstable();
+	}
+	let mut hasher = Sha256::new();
+	for (column, value) in parts {
+		// Length-prefix each field so ("ab", "c") and ("a", "bc") differ.
+		for field in [column, value] {
+			hasher.update((field.len() as u64).to_be_bytes());
+			hasher.update(field.as_bytes());
+		}
+	}
+	format!("{:x}", hasher.finalize())
+}
+
+/// Queries issued by `AgeClient::persist_row` for one row.
+struct RowCypher {
+	/// For rows with a `row_hash`: merges the Row on `(dump_id, row_hash)`,
+	/// increments its `seen_count` and returns it.
+	claim: Option<String>,
+	/// Creates the Row (or matches the claimed one) and its sightings.
+	body: String,
+}
+
+/// Build the Cypher for `persist_row`. `key_prop` is the FieldValue key
+/// property. With a `compactor`, sightings it coalesces update an existing
+/// `Sighting` instead of creating one.
+fn row_cypher(
+	key_prop: &str,
+	dump_id: &str,
+	row_index: i64,
+	row_hash: Option<&str>,
+	cells: &[(String, String, String, String)],
+	timestamp: &str,
+	compactor: Option<&SightingCompactor>,
+) -> AgeResult<RowCypher> {
+	let dump_id_json = serde_json::to_string(dump_id)?;
+	let timestamp_json = serde_json::to_string(timestamp)?;
+	let dump_clause = dump_clause(&dump_id_json, &timestamp_json);
+
+	let (claim, mut cypher) = if let Some(hash) = row_hash {
+		let hash_json = serde_json::to_string(hash)?;
+		let claim = format!(
+			"{}\nMERGE (r:Row {{dump_id: {}, row_hash: {}}}) \
+			 ON CREATE SET r.index = {}, r.seen_count = 0\n\
+			 SET r.seen_count = r.seen_count + 1\n\
+			 MERGE (d)-[:HAS_ROW]->(r)\n\
+			 RETURN r.seen_count",
+			dump_clause, dump_id_json, hash_json, row_index
+		);
+		let matched = format!(
+			"MATCH (r:Row {{dump_id: {}, row_hash: {}}})",
+			dump_id_json, hash_json
+		);
+		(Some(claim), matched)
+	} else {
+		let created = format!(
+			"{}\nCREATE (r:Row {{dump_id: {}, index: {}}})\nCREATE (d)-[:HAS_ROW]->(r)",
+			dump_clause, dump_id_json, row_index
+		);
+		(None, created)
+	};
+
+	// Process each cell
+	for (i, (column, raw, canonical_key, canonical_value)) in cells.iter().enumerate() {
+		let column_json = serde_json::to_string(column)?;
+		let raw_json = serde_json::to_string(raw)?;
+		let canonical_key_json = serde_json::to_string(canonical_key)?;
+		let canonical_value_json = serde_json::to_string(canonical_value)?;
+
+		// Use unique variable names for each cell
+		let fv_var = format!("fv{}", i);
+		let f_var = format!("f{}", i);
+		let s_var = format!("s{}", i);
+
+		cypher.push_str(&format!(
+			"\nMERGE ({}:FieldValue {{{}: {}}}) ON CREATE SET {}.value = {}, {}.created_at = {}",
+			fv_var,
+			key_prop,
+			canonical_key_json,
+			fv_var,
+			canonical_value_json,
+			fv_var,
+			timestamp_json
+		));
+		cypher.push_str(&format!(
+			"\nMERGE ({}:Field {{name: {}}})",
+			f_var, column_json
+		));
+		cypher.push_str(&format!("\nMERGE ({})-[:VALUE_OF]->({})", fv_var, f_var));
+		let (sighting, link) = match compactor.map(|c| c.admit(canonical_key, column, timestamp)) {
+			None => (
+				format!(
+					"CREATE ({}:Sighting {{column: {}, raw: {}, timestamp: {}}})",
+					s_var, column_json, raw_json, timestamp_json
+				),
+				"CREATE",
+			),
+			Some(Compaction::New(id)) => (
+				format!(
+					"CREATE ({}:Sighting {{sighting_id: {}, column: {}, raw: {}, timestamp: {}, \
+					 count: 1, first_seen: {}, last_seen: {}}})",
+					s_var,
+					serde_json::to_string(&id)?,
+					column_json,
+					raw_json,
+					timestamp_json,
+					timestamp_json,
+					timestamp_json
+				),
+				"CREATE",
+			),
+			// MERGE rather than MATCH: a sighting admitted for a row that
+			// turned out to be a duplicate was never written.
+			Some(Compaction::Coalesced {
+				id,
+				count,
+				last_seen,
+			}) => (
+				format!(
+					"MERGE ({s}:Sighting {{sighting_id: {}}}) ON CREATE SET {s}.column = {}, \
+					 {s}.raw = {}, {s}.timestamp = {}, {s}.first_seen = {}\n\
+					 SET {s}.count = {}, {s}.last_seen = {}",
+					serde_json::to_string(&id)?,
+					column_json,
+					raw_json,
+					timestamp_json,
+					timestamp_json,
+					count,
+					serde_json::to_string(&last_seen)?,
+					s = s_var
+				),
+				"MERGE",
+			),
+		};
+		cypher.push_str(&format!("\n{}", sighting));
+		cypher.push_str(&format!("\n{} (r)-[:HAS_SIGHTING]->({})", link, s_var));
+		cypher.push_str(&format!(
+			"\n{} ({})-[:OBSERVED_VALUE]->({})",
+			link, s_var, fv_var
+		));
+	}
+
+	cypher.push_str("\nRETURN r");
+	Ok(RowCypher {
+		claim,
+		body: cypher,
+	})
+}
+
+/// Oldest Apache AGE release Heimdall's Cypher is known to work with.
+pub const MIN_AGE_VERSION: &str = "1.4.0";
+
+/// The `age` extension installed in the connected database.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AgeInfo {
+	/// `extversion` from `pg_extension`, e.g. `1.5.0`.
+	pub version: String,
+}
+
+/// Whether dotted version `version` is at least `min`. Missing or
+/// non-numeric components compare as 0, so `1.5` equals `1.5.0`.
+pub fn version_at_least(version: &str, min: &str) -> bool {
+	let parts = |v: &str| -> Vec<u64> {
+		v.split(['.', '-'])
+			.map(|p| p.parse::<u64>().unwrap_or(0))
+			.collect()
+	};
+	let (have, want) = (parts(version), parts(min));
+	for i in 0..have.len().max(want.len()) {
+		let (h, w) = (
+			have.get(i).copied().unwrap_or(0),
+			want.get(i).copied().unwrap_or(0),
+		);
+		if h != w {
+			return h > w;
+		}
+	}
+	true
+}
+
+/// Per-connection settings applied by [`AgeClient::connect_with`].
+#[derive(Debug, Clone, Default)]
+pub struct AgeConnectOptions {
+	/// `search_path` set on each new connection, e.g.
+	/// `ag_catalog, tenant_x, public`. Queries call `cypher` and `agtype`
+	/// unqualified, so it must include `ag_catalog`. `None` keeps the
+	/// server default.
+	pub search_path: Option<String>,
+}
+
+/// Whether the graph `name` exists in the AGE catalog.
+async fn graph_exists(pool: &PgPool, name: &str) -> AgeResult<bool> {
+	let exists: bool = sqlx::query_scalar(
+		"SELECT EXISTS (SELECT 1 FROM ag_catalog.ag_graph WHERE name = $1::name);",
+	)
+	.bind(name)
+	.fetch_one(pool)
+	.await?;
+	Ok(exists)
+}
+
 /// Minimal AGE client wrapper for Postgres + Apache AGE.
 pub struct AgeClient {
 	pool: PgPool,
 	graph: String,
+	/// Node property holding the canonical key that nodes are merged on.
+	key_property: String,
+	/// Capacity of the per-node `raw_samples` reservoir; `None` disables it.
+	raw_samples: Option<usize>,
+	/// Hash rows persisted without a `row_hash`; `None` leaves them unhashed.
+	auto_row_hash: Option<RowHashOrder>,
+	/// Skip co-occurrences at hub values; `None` records every pair.
+	hub_guard: Option<HubGuard>,
+	/// Reject single-node writes that change a stored property's type.
+	strict_property_types: bool,
+	/// Coalesce identical consecutive sightings; `None` creates every one.
+	sightings: Option<SightingCompactor>,
+	/// What writes do with property keys that are not plain identifiers.
+	prop_keys: PropKeyPolicy,
 }
 
 impl AgeClient {
@@ -49,12 +1316,178 @@ impl AgeClient {
 		Self {
 			pool,
 			graph: graph.into(),
+			key_property: DEFAULT_KEY_PROPERTY.to_string(),
+			raw_samples: None,
+			auto_row_hash: None,
+			hub_guard: None,
+			strict_property_types: false,
+			sightings: None,
+			prop_keys: PropKeyPolicy::default(),
 		}
 	}
 
+	/// Merge nodes on `property` instead of `canonical_key`, e.g. to match an
+	/// existing schema. The name is sanitized like other property keys.
+	pub fn with_key_property(mut self, property: &str) -> Self {
+		self.key_property = sanitize_prop_key(property);
+		self
+	}
+
+	/// Node property nodes are merged on.
+	pub fn key_property(&self) -> &str {
+		&self.key_property
+	}
+
+	/// AGE graph the client reads and writes.
+	pub fn graph(&self) -> &str {
+		&self.graph
+	}
+
+	/// Keep up to `capacity` distinct raw values per node in a `raw_samples`
+	/// array, chosen by reservoir sampling over observations. Only props
+	/// carrying a `raw` string are sampled; 0 disables sampling.
+	pub fn with_raw_samples(mut self, capacity: usize) -> Self {
+		self.raw_samples = (capacity > 0).then_some(capacity);
+		self
+	}
+
+	/// Compute a `row_hash` with [`row_hash_with`] for rows persisted
+	/// without one, so identical rows of a dump are deduplicated.
+	pub fn with_auto_row_hash(mut self, order: RowHashOrder) -> Self {
+		self.auto_row_hash = Some(order);
+		self
+	}
+
+	/// Skip co-occurrences involving a value that already has
+	/// `guard.max_degree` `CO_OCCURS` edges (or is flagged `is_hub`),
+	/// counting each skip and optionally flagging the hub. A `max_degree`
+	/// of 0 disables the guard.
+	pub fn with_hub_guard(mut self, guard: HubGuard) -> Self {
+		self.hub_guard = (guard.max_degree > 0).then_some(guard);
+		self
+	}
+
+	/// Make `merge_entity` and `observe_value` fail with
+	/// [`AgeError::PropertyType`] instead of storing a value whose type
+	/// differs from the one the node already holds for that property, e.g.
+	/// a string `seen_count` on a node where it is a number.
+	pub fn with_strict_property_types(mut self) -> Self {
+		self.strict_property_types = true;
+		self
+	}
+
+	/// Fold a sighting of a value in a column seen again within
+	/// `window_secs` of its previous sighting into that `Sighting`, raising
+	/// its `count` and `last_seen`, instead of creating another; see
+	/// [`SightingCompactor`]. A window of 0 disables compaction.
+	pub fn with_sighting_compaction(mut self, window_secs: u64) -> Self {
+		self.sightings = (window_secs > 0).then(|| SightingCompactor::new(window_secs));
+		self
+	}
+
+	/// Encode or reject property keys that are not plain identifiers; see
+	/// [`PropKeyPolicy`]. Keys are encoded by default.
+	pub fn with_prop_key_policy(mut self, policy: PropKeyPolicy) -> Self {
+		self.prop_keys = policy;
+		self
+	}
+
+	/// With strict property typing, fail if `props` (other than `skip`) would
+	/// change the type of a property stored on the `(label, key)` node. A
+	/// missing node passes.
+	async fn check_property_types(
+		&self,
+		label: &str,
+		key: &str,
+		props: &Value,
+		skip: &[&str],
+	) -> AgeResult<()> {
+		if !self.strict_property_types {
+			return Ok(());
+		}
+		let node: Option<String> =
+			sqlx::query_scalar("SELECT n::text FROM cypher($1::text, $2::text) as (n agtype);")
+				.bind(&self.graph)
+				.bind(node_cypher(label, &self.key_property, key)?)
+				.fetch_optional(&self.pool)
+				.await?;
+		let Some(node) = node else {
+			return Ok(());
+		};
+		let node: Value = serde_json::from_str(&node)?;
+		let mismatches = property_type_mismatches(&node["properties"], props, skip);
+		if mismatches.is_empty() {
+			Ok(())
+		} else {
+			Err(AgeError::PropertyType(format!(
+				"{} {}: {}",
+				sanitize_label(label),
+				key,
+				mismatches.join("; ")
+			)))
+		}
+	}
+
+	/// Keys among `keys` whose `FieldValue` is a hub under `max_degree`.
+	async fn hubs<'k>(&self, keys: [&'k str; 2], max_degree: usize) -> AgeResult<Vec<&'k str>> {
+		let mut hubs = Vec::new();
+		for key in keys {
+			let hub: Option<String> =
+				sqlx::query_scalar("SELECT h::text FROM cypher($1::text, $2::text) as (h agtype);")
+					.bind(&self.graph)
+					.bind(hub_cypher(&self.key_property, key, max_degree)?)
+					.fetch_optional(&self.pool)
+					.await?;
+			if hub.as_deref() == Some("true") {
+				hubs.push(key);
+			}
+		}
+		Ok(hubs)
+	}
+
+	/// The `row_hash` a row is persisted with: the caller's, or a computed
+	/// one when automatic hashing is on.
+	fn row_hash(&self, row_hash: Option<&str>, cells: &[Cell]) -> Option<String> {
+		match (row_hash, self.auto_row_hash) {
+			(Some(hash), _) => Some(hash.to_string()),
+			(None, Some(order)) => Some(row_hash_with(cells, order)),
+			(None, None) => None,
+		}
+	}
+
+	fn raw_sample<'a>(&self, props: &'a Value) -> Option<RawSample<'a>> {
+		RawSample::from_props(props, self.raw_samples?)
+	}
+
 	/// Connect helper using a DATABASE_URL-like string
-	pub async fn connect(database_url: &str, graph: &str) -> Result<Self> {
-		let pool = PgPool::connect(database_url).await?;
+	pub async fn connect(database_url: &str, graph: &str) -> AgeResult<Self> {
+		Self::connect_with(database_url, graph, AgeConnectOptions::default()).await
+	}
+
+	/// Like [`AgeClient::connect`], applying `options` to every pooled
+	/// connection as it is opened.
+	pub async fn connect_with(
+		database_url: &str,
+		graph: &str,
+		options: AgeConnectOptions,
+	) -> AgeResult<Self> {
+		let search_path = options.search_path.filter(|p| !p.trim().is_empty());
+		let pool = PgPoolOptions::new()
+			.after_connect(move |conn, _meta| {
+				let search_path = search_path.clone();
+				Box::pin(async move {
+					// `SET` can't take bind parameters; `set_config` can.
+					if let Some(path) = search_path {
+						sqlx::query("SELECT set_config('search_path', $1, false);")
+							.bind(path)
+							.execute(&mut *conn)
+							.await?;
+					}
+					Ok(())
+				})
+			})
+			.connect(database_url)
+			.await?;
 		Ok(Self::new(pool, graph))
 	}
 
@@ -63,32 +1496,12 @@ impl AgeClient {
 	/// NOTE: This implementation constructs a Cypher string directly and is
 	/// intended as a minimal example. In production code you should carefully
 	/// validate/escape inputs or use parameterization patterns if available.
-	pub async fn merge_entity(&self, label: &str, key: &str, props: &Value) -> Result<()> {
-		// Build a Cypher map from JSON properties, sanitizing keys and
-		// using JSON-serialized values to ensure proper escaping.
-		let mut props_kv = Vec::new();
-		if let Value::Object(map) = props {
-			for (k, v) in map.iter() {
-				// Sanitize property key and serialize the value safely to JSON
-				let k_s = sanitize_prop_key(k);
-				let s = serde_json::to_string(v)?;
-				props_kv.push(format!("{}: {}", k_s, s));
-			}
-		}
-		let props_str = props_kv.join(", ");
-
-		// Sanitize label and serialize key using JSON encoding to ensure
-		// safe, quoted string injection into the Cypher statement.
-		let label_s = sanitize_label(label);
-		let key_json = serde_json::to_string(&key)?;
-
+	pub async fn merge_entity(&self, label: &str, key: &str, props: &Value) -> AgeResult<()> {
+		self.prop_keys.check(props)?;
+		self.check_property_types(label, key, props, &[]).await?;
 		// Cypher MERGE statement (creates node if missing, otherwise matches)
-		let cypher = format!(
-			"MERGE (n:{label} {{canonical_key: {key}}}) SET n += {{{props}}} RETURN n",
-			label = label_s,
-			key = key_json,
-			props = props_str
-		);
+		let merge = merge_cypher(label, &self.key_property, key, props)?;
+		let cypher = format!("{} RETURN n", merge);
 
 		// Execute via AGE's `cypher` SQL function
 		// Use parameterized SQL to avoid embedding graph name or cypher
@@ -105,11 +1518,133 @@ impl AgeClient {
 		Ok(())
 	}
 
+	/// Record an observation of an entity, preserving provenance.
+	///
+	/// Unlike `merge_entity`, repeated observations keep the original
+	/// `first_seen`, move `last_seen` to `timestamp` and increment
+	/// `seen_count`. Incoming values for those three properties are ignored.
+	/// An incoming [`SOURCE_PROP`] is added to the node's [`SOURCES_PROP`]
+	/// rather than stored as is.
+	pub async fn observe_value(
+		&self,
+		label: &str,
+		key: &str,
+		props: &Value,
+		timestamp: &str,
+	) -> AgeResult<()> {
+		self.prop_keys.check(props)?;
+		self.check_property_types(label, key, props, &OBSERVATION_PROPS)
+			.await?;
+		let clause = observe_cypher(
+			"n",
+			label,
+			&self.key_property,
+			key,
+			props,
+			timestamp,
+			self.raw_sample(props),
+		)?;
+		let cypher = format!("{} RETURN n", clause);
+		let sql = "SELECT * FROM cypher($1::text, $2::text) as (v agtype);";
+		sqlx::query(sql)
+			.bind(&self.graph)
+			.bind(&cypher)
+			.execute(&self.pool)
+			.await?;
+		Ok(())
+	}
+
+	/// The statements [`AgeClient::merge_record_tx`] runs: the node's
+	/// observation first, then one merge per edge.
+	fn record_statements(
+		&self,
+		node: &GraphNode,
+		edges: &[Edge],
+		timestamp: &str,
+	) -> AgeResult<Vec<String>> {
+		self.prop_keys.check(&node.props)?;
+		for edge in edges {
+			self.prop_keys.check(&edge.props)?;
+		}
+		let clause = observe_cypher(
+			"n",
+			&node.label,
+			&self.key_property,
+			&node.key,
+			&node.props,
+			timestamp,
+			self.raw_sample(&node.props),
+		)?;
+		let mut statements = Vec::with_capacity(edges.len() + 1);
+		statements.push(format!("{} RETURN n", clause));
+		for edge in edges {
+			statements.push(record_edge_cypher(
+				&self.key_property,
+				node,
+				edge,
+				timestamp,
+			)?);
+		}
+		Ok(statements)
+	}
+
+	/// Observe `node` and merge its `edges` in one transaction.
+	///
+	/// The node is recorded like [`AgeClient::observe_value`] and each edge
+	/// gets its `count` incremented and `last_seen` set to `timestamp`. If
+	/// any statement fails the transaction is rolled back, so neither the
+	/// node nor any edge is committed.
+	pub async fn merge_record_tx(
+		&self,
+		node: &GraphNode,
+		edges: &[Edge],
+		timestamp: &str,
+	) -> AgeResult<()> {
+		let statements = self.record_statements(node, edges, timestamp)?;
+		let sql = "SELECT * FROM cypher($1::text, $2::text) as (v agtype);";
+		// Dropping `tx` on an early return rolls it back.
+		let mut tx = self.pool.begin().await?;
+		for cypher in &statements {
+			sqlx::query(sql)
+				.bind(&self.graph)
+				.bind(cypher)
+				.execute(&mut *tx)
+				.await?;
+		}
+		tx.commit().await?;
+		Ok(())
+	}
+
+	/// Merge `edge` from `node`, which must already exist, like one edge of
+	/// [`AgeClient::merge_record_tx`]: the `edge.to_label` target is created
+	/// if missing, the edge's `count` incremented and its `last_seen` moved
+	/// to `timestamp`.
+	pub async fn merge_edge(
+		&self,
+		node: &GraphNode,
+		edge: &Edge,
+		timestamp: &str,
+	) -> AgeResult<()> {
+		self.prop_keys.check(&edge.props)?;
+		let cypher = record_edge_cypher(&self.key_property, node, edge, timestamp)?;
+		sqlx::query("SELECT * FROM cypher($1::text, $2::text) as (v agtype);")
+			.bind(&self.graph)
+			.bind(&cypher)
+			.execute(&self.pool)
+			.await?;
+		Ok(())
+	}
+
 	/// Persist a single row with its cells (sightings) into the graph.
 	///
 	/// This creates Row + Sighting nodes and links them to canonical FieldValue nodes.
 	/// The row structure is preserved for provenance while deduplicating values.
 	///
+	/// When `row_hash` is given the Row is merged on `(dump_id, row_hash)`:
+	/// persisting an identical row again only bumps the Row's `seen_count`
+	/// and creates no new sightings. Returns `true` if the row was new.
+	/// Without one, a hash is computed when `with_auto_row_hash` is set.
+	///
 	/// # Arguments
 	/// * `dump_id` - Unique identifier for the parent Dump
 	/// * `row_index` - Zero-based row number in the dump
@@ -123,65 +1658,31 @@ impl AgeClient {
 		row_hash: Option<&str>,
 		cells: &[(String, String, String, String)],
 		timestamp: &str,
-	) -> Result<()> {
-		// Build Cypher statements for row and sighting creation
-		let dump_id_json = serde_json::to_string(dump_id)?;
-		let timestamp_json = serde_json::to_string(timestamp)?;
-
-		// Start building the Cypher script
-		let mut cypher = format!(
-			"MERGE (d:Dump {{id: {}}}) ON CREATE SET d.received_at = {}",
-			dump_id_json, timestamp_json
-		);
-
-		// Create row node with optional row_hash
-		if let Some(hash) = row_hash {
-			let hash_json = serde_json::to_string(hash)?;
-			cypher.push_str(&format!(
-				"\nCREATE (r:Row {{dump_id: {}, index: {}, row_hash: {}}}))",
-				dump_id_json, row_index, hash_json
-			));
-		} else {
-			cypher.push_str(&format!(
-				"\nCREATE (r:Row {{dump_id: {}, index: {}}})",
-				dump_id_json, row_index
-			));
-		}
-		cypher.push_str("\nCREATE (d)-[:HAS_ROW]->(r)");
-
-		// Process each cell
-		for (i, (column, raw, canonical_key, canonical_value)) in cells.iter().enumerate() {
-			let column_json = serde_json::to_string(column)?;
-			let raw_json = serde_json::to_string(raw)?;
-			let canonical_key_json = serde_json::to_string(canonical_key)?;
-			let canonical_value_json = serde_json::to_string(canonical_value)?;
+	) -> AgeResult<bool> {
+		let row_hash = self.row_hash(row_hash, cells);
+		let RowCypher { claim, body } = row_cypher(
+			&self.key_property,
+			dump_id,
+			row_index,
+			row_hash.as_deref(),
+			cells,
+			timestamp,
+			self.sightings.as_ref(),
+		)?;
 
-			// Use unique variable names for each cell
-			let fv_var = format!("fv{}", i);
-			let f_var = format!("f{}", i);
-			let s_var = format!("s{}", i);
-
-			cypher.push_str(&format!(
-				"\nMERGE ({}:FieldValue {{canonical_key: {}}}) ON CREATE SET {}.value = {}, {}.created_at = {}",
-				fv_var, canonical_key_json, fv_var, canonical_value_json, fv_var, timestamp_json
-			));
-			cypher.push_str(&format!(
-				"\nMERGE ({}:Field {{name: {}}})",
-				f_var, column_json
-			));
-			cypher.push_str(&format!("\nMERGE ({})-[:VALUE_OF]->({})", fv_var, f_var));
-			cypher.push_str(&format!(
-				"\nCREATE ({}:Sighting {{column: {}, raw: {}, timestamp: {}}})",
-				s_var, column_json, raw_json, timestamp_json
-			));
-			cypher.push_str(&format!("\nCREATE (r)-[:HAS_SIGHTING]->({})", s_var));
-			cypher.push_str(&format!(
-				"\nCREATE ({})-[:OBSERVED_VALUE]->({})",
-				s_var, fv_var
-			));
+		// Claim a hashed row first; only the first claim adds sightings.
+		if let Some(claim) = claim {
+			let seen: String =
+				sqlx::query_scalar("SELECT c::text FROM cypher($1::text, $2::text) as (c agtype);")
+					.bind(&self.graph)
+					.bind(&claim)
+					.fetch_one(&self.pool)
+					.await?;
+			if seen.trim() != "1" {
+				return Ok(false);
+			}
 		}
-
-		cypher.push_str("\nRETURN r");
+		let cypher = body;
 
 		// Execute the Cypher script
 		let sql = "SELECT * FROM cypher($1::text, $2::text) as (v agtype);";
@@ -191,19 +1692,22 @@ impl AgeClient {
 			.execute(&self.pool)
 			.await?;
 
-		Ok(())
+		Ok(true)
 	}
 
 	/// Increment co-occurrence count between two canonical values.
 	///
 	/// Creates or updates a CO_OCCURS relationship between two FieldValue nodes.
 	/// Uses deterministic ordering (a_key < b_key) to avoid duplicate edges.
+	/// Keys must be built like ingest's (`AppState::record_key`) to match
+	/// existing nodes. With a [`HubGuard`], nothing is recorded when either
+	/// value is a hub.
 	pub async fn increment_co_occurrence(
 		&self,
 		a_key: &str,
 		b_key: &str,
 		timestamp: &str,
-	) -> Result<()> {
+	) -> AgeResult<()> {
 		// Ensure deterministic ordering
 		let (first, second) = if a_key < b_key {
 			(a_key, b_key)
@@ -211,17 +1715,42 @@ impl AgeClient {
 			(b_key, a_key)
 		};
 
+		if let Some(guard) = &self.hub_guard {
+			let hubs = self.hubs([first, second], guard.max_degree).await?;
+			if !hubs.is_empty() {
+				guard.skipped.inc();
+				if guard.flag {
+					for key in hubs {
+						let cypher = format!(
+							"MATCH (n:FieldValue {{{prop}: {key}}}) SET n.is_hub = true RETURN n",
+							prop = self.key_property,
+							key = serde_json::to_string(key)?,
+						);
+						sqlx::query("SELECT * FROM cypher($1::text, $2::text) as (v agtype);")
+							.bind(&self.graph)
+							.bind(&cypher)
+							.execute(&self.pool)
+							.await?;
+					}
+				}
+				return Ok(());
+			}
+		}
+
 		let first_json = serde_json::to_string(first)?;
 		let second_json = serde_json::to_string(second)?;
 		let timestamp_json = serde_json::to_string(timestamp)?;
 
 		let cypher = format!(
-			"MERGE (a:FieldValue {{canonical_key: {}}}) \
-			 MERGE (b:FieldValue {{canonical_key: {}}}) \
+			"MERGE (a:FieldValue {{{prop}: {}}}) \
+			 MERGE (b:FieldValue {{{prop}: {}}}) \
 			 MERGE (a)-[co:CO_OCCURS]-(b) \
 			 SET co.count = coalesce(co.count, 0) + 1, co.last_seen = {} \
 			 RETURN co",
-			first_json, second_json, timestamp_json
+			first_json,
+			second_json,
+			timestamp_json,
+			prop = self.key_property
 		);
 
 		let sql = "SELECT * FROM cypher($1::text, $2::text) as (v agtype);";
@@ -234,26 +1763,83 @@ impl AgeClient {
 		Ok(())
 	}
 
+	/// Recompute `CO_OCCURS` edges from the `Row` → `Sighting` →
+	/// `FieldValue` paths stored by [`Self::persist_row`], for graphs
+	/// ingested before co-occurrences were tracked.
+	///
+	/// An edge's `count` becomes the number of rows holding both values, as
+	/// if [`Self::increment_co_occurrence`] had been called once per row,
+	/// and its `last_seen` their latest sighting. Counts are set rather than
+	/// incremented, so running the rebuild again changes nothing. With
+	/// `dump_id`, only pairs sharing a row of that dump are rebuilt, but
+	/// their counts include rows of every dump. Values are processed in
+	/// batches of [`COOCCURRENCE_REBUILD_BATCH`]; returns the number of
+	/// edges written.
+	pub async fn rebuild_cooccurrence(&self, dump_id: Option<&str>) -> AgeResult<u64> {
+		let mut after = String::new();
+		let mut written = 0u64;
+		loop {
+			let cypher = cooccurrence_values_cypher(
+				&self.key_property,
+				dump_id,
+				&after,
+				COOCCURRENCE_REBUILD_BATCH,
+			)?;
+			let keys: Vec<String> =
+				sqlx::query_scalar("SELECT k::text FROM cypher($1::text, $2::text) as (k agtype);")
+					.bind(&self.graph)
+					.bind(&cypher)
+					.fetch_all(&self.pool)
+					.await?;
+			let keys = keys
+				.iter()
+				.map(|k| serde_json::from_str(k).map_err(AgeError::from))
+				.collect::<AgeResult<Vec<String>>>()?;
+			let Some(last) = keys.last() else {
+				break;
+			};
+			after = last.clone();
+
+			let cypher = rebuild_cooccurrence_cypher(&self.key_property, &keys, dump_id)?;
+			let edges: i64 = sqlx::query_scalar(
+				"SELECT count(*) FROM cypher($1::text, $2::text) as (v agtype);",
+			)
+			.bind(&self.graph)
+			.bind(&cypher)
+			.fetch_one(&self.pool)
+			.await?;
+			written += edges as u64;
+			if keys.len() < COOCCURRENCE_REBUILD_BATCH {
+				break;
+			}
+		}
+		Ok(written)
+	}
+
 	/// Persist a credential relationship (e.g., email -> password).
 	///
 	/// Creates or updates a CREDENTIAL edge with count and last_seen tracking.
+	/// Keys must be built like ingest's (`AppState::record_key`).
 	pub async fn persist_credential(
 		&self,
 		from_key: &str,
 		to_key: &str,
 		timestamp: &str,
-	) -> Result<()> {
+	) -> AgeResult<()> {
 		let from_json = serde_json::to_string(from_key)?;
 		let to_json = serde_json::to_string(to_key)?;
 		let timestamp_json = serde_json::to_string(timestamp)?;
 
 		let cypher = format!(
-			"MERGE (a:FieldValue {{canonical_key: {}}}) \
-			 MERGE (b:FieldValue {{canonical_key: {}}}) \
+			"MERGE (a:FieldValue {{{prop}: {}}}) \
+			 MERGE (b:FieldValue {{{prop}: {}}}) \
 			 MERGE (a)-[c:CREDENTIAL]->(b) \
 			 SET c.count = coalesce(c.count, 0) + 1, c.last_seen = {} \
 			 RETURN c",
-			from_json, to_json, timestamp_json
+			from_json,
+			to_json,
+			timestamp_json,
+			prop = self.key_property
 		);
 
 		let sql = "SELECT * FROM cypher($1::text, $2::text) as (v agtype);";
@@ -266,6 +1852,585 @@ impl AgeClient {
 		Ok(())
 	}
 
+	/// Merge the `Dump` node `dump_id` and set `props` (e.g. a dump
+	/// manifest) on it. Rows persisted later attach to the same node.
+	pub async fn merge_dump(&self, dump_id: &str, props: &Value, timestamp: &str) -> AgeResult<()> {
+		self.prop_keys.check(props)?;
+		let cypher = dump_cypher(dump_id, props, timestamp)?;
+		let sql = "SELECT * FROM cypher($1::text, $2::text) as (v agtype);";
+		sqlx::query(sql)
+			.bind(&self.graph)
+			.bind(&cypher)
+			.execute(&self.pool)
+			.await?;
+
+		Ok(())
+	}
+
+	/// Relate two existing nodes with a typed, directed edge.
+	///
+	/// Nodes are matched by their key property; the `rel_type` edge from
+	/// `from_key` to `to_key` is merged and `props` applied to it.
+	pub async fn relate(
+		&self,
+		from_key: &str,
+		to_key: &str,
+		rel_type: &str,
+		props: &Value,
+	) -> AgeResult<()> {
+		self.prop_keys.check(props)?;
+		let cypher = relate_cypher(&self.key_property, from_key, to_key, rel_type, props)?;
+		let sql = "SELECT * FROM cypher($1::text, $2::text) as (v agtype);";
+		sqlx::query(sql)
+			.bind(&self.graph)
+			.bind(&cypher)
+			.execute(&self.pool)
+			.await?;
+
+		Ok(())
+	}
+
+	/// Keys of up to `limit` nodes whose canonical value was produced by an
+	/// older version of `normalizer` than the current one, i.e. values to
+	/// re-normalize after an algorithm change. Only nodes written with
+	/// `Settings.normalizer_provenance` record their normalizer.
+	pub async fn outdated_normalizations(
+		&self,
+		normalizer: &str,
+		limit: usize,
+	) -> AgeResult<Vec<String>> {
+		let normalizer = normalizer.trim().to_ascii_lowercase();
+		let current = crate::lib::normalizers::normalizer_version(&normalizer)
+			.ok_or_else(|| AgeError::Query(format!("unknown normalizer: {:?}", normalizer)))?;
+		let cypher =
+			outdated_normalizations_cypher(&self.key_property, &normalizer, current, limit)?;
+		let keys: Vec<String> =
+			sqlx::query_scalar("SELECT k::text FROM cypher($1::text, $2::text) as (k agtype);")
+				.bind(&self.graph)
+				.bind(&cypher)
+				.fetch_all(&self.pool)
+				.await?;
+		keys.iter()
+			.map(|k| serde_json::from_str(k).map_err(AgeError::from))
+			.collect()
+	}
+
+	/// Addresses of the `ip` nodes within `cidr`, e.g. `10.0.0.0/8` or
+	/// `2001:db8::/32`, in ascending order. Only nodes ingested with an
+	/// `ip_hex` (see [`crate::lib::normalizers::ip_hex`]) are found.
+	pub async fn ips_in_cidr(&self, cidr: &str) -> AgeResult<Vec<std::net::IpAddr>> {
+		let range = crate::lib::normalizers::cidr_range(cidr)
+			.map_err(|e| AgeError::Query(e.to_string()))?;
+		let cypher = ips_in_range_cypher(&range)?;
+		let hexes: Vec<String> =
+			sqlx::query_scalar("SELECT h::text FROM cypher($1::text, $2::text) as (h agtype);")
+				.bind(&self.graph)
+				.bind(&cypher)
+				.fetch_all(&self.pool)
+				.await?;
+		hexes
+			.iter()
+			.map(|h| {
+				let hex: String = serde_json::from_str(h)?;
+				crate::lib::normalizers::ip_from_hex(&hex)
+					.ok_or_else(|| AgeError::Serialization(format!("invalid ip_hex {:?}", hex)))
+			})
+			.collect()
+	}
+
+	/// Expire up to `limit` `label` nodes whose `last_seen` is before
+	/// `cutoff`, returning how many were expired.
+	pub async fn expire(
+		&self,
+		label: &str,
+		cutoff: &str,
+		action: RetentionAction,
+		limit: usize,
+	) -> AgeResult<u64> {
+		let now = chrono::Utc::now().to_rfc3339();
+		let cypher = expire_cypher(label, cutoff, &now, action, limit)?;
+		let count: Option<String> =
+			sqlx::query_scalar("SELECT c::text FROM cypher($1::text, $2::text) as (c agtype);")
+				.bind(&self.graph)
+				.bind(&cypher)
+				.fetch_optional(&self.pool)
+				.await?;
+		Ok(count.and_then(|c| c.parse::<u64>().ok()).unwrap_or(0))
+	}
+
+	/// Remove `source` from every node it reported (see
+	/// [`crate::ingest::source`]), detaching and deleting the nodes no
+	/// other source reported, in batches of [`SOURCE_DELETE_BATCH`].
+	/// Returns how many nodes were deleted.
+	pub async fn delete_by_source(&self, source: &str) -> AgeResult<u64> {
+		let deleted = self
+			.count_in_batches(&delete_by_source_cypher(source, SOURCE_DELETE_BATCH)?)
+			.await?;
+		self.count_in_batches(&release_source_cypher(source, SOURCE_DELETE_BATCH)?)
+			.await?;
+		Ok(deleted)
+	}
+
+	/// Run `cypher`, which handles up to [`SOURCE_DELETE_BATCH`] nodes and
+	/// returns how many it handled, until a run handles fewer. Returns the
+	/// total handled.
+	async fn count_in_batches(&self, cypher: &str) -> AgeResult<u64> {
+		let mut total = 0;
+		loop {
+			let count: Option<String> =
+				sqlx::query_scalar("SELECT c::text FROM cypher($1::text, $2::text) as (c agtype);")
+					.bind(&self.graph)
+					.bind(cypher)
+					.fetch_optional(&self.pool)
+					.await?;
+			let count = count.and_then(|c| c.parse::<u64>().ok()).unwrap_or(0);
+			total += count;
+			if count < SOURCE_DELETE_BATCH as u64 {
+				return Ok(total);
+			}
+		}
+	}
+
+	/// Run `cypher`, whose `RETURN` yields a single value per row, streaming
+	/// the rows as the database produces them instead of collecting them.
+	///
+	/// The query runs in a read-only transaction on its own task and is
+	/// cancelled by the server after `timeout`. At most a small buffer of
+	/// rows is read ahead; dropping the stream stops the query.
+	pub fn query_stream(
+		&self,
+		cypher: &str,
+		timeout: std::time::Duration,
+	) -> BoxStream<'static, AgeResult<Value>> {
+		let (sender, receiver) = tokio::sync::mpsc::channel(QUERY_STREAM_BUFFER);
+		let pool = self.pool.clone();
+		let graph = self.graph.clone();
+		let cypher = cypher.to_string();
+		tokio::spawn(async move {
+			let run = async {
+				let mut tx = pool.begin().await?;
+				sqlx::query("SET TRANSACTION READ ONLY")
+					.execute(&mut *tx)
+					.await?;
+				sqlx::query(&format!(
+					"SET LOCAL statement_timeout = {}",
+					timeout.as_millis().max(1)
+				))
+				.execute(&mut *tx)
+				.await?;
+				let mut rows = sqlx::query_scalar::<_, String>(
+					"SELECT v::text FROM cypher($1::text, $2::text) as (v agtype);",
+				)
+				.bind(&graph)
+				.bind(&cypher)
+				.fetch(&mut *tx);
+				while let Some(row) = rows.try_next().await? {
+					if sender.send(Ok(agtype_json(&row))).await.is_err() {
+						// The consumer went away; dropping `tx` rolls back.
+						break;
+					}
+				}
+				Ok::<(), AgeError>(())
+			};
+			if let Err(e) = run.await {
+				let _ = sender.send(Err(e)).await;
+			}
+		});
+		tokio_stream::wrappers::ReceiverStream::new(receiver).boxed()
+	}
+
+	/// Look up the `label` node keyed `key` and its immediate neighbors.
+	/// Returns `None` when the node does not exist.
+	pub async fn neighbors(
+		&self,
+		label: &str,
+		key: &str,
+		edge_types: &[&str],
+		limit: usize,
+	) -> AgeResult<Option<GraphFragment>> {
+		let node: Option<String> =
+			sqlx::query_scalar("SELECT n::text FROM cypher($1::text, $2::text) as (n agtype);")
+				.bind(&self.graph)
+				.bind(node_cypher(label, &self.key_property, key)?)
+				.fetch_optional(&self.pool)
+				.await?;
+		let Some(node) = node else {
+			return Ok(None);
+		};
+		let node: Value = serde_json::from_str(&node)?;
+
+		let cypher = neighbors_cypher(label, &self.key_property, key, edge_types, limit)?;
+		let rows: Vec<(String, String)> = sqlx::query_as(
+			"SELECT e::text, m::text FROM cypher($1::text, $2::text) as (e agtype, m agtype);",
+		)
+		.bind(&self.graph)
+		.bind(&cypher)
+		.fetch_all(&self.pool)
+		.await?;
+
+		// A neighbor reached through several edges is listed once.
+		let mut seen = std::collections::HashSet::new();
+		seen.insert(node["id"].to_string());
+		let mut fragment = GraphFragment {
+			nodes: vec![node],
+			edges: Vec::with_capacity(rows.len()),
+		};
+		for (edge, other) in rows {
+			fragment.edges.push(serde_json::from_str(&edge)?);
+			let other: Value = serde_json::from_str(&other)?;
+			if seen.insert(other["id"].to_string()) {
+				fragment.nodes.push(other);
+			}
+		}
+		Ok(Some(fragment))
+	}
+
+	/// The key collision fingerprint stored on the `label` node keyed
+	/// `key`. Returns `None` when the node does not exist or has none.
+	pub async fn key_fingerprint(&self, label: &str, key: &str) -> AgeResult<Option<String>> {
+		let node: Option<String> =
+			sqlx::query_scalar("SELECT n::text FROM cypher($1::text, $2::text) as (n agtype);")
+				.bind(&self.graph)
+				.bind(node_cypher(label, &self.key_property, key)?)
+				.fetch_optional(&self.pool)
+				.await?;
+		let Some(node) = node else {
+			return Ok(None);
+		};
+		let node: Value = serde_json::from_str(&node)?;
+		Ok(node["properties"][KEY_FINGERPRINT_PROP]
+			.as_str()
+			.map(str::to_string))
+	}
+
+	/// Up to `limit` `label` nodes keyed after `after`, in key order.
+	pub async fn rekey_candidates(
+		&self,
+		label: &str,
+		after: &str,
+		limit: usize,
+	) -> AgeResult<Vec<RekeyCandidate>> {
+		let cypher = rekey_candidates_cypher(label, &self.key_property, after, limit)?;
+		let rows: Vec<String> =
+			sqlx::query_scalar("SELECT c::text FROM cypher($1::text, $2::text) as (c agtype);")
+				.bind(&self.graph)
+				.bind(&cypher)
+				.fetch_all(&self.pool)
+				.await?;
+		rows.iter()
+			.map(|row| serde_json::from_str(row).map_err(AgeError::from))
+			.collect()
+	}
+
+	/// Give the `label` node keyed `old_key` the key `new_key`, in one
+	/// transaction.
+	///
+	/// If a `new_key` node already exists, every edge of the old node is
+	/// re-pointed to it and the old node is deleted; the old node's own
+	/// properties are dropped and parallel edges are not collapsed.
+	pub async fn rekey(
+		&self,
+		label: &str,
+		old_key: &str,
+		new_key: &str,
+	) -> AgeResult<RekeyOutcome> {
+		let sql = "SELECT * FROM cypher($1::text, $2::text) as (v agtype);";
+		// Dropping `tx` on an early return rolls it back.
+		let mut tx = self.pool.begin().await?;
+		let ids: Option<String> =
+			sqlx::query_scalar("SELECT v::text FROM cypher($1::text, $2::text) as (v agtype);")
+				.bind(&self.graph)
+				.bind(rekey_ids_cypher(
+					label,
+					&self.key_property,
+					old_key,
+					new_key,
+				)?)
+				.fetch_optional(&mut *tx)
+				.await?;
+		let ids: Vec<Option<i64>> = match ids {
+			Some(ids) => serde_json::from_str(&ids)?,
+			None => return Ok(RekeyOutcome::Missing),
+		};
+		let outcome = match ids.as_slice() {
+			[Some(_), None] => {
+				let cypher = format!(
+					"MATCH (o:{label} {{{prop}: {old}}}) SET o.{prop} = {new} RETURN o",
+					label = sanitize_label(label),
+					prop = self.key_property,
+					old = serde_json::to_string(old_key)?,
+					new = serde_json::to_string(new_key)?,
+				);
+				sqlx::query(sql)
+					.bind(&self.graph)
+					.bind(&cypher)
+					.execute(&mut *tx)
+					.await?;
+				RekeyOutcome::Renamed
+			}
+			[Some(old_id), Some(new_id)] => {
+				// Edges of every type inherit from `_ag_label_edge`.
+				for column in ["start_id", "end_id"] {
+					let update = format!(
+						"UPDATE {}._ag_label_edge SET {col} = $1::text::graphid \
+						 WHERE {col} = $2::text::graphid;",
+						quote_ident(&self.graph),
+						col = column,
+					);
+					sqlx::query(&update)
+						.bind(new_id.to_string())
+						.bind(old_id.to_string())
+						.execute(&mut *tx)
+						.await?;
+				}
+				let cypher = format!(
+					"MATCH (o:{label} {{{prop}: {old}}}) DELETE o",
+					label = sanitize_label(label),
+					prop = self.key_property,
+					old = serde_json::to_string(old_key)?,
+				);
+				sqlx::query(sql)
+					.bind(&self.graph)
+					.bind(&cypher)
+					.execute(&mut *tx)
+					.await?;
+				RekeyOutcome::Merged
+			}
+			_ => return Ok(RekeyOutcome::Missing),
+		};
+		tx.commit().await?;
+		Ok(outcome)
+	}
+
+	/// Check that the configured graph exists in the AGE catalog, returning
+	/// `GraphMissing` if it does not.
+	pub async fn verify_graph(&self) -> AgeResult<()> {
+		if graph_exists(&self.pool, &self.graph).await? {
+			Ok(())
+		} else {
+			Err(AgeError::GraphMissing(self.graph.clone()))
+		}
+	}
+
+	/// Create the AGE graph `name`, returning whether it was created. A
+	/// graph that already exists, including one created concurrently, is
+	/// left as it is.
+	pub async fn create_graph(&self, name: &str) -> AgeResult<bool> {
+		if graph_exists(&self.pool, name).await? {
+			return Ok(false);
+		}
+		match sqlx::query("SELECT ag_catalog.create_graph($1::name);")
+			.bind(name)
+			.execute(&self.pool)
+			.await
+		{
+			Ok(_) => Ok(true),
+			// duplicate_schema: another caller created it first
+			Err(sqlx::Error::Database(db)) if db.code().as_deref() == Some("42P06") => Ok(false),
+			Err(e) => Err(e.into()),
+		}
+	}
+
+	/// Drop the AGE graph `name`, returning whether it existed. `cascade`
+	/// also drops its labels and data; without it only an empty graph can
+	/// be dropped.
+	pub async fn drop_graph(&self, name: &str, cascade: bool) -> AgeResult<bool> {
+		if !graph_exists(&self.pool, name).await? {
+			return Ok(false);
+		}
+		match sqlx::query("SELECT ag_catalog.drop_graph($1::name, $2);")
+			.bind(name)
+			.bind(cascade)
+			.execute(&self.pool)
+			.await
+		{
+			Ok(_) => Ok(true),
+			// invalid_schema_name: another caller dropped it first
+			Err(e) => match AgeError::from(e) {
+				AgeError::GraphMissing(_) => Ok(false),
+				e => Err(e),
+			},
+		}
+	}
+
+	/// Check that the `age` extension is created in the database and is at
+	/// least [`MIN_AGE_VERSION`], returning its version.
+	pub async fn check_extension(&self) -> AgeResult<AgeInfo> {
+		let version: Option<String> =
+			sqlx::query_scalar("SELECT extversion FROM pg_extension WHERE extname = 'age';")
+				.fetch_optional(&self.pool)
+				.await?;
+		let Some(version) = version else {
+			return Err(AgeError::ExtensionUnavailable(
+				"the `age` extension is not created in this database; install Apache AGE and run \
+				 `CREATE EXTENSION age;`"
+					.to_string(),
+			));
+		};
+		if !version_at_least(&version, MIN_AGE_VERSION) {
+			return Err(AgeError::ExtensionUnavailable(format!(
+				"`age` {} is installed but {} or newer is required; run \
+				 `ALTER EXTENSION age UPDATE;` after upgrading Apache AGE",
+				version, MIN_AGE_VERSION
+			)));
+		}
+		Ok(AgeInfo { version })
+	}
+
+	/// Create the [`PROPERTY_INDEXES`] that do not exist yet. Labels with no
+	/// table in the graph are skipped.
+	pub async fn create_property_indexes(&self) -> AgeResult<()> {
+		for (label, property) in PROPERTY_INDEXES {
+			if !self.label_exists(label).await? {
+				continue;
+			}
+			sqlx::query(&create_index_sql(&self.graph, label, property))
+				.execute(&self.pool)
+				.await?;
+		}
+		Ok(())
+	}
+
+	/// Names of the [`PROPERTY_INDEXES`] currently present in the graph.
+	pub async fn property_indexes(&self) -> AgeResult<Vec<String>> {
+		let names: Vec<String> = PROPERTY_INDEXES
+			.iter()
+			.map(|(label, property)| property_index_name(label, property))
+			.collect();
+		let present: Vec<String> = sqlx::query_scalar(
+			"SELECT indexname::text FROM pg_indexes \
+			 WHERE schemaname = $1 AND indexname = ANY($2) ORDER BY indexname;",
+		)
+		.bind(&self.graph)
+		.bind(&names)
+		.fetch_all(&self.pool)
+		.await?;
+		Ok(present)
+	}
+
+	async fn label_exists(&self, label: &str) -> AgeResult<bool> {
+		let exists: bool = sqlx::query_scalar(
+			"SELECT to_regclass(format('%I.%I', $1::text, $2::text)) IS NOT NULL;",
+		)
+		.bind(&self.graph)
+		.bind(sanitize_label(label))
+		.fetch_one(&self.pool)
+		.await?;
+		Ok(exists)
+	}
+
+	/// Start a bulk load by dropping the [`PROPERTY_INDEXES`] so the load
+	/// does not pay for index maintenance on every write.
+	///
+	/// This is an admin operation: lookups slow down and concurrent ingest
+	/// can create duplicates until [`AgeClient::end_bulk_load`] runs. If a
+	/// drop fails the indexes already dropped are recreated.
+	pub async fn begin_bulk_load(&self) -> AgeResult<()> {
+		for (label, property) in PROPERTY_INDEXES {
+			let sql = format!(
+				"DROP INDEX IF EXISTS {}.{};",
+				quote_ident(&self.graph),
+				quote_ident(&property_index_name(label, property))
+			);
+			if let Err(e) = sqlx::query(&sql).execute(&self.pool).await {
+				self.restore_property_indexes().await;
+				return Err(e.into());
+			}
+		}
+		Ok(())
+	}
+
+	/// Create `items` as new nodes without matching existing ones. Only
+	/// meaningful between `begin_bulk_load` and `end_bulk_load`, which
+	/// removes the duplicates this can produce.
+	pub async fn bulk_create(&self, items: &[(String, String, Value)]) -> AgeResult<()> {
+		if items.is_empty() {
+			return Ok(());
+		}
+		let mut clauses = Vec::with_capacity(items.len());
+		for (idx, (label, key, props)) in items.iter().enumerate() {
+			self.prop_keys.check(props)?;
+			let var = format!("n{}", idx);
+			clauses.push(create_cypher(&var, label, &self.key_property, key, props)?);
+		}
+		let cypher = clauses.join("\n");
+		let sql = "SELECT * FROM cypher($1::text, $2::text) as (v agtype);";
+		sqlx::query(sql)
+			.bind(&self.graph)
+			.bind(&cypher)
+			.execute(&self.pool)
+			.await?;
+		Ok(())
+	}
+
+	/// Finish a bulk load: collapse duplicate `labels` nodes on the key
+	/// property, then rebuild the [`PROPERTY_INDEXES`]. Indexes are rebuilt
+	/// even when de-duplication fails. Returns the number of nodes removed.
+	pub async fn end_bulk_load(&self, labels: &[String]) -> AgeResult<u64> {
+		let deduped = self.dedupe(labels).await;
+		self.create_property_indexes().await?;
+		deduped
+	}
+
+	async fn dedupe(&self, labels: &[String]) -> AgeResult<u64> {
+		let mut removed = 0;
+		for label in labels {
+			let cypher = dedupe_cypher(label, &self.key_property);
+			let count: Option<String> =
+				sqlx::query_scalar("SELECT c::text FROM cypher($1::text, $2::text) as (c agtype);")
+					.bind(&self.graph)
+					.bind(&cypher)
+					.fetch_optional(&self.pool)
+					.await?;
+			removed += count.and_then(|c| c.parse::<u64>().ok()).unwrap_or(0);
+		}
+		Ok(removed)
+	}
+
+	/// Recreate the property indexes after a failed bulk load, logging
+	/// rather than masking the original error.
+	async fn restore_property_indexes(&self) {
+		if let Err(e) = self.create_property_indexes().await {
+			eprintln!("failed to restore property indexes after bulk load: {}", e);
+		}
+	}
+
+	/// Load `items` with indexes dropped, creating `chunk_size` nodes per
+	/// statement, then de-duplicate and rebuild indexes. A failure at any
+	/// point restores the indexes before the error is returned.
+	pub async fn bulk_load<I>(&self, items: I, chunk_size: usize) -> AgeResult<BulkLoadReport>
+	where
+		I: IntoIterator<Item = (String, String, Value)>,
+	{
+		self.begin_bulk_load().await?;
+
+		let mut report = BulkLoadReport::default();
+		let mut labels: Vec<String> = Vec::new();
+		let mut chunk = Vec::with_capacity(chunk_size.max(1));
+		for item in items {
+			if !labels.contains(&item.0) {
+				labels.push(item.0.clone());
+			}
+			chunk.push(item);
+			if chunk.len() >= chunk_size.max(1) {
+				if let Err(e) = self.bulk_create(&chunk).await {
+					self.restore_property_indexes().await;
+					return Err(e);
+				}
+				report.created += chunk.len() as u64;
+				chunk.clear();
+			}
+		}
+		if let Err(e) = self.bulk_create(&chunk).await {
+			self.restore_property_indexes().await;
+			return Err(e);
+		}
+		report.created += chunk.len() as u64;
+
+		report.duplicates_removed = self.end_bulk_load(&labels).await?;
+		Ok(report)
+	}
+
 	/// Apply SQL migrations from a file to set up the graph schema.
 	///
 	/// This executes raw SQL statements (including Cypher via AGE functions)
@@ -275,36 +2440,171 @@ impl AgeClient {
 	/// - Executes the entire SQL content as a single statement batch
 	/// - Does not parse individual statements or handle complex transaction boundaries
 	/// - Suitable for initial schema setup and idempotent migration scripts
-	/// - For production use with multiple migrations, consider using a dedicated
-	///   migration tool like `sqlx-cli` or `refinery` that supports proper
-	///   versioning, rollback, and statement-by-statement execution
+	/// - Records nothing; [`AgeClient::run_migrations`] tracks versions and
+	///   retries transient errors
 	///
 	/// **Safety:**
 	/// - Only execute trusted SQL content (typically embedded via `include_str!`)
 	/// - Never pass user-provided content to this method
 	/// - Ensure migrations are idempotent (use CREATE IF NOT EXISTS, MERGE, etc.)
-	pub async fn apply_migration(&self, sql_content: &str) -> Result<()> {
+	pub async fn apply_migration(&self, sql_content: &str) -> AgeResult<()> {
 		// Execute the SQL content directly as a batch.
 		// This works for simple DO blocks and CREATE IF NOT EXISTS statements
 		// but does not handle complex multi-statement scripts with dependencies.
 		sqlx::query(sql_content).execute(&self.pool).await?;
 		Ok(())
 	}
+
+	/// Apply `migrations` in order, recording each in
+	/// [`MIGRATIONS_TABLE`](crate::persist::migrations::MIGRATIONS_TABLE).
+	///
+	/// Completed migrations are skipped, so re-running is a no-op. A
+	/// migration recorded as started but not completed stops the run unless
+	/// `opts.force` is set; a migration whose SQL fails stays recorded as
+	/// started. Retryable errors are retried per `opts`.
+	pub async fn run_migrations(
+		&self,
+		migrations: &[crate::persist::migrations::Migration],
+		opts: &crate::persist::migrations::MigrationOptions,
+	) -> Result<
+		crate::persist::migrations::MigrationReport,
+		crate::persist::migrations::MigrationError,
+	> {
+		use crate::persist::migrations::{
+			MIGRATIONS_TABLE, MigrationError, MigrationReport, MigrationStatus, MigrationStep,
+			next_step, with_retries,
+		};
+
+		let create = format!(
+			"CREATE TABLE IF NOT EXISTS {} (\
+			 version BIGINT PRIMARY KEY, \
+			 name TEXT NOT NULL, \
+			 status TEXT NOT NULL, \
+			 started_at TIMESTAMPTZ NOT NULL DEFAULT now(), \
+			 completed_at TIMESTAMPTZ)",
+			MIGRATIONS_TABLE
+		);
+		with_retries(opts, || async {
+			sqlx::query(&create)
+				.execute(&self.pool)
+				.await
+				.map(|_| ())
+				.map_err(AgeError::from)
+		})
+		.await
+		.map_err(|source| MigrationError::Database { version: 0, source })?;
+
+		let select = format!("SELECT status FROM {} WHERE version = $1", MIGRATIONS_TABLE);
+		let start = format!(
+			"INSERT INTO {} (version, name, status) VALUES ($1, $2, 'started') \
+			 ON CONFLICT (version) DO UPDATE SET name = EXCLUDED.name, status = 'started', \
+			 started_at = now(), completed_at = NULL",
+			MIGRATIONS_TABLE
+		);
+		let complete = format!(
+			"UPDATE {} SET status = 'completed', completed_at = now() WHERE version = $1",
+			MIGRATIONS_TABLE
+		);
+
+		let mut report = MigrationReport::default();
+		for m in migrations {
+			let db_err = |source: AgeError| MigrationError::Database {
+				version: m.version,
+				source,
+			};
+			let status: Option<String> = with_retries(opts, || async {
+				sqlx::query_scalar(&select)
+					.bind(m.version)
+					.fetch_optional(&self.pool)
+					.await
+					.map_err(AgeError::from)
+			})
+			.await
+			.map_err(db_err)?;
+			let status = status.as_deref().and_then(MigrationStatus::parse);
+			match next_step(status, opts.force) {
+				MigrationStep::Skip => {
+					report.skipped.push(m.version);
+					continue;
+				}
+				MigrationStep::Blocked => {
+					return Err(MigrationError::Interrupted {
+						version: m.version,
+						name: m.name,
+					});
+				}
+				MigrationStep::Apply => {}
+			}
+
+			with_retries(opts, || async {
+				sqlx::query(&start)
+					.bind(m.version)
+					.bind(m.name)
+					.execute(&self.pool)
+					.await
+					.map(|_| ())
+					.map_err(AgeError::from)
+			})
+			.await
+			.map_err(db_err)?;
+			with_retries(opts, || self.apply_migration(m.sql))
+				.await
+				.map_err(db_err)?;
+			with_retries(opts, || async {
+				sqlx::query(&complete)
+					.bind(m.version)
+					.execute(&self.pool)
+					.await
+					.map(|_| ())
+					.map_err(AgeError::from)
+			})
+			.await
+			.map_err(db_err)?;
+			tracing::info!(
+				version = m.version,
+				name = m.name,
+				"applied schema migration"
+			);
+			report.applied.push(m.version);
+		}
+		Ok(report)
+	}
 }
 
 /// Trait abstraction for persistence operations so tests can substitute a
 /// mock implementation. Implemented by `AgeClient` and any test doubles.
 #[async_trait]
 pub trait AgeRepo: Send + Sync + 'static {
-	async fn merge_entity(&self, label: &str, key: &str, props: &Value) -> Result<()>;
+	async fn merge_entity(&self, label: &str, key: &str, props: &Value) -> AgeResult<()>;
 	/// Lightweight ping to verify DB connectivity / readiness.
-	async fn ping(&self) -> Result<()>;
+	async fn ping(&self) -> AgeResult<()>;
 	/// Merge a batch of entities in a single Cypher call for improved
 	/// throughput. Implementations should attempt to execute the batch in
 	/// a single `cypher` invocation where possible and fall back to per-item
 	/// merges on partial failure.
-	async fn merge_batch(&self, items: &[(String, String, Value)]) -> Result<()>;
-	/// Persist a single row with its cells into the graph.
+	async fn merge_batch(&self, items: &[(String, String, Value)]) -> AgeResult<()>;
+	/// Record an observation: keep `first_seen`, set `last_seen` to
+	/// `timestamp` and increment `seen_count`. Defaults to `merge_entity`
+	/// for repositories that don't track observations.
+	async fn observe_value(
+		&self,
+		label: &str,
+		key: &str,
+		props: &Value,
+		_timestamp: &str,
+	) -> AgeResult<()> {
+		self.merge_entity(label, key, props).await
+	}
+	/// Batched `observe_value`. Defaults to `merge_batch`.
+	async fn observe_batch(
+		&self,
+		items: &[(String, String, Value)],
+		_timestamp: &str,
+	) -> AgeResult<()> {
+		self.merge_batch(items).await
+	}
+	/// Persist a single row with its cells into the graph. Rows carrying a
+	/// `row_hash` are deduplicated within their dump.
 	async fn persist_row(
 		&self,
 		dump_id: &str,
@@ -312,64 +2612,173 @@ pub trait AgeRepo: Send + Sync + 'static {
 		row_hash: Option<&str>,
 		cells: &[(String, String, String, String)],
 		timestamp: &str,
-	) -> Result<()>;
+	) -> AgeResult<()>;
 	/// Increment co-occurrence count between two canonical values.
 	async fn increment_co_occurrence(
 		&self,
 		a_key: &str,
 		b_key: &str,
 		timestamp: &str,
-	) -> Result<()>;
+	) -> AgeResult<()>;
 	/// Persist a credential relationship (e.g., email -> password).
-	async fn persist_credential(&self, from_key: &str, to_key: &str, timestamp: &str)
-	-> Result<()>;
+	async fn persist_credential(
+		&self,
+		from_key: &str,
+		to_key: &str,
+		timestamp: &str,
+	) -> AgeResult<()>;
+	/// Merge the `Dump` node `dump_id` and set `props` on it.
+	async fn merge_dump(&self, _dump_id: &str, _props: &Value, _timestamp: &str) -> AgeResult<()> {
+		Err(AgeError::Query(
+			"merge_dump is not supported by this repository".to_string(),
+		))
+	}
+	/// Relate two nodes, matched by their key property, with a directed
+	/// `rel_type` edge carrying `props`.
+	async fn relate(
+		&self,
+		_from_key: &str,
+		_to_key: &str,
+		_rel_type: &str,
+		_props: &Value,
+	) -> AgeResult<()> {
+		Err(AgeError::Query(
+			"relate is not supported by this repository".to_string(),
+		))
+	}
+	/// Merge `edge` from `node`, which must already exist: create the
+	/// `edge.to_label` target if missing, increment the edge's `count` and
+	/// move its `last_seen` to `timestamp`.
+	async fn merge_edge(&self, _node: &GraphNode, _edge: &Edge, _timestamp: &str) -> AgeResult<()> {
+		Err(AgeError::Query(
+			"merge_edge is not supported by this repository".to_string(),
+		))
+	}
+	/// Observe `node` and merge its `edges`. Defaults to `observe_value`
+	/// followed by one `merge_edge` per edge, which is not atomic;
+	/// `AgeClient` commits them together in one transaction.
+	async fn merge_record(
+		&self,
+		node: &GraphNode,
+		edges: &[Edge],
+		timestamp: &str,
+	) -> AgeResult<()> {
+		self.observe_value(&node.label, &node.key, &node.props, timestamp)
+			.await?;
+		for edge in edges {
+			self.merge_edge(node, edge, timestamp).await?;
+		}
+		Ok(())
+	}
+	/// Expire up to `limit` `label` nodes last seen before `cutoff` (RFC
+	/// 3339), returning how many were expired.
+	async fn expire(
+		&self,
+		_label: &str,
+		_cutoff: &str,
+		_action: RetentionAction,
+		_limit: usize,
+	) -> AgeResult<u64> {
+		Err(AgeError::Query(
+			"expire is not supported by this repository".to_string(),
+		))
+	}
+	/// Delete every node ingested from `source`, returning how many were
+	/// deleted.
+	async fn delete_by_source(&self, _source: &str) -> AgeResult<u64> {
+		Err(AgeError::Query(
+			"delete_by_source is not supported by this repository".to_string(),
+		))
+	}
+	/// Look up the `label` node keyed `key` with up to `limit` edges to its
+	/// immediate neighbors, following only `edge_types` unless empty.
+	/// Returns `None` when the node does not exist.
+	async fn neighbors(
+		&self,
+		_label: &str,
+		_key: &str,
+		_edge_types: &[&str],
+		_limit: usize,
+	) -> AgeResult<Option<GraphFragment>> {
+		Err(AgeError::Query(
+			"neighbors is not supported by this repository".to_string(),
+		))
+	}
+	/// The key collision fingerprint stored on the `label` node keyed
+	/// `key`, if any. Repositories that do not store fingerprints report
+	/// none, which disables collision checks against existing nodes.
+	async fn key_fingerprint(&self, _label: &str, _key: &str) -> AgeResult<Option<String>> {
+		Ok(None)
+	}
+	/// Up to `limit` `label` nodes keyed after `after`, in key order, with
+	/// the properties a salt rotation recovers their unsalted keys from.
+	async fn rekey_candidates(
+		&self,
+		_label: &str,
+		_after: &str,
+		_limit: usize,
+	) -> AgeResult<Vec<RekeyCandidate>> {
+		Err(AgeError::Query(
+			"rekey_candidates is not supported by this repository".to_string(),
+		))
+	}
+	/// Move the `label` node keyed `old_key` to `new_key`, merging it into
+	/// an existing `new_key` node by re-pointing its edges.
+	async fn rekey(&self, _label: &str, _old_key: &str, _new_key: &str) -> AgeResult<RekeyOutcome> {
+		Err(AgeError::Query(
+			"rekey is not supported by this repository".to_string(),
+		))
+	}
+	/// Run the read-only `cypher`, returning one value per row, as a stream
+	/// of rows in the order the database produces them. The database
+	/// cancels the query after `timeout`.
+	fn query_stream(
+		&self,
+		_cypher: &str,
+		_timeout: std::time::Duration,
+	) -> BoxStream<'static, AgeResult<Value>> {
+		futures_util::stream::once(async {
+			Err(AgeError::Query(
+				"query_stream is not supported by this repository".to_string(),
+			))
+		})
+		.boxed()
+	}
 	/// Apply SQL migrations to set up the graph schema.
-	async fn apply_migration(&self, sql_content: &str) -> Result<()>;
+	async fn apply_migration(&self, sql_content: &str) -> AgeResult<()>;
+	/// Node property holding the key nodes are merged on.
+	fn key_property(&self) -> &str {
+		DEFAULT_KEY_PROPERTY
+	}
 }
 
 #[async_trait]
 impl AgeRepo for AgeClient {
-	async fn merge_entity(&self, label: &str, key: &str, props: &Value) -> Result<()> {
+	async fn merge_entity(&self, label: &str, key: &str, props: &Value) -> AgeResult<()> {
 		// Call the inherent method
 		AgeClient::merge_entity(self, label, key, props).await
 	}
 
-	async fn ping(&self) -> Result<()> {
+	async fn ping(&self) -> AgeResult<()> {
 		// Simple lightweight query to verify the connection
 		// We don't need the returned row; success indicates connectivity.
 		sqlx::query("SELECT 1").fetch_one(&self.pool).await?;
 		Ok(())
 	}
 
-	async fn merge_batch(&self, items: &[(String, String, Value)]) -> Result<()> {
+	async fn merge_batch(&self, items: &[(String, String, Value)]) -> AgeResult<()> {
 		if items.is_empty() {
 			return Ok(());
 		}
 
 		// Build a single Cypher script with multiple MERGE statements while
 		// sanitizing keys and labels. Values are JSON-serialized to ensure
-		// correct escaping. Property keys are transformed to a safe
-		// identifier (alphanumeric + underscore).
+		// correct escaping. Property keys are encoded as safe identifiers
+		// (alphanumeric + underscore) or rejected, per the policy.
 		let mut stmts: Vec<String> = Vec::with_capacity(items.len());
 		for (label, key, props) in items.iter() {
-			let label_s = sanitize_label(label);
-			let mut props_kv = Vec::new();
-			if let Value::Object(map) = props {
-				for (k, v) in map.iter() {
-					let k_s = sanitize_prop_key(k);
-					let s = serde_json::to_string(v)?;
-					props_kv.push(format!("{}: {}", k_s, s));
-				}
-			}
-			let props_str = props_kv.join(", ");
-			let key_json = serde_json::to_string(key)?;
-			let stmt = format!(
-				"MERGE (n:{label} {{canonical_key: {key}}}) SET n += {{{props}}}",
-				label = label_s,
-				key = key_json,
-				props = props_str
-			);
-			stmts.push(stmt);
+			self.prop_keys.check(props)?;
+			stmts.push(merge_cypher(label, &self.key_property, key, props)?);
 		}
 
 		let cypher = stmts.join("\n");
@@ -385,11 +2794,75 @@ impl AgeRepo for AgeClient {
 		match res {
 			Ok(_) => Ok(()),
 			Err(e) => {
-				// On batch failure, attempt per-item merges to make progress
-				eprintln!("batch merge failed: {}; falling back to per-item merges", e);
+				// On batch failure, attempt per-item merges to make progress
+				eprintln!("batch merge failed: {}; falling back to per-item merges", e);
+				for (label, key, props) in items.iter() {
+					if let Err(e2) = AgeClient::merge_entity(self, label, key, props).await {
+						eprintln!("per-item merge failed for {}: {}", key, e2);
+					}
+				}
+				Ok(())
+			}
+		}
+	}
+
+	async fn observe_value(
+		&self,
+		label: &str,
+		key: &str,
+		props: &Value,
+		timestamp: &str,
+	) -> AgeResult<()> {
+		AgeClient::observe_value(self, label, key, props, timestamp).await
+	}
+
+	async fn observe_batch(
+		&self,
+		items: &[(String, String, Value)],
+		timestamp: &str,
+	) -> AgeResult<()> {
+		if items.is_empty() {
+			return Ok(());
+		}
+
+		// One MERGE clause per item, each with its own node variable so the
+		// clauses compose into a single query. Duplicate keys within the
+		// batch count as separate observations.
+		let mut clauses = Vec::with_capacity(items.len());
+		for (idx, (label, key, props)) in items.iter().enumerate() {
+			self.prop_keys.check(props)?;
+			let var = format!("n{}", idx);
+			clauses.push(observe_cypher(
+				&var,
+				label,
+				&self.key_property,
+				key,
+				props,
+				timestamp,
+				self.raw_sample(props),
+			)?);
+		}
+		let cypher = clauses.join("\n");
+		let sql = "SELECT * FROM cypher($1::text, $2::text) as (v agtype);";
+
+		let res = sqlx::query(sql)
+			.bind(&self.graph)
+			.bind(&cypher)
+			.execute(&self.pool)
+			.await;
+
+		match res {
+			Ok(_) => Ok(()),
+			Err(e) => {
+				eprintln!(
+					"batch observe failed: {}; falling back to per-item observes",
+					e
+				);
 				for (label, key, props) in items.iter() {
-					if let Err(e2) = AgeClient::merge_entity(self, label, key, props).await {
-						eprintln!("per-item merge failed for {}: {}", key, e2);
+					if let Err(e2) =
+						AgeClient::observe_value(self, label, key, props, timestamp).await
+					{
+						eprintln!("per-item observe failed for {}: {}", key, e2);
 					}
 				}
 				Ok(())
@@ -404,8 +2877,10 @@ impl AgeRepo for AgeClient {
 		row_hash: Option<&str>,
 		cells: &[(String, String, String, String)],
 		timestamp: &str,
-	) -> Result<()> {
-		AgeClient::persist_row(self, dump_id, row_index, row_hash, cells, timestamp).await
+	) -> AgeResult<()> {
+		AgeClient::persist_row(self, dump_id, row_index, row_hash, cells, timestamp)
+			.await
+			.map(|_| ())
 	}
 
 	async fn increment_co_occurrence(
@@ -413,7 +2888,7 @@ impl AgeRepo for AgeClient {
 		a_key: &str,
 		b_key: &str,
 		timestamp: &str,
-	) -> Result<()> {
+	) -> AgeResult<()> {
 		AgeClient::increment_co_occurrence(self, a_key, b_key, timestamp).await
 	}
 
@@ -422,11 +2897,91 @@ impl AgeRepo for AgeClient {
 		from_key: &str,
 		to_key: &str,
 		timestamp: &str,
-	) -> Result<()> {
+	) -> AgeResult<()> {
 		AgeClient::persist_credential(self, from_key, to_key, timestamp).await
 	}
 
-	async fn apply_migration(&self, sql_content: &str) -> Result<()> {
+	async fn merge_dump(&self, dump_id: &str, props: &Value, timestamp: &str) -> AgeResult<()> {
+		AgeClient::merge_dump(self, dump_id, props, timestamp).await
+	}
+
+	async fn relate(
+		&self,
+		from_key: &str,
+		to_key: &str,
+		rel_type: &str,
+		props: &Value,
+	) -> AgeResult<()> {
+		AgeClient::relate(self, from_key, to_key, rel_type, props).await
+	}
+
+	async fn merge_edge(&self, node: &GraphNode, edge: &Edge, timestamp: &str) -> AgeResult<()> {
+		AgeClient::merge_edge(self, node, edge, timestamp).await
+	}
+
+	async fn merge_record(
+		&self,
+		node: &GraphNode,
+		edges: &[Edge],
+		timestamp: &str,
+	) -> AgeResult<()> {
+		AgeClient::merge_record_tx(self, node, edges, timestamp).await
+	}
+
+	async fn expire(
+		&self,
+		label: &str,
+		cutoff: &str,
+		action: RetentionAction,
+		limit: usize,
+	) -> AgeResult<u64> {
+		AgeClient::expire(self, label, cutoff, action, limit).await
+	}
+
+	async fn delete_by_source(&self, source: &str) -> AgeResult<u64> {
+		AgeClient::delete_by_source(self, source).await
+	}
+
+	async fn neighbors(
+		&self,
+		label: &str,
+		key: &str,
+		edge_types: &[&str],
+		limit: usize,
+	) -> AgeResult<Option<GraphFragment>> {
+		AgeClient::neighbors(self, label, key, edge_types, limit).await
+	}
+
+	async fn key_fingerprint(&self, label: &str, key: &str) -> AgeResult<Option<String>> {
+		AgeClient::key_fingerprint(self, label, key).await
+	}
+
+	fn key_property(&self) -> &str {
+		AgeClient::key_property(self)
+	}
+
+	async fn rekey_candidates(
+		&self,
+		label: &str,
+		after: &str,
+		limit: usize,
+	) -> AgeResult<Vec<RekeyCandidate>> {
+		AgeClient::rekey_candidates(self, label, after, limit).await
+	}
+
+	async fn rekey(&self, label: &str, old_key: &str, new_key: &str) -> AgeResult<RekeyOutcome> {
+		AgeClient::rekey(self, label, old_key, new_key).await
+	}
+
+	fn query_stream(
+		&self,
+		cypher: &str,
+		timeout: std::time::Duration,
+	) -> BoxStream<'static, AgeResult<Value>> {
+		AgeClient::query_stream(self, cypher, timeout)
+	}
+
+	async fn apply_migration(&self, sql_content: &str) -> AgeResult<()> {
 		AgeClient::apply_migration(self, sql_content).await
 	}
 }
@@ -440,6 +2995,54 @@ impl AgeRepo for AgeClient {
 mod tests {
 	use super::*;
 
+	#[test]
+	fn property_values_keep_their_agtype() {
+		let props = serde_json::json!({
+			"seen_count": 7,
+			"score": 2.0,
+			"ratio": 1e21,
+			"name": "7",
+			"ok": true,
+			"tags": [1, "a"],
+			"meta": {"bad-key": -3},
+		});
+		let cypher = merge_cypher("FieldValue", DEFAULT_KEY_PROPERTY, "k", &props).unwrap();
+		assert!(cypher.contains("seen_count: 7,"), "{}", cypher);
+		assert!(cypher.contains("score: 2.0,"), "{}", cypher);
+		assert!(cypher.contains("ratio: 1e21,"), "{}", cypher);
+		assert!(cypher.contains("name: \"7\","), "{}", cypher);
+		assert!(cypher.contains("ok: true,"), "{}", cypher);
+		assert!(cypher.contains("tags: [1, \"a\"]"), "{}", cypher);
+		assert!(cypher.contains("meta: {_enc_bad_2dkey: -3}"), "{}", cypher);
+
+		let too_big = serde_json::json!({ "n": u64::MAX });
+		assert!(matches!(
+			merge_cypher("FieldValue", DEFAULT_KEY_PROPERTY, "k", &too_big),
+			Err(AgeError::Serialization(_))
+		));
+	}
+
+	#[test]
+	fn property_type_mismatches_are_reported() {
+		let stored = serde_json::json!({"seen_count": 3, "source": "feed", "note": null});
+		let incoming = serde_json::json!({
+			"seen_count": "9",
+			"source": "other",
+			"note": 1,
+			"fresh": [],
+		});
+		assert_eq!(
+			property_type_mismatches(&stored, &incoming, &[]),
+			vec!["seen_count: number, got string".to_string()]
+		);
+		assert!(property_type_mismatches(&stored, &incoming, &["seen_count"]).is_empty());
+		// Integers and floats are both numbers.
+		assert!(
+			property_type_mismatches(&stored, &serde_json::json!({"seen_count": 2.5}), &[])
+				.is_empty()
+		);
+	}
+
 	#[test]
 	fn sanitize_prop_key_alphanumeric() {
 		assert_eq!(sanitize_prop_key("valid_key123"), "valid_key123");
@@ -447,40 +3050,747 @@ mod tests {
 
 	#[test]
 	fn sanitize_prop_key_special_chars() {
-		assert_eq!(sanitize_prop_key("key-with-dashes"), "key_with_dashes");
-		assert_eq!(sanitize_prop_key("key.with.dots"), "key_with_dots");
-		assert_eq!(sanitize_prop_key("key:with:colons"), "key_with_colons");
+		assert_eq!(
+			sanitize_prop_key("key-with-dashes"),
+			"_enc_key_2dwith_2ddashes"
+		);
+		assert_eq!(sanitize_prop_key("key.with.dots"), "_enc_key_2ewith_2edots");
+		assert_eq!(
+			sanitize_prop_key("key:with:colons"),
+			"_enc_key_3awith_3acolons"
+		);
+	}
+
+	#[test]
+	fn distinct_prop_keys_never_collide() {
+		let keys = [
+			"a.b",
+			"a_b",
+			"a-b",
+			"a b",
+			"_enc_a_2eb",
+			"a_2eb",
+			"",
+			"prop",
+		];
+		let sanitized: std::collections::HashSet<String> =
+			keys.iter().map(|k| sanitize_prop_key(k)).collect();
+		assert_eq!(sanitized.len(), keys.len(), "{:?}", sanitized);
+		for k in keys {
+			assert_eq!(decode_prop_key(&sanitize_prop_key(k)), k);
+		}
+
+		// Both keys survive as separate properties of one node.
+		let props = serde_json::json!({"a.b": 1, "a_b": 2});
+		let cypher = merge_cypher("FieldValue", DEFAULT_KEY_PROPERTY, "k", &props).unwrap();
+		assert!(cypher.contains("_enc_a_2eb: 1"), "{}", cypher);
+		assert!(cypher.contains("a_b: 2"), "{}", cypher);
+	}
+
+	#[test]
+	fn reject_policy_refuses_keys_that_would_be_encoded() {
+		let policy = PropKeyPolicy::Reject;
+		assert!(
+			policy
+				.check(&serde_json::json!({"seen_count": 1, "raw_2": "x"}))
+				.is_ok()
+		);
+		for props in [
+			serde_json::json!({"a.b": 1, "a_b": 2}),
+			serde_json::json!({"meta": {"bad-key": 1}}),
+			serde_json::json!({"tags": [{"": 1}]}),
+			serde_json::json!({"_enc_a": 1}),
+		] {
+			assert!(
+				matches!(policy.check(&props), Err(AgeError::InvalidPropertyKey(_))),
+				"{}",
+				props
+			);
+		}
+		assert!(
+			PropKeyPolicy::Encode
+				.check(&serde_json::json!({"a.b": 1}))
+				.is_ok()
+		);
+		assert_eq!("Reject".parse(), Ok(PropKeyPolicy::Reject));
+		assert!("drop".parse::<PropKeyPolicy>().is_err());
+	}
+
+	#[test]
+	fn observe_cypher_preserves_first_seen_and_counts() {
+		let cypher = observe_cypher(
+			"n",
+			"FieldValue",
+			DEFAULT_KEY_PROPERTY,
+			"example.com",
+			&serde_json::json!({"field_type": "domain", "first_seen": "ignored"}),
+			"2024-01-01T00:00:00Z",
+			None,
+		)
+		.unwrap();
+		let (create, matched) = cypher.split_once(" ON MATCH SET ").unwrap();
+		assert!(
+			create.starts_with(
+				"MERGE (n:FieldValue {canonical_key: \"example.com\"}) ON CREATE SET "
+			)
+		);
+		assert!(create.contains("n.first_seen = \"2024-01-01T00:00:00Z\""));
+		assert!(create.contains("n.seen_count = 1"));
+		assert!(create.contains("n.field_type = \"domain\""));
+		assert!(!matched.contains("first_seen"));
+		assert!(matched.contains("n.last_seen = \"2024-01-01T00:00:00Z\""));
+		assert!(matched.contains("n.seen_count = coalesce(n.seen_count, 0) + 1"));
+		assert!(!cypher.contains("ignored"));
+	}
+
+	#[test]
+	fn observe_cypher_uses_configured_key_property() {
+		let cypher = observe_cypher(
+			"n0",
+			"Domain",
+			"uid",
+			"example.com",
+			&serde_json::json!({}),
+			"2024-01-01T00:00:00Z",
+			None,
+		)
+		.unwrap();
+		assert!(cypher.starts_with("MERGE (n0:Domain {uid: \"example.com\"})"));
+		assert!(!cypher.contains(DEFAULT_KEY_PROPERTY));
+	}
+
+	#[test]
+	fn observe_cypher_samples_raw_values_before_counting() {
+		let sample = RawSample {
+			raw: "Example.COM",
+			capacity: 3,
+			draw: 0.5,
+			slot: 1,
+		};
+		let cypher = observe_cypher(
+			"n",
+			"FieldValue",
+			DEFAULT_KEY_PROPERTY,
+			"example.com",
+			&serde_json::json!({"raw": "Example.COM", "raw_samples": ["ignored"]}),
+			"2024-01-01T00:00:00Z",
+			Some(sample),
+		)
+		.unwrap();
+		let (create, matched) = cypher.split_once(" ON MATCH SET ").unwrap();
+		assert!(create.contains("n.raw_samples = [\"Example.COM\"]"));
+		assert!(matched.contains("WHEN size(coalesce(n.raw_samples, [])) < 3"));
+		assert!(matched.contains("WHEN 0.500000000 * (coalesce(n.seen_count, 0) + 1) < 3"));
+		assert!(matched.contains("[0..1] + [\"Example.COM\"] + coalesce(n.raw_samples, [])[2..3]"));
+		assert!(
+			matched.find("raw_samples").unwrap() < matched.find("n.seen_count = ").unwrap(),
+			"reservoir must see the count before this sighting"
+		);
+		assert!(!cypher.contains("ignored"));
+		assert!(balanced(&cypher));
+	}
+
+	#[test]
+	fn raw_sample_requires_raw_string_and_capacity() {
+		let props = serde_json::json!({"raw": "Example.COM"});
+		let sample = RawSample::from_props(&props, 4).unwrap();
+		assert_eq!(sample.raw, "Example.COM");
+		assert!((0.0..1.0).contains(&sample.draw));
+		assert!(sample.slot < 4);
+		assert!(RawSample::from_props(&props, 0).is_none());
+		assert!(RawSample::from_props(&serde_json::json!({"field_type": "domain"}), 4).is_none());
+	}
+
+	#[tokio::test]
+	async fn key_property_defaults_and_is_sanitized() {
+		let pool = PgPool::connect_lazy("postgres://localhost/heimdall").unwrap();
+		let client = AgeClient::new(pool, "g");
+		assert_eq!(client.key_property(), DEFAULT_KEY_PROPERTY);
+		let client = client.with_key_property("ext_id");
+		assert_eq!(client.key_property(), "ext_id");
+		let client = client.with_key_property("ext-id");
+		assert_eq!(client.key_property(), "_enc_ext_2did");
+	}
+
+	fn cells(pairs: &[(&str, &str)]) -> Vec<Cell> {
+		pairs
+			.iter()
+			.map(|(column, value)| {
+				let key = format!("{}:{}", column, value);
+				(
+					column.to_string(),
+					value.to_uppercase(),
+					key,
+					value.to_string(),
+				)
+			})
+			.collect()
+	}
+
+	#[test]
+	fn row_hash_is_deterministic_and_ignores_raw_values() {
+		let row = cells(&[("email", "a@example.com"), ("ip", "10.0.0.1")]);
+		let hash = row_hash_for(&row);
+		assert_eq!(hash.len(), 64);
+		assert_eq!(hash, row_hash_for(&row.clone()));
+
+		let mut reformatted = row.clone();
+		reformatted[0].1 = " A@Example.COM ".to_string();
+		assert_eq!(row_hash_for(&reformatted), hash);
+
+		let other = cells(&[("email", "b@example.com"), ("ip", "10.0.0.1")]);
+		assert_ne!(row_hash_for(&other), hash);
+		// Field boundaries are unambiguous.
+		assert_ne!(
+			row_hash_for(&cells(&[("ab", "c")])),
+			row_hash_for(&cells(&[("a", "bc")]))
+		);
+	}
+
+	#[test]
+	fn row_hash_order_flag_controls_column_order_sensitivity() {
+		let row = cells(&[("email", "a@example.com"), ("ip", "10.0.0.1")]);
+		let swapped = cells(&[("ip", "10.0.0.1"), ("email", "a@example.com")]);
+
+		assert_ne!(row_hash_for(&row), row_hash_for(&swapped));
+		assert_eq!(
+			row_hash_with(&row, RowHashOrder::Unordered),
+			row_hash_with(&swapped, RowHashOrder::Unordered)
+		);
+		assert_eq!(
+			row_hash_with(&row, RowHashOrder::Columns),
+			row_hash_for(&row)
+		);
+	}
+
+	#[tokio::test]
+	async fn auto_row_hash_fills_in_missing_hashes_only() {
+		let row = cells(&[("email", "a@example.com")]);
+		let pool = PgPool::connect_lazy("postgres://localhost/heimdall").unwrap();
+		let client = AgeClient::new(pool, "g");
+		assert_eq!(client.row_hash(None, &row), None);
+
+		let client = client.with_auto_row_hash(RowHashOrder::Unordered);
+		assert_eq!(
+			client.row_hash(None, &row),
+			Some(row_hash_with(&row, RowHashOrder::Unordered))
+		);
+		assert_eq!(
+			client.row_hash(Some("given"), &row).as_deref(),
+			Some("given")
+		);
+	}
+
+	#[test]
+	fn relate_cypher_merges_directed_typed_edge() {
+		let cypher = relate_cypher(
+			DEFAULT_KEY_PROPERTY,
+			"example.com",
+			"192.0.2.1",
+			"RESOLVES-TO",
+			&serde_json::json!({"source": "dns", "ttl": 300}),
+		)
+		.unwrap();
+		assert_eq!(
+			cypher,
+			"MATCH (a {canonical_key: \"example.com\"}), (b {canonical_key: \"192.0.2.1\"}) \
+			 MERGE (a)-[e:RESOLVESTO]->(b) \
+			 SET e += {source: \"dns\", ttl: 300} \
+			 RETURN e"
+		);
+	}
+
+	#[test]
+	fn property_index_sql_quotes_identifiers() {
+		assert_eq!(
+			property_index_name("FieldValue", "canonical_key"),
+			"idx_fieldvalue_canonical_key"
+		);
+		let sql = create_index_sql("tenant\"x", "FieldValue", "canonical_key");
+		assert!(sql.contains("ON \"tenant\"\"x\".\"FieldValue\""), "{}", sql);
+		assert!(sql.contains("'\"canonical_key\"'::agtype"), "{}", sql);
+	}
+
+	#[test]
+	fn create_cypher_sets_key_once() {
+		let cypher = create_cypher(
+			"n0",
+			"FieldValue",
+			"canonical_key",
+			"example.com",
+			&serde_json::json!({"canonical_key": "other", "field_type": "domain"}),
+		)
+		.unwrap();
+		assert_eq!(
+			cypher,
+			"CREATE (n0:FieldValue {canonical_key: \"example.com\", field_type: \"domain\"})"
+		);
+		assert!(balanced(&cypher));
+	}
+
+	#[test]
+	fn relate_cypher_rejects_empty_rel_type() {
+		let props = serde_json::json!({});
+		assert!(relate_cypher(DEFAULT_KEY_PROPERTY, "a", "b", "-->", &props).is_err());
+	}
+
+	#[test]
+	fn agtype_json_drops_graph_type_suffixes() {
+		assert_eq!(agtype_json("42"), serde_json::json!(42));
+		assert_eq!(
+			agtype_json("{\"id\": 1, \"label\": \"FieldValue\", \"properties\": {}}::vertex"),
+			serde_json::json!({"id": 1, "label": "FieldValue", "properties": {}})
+		);
+		assert_eq!(
+			agtype_json("[{\"id\": 1}::vertex, {\"id\": 2}::edge, {\"id\": 3}::vertex]::path"),
+			serde_json::json!([{"id": 1}, {"id": 2}, {"id": 3}])
+		);
+		assert_eq!(agtype_json("NaN"), serde_json::json!("NaN"));
+	}
+
+	#[test]
+	fn hub_cypher_counts_a_bounded_degree() {
+		let cypher = hub_cypher(DEFAULT_KEY_PROPERTY, "source:feed", 1000).unwrap();
+		assert!(cypher.starts_with("MATCH (n:FieldValue {canonical_key: \"source:feed\"})"));
+		assert!(cypher.contains("WITH n, e LIMIT 1000"));
+		assert!(cypher.ends_with("RETURN coalesce(n.is_hub, false) OR degree >= 1000"));
+	}
+
+	#[test]
+	fn cooccurrence_rebuild_cypher_sets_counts_per_row() {
+		let page =
+			cooccurrence_values_cypher(DEFAULT_KEY_PROPERTY, Some("dump-1"), "ip:1", 500).unwrap();
+		assert!(page.starts_with("MATCH (r:Row {dump_id: \"dump-1\"})-[:HAS_SIGHTING]->"));
+		assert!(page.contains("WHERE a.canonical_key > \"ip:1\""));
+		assert!(page.ends_with("ORDER BY k LIMIT 500"));
+
+		let keys = vec!["domain:a.example".to_string()];
+		let all = rebuild_cooccurrence_cypher(DEFAULT_KEY_PROPERTY, &keys, None).unwrap();
+		assert!(all.contains("WHERE a.canonical_key IN [\"domain:a.example\"]"));
+		assert!(all.contains("count(DISTINCT r) AS rows"));
+		assert!(all.contains("SET co.count = rows, co.last_seen = last_seen"));
+		assert!(!all.contains("IN dumps"));
+
+		let scoped =
+			rebuild_cooccurrence_cypher(DEFAULT_KEY_PROPERTY, &keys, Some("dump-1")).unwrap();
+		assert!(scoped.contains("WHERE \"dump-1\" IN dumps MERGE"));
+	}
+
+	#[test]
+	fn ips_in_range_cypher_bounds_one_ip_version() {
+		let range = crate::lib::normalizers::cidr_range("10.0.0.0/8").unwrap();
+		assert_eq!(
+			ips_in_range_cypher(&range).unwrap(),
+			"MATCH (n:FieldValue) WHERE n.ip_version = 4 \
+			 AND n.ip_hex >= \"0a000000\" AND n.ip_hex <= \"0affffff\" \
+			 RETURN DISTINCT n.ip_hex ORDER BY n.ip_hex"
+		);
+	}
+
+	#[test]
+	fn outdated_normalizations_cypher_targets_older_versions() {
+		let cypher = outdated_normalizations_cypher(DEFAULT_KEY_PROPERTY, "ip", 2, 100).unwrap();
+		assert_eq!(
+			cypher,
+			"MATCH (n) WHERE n.normalizer = \"ip\" AND n.normalizer_version < 2 \
+			 RETURN n.canonical_key LIMIT 100"
+		);
+	}
+
+	#[tokio::test]
+	async fn record_statements_observe_node_before_edges() {
+		let pool = PgPool::connect_lazy("postgres://localhost/heimdall").unwrap();
+		let client = AgeClient::new(pool, "g");
+		let node = GraphNode::new("FieldValue", "email:a@example.com", serde_json::json!({}));
+		let edges = [
+			Edge::new("CO_OCCURS", "domain:example.com"),
+			Edge::new("USES-PASSWORD", "hash:abc")
+				.with_to_label("Credential")
+				.with_props(serde_json::json!({"source": "dump-1", "count": 99})),
+		];
+		let statements = client
+			.record_statements(&node, &edges, "2024-01-01T00:00:00Z")
+			.unwrap();
+		assert_eq!(statements.len(), 3);
+		assert!(statements[0].starts_with(
+			"MERGE (n:FieldValue {canonical_key: \"email:a@example.com\"}) ON CREATE SET"
+		));
+		assert!(statements[1].starts_with(
+			"MATCH (n:FieldValue {canonical_key: \"email:a@example.com\"}) \
+			 MERGE (m:FieldValue {canonical_key: \"domain:example.com\"}) \
+			 MERGE (n)-[e:CO_OCCURS]->(m)"
+		));
+		assert!(statements[2].contains("MERGE (m:Credential {canonical_key: \"hash:abc\"})"));
+		assert!(statements[2].contains("-[e:USESPASSWORD]->"));
+		assert!(statements[2].contains("e.count = coalesce(e.count, 0) + 1"));
+		assert!(statements[2].contains("e.source = \"dump-1\""));
+		assert!(!statements[2].contains("99"));
+
+		let bad = [Edge::new("-->", "x")];
+		assert!(client.record_statements(&node, &bad, "t").is_err());
+	}
+
+	#[test]
+	fn expire_cypher_is_bounded_and_skips_tombstones() {
+		let cutoff = "2026-01-01T00:00:00+00:00";
+		let now = "2026-02-01T00:00:00+00:00";
+		let cypher =
+			expire_cypher("Field-Value", cutoff, now, RetentionAction::Tombstone, 50).unwrap();
+		assert_eq!(
+			cypher,
+			"MATCH (n:FieldValue) WHERE n.last_seen < \"2026-01-01T00:00:00+00:00\" \
+			 AND n.tombstone IS NULL WITH n LIMIT 50 \
+			 SET n.tombstone = true, n.expired_at = \"2026-02-01T00:00:00+00:00\" \
+			 RETURN count(n)"
+		);
+
+		let cypher = expire_cypher("Token", cutoff, now, RetentionAction::Delete, 10).unwrap();
+		assert!(
+			cypher.contains("WITH n LIMIT 10 DETACH DELETE n"),
+			"{}",
+			cypher
+		);
+		assert!(!cypher.contains("tombstone"));
+		assert_eq!("Delete".parse(), Ok(RetentionAction::Delete));
+		assert!("purge".parse::<RetentionAction>().is_err());
+	}
+
+	#[test]
+	fn delete_by_source_cypher_is_scoped_and_escaped() {
+		assert_eq!(
+			delete_by_source_cypher("feedA", 500).unwrap(),
+			"MATCH (n) WHERE n.sources = [\"feedA\"] WITH n LIMIT 500 DETACH DELETE n RETURN count(n)"
+		);
+		assert_eq!(
+			release_source_cypher("feedA", 500).unwrap(),
+			"MATCH (n) WHERE \"feedA\" IN n.sources WITH n LIMIT 500 \
+			 SET n.sources = [s IN n.sources WHERE s <> \"feedA\"] RETURN count(n)"
+		);
+		let cypher = delete_by_source_cypher("x\" OR true //", 10).unwrap();
+		assert!(
+			cypher.contains(r#"n.sources = ["x\" OR true //"]"#),
+			"{}",
+			cypher
+		);
+		let cypher = release_source_cypher("x\" OR true //", 10).unwrap();
+		assert!(
+			cypher.contains(r#"WHERE "x\" OR true //" IN"#),
+			"{}",
+			cypher
+		);
+	}
+
+	#[test]
+	fn observe_cypher_adds_the_source_to_the_node_sources() {
+		let cypher = observe_cypher(
+			"n",
+			"FieldValue",
+			DEFAULT_KEY_PROPERTY,
+			"example.com",
+			&serde_json::json!({"field_type": "domain", "source": "feedB"}),
+			"2024-01-01T00:00:00Z",
+			None,
+		)
+		.unwrap();
+		let (create, matched) = cypher.split_once(" ON MATCH SET ").unwrap();
+		assert!(create.contains("n.sources = [\"feedB\"]"));
+		assert!(matched.contains(
+			"n.sources = CASE WHEN n.sources IS NULL THEN [\"feedB\"] \
+			 WHEN \"feedB\" IN n.sources THEN n.sources ELSE n.sources + [\"feedB\"] END"
+		));
+		// The source of the latest write does not replace the set.
+		assert!(!cypher.contains("n.source ="));
+	}
+
+	#[test]
+	fn neighbors_cypher_escapes_key_and_bounds_limit() {
+		let key = "evil\"}) DETACH DELETE n //";
+		let cypher = neighbors_cypher(
+			"FieldValue",
+			DEFAULT_KEY_PROPERTY,
+			key,
+			&["RESOLVES-TO", "CO_OCCURS"],
+			10_000,
+		)
+		.unwrap();
+		assert!(
+			cypher.starts_with(&format!(
+				"MATCH (n:FieldValue {{canonical_key: {}}})-[e]-(m) \
+				 WHERE label(e) IN [\"RESOLVESTO\", \"CO_OCCURS\"] RETURN",
+				serde_json::to_string(key).unwrap()
+			)),
+			"{}",
+			cypher
+		);
+		assert!(cypher.ends_with(&format!("LIMIT {}", MAX_NEIGHBORS)));
+		assert!(balanced(&cypher));
+
+		let all = neighbors_cypher("FieldValue", DEFAULT_KEY_PROPERTY, "k", &[], 5).unwrap();
+		assert!(!all.contains("WHERE"));
+		assert!(all.ends_with("LIMIT 5"));
+		assert!(neighbors_cypher("FieldValue", DEFAULT_KEY_PROPERTY, "k", &["--"], 5).is_err());
+
+		let node = node_cypher("Field Value", DEFAULT_KEY_PROPERTY, "k").unwrap();
+		assert!(node.starts_with("MATCH (n:FieldValue {canonical_key: \"k\"})"));
+		assert!(node.ends_with("LIMIT 1"));
+	}
+
+	#[test]
+	fn rekey_cypher_pages_in_key_order_and_escapes_keys() {
+		let cypher =
+			rekey_candidates_cypher("FieldValue", DEFAULT_KEY_PROPERTY, "a\"b", 50).unwrap();
+		assert!(cypher.starts_with(
+			"MATCH (n:FieldValue) WHERE n.canonical_key > \"a\\\"b\" \
+			 WITH n ORDER BY n.canonical_key LIMIT 50"
+		));
+		assert!(balanced(&cypher));
+
+		let key = "evil\"}) DETACH DELETE n //";
+		let ids = rekey_ids_cypher("FieldValue", DEFAULT_KEY_PROPERTY, key, "new").unwrap();
+		assert!(ids.contains(&serde_json::to_string(key).unwrap()));
+		assert!(ids.ends_with("RETURN [id(o), id(n)] LIMIT 1"));
+		assert!(balanced(&ids));
+	}
+
+	/// Database error carrying only a SQLSTATE code.
+	#[derive(Debug)]
+	struct StateError(&'static str);
+
+	impl std::fmt::Display for StateError {
+		fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+			write!(f, "sqlstate {}", self.0)
+		}
+	}
+
+	impl std::error::Error for StateError {}
+
+	impl sqlx::error::DatabaseError for StateError {
+		fn message(&self) -> &str {
+			"test error"
+		}
+
+		fn code(&self) -> Option<std::borrow::Cow<'_, str>> {
+			Some(self.0.into())
+		}
+
+		fn as_error(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+			self
+		}
+
+		fn as_error_mut(&mut self) -> &mut (dyn std::error::Error + Send + Sync + 'static) {
+			self
+		}
+
+		fn into_error(self: Box<Self>) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+			self
+		}
+
+		fn kind(&self) -> sqlx::error::ErrorKind {
+			sqlx::error::ErrorKind::Other
+		}
+	}
+
+	fn db_error(code: &'static str) -> AgeError {
+		sqlx::Error::Database(Box::new(StateError(code))).into()
+	}
+
+	#[test]
+	fn sqlx_errors_map_to_age_error_variants() {
+		let refused = std::io::Error::from(std::io::ErrorKind::ConnectionRefused);
+		assert!(matches!(
+			AgeError::from(sqlx::Error::Io(refused)),
+			AgeError::Connection(_)
+		));
+		assert!(matches!(
+			AgeError::from(sqlx::Error::PoolTimedOut),
+			AgeError::Transient(_)
+		));
+		assert!(matches!(db_error("08006"), AgeError::Connection(_)));
+		assert!(matches!(db_error("57P01"), AgeError::Connection(_)));
+		assert!(matches!(db_error("40001"), AgeError::Transient(_)));
+		assert!(matches!(db_error("40P01"), AgeError::Transient(_)));
+		assert!(matches!(db_error("42601"), AgeError::Query(_)));
+		assert!(matches!(db_error("23505"), AgeError::Query(_)));
+		assert!(matches!(db_error("3F000"), AgeError::GraphMissing(_)));
+		assert!(matches!(
+			AgeError::from(sqlx::Error::Decode("bad agtype".into())),
+			AgeError::Serialization(_)
+		));
+		assert!(matches!(
+			AgeError::from(sqlx::Error::RowNotFound),
+			AgeError::Query(_)
+		));
+
+		assert!(db_error("40001").is_retryable());
+		assert!(!db_error("42601").is_retryable());
+		let any: anyhow::Error = db_error("3F000").into();
+		assert!(any.to_string().starts_with("graph does not exist"));
+	}
+
+	#[test]
+	fn version_at_least_compares_numeric_components() {
+		assert!(version_at_least("1.5.0", MIN_AGE_VERSION));
+		assert!(version_at_least("1.4.0", "1.4.0"));
+		assert!(version_at_least("1.10.0", "1.4.0"));
+		assert!(version_at_least("1.5", "1.5.0"));
+		assert!(!version_at_least("1.3.9", "1.4.0"));
+		assert!(!version_at_least("0.9.0", "1.4.0"));
+	}
+
+	/// True if every `(`, `{` and `[` outside string literals is closed in order.
+	fn balanced(cypher: &str) -> bool {
+		let mut stack = Vec::new();
+		let mut in_string = false;
+		let mut escaped = false;
+		for c in cypher.chars() {
+			if in_string {
+				match c {
+					'\\' if !escaped => escaped = true,
+					'"' if !escaped => in_string = false,
+					_ => escaped = false,
+				}
+				continue;
+			}
+			match c {
+				'"' => in_string = true,
+				'(' | '{' | '[' => stack.push(c),
+				')' | '}' | ']' => {
+					let open = match c {
+						')' => '(',
+						'}' => '{',
+						_ => '[',
+					};
+					if stack.pop() != Some(open) {
+						return false;
+					}
+				}
+				_ => {}
+			}
+		}
+		stack.is_empty() && !in_string
+	}
+
+	#[test]
+	fn row_cypher_with_row_hash_is_balanced() {
+		let cells = vec![(
+			"email".to_string(),
+			"A@Example.com (work)".to_string(),
+			"email:a@example.com".to_string(),
+			"a@example.com".to_string(),
+		)];
+		let row = row_cypher(
+			DEFAULT_KEY_PROPERTY,
+			"dump-1",
+			3,
+			Some("abc123"),
+			&cells,
+			"2024-01-01T00:00:00Z",
+			None,
+		)
+		.unwrap();
+		let claim = row.claim.expect("hashed rows are claimed first");
+		assert!(balanced(&claim), "{}", claim);
+		assert!(claim.contains("MERGE (r:Row {dump_id: \"dump-1\", row_hash: \"abc123\"})"));
+		assert!(balanced(&row.body), "{}", row.body);
+		assert!(
+			row.body
+				.starts_with("MATCH (r:Row {dump_id: \"dump-1\", row_hash: \"abc123\"})")
+		);
+	}
+
+	#[test]
+	fn row_cypher_without_row_hash_creates_row() {
+		let row = row_cypher(DEFAULT_KEY_PROPERTY, "dump-1", 0, None, &[], "t", None).unwrap();
+		assert!(row.claim.is_none());
+		assert!(balanced(&row.body), "{}", row.body);
+		assert!(
+			row.body
+				.contains("CREATE (r:Row {dump_id: \"dump-1\", index: 0})")
+		);
+	}
+
+	#[test]
+	fn row_cypher_coalesces_repeated_sightings() {
+		let compactor = SightingCompactor::new(60);
+		let cells = vec![(
+			"ip".to_string(),
+			"192.0.2.1".to_string(),
+			"ip:192.0.2.1".to_string(),
+			"192.0.2.1".to_string(),
+		)];
+		let row = |index: i64, ts: &str| {
+			row_cypher(
+				DEFAULT_KEY_PROPERTY,
+				"feed",
+				index,
+				None,
+				&cells,
+				ts,
+				Some(&compactor),
+			)
+			.unwrap()
+			.body
+		};
+		let first = row(0, "2024-01-01T00:00:00Z");
+		assert!(balanced(&first), "{}", first);
+		assert!(first.contains("CREATE (s0:Sighting {sighting_id: "));
+		assert!(first.contains("count: 1"));
+
+		let second = row(1, "2024-01-01T00:00:30Z");
+		assert!(balanced(&second), "{}", second);
+		assert!(second.contains("MERGE (s0:Sighting {sighting_id: "));
+		assert!(second.contains("SET s0.count = 2, s0.last_seen = \"2024-01-01T00:00:30+00:00\""));
+		assert!(second.contains("MERGE (r)-[:HAS_SIGHTING]->(s0)"));
+	}
+
+	#[test]
+	fn dump_cypher_sets_non_null_props() {
+		let props = serde_json::json!({
+			"format": "csv",
+			"record_count": 2,
+			"source_filename": null,
+		});
+		let cypher = dump_cypher("dump-1", &props, "t").unwrap();
+		assert!(balanced(&cypher), "{}", cypher);
+		assert!(
+			cypher
+				.starts_with("MERGE (d:Dump {id: \"dump-1\"}) ON CREATE SET d.received_at = \"t\"")
+		);
+		assert!(cypher.contains("d.format = \"csv\""));
+		assert!(cypher.contains("d.record_count = 2"));
+		assert!(!cypher.contains("source_filename"));
 	}
 
 	#[test]
 	fn sanitize_prop_key_empty() {
-		assert_eq!(sanitize_prop_key(""), "prop");
-		assert_eq!(sanitize_prop_key("!!!"), "___");
+		assert_eq!(sanitize_prop_key(""), "_enc_");
+		assert_eq!(sanitize_prop_key("!!!"), "_enc__21_21_21");
 	}
 
 	#[test]
 	fn sanitize_prop_key_unicode() {
-		// Unicode characters are replaced with underscores, except ASCII alphanumeric and underscore
+		// Non-ASCII characters are encoded byte by byte
 		let result = sanitize_prop_key("key_with_émojis_🔥");
-		assert!(result.starts_with("key_with_"));
+		assert!(result.starts_with("_enc_key_5fwith_5f_c3_a9mojis"));
 		// Verify no unicode characters remain
 		assert!(
 			result
 				.chars()
 				.all(|c| c.is_ascii_alphanumeric() || c == '_')
 		);
+		assert_eq!(decode_prop_key(&result), "key_with_émojis_🔥");
 	}
 
 	#[test]
 	fn sanitize_prop_key_sql_injection_attempt() {
-		// SQL injection attempts should be sanitized to underscores
+		// SQL injection attempts are encoded to letters, digits and underscores
 		let result = sanitize_prop_key("'; DROP TABLE users; --");
 		assert!(
 			result
 				.chars()
 				.all(|c| c.is_ascii_alphanumeric() || c == '_')
 		);
-		assert!(result.contains("DROP_TABLE_users"));
+		assert!(result.contains("DROP_20TABLE_20users"));
 	}
 
 	#[test]
@@ -607,6 +3917,159 @@ mod tests {
 		assert!(result.chars().all(|c| c == 'L'));
 	}
 
+	/// Adversarial labels, keys, property names and values must only ever
+	/// reach a query as sanitized identifiers or quoted literals.
+	mod injection {
+		use super::*;
+		use proptest::prelude::*;
+
+		const PAYLOADS: &[&str] = &[
+			"\"}) DETACH DELETE (n) //",
+			"'}) DETACH DELETE (n) //",
+			"\\\"}) MATCH (m) DETACH DELETE m //",
+			"x` {a: 1}) DELETE n //",
+			"a\"\nRETURN 1 /*",
+			"*/ MATCH (n) DETACH DELETE n /*",
+			"$$) as (v agtype); DROP TABLE ag_catalog.ag_graph; --",
+			"\\",
+			"\u{0}\u{1b}\u{2028}",
+			"",
+		];
+
+		/// Split `cypher` into its shape (string literals replaced by `"?"`,
+		/// identifiers and numbers by `w`, whitespace dropped) and its
+		/// decoded string literals.
+		fn scan(cypher: &str) -> (String, Vec<String>) {
+			let mut shape = String::new();
+			let mut literals = Vec::new();
+			let mut chars = cypher.char_indices().peekable();
+			while let Some((start, c)) = chars.next() {
+				if c == '"' {
+					let mut escaped = false;
+					let end = loop {
+						match chars.next() {
+							Some((i, '"')) if !escaped => break i,
+							Some((_, '\\')) => escaped = !escaped,
+							Some(_) => escaped = false,
+							None => panic!("unterminated string literal in {}", cypher),
+						}
+					};
+					let literal = serde_json::from_str(&cypher[start..=end])
+						.unwrap_or_else(|e| panic!("malformed literal in {}: {}", cypher, e));
+					literals.push(literal);
+					shape.push_str("\"?\"");
+				} else if c.is_ascii_alphanumeric() || c == '_' {
+					while chars
+						.next_if(|(_, c)| c.is_ascii_alphanumeric() || *c == '_')
+						.is_some()
+					{}
+					shape.push('w');
+				} else if !c.is_whitespace() {
+					shape.push(c);
+				}
+			}
+			(shape, literals)
+		}
+
+		fn props(prop: &str, value: &str) -> Value {
+			Value::Object(
+				[(prop.to_string(), Value::from(value))]
+					.into_iter()
+					.collect(),
+			)
+		}
+
+		/// Build the queries behind `merge_entity`/`merge_batch`, `relate`
+		/// and `persist_row` from the given inputs.
+		fn queries(label: &str, key: &str, prop: &str, value: &str) -> Vec<String> {
+			let props = props(prop, value);
+			let cell = (
+				prop.to_string(),
+				value.to_string(),
+				key.to_string(),
+				value.to_string(),
+			);
+			let hashed = row_cypher(
+				DEFAULT_KEY_PROPERTY,
+				key,
+				7,
+				Some(value),
+				std::slice::from_ref(&cell),
+				value,
+				None,
+			)
+			.unwrap();
+			let plain =
+				row_cypher(DEFAULT_KEY_PROPERTY, key, 7, None, &[cell], value, None).unwrap();
+			let rel_type = format!("R{}", label);
+			vec![
+				merge_cypher(label, DEFAULT_KEY_PROPERTY, key, &props).unwrap(),
+				relate_cypher(DEFAULT_KEY_PROPERTY, key, value, &rel_type, &props).unwrap(),
+				hashed.claim.unwrap(),
+				hashed.body,
+				plain.body,
+			]
+		}
+
+		/// Every query built from the inputs has the same shape as one built
+		/// from benign inputs, and carries `key` and `value` verbatim as data.
+		fn assert_only_data(label: &str, key: &str, prop: &str, value: &str) {
+			let benign = queries("Label", "key", "prop", "value");
+			for (query, expected) in queries(label, key, prop, value).iter().zip(&benign) {
+				let (shape, literals) = scan(query);
+				assert_eq!(shape, scan(expected).0, "query shape changed: {}", query);
+				for input in [key, value] {
+					assert!(
+						literals.iter().any(|l| l == input),
+						"{:?} not a literal in {}",
+						input,
+						query
+					);
+				}
+			}
+		}
+
+		#[test]
+		fn known_payloads_stay_data() {
+			for payload in PAYLOADS {
+				assert_only_data(payload, payload, payload, payload);
+			}
+		}
+
+		#[test]
+		fn batched_merges_stay_data() {
+			let stmts: Vec<String> = PAYLOADS
+				.iter()
+				.map(|p| merge_cypher(p, DEFAULT_KEY_PROPERTY, p, &props(p, p)))
+				.collect::<AgeResult<_>>()
+				.unwrap();
+			let (shape, literals) = scan(&stmts.join("\n"));
+			let benign = merge_cypher("L", DEFAULT_KEY_PROPERTY, "k", &props("p", "v"));
+			assert_eq!(shape, scan(&benign.unwrap()).0.repeat(PAYLOADS.len()));
+			assert_eq!(literals.len(), 2 * PAYLOADS.len());
+		}
+
+		/// Arbitrary strings, biased towards Cypher punctuation.
+		fn payload() -> impl Strategy<Value = String> {
+			prop_oneof![
+				any::<String>(),
+				r#"["'`\\(){}\[\]:;/*$ \n\ta-zA-Z0-9-]{0,32}"#,
+			]
+		}
+
+		proptest! {
+			#[test]
+			fn arbitrary_inputs_stay_data(
+				label in payload(),
+				key in payload(),
+				prop in payload(),
+				value in payload(),
+			) {
+				assert_only_data(&label, &key, &prop, &value);
+			}
+		}
+	}
+
 	#[cfg(feature = "integration-tests")]
 	mod integration {
 		use super::*;
@@ -0,0 +1,357 @@
+//! Audit trail of mutating operations.
+//!
+//! Ingest, change log imports, deletes (retention expiry, change log
+//! compaction) and PII decrypts each emit an [`AuditEvent`] to the
+//! configured [`AuditLog`]: the application log under the `audit` target,
+//! an append-only JSONL file, or nowhere. Every event carries a timestamp
+//! and a request ID correlating it with the request that caused it.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use axum::{
+	extract::{Request, State},
+	http::{HeaderMap, HeaderValue},
+	middleware::Next,
+	response::Response,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::auth::Subject;
+use crate::state::AppState;
+
+/// Header carrying the request ID; taken from the request when present and
+/// always echoed on the response.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Sink names accepted in `Settings.audit_sink`.
+pub const AUDIT_SINKS: &[&str] = &["log", "jsonl", "off"];
+
+/// What an audited operation did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+	Ingest,
+	Import,
+	Expire,
+	Compact,
+	Decrypt,
+	Rekey,
+}
+
+/// One audited operation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEvent {
+	/// RFC 3339 time the event was emitted.
+	pub timestamp: String,
+	pub action: AuditAction,
+	/// Correlates the event with its request; see [`REQUEST_ID_HEADER`].
+	pub request_id: String,
+	/// Who performed the operation: a token subject, a decrypting actor or
+	/// a background task.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub subject: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub endpoint: Option<String>,
+	/// Records (or entries, or nodes) affected.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub records: Option<u64>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub dump_id: Option<String>,
+	/// What was acted on, e.g. the key a payload was decrypted with.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub target: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub reason: Option<String>,
+}
+
+impl AuditEvent {
+	pub fn new(action: AuditAction, request_id: impl Into<String>) -> Self {
+		Self {
+			timestamp: chrono::Utc::now().to_rfc3339(),
+			action,
+			request_id: request_id.into(),
+			subject: None,
+			endpoint: None,
+			records: None,
+			dump_id: None,
+			target: None,
+			reason: None,
+		}
+	}
+
+	pub fn with_subject(mut self, subject: impl Into<String>) -> Self {
+		self.subject = Some(subject.into());
+		self
+	}
+
+	pub fn with_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+		self.endpoint = Some(endpoint.into());
+		self
+	}
+
+	pub fn with_records(mut self, records: u64) -> Self {
+		self.records = Some(records);
+		self
+	}
+
+	pub fn with_dump_id(mut self, dump_id: impl Into<String>) -> Self {
+		self.dump_id = Some(dump_id.into());
+		self
+	}
+
+	pub fn with_target(mut self, target: impl Into<String>) -> Self {
+		self.target = Some(target.into());
+		self
+	}
+
+	pub fn with_reason(mut self, reason: impl Into<String>) -> Self {
+		self.reason = Some(reason.into());
+		self
+	}
+}
+
+/// A fresh random request ID.
+pub fn new_request_id() -> String {
+	let bytes: [u8; 16] = rand::thread_rng().r#gen();
+	bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The request's `x-request-id`, or a fresh ID when it has none.
+pub fn request_id(headers: &HeaderMap) -> String {
+	headers
+		.get(REQUEST_ID_HEADER)
+		.and_then(|v| v.to_str().ok())
+		.map(str::trim)
+		.filter(|v| !v.is_empty() && v.len() <= 128)
+		.map(str::to_string)
+		.unwrap_or_else(new_request_id)
+}
+
+enum Sink {
+	Disabled,
+	Log,
+	Jsonl(PathBuf),
+	Memory(Mutex<Vec<AuditEvent>>),
+}
+
+/// Where audit events go.
+pub struct AuditLog {
+	sink: Sink,
+	/// Serializes appends so concurrent events never interleave.
+	write: Mutex<()>,
+}
+
+impl AuditLog {
+	fn with_sink(sink: Sink) -> Self {
+		Self {
+			sink,
+			write: Mutex::new(()),
+		}
+	}
+
+	/// Drop every event.
+	pub fn disabled() -> Self {
+		Self::with_sink(Sink::Disabled)
+	}
+
+	/// Log events as JSON under the `audit` target.
+	pub fn log() -> Self {
+		Self::with_sink(Sink::Log)
+	}
+
+	/// Append events to `path` as JSON lines.
+	pub fn open(path: impl AsRef<Path>) -> Self {
+		Self::with_sink(Sink::Jsonl(path.as_ref().to_path_buf()))
+	}
+
+	/// Keep events in memory; see [`AuditLog::events`].
+	pub fn in_memory() -> Self {
+		Self::with_sink(Sink::Memory(Mutex::new(Vec::new())))
+	}
+
+	pub fn is_enabled(&self) -> bool {
+		!matches!(self.sink, Sink::Disabled)
+	}
+
+	/// Record `event`. Failing to write it is logged and does not fail the
+	/// audited operation.
+	pub fn emit(&self, event: AuditEvent) {
+		match &self.sink {
+			Sink::Disabled => {}
+			Sink::Log => match serde_json::to_string(&event) {
+				Ok(line) => log::info!(target: "audit", "{}", line),
+				Err(e) => log::error!("failed to serialize audit event: {}", e),
+			},
+			Sink::Jsonl(path) => {
+				let _guard = self.write.lock().unwrap();
+				if let Err(e) = append_line(path, &event) {
+					log::error!("failed to write audit event to {}: {}", path.display(), e);
+				}
+			}
+			Sink::Memory(events) => events.lock().unwrap().push(event),
+		}
+	}
+
+	/// Events kept by an in-memory log, oldest first; empty for other sinks.
+	pub fn events(&self) -> Vec<AuditEvent> {
+		match &self.sink {
+			Sink::Memory(events) => events.lock().unwrap().clone(),
+			_ => Vec::new(),
+		}
+	}
+}
+
+impl std::fmt::Debug for AuditLog {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let sink = match &self.sink {
+			Sink::Disabled => "off".to_string(),
+			Sink::Log => "log".to_string(),
+			Sink::Jsonl(path) => format!("jsonl:{}", path.display()),
+			Sink::Memory(_) => "memory".to_string(),
+		};
+		f.debug_struct("AuditLog").field("sink", &sink).finish()
+	}
+}
+
+fn append_line(path: &Path, event: &AuditEvent) -> std::io::Result<()> {
+	let mut line = serde_json::to_vec(event)?;
+	line.push(b'\n');
+	OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(path)?
+		.write_all(&line)
+}
+
+/// Records and dump an ingest handler accepted, attached to its response
+/// for [`audit_requests`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IngestOutcome {
+	pub records: usize,
+	pub dump_id: Option<String>,
+}
+
+impl IngestOutcome {
+	/// Attach this outcome to `resp`.
+	pub fn attach(self, mut resp: Response) -> Response {
+		resp.extensions_mut().insert(self);
+		resp
+	}
+}
+
+/// Middleware assigning every request an ID, set on the request for
+/// handlers to read with [`request_id`] and echoed in `x-request-id`, and
+/// emitting an `ingest` event for responses carrying an [`IngestOutcome`].
+/// The subject comes from the [`Subject`] set by
+/// [`crate::auth::identify`].
+pub async fn audit_requests(
+	State(state): State<AppState>,
+	mut req: Request,
+	next: Next,
+) -> Response {
+	let request_id = request_id(req.headers());
+	if let Ok(value) = HeaderValue::from_str(&request_id) {
+		req.headers_mut().insert(REQUEST_ID_HEADER, value);
+	}
+	let subject = req.extensions().get::<Subject>().cloned();
+	let endpoint = req.uri().path().to_string();
+
+	let mut resp = next.run(req).await;
+	if let Some(outcome) = resp.extensions_mut().remove::<IngestOutcome>() {
+		let mut event = AuditEvent::new(AuditAction::Ingest, &request_id)
+			.with_endpoint(endpoint)
+			.with_records(outcome.records as u64);
+		if let Some(Subject(subject)) = subject {
+			event = event.with_subject(subject);
+		}
+		if let Some(dump_id) = outcome.dump_id {
+			event = event.with_dump_id(dump_id);
+		}
+		state.audit.emit(event);
+	}
+	if let Ok(value) = HeaderValue::from_str(&request_id) {
+		resp.headers_mut().insert(REQUEST_ID_HEADER, value);
+	}
+	resp
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use axum::{Router, body::Body, http::StatusCode, routing::post};
+	use std::sync::Arc;
+	use tower::ServiceExt;
+
+	async fn ingest_records(audit: Arc<AuditLog>) -> Response {
+		let state = crate::ingest::test_utils::create_test_app_state().with_audit_log(audit);
+		let app = Router::new()
+			.route("/ingest/records", post(crate::ingest::records_upload))
+			.layer(axum::middleware::from_fn_with_state(
+				state.clone(),
+				audit_requests,
+			))
+			.with_state(state);
+
+		let body = r#"[
+			{"field_type": "domain", "raw": "Example.com", "canonical": "example.com"},
+			{"field_type": "ip", "raw": "10.0.0.1", "canonical": "10.0.0.1"}
+		]"#;
+		let mut req = Request::builder()
+			.method("POST")
+			.uri("/ingest/records")
+			.header(REQUEST_ID_HEADER, "req-1")
+			.body(Body::from(body))
+			.unwrap();
+		req.extensions_mut()
+			.insert(Subject("alice@example.com".to_string()));
+		app.oneshot(req).await.unwrap()
+	}
+
+	#[tokio::test]
+	async fn authenticated_ingest_emits_event_with_subject_and_count() {
+		let audit = Arc::new(AuditLog::in_memory());
+		let resp = ingest_records(audit.clone()).await;
+		assert_eq!(resp.status(), StatusCode::OK);
+		assert_eq!(resp.headers()[REQUEST_ID_HEADER], "req-1");
+
+		let events = audit.events();
+		assert_eq!(events.len(), 1);
+		let event = &events[0];
+		assert_eq!(event.action, AuditAction::Ingest);
+		assert_eq!(event.request_id, "req-1");
+		assert_eq!(event.subject.as_deref(), Some("alice@example.com"));
+		assert_eq!(event.endpoint.as_deref(), Some("/ingest/records"));
+		assert_eq!(event.records, Some(2));
+	}
+
+	#[tokio::test]
+	async fn disabled_sink_suppresses_events() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("audit.jsonl");
+
+		let resp = ingest_records(Arc::new(AuditLog::disabled())).await;
+		assert_eq!(resp.status(), StatusCode::OK);
+		assert!(!AuditLog::disabled().is_enabled());
+
+		AuditLog::open(&path).emit(AuditEvent::new(AuditAction::Expire, "r").with_records(3));
+		let written = std::fs::read_to_string(&path).unwrap();
+		let event: AuditEvent = serde_json::from_str(written.trim()).unwrap();
+		assert_eq!(event.records, Some(3));
+		assert_eq!(written.lines().count(), 1);
+	}
+
+	#[test]
+	fn request_id_is_taken_from_header_or_generated() {
+		let mut headers = HeaderMap::new();
+		let generated = request_id(&headers);
+		assert_eq!(generated.len(), 32);
+		assert_ne!(generated, request_id(&headers));
+
+		headers.insert(REQUEST_ID_HEADER, HeaderValue::from_static("abc-123"));
+		assert_eq!(request_id(&headers), "abc-123");
+	}
+}
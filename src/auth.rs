@@ -0,0 +1,94 @@
+use axum::extract::{Request, State};
+use axum::http::{HeaderMap, StatusCode, header::AUTHORIZATION};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use crate::state::AppState;
+use crate::sync::auth::Claims;
+
+/// Authenticated subject (`Claims.sub`) of an ingest request, set as a
+/// request extension by [`identify`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subject(pub String);
+
+/// Extract the bearer token from an `Authorization` header, if present.
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+	let value = headers.get(AUTHORIZATION)?.to_str().ok()?;
+	let (scheme, token) = value.split_once(' ')?;
+	if !scheme.eq_ignore_ascii_case("bearer") {
+		return None;
+	}
+	let token = token.trim();
+	if token.is_empty() { None } else { Some(token) }
+}
+
+/// Authenticate a request against the configured OIDC provider.
+///
+/// Protected routes call this before doing any work. Requests are rejected
+/// with 401 when no provider is configured, when the bearer token is
+/// missing, or when the token fails validation.
+pub async fn authenticate(state: &AppState, headers: &HeaderMap) -> Result<Claims, Response> {
+	let Some(provider) = state.oidc.as_ref() else {
+		return Err((StatusCode::UNAUTHORIZED, "authentication is not configured").into_response());
+	};
+	let Some(token) = bearer_token(headers) else {
+		return Err((StatusCode::UNAUTHORIZED, "missing bearer token").into_response());
+	};
+
+	provider.validate_token(token).await.map_err(|e| {
+		log::warn!("rejecting bearer token: {}", e);
+		(StatusCode::UNAUTHORIZED, "invalid bearer token").into_response()
+	})
+}
+
+/// Middleware identifying the caller of `/ingest/*` requests that carry a
+/// bearer token while OIDC is configured, for quotas and auditing. A token
+/// that fails validation is rejected; requests without one pass through
+/// anonymously.
+pub async fn identify(State(state): State<AppState>, mut req: Request, next: Next) -> Response {
+	if !req.uri().path().starts_with("/ingest/")
+		|| state.oidc.is_none()
+		|| bearer_token(req.headers()).is_none()
+	{
+		return next.run(req).await;
+	}
+	match authenticate(&state, req.headers()).await {
+		Ok(claims) => {
+			req.extensions_mut().insert(Subject(claims.sub));
+			next.run(req).await
+		}
+		Err(resp) => resp,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use axum::http::HeaderValue;
+
+	#[test]
+	fn bearer_token_parses_scheme_case_insensitively() {
+		let mut headers = HeaderMap::new();
+		headers.insert(AUTHORIZATION, HeaderValue::from_static("bearer abc.def"));
+		assert_eq!(bearer_token(&headers), Some("abc.def"));
+
+		headers.insert(
+			AUTHORIZATION,
+			HeaderValue::from_static("Basic dXNlcjpwYXNz"),
+		);
+		assert_eq!(bearer_token(&headers), None);
+
+		headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer "));
+		assert_eq!(bearer_token(&headers), None);
+	}
+
+	#[tokio::test]
+	async fn authenticate_rejects_when_no_provider_configured() {
+		let state = crate::ingest::test_utils::create_test_app_state();
+		let mut headers = HeaderMap::new();
+		headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer token"));
+
+		let err = authenticate(&state, &headers).await.unwrap_err();
+		assert_eq!(err.status(), StatusCode::UNAUTHORIZED);
+	}
+}
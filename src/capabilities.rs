@@ -0,0 +1,113 @@
+//! What this instance supports, for clients deciding how to upload and how
+//! to interpret canonical keys.
+//!
+//! `GET /capabilities` reports the version of each normalizer, the formats
+//! ingest parses and the compression codecs it unwraps, the canonical key
+//! scheme and record schema versions, and which optional features are on.
+
+use std::collections::BTreeMap;
+
+use axum::{Json, extract::State};
+use serde::{Deserialize, Serialize};
+
+use crate::ingest::NormalizedRecord;
+use crate::ingest::format_detection::FormatType;
+use crate::ingest::keys::CANONICAL_KEY_SCHEME_VERSION;
+use crate::lib::normalizers::{NORMALIZER_KINDS, normalizer_version};
+
+/// Body of `GET /capabilities`.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Capabilities {
+	/// Normalizer kind → version of the algorithm it applies.
+	pub normalizers: BTreeMap<String, u32>,
+	/// Formats ingest parses into records.
+	pub formats: Vec<String>,
+	/// Compression formats unwrapped before parsing.
+	pub codecs: Vec<String>,
+	pub canonical_key_scheme: u32,
+	/// Highest `NormalizedRecord.schema_version` accepted.
+	pub record_schema_version: u32,
+	pub features: Features,
+}
+
+/// Optional features and whether they are enabled.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Features {
+	/// A PII policy is applied to raw values.
+	pub pii: bool,
+	/// Changes are exchanged with peers through the sync change log.
+	pub sync: bool,
+	/// Protected endpoints require an OIDC bearer token.
+	pub auth: bool,
+}
+
+impl Capabilities {
+	/// The capabilities of the instance behind `state`.
+	pub fn of(state: &crate::state::AppState) -> Self {
+		Self {
+			normalizers: NORMALIZER_KINDS
+				.iter()
+				.filter_map(|kind| normalizer_version(kind).map(|v| (kind.to_string(), v)))
+				.collect(),
+			formats: format_names(&FormatType::PARSED),
+			codecs: format_names(&FormatType::CODECS),
+			canonical_key_scheme: CANONICAL_KEY_SCHEME_VERSION,
+			record_schema_version: NormalizedRecord::CURRENT_SCHEMA,
+			features: Features {
+				pii: state.pii_engine.load().is_some(),
+				sync: state.settings.sync_enabled,
+				auth: state.oidc.is_some(),
+			},
+		}
+	}
+}
+
+fn format_names(formats: &[FormatType]) -> Vec<String> {
+	formats.iter().map(|f| f.as_str().to_string()).collect()
+}
+
+/// Capabilities endpoint; needs no authentication.
+pub async fn capabilities(State(state): State<crate::state::AppState>) -> Json<Capabilities> {
+	Json(Capabilities::of(&state))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::lib::normalizers::normalize;
+
+	#[tokio::test]
+	async fn capabilities_reflect_compiled_in_normalizers_and_formats() {
+		let state = crate::ingest::test_utils::create_test_app_state();
+		let Json(caps) = capabilities(State(state)).await;
+
+		let samples = [
+			("ip", "192.0.2.1"),
+			("domain", "example.com"),
+			("hash", "d41d8cd98f00b204e9800998ecf8427e"),
+			("email", "user@example.com"),
+			("timestamp", "2024-01-01T00:00:00Z"),
+			("amount", "12.50"),
+			("username", "alice"),
+			("pan", "4111111111111111"),
+		];
+		assert_eq!(caps.normalizers.len(), samples.len());
+		for (kind, raw) in samples {
+			let version = normalize(kind, raw).expect(kind).version();
+			assert_eq!(caps.normalizers[kind], version, "{}", kind);
+		}
+
+		assert_eq!(caps.formats, ["csv", "tsv", "ndjson", "json", "xlsx"]);
+		assert_eq!(caps.codecs, ["gzip", "zip"]);
+		assert_eq!(caps.canonical_key_scheme, CANONICAL_KEY_SCHEME_VERSION);
+		assert_eq!(caps.record_schema_version, NormalizedRecord::CURRENT_SCHEMA);
+		assert_eq!(
+			caps.features,
+			Features {
+				pii: false,
+				sync: false,
+				auth: false,
+			}
+		);
+	}
+}
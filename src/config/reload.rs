@@ -0,0 +1,252 @@
+//! Reloading settings without a restart.
+//!
+//! On SIGHUP the [`ConfigReloader`] loads and validates the configuration
+//! again and hot-applies the reloadable subset: rate limits, the PII policy
+//! engine, the key prefix map, the label allowlist and the log level. These
+//! live behind `ArcSwap`s shared with [`AppState`], so requests in flight
+//! keep the snapshot they started with. A configuration that fails to load
+//! or validate is rejected and the running one stays in effect. Other
+//! settings, such as the bind address or TLS files, are only read at
+//! startup; changing them logs that a restart is required.
+
+use std::sync::Arc;
+
+use arc_swap::{ArcSwap, ArcSwapOption};
+use futures_util::{Stream, StreamExt};
+use tokio::task::JoinHandle;
+
+use crate::audit::AuditLog;
+use crate::config::{Settings, SettingsError};
+use crate::devops::SharedRateLimitLayer;
+use crate::ingest::KeyPrefixMap;
+use crate::persist::labels::LabelAllowlist;
+use crate::pii::pii_policy::PiiPolicyEngine;
+use crate::state::AppState;
+
+type Loader = Box<dyn Fn() -> Result<Settings, SettingsError> + Send + Sync>;
+
+/// What a successful reload changed.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReloadReport {
+	/// Reloadable settings whose new values are now in effect.
+	pub applied: Vec<&'static str>,
+	/// Changed settings that only take effect after a restart.
+	pub restart_required: Vec<&'static str>,
+}
+
+/// Reloads settings and applies the reloadable ones to shared state.
+pub struct ConfigReloader {
+	current: ArcSwap<Settings>,
+	load: Loader,
+	rate_limit: SharedRateLimitLayer,
+	pii_engine: Arc<ArcSwapOption<PiiPolicyEngine>>,
+	key_prefixes: Arc<ArcSwap<KeyPrefixMap>>,
+	label_allowlist: Arc<ArcSwap<LabelAllowlist>>,
+	audit: Arc<AuditLog>,
+}
+
+impl ConfigReloader {
+	/// Reload into `state`'s shared components and `rate_limit`, starting
+	/// from the `running` settings. Settings are loaded with
+	/// [`crate::config::load`].
+	pub fn new(running: Settings, state: &AppState, rate_limit: SharedRateLimitLayer) -> Self {
+		Self {
+			current: ArcSwap::from_pointee(running),
+			load: Box::new(crate::config::load),
+			rate_limit,
+			pii_engine: state.pii_engine.clone(),
+			key_prefixes: state.key_prefixes.clone(),
+			label_allowlist: state.label_allowlist.clone(),
+			audit: state.audit.clone(),
+		}
+	}
+
+	/// Load settings with `load` instead, e.g. [`crate::config::load_with_file`].
+	pub fn with_loader(
+		mut self,
+		load: impl Fn() -> Result<Settings, SettingsError> + Send + Sync + 'static,
+	) -> Self {
+		self.load = Box::new(load);
+		self
+	}
+
+	/// The settings in effect.
+	pub fn current(&self) -> Arc<Settings> {
+		self.current.load_full()
+	}
+
+	/// Load and validate the configuration, then apply what it changes.
+	/// Nothing is applied when loading, validation or building any of the
+	/// reloadable components fails.
+	pub async fn reload(&self) -> Result<ReloadReport, SettingsError> {
+		let new = (self.load)()?;
+		let old = self.current.load_full();
+		let mut report = ReloadReport::default();
+
+		// Build everything fallible before touching shared state.
+		let key_prefixes = if new.key_prefixes != old.key_prefixes {
+			let mut map = KeyPrefixMap::parse(&new.key_prefixes).map_err(SettingsError::Invalid)?;
+			if let Some(cache) = self.key_prefixes.load().cache() {
+				map = map.with_cache(cache.clone());
+			}
+			Some(map)
+		} else {
+			None
+		};
+		let pii_engine = if new.pii_master_key != old.pii_master_key {
+			let engine = PiiPolicyEngine::from_settings(&new, self.audit.clone())
+				.map_err(|e| SettingsError::Invalid(e.to_string()))?;
+			Some(engine.map(Arc::new))
+		} else {
+			None
+		};
+
+		if (new.rate_limit_burst, new.rate_limit_rps) != (old.rate_limit_burst, old.rate_limit_rps)
+		{
+			self.rate_limit
+				.set_limits(new.rate_limit_burst as usize, new.rate_limit_rps)
+				.await;
+			report.applied.push("rate_limit");
+		}
+		if let Some(engine) = pii_engine {
+			self.pii_engine.store(engine);
+			report.applied.push("pii_master_key");
+		}
+		if let Some(map) = key_prefixes {
+			self.key_prefixes.store(Arc::new(map));
+			report.applied.push("key_prefixes");
+		}
+		if (&new.allowed_labels, &new.allowed_edge_types)
+			!= (&old.allowed_labels, &old.allowed_edge_types)
+		{
+			self.label_allowlist.store(Arc::new(new.label_allowlist()));
+			report.applied.push("label_allowlist");
+		}
+		if new.log_level != old.log_level {
+			if let Err(e) = crate::observability::set_log_level(new.log_level) {
+				tracing::warn!(error = %e, "failed to apply the reloaded log level");
+			} else {
+				report.applied.push("log_level");
+			}
+		}
+
+		for (name, changed) in [
+			("host", new.host != old.host),
+			("port", new.port != old.port),
+			("tls_cert", new.tls_cert != old.tls_cert),
+			("tls_key", new.tls_key != old.tls_key),
+		] {
+			if changed {
+				report.restart_required.push(name);
+			}
+		}
+
+		self.current.store(Arc::new(new));
+		Ok(report)
+	}
+
+	/// Reload each time `signals` yields, until it ends, logging the outcome.
+	pub fn spawn_on<S>(self: Arc<Self>, mut signals: S) -> JoinHandle<()>
+	where
+		S: Stream<Item = ()> + Send + Unpin + 'static,
+	{
+		tokio::spawn(async move {
+			while signals.next().await.is_some() {
+				match self.reload().await {
+					Ok(report) => {
+						tracing::info!(applied = ?report.applied, "configuration reloaded");
+						if !report.restart_required.is_empty() {
+							tracing::warn!(
+								settings = ?report.restart_required,
+								"changed settings take effect only after a restart"
+							);
+						}
+					}
+					Err(e) => {
+						tracing::error!(error = %e, "configuration reload rejected; keeping the running configuration");
+					}
+				}
+			}
+		})
+	}
+
+	/// Reload on every SIGHUP.
+	#[cfg(unix)]
+	pub fn spawn_on_sighup(self: Arc<Self>) -> std::io::Result<JoinHandle<()>> {
+		use tokio::signal::unix::{SignalKind, signal};
+		let mut hangup = signal(SignalKind::hangup())?;
+		Ok(self.spawn_on(futures_util::stream::poll_fn(move |cx| {
+			hangup.poll_recv(cx)
+		})))
+	}
+}
+
+impl std::fmt::Debug for ConfigReloader {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("ConfigReloader").finish_non_exhaustive()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A reloader over a fresh state whose settings come from `path`.
+	fn reloader(path: &std::path::Path) -> (ConfigReloader, AppState, SharedRateLimitLayer) {
+		let state = crate::ingest::test_utils::create_test_app_state();
+		let running = Settings::default();
+		let rate_limit =
+			SharedRateLimitLayer::new(running.rate_limit_burst as usize, running.rate_limit_rps);
+		let path = path.to_path_buf();
+		let reloader = ConfigReloader::new(running, &state, rate_limit.clone())
+			.with_loader(move || crate::config::load_with_file(&path));
+		(reloader, state, rate_limit)
+	}
+
+	#[tokio::test]
+	async fn a_signal_applies_the_reloadable_settings() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("heimdall.json");
+		std::fs::write(
+			&path,
+			r#"{"rate_limit_rps": 7, "rate_limit_burst": 3, "key_prefixes": "ip=addr:", "port": 9443}"#,
+		)
+		.unwrap();
+		let (reloader, state, rate_limit) = reloader(&path);
+
+		let (tx, rx) = tokio::sync::mpsc::channel(1);
+		let task = Arc::new(reloader).spawn_on(tokio_stream::wrappers::ReceiverStream::new(rx));
+		tx.send(()).await.unwrap();
+		drop(tx);
+		task.await.unwrap();
+
+		assert_eq!(rate_limit.limits().await, (3, 7));
+		assert_eq!(
+			state.key_prefixes.load().key("ip", "192.0.2.1"),
+			"addr:192.0.2.1"
+		);
+	}
+
+	#[tokio::test]
+	async fn an_invalid_configuration_changes_nothing() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("heimdall.json");
+		std::fs::write(&path, r#"{"rate_limit_rps": 7, "port": 9443}"#).unwrap();
+		let (reloader, _state, rate_limit) = reloader(&path);
+		let report = reloader.reload().await.unwrap();
+		assert_eq!(report.applied, vec!["rate_limit"]);
+		assert_eq!(report.restart_required, vec!["port"]);
+
+		std::fs::write(
+			&path,
+			r#"{"rate_limit_rps": 50, "json_detect_sample_bytes": 0}"#,
+		)
+		.unwrap();
+		assert!(matches!(
+			reloader.reload().await,
+			Err(SettingsError::Invalid(_))
+		));
+		assert_eq!(rate_limit.limits().await.1, 7);
+		assert_eq!(reloader.current().rate_limit_rps, 7);
+	}
+}
@@ -0,0 +1,114 @@
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::net::{TcpListener, TcpSocket, TcpStream};
+use tokio::sync::Semaphore;
+
+/// Bind a TCP listener on `addr` with an explicit accept `backlog`.
+///
+/// `TcpListener::bind` uses a fixed backlog; under bursts of new
+/// connections a larger queue avoids SYNs being dropped by the kernel.
+pub fn bind_with_backlog(addr: SocketAddr, backlog: u32) -> io::Result<TcpListener> {
+	let socket = if addr.is_ipv4() {
+		TcpSocket::new_v4()?
+	} else {
+		TcpSocket::new_v6()?
+	};
+	socket.set_reuseaddr(true)?;
+	socket.bind(addr)?;
+	socket.listen(backlog)
+}
+
+/// Accept connections forever, serving each with `serve` on its own task
+/// while at most `max_connections` are in flight.
+///
+/// A permit is taken before the task is spawned and released when `serve`
+/// finishes. When no permit is available the new connection is closed
+/// immediately instead of queueing, so a connection flood cannot exhaust
+/// memory or file descriptors.
+pub async fn accept_with_limit<F, Fut>(listener: TcpListener, max_connections: usize, serve: F)
+where
+	F: Fn(TcpStream, SocketAddr) -> Fut,
+	Fut: Future<Output = ()> + Send + 'static,
+{
+	let permits = Arc::new(Semaphore::new(max_connections));
+	loop {
+		let (tcp_stream, peer_addr) = match listener.accept().await {
+			Ok(t) => t,
+			Err(e) => {
+				eprintln!("accept error: {}", e);
+				tokio::time::sleep(Duration::from_millis(100)).await;
+				continue;
+			}
+		};
+
+		let Ok(permit) = permits.clone().try_acquire_owned() else {
+			tracing::warn!(
+				peer = %peer_addr,
+				max_connections,
+				"connection limit reached; shedding connection"
+			);
+			drop(tcp_stream);
+			continue;
+		};
+
+		let conn = serve(tcp_stream, peer_addr);
+		tokio::spawn(async move {
+			conn.await;
+			drop(permit);
+		});
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+	/// Read one byte, or `None` once the peer has closed the connection.
+	async fn read_byte(stream: &mut TcpStream) -> Option<u8> {
+		let mut buf = [0u8; 1];
+		let n = tokio::time::timeout(Duration::from_secs(2), stream.read(&mut buf))
+			.await
+			.expect("server neither wrote nor closed")
+			.unwrap_or(0);
+		(n == 1).then_some(buf[0])
+	}
+
+	#[tokio::test]
+	async fn connections_beyond_limit_are_shed() {
+		let listener = bind_with_backlog("127.0.0.1:0".parse().unwrap(), 16).unwrap();
+		let addr = listener.local_addr().unwrap();
+
+		// Greet each connection, then hold it open until the client leaves.
+		tokio::spawn(accept_with_limit(listener, 1, |mut stream, _| async move {
+			let _ = stream.write_all(b"k").await;
+			let mut buf = [0u8; 1];
+			let _ = stream.read(&mut buf).await;
+		}));
+
+		let mut first = TcpStream::connect(addr).await.unwrap();
+		assert_eq!(read_byte(&mut first).await, Some(b'k'));
+
+		let mut second = TcpStream::connect(addr).await.unwrap();
+		assert_eq!(read_byte(&mut second).await, None);
+
+		// Closing the first connection frees its permit.
+		drop(first);
+		let deadline = tokio::time::Instant::now() + Duration::from_secs(2);
+		loop {
+			let mut next = TcpStream::connect(addr).await.unwrap();
+			if read_byte(&mut next).await == Some(b'k') {
+				break;
+			}
+			assert!(
+				tokio::time::Instant::now() < deadline,
+				"permit never released"
+			);
+			tokio::time::sleep(Duration::from_millis(10)).await;
+		}
+	}
+}
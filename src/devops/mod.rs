@@ -1,8 +1,12 @@
+pub mod conn_limit;
 pub mod docker_manager;
 pub mod rate_limiter;
+pub mod route_timeouts;
 
+pub use conn_limit::{accept_with_limit, bind_with_backlog};
 pub use docker_manager::{start_dev_db, stop_dev_db};
 pub use rate_limiter::SharedRateLimitLayer;
+pub use route_timeouts::RouteTimeouts;
 
 #[cfg(feature = "devops-tests")]
 mod tests {
@@ -43,6 +43,14 @@ impl TokenBucket {
 			false
 		}
 	}
+
+	/// Adopt new limits, keeping the tokens already accrued up to the new
+	/// capacity.
+	fn set_limits(&mut self, capacity: usize, refill_per_sec: u32) {
+		self.capacity = capacity as f64;
+		self.refill_per_sec = refill_per_sec as f64;
+		self.tokens = self.tokens.min(self.capacity);
+	}
 }
 
 #[derive(Clone)]
@@ -74,6 +82,18 @@ impl SharedRateLimitLayer {
 			limiter: SharedLimiter::new(burst, rps),
 		}
 	}
+
+	/// Change the burst size and rate of every service built from this
+	/// layer, e.g. on config reload.
+	pub async fn set_limits(&self, burst: usize, rps: u32) {
+		self.limiter.inner.lock().await.set_limits(burst, rps);
+	}
+
+	/// Current `(burst, rps)`.
+	pub async fn limits(&self) -> (usize, u32) {
+		let b = self.limiter.inner.lock().await;
+		(b.capacity as usize, b.refill_per_sec as u32)
+	}
 }
 
 #[derive(Clone)]
@@ -170,4 +190,26 @@ mod tests {
 		let second = svc.call(req2).await.unwrap();
 		assert_eq!(second.status(), StatusCode::TOO_MANY_REQUESTS);
 	}
+
+	#[tokio::test]
+	async fn new_limits_apply_to_existing_services() {
+		let layer = SharedRateLimitLayer::new(1, 0);
+		let svc = service_fn(|_req: Request<Body>| async move {
+			Ok::<_, std::convert::Infallible>(Response::new(Body::from("ok")))
+		});
+		let mut svc = layer.layer(svc);
+		let call = |svc: &mut SharedRateLimitService<_>| {
+			svc.call(Request::builder().body(Body::empty()).unwrap())
+		};
+		assert_eq!(call(&mut svc).await.unwrap().status(), StatusCode::OK);
+		assert_eq!(
+			call(&mut svc).await.unwrap().status(),
+			StatusCode::TOO_MANY_REQUESTS
+		);
+
+		layer.set_limits(5, 1000).await;
+		assert_eq!(layer.limits().await, (5, 1000));
+		tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+		assert_eq!(call(&mut svc).await.unwrap().status(), StatusCode::OK);
+	}
 }
@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use axum::Router;
+use axum::http::StatusCode;
+use tower_http::timeout::{RequestBodyTimeoutLayer, TimeoutLayer};
+
+/// Timeouts of one group of routes.
+///
+/// Bulk uploads legitimately stream for minutes while a query should answer
+/// in seconds, so each route group gets its own limits instead of sharing
+/// one transport-wide timeout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RouteTimeouts {
+	/// Time to produce the response head; 408 once exceeded.
+	pub request: Duration,
+	/// Time between frames of the request body; reading fails once exceeded.
+	pub body: Duration,
+}
+
+impl RouteTimeouts {
+	pub fn from_secs(request: u64, body: u64) -> Self {
+		Self {
+			request: Duration::from_secs(request),
+			body: Duration::from_secs(body),
+		}
+	}
+
+	/// `router` with these timeouts applied to every route it holds so far.
+	pub fn apply<S>(self, router: Router<S>) -> Router<S>
+	where
+		S: Clone + Send + Sync + 'static,
+	{
+		router
+			.layer(TimeoutLayer::with_status_code(
+				StatusCode::REQUEST_TIMEOUT,
+				self.request,
+			))
+			.layer(RequestBodyTimeoutLayer::new(self.body))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use axum::body::Body;
+	use axum::http::Request;
+	use axum::routing::post;
+	use tower::ServiceExt;
+
+	async fn slow() -> &'static str {
+		tokio::time::sleep(Duration::from_millis(200)).await;
+		"done"
+	}
+
+	fn app() -> Router {
+		let bulk = RouteTimeouts {
+			request: Duration::from_secs(5),
+			body: Duration::from_secs(5),
+		};
+		let query = RouteTimeouts {
+			request: Duration::from_millis(50),
+			body: Duration::from_millis(50),
+		};
+		bulk.apply(Router::new().route("/ingest/bulk", post(slow)))
+			.merge(query.apply(Router::new().route("/query/stream", post(slow))))
+	}
+
+	async fn status_of(path: &str) -> StatusCode {
+		let req = Request::post(path).body(Body::empty()).unwrap();
+		app().oneshot(req).await.unwrap().status()
+	}
+
+	#[tokio::test]
+	async fn slow_bulk_upload_fits_its_longer_timeout() {
+		assert_eq!(status_of("/ingest/bulk").await, StatusCode::OK);
+	}
+
+	#[tokio::test]
+	async fn same_delay_times_out_a_query() {
+		assert_eq!(
+			status_of("/query/stream").await,
+			StatusCode::REQUEST_TIMEOUT
+		);
+	}
+}
@@ -0,0 +1,63 @@
+use axum::{
+	Json,
+	extract::State,
+	response::{IntoResponse, Response},
+};
+
+use crate::state::AppState;
+
+/// Report each enrichment provider's circuit breaker state, available
+/// rate-limit tokens and last error.
+///
+/// Unauthenticated, like `/health/db`, so load balancers and dashboards can
+/// poll it.
+pub async fn enrich_status(State(state): State<AppState>) -> Response {
+	let providers = state.enrich_providers.status().await;
+	Json(serde_json::json!({ "providers": providers })).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::enrich::{ProviderConfig, ProviderRegistry, ResilientClientBuilder};
+	use axum::http::StatusCode;
+	use serde_json::Value;
+
+	#[tokio::test]
+	async fn failing_provider_is_reported_open() {
+		// An upstream that always fails.
+		let upstream = axum::Router::new().fallback(|| async { StatusCode::INTERNAL_SERVER_ERROR });
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let addr = listener.local_addr().unwrap();
+		tokio::spawn(async move { axum::serve(listener, upstream).await });
+
+		let registry = ProviderRegistry::new();
+		let client = ResilientClientBuilder::new(ProviderConfig {
+			name: "geoip".to_string(),
+			base_url: format!("http://{}", addr),
+			max_retries: 0,
+			circuit_breaker_threshold: 2,
+			..ProviderConfig::default()
+		})
+		.with_shared_state(&registry, "geoip")
+		.build();
+		for _ in 0..2 {
+			assert!(client.get("/lookup").await.is_err());
+		}
+
+		let state =
+			crate::ingest::test_utils::create_test_app_state().with_enrich_providers(registry);
+		let response = enrich_status(State(state)).await;
+		assert_eq!(response.status(), StatusCode::OK);
+		let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+			.await
+			.unwrap();
+		let json: Value = serde_json::from_slice(&body).unwrap();
+
+		let geoip = &json["providers"]["geoip"];
+		assert_eq!(geoip["state"], "open");
+		assert_eq!(geoip["failure_count"], 2);
+		assert!(geoip["last_error"].as_str().unwrap().contains("500"));
+		assert!(geoip["open_for_secs"].as_f64().is_some());
+	}
+}
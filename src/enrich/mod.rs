@@ -1,5 +1,14 @@
+pub mod http;
+pub mod pipeline;
 pub mod provider_config;
 pub mod resilient_client;
+pub mod result;
 
+pub use pipeline::{
+	EnrichmentPipeline, EnrichmentStep, Entity, FanOutLimits, PipelineReport, Relation, StepOutput,
+};
 pub use provider_config::{ProviderConfig, ProviderCredentials};
-pub use resilient_client::{ResilientClient, ResilientClientBuilder};
+pub use resilient_client::{
+	BreakerState, ClientMetrics, ProviderRegistry, ResilientClient, ResilientClientBuilder,
+};
+pub use result::{ENRICHED_BY, EnrichmentResult};
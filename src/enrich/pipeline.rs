@@ -0,0 +1,659 @@
+//! Chained enrichment: run provider steps over a seed entity, feed what
+//! they discover into later steps and persist the resulting graph.
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use log::warn;
+use serde_json::Value;
+
+use crate::age_client::AgeRepo;
+use crate::lineage::{Dataset, LineageEmitter, LineageEvent};
+use crate::observability::MetricsRegistry;
+use crate::persist::labels::LabelAllowlist;
+
+/// Default number of hops explored from the seed entity.
+pub const DEFAULT_MAX_DEPTH: usize = 3;
+/// Default number of entities kept from one step's output for one entity.
+pub const DEFAULT_MAX_ENTITIES_PER_STEP: usize = 50;
+/// Default number of step lookups made for one seed.
+pub const DEFAULT_MAX_REQUESTS_PER_SEED: usize = 500;
+
+/// Bounds on how far one seed fans out. A DNS answer with hundreds of
+/// addresses, each looked up by GeoIP and ASN steps, would otherwise turn
+/// one seed into thousands of requests and nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FanOutLimits {
+	/// Hops explored from the seed; entities this far out are persisted but
+	/// not enriched.
+	pub max_depth: usize,
+	/// Entities kept from one step's output for one entity, in the order the
+	/// step returned them; 0 keeps all.
+	pub max_entities_per_step: usize,
+	/// Step lookups made for the seed and everything derived from it; 0
+	/// allows any number.
+	pub max_requests_per_seed: usize,
+}
+
+impl Default for FanOutLimits {
+	fn default() -> Self {
+		Self {
+			max_depth: DEFAULT_MAX_DEPTH,
+			max_entities_per_step: DEFAULT_MAX_ENTITIES_PER_STEP,
+			max_requests_per_seed: DEFAULT_MAX_REQUESTS_PER_SEED,
+		}
+	}
+}
+
+/// A graph node produced or consumed by an enrichment step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entity {
+	pub label: String,
+	pub key: String,
+	pub props: Value,
+}
+
+impl Entity {
+	pub fn new(label: impl Into<String>, key: impl Into<String>, props: Value) -> Self {
+		Self {
+			label: label.into(),
+			key: key.into(),
+			props,
+		}
+	}
+}
+
+/// A directed, typed edge between two entities identified by key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relation {
+	pub from_key: String,
+	pub rel_type: String,
+	pub to_key: String,
+	pub props: Value,
+}
+
+/// Entities and edges discovered by one step for one input entity.
+#[derive(Debug, Clone, Default)]
+pub struct StepOutput {
+	pub entities: Vec<Entity>,
+	pub relations: Vec<Relation>,
+}
+
+impl StepOutput {
+	/// Add `entity` and an edge `from -[rel_type]-> entity`.
+	pub fn link(mut self, from: &Entity, rel_type: &str, entity: Entity) -> Self {
+		self.relations.push(Relation {
+			from_key: from.key.clone(),
+			rel_type: rel_type.to_string(),
+			to_key: entity.key.clone(),
+			props: Value::Object(Default::default()),
+		});
+		self.entities.push(entity);
+		self
+	}
+}
+
+/// One provider in the pipeline, e.g. DNS resolution or GeoIP lookup.
+#[async_trait]
+pub trait EnrichmentStep: Send + Sync {
+	/// Name used in logs and error reports.
+	fn name(&self) -> &str;
+	/// Whether this step can enrich `entity`.
+	fn accepts(&self, entity: &Entity) -> bool;
+	/// Look up `entity` and return what was discovered.
+	async fn enrich(&self, entity: &Entity) -> anyhow::Result<StepOutput>;
+}
+
+/// Summary of a pipeline run.
+#[derive(Debug, Default)]
+pub struct PipelineReport {
+	/// Every entity persisted, including the seed.
+	pub entities: Vec<Entity>,
+	/// Every edge persisted.
+	pub relations: Vec<Relation>,
+	/// Step failures as `(step name, entity key, error)`. A failing step
+	/// does not stop the rest of the pipeline.
+	pub errors: Vec<(String, String, String)>,
+	/// Entities dropped from step outputs over `max_entities_per_step`.
+	pub shed_entities: usize,
+	/// Step lookups skipped once `max_requests_per_seed` were made.
+	pub shed_requests: usize,
+	/// Entities left unenriched because they are `max_depth` hops out.
+	pub shed_depth: usize,
+}
+
+/// Ordered list of enrichment steps applied breadth-first from a seed.
+///
+/// Every entity is offered to each step that accepts it. Entities a step
+/// discovers are enriched in turn within the [`FanOutLimits`]; each key is
+/// enriched at most once per run. Whatever the limits shed is counted in
+/// the report and, given metrics, in `heimdall_enrichment_shed_total`.
+pub struct EnrichmentPipeline {
+	repo: Arc<dyn AgeRepo>,
+	steps: Vec<Box<dyn EnrichmentStep>>,
+	limits: FanOutLimits,
+	allowlist: Option<(Arc<LabelAllowlist>, Arc<MetricsRegistry>)>,
+	metrics: Option<Arc<MetricsRegistry>>,
+	lineage: Option<Arc<LineageEmitter>>,
+}
+
+impl EnrichmentPipeline {
+	pub fn new(repo: Arc<dyn AgeRepo>) -> Self {
+		Self {
+			repo,
+			steps: Vec::new(),
+			limits: FanOutLimits::default(),
+			allowlist: None,
+			metrics: None,
+			lineage: None,
+		}
+	}
+
+	/// Append a step; steps run in the order they are added.
+	pub fn with_step(mut self, step: impl EnrichmentStep + 'static) -> Self {
+		self.steps.push(Box::new(step));
+		self
+	}
+
+	/// Maximum number of hops explored from the seed entity.
+	pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+		self.limits.max_depth = max_depth;
+		self
+	}
+
+	/// Replace all fan-out limits, e.g. with `Settings::enrichment_limits`.
+	pub fn with_limits(mut self, limits: FanOutLimits) -> Self {
+		self.limits = limits;
+		self
+	}
+
+	/// Count lookups, failures and shed work in `metrics`.
+	pub fn with_metrics(mut self, metrics: Arc<MetricsRegistry>) -> Self {
+		self.metrics = Some(metrics);
+		self
+	}
+
+	/// Quarantine labels and edge types outside `allowlist`, counting them
+	/// in `metrics`.
+	pub fn with_label_allowlist(
+		mut self,
+		allowlist: Arc<LabelAllowlist>,
+		metrics: Arc<MetricsRegistry>,
+	) -> Self {
+		self.allowlist = Some((allowlist, metrics));
+		self
+	}
+
+	/// Emit an `enrich.<step>` lineage event to `lineage` for every step
+	/// that derives entities, from the enriched entity to them.
+	pub fn with_lineage(mut self, lineage: Arc<LineageEmitter>) -> Self {
+		self.lineage = Some(lineage);
+		self
+	}
+
+	fn label<'a>(&self, label: &'a str) -> &'a str {
+		match &self.allowlist {
+			Some((allowlist, metrics)) => allowlist.node_label(label, metrics),
+			None => label,
+		}
+	}
+
+	fn edge_type<'a>(&self, edge_type: &'a str) -> &'a str {
+		match &self.allowlist {
+			Some((allowlist, metrics)) => allowlist.edge_type(edge_type, metrics),
+			None => edge_type,
+		}
+	}
+
+	fn count_shed(&self, limit: &str, n: usize) {
+		if let (Some(metrics), true) = (&self.metrics, n > 0) {
+			metrics
+				.enrichment_shed_total
+				.with_label_values(&[limit])
+				.inc_by(n as u64);
+		}
+	}
+
+	/// Enrich `seed`, persisting discovered entities and edges via the repo.
+	///
+	/// Returns an error only when persistence fails; step failures are
+	/// collected in the report.
+	pub async fn run(&self, seed: Entity) -> anyhow::Result<PipelineReport> {
+		let mut report = PipelineReport::default();
+		let mut visited: HashSet<String> = HashSet::new();
+		let mut queue: VecDeque<(Entity, usize)> = VecDeque::new();
+		let mut requests = 0usize;
+		let run_id = crate::lineage::new_run_id();
+
+		self.repo
+			.merge_entity(self.label(&seed.label), &seed.key, &seed.props)
+			.await?;
+		visited.insert(seed.key.clone());
+		report.entities.push(seed.clone());
+		queue.push_back((seed, 0));
+
+		while let Some((entity, depth)) = queue.pop_front() {
+			if depth >= self.limits.max_depth {
+				if self.steps.iter().any(|s| s.accepts(&entity)) {
+					report.shed_depth += 1;
+					self.count_shed("depth", 1);
+				}
+				continue;
+			}
+			for step in self.steps.iter().filter(|s| s.accepts(&entity)) {
+				let max_requests = self.limits.max_requests_per_seed;
+				if max_requests > 0 && requests >= max_requests {
+					report.shed_requests += 1;
+					self.count_shed("requests", 1);
+					continue;
+				}
+				requests += 1;
+				if let Some(metrics) = &self.metrics {
+					metrics.enrichment_requests_total.inc();
+				}
+				let mut output = match step.enrich(&entity).await {
+					Ok(o) => o,
+					Err(e) => {
+						if let Some(metrics) = &self.metrics {
+							metrics.enrichment_failures_total.inc();
+						}
+						warn!(
+							"enrichment step {} failed for {}: {}",
+							step.name(),
+							entity.key,
+							e
+						);
+						report.errors.push((
+							step.name().to_string(),
+							entity.key.clone(),
+							e.to_string(),
+						));
+						continue;
+					}
+				};
+
+				let max_entities = self.limits.max_entities_per_step;
+				if max_entities > 0 && output.entities.len() > max_entities {
+					let dropped = output.entities.split_off(max_entities);
+					let kept: HashSet<&str> =
+						output.entities.iter().map(|e| e.key.as_str()).collect();
+					let shed: HashSet<&str> = dropped
+						.iter()
+						.map(|e| e.key.as_str())
+						.filter(|key| !kept.contains(key))
+						.collect();
+					// Edges to shed entities would point at nodes never written.
+					output.relations.retain(|r| {
+						!shed.contains(r.from_key.as_str()) && !shed.contains(r.to_key.as_str())
+					});
+					warn!(
+						"enrichment step {} returned {} entities for {}; shed {} over the limit",
+						step.name(),
+						max_entities + dropped.len(),
+						entity.key,
+						dropped.len()
+					);
+					report.shed_entities += dropped.len();
+					self.count_shed("entities", dropped.len());
+				}
+
+				let derivation = self
+					.lineage
+					.as_ref()
+					.filter(|_| !output.entities.is_empty())
+					.map(|_| {
+						output.entities.iter().fold(
+							LineageEvent::new(format!("enrich.{}", step.name()), &run_id)
+								.with_input(dataset(&entity)),
+							|event, found| event.with_output(dataset(found)),
+						)
+					});
+
+				// Nodes must exist before edges can reference them.
+				for found in output.entities {
+					self.repo
+						.merge_entity(self.label(&found.label), &found.key, &found.props)
+						.await?;
+					if visited.insert(found.key.clone()) {
+						report.entities.push(found.clone());
+						queue.push_back((found, depth + 1));
+					}
+				}
+				for rel in output.relations {
+					self.repo
+						.relate(
+							&rel.from_key,
+							&rel.to_key,
+							self.edge_type(&rel.rel_type),
+							&rel.props,
+						)
+						.await?;
+					report.relations.push(rel);
+				}
+				if let (Some(lineage), Some(event)) = (&self.lineage, derivation) {
+					lineage.emit(event);
+				}
+			}
+		}
+
+		Ok(report)
+	}
+}
+
+/// `entity` as a lineage dataset named by its key.
+fn dataset(entity: &Entity) -> Dataset {
+	Dataset::new(entity.key.clone()).with_facet("label", entity.label.clone())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::ingest::test_utils::RecordingRepo;
+	use serde_json::json;
+
+	/// Resolves every domain to two fixed addresses.
+	struct MockDns;
+
+	#[async_trait]
+	impl EnrichmentStep for MockDns {
+		fn name(&self) -> &str {
+			"dns"
+		}
+
+		fn accepts(&self, entity: &Entity) -> bool {
+			entity.label == "Domain"
+		}
+
+		async fn enrich(&self, entity: &Entity) -> anyhow::Result<StepOutput> {
+			Ok(StepOutput::default()
+				.link(
+					entity,
+					"RESOLVES_TO",
+					Entity::new("IPAddress", "192.0.2.1", json!({})),
+				)
+				.link(
+					entity,
+					"RESOLVES_TO",
+					Entity::new("IPAddress", "192.0.2.2", json!({})),
+				))
+		}
+	}
+
+	/// Locates every address in the same country.
+	struct MockGeoIp;
+
+	#[async_trait]
+	impl EnrichmentStep for MockGeoIp {
+		fn name(&self) -> &str {
+			"geoip"
+		}
+
+		fn accepts(&self, entity: &Entity) -> bool {
+			entity.label == "IPAddress"
+		}
+
+		async fn enrich(&self, entity: &Entity) -> anyhow::Result<StepOutput> {
+			let geo = Entity::new("GeoIP", "geo:US", json!({"country": "US"}));
+			Ok(StepOutput::default().link(entity, "LOCATED_IN", geo))
+		}
+	}
+
+	fn seed() -> Entity {
+		Entity::new("Domain", "example.com", json!({}))
+	}
+
+	fn edge(from: &str, rel: &str, to: &str) -> (String, String, String) {
+		(from.to_string(), rel.to_string(), to.to_string())
+	}
+
+	#[tokio::test]
+	async fn builds_domain_ip_geoip_chain() {
+		let repo = Arc::new(RecordingRepo::default());
+		let pipeline = EnrichmentPipeline::new(repo.clone())
+			.with_step(MockDns)
+			.with_step(MockGeoIp);
+
+		let report = pipeline.run(seed()).await.unwrap();
+
+		let keys: Vec<&str> = report.entities.iter().map(|e| e.key.as_str()).collect();
+		assert_eq!(keys, ["example.com", "192.0.2.1", "192.0.2.2", "geo:US"]);
+		assert!(report.errors.is_empty());
+		assert_eq!(
+			*repo.relations.lock().unwrap(),
+			vec![
+				edge("example.com", "RESOLVES_TO", "192.0.2.1"),
+				edge("example.com", "RESOLVES_TO", "192.0.2.2"),
+				edge("192.0.2.1", "LOCATED_IN", "geo:US"),
+				edge("192.0.2.2", "LOCATED_IN", "geo:US"),
+			]
+		);
+		assert!(
+			repo.nodes()
+				.contains_key(&("GeoIP".to_string(), "geo:US".to_string()))
+		);
+	}
+
+	#[tokio::test]
+	async fn quarantines_labels_and_edge_types_outside_the_allowlist() {
+		use crate::persist::labels::{QUARANTINE_EDGE_TYPE, QUARANTINE_LABEL};
+
+		let repo = Arc::new(RecordingRepo::default());
+		let metrics = Arc::new(MetricsRegistry::new());
+		let allowlist = LabelAllowlist::new(["Domain", "IPAddress"], ["RESOLVES_TO"]);
+		let pipeline = EnrichmentPipeline::new(repo.clone())
+			.with_step(MockDns)
+			.with_step(MockGeoIp)
+			.with_label_allowlist(Arc::new(allowlist), metrics.clone());
+
+		pipeline.run(seed()).await.unwrap();
+
+		let edges = repo.relations.lock().unwrap();
+		assert_eq!(edges[0], edge("example.com", "RESOLVES_TO", "192.0.2.1"));
+		assert_eq!(edges[2], edge("192.0.2.1", QUARANTINE_EDGE_TYPE, "geo:US"));
+		let nodes = repo.nodes();
+		assert!(nodes.contains_key(&(QUARANTINE_LABEL.to_string(), "geo:US".to_string())));
+		assert!(!repo.labels().iter().any(|label| label == "GeoIP"));
+		assert!(metrics.ingest_label_quarantined_total.get() > 0);
+	}
+
+	#[tokio::test]
+	async fn enriching_an_ip_links_its_geoip_enrichment() {
+		use crate::enrich::result::{ENRICHED_BY, EnrichmentResult};
+
+		struct GeoIpLookup;
+
+		#[async_trait]
+		impl EnrichmentStep for GeoIpLookup {
+			fn name(&self) -> &str {
+				"geoip"
+			}
+
+			fn accepts(&self, entity: &Entity) -> bool {
+				entity.label == "IPAddress"
+			}
+
+			async fn enrich(&self, entity: &Entity) -> anyhow::Result<StepOutput> {
+				let result = EnrichmentResult::new(
+					"GeoIPEnrichment",
+					"geoip",
+					entity,
+					json!({"country": "US"}),
+				);
+				Ok(StepOutput::default().enriched(result))
+			}
+		}
+
+		let repo = Arc::new(RecordingRepo::default());
+		let lineage = Arc::new(LineageEmitter::in_memory());
+		let pipeline = EnrichmentPipeline::new(repo.clone())
+			.with_step(GeoIpLookup)
+			.with_lineage(lineage.clone());
+		let ip = Entity::new("IPAddress", "8.8.8.8", json!({}));
+
+		let report = pipeline.run(ip).await.unwrap();
+
+		let key = "GeoIPEnrichment:geoip:8.8.8.8";
+		assert!(
+			repo.nodes()
+				.contains_key(&("GeoIPEnrichment".to_string(), key.to_string()))
+		);
+		assert_eq!(
+			*repo.relations.lock().unwrap(),
+			vec![edge("8.8.8.8", ENRICHED_BY, key)]
+		);
+		let geo = &report.entities[1];
+		assert_eq!(geo.props["enrichment_source"], "geoip");
+		assert!(geo.props["enriched_at"].is_string());
+
+		// The derivation is emitted from the IP to its enrichment node.
+		let events = lineage.events();
+		assert_eq!(events.len(), 1);
+		assert_eq!(events[0].job.name, "enrich.geoip");
+		assert_eq!(events[0].inputs[0].name, "8.8.8.8");
+		assert_eq!(events[0].inputs[0].facets["label"], "IPAddress");
+		let outputs: Vec<&str> = events[0].outputs.iter().map(|o| o.name.as_str()).collect();
+		assert_eq!(outputs, [key]);
+		assert_eq!(events[0].outputs[0].facets["label"], "GeoIPEnrichment");
+	}
+
+	#[tokio::test]
+	async fn stops_at_max_depth() {
+		let repo = Arc::new(RecordingRepo::default());
+		let pipeline = EnrichmentPipeline::new(repo.clone())
+			.with_step(MockDns)
+			.with_step(MockGeoIp)
+			.with_max_depth(1);
+
+		let report = pipeline.run(seed()).await.unwrap();
+
+		assert_eq!(report.entities.len(), 3);
+		assert!(report.relations.iter().all(|r| r.rel_type == "RESOLVES_TO"));
+	}
+
+	/// Resolves every domain to 500 addresses.
+	struct WideDns;
+
+	#[async_trait]
+	impl EnrichmentStep for WideDns {
+		fn name(&self) -> &str {
+			"dns"
+		}
+
+		fn accepts(&self, entity: &Entity) -> bool {
+			entity.label == "Domain"
+		}
+
+		async fn enrich(&self, entity: &Entity) -> anyhow::Result<StepOutput> {
+			Ok((0..500).fold(StepOutput::default(), |out, i| {
+				let ip = format!("10.0.{}.{}", i / 256, i % 256);
+				out.link(
+					entity,
+					"RESOLVES_TO",
+					Entity::new("IPAddress", ip, json!({})),
+				)
+			}))
+		}
+	}
+
+	/// Counts the addresses it is asked to locate.
+	#[derive(Clone, Default)]
+	struct CountingGeoIp(Arc<std::sync::atomic::AtomicUsize>);
+
+	#[async_trait]
+	impl EnrichmentStep for CountingGeoIp {
+		fn name(&self) -> &str {
+			"geoip"
+		}
+
+		fn accepts(&self, entity: &Entity) -> bool {
+			entity.label == "IPAddress"
+		}
+
+		async fn enrich(&self, _entity: &Entity) -> anyhow::Result<StepOutput> {
+			self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+			Ok(StepOutput::default())
+		}
+	}
+
+	#[tokio::test]
+	async fn breadth_cap_sheds_the_overflow() {
+		let repo = Arc::new(RecordingRepo::default());
+		let metrics = Arc::new(MetricsRegistry::new());
+		let geoip = CountingGeoIp::default();
+		let pipeline = EnrichmentPipeline::new(repo.clone())
+			.with_step(WideDns)
+			.with_step(geoip.clone())
+			.with_limits(FanOutLimits {
+				max_entities_per_step: 10,
+				..FanOutLimits::default()
+			})
+			.with_metrics(metrics.clone());
+
+		let report = pipeline.run(seed()).await.unwrap();
+
+		assert_eq!(geoip.0.load(std::sync::atomic::Ordering::SeqCst), 10);
+		assert_eq!(report.entities.len(), 11);
+		assert_eq!(repo.relations.lock().unwrap().len(), 10);
+		assert_eq!(report.shed_entities, 490);
+		let shed = &metrics.enrichment_shed_total;
+		assert_eq!(shed.with_label_values(&["entities"]).get(), 490);
+		assert_eq!(metrics.enrichment_requests_total.get(), 11);
+	}
+
+	#[tokio::test]
+	async fn request_cap_bounds_lookups_per_seed() {
+		let repo = Arc::new(RecordingRepo::default());
+		let metrics = Arc::new(MetricsRegistry::new());
+		let geoip = CountingGeoIp::default();
+		let pipeline = EnrichmentPipeline::new(repo.clone())
+			.with_step(WideDns)
+			.with_step(geoip.clone())
+			.with_limits(FanOutLimits {
+				max_entities_per_step: 0,
+				max_requests_per_seed: 25,
+				..FanOutLimits::default()
+			})
+			.with_metrics(metrics.clone());
+
+		let report = pipeline.run(seed()).await.unwrap();
+
+		// The DNS lookup is the first of the 25.
+		assert_eq!(geoip.0.load(std::sync::atomic::Ordering::SeqCst), 24);
+		assert_eq!(report.entities.len(), 501);
+		assert_eq!(report.shed_requests, 476);
+		let shed = &metrics.enrichment_shed_total;
+		assert_eq!(shed.with_label_values(&["requests"]).get(), 476);
+	}
+
+	#[tokio::test]
+	async fn step_failure_is_reported_and_run_continues() {
+		struct Failing;
+
+		#[async_trait]
+		impl EnrichmentStep for Failing {
+			fn name(&self) -> &str {
+				"failing"
+			}
+
+			fn accepts(&self, _entity: &Entity) -> bool {
+				true
+			}
+
+			async fn enrich(&self, _entity: &Entity) -> anyhow::Result<StepOutput> {
+				anyhow::bail!("provider unavailable")
+			}
+		}
+
+		let repo = Arc::new(RecordingRepo::default());
+		let pipeline = EnrichmentPipeline::new(repo.clone())
+			.with_step(Failing)
+			.with_step(MockDns);
+
+		let report = pipeline.run(seed()).await.unwrap();
+
+		assert_eq!(report.relations.len(), 2);
+		assert_eq!(report.errors[0].0, "failing");
+		assert_eq!(report.errors[0].1, "example.com");
+	}
+}
@@ -1,3 +1,4 @@
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
@@ -8,6 +9,7 @@ use hyper_util::client::legacy::Client;
 use hyper_util::rt::TokioExecutor;
 use log::{debug, warn};
 use rand::Rng;
+use serde::Serialize;
 use thiserror::Error;
 use tokio::sync::Mutex;
 use tokio::time::sleep;
@@ -51,29 +53,65 @@ enum CircuitState {
 }
 
 /// Circuit breaker implementation.
+///
+/// While half-open, at most `max_probes` requests are in flight at once;
+/// others are rejected until a probe resolves. The circuit closes after
+/// `success_threshold` successful probes and re-opens on any probe failure.
+/// A probe that is abandoned instead (rate limited or dropped) frees its
+/// slot through [`ProbeGuard`].
 struct CircuitBreaker {
 	state: CircuitState,
 	failure_count: u32,
+	/// The most recent failure, already scrubbed of credentials.
+	last_error: Option<String>,
 	threshold: u32,
 	timeout: Duration,
+	max_probes: u32,
+	success_threshold: u32,
+	probes_in_flight: u32,
+	probe_successes: u32,
+	/// Bumped on every transition to half-open, so a probe admitted in an
+	/// earlier half-open period cannot free a slot of the current one.
+	half_open_generation: u64,
 }
 
 impl CircuitBreaker {
 	fn new(threshold: u32, timeout: Duration) -> Self {
+		Self::with_half_open(threshold, timeout, 1, 1)
+	}
+
+	fn with_half_open(
+		threshold: u32,
+		timeout: Duration,
+		max_probes: u32,
+		success_threshold: u32,
+	) -> Self {
 		Self {
 			state: CircuitState::Closed,
 			failure_count: 0,
+			last_error: None,
 			threshold,
 			timeout,
+			max_probes: max_probes.max(1),
+			success_threshold: success_threshold.max(1),
+			probes_in_flight: 0,
+			probe_successes: 0,
+			half_open_generation: 0,
 		}
 	}
 
 	fn record_success(&mut self) {
 		match self.state {
 			CircuitState::HalfOpen => {
-				debug!("Circuit breaker transitioning to Closed after success");
-				self.state = CircuitState::Closed;
-				self.failure_count = 0;
+				self.probes_in_flight = self.probes_in_flight.saturating_sub(1);
+				self.probe_successes += 1;
+				if self.probe_successes >= self.success_threshold {
+					debug!("Circuit breaker transitioning to Closed after success");
+					self.state = CircuitState::Closed;
+					self.failure_count = 0;
+					self.probes_in_flight = 0;
+					self.probe_successes = 0;
+				}
 			}
 			CircuitState::Closed => {
 				self.failure_count = 0;
@@ -102,20 +140,34 @@ impl CircuitBreaker {
 				self.state = CircuitState::Open {
 					opened_at: Instant::now(),
 				};
+				self.probes_in_flight = 0;
+				self.probe_successes = 0;
 			}
 			CircuitState::Open { .. } => {}
 		}
 	}
 
+	/// Record a failed request and keep `error` for status reports.
+	fn record_error(&mut self, error: &ResilientClientError) {
+		self.last_error = Some(error.to_string());
+		self.record_failure();
+	}
+
+	/// Whether a request may proceed. In half-open state an admitted
+	/// request is a probe and must be resolved with `record_success` or
+	/// `record_failure`.
 	fn can_attempt(&mut self) -> bool {
 		match self.state {
 			CircuitState::Closed => true,
-			CircuitState::HalfOpen => true,
+			CircuitState::HalfOpen => self.admit_probe(),
 			CircuitState::Open { opened_at } => {
 				if Instant::now().duration_since(opened_at) >= self.timeout {
 					debug!("Circuit breaker transitioning to HalfOpen after timeout");
 					self.state = CircuitState::HalfOpen;
-					true
+					self.half_open_generation += 1;
+					self.probes_in_flight = 0;
+					self.probe_successes = 0;
+					self.admit_probe()
 				} else {
 					false
 				}
@@ -123,12 +175,97 @@ impl CircuitBreaker {
 		}
 	}
 
+	fn admit_probe(&mut self) -> bool {
+		if self.probes_in_flight < self.max_probes {
+			self.probes_in_flight += 1;
+			true
+		} else {
+			false
+		}
+	}
+
+	/// Whether `can_attempt` would reject a request now. Changes nothing,
+	/// so callers can check before spending anything on the request.
+	fn rejects(&self) -> bool {
+		match self.state {
+			CircuitState::Closed => false,
+			CircuitState::HalfOpen => self.probes_in_flight >= self.max_probes,
+			CircuitState::Open { opened_at } => opened_at.elapsed() < self.timeout,
+		}
+	}
+
+	/// The current half-open period, while half-open.
+	fn probe_generation(&self) -> Option<u64> {
+		self.is_half_open().then_some(self.half_open_generation)
+	}
+
+	/// Free the slot of a probe admitted in half-open period `generation`
+	/// that ended without an outcome.
+	fn release_probe(&mut self, generation: u64) {
+		if self.probe_generation() == Some(generation) {
+			self.probes_in_flight = self.probes_in_flight.saturating_sub(1);
+		}
+	}
+
 	fn is_open(&self) -> bool {
 		matches!(self.state, CircuitState::Open { .. })
 	}
+
+	fn is_half_open(&self) -> bool {
+		self.state == CircuitState::HalfOpen
+	}
+
+	/// The state as reported to operators, with how long it has been open.
+	fn status(&self) -> (BreakerState, Option<Duration>) {
+		match self.state {
+			CircuitState::Closed => (BreakerState::Closed, None),
+			CircuitState::HalfOpen => (BreakerState::HalfOpen, None),
+			CircuitState::Open { opened_at } => (BreakerState::Open, Some(opened_at.elapsed())),
+		}
+	}
+}
+
+/// A request admitted by the circuit breaker. When admitted as a half-open
+/// probe, the probe slot is freed on drop unless the outcome was recorded
+/// first, so a request abandoned mid-flight cannot hold the slot forever.
+struct ProbeGuard {
+	breaker: Arc<Mutex<CircuitBreaker>>,
+	generation: Option<u64>,
+}
+
+impl ProbeGuard {
+	fn is_probe(&self) -> bool {
+		self.generation.is_some()
+	}
+
+	/// Mark the outcome as recorded; `record_success` and `record_error`
+	/// resolve the probe themselves.
+	fn resolve(&mut self) {
+		self.generation = None;
+	}
+}
+
+impl Drop for ProbeGuard {
+	fn drop(&mut self) {
+		let Some(generation) = self.generation.take() else {
+			return;
+		};
+		if let Ok(mut cb) = self.breaker.try_lock() {
+			cb.release_probe(generation);
+		} else if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+			let breaker = self.breaker.clone();
+			runtime.spawn(async move {
+				breaker.lock().await.release_probe(generation);
+			});
+		}
+	}
 }
 
 /// Token bucket rate limiter.
+///
+/// A `refill_per_sec` of 0 disables rate limiting: every acquire succeeds.
+/// (A bucket that never refills would otherwise reject all requests for
+/// the life of the client once the initial burst was spent.)
 struct TokenBucket {
 	capacity: f64,
 	tokens: f64,
@@ -147,7 +284,24 @@ impl TokenBucket {
 		}
 	}
 
+	fn is_unlimited(&self) -> bool {
+		self.refill_per_sec <= 0.0
+	}
+
+	/// Tokens available now, counting the refill since the last acquire.
+	fn available(&self) -> f64 {
+		if self.is_unlimited() {
+			return self.capacity;
+		}
+		let elapsed = self.last_refill.elapsed().as_secs_f64();
+		(self.tokens + elapsed * self.refill_per_sec).min(self.capacity)
+	}
+
 	fn try_acquire(&mut self) -> bool {
+		if self.is_unlimited() {
+			return true;
+		}
+
 		let now = Instant::now();
 		let elapsed = now.duration_since(self.last_refill).as_secs_f64();
 		self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
@@ -173,7 +327,18 @@ pub struct ResilientClient {
 impl ResilientClient {
 	/// Execute a GET request with resilience features.
 	pub async fn get(&self, path: &str) -> Result<Bytes, ResilientClientError> {
-		self.execute_with_retry(Method::GET, path, None).await
+		self.get_with_query(path, &[]).await
+	}
+
+	/// Execute a GET request with percent-encoded query parameters appended
+	/// to `path`. Parameters are merged with any query already in `path`.
+	pub async fn get_with_query(
+		&self,
+		path: &str,
+		params: &[(&str, &str)],
+	) -> Result<Bytes, ResilientClientError> {
+		let path = append_query(path, params);
+		self.execute_with_retry(Method::GET, &path, None).await
 	}
 
 	/// Execute a POST request with resilience features.
@@ -194,14 +359,12 @@ impl ResilientClient {
 
 		loop {
 			// Check circuit breaker
-			{
-				let mut cb = self.circuit_breaker.lock().await;
-				if !cb.can_attempt() {
-					return Err(ResilientClientError::CircuitBreakerOpen);
-				}
+			if self.circuit_breaker.lock().await.rejects() {
+				return Err(ResilientClientError::CircuitBreakerOpen);
 			}
 
-			// Check rate limiter
+			// Check rate limiter before taking a probe slot, so a rate
+			// limited request never holds one.
 			{
 				let mut rl = self.rate_limiter.lock().await;
 				if !rl.try_acquire() {
@@ -209,25 +372,40 @@ impl ResilientClient {
 				}
 			}
 
+			let mut admitted = {
+				let mut cb = self.circuit_breaker.lock().await;
+				if !cb.can_attempt() {
+					return Err(ResilientClientError::CircuitBreakerOpen);
+				}
+				ProbeGuard {
+					breaker: self.circuit_breaker.clone(),
+					generation: cb.probe_generation(),
+				}
+			};
+
 			// Execute the request
 			match self.execute_once(&method, path, body.clone()).await {
 				Ok(response) => {
 					// Success: update circuit breaker
 					let mut cb = self.circuit_breaker.lock().await;
+					admitted.resolve();
 					cb.record_success();
 					return Ok(response);
 				}
 				Err(e) => {
 					attempts += 1;
 
-					// Determine if we should retry
-					let should_retry = attempts <= self.config.max_retries
+					// Determine if we should retry. A failed half-open probe
+					// re-opens the circuit immediately instead of retrying.
+					let should_retry = !admitted.is_probe()
+						&& attempts <= self.config.max_retries
 						&& self.is_retryable_error(&e);
 
 					if !should_retry {
 						// Record failure in circuit breaker
 						let mut cb = self.circuit_breaker.lock().await;
-						cb.record_failure();
+						admitted.resolve();
+						cb.record_error(&e);
 						return Err(e);
 					}
 
@@ -249,7 +427,7 @@ impl ResilientClient {
 					// Record failure if this was the last retry
 					if attempts >= self.config.max_retries {
 						let mut cb = self.circuit_breaker.lock().await;
-						cb.record_failure();
+						cb.record_error(&e);
 						return Err(ResilientClientError::MaxRetriesExceeded);
 					}
 				}
@@ -265,7 +443,7 @@ impl ResilientClient {
 		body: Option<String>,
 	) -> Result<Bytes, ResilientClientError> {
 		// Build the full URL
-		let url = format!("{}{}", self.config.base_url.trim_end_matches('/'), path);
+		let url = join_url(&self.config.base_url, path);
 		let uri: Uri = url.parse()?;
 
 		// Build the request
@@ -291,10 +469,10 @@ impl ResilientClient {
 		let request = if let Some(b) = body {
 			req.header("Content-Type", "application/json")
 				.body(Full::new(Bytes::from(b)))
-				.map_err(|e| ResilientClientError::InvalidRequest(e.to_string()))?
+				.map_err(|e| ResilientClientError::InvalidRequest(self.scrub(e)))?
 		} else {
 			req.body(Full::new(Bytes::new()))
-				.map_err(|e| ResilientClientError::InvalidRequest(e.to_string()))?
+				.map_err(|e| ResilientClientError::InvalidRequest(self.scrub(e)))?
 		};
 
 		// Execute with timeout
@@ -305,7 +483,7 @@ impl ResilientClient {
 
 		let response = match timeout_future.await {
 			Ok(Ok(resp)) => resp,
-			Ok(Err(e)) => return Err(ResilientClientError::HttpError(e.to_string())),
+			Ok(Err(e)) => return Err(ResilientClientError::HttpError(self.scrub(e))),
 			Err(_) => return Err(ResilientClientError::Timeout),
 		};
 
@@ -320,12 +498,17 @@ impl ResilientClient {
 			.into_body()
 			.collect()
 			.await
-			.map_err(|e| ResilientClientError::HttpError(e.to_string()))?
+			.map_err(|e| ResilientClientError::HttpError(self.scrub(e)))?
 			.to_bytes();
 
 		Ok(body_bytes)
 	}
 
+	/// Render `err` for an error message with credential secrets removed.
+	fn scrub(&self, err: impl std::fmt::Display) -> String {
+		self.config.credentials.scrub(&err.to_string())
+	}
+
 	/// Check if an error is retryable.
 	fn is_retryable_error(&self, error: &ResilientClientError) -> bool {
 		match error {
@@ -343,34 +526,155 @@ impl ResilientClient {
 
 	/// Get metrics about the client state.
 	pub async fn get_metrics(&self) -> ClientMetrics {
-		let cb = self.circuit_breaker.lock().await;
-		let rl = self.rate_limiter.lock().await;
-
-		ClientMetrics {
-			circuit_breaker_open: cb.is_open(),
-			failure_count: cb.failure_count,
-			available_tokens: rl.tokens as u32,
-		}
+		client_metrics(&self.rate_limiter, &self.circuit_breaker).await
 	}
 }
 
+/// Circuit breaker state as reported by [`ClientMetrics`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BreakerState {
+	Closed,
+	Open,
+	HalfOpen,
+}
+
 /// Metrics for observability.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ClientMetrics {
+	pub state: BreakerState,
 	pub circuit_breaker_open: bool,
 	pub failure_count: u32,
 	pub available_tokens: u32,
+	/// The most recent failed request's error.
+	pub last_error: Option<String>,
+	/// Seconds since the breaker opened, while it is open.
+	pub open_for_secs: Option<f64>,
+}
+
+async fn client_metrics(
+	rate_limiter: &Mutex<TokenBucket>,
+	circuit_breaker: &Mutex<CircuitBreaker>,
+) -> ClientMetrics {
+	let cb = circuit_breaker.lock().await;
+	let rl = rate_limiter.lock().await;
+	let (state, open_for) = cb.status();
+
+	ClientMetrics {
+		state,
+		circuit_breaker_open: cb.is_open(),
+		failure_count: cb.failure_count,
+		available_tokens: rl.available() as u32,
+		last_error: cb.last_error.clone(),
+		open_for_secs: open_for.map(|d| d.as_secs_f64()),
+	}
+}
+
+/// Rate-limit and circuit-breaker state shared by every client of one
+/// provider.
+#[derive(Clone)]
+struct SharedState {
+	rate_limiter: Arc<Mutex<TokenBucket>>,
+	circuit_breaker: Arc<Mutex<CircuitBreaker>>,
+}
+
+impl SharedState {
+	fn new(config: &ProviderConfig) -> Self {
+		Self {
+			rate_limiter: Arc::new(Mutex::new(TokenBucket::new(
+				config.rate_limit_burst,
+				config.rate_limit_rps,
+			))),
+			circuit_breaker: Arc::new(Mutex::new(CircuitBreaker::with_half_open(
+				config.circuit_breaker_threshold,
+				config.circuit_breaker_timeout(),
+				config.circuit_breaker_half_open_max_probes,
+				config.circuit_breaker_success_threshold,
+			))),
+		}
+	}
+}
+
+/// Registry of per-provider rate limiters and circuit breakers, keyed by
+/// provider name.
+///
+/// Clients built against the same registry and provider name share one
+/// token bucket and one breaker, so several clients for the same upstream
+/// (e.g. sharded by path) draw on a single budget and trip together. The
+/// state is created from the configuration of the first client registered
+/// under a name; later clients reuse it as-is. Cloning the registry shares
+/// the underlying map.
+#[derive(Clone, Default)]
+pub struct ProviderRegistry {
+	providers: Arc<std::sync::Mutex<HashMap<String, SharedState>>>,
+}
+
+impl ProviderRegistry {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Names of the providers with registered state.
+	pub fn providers(&self) -> Vec<String> {
+		let mut names: Vec<String> = self.providers.lock().unwrap().keys().cloned().collect();
+		names.sort();
+		names
+	}
+
+	/// Breaker and rate-limiter state of every registered provider, by
+	/// name.
+	pub async fn status(&self) -> BTreeMap<String, ClientMetrics> {
+		let providers: Vec<(String, SharedState)> = self
+			.providers
+			.lock()
+			.unwrap()
+			.iter()
+			.map(|(name, state)| (name.clone(), state.clone()))
+			.collect();
+		let mut status = BTreeMap::new();
+		for (name, state) in providers {
+			let metrics = client_metrics(&state.rate_limiter, &state.circuit_breaker).await;
+			status.insert(name, metrics);
+		}
+		status
+	}
+
+	/// State for `name`, created from `config` on first use.
+	fn state_for(&self, name: &str, config: &ProviderConfig) -> SharedState {
+		self.providers
+			.lock()
+			.unwrap()
+			.entry(name.to_string())
+			.or_insert_with(|| SharedState::new(config))
+			.clone()
+	}
 }
 
 /// Builder for ResilientClient.
 pub struct ResilientClientBuilder {
 	config: ProviderConfig,
+	shared: Option<(ProviderRegistry, String)>,
 }
 
 impl ResilientClientBuilder {
 	/// Create a new builder with the given provider configuration.
 	pub fn new(config: ProviderConfig) -> Self {
-		Self { config }
+		Self {
+			config,
+			shared: None,
+		}
+	}
+
+	/// Share rate-limit and circuit-breaker state with every other client
+	/// built for `provider` from `registry`. Without this the client owns
+	/// its own state.
+	pub fn with_shared_state(
+		mut self,
+		registry: &ProviderRegistry,
+		provider: impl Into<String>,
+	) -> Self {
+		self.shared = Some((registry.clone(), provider.into()));
+		self
 	}
 
 	/// Build the ResilientClient.
@@ -378,15 +682,13 @@ impl ResilientClientBuilder {
 		let client = Client::builder(TokioExecutor::new())
 			.build_http();
 
-		let rate_limiter = Arc::new(Mutex::new(TokenBucket::new(
-			self.config.rate_limit_burst,
-			self.config.rate_limit_rps,
-		)));
-
-		let circuit_breaker = Arc::new(Mutex::new(CircuitBreaker::new(
-			self.config.circuit_breaker_threshold,
-			self.config.circuit_breaker_timeout(),
-		)));
+		let SharedState {
+			rate_limiter,
+			circuit_breaker,
+		} = match &self.shared {
+			Some((registry, provider)) => registry.state_for(provider, &self.config),
+			None => SharedState::new(&self.config),
+		};
 
 		ResilientClient {
 			config: self.config,
@@ -427,11 +729,83 @@ fn base64_encode(input: &str) -> String {
 	result
 }
 
+/// Percent-encode `s` for use in a query string, leaving only RFC 3986
+/// unreserved characters as-is.
+fn percent_encode(s: &str) -> String {
+	let mut out = String::with_capacity(s.len());
+	for b in s.bytes() {
+		if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') {
+			out.push(b as char);
+		} else {
+			out.push_str(&format!("%{:02X}", b));
+		}
+	}
+	out
+}
+
+/// Append encoded `params` to `path`, extending an existing query rather
+/// than starting a second one.
+fn append_query(path: &str, params: &[(&str, &str)]) -> String {
+	if params.is_empty() {
+		return path.to_string();
+	}
+	let encoded: Vec<String> = params
+		.iter()
+		.map(|(k, v)| format!("{}={}", percent_encode(k), percent_encode(v)))
+		.collect();
+	let sep = match path.find('?') {
+		None => "?",
+		Some(_) if path.ends_with('?') || path.ends_with('&') => "",
+		Some(_) => "&",
+	};
+	format!("{}{}{}", path, sep, encoded.join("&"))
+}
+
+/// Join `base` and `path` into one URL. A query on either side is kept and
+/// the two are merged into a single query string.
+fn join_url(base: &str, path: &str) -> String {
+	let (base, base_query) = base.split_once('?').unwrap_or((base, ""));
+	let (path, path_query) = path.split_once('?').unwrap_or((path, ""));
+	let mut url = format!("{}{}", base.trim_end_matches('/'), path);
+	let query: Vec<&str> = [base_query, path_query]
+		.into_iter()
+		.flat_map(|q| q.split('&'))
+		.filter(|p| !p.is_empty())
+		.collect();
+	if !query.is_empty() {
+		url.push('?');
+		url.push_str(&query.join("&"));
+	}
+	url
+}
+
 #[cfg(test)]
 #[cfg(feature = "unit-tests")]
 mod tests {
 	use super::*;
 
+	#[test]
+	fn test_query_params_are_percent_encoded() {
+		let path = append_query("/lookup", &[("q", "a b&c=d"), ("tag", "é/+")]);
+		assert_eq!(path, "/lookup?q=a%20b%26c%3Dd&tag=%C3%A9%2F%2B");
+		assert_eq!(append_query("/lookup", &[]), "/lookup");
+	}
+
+	#[test]
+	fn test_existing_query_is_merged() {
+		let path = append_query("/lookup?type=A", &[("name", "example.com")]);
+		assert_eq!(path, "/lookup?type=A&name=example.com");
+		assert_eq!(append_query("/lookup?", &[("a", "1")]), "/lookup?a=1");
+		assert_eq!(
+			join_url("https://api.example.com/v1/?key=k", &path),
+			"https://api.example.com/v1/lookup?key=k&type=A&name=example.com"
+		);
+		assert_eq!(
+			join_url("https://api.example.com/", "/ip"),
+			"https://api.example.com/ip"
+		);
+	}
+
 	#[test]
 	fn test_circuit_breaker_closed_to_open() {
 		let mut cb = CircuitBreaker::new(3, Duration::from_secs(60));
@@ -460,15 +834,234 @@ mod tests {
 		assert_eq!(cb.failure_count, 0);
 	}
 
+	/// Open the breaker and let its timeout elapse.
+	fn tripped_breaker(max_probes: u32, success_threshold: u32) -> CircuitBreaker {
+		let mut cb = CircuitBreaker::with_half_open(1, Duration::ZERO, max_probes, success_threshold);
+		cb.record_failure();
+		assert!(cb.is_open());
+		cb
+	}
+
+	#[test]
+	fn test_half_open_admits_only_max_probes() {
+		let mut cb = tripped_breaker(2, 1);
+
+		assert!(cb.can_attempt());
+		assert!(cb.is_half_open());
+		assert!(cb.can_attempt());
+		assert!(!cb.can_attempt());
+		assert!(!cb.can_attempt());
+	}
+
+	#[test]
+	fn test_half_open_default_admits_single_probe() {
+		let mut cb = CircuitBreaker::new(1, Duration::ZERO);
+		cb.record_failure();
+
+		assert!(cb.can_attempt());
+		assert!(!cb.can_attempt());
+	}
+
+	#[test]
+	fn test_half_open_closes_after_required_successes() {
+		let mut cb = tripped_breaker(1, 2);
+
+		assert!(cb.can_attempt());
+		cb.record_success();
+		assert!(cb.is_half_open());
+
+		// The resolved probe frees its slot for the next one.
+		assert!(cb.can_attempt());
+		cb.record_success();
+		assert_eq!(cb.state, CircuitState::Closed);
+
+		// Closed again: no probe limit.
+		assert!(cb.can_attempt());
+		assert!(cb.can_attempt());
+	}
+
+	#[test]
+	fn test_half_open_probe_failure_reopens() {
+		let mut cb = CircuitBreaker::with_half_open(1, Duration::from_secs(60), 1, 1);
+		cb.record_failure();
+		cb.state = CircuitState::HalfOpen;
+
+		assert!(cb.can_attempt());
+		cb.record_failure();
+		assert!(cb.is_open());
+		assert!(!cb.can_attempt());
+	}
+
+	#[test]
+	fn test_released_probe_frees_its_slot_only_in_its_period() {
+		let mut cb = tripped_breaker(1, 1);
+
+		assert!(cb.can_attempt());
+		let generation = cb.probe_generation().unwrap();
+		assert!(cb.rejects());
+		cb.release_probe(generation);
+		assert!(!cb.rejects());
+		assert!(cb.can_attempt());
+
+		// A probe from an earlier half-open period frees nothing.
+		cb.record_failure();
+		assert!(cb.can_attempt());
+		cb.release_probe(generation);
+		assert!(cb.rejects());
+	}
+
+	/// A client whose breaker is half-open with one probe slot.
+	async fn half_open_client(config: ProviderConfig) -> ResilientClient {
+		let client = ResilientClientBuilder::new(ProviderConfig {
+			circuit_breaker_threshold: 1,
+			..config
+		})
+		.build();
+		let mut cb = client.circuit_breaker.lock().await;
+		cb.record_failure();
+		cb.state = CircuitState::HalfOpen;
+		drop(cb);
+		client
+	}
+
+	#[tokio::test]
+	async fn test_rate_limited_request_takes_no_probe_slot() {
+		let client = half_open_client(ProviderConfig {
+			rate_limit_rps: 1,
+			rate_limit_burst: 1,
+			..ProviderConfig::default()
+		})
+		.await;
+		assert!(client.rate_limiter.lock().await.try_acquire());
+
+		assert!(matches!(
+			client.get("/").await,
+			Err(ResilientClientError::RateLimitExceeded)
+		));
+		let cb = client.circuit_breaker.lock().await;
+		assert_eq!(cb.probes_in_flight, 0);
+		assert!(!cb.rejects());
+	}
+
+	#[tokio::test]
+	async fn test_dropped_probe_frees_its_slot() {
+		// Accepts connections but never answers.
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let addr = listener.local_addr().unwrap();
+		tokio::spawn(async move {
+			let mut held = Vec::new();
+			while let Ok((stream, _)) = listener.accept().await {
+				held.push(stream);
+			}
+		});
+		let client = half_open_client(ProviderConfig {
+			base_url: format!("http://{}", addr),
+			..ProviderConfig::default()
+		})
+		.await;
+
+		let abandoned = tokio::time::timeout(Duration::from_millis(50), client.get("/")).await;
+		assert!(abandoned.is_err());
+		let cb = client.circuit_breaker.lock().await;
+		assert!(cb.is_half_open());
+		assert_eq!(cb.probes_in_flight, 0);
+	}
+
 	#[test]
 	fn test_token_bucket_rate_limiting() {
-		let mut bucket = TokenBucket::new(2, 0);
+		// 1 token/sec: effectively no refill within the test.
+		let mut bucket = TokenBucket::new(2, 1);
 
 		assert!(bucket.try_acquire());
 		assert!(bucket.try_acquire());
 		assert!(!bucket.try_acquire());
 	}
 
+	#[test]
+	fn test_token_bucket_zero_refill_is_unlimited() {
+		let mut bucket = TokenBucket::new(2, 0);
+		for _ in 0..100 {
+			assert!(bucket.try_acquire());
+		}
+	}
+
+	#[tokio::test]
+	async fn test_client_with_zero_rps_is_not_rate_limited() {
+		let config = ProviderConfig {
+			rate_limit_rps: 0,
+			rate_limit_burst: 1,
+			..ProviderConfig::default()
+		};
+		let client = ResilientClientBuilder::new(config).build();
+
+		for _ in 0..10 {
+			assert!(client.rate_limiter.lock().await.try_acquire());
+		}
+	}
+
+	/// Open `client`'s breaker by recording failures up to its threshold.
+	async fn trip(client: &ResilientClient) {
+		let mut cb = client.circuit_breaker.lock().await;
+		for _ in 0..cb.threshold {
+			cb.record_failure();
+		}
+	}
+
+	#[tokio::test]
+	async fn test_clients_for_same_provider_share_breaker() {
+		let registry = ProviderRegistry::new();
+		let a = ResilientClientBuilder::new(ProviderConfig::default())
+			.with_shared_state(&registry, "virustotal")
+			.build();
+		let b = ResilientClientBuilder::new(ProviderConfig::default())
+			.with_shared_state(&registry, "virustotal")
+			.build();
+
+		trip(&a).await;
+
+		assert!(b.get_metrics().await.circuit_breaker_open);
+		assert!(!b.circuit_breaker.lock().await.can_attempt());
+		assert_eq!(registry.providers(), vec!["virustotal".to_string()]);
+	}
+
+	#[tokio::test]
+	async fn test_clients_for_different_providers_are_independent() {
+		let registry = ProviderRegistry::new();
+		let a = ResilientClientBuilder::new(ProviderConfig::default())
+			.with_shared_state(&registry, "virustotal")
+			.build();
+		let b = ResilientClientBuilder::new(ProviderConfig::default())
+			.with_shared_state(&registry, "shodan")
+			.build();
+		let unshared = ResilientClientBuilder::new(ProviderConfig::default()).build();
+
+		trip(&a).await;
+
+		assert!(a.get_metrics().await.circuit_breaker_open);
+		assert!(!b.get_metrics().await.circuit_breaker_open);
+		assert!(!unshared.get_metrics().await.circuit_breaker_open);
+	}
+
+	#[tokio::test]
+	async fn test_clients_for_same_provider_share_rate_limit() {
+		let config = ProviderConfig {
+			rate_limit_rps: 1,
+			rate_limit_burst: 2,
+			..ProviderConfig::default()
+		};
+		let registry = ProviderRegistry::new();
+		let a = ResilientClientBuilder::new(config.clone())
+			.with_shared_state(&registry, "shodan")
+			.build();
+		let b = ResilientClientBuilder::new(config)
+			.with_shared_state(&registry, "shodan")
+			.build();
+
+		assert!(a.rate_limiter.lock().await.try_acquire());
+		assert!(b.rate_limiter.lock().await.try_acquire());
+		assert!(!a.rate_limiter.lock().await.try_acquire());
+	}
+
 	#[test]
 	fn test_base64_encode() {
 		assert_eq!(base64_encode("hello"), "aGVsbG8=");
@@ -0,0 +1,160 @@
+//! The standard shape enrichment results are stored in.
+//!
+//! Every provider's findings about an entity become one node labelled for
+//! the kind of enrichment (e.g. `GeoIPEnrichment`), carrying the provider's
+//! attributes plus `enrichment_source` and `enriched_at`, and an
+//! [`ENRICHED_BY`] edge from the enriched entity to it. The node's key is
+//! derived from the label, source and target, so enriching the same entity
+//! again updates the node in place.
+
+use serde_json::Value;
+
+use super::pipeline::{Entity, Relation, StepOutput};
+use crate::age_client::{AgeRepo, AgeResult};
+
+/// Edge type from an enriched entity to its enrichment node.
+pub const ENRICHED_BY: &str = "ENRICHED_BY";
+
+/// What one provider found about one entity.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnrichmentResult {
+	/// Label of the enrichment node, e.g. `GeoIPEnrichment`.
+	pub label: String,
+	/// Provider that produced the result, stored as `enrichment_source`.
+	pub source: String,
+	/// Key of the enriched entity.
+	pub target_key: String,
+	/// Provider attributes, e.g. `{"country": "US"}`.
+	pub attributes: Value,
+	/// RFC 3339 time of the lookup, stored as `enriched_at`.
+	pub enriched_at: String,
+}
+
+impl EnrichmentResult {
+	/// A result for `target` enriched now.
+	pub fn new(
+		label: impl Into<String>,
+		source: impl Into<String>,
+		target: &Entity,
+		attributes: Value,
+	) -> Self {
+		Self {
+			label: label.into(),
+			source: source.into(),
+			target_key: target.key.clone(),
+			attributes,
+			enriched_at: chrono::Utc::now().to_rfc3339(),
+		}
+	}
+
+	/// Record the lookup as made at `enriched_at` instead of now.
+	pub fn with_enriched_at(mut self, enriched_at: impl Into<String>) -> Self {
+		self.enriched_at = enriched_at.into();
+		self
+	}
+
+	/// Key of the enrichment node: `<label>:<source>:<target key>`.
+	pub fn key(&self) -> String {
+		format!("{}:{}:{}", self.label, self.source, self.target_key)
+	}
+
+	/// Properties both the node and the edge carry.
+	fn provenance(&self) -> serde_json::Map<String, Value> {
+		let mut props = serde_json::Map::new();
+		props.insert("enrichment_source".into(), self.source.clone().into());
+		props.insert("enriched_at".into(), self.enriched_at.clone().into());
+		props
+	}
+
+	/// The enrichment node. Attributes named like the provenance
+	/// properties are overridden by them.
+	pub fn entity(&self) -> Entity {
+		let mut props = match &self.attributes {
+			Value::Object(map) => map.clone(),
+			_ => serde_json::Map::new(),
+		};
+		props.extend(self.provenance());
+		Entity::new(self.label.clone(), self.key(), Value::Object(props))
+	}
+
+	/// The [`ENRICHED_BY`] edge from the target to the enrichment node.
+	pub fn relation(&self) -> Relation {
+		Relation {
+			from_key: self.target_key.clone(),
+			rel_type: ENRICHED_BY.to_string(),
+			to_key: self.key(),
+			props: Value::Object(self.provenance()),
+		}
+	}
+
+	/// Merge the enrichment node, then its edge from the target, which
+	/// must already exist.
+	pub async fn persist(&self, repo: &dyn AgeRepo) -> AgeResult<()> {
+		let node = self.entity();
+		repo.merge_entity(&node.label, &node.key, &node.props)
+			.await?;
+		let edge = self.relation();
+		repo.relate(&edge.from_key, &edge.to_key, &edge.rel_type, &edge.props)
+			.await
+	}
+}
+
+impl StepOutput {
+	/// Add `result`'s node and its [`ENRICHED_BY`] edge.
+	pub fn enriched(mut self, result: EnrichmentResult) -> Self {
+		self.relations.push(result.relation());
+		self.entities.push(result.entity());
+		self
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+
+	fn geoip() -> EnrichmentResult {
+		let ip = Entity::new("IPAddress", "8.8.8.8", json!({}));
+		EnrichmentResult::new(
+			"GeoIPEnrichment",
+			"mock_geoip",
+			&ip,
+			json!({"country": "US", "enrichment_source": "spoofed"}),
+		)
+		.with_enriched_at("2024-01-01T00:00:00Z")
+	}
+
+	#[test]
+	fn result_is_a_node_and_an_edge_from_its_target() {
+		let result = geoip();
+		let node = result.entity();
+		assert_eq!(node.label, "GeoIPEnrichment");
+		assert_eq!(node.key, "GeoIPEnrichment:mock_geoip:8.8.8.8");
+		assert_eq!(
+			node.props,
+			json!({
+				"country": "US",
+				"enrichment_source": "mock_geoip",
+				"enriched_at": "2024-01-01T00:00:00Z",
+			})
+		);
+
+		let edge = result.relation();
+		assert_eq!(
+			(
+				edge.from_key.as_str(),
+				edge.rel_type.as_str(),
+				edge.to_key.as_str()
+			),
+			("8.8.8.8", ENRICHED_BY, node.key.as_str())
+		);
+		assert_eq!(
+			edge.props,
+			json!({"enrichment_source": "mock_geoip", "enriched_at": "2024-01-01T00:00:00Z"})
+		);
+
+		let output = StepOutput::default().enriched(result);
+		assert_eq!(output.entities, vec![node]);
+		assert_eq!(output.relations, vec![edge]);
+	}
+}
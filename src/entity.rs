@@ -0,0 +1,85 @@
+//! Entity lookups for analysts pivoting through the graph.
+
+use axum::{
+	Json,
+	extract::{Path, Query, State},
+	http::{HeaderMap, StatusCode},
+	response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+
+use crate::age_client::MAX_NEIGHBORS;
+use crate::state::AppState;
+
+/// Edges returned when the request does not set `limit`.
+pub const DEFAULT_NEIGHBORS: usize = 100;
+
+/// Query parameters for `GET /entity/{label}/{key}/neighbors`.
+#[derive(Debug, Default, Deserialize)]
+pub struct NeighborParams {
+	/// Comma-separated edge types to follow; every type when empty.
+	#[serde(default)]
+	pub edge_types: String,
+	/// Maximum number of edges returned, capped at [`MAX_NEIGHBORS`].
+	pub limit: Option<usize>,
+}
+
+/// Return the `label` node keyed `key` (the stored graph key, including
+/// its kind prefix) together with its immediate neighbors and the edges
+/// connecting them. Responds 404 when the node does not exist. Requires a
+/// valid bearer token.
+pub async fn entity_neighbors(
+	State(state): State<AppState>,
+	headers: HeaderMap,
+	Path((label, key)): Path<(String, String)>,
+	Query(params): Query<NeighborParams>,
+) -> Response {
+	if let Err(resp) = crate::auth::authenticate(&state, &headers).await {
+		return resp;
+	}
+
+	let edge_types: Vec<&str> = params
+		.edge_types
+		.split(',')
+		.map(str::trim)
+		.filter(|t| !t.is_empty())
+		.collect();
+	if let Some(bad) = edge_types
+		.iter()
+		.find(|t| !t.chars().any(|c| c.is_ascii_alphanumeric() || c == '_'))
+	{
+		return (
+			StatusCode::BAD_REQUEST,
+			format!("invalid edge type: {:?}", bad),
+		)
+			.into_response();
+	}
+	let limit = params.limit.unwrap_or(DEFAULT_NEIGHBORS).min(MAX_NEIGHBORS);
+
+	match state.repo.neighbors(&label, &key, &edge_types, limit).await {
+		Ok(Some(fragment)) => (StatusCode::OK, Json(fragment)).into_response(),
+		Ok(None) => (StatusCode::NOT_FOUND, "entity not found").into_response(),
+		Err(e) => {
+			log::error!("neighbor lookup for {} {} failed: {}", label, key, e);
+			(StatusCode::INTERNAL_SERVER_ERROR, "neighbor lookup failed").into_response()
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[tokio::test]
+	async fn neighbors_require_authentication() {
+		let state = crate::ingest::test_utils::create_test_app_state();
+		let resp = entity_neighbors(
+			State(state),
+			HeaderMap::new(),
+			Path(("FieldValue".to_string(), "domain:example.com".to_string())),
+			Query(NeighborParams::default()),
+		)
+		.await;
+		assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+	}
+}
@@ -1,75 +1,231 @@
-use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use axum::{
+	Json,
+	extract::State,
+	http::{StatusCode, header},
+	response::{IntoResponse, Response},
+};
+use tokio::task::JoinHandle;
+
+use crate::age_client::AgeRepo;
+use crate::persist::circuit::PersistCircuit;
+
+/// Last known reachability of the database, shared through `AppState`.
+///
+/// Starts out healthy. [`DbHealth::spawn_monitor`] keeps it current by
+/// pinging the repo; ingest handlers consult it so a DB outage produces a
+/// single fast `503` per request rather than a failed write per record.
+/// The same holds while an attached [`PersistCircuit`] is open.
+pub struct DbHealth {
+	healthy: AtomicBool,
+	retry_after_secs: u64,
+	circuit: Option<Arc<PersistCircuit>>,
+	/// AGE extension version detected at startup, reported by `/health/db`.
+	age_version: OnceLock<String>,
+}
+
+impl Default for DbHealth {
+	fn default() -> Self {
+		Self::new(5)
+	}
+}
+
+impl DbHealth {
+	/// Create a healthy flag whose rejections ask clients to retry after
+	/// `retry_after_secs`.
+	pub fn new(retry_after_secs: u64) -> Self {
+		Self {
+			healthy: AtomicBool::new(true),
+			retry_after_secs,
+			circuit: None,
+			age_version: OnceLock::new(),
+		}
+	}
+
+	/// Also reject ingest while `circuit` has paused persistence.
+	pub fn with_persist_circuit(mut self, circuit: Arc<PersistCircuit>) -> Self {
+		self.circuit = Some(circuit);
+		self
+	}
+
+	/// Record the AGE extension version found by
+	/// [`crate::age_client::AgeClient::check_extension`].
+	pub fn set_age_version(&self, version: impl Into<String>) {
+		let _ = self.age_version.set(version.into());
+	}
+
+	pub fn age_version(&self) -> Option<&str> {
+		self.age_version.get().map(String::as_str)
+	}
+
+	pub fn is_healthy(&self) -> bool {
+		self.healthy.load(Ordering::Relaxed)
+	}
+
+	/// Record the outcome of a health check, logging transitions.
+	pub fn set_healthy(&self, healthy: bool) {
+		if self.healthy.swap(healthy, Ordering::Relaxed) != healthy {
+			if healthy {
+				tracing::info!("database reachable again; accepting ingest");
+			} else {
+				tracing::warn!("database unreachable; rejecting ingest with 503");
+			}
+		}
+	}
+
+	/// `503 Service Unavailable` with `Retry-After` while the database is
+	/// known to be down or persistence is paused, otherwise `None`.
+	pub fn reject_if_down(&self) -> Option<Response> {
+		let reason = if !self.is_healthy() {
+			"database unavailable"
+		} else if self.circuit.as_ref().is_some_and(|c| c.is_open()) {
+			"ingest paused: persistence failing"
+		} else {
+			return None;
+		};
+		Some(
+			(
+				StatusCode::SERVICE_UNAVAILABLE,
+				[(header::RETRY_AFTER, self.retry_after_secs.to_string())],
+				reason,
+			)
+				.into_response(),
+		)
+	}
+
+	/// Ping `repo` every `interval` and update the flag with the result.
+	pub fn spawn_monitor(
+		self: &Arc<Self>,
+		repo: Arc<dyn AgeRepo>,
+		interval: Duration,
+	) -> JoinHandle<()> {
+		let health = Arc::clone(self);
+		tokio::spawn(async move {
+			let mut ticker = tokio::time::interval(interval);
+			loop {
+				ticker.tick().await;
+				match repo.ping().await {
+					Ok(()) => health.set_healthy(true),
+					Err(e) => {
+						tracing::debug!(error = %e, "database ping failed");
+						health.set_healthy(false);
+					}
+				}
+			}
+		})
+	}
+}
 
 /// DB health endpoint: returns 200 OK when the configured repo can run a
-/// simple query, otherwise returns 503 Service Unavailable.
+/// simple query, otherwise returns 503 Service Unavailable. The JSON body
+/// carries the AGE extension version when it was checked at startup and
+/// the state of each enrichment provider; a degraded provider does not
+/// change the status code.
 pub async fn db_health(State(state): State<crate::state::AppState>) -> impl IntoResponse {
+	let age_version = state.db_health.age_version();
+	let providers = state.enrich_providers.status().await;
 	match state.repo.ping().await {
-		Ok(()) => (StatusCode::OK, "OK").into_response(),
-		Err(e) => (StatusCode::SERVICE_UNAVAILABLE, format!("db error: {}", e)).into_response(),
+		Ok(()) => (
+			StatusCode::OK,
+			Json(serde_json::json!({
+				"status": "ok",
+				"age_version": age_version,
+				"enrichment": providers,
+			})),
+		)
+			.into_response(),
+		Err(e) => (
+			StatusCode::SERVICE_UNAVAILABLE,
+			Json(serde_json::json!({
+				"status": "error",
+				"error": format!("db error: {}", e),
+				"age_version": age_version,
+				"enrichment": providers,
+			})),
+		)
+			.into_response(),
 	}
 }
 
 #[cfg(test)]
 mod tests {
 	use super::*;
-	use crate::age_client::AgeRepo;
-	use anyhow::{Result, anyhow};
-	use async_trait::async_trait;
+	use crate::ingest::test_utils::{RecordingRepo, app_state_with_receiver};
 	use serde_json::Value;
-	use std::sync::Arc;
-	use tokio::sync::mpsc;
 
-	// Mock repository for testing
-	struct MockAgeRepo {
-		should_succeed: bool,
+	/// Repo whose `ping` fails when `down`.
+	fn repo(down: bool) -> Arc<RecordingRepo> {
+		Arc::new(RecordingRepo {
+			down: AtomicBool::new(down),
+			..RecordingRepo::default()
+		})
 	}
 
-	#[async_trait]
-	impl AgeRepo for MockAgeRepo {
-		async fn merge_entity(&self, _label: &str, _key: &str, _props: &Value) -> Result<()> {
-			Ok(())
-		}
-
-		async fn ping(&self) -> Result<()> {
-			if self.should_succeed {
-				Ok(())
-			} else {
-				Err(anyhow!("database unavailable"))
-			}
-		}
+	#[tokio::test]
+	async fn health_check_returns_ok_when_db_healthy() {
+		let (state, _rx) = app_state_with_receiver(repo(false));
 
-		async fn merge_batch(&self, _items: &[(String, String, Value)]) -> Result<()> {
-			Ok(())
-		}
+		let response = db_health(State(state)).await.into_response();
+		assert_eq!(response.status(), StatusCode::OK);
 	}
 
 	#[tokio::test]
-	async fn health_check_returns_ok_when_db_healthy() {
-		let repo: Arc<dyn AgeRepo> = Arc::new(MockAgeRepo {
-			should_succeed: true,
-		});
-		let (tx, _rx) = mpsc::channel(10);
-		let state = crate::state::AppState {
-			repo,
-			persist_sender: tx,
-		};
+	async fn health_check_reports_age_version() {
+		let health = Arc::new(DbHealth::default());
+		health.set_age_version("1.5.0");
+		let state = app_state_with_receiver(repo(false))
+			.0
+			.with_db_health(health);
 
 		let response = db_health(State(state)).await.into_response();
 		assert_eq!(response.status(), StatusCode::OK);
+		let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+			.await
+			.unwrap();
+		let json: Value = serde_json::from_slice(&body).unwrap();
+		assert_eq!(json["status"], "ok");
+		assert_eq!(json["age_version"], "1.5.0");
 	}
 
 	#[tokio::test]
 	async fn health_check_returns_service_unavailable_when_db_fails() {
-		let repo: Arc<dyn AgeRepo> = Arc::new(MockAgeRepo {
-			should_succeed: false,
-		});
-		let (tx, _rx) = mpsc::channel(10);
-		let state = crate::state::AppState {
-			repo,
-			persist_sender: tx,
-		};
+		let (state, _rx) = app_state_with_receiver(repo(true));
 
 		let response = db_health(State(state)).await.into_response();
 		assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
 	}
+
+	async fn wait_for(health: &DbHealth, healthy: bool) {
+		let deadline = tokio::time::Instant::now() + Duration::from_secs(2);
+		while health.is_healthy() != healthy {
+			assert!(
+				tokio::time::Instant::now() < deadline,
+				"flag never became {}",
+				healthy
+			);
+			tokio::time::sleep(Duration::from_millis(5)).await;
+		}
+	}
+
+	#[tokio::test]
+	async fn monitor_tracks_outage_and_recovery() {
+		let repo = repo(false);
+		let health = Arc::new(DbHealth::new(7));
+		let monitor = health.spawn_monitor(repo.clone(), Duration::from_millis(10));
+		assert!(health.reject_if_down().is_none());
+
+		repo.down.store(true, Ordering::SeqCst);
+		wait_for(&health, false).await;
+		let resp = health.reject_if_down().expect("rejects while down");
+		assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+		assert_eq!(resp.headers()[header::RETRY_AFTER], "7");
+
+		repo.down.store(false, Ordering::SeqCst);
+		wait_for(&health, true).await;
+		assert!(health.reject_if_down().is_none());
+		monitor.abort();
+	}
 }
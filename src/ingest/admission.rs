@@ -0,0 +1,398 @@
+//! Admission control for concurrent uploads.
+//!
+//! Each upload endpoint admits at most `max_in_flight` requests at once, so
+//! a few large uploads cannot hog the persist channel and temp-file IO.
+//! Further uploads wait in a small queue, smallest declared
+//! `Content-Length` first (uploads declaring none go last), so a small
+//! upload is never stuck behind a large one. An upload that finds the queue
+//! full, or is not admitted within `queue_wait`, gets `503` with
+//! `Retry-After` and is counted in `heimdall_ingest_upload_shed_total`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::{
+	extract::{Request, State},
+	http::{Method, StatusCode, header},
+	middleware::Next,
+	response::{IntoResponse, Response},
+};
+use thiserror::Error;
+use tokio::sync::oneshot;
+
+use crate::state::AppState;
+
+/// Endpoints whose uploads are admitted through their own queue.
+pub const UPLOAD_ENDPOINTS: &[&str] = &[
+	"/ingest/ndjson",
+	"/ingest/bulk",
+	"/ingest/detect",
+	"/ingest/multipart",
+	"/ingest/records",
+];
+
+/// Concurrency and queueing limits applied to each endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdmissionLimits {
+	pub max_in_flight: usize,
+	pub max_queued: usize,
+	pub queue_wait: Duration,
+}
+
+/// Why an upload was not admitted.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AdmissionError {
+	#[error("{0} uploads are already queued")]
+	QueueFull(usize),
+	#[error("no upload slot became free within {0:?}")]
+	TimedOut(Duration),
+}
+
+/// Queue position: declared size, then arrival order.
+type Ticket = (u64, u64);
+
+#[derive(Default)]
+struct Slots {
+	in_flight: usize,
+	waiting: BTreeMap<Ticket, oneshot::Sender<()>>,
+	next_seq: u64,
+}
+
+/// Admits uploads to one endpoint.
+pub struct UploadAdmission {
+	limits: AdmissionLimits,
+	slots: Mutex<Slots>,
+}
+
+impl UploadAdmission {
+	pub fn new(limits: AdmissionLimits) -> Self {
+		Self {
+			limits,
+			slots: Mutex::new(Slots::default()),
+		}
+	}
+
+	/// Uploads currently admitted.
+	pub fn in_flight(&self) -> usize {
+		self.slots.lock().unwrap().in_flight
+	}
+
+	/// Uploads waiting to be admitted.
+	pub fn queued(&self) -> usize {
+		self.slots.lock().unwrap().waiting.len()
+	}
+
+	/// Admit an upload declaring `declared_bytes`, waiting for a free slot
+	/// if needed. The slot is held until the returned permit is dropped.
+	pub async fn admit(
+		self: &Arc<Self>,
+		declared_bytes: u64,
+	) -> Result<AdmissionPermit, AdmissionError> {
+		let (ticket, rx) = {
+			let mut slots = self.slots.lock().unwrap();
+			if slots.in_flight < self.limits.max_in_flight && slots.waiting.is_empty() {
+				slots.in_flight += 1;
+				return Ok(AdmissionPermit(self.clone()));
+			}
+			if slots.waiting.len() >= self.limits.max_queued {
+				return Err(AdmissionError::QueueFull(slots.waiting.len()));
+			}
+			let ticket = (declared_bytes, slots.next_seq);
+			slots.next_seq += 1;
+			let (tx, rx) = oneshot::channel();
+			slots.waiting.insert(ticket, tx);
+			(ticket, rx)
+		};
+		// Leaves the queue however the wait ends, including when the request
+		// is dropped.
+		let mut waiting = Waiting {
+			admission: self,
+			ticket,
+			rx,
+			admitted: false,
+		};
+		match tokio::time::timeout(self.limits.queue_wait, &mut waiting.rx).await {
+			Ok(Ok(())) => {
+				waiting.admitted = true;
+				Ok(AdmissionPermit(self.clone()))
+			}
+			_ => Err(AdmissionError::TimedOut(self.limits.queue_wait)),
+		}
+	}
+
+	/// Hand a freed slot to the first waiting upload, or free it.
+	fn release(&self) {
+		let mut slots = self.slots.lock().unwrap();
+		while let Some((_, tx)) = slots.waiting.pop_first() {
+			if tx.send(()).is_ok() {
+				return;
+			}
+		}
+		slots.in_flight -= 1;
+	}
+}
+
+/// An upload's place in the queue while it waits.
+struct Waiting<'a> {
+	admission: &'a UploadAdmission,
+	ticket: Ticket,
+	// Kept alive until `drop` has run so a slot sent to it is never lost.
+	rx: oneshot::Receiver<()>,
+	admitted: bool,
+}
+
+impl Drop for Waiting<'_> {
+	fn drop(&mut self) {
+		if self.admitted {
+			return;
+		}
+		let removed = self
+			.admission
+			.slots
+			.lock()
+			.unwrap()
+			.waiting
+			.remove(&self.ticket)
+			.is_some();
+		if !removed {
+			// A slot was handed over just as the wait ended: pass it on.
+			self.admission.release();
+		}
+	}
+}
+
+/// An admitted upload's slot, freed on drop.
+pub struct AdmissionPermit(Arc<UploadAdmission>);
+
+impl Drop for AdmissionPermit {
+	fn drop(&mut self) {
+		self.0.release();
+	}
+}
+
+/// One [`UploadAdmission`] per [`UPLOAD_ENDPOINTS`] path.
+pub struct UploadGates {
+	limits: AdmissionLimits,
+	endpoints: HashMap<&'static str, Arc<UploadAdmission>>,
+}
+
+impl UploadGates {
+	pub fn new(limits: AdmissionLimits) -> Self {
+		Self {
+			limits,
+			endpoints: UPLOAD_ENDPOINTS
+				.iter()
+				.map(|path| (*path, Arc::new(UploadAdmission::new(limits))))
+				.collect(),
+		}
+	}
+
+	pub fn limits(&self) -> AdmissionLimits {
+		self.limits
+	}
+
+	/// The admission for uploads to `path`, if it is an upload endpoint.
+	pub fn endpoint(&self, path: &str) -> Option<&Arc<UploadAdmission>> {
+		self.endpoints.get(path)
+	}
+}
+
+/// Middleware admitting `POST` uploads through [`UploadGates`].
+pub async fn admit(State(state): State<AppState>, req: Request, next: Next) -> Response {
+	let Some(gates) = state.upload_admission.clone() else {
+		return next.run(req).await;
+	};
+	if req.method() != Method::POST {
+		return next.run(req).await;
+	}
+	let path = req.uri().path().to_string();
+	let Some(admission) = gates.endpoint(&path) else {
+		return next.run(req).await;
+	};
+	let declared = req
+		.headers()
+		.get(header::CONTENT_LENGTH)
+		.and_then(|v| v.to_str().ok())
+		.and_then(|v| v.parse::<u64>().ok())
+		.unwrap_or(u64::MAX);
+	match admission.admit(declared).await {
+		Ok(_permit) => next.run(req).await,
+		Err(e) => shed(&state, &path, gates.limits(), e),
+	}
+}
+
+/// `503 Service Unavailable` for an upload to `path` that was not admitted.
+fn shed(state: &AppState, path: &str, limits: AdmissionLimits, e: AdmissionError) -> Response {
+	state
+		.metrics
+		.ingest_upload_shed_total
+		.with_label_values(&[path])
+		.inc();
+	tracing::debug!(endpoint = path, error = %e, "upload shed");
+	let secs = limits.queue_wait.as_secs_f64().ceil().max(1.0) as u64;
+	(
+		StatusCode::SERVICE_UNAVAILABLE,
+		[(header::RETRY_AFTER, secs.to_string())],
+		e.to_string(),
+	)
+		.into_response()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn admission(max_in_flight: usize, max_queued: usize, wait_ms: u64) -> Arc<UploadAdmission> {
+		Arc::new(UploadAdmission::new(AdmissionLimits {
+			max_in_flight,
+			max_queued,
+			queue_wait: Duration::from_millis(wait_ms),
+		}))
+	}
+
+	/// Wait until `n` uploads are queued on `admission`.
+	async fn queued(admission: &UploadAdmission, n: usize) {
+		while admission.queued() < n {
+			tokio::time::sleep(Duration::from_millis(1)).await;
+		}
+	}
+
+	#[tokio::test]
+	async fn uploads_beyond_the_cap_queue_then_shed() {
+		let admission = admission(2, 1, 50);
+		let first = admission.admit(10).await.unwrap();
+		let _second = admission.admit(10).await.unwrap();
+		assert_eq!(admission.in_flight(), 2);
+
+		// The third waits for a slot...
+		let waiter = tokio::spawn({
+			let admission = admission.clone();
+			async move { admission.admit(10).await.map(|_| ()) }
+		});
+		queued(&admission, 1).await;
+		// ...and a fourth finds the queue full.
+		assert_eq!(
+			admission.admit(10).await.err(),
+			Some(AdmissionError::QueueFull(1))
+		);
+
+		drop(first);
+		waiter.await.unwrap().unwrap();
+		assert_eq!(admission.queued(), 0);
+		assert_eq!(admission.in_flight(), 1);
+
+		// Nothing frees a slot in time.
+		let _third = admission.admit(10).await.unwrap();
+		assert_eq!(
+			admission.admit(10).await.err(),
+			Some(AdmissionError::TimedOut(Duration::from_millis(50)))
+		);
+		assert_eq!(admission.queued(), 0);
+	}
+
+	#[tokio::test]
+	async fn small_uploads_are_admitted_before_large_ones() {
+		let admission = admission(1, 4, 5_000);
+		let running = admission.admit(1 << 30).await.unwrap();
+
+		let (order_tx, mut order) = tokio::sync::mpsc::unbounded_channel();
+		let mut waiters = Vec::new();
+		for (n, size) in [(1, 1u64 << 30), (2, u64::MAX), (3, 1024)] {
+			let (waiting, order_tx) = (admission.clone(), order_tx.clone());
+			waiters.push(tokio::spawn(async move {
+				let _permit = waiting.admit(size).await.unwrap();
+				order_tx.send(size).unwrap();
+			}));
+			queued(&admission, n).await;
+		}
+
+		// The small upload queued last goes first; undeclared sizes go last.
+		drop(running);
+		for waiter in waiters {
+			waiter.await.unwrap();
+		}
+		let mut admitted = Vec::new();
+		while let Ok(size) = order.try_recv() {
+			admitted.push(size);
+		}
+		assert_eq!(admitted, vec![1024, 1 << 30, u64::MAX]);
+		assert_eq!(admission.in_flight(), 0);
+	}
+
+	#[tokio::test]
+	async fn abandoned_waits_leave_the_queue() {
+		let admission = admission(1, 1, 5_000);
+		let running = admission.admit(1).await.unwrap();
+		let waiter = tokio::spawn({
+			let admission = admission.clone();
+			async move { admission.admit(1).await.map(|_| ()) }
+		});
+		queued(&admission, 1).await;
+		waiter.abort();
+		let _ = waiter.await;
+		assert_eq!(admission.queued(), 0);
+
+		drop(running);
+		assert_eq!(admission.in_flight(), 0);
+		let _permit = admission.admit(1).await.unwrap();
+	}
+
+	#[tokio::test]
+	async fn middleware_sheds_uploads_to_a_busy_endpoint() {
+		use axum::{Router, body::Body, routing::post};
+		use tower::ServiceExt;
+
+		let state = crate::ingest::test_utils::create_test_app_state().with_upload_admission(
+			AdmissionLimits {
+				max_in_flight: 1,
+				max_queued: 0,
+				queue_wait: Duration::from_secs(1),
+			},
+		);
+		let app = Router::new()
+			.route("/ingest/records", post(|| async { "ok" }))
+			.route("/ingest/ndjson", post(|| async { "ok" }))
+			.layer(axum::middleware::from_fn_with_state(state.clone(), admit))
+			.with_state(state.clone());
+		let post_to = |uri: &str| {
+			Request::builder()
+				.method("POST")
+				.uri(uri)
+				.body(Body::empty())
+				.unwrap()
+		};
+
+		let gates = state.upload_admission.clone().unwrap();
+		let _busy = gates
+			.endpoint("/ingest/records")
+			.unwrap()
+			.admit(0)
+			.await
+			.unwrap();
+		let resp = app
+			.clone()
+			.oneshot(post_to("/ingest/records"))
+			.await
+			.unwrap();
+		assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+		// Other endpoints have their own slots.
+		let resp = app.oneshot(post_to("/ingest/ndjson")).await.unwrap();
+		assert_eq!(resp.status(), StatusCode::OK);
+	}
+
+	#[test]
+	fn shedding_sets_retry_after_and_counts_the_endpoint() {
+		let state = crate::ingest::test_utils::create_test_app_state();
+		let limits = AdmissionLimits {
+			max_in_flight: 1,
+			max_queued: 0,
+			queue_wait: Duration::from_millis(2500),
+		};
+		let resp = shed(&state, "/ingest/bulk", limits, AdmissionError::QueueFull(0));
+		assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+		assert_eq!(resp.headers()[header::RETRY_AFTER], "3");
+		let shed = &state.metrics.ingest_upload_shed_total;
+		assert_eq!(shed.with_label_values(&["/ingest/bulk"]).get(), 1);
+	}
+}
@@ -1,15 +1,214 @@
 use anyhow::Result;
 use regex::Regex;
-use serde::Serialize;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashMap;
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+use crate::lib::normalizers::{TextNormalizeOptions, normalize_text, normalizer_version};
+
+/// A single normalized field value.
+///
+/// Serialized as JSON for API responses and accepted by
+/// `POST /ingest/records`; the shape is versioned by `schema_version`:
+///
+/// ```json
+/// {"schema_version": 1, "field_type": "domain", "raw": "Example.COM", "canonical": "example.com", "confidence": 1.0}
+/// ```
+///
+/// Fields are only ever added within a schema version; renaming, removing
+/// or changing the meaning of a field bumps [`NormalizedRecord::CURRENT_SCHEMA`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct NormalizedRecord {
+	/// Version of this JSON shape. Records without one are treated as
+	/// version 1; versions newer than `CURRENT_SCHEMA` are rejected.
+	#[serde(
+		default = "NormalizedRecord::first_schema",
+		deserialize_with = "supported_schema_version"
+	)]
+	pub schema_version: u32,
 	/// The type of the field (e.g. "ip", "domain", "hash", "email")
 	pub field_type: String,
-	/// Original/raw value as seen in the dump
+	/// Original/raw value as seen in the dump; may be omitted by clients
+	/// submitting pre-normalized records
+	#[serde(default)]
 	pub raw: String,
 	/// Canonicalized value used as a merge key
 	pub canonical: String,
+	/// How certain it is that the value is of `field_type`, from 0.0 to
+	/// 1.0; set by classification, records submitted without one are
+	/// taken as certain
+	#[serde(default = "NormalizedRecord::certain")]
+	pub confidence: f32,
+	/// Name of the input field the value was read from (e.g.
+	/// `recovery_email`), when the input named one
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub source_field: Option<String>,
+}
+
+impl NormalizedRecord {
+	/// Schema version written by this build.
+	pub const CURRENT_SCHEMA: u32 = 1;
+
+	/// A record in the current schema.
+	pub fn new(
+		field_type: impl Into<String>,
+		raw: impl Into<String>,
+		canonical: impl Into<String>,
+	) -> Self {
+		Self {
+			schema_version: Self::CURRENT_SCHEMA,
+			field_type: field_type.into(),
+			raw: raw.into(),
+			canonical: canonical.into(),
+			confidence: Self::certain(),
+			source_field: None,
+		}
+	}
+
+	/// The same record, read from input field `name`.
+	pub fn with_source_field(mut self, name: impl Into<String>) -> Self {
+		self.source_field = Some(name.into());
+		self
+	}
+
+	/// Mask a payment card number found as the whole raw or canonical
+	/// value, whatever the field type or PII rules: the record becomes a
+	/// `pan` whose canonical is the masked form from
+	/// [`normalize_pan`](crate::lib::normalizers::normalize_pan) and whose
+	/// raw value is scrubbed. Returns whether a card number was found.
+	pub fn mask_pan(&mut self) -> bool {
+		let Some(pan) = [&self.raw, &self.canonical]
+			.into_iter()
+			.find_map(|v| crate::lib::normalizers::normalize_pan(v).ok())
+		else {
+			return false;
+		};
+		self.field_type = "pan".to_string();
+		self.canonical = pan.canonical;
+		if !self.raw.is_empty() {
+			self.raw = "[REDACTED]".to_string();
+		}
+		true
+	}
+
+	fn first_schema() -> u32 {
+		1
+	}
+
+	fn certain() -> f32 {
+		1.0
+	}
+}
+
+/// Reject schema versions this build does not understand.
+fn supported_schema_version<'de, D>(deserializer: D) -> std::result::Result<u32, D::Error>
+where
+	D: Deserializer<'de>,
+{
+	let version = u32::deserialize(deserializer)?;
+	if version == 0 || version > NormalizedRecord::CURRENT_SCHEMA {
+		return Err(serde::de::Error::custom(format!(
+			"unsupported NormalizedRecord schema_version {} (supported: 1..={})",
+			version,
+			NormalizedRecord::CURRENT_SCHEMA
+		)));
+	}
+	Ok(version)
+}
+
+/// How surrounding characters are stripped from a raw value before it is
+/// canonicalized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrimPolicy {
+	/// Strip surrounding whitespace only.
+	Whitespace,
+	/// Strip surrounding whitespace, punctuation and underscores.
+	Punctuation,
+}
+
+/// Per-field-kind trim policies.
+///
+/// Kinds where punctuation is meaningful (a leading `+` on a phone number,
+/// `-` in a hash) only have whitespace trimmed by default; any other kind is
+/// treated as generic text and has surrounding punctuation stripped.
+/// Generic text is then normalized with the rules' [`TextNormalizeOptions`].
+#[derive(Debug, Clone)]
+pub struct TrimRules {
+	default: TrimPolicy,
+	per_kind: HashMap<String, TrimPolicy>,
+	punct_re: Regex,
+	text: TextNormalizeOptions,
+}
+
+impl Default for TrimRules {
+	fn default() -> Self {
+		let mut rules = Self::new(TrimPolicy::Punctuation);
+		for kind in ["domain", "ip", "email", "hash", "phone"] {
+			rules = rules.with_policy(kind, TrimPolicy::Whitespace);
+		}
+		rules
+	}
+}
+
+impl TrimRules {
+	/// Rules applying `default` to every kind.
+	pub fn new(default: TrimPolicy) -> Self {
+		Self {
+			default,
+			per_kind: HashMap::new(),
+			punct_re: Regex::new(r"^[\W_]+|[\W_]+$").unwrap(),
+			text: TextNormalizeOptions::default(),
+		}
+	}
+
+	/// Normalize generic text with `opts` instead of the default.
+	pub fn with_text_options(mut self, opts: TextNormalizeOptions) -> Self {
+		self.text = opts;
+		self
+	}
+
+	/// Options generic text is normalized with.
+	pub fn text_options(&self) -> TextNormalizeOptions {
+		self.text
+	}
+
+	/// Use `policy` for values of field kind `kind`.
+	pub fn with_policy(mut self, kind: &str, policy: TrimPolicy) -> Self {
+		self.per_kind.insert(kind.to_lowercase(), policy);
+		self
+	}
+
+	/// Policy applied to `kind`.
+	pub fn policy(&self, kind: &str) -> TrimPolicy {
+		self.per_kind.get(kind).copied().unwrap_or(self.default)
+	}
+
+	/// Trim `raw` according to the policy for `kind`.
+	pub fn apply(&self, kind: &str, raw: &str) -> String {
+		match self.policy(kind) {
+			TrimPolicy::Whitespace => raw.trim().to_string(),
+			TrimPolicy::Punctuation => self.punct_re.replace_all(raw.trim(), "").to_string(),
+		}
+	}
+}
+
+/// Canonicalize `raw` for field kind `ftype`: trim it according to `rules`,
+/// then apply kind-specific normalization. Kinds without a normalizer are
+/// generic text, normalized with [`TrimRules::text_options`]; the raw value
+/// is left for the record to keep.
+pub fn canonicalize(ftype: &str, raw: &str, rules: &TrimRules) -> String {
+	let v = rules.apply(ftype, raw);
+	match ftype {
+		"domain" => {
+			let mut v = v.to_lowercase();
+			if v.ends_with('.') {
+				v.pop();
+			}
+			v
+		}
+		"ip" | "phone" => v,
+		kind if normalizer_version(kind).is_some() => v.to_lowercase(),
+		_ => normalize_text(&v, rules.text).canonical,
+	}
 }
 
 /// Normalize a CSV input where each row is: `field_type,value`.
@@ -22,8 +221,7 @@ pub fn normalize_csv(input: &str) -> Result<Vec<NormalizedRecord>> {
 
 	let mut out = Vec::new();
 
-	// simple regex for trimming surrounding punctuation from hashes/emails
-	let punct_re = Regex::new(r"^[\W_]+|[\W_]+$").unwrap();
+	let trim = TrimRules::default();
 
 	for result in rdr.records() {
 		let record = result?;
@@ -33,25 +231,9 @@ pub fn normalize_csv(input: &str) -> Result<Vec<NormalizedRecord>> {
 		let ftype = record.get(0).unwrap_or("").to_lowercase();
 		let raw = record.get(1).unwrap_or("").to_string();
 
-		let canonical = match ftype.as_str() {
-			"domain" => {
-				let mut v = raw.trim().to_lowercase();
-				if v.ends_with('.') {
-					v.pop();
-				}
-				v
-			}
-			"ip" => raw.trim().to_string(),
-			"hash" => punct_re.replace_all(&raw.to_lowercase(), "").to_string(),
-			"email" => raw.trim().to_lowercase().to_string(),
-			_ => raw.trim().to_lowercase().to_string(),
-		};
+		let canonical = canonicalize(&ftype, &raw, &trim);
 
-		out.push(NormalizedRecord {
-			field_type: ftype,
-			raw,
-			canonical,
-		});
+		out.push(NormalizedRecord::new(ftype, raw, canonical));
 	}
 
 	Ok(out)
@@ -80,4 +262,82 @@ email,USER@EXAMPLE.COM\n";
 		assert_eq!(got[3].field_type, "email");
 		assert_eq!(got[3].canonical, "user@example.com");
 	}
+
+	#[test]
+	fn record_round_trips_with_schema_version() {
+		let rec = NormalizedRecord::new("domain", "Example.COM", "example.com");
+		let json = serde_json::to_value(&rec).unwrap();
+		assert_eq!(
+			json,
+			serde_json::json!({
+				"schema_version": NormalizedRecord::CURRENT_SCHEMA,
+				"field_type": "domain",
+				"raw": "Example.COM",
+				"canonical": "example.com",
+				"confidence": 1.0,
+			})
+		);
+		let back: NormalizedRecord = serde_json::from_value(json).unwrap();
+		assert_eq!(back, rec);
+
+		// Records predating the version field are schema 1.
+		let legacy: NormalizedRecord =
+			serde_json::from_str(r#"{"field_type":"ip","canonical":"192.0.2.1"}"#).unwrap();
+		assert_eq!(legacy.schema_version, 1);
+		assert_eq!(legacy.confidence, 1.0);
+	}
+
+	#[test]
+	fn future_schema_version_is_rejected() {
+		let future = format!(
+			r#"{{"schema_version":{},"field_type":"ip","canonical":"192.0.2.1"}}"#,
+			NormalizedRecord::CURRENT_SCHEMA + 1
+		);
+		let err = serde_json::from_str::<NormalizedRecord>(&future).unwrap_err();
+		assert!(
+			err.to_string()
+				.contains("unsupported NormalizedRecord schema_version")
+		);
+	}
+
+	#[test]
+	fn card_numbers_are_masked_whatever_the_field_type() {
+		let mut rec =
+			NormalizedRecord::new("username", "5555 5555 5555 4444", "5555 5555 5555 4444");
+		assert!(rec.mask_pan());
+		assert_eq!(rec.field_type, "pan");
+		assert_eq!(rec.canonical, "555555...4444");
+		assert_eq!(rec.raw, "[REDACTED]");
+		let json = serde_json::to_string(&rec).unwrap();
+		assert!(!json.contains("5555 5555 5555 4444") && !json.contains("5555555555554444"));
+
+		// Luhn-invalid numbers are left alone.
+		let mut rec = NormalizedRecord::new("id", "5555555555554445", "5555555555554445");
+		assert!(!rec.mask_pan());
+		assert_eq!(rec.canonical, "5555555555554445");
+	}
+
+	#[test]
+	fn text_variants_share_a_canonical_form() {
+		let full_width = "\u{FF26}\u{FF4F}\u{FF4F}\u{3000}\u{FF22}\u{FF41}\u{FF52}";
+		let variants = ["  Foo   Bar ", "foo bar", full_width];
+
+		let rules = TrimRules::default();
+		for raw in variants {
+			assert_eq!(canonicalize("note", raw, &rules), "foo bar", "{:?}", raw);
+		}
+
+		let unfolded = TrimRules::default().with_text_options(TextNormalizeOptions {
+			case_fold: false,
+			..TextNormalizeOptions::default()
+		});
+		let canonical: Vec<String> = variants
+			.iter()
+			.map(|raw| canonicalize("note", raw, &unfolded))
+			.collect();
+		assert_eq!(canonical, ["Foo Bar", "foo bar", "Foo Bar"]);
+
+		// Kinds with a normalizer of their own are not treated as text.
+		assert_eq!(canonicalize("hash", " AB  CD ", &rules), "ab  cd");
+	}
 }
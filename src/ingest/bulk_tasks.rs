@@ -0,0 +1,170 @@
+//! Progress registry for background bulk processing tasks.
+//!
+//! Each auto-processed upload is registered under its temp filename with a
+//! cancellation flag. The blocking worker polls the flag between records,
+//! so a task can be cancelled on request or when its deadline passes.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// Finished tasks remembered for status queries; older ones are forgotten.
+const MAX_FINISHED: usize = 1024;
+
+/// State of a bulk processing task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkOutcome {
+	Running,
+	Completed,
+	Failed,
+	Cancelled,
+	Timeout,
+}
+
+struct TaskEntry {
+	cancel: Arc<AtomicBool>,
+	outcome: BulkOutcome,
+}
+
+#[derive(Default)]
+struct Inner {
+	tasks: HashMap<String, TaskEntry>,
+	finished: VecDeque<String>,
+}
+
+/// Tracks running and recently finished bulk tasks.
+#[derive(Default)]
+pub struct BulkTaskRegistry {
+	inner: Mutex<Inner>,
+}
+
+impl BulkTaskRegistry {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Register a running task and return its cancellation flag.
+	pub fn register(&self, id: &str) -> Arc<AtomicBool> {
+		let cancel = Arc::new(AtomicBool::new(false));
+		let entry = TaskEntry {
+			cancel: cancel.clone(),
+			outcome: BulkOutcome::Running,
+		};
+		self.inner.lock().unwrap().tasks.insert(id.to_string(), entry);
+		cancel
+	}
+
+	/// Record the final outcome of a task.
+	pub fn finish(&self, id: &str, outcome: BulkOutcome) {
+		let mut inner = self.inner.lock().unwrap();
+		let Some(entry) = inner.tasks.get_mut(id) else {
+			return;
+		};
+		entry.outcome = outcome;
+		inner.finished.push_back(id.to_string());
+		while inner.finished.len() > MAX_FINISHED {
+			if let Some(old) = inner.finished.pop_front() {
+				inner.tasks.remove(&old);
+			}
+		}
+	}
+
+	/// Ask a running task to stop. Returns `false` if the task is unknown
+	/// or already finished.
+	pub fn cancel(&self, id: &str) -> bool {
+		match self.inner.lock().unwrap().tasks.get(id) {
+			Some(entry) if entry.outcome == BulkOutcome::Running => {
+				entry.cancel.store(true, Ordering::Relaxed);
+				true
+			}
+			_ => false,
+		}
+	}
+
+	/// Current outcome of a task, if known.
+	pub fn outcome(&self, id: &str) -> Option<BulkOutcome> {
+		self.inner.lock().unwrap().tasks.get(id).map(|e| e.outcome)
+	}
+}
+
+/// Run `work` on a blocking thread as task `id`, enforcing `deadline` when
+/// given.
+///
+/// `work` receives the task's cancellation flag and must poll it. When the
+/// deadline passes the flag is set, the outcome is recorded as `Timeout`
+/// and this function returns without waiting for the worker to notice.
+pub async fn run_bulk_task<F>(
+	registry: Arc<BulkTaskRegistry>,
+	id: String,
+	deadline: Option<Duration>,
+	work: F,
+) -> BulkOutcome
+where
+	F: FnOnce(&AtomicBool) -> BulkOutcome + Send + 'static,
+{
+	let cancel = registry.register(&id);
+	let flag = cancel.clone();
+	let handle = tokio::task::spawn_blocking(move || work(&flag));
+
+	let joined = match deadline {
+		Some(limit) => match tokio::time::timeout(limit, handle).await {
+			Ok(joined) => joined,
+			Err(_) => {
+				cancel.store(true, Ordering::Relaxed);
+				tracing::warn!(
+					task = %id,
+					deadline_ms = limit.as_millis() as u64,
+					"bulk processing exceeded deadline; aborting"
+				);
+				registry.finish(&id, BulkOutcome::Timeout);
+				return BulkOutcome::Timeout;
+			}
+		},
+		None => handle.await,
+	};
+
+	let outcome = joined.unwrap_or_else(|e| {
+		tracing::error!(task = %id, error = %e, "bulk processing task failed");
+		BulkOutcome::Failed
+	});
+	registry.finish(&id, outcome);
+	outcome
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn cancel_only_affects_running_tasks() {
+		let registry = BulkTaskRegistry::new();
+		let flag = registry.register("a");
+		assert_eq!(registry.outcome("a"), Some(BulkOutcome::Running));
+		assert!(registry.cancel("a"));
+		assert!(flag.load(Ordering::Relaxed));
+
+		registry.finish("a", BulkOutcome::Cancelled);
+		assert!(!registry.cancel("a"));
+		assert!(!registry.cancel("missing"));
+		assert_eq!(registry.outcome("a"), Some(BulkOutcome::Cancelled));
+	}
+
+	#[test]
+	fn finished_tasks_are_bounded() {
+		let registry = BulkTaskRegistry::new();
+		for i in 0..MAX_FINISHED + 5 {
+			let id = i.to_string();
+			registry.register(&id);
+			registry.finish(&id, BulkOutcome::Completed);
+		}
+		assert_eq!(registry.outcome("0"), None);
+		assert_eq!(
+			registry.outcome(&(MAX_FINISHED + 4).to_string()),
+			Some(BulkOutcome::Completed)
+		);
+	}
+}
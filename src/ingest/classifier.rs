@@ -0,0 +1,208 @@
+//! Pluggable field-kind classification.
+//!
+//! Ingest takes each record's field type from the input. Deployments that
+//! need to recognize domain-specific identifiers (asset IDs, ticket numbers,
+//! tokens) register [`FieldClassifier`]s in a [`FieldClassifiers`] chain on
+//! `AppState`; the chain is consulted for every value before the declared
+//! type is used, and the resulting kind is scored with
+//! [`detection_confidence`].
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::ingest::NormalizedRecord;
+use crate::ingest::bulk_normalizer::{TrimRules, canonicalize};
+use crate::lib::normalizers::{NormalizerError, normalize};
+
+/// Confidence of a value whose kind has no normalizer and is kept as text.
+pub const TEXT_CONFIDENCE: f32 = 0.5;
+
+/// Confidence of a value the normalizer for its kind rejects.
+pub const UNPARSED_CONFIDENCE: f32 = 0.2;
+
+/// How certain it is that `value` is of kind `field_type`: the normalizer's
+/// own confidence when it accepts the value, [`TEXT_CONFIDENCE`] for kinds
+/// without a normalizer and [`UNPARSED_CONFIDENCE`] when it rejects it.
+pub fn detection_confidence(field_type: &str, value: &str) -> f32 {
+	match normalize(field_type, value) {
+		Ok(normalized) => normalized.confidence(),
+		Err(NormalizerError::UnsupportedKind(_)) => TEXT_CONFIDENCE,
+		Err(_) => UNPARSED_CONFIDENCE,
+	}
+}
+
+/// Kind of a field value; stored as the record's `field_type`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldKind {
+	Domain,
+	Ip,
+	Email,
+	Hash,
+	Phone,
+	Username,
+	/// A deployment-specific kind, e.g. `jwt` or `asset_id`.
+	Custom(String),
+}
+
+impl FieldKind {
+	/// The `field_type` string for this kind.
+	pub fn as_str(&self) -> &str {
+		match self {
+			FieldKind::Domain => "domain",
+			FieldKind::Ip => "ip",
+			FieldKind::Email => "email",
+			FieldKind::Hash => "hash",
+			FieldKind::Phone => "phone",
+			FieldKind::Username => "username",
+			FieldKind::Custom(name) => name,
+		}
+	}
+
+	/// Parse a `field_type`, mapping unknown types to `Custom`.
+	pub fn from_field_type(field_type: &str) -> Self {
+		match field_type.trim().to_lowercase().as_str() {
+			"domain" => FieldKind::Domain,
+			"ip" => FieldKind::Ip,
+			"email" => FieldKind::Email,
+			"hash" => FieldKind::Hash,
+			"phone" => FieldKind::Phone,
+			"username" => FieldKind::Username,
+			other => FieldKind::Custom(other.to_string()),
+		}
+	}
+}
+
+impl fmt::Display for FieldKind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+/// Recognizes values of one or more field kinds.
+pub trait FieldClassifier: Send + Sync {
+	/// The kind of `value`, or `None` to defer to the next classifier.
+	fn classify(&self, value: &str) -> Option<FieldKind>;
+}
+
+/// Ordered chain of classifiers; the first match wins.
+#[derive(Clone, Default)]
+pub struct FieldClassifiers {
+	chain: Vec<Arc<dyn FieldClassifier>>,
+}
+
+impl FieldClassifiers {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Append `classifier` to the end of the chain.
+	pub fn with_classifier(mut self, classifier: impl FieldClassifier + 'static) -> Self {
+		self.chain.push(Arc::new(classifier));
+		self
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.chain.is_empty()
+	}
+
+	/// Kind reported by the first classifier that recognizes `value`.
+	pub fn classify(&self, value: &str) -> Option<FieldKind> {
+		self.chain.iter().find_map(|c| c.classify(value))
+	}
+
+	/// Relabel `rec` with the kind of its raw value (its canonical value when
+	/// it has no raw one), recompute the canonical form and score the
+	/// record's confidence. Records no classifier recognizes keep their
+	/// declared field type.
+	pub fn apply(&self, rec: &mut NormalizedRecord, trim: &TrimRules) {
+		if let Some(kind) = self.classify(classified_value(rec)) {
+			let field_type = kind.as_str().trim().to_lowercase();
+			if !field_type.is_empty() && field_type != rec.field_type {
+				if !rec.raw.is_empty() {
+					rec.canonical = canonicalize(&field_type, &rec.raw, trim);
+				}
+				rec.field_type = field_type;
+			}
+		}
+		rec.confidence = detection_confidence(&rec.field_type, classified_value(rec));
+	}
+}
+
+/// The value classification looks at: the raw value, or the canonical one
+/// for records without a raw value.
+fn classified_value(rec: &NormalizedRecord) -> &str {
+	if rec.raw.is_empty() {
+		&rec.canonical
+	} else {
+		&rec.raw
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	struct Prefix(&'static str, &'static str);
+
+	impl FieldClassifier for Prefix {
+		fn classify(&self, value: &str) -> Option<FieldKind> {
+			value
+				.starts_with(self.0)
+				.then(|| FieldKind::from_field_type(self.1))
+		}
+	}
+
+	#[test]
+	fn first_matching_classifier_wins() {
+		let chain = FieldClassifiers::new()
+			.with_classifier(Prefix("INC", "ticket"))
+			.with_classifier(Prefix("IN", "Domain"));
+		assert_eq!(
+			chain.classify("INC0042"),
+			Some(FieldKind::Custom("ticket".to_string()))
+		);
+		assert_eq!(chain.classify("INTERNAL"), Some(FieldKind::Domain));
+		assert_eq!(chain.classify("other"), None);
+	}
+
+	#[test]
+	fn apply_relabels_and_recanonicalizes() {
+		let chain = FieldClassifiers::new().with_classifier(Prefix("asset-", "asset_id"));
+		let trim = TrimRules::default();
+
+		let mut rec = NormalizedRecord::new("username", "asset-7F ", "asset-7f");
+		chain.apply(&mut rec, &trim);
+		assert_eq!(rec.field_type, "asset_id");
+		assert_eq!(rec.canonical, canonicalize("asset_id", "asset-7F ", &trim));
+
+		let mut rec = NormalizedRecord::new("domain", "Example.COM", "example.com");
+		chain.apply(&mut rec, &trim);
+		assert_eq!(rec.field_type, "domain");
+		assert_eq!(rec.canonical, "example.com");
+	}
+
+	#[test]
+	fn apply_scores_detection_confidence() {
+		let chain = FieldClassifiers::new();
+		let trim = TrimRules::default();
+		let scored = |field_type: &str, raw: &str| {
+			let mut rec =
+				NormalizedRecord::new(field_type, raw, canonicalize(field_type, raw, &trim));
+			chain.apply(&mut rec, &trim);
+			rec.confidence
+		};
+
+		assert_eq!(scored("ip", "192.0.2.1"), 1.0);
+		assert_eq!(scored("timestamp", "2024-01-15T11:30:00Z"), 1.0);
+		// An integer could be a Unix timestamp or any other number.
+		let epoch = scored("timestamp", "1705318200");
+		assert!(epoch > UNPARSED_CONFIDENCE && epoch < 1.0, "{}", epoch);
+		assert_eq!(scored("ticket", "INC0042"), TEXT_CONFIDENCE);
+		assert_eq!(scored("ip", "not-an-ip"), UNPARSED_CONFIDENCE);
+
+		// Records without a raw value are scored on their canonical one.
+		let mut rec = NormalizedRecord::new("ip", "", "10.0.0.1");
+		chain.apply(&mut rec, &trim);
+		assert_eq!(rec.confidence, 1.0);
+	}
+}
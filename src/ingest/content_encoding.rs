@@ -0,0 +1,126 @@
+//! Transparent decoding of compressed request bodies.
+//!
+//! Ingest handlers parse the body as NDJSON or a raw dump, so a client
+//! sending `Content-Encoding: gzip` would otherwise have its compressed bytes
+//! parsed as binary. [`decompression_layer`] decodes gzip, deflate and zstd
+//! bodies before they reach the handlers and caps the decoded size, so a
+//! small compressed payload cannot expand without bound. Unsupported
+//! encodings are rejected with 415.
+
+use tower::layer::util::Stack;
+use tower_http::decompression::RequestDecompressionLayer;
+use tower_http::limit::RequestBodyLimitLayer;
+
+/// Decodes `Content-Encoding` request bodies, limiting the decoded body to
+/// a configured number of bytes.
+pub type DecompressionLayer = Stack<RequestBodyLimitLayer, RequestDecompressionLayer>;
+
+/// Build the decoding layer. Bodies that decode to more than
+/// `max_decompressed_bytes` fail with a length-limit error when read.
+pub fn decompression_layer(max_decompressed_bytes: usize) -> DecompressionLayer {
+	Stack::new(
+		RequestBodyLimitLayer::new(max_decompressed_bytes),
+		RequestDecompressionLayer::new().no_br(),
+	)
+}
+
+/// Whether `err`, or any error it wraps, is a body length-limit error.
+pub fn is_length_limit(err: &(dyn std::error::Error + 'static)) -> bool {
+	let mut current = Some(err);
+	while let Some(e) = current {
+		if e.is::<http_body_util::LengthLimitError>() {
+			return true;
+		}
+		current = e.source();
+	}
+	false
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use axum::Router;
+	use axum::body::Body;
+	use axum::http::{Request, StatusCode, header};
+	use axum::routing::post;
+	use flate2::Compression;
+	use flate2::write::{GzEncoder, ZlibEncoder};
+	use http_body_util::BodyExt;
+	use std::io::Write;
+	use tower::{ServiceBuilder, ServiceExt};
+
+	const NDJSON: &str = "{\"field_type\":\"domain\",\"value\":\"Example.COM\"}\n\
+		{\"field_type\":\"ip\",\"value\":\"10.0.0.1\"}\n";
+
+	/// POST `body` to the NDJSON endpoint behind the decoding layer.
+	async fn upload(
+		body: Vec<u8>,
+		encoding: Option<&str>,
+		max_decompressed_bytes: usize,
+	) -> (StatusCode, Vec<u8>) {
+		let app = Router::new()
+			.route("/ingest/ndjson", post(crate::ingest::ndjson_upload))
+			.with_state(crate::ingest::test_utils::create_test_app_state());
+		let svc = ServiceBuilder::new()
+			.layer(decompression_layer(max_decompressed_bytes))
+			.service(app);
+
+		let mut req = Request::builder().method("POST").uri("/ingest/ndjson");
+		if let Some(encoding) = encoding {
+			req = req.header(header::CONTENT_ENCODING, encoding);
+		}
+		let resp = svc
+			.oneshot(req.body(Body::from(body)).unwrap())
+			.await
+			.unwrap();
+		let status = resp.status();
+		let bytes = resp.into_body().collect().await.unwrap().to_bytes();
+		(status, bytes.to_vec())
+	}
+
+	fn gzip(data: &[u8]) -> Vec<u8> {
+		let mut enc = GzEncoder::new(Vec::new(), Compression::default());
+		enc.write_all(data).unwrap();
+		enc.finish().unwrap()
+	}
+
+	#[tokio::test]
+	async fn gzip_body_parses_like_plaintext() {
+		let (plain_status, plain) = upload(NDJSON.as_bytes().to_vec(), None, 1 << 20).await;
+		let (gz_status, gz) = upload(gzip(NDJSON.as_bytes()), Some("gzip"), 1 << 20).await;
+
+		assert_eq!(plain_status, StatusCode::OK);
+		assert_eq!(gz_status, StatusCode::OK);
+		assert_eq!(gz, plain);
+		let records: Vec<crate::ingest::NormalizedRecord> = serde_json::from_slice(&gz).unwrap();
+		assert_eq!(records.len(), 2);
+	}
+
+	#[tokio::test]
+	async fn deflate_body_is_decoded() {
+		let mut enc = ZlibEncoder::new(Vec::new(), Compression::default());
+		enc.write_all(NDJSON.as_bytes()).unwrap();
+		let (status, body) = upload(enc.finish().unwrap(), Some("deflate"), 1 << 20).await;
+
+		assert_eq!(status, StatusCode::OK);
+		let records: Vec<crate::ingest::NormalizedRecord> = serde_json::from_slice(&body).unwrap();
+		assert_eq!(records.len(), 2);
+	}
+
+	#[tokio::test]
+	async fn oversized_decompression_is_rejected() {
+		// ~1 MiB of NDJSON compresses to a few KiB.
+		let big = NDJSON.repeat(1 << 14);
+		let compressed = gzip(big.as_bytes());
+		assert!(compressed.len() < 64 * 1024);
+
+		let (status, _) = upload(compressed, Some("gzip"), 64 * 1024).await;
+		assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+	}
+
+	#[tokio::test]
+	async fn unsupported_encoding_is_rejected() {
+		let (status, _) = upload(NDJSON.as_bytes().to_vec(), Some("br"), 1 << 20).await;
+		assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+	}
+}
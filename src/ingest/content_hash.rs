@@ -0,0 +1,76 @@
+//! SHA-256 of uploaded content, computed while it streams in.
+//!
+//! Every ingest endpoint hashes an upload's bytes as received, so a
+//! compressed dump is hashed before it is decompressed and the same file
+//! gets the same hash whichever endpoint it was sent to. Clients can use
+//! the hash for idempotency and deduplication; it is returned in the
+//! [`CONTENT_SHA256_HEADER`] response header and, for endpoints replying
+//! with a JSON object, as its `content_sha256` field.
+
+use axum::http::HeaderValue;
+use sha2::{Digest, Sha256};
+
+/// Response header carrying the lowercase hex SHA-256 of the upload.
+pub const CONTENT_SHA256_HEADER: &str = "x-content-sha256";
+
+/// Hashes an upload chunk by chunk.
+#[derive(Clone, Default)]
+pub struct ContentHasher {
+	hasher: Sha256,
+	bytes: u64,
+}
+
+impl ContentHasher {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Hash the next chunk of the upload.
+	pub fn update(&mut self, chunk: &[u8]) {
+		self.hasher.update(chunk);
+		self.bytes += chunk.len() as u64;
+	}
+
+	/// Bytes hashed so far.
+	pub fn bytes(&self) -> u64 {
+		self.bytes
+	}
+
+	/// Lowercase hex SHA-256 of everything hashed.
+	pub fn finish(self) -> String {
+		format!("{:x}", self.hasher.finalize())
+	}
+}
+
+/// Lowercase hex SHA-256 of a whole upload.
+pub fn content_sha256(content: &[u8]) -> String {
+	let mut hasher = ContentHasher::new();
+	hasher.update(content);
+	hasher.finish()
+}
+
+/// Report `sha256` in the [`CONTENT_SHA256_HEADER`] of `resp`.
+pub fn set_header(resp: &mut axum::response::Response, sha256: &str) {
+	if let Ok(value) = HeaderValue::from_str(sha256) {
+		resp.headers_mut().insert(CONTENT_SHA256_HEADER, value);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn chunked_and_whole_hashes_agree() {
+		let mut hasher = ContentHasher::new();
+		for chunk in [&b"a,b\n"[..], b"", b"1,2\n"] {
+			hasher.update(chunk);
+		}
+		assert_eq!(hasher.bytes(), 8);
+		assert_eq!(hasher.finish(), content_sha256(b"a,b\n1,2\n"));
+		assert_eq!(
+			content_sha256(b""),
+			"e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+		);
+	}
+}
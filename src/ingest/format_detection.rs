@@ -1,4 +1,19 @@
 use anyhow::Result;
+use std::borrow::Cow;
+
+/// Delimiters considered by [`sniff_delimiter`], in order of preference.
+pub const DELIMITER_CANDIDATES: [u8; 4] = [b',', b'\t', b'|', b';'];
+
+/// Lines sampled by [`sniff_delimiter`].
+const SNIFF_LINES: usize = 20;
+
+/// Bytes of a peek examined by [`classify_json`] unless configured
+/// otherwise; see `Settings.json_detect_sample_bytes`.
+pub const DEFAULT_JSON_SAMPLE_BYTES: usize = 64 * 1024;
+
+/// Bytes of a peek that line-based detection heuristics and previews look
+/// at, however much of the dump was read.
+pub const DETECT_SCAN_BYTES: usize = 8 * 1024;
 
 /// Detected format type
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -15,6 +30,18 @@ pub enum FormatType {
 }
 
 impl FormatType {
+	/// Formats ingest parses into records.
+	pub const PARSED: [FormatType; 5] = [
+		FormatType::Csv,
+		FormatType::Tsv,
+		FormatType::Ndjson,
+		FormatType::Json,
+		FormatType::Xlsx,
+	];
+
+	/// Compression formats unwrapped before parsing.
+	pub const CODECS: [FormatType; 2] = [FormatType::Gzip, FormatType::Zip];
+
 	pub fn as_str(&self) -> &str {
 		match self {
 			FormatType::Csv => "csv",
@@ -94,32 +121,120 @@ pub fn detect_format(peek: &[u8], hint: Option<&str>) -> Result<(FormatType, boo
 
 	// Check for JSON/NDJSON
 	if text_trim.starts_with('{') || text_trim.starts_with('[') {
-		let lines: Vec<&str> = text.lines().collect();
-		if lines.len() > 1 {
-			// Check if it's NDJSON (multiple lines starting with { or [)
-			let ndjson_like = lines.iter().all(|l| {
-				l.trim().is_empty() || l.trim().starts_with('{') || l.trim().starts_with('[')
-			});
-			if ndjson_like {
-				return Ok((FormatType::Ndjson, false));
-			}
-		}
-		return Ok((FormatType::Json, false));
+		return Ok((classify_json(&text, DEFAULT_JSON_SAMPLE_BYTES), false));
 	}
 
-	// Check for CSV/TSV
-	let first_line = text.lines().next().unwrap_or("");
-	if first_line.contains(',') {
-		return Ok((FormatType::Csv, false));
-	}
-	if first_line.contains('\t') {
-		return Ok((FormatType::Tsv, false));
+	// Check for delimited text; pipe and semicolon files are read as CSV
+	// with the sniffed delimiter
+	match sniff_delimiter(peek) {
+		Some(b'\t') => return Ok((FormatType::Tsv, false)),
+		Some(_) => return Ok((FormatType::Csv, false)),
+		None => {}
 	}
 
 	// Default to text
 	Ok((FormatType::Text, false))
 }
 
+/// The first `max` bytes of `peek` as text, borrowed rather than copied
+/// when they are valid UTF-8. A multi-byte character cut off by the limit
+/// is dropped rather than replaced.
+pub fn text_head(peek: &[u8], max: usize) -> Cow<'_, str> {
+	let head = &peek[..peek.len().min(max)];
+	match std::str::from_utf8(head) {
+		Ok(text) => Cow::Borrowed(text),
+		// Only the last character is incomplete.
+		Err(e) if e.error_len().is_none() => {
+			Cow::Borrowed(std::str::from_utf8(&head[..e.valid_up_to()]).unwrap_or_default())
+		}
+		Err(_) => String::from_utf8_lossy(head),
+	}
+}
+
+/// Tell JSON from NDJSON in text starting with `{` or `[`, looking at its
+/// first `sample_bytes` bytes.
+///
+/// A sample that parses as one JSON value is JSON, however it is laid out.
+/// Otherwise it is NDJSON when a complete value is followed, on a later
+/// line, by another one, which may be cut off by the end of the sample.
+/// Anything else, such as a pretty-printed document longer than the
+/// sample, is JSON.
+pub fn classify_json(text: &str, sample_bytes: usize) -> FormatType {
+	let mut end = text.len().min(sample_bytes);
+	while !text.is_char_boundary(end) {
+		end -= 1;
+	}
+	let sample = &text[..end];
+	if serde_json::from_str::<serde_json::Value>(sample).is_ok() {
+		return FormatType::Json;
+	}
+
+	let mut values = serde_json::Deserializer::from_str(sample).into_iter::<serde_json::Value>();
+	match values.next() {
+		Some(Ok(_)) => {}
+		_ => return FormatType::Json,
+	}
+	let rest = &sample[values.byte_offset()..];
+	let next = rest.trim_start();
+	let gap = &rest[..rest.len() - next.len()];
+	if gap.contains('\n') && (next.starts_with('{') || next.starts_with('[')) {
+		FormatType::Ndjson
+	} else {
+		FormatType::Json
+	}
+}
+
+/// Guess the field delimiter of delimited text from its first lines.
+///
+/// Each candidate in [`DELIMITER_CANDIDATES`] is counted per line outside
+/// double quotes. The candidate splitting the most lines into as many
+/// fields as the header wins; ties go to the one producing more fields,
+/// then to the earlier candidate. Returns `None` when the header contains
+/// no candidate.
+pub fn sniff_delimiter(sample: &[u8]) -> Option<u8> {
+	let mut lines: Vec<&[u8]> = sample
+		.split(|b| *b == b'\n')
+		.filter(|l| !l.iter().all(u8::is_ascii_whitespace))
+		.take(SNIFF_LINES + 1)
+		.collect();
+	// The sample may end mid-line; only judge complete lines.
+	if lines.len() > 1 && !sample.ends_with(b"\n") {
+		lines.pop();
+	}
+	lines.truncate(SNIFF_LINES);
+	let (header, rows) = lines.split_first()?;
+
+	let mut best: Option<(usize, usize, u8)> = None;
+	for delimiter in DELIMITER_CANDIDATES {
+		let splits = count_outside_quotes(header, delimiter);
+		if splits == 0 {
+			continue;
+		}
+		let consistent = rows
+			.iter()
+			.filter(|row| count_outside_quotes(row, delimiter) == splits)
+			.count();
+		if best.is_none_or(|(c, s, _)| (consistent, splits) > (c, s)) {
+			best = Some((consistent, splits, delimiter));
+		}
+	}
+	best.map(|(_, _, delimiter)| delimiter)
+}
+
+/// Occurrences of `delimiter` in `line` outside double-quoted fields.
+fn count_outside_quotes(line: &[u8], delimiter: u8) -> usize {
+	let mut quoted = false;
+	let mut count = 0;
+	for &b in line {
+		if b == b'"' {
+			quoted = !quoted;
+		} else if b == delimiter && !quoted {
+			count += 1;
+		}
+	}
+	count
+}
+
 fn is_printable(b: u8) -> bool {
 	match b {
 		0x09 | 0x0A | 0x0D => true, // tab, lf, cr
@@ -132,6 +247,24 @@ fn is_printable(b: u8) -> bool {
 mod tests {
 	use super::*;
 
+	#[test]
+	fn text_head_borrows_a_bounded_prefix() {
+		let long = "é".repeat(DETECT_SCAN_BYTES);
+		let head = text_head(long.as_bytes(), DETECT_SCAN_BYTES);
+		assert!(matches!(head, Cow::Borrowed(_)));
+		assert_eq!(head.len(), DETECT_SCAN_BYTES);
+
+		// A character split by the limit is dropped.
+		let head = text_head(long.as_bytes(), 5);
+		assert!(matches!(head, Cow::Borrowed(_)));
+		assert_eq!(head, "éé");
+
+		// Invalid bytes are replaced within the limit only.
+		let mut bad = vec![0xff; 2];
+		bad.resize(64 * 1024, b'a');
+		assert_eq!(text_head(&bad, 6), "\u{FFFD}\u{FFFD}aaaa");
+	}
+
 	#[test]
 	fn detect_gzip() {
 		let peek = [0x1f_u8, 0x8b_u8, 0x08, 0x00, 0x00];
@@ -164,6 +297,34 @@ mod tests {
 		assert!(!compressed);
 	}
 
+	#[test]
+	fn classify_json_handles_multiline_documents() {
+		let pretty = "{\n  \"a\": 1,\n  \"b\": [\n    2\n  ]\n}\n";
+		assert_eq!(
+			classify_json(pretty, DEFAULT_JSON_SAMPLE_BYTES),
+			FormatType::Json
+		);
+
+		let array = "[\n{\"a\":1},\n{\"b\":2}\n]\n";
+		assert_eq!(
+			classify_json(array, DEFAULT_JSON_SAMPLE_BYTES),
+			FormatType::Json
+		);
+		// An array longer than the sample is still JSON.
+		assert_eq!(classify_json(array, 10), FormatType::Json);
+
+		let ndjson = "{\"a\":1}\n[2]\n{\"c\":{\"d\":3}}\n";
+		assert_eq!(
+			classify_json(ndjson, DEFAULT_JSON_SAMPLE_BYTES),
+			FormatType::Ndjson
+		);
+		// A line cut off by the end of the sample does not matter.
+		assert_eq!(classify_json(ndjson, 10), FormatType::Ndjson);
+
+		// Values on one line are not newline-delimited.
+		assert_eq!(classify_json("{\"a\":1} {\"b\":2}", 64), FormatType::Json);
+	}
+
 	#[test]
 	fn detect_csv() {
 		let csv = b"col1,col2\n1,2\n";
@@ -180,6 +341,35 @@ mod tests {
 		assert!(!compressed);
 	}
 
+	#[test]
+	fn sniff_pipe_delimited() {
+		let psv = b"type|value|source\nip|10.0.0.1|fw, edge\ndomain|example.com|dns\n";
+		assert_eq!(sniff_delimiter(psv), Some(b'|'));
+		let (format, _) = detect_format(psv, None).expect("detect");
+		assert_eq!(format, FormatType::Csv);
+
+		let records = crate::ingest::parsers::parse_csv_stream(&psv[..], sniff_delimiter(psv))
+			.expect("parse psv");
+		assert_eq!(records.len(), 2);
+		assert_eq!(records[0].canonical, "10.0.0.1");
+	}
+
+	#[test]
+	fn sniff_semicolon_eu_csv() {
+		let eu = b"type;value;amount\nip;10.0.0.1;1.234,56\nemail;a@example.com;7,5\n";
+		assert_eq!(sniff_delimiter(eu), Some(b';'));
+		let (format, _) = detect_format(eu, None).expect("detect");
+		assert_eq!(format, FormatType::Csv);
+	}
+
+	#[test]
+	fn sniff_ignores_quoted_delimiters_and_truncated_lines() {
+		let csv = b"type,value\nemail,\"a|b;c\"\nip,10.0.0.1\nip|10";
+		assert_eq!(sniff_delimiter(csv), Some(b','));
+		assert_eq!(sniff_delimiter(b"tab\tseparated\n1\t2\n"), Some(b'\t'));
+		assert_eq!(sniff_delimiter(b"just some text\n"), None);
+	}
+
 	#[test]
 	fn detect_with_hint() {
 		let data = b"some data";
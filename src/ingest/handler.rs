@@ -4,14 +4,44 @@ use futures_util::StreamExt;
 use serde::Serialize;
 use std::fs::File as StdFile;
 use std::io::{BufRead, BufReader, Read};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
 use tokio::fs::File as TokioFile;
 use tokio::io::AsyncWriteExt;
-use tokio::sync::mpsc::error::TrySendError;
+
+use crate::audit::IngestOutcome;
+use crate::auth::Subject;
+use crate::ingest::bulk_tasks::{BulkOutcome, run_bulk_task};
+use crate::ingest::content_hash::{ContentHasher, content_sha256};
+use crate::ingest::format_detection::{DETECT_SCAN_BYTES, text_head};
+use crate::ingest::ip_policy::{PRIVATE_IPS_PARAM, PrivateIpFilter, PrivateIpPolicy};
+use crate::ingest::ndjson_splitter::{RecordSplitter, escape_embedded_newlines};
+use crate::ingest::resumable::{ContentRange, SessionStatus, UPLOAD_SESSION_HEADER};
+use crate::ingest::source::ingest_source;
+
+/// Response header listing the (1-based) NDJSON lines rejected for invalid
+/// UTF-8 under strict decoding; at most [`MAX_REPORTED_LINES`] are listed.
+pub const INVALID_UTF8_LINES_HEADER: &str = "x-invalid-utf8-lines";
+/// Response header listing the (1-based) NDJSON lines skipped for exceeding
+/// [`MAX_LINE_BYTES`]; at most [`MAX_REPORTED_LINES`] are listed.
+pub const OVERSIZED_LINES_HEADER: &str = "x-oversized-lines";
+const MAX_REPORTED_LINES: usize = 100;
+/// Response header saying how a `?sync=true` request was persisted: `sync`
+/// when written before the response, `async` when it was over
+/// `Settings.sync_ingest_max_bytes` and went through the batcher instead.
+pub const PERSIST_MODE_HEADER: &str = "x-persist-mode";
+/// Longest NDJSON line accepted; longer lines are skipped.
+const MAX_LINE_BYTES: usize = 10 * 1024 * 1024;
 
 /// A streaming HTTP handler that parses NDJSON from the request body without
 /// buffering the entire payload in memory. It reads body chunks, splits them
-/// on newlines, and normalizes each line as it arrives.
+/// at record boundaries, and normalizes each record as it arrives.
+///
+/// With `?sync=true` a body within `Settings.sync_ingest_max_bytes` is
+/// persisted before the response, which then carries the accepted
+/// `records` and their `persisted` confirmations; larger bodies are
+/// enqueued as usual. [`PERSIST_MODE_HEADER`] says which happened.
 #[tracing::instrument(skip(state, req), fields(endpoint = "ndjson"))]
 pub async fn ndjson_upload(
 	State(state): State<crate::state::AppState>,
@@ -19,71 +49,185 @@ pub async fn ndjson_upload(
 ) -> impl IntoResponse {
 	let start_time = Instant::now();
 	state.metrics.ingest_requests_total.inc();
+	if let Some(resp) = state.db_health.reject_if_down() {
+		return resp;
+	}
 
-	// Stream the request body and process NDJSON line-by-line to avoid
-	// buffering very large payloads in memory. We collect complete lines
-	// by scanning for '\n' in the incoming byte stream and hand each line
-	// to the permissive normalizer.
-
-	use regex::Regex;
-
+	// Stream the request body and process NDJSON record-by-record to avoid
+	// buffering very large payloads in memory. A record ends at a newline
+	// outside JSON strings and brackets, so newlines inside string values
+	// don't split it; each record goes to the permissive normalizer.
+
+	// Invalid UTF-8 is replaced with U+FFFD unless strict decoding is on,
+	// in which case such lines are rejected and reported.
+	let strict_utf8 = state.settings.strict_utf8 || query_flag(req.uri().query(), "strict_utf8");
+	let query = req.uri().query().map(str::to_string);
+	let policy = match private_ip_policy(&state, req.uri().query()) {
+		Ok(policy) => policy,
+		Err(resp) => return *resp,
+	};
+	let mut private_ips = PrivateIpFilter::new(policy, &state.metrics);
+	let source = ingest_source(
+		&state.settings,
+		req.headers(),
+		req.extensions().get::<Subject>(),
+	);
 	let mut stream = req.into_body().into_data_stream();
 	let mut buf: Vec<u8> = Vec::new();
+	let mut splitter = RecordSplitter::new();
 	let mut records: Vec<crate::ingest::NormalizedRecord> = Vec::new();
-	let punct_re = Regex::new(r"^[\W_]+|[\W_]+$").unwrap();
+	// Original lines, index-aligned with `records`; only kept when the
+	// encrypted raw-payload store is enabled.
+	let mut raw_lines: Vec<String> = Vec::new();
+	let keep_raw = state.raw_store.is_some();
+	let trim = state.settings.trim_rules();
 	let mut total_bytes: usize = 0;
-
-	while let Some(chunk_res) = stream.next().await {
-		match chunk_res {
-			Ok(bytes_chunk) => {
-				let chunk = bytes_chunk.as_ref();
+	let mut content = ContentHasher::new();
+	// Non-empty lines the normalizer could not extract a record from.
+	let mut skipped: usize = 0;
+	// 1-based numbers of lines rejected for invalid UTF-8 in strict mode.
+	let mut line_no: usize = 0;
+	let mut invalid_utf8: Vec<usize> = Vec::new();
+	// 1-based numbers of lines longer than `MAX_LINE_BYTES`. While
+	// `discarding`, the rest of such a line is dropped up to its newline.
+	let mut oversized: Vec<usize> = Vec::new();
+	let mut discarding = false;
+
+	let mut ended = false;
+	while !ended {
+		match stream.next().await {
+			Some(Ok(chunk)) => {
 				total_bytes += chunk.len();
-				buf.extend_from_slice(chunk);
-
-				// Extract complete lines (terminated by '\n') and normalize each.
-				while let Some(pos) = buf.iter().position(|&b| b == b'\n') {
-					let mut line_bytes = buf.drain(..=pos).collect::<Vec<u8>>();
-					// remove trailing LF
-					if line_bytes.ends_with(&[b'\n']) {
-						line_bytes.pop();
-					}
-					// Optional: remove trailing CR if present
-					if line_bytes.ends_with(&[b'\r']) {
-						line_bytes.pop();
-					}
-
-					let line = String::from_utf8_lossy(&line_bytes);
-					if let Some(rec) = crate::ingest::normalize_ndjson_line(&line, &punct_re) {
-						records.push(rec);
-					}
-				}
-
-				// Safety: guard against pathological single-line sizes
-				if buf.len() > 10 * 1024 * 1024 {
-					state.metrics.ingest_errors_total.inc();
-					return (
-						StatusCode::BAD_REQUEST,
-						"line too long or streaming malformed",
-					)
-						.into_response();
-				}
+				content.update(&chunk);
+				buf.extend_from_slice(&chunk);
 			}
-			Err(e) => {
+			Some(Err(e)) => {
 				state.metrics.ingest_errors_total.inc();
 				return (
-					StatusCode::BAD_REQUEST,
+					body_read_status(&e),
 					format!("failed to read request body: {}", e),
 				)
 					.into_response();
 			}
+			None => ended = true,
+		}
+
+		// Extract complete records and normalize each. At the end of the
+		// body, a last record that ran on past a truncated line is split
+		// there too.
+		while let Some(pos) = match splitter.next_boundary(&buf) {
+			None if ended => splitter.finish(&buf),
+			boundary => boundary,
+		} {
+			if discarding {
+				// End of an oversized line that was already reported.
+				buf.drain(..=pos);
+				discarding = false;
+				continue;
+			}
+			if pos > MAX_LINE_BYTES {
+				line_no += 1;
+				skip_oversized_line(&state, line_no, &mut oversized);
+				buf.drain(..=pos);
+				continue;
+			}
+			let mut line_bytes = buf.drain(..=pos).collect::<Vec<u8>>();
+			// remove trailing LF
+			if line_bytes.ends_with(&[b'\n']) {
+				line_bytes.pop();
+			}
+			// Optional: remove trailing CR if present
+			if line_bytes.ends_with(&[b'\r']) {
+				line_bytes.pop();
+			}
+
+			line_no += 1;
+			let line_bytes = escape_embedded_newlines(&line_bytes);
+			let Some(line) = decode_line(&line_bytes, strict_utf8) else {
+				invalid_utf8.push(line_no);
+				continue;
+			};
+			if let Some(mut rec) = crate::ingest::normalize_ndjson_line(&line, &trim) {
+				state.classifiers.apply(&mut rec, &trim);
+				// Card numbers never reach the response, raw store or sinks.
+				let masked = rec.mask_pan();
+				if private_ips.admit(&rec) {
+					if keep_raw {
+						raw_lines.push(if masked {
+							rec.raw.clone()
+						} else {
+							line.into_owned()
+						});
+					}
+					records.push(rec);
+				}
+			} else if !line.trim().is_empty() {
+				skipped += 1;
+			}
+		}
+
+		// Skip a pathological single line instead of buffering it;
+		// records already parsed are kept.
+		if discarding {
+			buf.clear();
+			splitter.discard_buffered();
+		} else if buf.len() > MAX_LINE_BYTES {
+			line_no += 1;
+			skip_oversized_line(&state, line_no, &mut oversized);
+			buf.clear();
+			splitter.discard_buffered();
+			discarding = true;
 		}
 	}
 
 	// Process any trailing data after stream end
 	if !buf.is_empty() {
-		let line = String::from_utf8_lossy(&buf);
-		if let Some(rec) = crate::ingest::normalize_ndjson_line(&line, &punct_re) {
-			records.push(rec);
+		line_no += 1;
+		match decode_line(&escape_embedded_newlines(&buf), strict_utf8) {
+			Some(line) => {
+				if let Some(mut rec) = crate::ingest::normalize_ndjson_line(&line, &trim) {
+					state.classifiers.apply(&mut rec, &trim);
+					// Card numbers never reach the response, raw store or sinks.
+					let masked = rec.mask_pan();
+					if private_ips.admit(&rec) {
+						if keep_raw {
+							raw_lines.push(if masked {
+								rec.raw.clone()
+							} else {
+								line.into_owned()
+							});
+						}
+						records.push(rec);
+					}
+				} else if !line.trim().is_empty() {
+					skipped += 1;
+				}
+			}
+			None => invalid_utf8.push(line_no),
+		}
+	}
+	if !invalid_utf8.is_empty() {
+		state
+			.metrics
+			.ingest_invalid_utf8_total
+			.inc_by(invalid_utf8.len() as u64);
+		tracing::warn!(lines = ?invalid_utf8, "rejected NDJSON lines with invalid UTF-8");
+	}
+
+	// Keep encrypted copies of the original lines before anything is
+	// persisted so a failure here never leaves graph records without them.
+	if let Some(ref store) = state.raw_store {
+		let items = records
+			.iter()
+			.map(|r| r.canonical.as_str())
+			.zip(raw_lines.iter().map(String::as_str));
+		if let Err(e) = store.store_many(items) {
+			state.metrics.ingest_errors_total.inc();
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				format!("failed to store raw payloads: {}", e),
+			)
+				.into_response();
 		}
 	}
 
@@ -94,66 +238,53 @@ pub async fn ndjson_upload(
 		.ingest_records_total
 		.inc_by(records.len() as u64);
 
-	// Enqueue normalized records to the background batcher. If the
-	// persistence channel is full or closed we'll fall back to performing
-	// the persistence synchronously to avoid data loss.
-	let sender = state.persist_sender.clone();
-	for rec in &records {
-		// Apply PII policy to the raw value if PII engine is configured.
-		// The raw value will be transformed according to the policy (scrub/hash/encrypt).
-		let raw_value = if let Some(ref engine) = state.pii_engine {
-			match engine.apply_policy(&rec.field_type, &rec.raw) {
-				Ok(protected) => protected,
-				Err(e) => {
-					eprintln!("PII policy application failed for {}: {}", rec.field_type, e);
-					// Fall back to scrubbing on error
-					"[REDACTED]".to_string()
-				}
+	let sync = sync_persist(&state, query.as_deref(), total_bytes);
+	let persisted =
+		match persist_records(&state, &records, start_time, sync == Some(true), source).await {
+			Ok(persisted) => persisted,
+			Err(e) => {
+				return (
+					StatusCode::INTERNAL_SERVER_ERROR,
+					format!("failed to persist record: {}", e),
+				)
+					.into_response();
 			}
-		} else {
-			rec.raw.clone()
-		};
-
-		// Only persist sanitized/normalized properties. Store the canonical
-		// value as the merge key and the PII-protected raw value.
-		let props = serde_json::json!({
-			"field_type": rec.field_type,
-			"raw": raw_value,
-		});
-
-		let job = crate::persist::PersistJob {
-			label: "FieldValue".to_string(),
-			key: rec.canonical.clone(),
-			props: props.clone(),
 		};
 
-		match crate::persist::submit_job(&sender, job.clone(), &state.metrics) {
-			Ok(()) => {}
-			Err(TrySendError::Full(returned)) | Err(TrySendError::Closed(returned)) => {
-				// Channel unavailable; persist synchronously using the
-				// normalized/sanitized job we received back from the channel.
-				if let Err(e) = state
-					.repo
-					.merge_entity(&returned.label, &returned.key, &returned.props)
-					.await
-				{
-					state.metrics.ingest_errors_total.inc();
-					return (
-						StatusCode::INTERNAL_SERVER_ERROR,
-						format!("failed to persist record: {}", e),
-					)
-						.into_response();
-				}
-			}
-		}
-	}
-
 	// Record ingest duration
 	let duration = start_time.elapsed().as_secs_f64();
 	state.metrics.ingest_duration_seconds.observe(duration);
-
-	match serde_json::to_string(&records) {
-		Ok(body) => (StatusCode::OK, body).into_response(),
+	log_ingest_outcome("ndjson", "ndjson", total_bytes, records.len(), skipped, start_time);
+
+	// Records persisted before the response are listed with their keys.
+	let body = if sync == Some(true) {
+		serde_json::to_string(&serde_json::json!({
+			"records": records,
+			"persisted": persisted,
+		}))
+	} else {
+		serde_json::to_string(&records)
+	};
+	match body {
+		Ok(body) => {
+			let mut resp = (StatusCode::OK, body).into_response();
+			set_persist_mode(&mut resp, sync);
+			for (header, lines) in [
+				(INVALID_UTF8_LINES_HEADER, &invalid_utf8),
+				(OVERSIZED_LINES_HEADER, &oversized),
+			] {
+				if let Some(value) = line_list_header(lines) {
+					resp.headers_mut().insert(header, value);
+				}
+			}
+			crate::ingest::content_hash::set_header(&mut resp, &content.finish());
+			private_ips.report("ndjson", &mut resp);
+			IngestOutcome {
+				records: records.len(),
+				dump_id: None,
+			}
+			.attach(resp)
+		}
 		Err(e) => {
 			state.metrics.ingest_errors_total.inc();
 			(
@@ -165,82 +296,14 @@ pub async fn ndjson_upload(
 	}
 }
 
-#[cfg(feature = "ingest-tests")]
+#[cfg(all(test, feature = "ingest-tests"))]
 mod tests {
+	use crate::ingest::test_utils::create_test_app_state;
 	use axum::extract::State;
 	use axum::response::IntoResponse;
-	use std::sync::Arc;
-	use tokio::sync::mpsc;
 
 	#[tokio::test]
 	async fn handler_accepts_ndjson_stream() {
-		use std::sync::Arc;
-		use tokio::sync::mpsc;
-
-		struct DummyRepo;
-		#[async_trait::async_trait]
-		impl crate::age_client::AgeRepo for DummyRepo {
-			async fn merge_entity(
-				&self,
-				_label: &str,
-				_key: &str,
-				_props: &serde_json::Value,
-			) -> anyhow::Result<()> {
-				Ok(())
-			}
-
-			async fn ping(&self) -> anyhow::Result<()> {
-				Ok(())
-			}
-
-			async fn merge_batch(
-				&self,
-				_items: &[(String, String, serde_json::Value)],
-			) -> anyhow::Result<()> {
-				Ok(())
-			}
-
-			async fn persist_row(
-				&self,
-				_dump_id: &str,
-				_row_index: i64,
-				_row_hash: Option<&str>,
-				_cells: &[(String, String, String, String)],
-				_timestamp: &str,
-			) -> anyhow::Result<()> {
-				Ok(())
-			}
-
-			async fn increment_co_occurrence(
-				&self,
-				_a_key: &str,
-				_b_key: &str,
-				_timestamp: &str,
-			) -> anyhow::Result<()> {
-				Ok(())
-			}
-
-			async fn persist_credential(
-				&self,
-				_from_key: &str,
-				_to_key: &str,
-				_timestamp: &str,
-			) -> anyhow::Result<()> {
-				Ok(())
-			}
-
-			async fn apply_migration(&self, _sql_content: &str) -> anyhow::Result<()> {
-				Ok(())
-			}
-		}
-
-		let (tx, _rx) = mpsc::channel(16);
-		let repo: Arc<dyn crate::age_client::AgeRepo> = Arc::new(DummyRepo);
-		let app_state = crate::state::AppState {
-			repo,
-			persist_sender: tx,
-		};
-
 		let payload = r#"{"field_type":"domain","value":"Example.COM"}
 {"field_type":"email","value":"USER@EXAMPLE.COM"}
 "#;
@@ -251,41 +314,7 @@ mod tests {
 			.body(axum::body::Body::from(payload.to_string()))
 			.unwrap();
 
-		// Build test state
-		struct DummyRepo;
-		#[async_trait::async_trait]
-		impl crate::age_client::AgeRepo for DummyRepo {
-			async fn merge_entity(
-				&self,
-				_label: &str,
-				_key: &str,
-				_props: &serde_json::Value,
-			) -> anyhow::Result<()> {
-				Ok(())
-			}
-
-			async fn ping(&self) -> anyhow::Result<()> {
-				Ok(())
-			}
-
-			async fn merge_batch(
-				&self,
-				_items: &[(String, String, serde_json::Value)],
-			) -> anyhow::Result<()> {
-				Ok(())
-			}
-		}
-
-		let (tx, _rx) = mpsc::channel(16);
-		let repo: Arc<dyn crate::age_client::AgeRepo> = Arc::new(DummyRepo);
-		let metrics = Arc::new(crate::observability::MetricsRegistry::new());
-		let app_state = crate::state::AppState {
-			repo,
-			persist_sender: tx,
-			metrics,
-		};
-
-		let resp = super::ndjson_upload(State(app_state), req)
+		let resp = super::ndjson_upload(State(create_test_app_state()), req)
 			.await
 			.into_response();
 		assert_eq!(resp.status(), axum::http::StatusCode::OK);
@@ -307,74 +336,7 @@ mod tests {
 			.body(body)
 			.unwrap();
 
-		// Build a minimal AppState for the handler's State extractor
-		struct DummyRepo;
-		#[async_trait::async_trait]
-		impl crate::age_client::AgeRepo for DummyRepo {
-			async fn merge_entity(
-				&self,
-				_label: &str,
-				_key: &str,
-				_props: &serde_json::Value,
-			) -> anyhow::Result<()> {
-				Ok(())
-			}
-
-			async fn ping(&self) -> anyhow::Result<()> {
-				Ok(())
-			}
-
-			async fn merge_batch(
-				&self,
-				_items: &[(String, String, serde_json::Value)],
-			) -> anyhow::Result<()> {
-				Ok(())
-			}
-
-			async fn persist_row(
-				&self,
-				_dump_id: &str,
-				_row_index: i64,
-				_row_hash: Option<&str>,
-				_cells: &[(String, String, String, String)],
-				_timestamp: &str,
-			) -> anyhow::Result<()> {
-				Ok(())
-			}
-
-			async fn increment_co_occurrence(
-				&self,
-				_a_key: &str,
-				_b_key: &str,
-				_timestamp: &str,
-			) -> anyhow::Result<()> {
-				Ok(())
-			}
-
-			async fn persist_credential(
-				&self,
-				_from_key: &str,
-				_to_key: &str,
-				_timestamp: &str,
-			) -> anyhow::Result<()> {
-				Ok(())
-			}
-
-			async fn apply_migration(&self, _sql_content: &str) -> anyhow::Result<()> {
-				Ok(())
-			}
-		}
-
-		let (tx, _rx) = mpsc::channel(16);
-		let repo: Arc<dyn crate::age_client::AgeRepo> = Arc::new(DummyRepo);
-		let metrics = Arc::new(crate::observability::MetricsRegistry::new());
-		let app_state = crate::state::AppState {
-			repo,
-			persist_sender: tx,
-			metrics,
-		};
-
-		let resp = super::bulk_dump_upload(State(app_state), req)
+		let resp = super::bulk_dump_upload(State(create_test_app_state()), req)
 			.await
 			.into_response();
 		assert_eq!(resp.status(), axum::http::StatusCode::OK);
@@ -382,73 +344,6 @@ mod tests {
 
 	#[tokio::test]
 	async fn ndjson_streaming_chunked_lines() {
-		use std::sync::Arc;
-		use tokio::sync::mpsc;
-
-		struct DummyRepo;
-		#[async_trait::async_trait]
-		impl crate::age_client::AgeRepo for DummyRepo {
-			async fn merge_entity(
-				&self,
-				_label: &str,
-				_key: &str,
-				_props: &serde_json::Value,
-			) -> anyhow::Result<()> {
-				Ok(())
-			}
-
-			async fn ping(&self) -> anyhow::Result<()> {
-				Ok(())
-			}
-
-			async fn merge_batch(
-				&self,
-				_items: &[(String, String, serde_json::Value)],
-			) -> anyhow::Result<()> {
-				Ok(())
-			}
-
-			async fn persist_row(
-				&self,
-				_dump_id: &str,
-				_row_index: i64,
-				_row_hash: Option<&str>,
-				_cells: &[(String, String, String, String)],
-				_timestamp: &str,
-			) -> anyhow::Result<()> {
-				Ok(())
-			}
-
-			async fn increment_co_occurrence(
-				&self,
-				_a_key: &str,
-				_b_key: &str,
-				_timestamp: &str,
-			) -> anyhow::Result<()> {
-				Ok(())
-			}
-
-			async fn persist_credential(
-				&self,
-				_from_key: &str,
-				_to_key: &str,
-				_timestamp: &str,
-			) -> anyhow::Result<()> {
-				Ok(())
-			}
-
-			async fn apply_migration(&self, _sql_content: &str) -> anyhow::Result<()> {
-				Ok(())
-			}
-		}
-
-		let (tx, _rx) = mpsc::channel(16);
-		let repo: Arc<dyn crate::age_client::AgeRepo> = Arc::new(DummyRepo);
-		let app_state = crate::state::AppState {
-			repo,
-			persist_sender: tx,
-		};
-
 		// Simulate a NDJSON upload where a single JSON object is split across chunks
 		let s = futures_util::stream::iter(vec![
 			Ok::<_, std::io::Error>(b"{".to_vec()),
@@ -467,41 +362,7 @@ mod tests {
 			.body(body)
 			.unwrap();
 
-		// Build test state
-		struct DummyRepo;
-		#[async_trait::async_trait]
-		impl crate::age_client::AgeRepo for DummyRepo {
-			async fn merge_entity(
-				&self,
-				_label: &str,
-				_key: &str,
-				_props: &serde_json::Value,
-			) -> anyhow::Result<()> {
-				Ok(())
-			}
-
-			async fn ping(&self) -> anyhow::Result<()> {
-				Ok(())
-			}
-
-			async fn merge_batch(
-				&self,
-				_items: &[(String, String, serde_json::Value)],
-			) -> anyhow::Result<()> {
-				Ok(())
-			}
-		}
-
-		let (tx, _rx) = mpsc::channel(16);
-		let repo: Arc<dyn crate::age_client::AgeRepo> = Arc::new(DummyRepo);
-		let metrics = Arc::new(crate::observability::MetricsRegistry::new());
-		let app_state = crate::state::AppState {
-			repo,
-			persist_sender: tx,
-			metrics,
-		};
-
-		let resp = super::ndjson_upload(State(app_state), req)
+		let resp = super::ndjson_upload(State(create_test_app_state()), req)
 			.await
 			.into_response();
 		assert_eq!(resp.status(), axum::http::StatusCode::OK);
@@ -511,9 +372,16 @@ mod tests {
 	}
 }
 
+/// Number of leading bytes inspected when detecting a dump's type.
+pub(crate) const DETECT_PEEK_BYTES: usize = 64 * 1024;
+
 /// Bulk dump upload endpoint: accepts any raw data stream, writes it to a
 /// temporary file, and attempts to determine the dump type (ndjson/csv/json/text/binary/compressed).
 /// Returns a small JSON description including detected type, size, preview and the temp filename.
+/// The preview is limited to `Settings.bulk_preview_lines` lines with likely PII masked.
+///
+/// Requests carrying an upload session id are parts of a resumable upload;
+/// see [`crate::ingest::resumable`].
 #[tracing::instrument(skip(state, req), fields(endpoint = "bulk"))]
 pub async fn bulk_dump_upload(
 	State(state): State<crate::state::AppState>,
@@ -521,24 +389,33 @@ pub async fn bulk_dump_upload(
 ) -> impl IntoResponse {
 	let start_time = Instant::now();
 	state.metrics.ingest_requests_total.inc();
-
-	// Peek up to this many bytes for detection
-	const MAX_PEEK: usize = 64 * 1024;
+	if let Some(resp) = state.db_health.reject_if_down() {
+		return resp;
+	}
+	if let Some(session) = req.headers().get(UPLOAD_SESSION_HEADER) {
+		let session = session.to_str().unwrap_or_default().to_string();
+		return bulk_session_upload(&state, &session, req, start_time).await;
+	}
 
 	// Note: headers are intentionally not used here, kept in earlier
 	// iterations for potential content-type based detection. Remove the
 	// clone to avoid an unused-variable warning.
 
 	// Prepare temp file path early so we can stream into it
-	let tmpdir = std::env::temp_dir();
-	let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
-	let fname = format!(
-		"heimdall_dump_{}_{}.bin",
-		std::process::id(),
-		now.as_millis()
-	);
+	let tmpdir = crate::ingest::uploads::upload_dir(&state.settings);
+	if let Err(e) = tokio::fs::create_dir_all(&tmpdir).await {
+		state.metrics.ingest_errors_total.inc();
+		return (
+			StatusCode::INTERNAL_SERVER_ERROR,
+			format!("failed to create upload directory: {}", e),
+		)
+			.into_response();
+	}
+	let fname = crate::ingest::uploads::upload_filename("bin");
 	let tmp_path = tmpdir.join(&fname);
 
+	let keep_raw = keep_raw_upload(&state, req.uri().query());
+
 	// Create the file
 	let mut file = match TokioFile::create(&tmp_path).await {
 		Ok(f) => f,
@@ -552,10 +429,20 @@ pub async fn bulk_dump_upload(
 		}
 	};
 
-	// Stream the request body to the temp file while collecting a small peek buffer
+	// Client-supplied name of the dump, recorded in its manifest.
+	let source_filename = query_value(req.uri().query(), "filename");
+	let source = ingest_source(
+		&state.settings,
+		req.headers(),
+		req.extensions().get::<Subject>(),
+	);
+
+	// Stream the request body to the temp file while collecting a small
+	// peek buffer and hashing it for the dump manifest
 	let mut stream = req.into_body().into_data_stream();
 	let mut total: usize = 0;
-	let mut peek_buf: Vec<u8> = Vec::with_capacity(std::cmp::min(MAX_PEEK, 4096));
+	let mut manifest_builder = crate::ingest::ManifestBuilder::new();
+	let mut peek_buf: Vec<u8> = Vec::with_capacity(std::cmp::min(DETECT_PEEK_BYTES, 4096));
 
 	while let Some(chunk_res) = stream.next().await {
 		match chunk_res {
@@ -564,11 +451,12 @@ pub async fn bulk_dump_upload(
 				total = total.saturating_add(chunk.len());
 
 				// Fill the peek buffer until full
-				if peek_buf.len() < MAX_PEEK {
-					let remaining = MAX_PEEK - peek_buf.len();
+				if peek_buf.len() < DETECT_PEEK_BYTES {
+					let remaining = DETECT_PEEK_BYTES - peek_buf.len();
 					let take = std::cmp::min(remaining, chunk.len());
 					peek_buf.extend_from_slice(&chunk[..take]);
 				}
+				manifest_builder.update(chunk);
 
 				if let Err(e) = file.write_all(chunk).await {
 					state.metrics.ingest_errors_total.inc();
@@ -582,7 +470,7 @@ pub async fn bulk_dump_upload(
 			Err(e) => {
 				state.metrics.ingest_errors_total.inc();
 				return (
-					StatusCode::BAD_REQUEST,
+					body_read_status(&e),
 					format!("failed to read request body chunk: {}", e),
 				)
 					.into_response();
@@ -599,14 +487,87 @@ pub async fn bulk_dump_upload(
 		)
 			.into_response();
 	}
-
-	// Update metrics
 	state.metrics.ingest_bytes_total.inc_by(total as f64);
+
+	let upload = StoredUpload {
+		path: tmp_path,
+		fname,
+		bytes: total,
+		peek: peek_buf,
+		manifest: manifest_builder,
+		source_filename,
+		ingest_source: source,
+		keep_raw,
+	};
+	finish_bulk_upload(&state, upload, start_time).await
+}
+
+/// A bulk upload written to disk in full, with what was gathered about it
+/// while it arrived.
+struct StoredUpload {
+	path: std::path::PathBuf,
+	fname: String,
+	bytes: usize,
+	/// Up to [`DETECT_PEEK_BYTES`] leading bytes.
+	peek: Vec<u8>,
+	manifest: crate::ingest::ManifestBuilder,
+	source_filename: Option<String>,
+	/// Feed or tenant stamped on the nodes processing creates.
+	ingest_source: Option<String>,
+	keep_raw: bool,
+}
+
+/// Detect, describe and optionally start processing a stored bulk upload,
+/// returning the bulk endpoint's response.
+async fn finish_bulk_upload(
+	state: &crate::state::AppState,
+	upload: StoredUpload,
+	start_time: Instant,
+) -> axum::response::Response {
+	let StoredUpload {
+		path: tmp_path,
+		fname,
+		bytes: total,
+		peek: peek_buf,
+		manifest: manifest_builder,
+		source_filename,
+		ingest_source: source,
+		keep_raw,
+	} = upload;
 	state.metrics.ingest_records_total.inc();
 
-	// Detect type from peek (use a slice of the bytes up to MAX_PEEK)
+	// Detect type from peek (use a slice of the bytes up to DETECT_PEEK_BYTES)
 	let peek = &peek_buf[..];
-	let (kind, preview, compressed) = detect_dump_type(peek);
+	let (kind, detected, compressed) =
+		detect_dump_type(peek, state.settings.json_detect_sample_bytes);
+	let preview_lines = if state.settings.bulk_preview_enabled {
+		state.settings.bulk_preview_lines
+	} else {
+		0
+	};
+	let preview = crate::ingest::preview::bulk_preview(&kind, detected, peek, preview_lines);
+
+	// Describe the dump on its `Dump` node; rows persisted from it later
+	// attach to the same node. Failing to store the manifest does not
+	// fail the upload.
+	let manifest = manifest_builder.finish(fname.clone(), source_filename, &kind, compressed);
+	if state.settings.dump_manifests {
+		let mut props = serde_json::to_value(&manifest).unwrap_or_default();
+		if let Some(map) = props.as_object_mut() {
+			map.remove("dump_id");
+		}
+		if let Err(e) = state
+			.repo
+			.merge_dump(&manifest.dump_id, &props, &chrono::Utc::now().to_rfc3339())
+			.await
+		{
+			tracing::warn!(
+				dump_id = %manifest.dump_id,
+				error = %e,
+				"failed to persist dump manifest"
+			);
+		}
+	}
 
 	#[derive(Serialize)]
 	struct Resp {
@@ -615,105 +576,92 @@ pub async fn bulk_dump_upload(
 		bytes: usize,
 		filename: String,
 		compressed: bool,
+		manifest: crate::ingest::DumpManifest,
 	}
 
+	let outcome = IngestOutcome {
+		records: manifest.record_count.unwrap_or(0) as usize,
+		dump_id: Some(manifest.dump_id.clone()),
+	};
+	state
+		.lineage
+		.emit(crate::lineage::LineageEvent::dump_ingested(
+			&manifest.dump_id,
+			&state.settings.graph_name(),
+			outcome.records as u64,
+		));
 	let resp = Resp {
 		kind,
 		preview,
 		bytes: total,
 		filename: tmp_path.to_string_lossy().to_string(),
 		compressed,
+		manifest,
 	};
 
-	// Optionally auto-process the uploaded dump in the background. This behavior
-	// is gated by `HMD_AUTO_PROCESS_BULK` env var (default: disabled). When
-	// enabled Heimdall will spawn a background task that will attempt to parse
-	// and normalize supported formats (ndjson/json arrays) and enqueue
-	// sanitized `PersistJob`s into the persistence batcher.
-	if std::env::var("HMD_AUTO_PROCESS_BULK")
-		.map(|v| v == "1" || v.to_lowercase() == "true")
-		.unwrap_or(false)
-	{
-		let sender = state.persist_sender.clone();
+	// Optionally auto-process the uploaded dump in the background
+	// (`Settings.auto_process_bulk`, default: disabled). Supported formats
+	// (ndjson/json arrays) are normalized and sanitized `PersistJob`s are
+	// enqueued into the persistence batcher. Once a file has been processed
+	// successfully it is deleted unless raw uploads are being kept.
+	//
+	// The task is registered in `state.bulk_tasks` under the temp filename
+	// and is cancelled once `Settings.bulk_process_timeout_secs` elapses.
+	// A resent last part of a session whose dump is still being processed
+	// does not start a second task.
+	let running = state.bulk_tasks.outcome(&fname) == Some(BulkOutcome::Running);
+	if state.settings.auto_process_bulk && !running {
+		let delivery = BulkDelivery::from_state(&state, source);
 		let path = tmp_path.clone();
 		let compressed_flag = compressed;
+		let registry = state.bulk_tasks.clone();
+		let deadline = match state.settings.bulk_process_timeout_secs {
+			0 => None,
+			secs => Some(std::time::Duration::from_secs(secs)),
+		};
 
 		// Spawn a background task to process the file without blocking the
-		// request/response lifecycle.
-		tokio::spawn(async move {
-			// Delegate to a blocking worker for file IO and decompression.
-			let _ =
-				tokio::task::spawn_blocking(move || {
-					// Re-open the file for reading
-					if let Ok(f) = StdFile::open(&path) {
-						// Create reader (decompress if gzip)
-						let reader: Box<dyn Read> = if compressed_flag {
-							Box::new(GzDecoder::new(f))
-						} else {
-							Box::new(f)
-						};
-
-						let buf = BufReader::new(reader);
-						let punct_re = regex::Regex::new(r"^[\W_]+|[\W_]+$").unwrap();
-
-						for line_res in buf.lines() {
-							match line_res {
-								Ok(line) => {
-									if let Some(rec) =
-										crate::ingest::normalize_ndjson_line(&line, &punct_re)
-									{
-										let props =
-											serde_json::json!({ "field_type": rec.field_type });
-										let job = crate::persist::PersistJob {
-											label: "FieldValue".to_string(),
-											key: rec.canonical.clone(),
-											props: props.clone(),
-										};
-
-										// Best-effort submission: try a few times before dropping the job.
-										// Note: metrics not available in this background task context
-										let mut attempts = 0;
-										loop {
-											match sender.try_send(job.clone()) {
-						 Ok(()) => break,
-						 Err(e) => match e {
-							tokio::sync::mpsc::error::TrySendError::Full(_ret) => {
-							 attempts += 1;
-							 if attempts > 5 {
-								eprintln!("persist channel full; dropping job {}", job.key);
-								break;
-							 }
-							 std::thread::sleep(std::time::Duration::from_millis(200));
-							}
-							tokio::sync::mpsc::error::TrySendError::Closed(_ret) => {
-							 eprintln!("persist channel closed; dropping job {}", job.key);
-							 break;
-							}
-						 },
-						}
-										}
-									}
-								}
-								Err(e) => {
-									eprintln!("error reading dump file {}: {}", path.display(), e);
-								}
-							}
-						}
+		// request/response lifecycle. File IO and decompression run on a
+		// blocking worker.
+		tokio::spawn(run_bulk_task(
+			registry,
+			fname.clone(),
+			deadline,
+			move |cancel| {
+				let outcome = process_bulk_file(&path, compressed_flag, &delivery, cancel);
+				if outcome == BulkOutcome::Completed && !keep_raw {
+					if let Err(e) = std::fs::remove_file(&path) {
+						tracing::warn!(
+							path = %path.display(),
+							error = %e,
+							"failed to remove processed dump"
+						);
 					}
-					// Note: we intentionally do not delete uploaded files here; retention
-					// and archival policies should be handled externally or by a separate
-					// cleanup worker.
-				})
-				.await;
-		});
+				}
+				outcome
+			},
+		));
 	}
 
-	// Record ingest duration
+	// Record ingest duration. Records are only known once background
+	// processing finishes, which logs its own outcome.
 	let duration = start_time.elapsed().as_secs_f64();
 	state.metrics.ingest_duration_seconds.observe(duration);
+	tracing::info!(
+		endpoint = "bulk",
+		format = %resp.kind,
+		bytes = total,
+		compressed = resp.compressed,
+		duration_ms = start_time.elapsed().as_millis() as u64,
+		"bulk upload stored"
+	);
 
 	match serde_json::to_string(&resp) {
-		Ok(body) => (StatusCode::OK, body).into_response(),
+		Ok(body) => {
+			let mut response = (StatusCode::OK, body).into_response();
+			crate::ingest::content_hash::set_header(&mut response, &resp.manifest.content_sha256);
+			outcome.attach(response)
+		}
 		Err(e) => {
 			state.metrics.ingest_errors_total.inc();
 			(
@@ -725,270 +673,405 @@ pub async fn bulk_dump_upload(
 	}
 }
 
-fn is_printable(b: u8) -> bool {
-	match b {
-		0x09 | 0x0A | 0x0D => true, // tab, lf, cr
-		0x20..=0x7E => true,
-		_ => false,
-	}
-}
-
-fn detect_dump_type(peek: &[u8]) -> (String, String, bool) {
-	if peek.len() >= 2 && peek[0] == 0x1f && peek[1] == 0x8b {
-		// gzip magic
+/// Append one part of resumable upload `session` to its temp file. Once the
+/// session holds the whole upload it is finished like a single-request
+/// upload; until then the reply is `202` with the bytes received so far.
+async fn bulk_session_upload(
+	state: &crate::state::AppState,
+	session: &str,
+	req: Request<Body>,
+	start_time: Instant,
+) -> axum::response::Response {
+	let Some(fname) = crate::ingest::uploads::session_filename(session) else {
+		return (StatusCode::BAD_REQUEST, "invalid upload session id").into_response();
+	};
+	let range = match req.headers().get(axum::http::header::CONTENT_RANGE) {
+		None => None,
+		Some(v) => match v.to_str().ok().and_then(ContentRange::parse) {
+			Some(range) => Some(range),
+			None => return (StatusCode::BAD_REQUEST, "invalid Content-Range").into_response(),
+		},
+	};
+	let tmpdir = crate::ingest::uploads::upload_dir(&state.settings);
+	if let Err(e) = tokio::fs::create_dir_all(&tmpdir).await {
+		state.metrics.ingest_errors_total.inc();
 		return (
-			"gzip".to_string(),
-			format!("gzip compressed ({} bytes peek)", peek.len()),
-			true,
-		);
+			StatusCode::INTERNAL_SERVER_ERROR,
+			format!("failed to create upload directory: {}", e),
+		)
+			.into_response();
 	}
+	let path = tmpdir.join(&fname);
+	let keep_raw = keep_raw_upload(state, req.uri().query());
+	let source_filename = query_value(req.uri().query(), "filename");
+	let source = ingest_source(
+		&state.settings,
+		req.headers(),
+		req.extensions().get::<Subject>(),
+	);
 
-	let printable = peek.iter().filter(|b| is_printable(**b)).count();
-	let ratio = if peek.is_empty() {
-		1.0
-	} else {
-		printable as f64 / peek.len() as f64
+	let opened = tokio::fs::OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(&path)
+		.await;
+	let (mut file, mut received) = match opened {
+		Ok(f) => match f.metadata().await {
+			Ok(meta) => (f, meta.len()),
+			Err(e) => {
+				state.metrics.ingest_errors_total.inc();
+				return (
+					StatusCode::INTERNAL_SERVER_ERROR,
+					format!("failed to open upload session: {}", e),
+				)
+					.into_response();
+			}
+		},
+		Err(e) => {
+			state.metrics.ingest_errors_total.inc();
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				format!("failed to open upload session: {}", e),
+			)
+				.into_response();
+		}
 	};
 
-	if ratio < 0.7 {
-		// likely binary
-		let hex_preview: String = peek
-			.iter()
-			.take(32)
-			.map(|b| format!("{:02x}", b))
-			.collect::<Vec<_>>()
-			.join(" ");
-		return ("binary".to_string(), hex_preview, false);
-	}
-
-	let s = String::from_utf8_lossy(peek);
-	let s_trim = s.trim_start();
-
-	if s_trim.starts_with('{') || s_trim.starts_with('[') {
-		// Could be JSON or NDJSON
-		let lines: Vec<&str> = s.lines().collect();
-		let mut ndjson_like = false;
-		if lines.len() > 1 {
-			ndjson_like = lines.iter().all(|l| {
-				l.trim().is_empty() || l.trim().starts_with('{') || l.trim().starts_with('[')
-			});
-		}
-		if ndjson_like {
-			return (
-				"ndjson".to_string(),
-				s.lines().take(8).collect::<Vec<_>>().join("\n"),
-				false,
-			);
+	// Bytes before `received` were stored by an earlier attempt and are
+	// skipped; bytes past the end of the range are ignored. A part that
+	// starts past `received` would leave a gap.
+	let part = range.and_then(|r| r.range);
+	if let Some((start, _)) = part {
+		if start > received {
+			return session_status(StatusCode::RANGE_NOT_SATISFIABLE, session, received);
 		}
-		return (
-			"json".to_string(),
-			s.lines().take(8).collect::<Vec<_>>().join("\n"),
-			false,
-		);
 	}
+	let mut offset = part.map_or(received, |(start, _)| start);
+	let end = part.map_or(u64::MAX, |(_, end)| end.saturating_add(1));
 
-	// Heuristic for CSV: first non-empty line contains a comma
-	if s.lines().next().map(|l| l.contains(',')).unwrap_or(false) {
-		return (
-			"csv".to_string(),
-			s.lines().take(8).collect::<Vec<_>>().join("\n"),
-			false,
-		);
-	}
-
-	// Fallback to text
-	let preview = s.lines().take(8).collect::<Vec<_>>().join("\n");
-	("text".to_string(), preview, false)
-}
-
-/// Multipart upload endpoint: accepts multipart/form-data with streaming file uploads.
-/// Detects format, routes to appropriate parser, and normalizes records incrementally.
-pub async fn multipart_upload(
-	State(state): State<crate::state::AppState>,
-	mut multipart: axum::extract::Multipart,
-) -> impl IntoResponse {
-	use crate::ingest::format_detection::{detect_format, FormatType};
-	use crate::ingest::parsers;
-	use std::io::Cursor;
-
-	// Process each field in the multipart request
-	let mut format_hint: Option<String> = None;
-	let mut file_data: Option<Vec<u8>> = None;
-
-	while let Some(field) = multipart.next_field().await.transpose() {
-		let field = match field {
-			Ok(f) => f,
+	let mut stream = req.into_body().into_data_stream();
+	while let Some(chunk_res) = stream.next().await {
+		let chunk = match chunk_res {
+			Ok(chunk) => chunk,
 			Err(e) => {
+				state.metrics.ingest_errors_total.inc();
 				return (
-					StatusCode::BAD_REQUEST,
-					format!("failed to read multipart field: {}", e),
+					body_read_status(&e),
+					format!("failed to read request body chunk: {}", e),
 				)
 					.into_response();
 			}
 		};
-
-		let name = field.name().unwrap_or("").to_string();
-
-		if name == "format" {
-			// User-provided format hint
-			let hint_bytes = match field.bytes().await {
-				Ok(b) => b,
-				Err(e) => {
-					return (
-						StatusCode::BAD_REQUEST,
-						format!("failed to read format hint: {}", e),
-					)
-						.into_response();
-				}
-			};
-			format_hint = Some(String::from_utf8_lossy(&hint_bytes).to_string());
-		} else if name == "file" {
-			// File data
-			let bytes = match field.bytes().await {
-				Ok(b) => b,
-				Err(e) => {
-					return (
-						StatusCode::BAD_REQUEST,
-						format!("failed to read file data: {}", e),
-					)
-						.into_response();
-				}
-			};
-			file_data = Some(bytes.to_vec());
+		let from = received.saturating_sub(offset).min(chunk.len() as u64) as usize;
+		let to = end.saturating_sub(offset).min(chunk.len() as u64) as usize;
+		offset += chunk.len() as u64;
+		if from >= to {
+			continue;
 		}
-	}
-
-	let data = match file_data {
-		Some(d) => d,
-		None => {
-			return (StatusCode::BAD_REQUEST, "no file data provided").into_response();
+		if let Err(e) = file.write_all(&chunk[from..to]).await {
+			state.metrics.ingest_errors_total.inc();
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				format!("failed writing to upload session: {}", e),
+			)
+				.into_response();
 		}
-	};
-
-	// Detect format from peek
-	const PEEK_SIZE: usize = 64 * 1024;
-	let peek = if data.len() > PEEK_SIZE {
-		&data[..PEEK_SIZE]
-	} else {
-		&data
-	};
+		received += (to - from) as u64;
+		state.metrics.ingest_bytes_total.inc_by((to - from) as f64);
+	}
+	if let Err(e) = file.flush().await {
+		state.metrics.ingest_errors_total.inc();
+		return (
+			StatusCode::INTERNAL_SERVER_ERROR,
+			format!("failed to flush upload session: {}", e),
+		)
+			.into_response();
+	}
+	if !ContentRange::is_complete(range.as_ref(), received) {
+		return session_status(StatusCode::ACCEPTED, session, received);
+	}
 
-	let (format, compressed) = match detect_format(peek, format_hint.as_deref()) {
-		Ok(f) => f,
+	// The parts arrived over several requests, so the peek and manifest
+	// are taken from the assembled file.
+	let (peek, manifest) = match scan_upload(&path).await {
+		Ok(scanned) => scanned,
 		Err(e) => {
+			state.metrics.ingest_errors_total.inc();
 			return (
-				StatusCode::BAD_REQUEST,
-				format!("failed to detect format: {}", e),
+				StatusCode::INTERNAL_SERVER_ERROR,
+				format!("failed to read upload session: {}", e),
 			)
 				.into_response();
 		}
 	};
+	tracing::info!(session, bytes = received, "upload session complete");
+	let upload = StoredUpload {
+		path,
+		fname,
+		bytes: received as usize,
+		peek,
+		manifest,
+		source_filename,
+		ingest_source: source,
+		keep_raw,
+	};
+	finish_bulk_upload(state, upload, start_time).await
+}
 
-	// Handle compressed data first
-	let decompressed_data = if compressed {
-		match format {
-			FormatType::Gzip => match parsers::decompress_gzip(Cursor::new(&data)) {
-				Ok(d) => d,
-				Err(e) => {
-					return (
-						StatusCode::BAD_REQUEST,
-						format!("failed to decompress gzip: {}", e),
-					)
-						.into_response();
-				}
-			},
-			FormatType::Zip => match parsers::extract_first_zip_entry(Cursor::new(&data)) {
-				Ok(d) => d,
-				Err(e) => {
-					return (
-						StatusCode::BAD_REQUEST,
-						format!("failed to extract zip: {}", e),
-					)
-						.into_response();
-				}
-			},
-			_ => data.clone(),
+fn session_status(status: StatusCode, session: &str, received: u64) -> axum::response::Response {
+	let body = serde_json::to_string(&SessionStatus { session, received }).unwrap_or_default();
+	(status, body).into_response()
+}
+
+/// Leading [`DETECT_PEEK_BYTES`] and manifest of the file at `path`.
+async fn scan_upload(
+	path: &std::path::Path,
+) -> std::io::Result<(Vec<u8>, crate::ingest::ManifestBuilder)> {
+	use tokio::io::AsyncReadExt;
+
+	let mut file = TokioFile::open(path).await?;
+	let mut manifest = crate::ingest::ManifestBuilder::new();
+	let mut peek = Vec::new();
+	let mut buf = vec![0u8; 64 * 1024];
+	loop {
+		let n = file.read(&mut buf).await?;
+		if n == 0 {
+			break;
 		}
+		let take = DETECT_PEEK_BYTES.saturating_sub(peek.len()).min(n);
+		peek.extend_from_slice(&buf[..take]);
+		manifest.update(&buf[..n]);
+	}
+	Ok((peek, manifest))
+}
+
+/// Deliver normalized records to the state's record sinks (by default the
+/// background batcher, see [`crate::sink::AgeSink`]). With `direct`, the
+/// sinks are flushed, so graph writes are resolved once this returns `Ok`
+/// with the records the graph sink confirmed persisted.
+///
+/// Raw values are replaced by the PII policy's output, if configured, before
+/// any sink sees them. A rule for the record's source field wins over one
+/// for its field type. Payment card numbers are masked regardless, see
+/// [`crate::ingest::NormalizedRecord::mask_pan`].
+async fn persist_records(
+	state: &crate::state::AppState,
+	records: &[crate::ingest::NormalizedRecord],
+	start_time: Instant,
+	direct: bool,
+	source: Option<String>,
+) -> anyhow::Result<Vec<PersistedRecord>> {
+	let sinks = if direct {
+		state.direct_record_sinks(start_time, source)
 	} else {
-		data.clone()
+		state.record_sinks(start_time, source)
 	};
+	let pii_engine = state.pii_engine.load_full();
+	for rec in records {
+		let mut rec = rec.clone();
+		// Card numbers are masked and scrubbed whatever the policy says.
+		let masked = rec.mask_pan();
+		if !masked && !rec.raw.is_empty() {
+			// The raw value is transformed according to the policy
+			// (scrub/hash/encrypt).
+			if let Some(ref engine) = pii_engine {
+				let rule = engine.rule_field(rec.source_field.as_deref(), &rec.field_type);
+				rec.raw = match engine.apply_policy(rule, &rec.raw) {
+					Ok(protected) => protected,
+					Err(e) => {
+						tracing::warn!(
+							field_type = %rec.field_type,
+							error = %e,
+							"PII policy application failed; scrubbing value"
+						);
+						// Fall back to scrubbing on error
+						"[REDACTED]".to_string()
+					}
+				};
+			}
+		}
+		if !state.settings.record_source_field {
+			rec.source_field = None;
+		}
 
-	// Parse based on detected format
-	let parse_result = match format {
-		FormatType::Csv => parsers::parse_csv_stream(Cursor::new(&decompressed_data), None),
-		FormatType::Tsv => parsers::parse_csv_stream(Cursor::new(&decompressed_data), Some(b'\t')),
-		FormatType::Ndjson | FormatType::Json => {
-			parsers::parse_ndjson_stream(Cursor::new(&decompressed_data))
+		if let Err(e) = crate::sink::deliver(&sinks, &rec).await {
+			state.metrics.ingest_errors_total.inc();
+			return Err(e);
 		}
-		FormatType::Xlsx => parsers::parse_xlsx_stream(Cursor::new(&decompressed_data)),
-		_ => {
+	}
+	if !direct {
+		return Ok(Vec::new());
+	}
+	if let Err(e) = crate::sink::flush(&sinks).await {
+		state.metrics.ingest_errors_total.inc();
+		return Err(e);
+	}
+	Ok(sinks
+		.iter()
+		.find_map(|sink| sink.persisted())
+		.unwrap_or_default()
+		.into_iter()
+		.map(|(index, key)| PersistedRecord { index, key })
+		.collect())
+}
+
+/// Confirmation that a record was persisted, returned by `?sync=true`
+/// ingests in record order.
+#[derive(Debug, Serialize, serde::Deserialize, PartialEq)]
+pub struct PersistedRecord {
+	/// Position of the record among the accepted ones.
+	pub index: usize,
+	/// Key of the record's graph node.
+	pub key: String,
+}
+
+/// Whether a request of `bytes` is persisted before responding: `None`
+/// unless `?sync=true`, then `Some(true)` when it fits within
+/// `Settings.sync_ingest_max_bytes` and `Some(false)` when it falls back to
+/// the batcher.
+fn sync_persist(state: &crate::state::AppState, query: Option<&str>, bytes: usize) -> Option<bool> {
+	query_flag(query, "sync").then(|| bytes <= state.settings.sync_ingest_max_bytes)
+}
+
+/// Mark `resp` with how a `?sync=true` request was persisted.
+fn set_persist_mode(resp: &mut axum::response::Response, sync: Option<bool>) {
+	if let Some(sync) = sync {
+		let mode = if sync { "sync" } else { "async" };
+		resp.headers_mut().insert(
+			PERSIST_MODE_HEADER,
+			axum::http::HeaderValue::from_static(mode),
+		);
+	}
+}
+
+/// Why a pre-normalized record was rejected, or `None` if it is valid.
+///
+/// The canonical value must already be in canonical form for its field
+/// type and, when a raw value is given, must equal its normalization.
+/// Messages never include record values.
+fn invalid_record_reason(
+	rec: &crate::ingest::NormalizedRecord,
+	trim: &crate::ingest::TrimRules,
+) -> Option<&'static str> {
+	use crate::ingest::bulk_normalizer::canonicalize;
+
+	if rec.field_type.trim().is_empty() {
+		return Some("field_type is empty");
+	}
+	if rec.canonical.is_empty() {
+		return Some("canonical is empty");
+	}
+	if canonicalize(&rec.field_type, &rec.canonical, trim) != rec.canonical {
+		return Some("canonical is not in canonical form");
+	}
+	if !rec.raw.trim().is_empty() && canonicalize(&rec.field_type, &rec.raw, trim) != rec.canonical
+	{
+		return Some("canonical does not match the normalized raw value");
+	}
+	None
+}
+
+/// Pre-normalized ingest endpoint: accepts a JSON array of
+/// `NormalizedRecord`s from services that normalize data themselves and
+/// enqueues them for persistence without re-running detection.
+///
+/// The batch is validated up front; if any record is invalid nothing is
+/// persisted and a 400 naming the first offending record is returned.
+/// `?sync=true` persists a small batch before responding, as for
+/// [`ndjson_upload`], adding `persisted` to the response.
+#[tracing::instrument(skip(state, body), fields(endpoint = "records"))]
+pub async fn records_upload(
+	State(state): State<crate::state::AppState>,
+	axum::extract::RawQuery(query): axum::extract::RawQuery,
+	headers: axum::http::HeaderMap,
+	subject: Option<axum::Extension<Subject>>,
+	body: axum::body::Bytes,
+) -> impl IntoResponse {
+	let start_time = Instant::now();
+	state.metrics.ingest_requests_total.inc();
+	if let Some(resp) = state.db_health.reject_if_down() {
+		return resp;
+	}
+
+	let policy = match private_ip_policy(&state, query.as_deref()) {
+		Ok(policy) => policy,
+		Err(resp) => return *resp,
+	};
+	let body_sha256 = content_sha256(&body);
+	let mut records: Vec<crate::ingest::NormalizedRecord> = match serde_json::from_slice(&body) {
+		Ok(r) => r,
+		Err(e) => {
+			state.metrics.ingest_errors_total.inc();
 			return (
 				StatusCode::BAD_REQUEST,
-				format!("unsupported format: {}", format.as_str()),
+				format!("invalid record batch: {}", e),
 			)
 				.into_response();
 		}
 	};
 
-	let records = match parse_result {
-		Ok(r) => r,
-		Err(e) => {
+	let trim = state.settings.trim_rules();
+	for (idx, rec) in records.iter().enumerate() {
+		if let Some(reason) = invalid_record_reason(rec, &trim) {
+			state.metrics.ingest_errors_total.inc();
 			return (
 				StatusCode::BAD_REQUEST,
-				format!("failed to parse data: {}", e),
+				format!("record {} rejected: {}", idx, reason),
 			)
 				.into_response();
 		}
-	};
+	}
+	let mut private_ips = PrivateIpFilter::new(policy, &state.metrics);
+	private_ips.retain(&mut records);
 
-	// Persist records using the background batcher
-	let sender = state.persist_sender.clone();
-	for rec in &records {
-		let props = serde_json::json!({
-			"field_type": rec.field_type,
-		});
+	state.metrics.ingest_bytes_total.inc_by(body.len() as f64);
+	state
+		.metrics
+		.ingest_records_total
+		.inc_by(records.len() as u64);
 
-		let job = crate::persist::PersistJob {
-			label: "FieldValue".to_string(),
-			key: rec.canonical.clone(),
-			props: props.clone(),
+	let sync = sync_persist(&state, query.as_deref(), body.len());
+	let source = ingest_source(&state.settings, &headers, subject.as_deref());
+	let persisted =
+		match persist_records(&state, &records, start_time, sync == Some(true), source).await {
+			Ok(persisted) => persisted,
+			Err(e) => {
+				return (
+					StatusCode::INTERNAL_SERVER_ERROR,
+					format!("failed to persist record: {}", e),
+				)
+					.into_response();
+			}
 		};
 
-		match crate::persist::submit_job(&sender, job.clone()) {
-			Ok(()) => {}
-			Err(tokio::sync::mpsc::error::TrySendError::Full(returned))
-			| Err(tokio::sync::mpsc::error::TrySendError::Closed(returned)) => {
-				// Channel unavailable; persist synchronously
-				if let Err(e) = state
-					.repo
-					.merge_entity(&returned.label, &returned.key, &returned.props)
-					.await
-				{
-					return (
-						StatusCode::INTERNAL_SERVER_ERROR,
-						format!("failed to persist record: {}", e),
-					)
-						.into_response();
-				}
-			}
-		}
-	}
+	let duration = start_time.elapsed().as_secs_f64();
+	state.metrics.ingest_duration_seconds.observe(duration);
+	log_ingest_outcome("records", "json", body.len(), records.len(), 0, start_time);
 
 	#[derive(Serialize)]
-	struct Response {
-		format: String,
-		compressed: bool,
+	struct Response<'a> {
 		records_count: usize,
+		content_sha256: &'a str,
+		#[serde(skip_serializing_if = "Option::is_none")]
+		persisted: Option<Vec<PersistedRecord>>,
 	}
 
-	let resp = Response {
-		format: format.as_str().to_string(),
-		compressed,
+	match serde_json::to_string(&Response {
 		records_count: records.len(),
-	};
-
-	match serde_json::to_string(&resp) {
-		Ok(body) => (StatusCode::OK, body).into_response(),
+		content_sha256: &body_sha256,
+		persisted: (sync == Some(true)).then_some(persisted),
+	}) {
+		Ok(body) => {
+			let mut resp = (StatusCode::OK, body).into_response();
+			set_persist_mode(&mut resp, sync);
+			crate::ingest::content_hash::set_header(&mut resp, &body_sha256);
+			private_ips.report("records", &mut resp);
+			IngestOutcome {
+				records: records.len(),
+				dump_id: None,
+			}
+			.attach(resp)
+		}
 		Err(e) => (
 			StatusCode::INTERNAL_SERVER_ERROR,
 			format!("failed to serialize response: {}", e),
@@ -997,48 +1080,2639 @@ pub async fn multipart_upload(
 	}
 }
 
-#[cfg(test)]
-mod detect_tests {
-	use super::*;
+/// Emit the structured completion event shared by the ingest endpoints.
+/// Only counts and kinds are logged, never record values.
+fn log_ingest_outcome(
+	endpoint: &'static str,
+	format: &str,
+	bytes: usize,
+	records: usize,
+	skipped: usize,
+	started: Instant,
+) {
+	tracing::info!(
+		endpoint,
+		format,
+		bytes,
+		records,
+		skipped,
+		duration_ms = started.elapsed().as_millis() as u64,
+		"ingest completed"
+	);
+}
 
-	#[test]
-	fn detect_gzip() {
-		let peek = [0x1f_u8, 0x8b_u8, 0x08, 0x00, 0x00];
-		let (kind, _preview, compressed) = detect_dump_type(&peek);
-		assert_eq!(kind, "gzip");
-		assert!(compressed);
+/// Decode one NDJSON line. Invalid UTF-8 is replaced with U+FFFD, or makes
+/// the line undecodable (`None`) when `strict`.
+fn decode_line(bytes: &[u8], strict: bool) -> Option<std::borrow::Cow<'_, str>> {
+	if strict {
+		std::str::from_utf8(bytes)
+			.ok()
+			.map(std::borrow::Cow::Borrowed)
+	} else {
+		Some(String::from_utf8_lossy(bytes))
 	}
+}
 
-	#[test]
-	fn detect_binary() {
-		// low printable ratio
-		let peek = vec![
-			0xff_u8, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
-		];
-		let (kind, _preview, compressed) = detect_dump_type(&peek);
-		assert_eq!(kind, "binary");
-		assert!(!compressed);
+/// Record that NDJSON line `line_no` was skipped for exceeding
+/// [`MAX_LINE_BYTES`].
+fn skip_oversized_line(state: &crate::state::AppState, line_no: usize, oversized: &mut Vec<usize>) {
+	state.metrics.ingest_oversized_lines_total.inc();
+	tracing::warn!(
+		line = line_no,
+		max_bytes = MAX_LINE_BYTES,
+		"skipped oversized NDJSON line"
+	);
+	oversized.push(line_no);
+}
+
+/// Comma-separated header value listing the first [`MAX_REPORTED_LINES`]
+/// of `lines`, or `None` when there are none.
+fn line_list_header(lines: &[usize]) -> Option<axum::http::HeaderValue> {
+	if lines.is_empty() {
+		return None;
 	}
+	let listed: Vec<String> = lines
+		.iter()
+		.take(MAX_REPORTED_LINES)
+		.map(usize::to_string)
+		.collect();
+	axum::http::HeaderValue::from_str(&listed.join(",")).ok()
+}
 
-	#[test]
-	fn detect_ndjson_vs_json() {
-		let ndjson_peek = b"{\"a\":1}\n{\"b\":2}\n";
-		let (kind_nd, _preview, _c) = detect_dump_type(ndjson_peek);
-		assert_eq!(kind_nd, "ndjson");
+/// Status for a failed body read: 413 when a body size limit (such as the
+/// decompressed-size cap) was exceeded, 400 otherwise.
+fn body_read_status(err: &axum::Error) -> StatusCode {
+	if crate::ingest::content_encoding::is_length_limit(err) {
+		StatusCode::PAYLOAD_TOO_LARGE
+	} else {
+		StatusCode::BAD_REQUEST
+	}
+}
 
-		let json_peek = b"{\"a\":1, \"b\":2}\n";
-		let (kind_json, _preview, _c2) = detect_dump_type(json_peek);
-		assert_eq!(kind_json, "json");
+/// Whether a bulk upload is retained after processing: always under
+/// `Settings.keep_raw_uploads`, and on `?keep_raw=true` only when
+/// `Settings.allow_keep_raw_query` lets callers ask for it.
+fn keep_raw_upload(state: &crate::state::AppState, query: Option<&str>) -> bool {
+	state.settings.keep_raw_uploads
+		|| (state.settings.allow_keep_raw_query && query_flag(query, "keep_raw"))
+}
+
+/// Whether `name` is set to a truthy value (`name`, `name=1`, `name=true`)
+/// in a URL query string.
+fn query_flag(query: Option<&str>, name: &str) -> bool {
+	query
+		.unwrap_or("")
+		.split('&')
+		.filter_map(|pair| {
+			let mut kv = pair.splitn(2, '=');
+			Some((kv.next()?, kv.next()))
+		})
+		.any(|(k, v)| {
+			let v = v.map(|v| v.to_ascii_lowercase());
+			k == name && matches!(v.as_deref(), None | Some("1" | "true"))
+		})
+}
+
+/// Private IP policy for a request: `?private_ips=` when given, else
+/// `Settings.private_ip_policy`. An unknown policy is a 400.
+fn private_ip_policy(
+	state: &crate::state::AppState,
+	query: Option<&str>,
+) -> Result<PrivateIpPolicy, Box<axum::response::Response>> {
+	query_value(query, PRIVATE_IPS_PARAM)
+		.unwrap_or_else(|| state.settings.private_ip_policy.clone())
+		.parse()
+		.map_err(|e: String| {
+			state.metrics.ingest_errors_total.inc();
+			Box::new((StatusCode::BAD_REQUEST, e).into_response())
+		})
+}
+
+/// Percent-decoded value of query parameter `name`, if present and
+/// non-empty.
+fn query_value(query: Option<&str>, name: &str) -> Option<String> {
+	url::form_urlencoded::parse(query?.as_bytes())
+		.find(|(k, _)| k == name)
+		.map(|(_, v)| v.into_owned())
+		.filter(|v| !v.is_empty())
+}
+
+/// Where the records of a bulk dump go, and the classifiers and trim
+/// rules applied to them on the way.
+#[derive(Default)]
+pub(crate) struct BulkDelivery {
+	pub(crate) sinks: Vec<Arc<dyn crate::sink::RecordSink>>,
+	pub(crate) classifiers: Arc<crate::ingest::FieldClassifiers>,
+	pub(crate) trim: crate::ingest::TrimRules,
+}
+
+impl BulkDelivery {
+	/// Delivery through `state`'s sinks, classifiers and trim rules, with
+	/// `source` stamped on graph nodes.
+	pub(crate) fn from_state(state: &crate::state::AppState, source: Option<String>) -> Self {
+		Self {
+			sinks: state.record_sinks(Instant::now(), source),
+			classifiers: state.classifiers.clone(),
+			trim: state.settings.trim_rules(),
+		}
 	}
+}
 
-	#[test]
-	fn detect_csv_and_text() {
-		let csv = b"col1,col2\n1,2\n";
-		let (kind_csv, _p, _c) = detect_dump_type(csv);
-		assert_eq!(kind_csv, "csv");
+/// Normalize a stored bulk dump line-by-line and deliver the resulting
+/// records through `delivery`. Runs on a blocking worker of the runtime
+/// the sinks use. Stops early with `Cancelled` once `cancel` is set; returns
+/// `Failed` if the file could not be opened or read to the end.
+///
+/// Progress is checkpointed next to the file, and a later run over the same
+/// file resumes from the checkpoint instead of delivering the records again.
+fn process_bulk_file(
+	path: &std::path::Path,
+	compressed: bool,
+	delivery: &BulkDelivery,
+	cancel: &AtomicBool,
+) -> BulkOutcome {
+	let f = match StdFile::open(path) {
+		Ok(f) => f,
+		Err(e) => {
+			tracing::error!(path = %path.display(), error = %e, "failed to open dump file");
+			return BulkOutcome::Failed;
+		}
+	};
+	// Create reader (decompress if gzip)
+	let reader: Box<dyn Read> = if compressed {
+		Box::new(GzDecoder::new(f))
+	} else {
+		Box::new(f)
+	};
+	let resume_from = crate::ingest::uploads::read_checkpoint(path);
+	if resume_from > 0 {
+		tracing::info!(path = %path.display(), offset = resume_from, "resuming bulk processing");
+	}
+	let outcome = process_bulk_reader(
+		reader,
+		path,
+		compressed,
+		delivery,
+		cancel,
+		Some(resume_from),
+	);
+	if outcome == BulkOutcome::Completed {
+		crate::ingest::uploads::clear_checkpoint(path);
+	}
+	outcome
+}
 
-		let text = b"hello world\nthis is text\n";
-		let (kind_text, _p2, _c2) = detect_dump_type(text);
-		assert_eq!(kind_text, "text");
+/// Bytes of (decompressed) input processed between two checkpoints.
+const CHECKPOINT_BYTES: u64 = 1024 * 1024;
+
+/// Body of [`process_bulk_file`], split out so tests can supply a reader.
+///
+/// With `resume_from`, that many leading bytes are skipped and the offset
+/// of the last processed line is checkpointed for `path` every
+/// [`CHECKPOINT_BYTES`] and when processing stops.
+pub(crate) fn process_bulk_reader(
+	reader: impl Read,
+	path: &std::path::Path,
+	compressed: bool,
+	delivery: &BulkDelivery,
+	cancel: &AtomicBool,
+	resume_from: Option<u64>,
+) -> BulkOutcome {
+	let started = Instant::now();
+	let runtime = tokio::runtime::Handle::current();
+	let mut buf = BufReader::new(reader);
+	let mut outcome = BulkOutcome::Completed;
+	let (mut bytes, mut records, mut skipped, mut failed) = (0usize, 0usize, 0usize, 0usize);
+
+	let mut offset = resume_from.unwrap_or(0);
+	if offset > 0 {
+		let skipped_bytes = std::io::copy(&mut (&mut buf).take(offset), &mut std::io::sink());
+		if !matches!(skipped_bytes, Ok(n) if n == offset) {
+			tracing::error!(path = %path.display(), offset, "dump is shorter than its checkpoint");
+			return BulkOutcome::Failed;
+		}
+	}
+	let mut checkpointed = offset;
+	let checkpoint = |offset: u64| {
+		if let Err(e) = crate::ingest::uploads::write_checkpoint(path, offset) {
+			tracing::warn!(path = %path.display(), error = %e, "failed to checkpoint bulk processing");
+		}
+	};
+
+	let mut raw = Vec::new();
+	loop {
+		if cancel.load(Ordering::Relaxed) {
+			tracing::warn!(path = %path.display(), records, "bulk processing cancelled");
+			outcome = BulkOutcome::Cancelled;
+			break;
+		}
+		if resume_from.is_some() && offset - checkpointed >= CHECKPOINT_BYTES {
+			checkpoint(offset);
+			checkpointed = offset;
+		}
+		raw.clear();
+		let n = match buf.read_until(b'\n', &mut raw) {
+			Ok(0) => break,
+			Ok(n) => n,
+			Err(e) => {
+				tracing::error!(path = %path.display(), error = %e, "failed to read dump file");
+				outcome = BulkOutcome::Failed;
+				break;
+			}
+		};
+		offset += n as u64;
+		bytes += n;
+		let Ok(line) = std::str::from_utf8(&raw) else {
+			tracing::error!(path = %path.display(), offset, "dump line is not valid UTF-8");
+			outcome = BulkOutcome::Failed;
+			continue;
+		};
+		let line = line.strip_suffix('\n').unwrap_or(line);
+		let line = line.strip_suffix('\r').unwrap_or(line);
+		let Some(mut rec) = crate::ingest::normalize_ndjson_line(line, &delivery.trim) else {
+			if !line.trim().is_empty() {
+				skipped += 1;
+			}
+			continue;
+		};
+		delivery.classifiers.apply(&mut rec, &delivery.trim);
+		records += 1;
+		rec.mask_pan();
+		// Bulk dumps keep only field types, never raw values.
+		rec.raw.clear();
+		if let Err(e) = runtime.block_on(crate::sink::deliver(&delivery.sinks, &rec)) {
+			if failed == 0 {
+				tracing::warn!(path = %path.display(), error = %e, "failed to deliver record");
+			}
+			failed += 1;
+		}
+	}
+	if resume_from.is_some() && outcome != BulkOutcome::Completed {
+		checkpoint(offset);
+	}
+
+	if failed > 0 {
+		tracing::warn!(
+			endpoint = "bulk",
+			failed,
+			"records not delivered to every sink"
+		);
+	}
+	let format = if compressed { "gzip" } else { "ndjson" };
+	log_ingest_outcome("bulk", format, bytes, records, skipped, started);
+	outcome
+}
+
+fn is_printable(b: u8) -> bool {
+	match b {
+		0x09 | 0x0A | 0x0D => true, // tab, lf, cr
+		0x20..=0x7E => true,
+		_ => false,
+	}
+}
+
+/// Lines of a dump's text shown in the preview `detect_dump_type` returns.
+const DETECT_PREVIEW_LINES: usize = 8;
+
+/// Detect the kind of a dump from its first bytes, returning the kind, a
+/// preview and whether it is compressed. JSON is told from NDJSON by
+/// examining at most `json_sample_bytes` of `peek`; every other heuristic
+/// looks at no more than its first `DETECT_SCAN_BYTES`.
+pub(crate) fn detect_dump_type(peek: &[u8], json_sample_bytes: usize) -> (String, String, bool) {
+	if peek.len() >= 2 && peek[0] == 0x1f && peek[1] == 0x8b {
+		// gzip magic
+		return (
+			"gzip".to_string(),
+			format!("gzip compressed ({} bytes peek)", peek.len()),
+			true,
+		);
+	}
+
+	let head = &peek[..peek.len().min(DETECT_SCAN_BYTES)];
+	let printable = head.iter().filter(|b| is_printable(**b)).count();
+	let ratio = if head.is_empty() {
+		1.0
+	} else {
+		printable as f64 / head.len() as f64
+	};
+
+	if ratio < 0.7 {
+		// likely binary
+		let hex_preview: String = peek
+			.iter()
+			.take(32)
+			.map(|b| format!("{:02x}", b))
+			.collect::<Vec<_>>()
+			.join(" ");
+		return ("binary".to_string(), hex_preview, false);
+	}
+
+	let s = text_head(peek, DETECT_SCAN_BYTES);
+	let s_trim = s.trim_start();
+
+	if s_trim.starts_with('{') || s_trim.starts_with('[') {
+		let sample = text_head(peek, json_sample_bytes);
+		let kind = crate::ingest::format_detection::classify_json(&sample, json_sample_bytes);
+		return (kind.as_str().to_string(), preview_lines(&s), false);
+	}
+
+	// Heuristic for CSV: first non-empty line contains a comma
+	if s.lines().next().map(|l| l.contains(',')).unwrap_or(false) {
+		return ("csv".to_string(), preview_lines(&s), false);
+	}
+
+	// Fallback to text
+	("text".to_string(), preview_lines(&s), false)
+}
+
+/// The first `DETECT_PREVIEW_LINES` lines of `s`.
+fn preview_lines(s: &str) -> String {
+	s.lines()
+		.take(DETECT_PREVIEW_LINES)
+		.collect::<Vec<_>>()
+		.join("\n")
+}
+
+/// Rough confidence (0.0-1.0) in the kind `detect_dump_type` reported for
+/// `peek`. Magic bytes are certain; text kinds are scored by how printable
+/// the sample is and, for line-oriented kinds, how consistent the lines are.
+/// Only the first `DETECT_SCAN_BYTES` of `peek` are scored.
+fn detection_confidence(kind: &str, peek: &[u8]) -> f64 {
+	if peek.is_empty() {
+		return 0.0;
+	}
+	let head = &peek[..peek.len().min(DETECT_SCAN_BYTES)];
+	let printable = head.iter().filter(|b| is_printable(**b)).count() as f64 / head.len() as f64;
+	let s = text_head(head, DETECT_SCAN_BYTES);
+	let lines: Vec<&str> = s.lines().filter(|l| !l.trim().is_empty()).collect();
+
+	let score = match kind {
+		"gzip" => 1.0,
+		"binary" => 1.0 - printable,
+		"ndjson" => {
+			let parsed = lines
+				.iter()
+				.filter(|l| serde_json::from_str::<serde_json::Value>(l).is_ok())
+				.count();
+			// The last line of a peek is often truncated; don't count it against us.
+			let considered = if lines.len() > 1 { lines.len() - 1 } else { lines.len() };
+			printable * (parsed.min(considered) as f64 / considered.max(1) as f64)
+		}
+		"csv" => {
+			let columns = lines.first().map(|l| l.matches(',').count()).unwrap_or(0);
+			let consistent = lines.iter().filter(|l| l.matches(',').count() == columns).count();
+			printable * (consistent as f64 / lines.len().max(1) as f64)
+		}
+		"json" => printable,
+		// Text is the fallback when nothing else matched.
+		_ => printable * 0.5,
+	};
+	score.clamp(0.0, 1.0)
+}
+
+/// Result of `POST /ingest/detect`.
+#[derive(Debug, Serialize, serde::Deserialize)]
+pub struct DetectResponse {
+	pub kind: String,
+	pub compressed: bool,
+	pub preview: String,
+	pub confidence: f64,
+}
+
+/// Report the detected type of a dump without ingesting it.
+///
+/// Reads at most the first 64KiB of the body, runs the same detection as
+/// `POST /ingest/bulk` and returns `{ kind, compressed, preview, confidence }`.
+/// Nothing is written to disk or persisted.
+#[tracing::instrument(skip(state, req), fields(endpoint = "detect"))]
+pub async fn detect_upload(
+	State(state): State<crate::state::AppState>,
+	req: Request<Body>,
+) -> impl IntoResponse {
+	let mut stream = req.into_body().into_data_stream();
+	let mut peek: Vec<u8> = Vec::with_capacity(4096);
+
+	while peek.len() < DETECT_PEEK_BYTES {
+		match stream.next().await {
+			Some(Ok(chunk)) => {
+				let take = std::cmp::min(DETECT_PEEK_BYTES - peek.len(), chunk.len());
+				peek.extend_from_slice(&chunk[..take]);
+			}
+			Some(Err(e)) => {
+				state.metrics.ingest_errors_total.inc();
+				return (
+					body_read_status(&e),
+					format!("failed to read request body chunk: {}", e),
+				)
+					.into_response();
+			}
+			None => break,
+		}
+	}
+
+	let (kind, preview, compressed) =
+		detect_dump_type(&peek, state.settings.json_detect_sample_bytes);
+	let confidence = detection_confidence(&kind, &peek);
+	let resp = DetectResponse {
+		kind,
+		compressed,
+		preview,
+		confidence,
+	};
+	(StatusCode::OK, axum::Json(resp)).into_response()
+}
+
+/// One `file`/`file[]` part of a multipart upload, spooled to disk.
+struct SpooledPart {
+	filename: String,
+	path: std::path::PathBuf,
+	/// Hex SHA-256 of the part as received, before any decompression.
+	content_sha256: String,
+}
+
+/// A spooled part after detection and parsing on the parse pool.
+struct ParsedPart {
+	bytes: usize,
+	format: Option<crate::ingest::format_detection::FormatType>,
+	compressed: bool,
+	records: Vec<crate::ingest::NormalizedRecord>,
+	error: Option<String>,
+}
+
+/// Per-file entry of a multi-file multipart response.
+#[derive(Serialize)]
+struct FileResult {
+	filename: String,
+	format: String,
+	compressed: bool,
+	records: usize,
+	content_sha256: String,
+	errors: Vec<String>,
+}
+
+/// Multipart upload endpoint: accepts multipart/form-data with one or more
+/// `file` (or `file[]`) parts, each streamed to a temp file and then
+/// detected, decompressed and parsed independently. An optional `format`
+/// field hints the format of every file; otherwise each file's extension
+/// (ignoring `.gz`/`.zip`) or its content decides.
+///
+/// A single file keeps the original response,
+/// `{format, compressed, records_count}`, and fails the request with 400
+/// when it cannot be parsed. Several files get a JSON array of
+/// `{filename, format, compressed, records, errors}`, one entry per file,
+/// where a file that fails is reported without failing the others.
+#[tracing::instrument(skip(state, multipart), fields(endpoint = "multipart"))]
+pub async fn multipart_upload(
+	State(state): State<crate::state::AppState>,
+	axum::extract::RawQuery(query): axum::extract::RawQuery,
+	headers: axum::http::HeaderMap,
+	subject: Option<axum::Extension<Subject>>,
+	mut multipart: axum::extract::Multipart,
+) -> impl IntoResponse {
+	let start_time = Instant::now();
+	if let Some(resp) = state.db_health.reject_if_down() {
+		return resp;
+	}
+	let policy = match private_ip_policy(&state, query.as_deref()) {
+		Ok(policy) => policy,
+		Err(resp) => return *resp,
+	};
+
+	let tmpdir = crate::ingest::uploads::upload_dir(&state.settings);
+	if let Err(e) = tokio::fs::create_dir_all(&tmpdir).await {
+		state.metrics.ingest_errors_total.inc();
+		return (
+			StatusCode::INTERNAL_SERVER_ERROR,
+			format!("failed to create upload directory: {}", e),
+		)
+			.into_response();
+	}
+
+	let mut format_hint: Option<String> = None;
+	let mut parts: Vec<SpooledPart> = Vec::new();
+	let limits = MultipartLimits::from_settings(&state.settings);
+	if let Err(resp) = spool_parts(
+		&mut multipart,
+		&tmpdir,
+		limits,
+		&mut format_hint,
+		&mut parts,
+	)
+	.await
+	{
+		remove_parts(&parts).await;
+		return resp;
+	}
+	if parts.is_empty() {
+		return (StatusCode::BAD_REQUEST, "no file data provided").into_response();
+	}
+
+	// Decompress and parse each file on the bounded parse pool, keeping
+	// CPU-bound work off the async runtime.
+	let mut parsed = Vec::with_capacity(parts.len());
+	let decompression = state.settings.decompression_limits();
+	for part in &parts {
+		let path = part.path.clone();
+		let hint = format_hint
+			.clone()
+			.or_else(|| extension_hint(&part.filename));
+		match state
+			.parse_pool
+			.run(move || parse_part(&path, hint.as_deref(), decompression))
+			.await
+		{
+			Ok(p) => parsed.push(p),
+			Err(e) => {
+				remove_parts(&parts).await;
+				state.metrics.ingest_errors_total.inc();
+				return e.into_response();
+			}
+		}
+	}
+	remove_parts(&parts).await;
+
+	if parsed.len() == 1 {
+		if let Some(error) = parsed[0].error.take() {
+			return (StatusCode::BAD_REQUEST, error).into_response();
+		}
+	}
+
+	let trim = state.settings.trim_rules();
+	let mut private_ips = PrivateIpFilter::new(policy, &state.metrics);
+	for p in &mut parsed {
+		for rec in &mut p.records {
+			// Parsers canonicalize with the default rules.
+			if !rec.raw.is_empty() {
+				rec.canonical =
+					crate::ingest::bulk_normalizer::canonicalize(&rec.field_type, &rec.raw, &trim);
+			}
+			state.classifiers.apply(rec, &trim);
+			rec.mask_pan();
+		}
+		private_ips.retain(&mut p.records);
+	}
+	let counts: Vec<usize> = parsed.iter().map(|p| p.records.len()).collect();
+	let records: Vec<_> = parsed
+		.iter_mut()
+		.flat_map(|p| std::mem::take(&mut p.records))
+		.collect();
+
+	// Deliver records to the configured sinks. Multipart uploads keep only
+	// field types, never raw values.
+	let stripped: Vec<_> = records
+		.iter()
+		.map(|rec| crate::ingest::NormalizedRecord {
+			raw: String::new(),
+			..rec.clone()
+		})
+		.collect();
+	let source = ingest_source(&state.settings, &headers, subject.as_deref());
+	if let Err(e) = persist_records(&state, &stripped, start_time, false, source).await {
+		return (
+			StatusCode::INTERNAL_SERVER_ERROR,
+			format!("failed to persist record: {}", e),
+		)
+			.into_response();
+	}
+
+	// The stream parsers drop unparseable rows without reporting them, so
+	// no skipped count is available here.
+	let bytes = parsed.iter().map(|p| p.bytes).sum();
+	let format = match parsed.as_slice() {
+		[p] => p.format.as_ref().map_or("unknown", |f| f.as_str()),
+		_ => "mixed",
+	};
+	log_ingest_outcome("multipart", format, bytes, records.len(), 0, start_time);
+	let outcome = IngestOutcome {
+		records: records.len(),
+		dump_id: None,
+	};
+
+	#[derive(Serialize)]
+	struct Response<'a> {
+		format: String,
+		compressed: bool,
+		records_count: usize,
+		content_sha256: &'a str,
+	}
+
+	let body = if let [p] = parsed.as_slice() {
+		serde_json::to_string(&Response {
+			format: format.to_string(),
+			compressed: p.compressed,
+			records_count: records.len(),
+			content_sha256: &parts[0].content_sha256,
+		})
+	} else {
+		let results: Vec<FileResult> = parts
+			.iter()
+			.zip(&parsed)
+			.zip(counts)
+			.map(|((part, p), records)| FileResult {
+				filename: part.filename.clone(),
+				format: p
+					.format
+					.as_ref()
+					.map_or("unknown", |f| f.as_str())
+					.to_string(),
+				compressed: p.compressed,
+				records,
+				content_sha256: part.content_sha256.clone(),
+				errors: p.error.iter().cloned().collect(),
+			})
+			.collect();
+		serde_json::to_string(&results)
+	};
+
+	match body {
+		Ok(body) => {
+			let mut resp = (StatusCode::OK, body).into_response();
+			if let [part] = parts.as_slice() {
+				crate::ingest::content_hash::set_header(&mut resp, &part.content_sha256);
+			}
+			private_ips.report("multipart", &mut resp);
+			outcome.attach(resp)
+		}
+		Err(e) => (
+			StatusCode::INTERNAL_SERVER_ERROR,
+			format!("failed to serialize response: {}", e),
+		)
+			.into_response(),
+	}
+}
+
+/// Longest `format` hint accepted in a multipart upload.
+const FORMAT_HINT_MAX_BYTES: usize = 32;
+
+/// Caps on one multipart upload, see `Settings.multipart_max_fields` and
+/// `Settings.multipart_max_bytes`.
+#[derive(Debug, Clone, Copy)]
+struct MultipartLimits {
+	max_fields: usize,
+	max_bytes: usize,
+}
+
+impl MultipartLimits {
+	fn from_settings(settings: &crate::config::Settings) -> Self {
+		Self {
+			max_fields: settings.multipart_max_fields,
+			max_bytes: settings.multipart_max_bytes,
+		}
+	}
+}
+
+/// Stream every `file`/`file[]` part of `multipart` to its own temp file in
+/// `dir`, recording the `format` field in `format_hint`. Parts spooled
+/// before an error are left in `parts` for the caller to remove.
+///
+/// Fails with 400 past `limits.max_fields` fields (of any name) or a hint
+/// over [`FORMAT_HINT_MAX_BYTES`], and with 413 once the fields together
+/// exceed `limits.max_bytes`.
+async fn spool_parts(
+	multipart: &mut axum::extract::Multipart,
+	dir: &std::path::Path,
+	limits: MultipartLimits,
+	format_hint: &mut Option<String>,
+	parts: &mut Vec<SpooledPart>,
+) -> Result<(), axum::response::Response> {
+	let bad_request = |what: &str, e: &dyn std::fmt::Display| {
+		(StatusCode::BAD_REQUEST, format!("{}: {}", what, e)).into_response()
+	};
+	let mut fields = 0usize;
+	let mut total = 0usize;
+	// Adds `len` to the bytes read so far; false once they exceed the cap.
+	let mut count_bytes = |len: usize| {
+		total += len;
+		total <= limits.max_bytes
+	};
+	let too_large = || {
+		(
+			StatusCode::PAYLOAD_TOO_LARGE,
+			format!("multipart upload exceeds {} bytes", limits.max_bytes),
+		)
+			.into_response()
+	};
+
+	while let Some(field) = multipart.next_field().await.transpose() {
+		let mut field = field.map_err(|e| bad_request("failed to read multipart field", &e))?;
+		fields += 1;
+		if fields > limits.max_fields {
+			return Err((
+				StatusCode::BAD_REQUEST,
+				format!(
+					"multipart upload has more than {} fields",
+					limits.max_fields
+				),
+			)
+				.into_response());
+		}
+		let name = field.name().unwrap_or("").to_string();
+		match name.as_str() {
+			"format" => {
+				// User-provided format hint
+				let mut hint = Vec::new();
+				while let Some(chunk) = field
+					.chunk()
+					.await
+					.map_err(|e| bad_request("failed to read format hint", &e))?
+				{
+					if !count_bytes(chunk.len()) {
+						return Err(too_large());
+					}
+					if hint.len() + chunk.len() > FORMAT_HINT_MAX_BYTES {
+						return Err((
+							StatusCode::BAD_REQUEST,
+							format!("format hint exceeds {} bytes", FORMAT_HINT_MAX_BYTES),
+						)
+							.into_response());
+					}
+					hint.extend_from_slice(&chunk);
+				}
+				*format_hint = Some(String::from_utf8_lossy(&hint).trim().to_string());
+			}
+			"file" | "file[]" => {
+				let path = dir.join(crate::ingest::uploads::upload_filename("part"));
+				let filename = field
+					.file_name()
+					.map(str::to_string)
+					.unwrap_or_else(|| format!("file{}", parts.len() + 1));
+				let mut file = TokioFile::create(&path).await.map_err(|e| {
+					(
+						StatusCode::INTERNAL_SERVER_ERROR,
+						format!("failed to create temp file: {}", e),
+					)
+						.into_response()
+				})?;
+				parts.push(SpooledPart {
+					filename,
+					path,
+					content_sha256: String::new(),
+				});
+				let mut content = ContentHasher::new();
+				while let Some(chunk) = field
+					.chunk()
+					.await
+					.map_err(|e| bad_request("failed to read file data", &e))?
+				{
+					if !count_bytes(chunk.len()) {
+						return Err(too_large());
+					}
+					content.update(&chunk);
+					file.write_all(&chunk).await.map_err(|e| {
+						(
+							StatusCode::INTERNAL_SERVER_ERROR,
+							format!("failed to write temp file: {}", e),
+						)
+							.into_response()
+					})?;
+				}
+				file.flush().await.map_err(|e| {
+					(
+						StatusCode::INTERNAL_SERVER_ERROR,
+						format!("failed to write temp file: {}", e),
+					)
+						.into_response()
+				})?;
+				if let Some(part) = parts.last_mut() {
+					part.content_sha256 = content.finish();
+				}
+			}
+			_ => {
+				// Drain ignored fields so they count against the size cap.
+				while let Some(chunk) = field
+					.chunk()
+					.await
+					.map_err(|e| bad_request("failed to read multipart field", &e))?
+				{
+					if !count_bytes(chunk.len()) {
+						return Err(too_large());
+					}
+				}
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Delete the temp files of spooled parts.
+async fn remove_parts(parts: &[SpooledPart]) {
+	for part in parts {
+		if let Err(e) = tokio::fs::remove_file(&part.path).await {
+			tracing::warn!(
+				path = %part.path.display(),
+				error = %e,
+				"failed to remove multipart temp file"
+			);
+		}
+	}
+}
+
+/// Format hint taken from `filename`'s extension, looking past a trailing
+/// `.gz` or `.zip`, e.g. `ndjson` for `events.ndjson.gz`.
+fn extension_hint(filename: &str) -> Option<String> {
+	use crate::ingest::format_detection::FormatType;
+
+	let name = filename.to_ascii_lowercase();
+	let name = [".gz", ".gzip", ".zip"]
+		.iter()
+		.find_map(|ext| name.strip_suffix(ext))
+		.unwrap_or(&name);
+	let (_, ext) = name.rsplit_once('.')?;
+	match FormatType::from_hint(ext)? {
+		FormatType::Gzip | FormatType::Zip => None,
+		_ => Some(ext.to_string()),
+	}
+}
+
+/// Read a spooled part from `path`, decompress it when it is gzip or zip
+/// (other than an XLSX workbook, which is a zip container) and parse it as
+/// `hint` or, without one, as detected from its content. Decompression
+/// stops with an error once the output breaks `limits`.
+fn parse_part(
+	path: &std::path::Path,
+	hint: Option<&str>,
+	limits: crate::ingest::parsers::DecompressionLimits,
+) -> ParsedPart {
+	use crate::ingest::format_detection::{FormatType, detect_format, sniff_delimiter};
+	use crate::ingest::parsers;
+	use std::io::Cursor;
+
+	const PEEK_SIZE: usize = 64 * 1024;
+	let mut part = ParsedPart {
+		bytes: 0,
+		format: None,
+		compressed: false,
+		records: Vec::new(),
+		error: None,
+	};
+	let peek = |data: &[u8]| data[..data.len().min(PEEK_SIZE)].to_vec();
+
+	let mut data = match std::fs::read(path) {
+		Ok(d) => d,
+		Err(e) => {
+			part.error = Some(format!("failed to read upload: {}", e));
+			return part;
+		}
+	};
+	part.bytes = data.len();
+
+	// Compression is recognized by magic bytes; the hint names the content.
+	let hint = hint.filter(|h| {
+		!matches!(
+			FormatType::from_hint(h),
+			Some(FormatType::Gzip | FormatType::Zip)
+		)
+	});
+	let xlsx = hint.and_then(FormatType::from_hint) == Some(FormatType::Xlsx);
+	if let Ok((outer, true)) = detect_format(&peek(&data), None) {
+		if !(xlsx && outer == FormatType::Zip) {
+			part.compressed = true;
+			let inflated = match outer {
+				FormatType::Gzip => parsers::decompress_gzip(Cursor::new(&data), &limits)
+					.map_err(|e| format!("failed to decompress gzip: {}", e)),
+				_ => parsers::extract_first_zip_entry(Cursor::new(&data), &limits)
+					.map_err(|e| format!("failed to extract zip: {}", e)),
+			};
+			match inflated {
+				Ok(d) => data = d,
+				Err(e) => {
+					part.format = Some(outer);
+					part.error = Some(e);
+					return part;
+				}
+			}
+		}
+	}
+
+	let sample = peek(&data);
+	let format = match detect_format(&sample, hint) {
+		Ok((format, _)) => format,
+		Err(e) => {
+			part.error = Some(format!("failed to detect format: {}", e));
+			return part;
+		}
+	};
+	let parsed = match format {
+		FormatType::Csv => parsers::parse_csv_stream(Cursor::new(&data), sniff_delimiter(&sample)),
+		FormatType::Tsv => parsers::parse_csv_stream(Cursor::new(&data), Some(b'\t')),
+		FormatType::Ndjson | FormatType::Json => parsers::parse_ndjson_stream(Cursor::new(&data)),
+		FormatType::Xlsx => parsers::parse_xlsx_stream(Cursor::new(&data)),
+		_ => {
+			part.error = Some(format!("unsupported format: {}", format.as_str()));
+			part.format = Some(format);
+			return part;
+		}
+	};
+	match parsed {
+		Ok(records) => part.records = records,
+		Err(e) => part.error = Some(format!("failed to parse data: {}", e)),
+	}
+	part.format = Some(format);
+	part
+}
+
+#[cfg(test)]
+mod detect_tests {
+	use super::*;
+	use crate::ingest::format_detection::DEFAULT_JSON_SAMPLE_BYTES;
+
+	#[test]
+	fn detect_gzip() {
+		let peek = [0x1f_u8, 0x8b_u8, 0x08, 0x00, 0x00];
+		let (kind, _preview, compressed) = detect_dump_type(&peek, DEFAULT_JSON_SAMPLE_BYTES);
+		assert_eq!(kind, "gzip");
+		assert!(compressed);
+	}
+
+	#[test]
+	fn detect_binary() {
+		// low printable ratio
+		let peek = vec![
+			0xff_u8, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
+		];
+		let (kind, _preview, compressed) = detect_dump_type(&peek, DEFAULT_JSON_SAMPLE_BYTES);
+		assert_eq!(kind, "binary");
+		assert!(!compressed);
+	}
+
+	#[test]
+	fn detect_ndjson_vs_json() {
+		let ndjson_peek = b"{\"a\":1}\n{\"b\":2}\n";
+		let (kind_nd, _preview, _c) = detect_dump_type(ndjson_peek, DEFAULT_JSON_SAMPLE_BYTES);
+		assert_eq!(kind_nd, "ndjson");
+
+		let json_peek = b"{\"a\":1, \"b\":2}\n";
+		let (kind_json, _preview, _c2) = detect_dump_type(json_peek, DEFAULT_JSON_SAMPLE_BYTES);
+		assert_eq!(kind_json, "json");
+	}
+
+	#[test]
+	fn detect_multiline_json_documents() {
+		let pretty = b"{\n  \"email\": \"a@example.com\",\n  \"tags\": [\n    \"x\"\n  ]\n}\n";
+		assert_eq!(
+			detect_dump_type(pretty, DEFAULT_JSON_SAMPLE_BYTES).0,
+			"json"
+		);
+
+		let array = b"[\n  {\"a\": 1},\n  {\"b\": 2}\n]\n";
+		assert_eq!(detect_dump_type(array, DEFAULT_JSON_SAMPLE_BYTES).0, "json");
+
+		let ndjson = b"{\"a\": 1}\n{\"b\": 2}\n{\"c\": 3}\n";
+		assert_eq!(
+			detect_dump_type(ndjson, DEFAULT_JSON_SAMPLE_BYTES).0,
+			"ndjson"
+		);
+		// Sampling fewer bytes still sees the second line start.
+		assert_eq!(detect_dump_type(ndjson, 12).0, "ndjson");
+	}
+
+	#[test]
+	fn detect_csv_and_text() {
+		let csv = b"col1,col2\n1,2\n";
+		let (kind_csv, _p, _c) = detect_dump_type(csv, DEFAULT_JSON_SAMPLE_BYTES);
+		assert_eq!(kind_csv, "csv");
+
+		let text = b"hello world\nthis is text\n";
+		let (kind_text, _p2, _c2) = detect_dump_type(text, DEFAULT_JSON_SAMPLE_BYTES);
+		assert_eq!(kind_text, "text");
+	}
+
+	#[test]
+	fn detection_of_a_single_long_line_is_bounded() {
+		// A 64KiB peek with no line break: previews stop at the scan limit.
+		for (line, kind) in [(&b"a,"[..], "csv"), (&b"word "[..], "text")] {
+			let peek: Vec<u8> = line
+				.iter()
+				.copied()
+				.cycle()
+				.take(DETECT_PEEK_BYTES)
+				.collect();
+			let (detected, preview, _c) = detect_dump_type(&peek, DEFAULT_JSON_SAMPLE_BYTES);
+			assert_eq!(detected, kind);
+			assert_eq!(preview.len(), DETECT_SCAN_BYTES);
+			assert!(detection_confidence(kind, &peek) >= 0.5);
+		}
+
+		// JSON is still classified from the configured sample, past the
+		// scan limit.
+		let mut json = b"{\"k\":\"".to_vec();
+		json.resize(DETECT_PEEK_BYTES - 2, b'v');
+		json.extend_from_slice(b"\"}");
+		let (kind, preview, _c) = detect_dump_type(&json, DEFAULT_JSON_SAMPLE_BYTES);
+		assert_eq!(kind, "json");
+		assert_eq!(preview.len(), DETECT_SCAN_BYTES);
+
+		let line = format!("{{\"k\":\"{}\"}}\n", "v".repeat(2 * DETECT_SCAN_BYTES));
+		let ndjson = line.repeat(2);
+		assert_eq!(
+			detect_dump_type(ndjson.as_bytes(), DEFAULT_JSON_SAMPLE_BYTES).0,
+			"ndjson"
+		);
+	}
+
+	async fn detect_via_endpoint(
+		body: Vec<u8>,
+	) -> (DetectResponse, tokio::sync::mpsc::Receiver<crate::persist::PersistJob>) {
+		let (state, rx) = crate::ingest::test_utils::app_state_with_receiver(std::sync::Arc::new(
+			crate::ingest::test_utils::DummyRepo,
+		));
+		let req = Request::builder()
+			.uri("/ingest/detect")
+			.body(Body::from(body))
+			.unwrap();
+		let resp = detect_upload(State(state), req).await.into_response();
+		assert_eq!(resp.status(), StatusCode::OK);
+		let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+		(serde_json::from_slice(&bytes).unwrap(), rx)
+	}
+
+	#[tokio::test]
+	async fn detect_endpoint_matches_detect_dump_type() {
+		let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+		std::io::Write::write_all(&mut gz, b"{\"a\":1}\n").unwrap();
+		let gzip = gz.finish().unwrap();
+
+		let samples: Vec<Vec<u8>> = vec![
+			gzip,
+			b"{\"a\":1}\n{\"b\":2}\n".to_vec(),
+			b"col1,col2\n1,2\n3,4\n".to_vec(),
+			vec![0xff, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08],
+		];
+		for sample in samples {
+			let (expected_kind, expected_preview, expected_compressed) =
+				detect_dump_type(&sample, DEFAULT_JSON_SAMPLE_BYTES);
+			let (resp, mut rx) = detect_via_endpoint(sample).await;
+			assert_eq!(resp.kind, expected_kind);
+			assert_eq!(resp.preview, expected_preview);
+			assert_eq!(resp.compressed, expected_compressed);
+			assert!(resp.confidence > 0.5, "{}: confidence {}", resp.kind, resp.confidence);
+			assert!(rx.try_recv().is_err(), "detect must not enqueue persist jobs");
+		}
+	}
+
+	#[tokio::test]
+	async fn detect_endpoint_only_peeks_first_64k() {
+		let mut body = b"{\"a\":1}\n".repeat(DETECT_PEEK_BYTES / 8);
+		body.extend(std::iter::repeat_n(0u8, DETECT_PEEK_BYTES));
+		let peek = body[..DETECT_PEEK_BYTES].to_vec();
+		let (resp, _rx) = detect_via_endpoint(body).await;
+		assert_eq!(
+			resp.kind,
+			detect_dump_type(&peek, DEFAULT_JSON_SAMPLE_BYTES).0
+		);
+		assert_eq!(resp.kind, "ndjson");
+	}
+}
+
+#[cfg(test)]
+mod tracing_tests {
+	use super::*;
+	use std::collections::HashMap;
+	use std::sync::{Arc, Mutex};
+	use tracing_subscriber::layer::{Context, SubscriberExt};
+
+	type Events = Arc<Mutex<Vec<HashMap<String, String>>>>;
+
+	/// Records every event's fields as strings.
+	struct CaptureLayer(Events);
+
+	struct FieldVisitor<'a>(&'a mut HashMap<String, String>);
+
+	impl tracing::field::Visit for FieldVisitor<'_> {
+		fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+			self.0.insert(field.name().to_string(), format!("{:?}", value));
+		}
+
+		fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+			self.0.insert(field.name().to_string(), value.to_string());
+		}
+	}
+
+	impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for CaptureLayer {
+		fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+			let mut fields = HashMap::new();
+			event.record(&mut FieldVisitor(&mut fields));
+			self.0.lock().unwrap().push(fields);
+		}
+	}
+
+	#[tokio::test]
+	async fn ndjson_upload_emits_outcome_fields_without_values() {
+		let events: Events = Arc::default();
+		let subscriber = tracing_subscriber::registry().with(CaptureLayer(events.clone()));
+		let _guard = tracing::subscriber::set_default(subscriber);
+
+		let body = "{\"field_type\":\"email\",\"value\":\"secret.person@example.com\"}\n\
+			not a record\n\
+			{\"field_type\":\"domain\",\"value\":\"example.org\"}\n";
+		let state = crate::ingest::test_utils::create_test_app_state();
+		let req = Request::builder()
+			.uri("/ingest/ndjson")
+			.body(Body::from(body))
+			.unwrap();
+		let resp = ndjson_upload(State(state), req).await.into_response();
+		assert_eq!(resp.status(), StatusCode::OK);
+
+		let events = events.lock().unwrap();
+		let outcome = events
+			.iter()
+			.find(|e| e.get("message").map(String::as_str) == Some("ingest completed"))
+			.expect("no ingest outcome event");
+		assert_eq!(outcome["endpoint"], "ndjson");
+		assert_eq!(outcome["format"], "ndjson");
+		assert_eq!(outcome["bytes"], body.len().to_string());
+		assert_eq!(outcome["records"], "2");
+		assert_eq!(outcome["skipped"], "1");
+		assert!(outcome.contains_key("duration_ms"));
+
+		let logged: String = events.iter().flat_map(|e| e.values().cloned()).collect();
+		assert!(!logged.contains("secret.person"));
+	}
+}
+
+#[cfg(test)]
+mod bulk_task_tests {
+	use super::*;
+	use crate::ingest::bulk_tasks::BulkTaskRegistry;
+	use crate::sink::{AgeSink, RecordSink};
+	use std::sync::Arc;
+	use std::time::Duration;
+
+	/// Delivery to the graph sink, enqueueing onto `tx`.
+	fn age_delivery(tx: crate::persist::PersistSender) -> BulkDelivery {
+		BulkDelivery {
+			sinks: vec![Arc::new(AgeSink::new(
+				Arc::new(crate::ingest::test_utils::DummyRepo),
+				tx,
+				Arc::new(crate::observability::MetricsRegistry::new()),
+			))],
+			..BulkDelivery::default()
+		}
+	}
+
+	/// Endless NDJSON source that takes `delay` to produce each line.
+	struct SlowReader {
+		delay: Duration,
+		pending: Vec<u8>,
+	}
+
+	impl Read for SlowReader {
+		fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+			if self.pending.is_empty() {
+				std::thread::sleep(self.delay);
+				self.pending = b"{\"field_type\":\"domain\",\"value\":\"slow.example\"}\n".to_vec();
+			}
+			let n = out.len().min(self.pending.len());
+			out[..n].copy_from_slice(&self.pending[..n]);
+			self.pending.drain(..n);
+			Ok(n)
+		}
+	}
+
+	#[tokio::test]
+	async fn slow_bulk_task_is_aborted_at_deadline() {
+		let (tx, mut rx) = tokio::sync::mpsc::channel(1024);
+		let delivery = age_delivery(tx);
+		let registry = Arc::new(BulkTaskRegistry::new());
+		let (done_tx, done_rx) = std::sync::mpsc::channel();
+
+		let started = Instant::now();
+		let outcome = run_bulk_task(
+			registry.clone(),
+			"slow".to_string(),
+			Some(Duration::from_millis(200)),
+			move |cancel| {
+				let reader = SlowReader {
+					delay: Duration::from_millis(20),
+					pending: Vec::new(),
+				};
+				let path = std::path::Path::new("slow");
+				let outcome = process_bulk_reader(reader, path, false, &delivery, cancel, None);
+				let _ = done_tx.send(outcome);
+				outcome
+			},
+		)
+		.await;
+
+		assert_eq!(outcome, BulkOutcome::Timeout);
+		assert!(started.elapsed() < Duration::from_secs(2));
+		assert_eq!(registry.outcome("slow"), Some(BulkOutcome::Timeout));
+		assert!(!registry.cancel("slow"));
+
+		// The blocking worker notices the cancellation at its next line.
+		let worker = done_rx.recv_timeout(Duration::from_secs(2)).unwrap();
+		assert_eq!(worker, BulkOutcome::Cancelled);
+		assert!(rx.try_recv().is_ok());
+	}
+
+	#[tokio::test]
+	async fn bulk_task_completes_within_deadline() {
+		let (tx, _rx) = tokio::sync::mpsc::channel(16);
+		let delivery = age_delivery(tx);
+		let registry = Arc::new(BulkTaskRegistry::new());
+		let outcome = run_bulk_task(
+			registry.clone(),
+			"fast".to_string(),
+			Some(Duration::from_secs(5)),
+			move |cancel| {
+				let reader =
+					std::io::Cursor::new(b"{\"field_type\":\"domain\",\"value\":\"a.com\"}\n");
+				let path = std::path::Path::new("fast");
+				process_bulk_reader(reader, path, false, &delivery, cancel, None)
+			},
+		)
+		.await;
+		assert_eq!(outcome, BulkOutcome::Completed);
+		assert_eq!(registry.outcome("fast"), Some(BulkOutcome::Completed));
+	}
+
+	/// Sink that keeps records and cancels processing after the first one.
+	struct CancellingSink {
+		cancel: Arc<AtomicBool>,
+		records: crate::sink::test_utils::MemorySink,
+	}
+
+	#[async_trait::async_trait]
+	impl RecordSink for CancellingSink {
+		async fn send(&self, record: &crate::ingest::NormalizedRecord) -> anyhow::Result<()> {
+			self.cancel.store(true, Ordering::Relaxed);
+			self.records.send(record).await
+		}
+	}
+
+	#[tokio::test]
+	async fn interrupted_processing_resumes_from_checkpoint() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("heimdall_dump_resume.bin");
+		let first = "{\"field_type\":\"domain\",\"value\":\"a.example\"}\r\n";
+		let rest = "{\"field_type\":\"domain\",\"value\":\"b.example\"}\n\
+			{\"field_type\":\"domain\",\"value\":\"c.example\"}\n";
+		std::fs::write(&path, format!("{}{}", first, rest)).unwrap();
+
+		let cancel = Arc::new(AtomicBool::new(false));
+		let interrupted = Arc::new(CancellingSink {
+			cancel: cancel.clone(),
+			records: Default::default(),
+		});
+		let delivery = BulkDelivery {
+			sinks: vec![interrupted.clone()],
+			..BulkDelivery::default()
+		};
+		let p = path.clone();
+		let outcome =
+			tokio::task::spawn_blocking(move || process_bulk_file(&p, false, &delivery, &cancel))
+				.await
+				.unwrap();
+		assert_eq!(outcome, BulkOutcome::Cancelled);
+		assert_eq!(interrupted.records.records().len(), 1);
+		assert_eq!(
+			crate::ingest::uploads::read_checkpoint(&path),
+			first.len() as u64
+		);
+
+		let resumed = Arc::new(crate::sink::test_utils::MemorySink::default());
+		let delivery = BulkDelivery {
+			sinks: vec![resumed.clone()],
+			..BulkDelivery::default()
+		};
+		let p = path.clone();
+		let outcome = tokio::task::spawn_blocking(move || {
+			process_bulk_file(&p, false, &delivery, &AtomicBool::new(false))
+		})
+		.await
+		.unwrap();
+		assert_eq!(outcome, BulkOutcome::Completed);
+		let canonical: Vec<_> = resumed.records().into_iter().map(|r| r.canonical).collect();
+		assert_eq!(canonical, vec!["b.example", "c.example"]);
+		assert!(!crate::ingest::uploads::checkpoint_path(&path).exists());
+	}
+}
+
+#[cfg(test)]
+mod resumable_tests {
+	use super::*;
+	use std::sync::Arc;
+
+	fn state(dir: &std::path::Path) -> crate::state::AppState {
+		let settings = crate::config::Settings {
+			upload_dir: dir.to_string_lossy().into_owned(),
+			..Default::default()
+		};
+		crate::ingest::test_utils::create_test_app_state().with_settings(Arc::new(settings))
+	}
+
+	async fn send_part(
+		state: &crate::state::AppState,
+		range: &str,
+		body: &'static [u8],
+	) -> (StatusCode, serde_json::Value) {
+		let req = Request::builder()
+			.uri("/ingest/bulk")
+			.header(UPLOAD_SESSION_HEADER, "dump-1")
+			.header(axum::http::header::CONTENT_RANGE, range)
+			.body(Body::from(body))
+			.unwrap();
+		let resp = bulk_dump_upload(State(state.clone()), req)
+			.await
+			.into_response();
+		let status = resp.status();
+		let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+			.await
+			.unwrap();
+		(status, serde_json::from_slice(&bytes).unwrap_or_default())
+	}
+
+	#[tokio::test]
+	async fn ranged_parts_assemble_the_full_upload() {
+		let dir = tempfile::tempdir().unwrap();
+		let state = state(dir.path());
+		let full: &'static [u8] = b"{\"field_type\":\"domain\",\"value\":\"a.com\"}\n\
+			{\"field_type\":\"ip\",\"value\":\"10.0.0.1\"}\n";
+		let (head, tail) = full.split_at(30);
+		let total = full.len();
+
+		let first = format!("bytes 0-29/{}", total);
+		let (status, body) = send_part(&state, &first, head).await;
+		assert_eq!(status, StatusCode::ACCEPTED);
+		assert_eq!(
+			body,
+			serde_json::json!({"session": "dump-1", "received": 30})
+		);
+
+		// Retrying an acknowledged part stores nothing twice.
+		let (status, body) = send_part(&state, &first, head).await;
+		assert_eq!(status, StatusCode::ACCEPTED);
+		assert_eq!(body["received"], 30);
+
+		// A part past the received bytes would leave a gap.
+		let gap = format!("bytes 40-{}/{}", total - 1, total);
+		let (status, body) = send_part(&state, &gap, &full[40..]).await;
+		assert_eq!(status, StatusCode::RANGE_NOT_SATISFIABLE);
+		assert_eq!(body["received"], 30);
+
+		let last = format!("bytes 30-{}/{}", total - 1, total);
+		let (status, body) = send_part(&state, &last, tail).await;
+		assert_eq!(status, StatusCode::OK);
+		assert_eq!(body["bytes"], total);
+		assert_eq!(body["kind"], "ndjson");
+		assert_eq!(body["manifest"]["row_count"], 2);
+		let path = std::path::PathBuf::from(body["filename"].as_str().unwrap());
+		assert_eq!(std::fs::read(path).unwrap(), full);
+	}
+
+	#[tokio::test]
+	async fn invalid_session_parts_are_rejected() {
+		let dir = tempfile::tempdir().unwrap();
+		let state = state(dir.path());
+		let (status, _) = send_part(&state, "bytes 0-1/1", b"{}").await;
+		assert_eq!(status, StatusCode::BAD_REQUEST);
+
+		let req = Request::builder()
+			.uri("/ingest/bulk")
+			.header(UPLOAD_SESSION_HEADER, "../escape")
+			.body(Body::from("{}"))
+			.unwrap();
+		let resp = bulk_dump_upload(State(state), req).await.into_response();
+		assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+	}
+}
+
+#[cfg(test)]
+mod salt_tests {
+	use super::*;
+	use crate::ingest::test_utils::{DummyRepo, app_state_with_receiver};
+	use std::sync::Arc;
+	use std::time::Duration;
+
+	#[tokio::test]
+	async fn handlers_sharing_state_salt_produce_identical_keys() {
+		let (state, mut rx) = app_state_with_receiver(Arc::new(DummyRepo));
+		let dir = tempfile::tempdir().unwrap();
+		let settings = crate::config::Settings {
+			upload_dir: dir.path().to_string_lossy().into_owned(),
+			auto_process_bulk: true,
+			..Default::default()
+		};
+		let state = state
+			.with_settings(Arc::new(settings))
+			.with_canonical_salt("shared-salt");
+
+		let line = "{\"field_type\":\"domain\",\"value\":\"Example.COM\"}\n";
+		let req = Request::builder()
+			.uri("/ingest/ndjson")
+			.body(Body::from(line))
+			.unwrap();
+		let resp = ndjson_upload(State(state.clone()), req)
+			.await
+			.into_response();
+		assert!(resp.status().is_success());
+		let req = Request::builder()
+			.uri("/ingest/bulk")
+			.body(Body::from(line))
+			.unwrap();
+		let resp = bulk_dump_upload(State(state.clone()), req)
+			.await
+			.into_response();
+		assert!(resp.status().is_success());
+
+		let mut keys = Vec::new();
+		for _ in 0..2 {
+			let job = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+				.await
+				.expect("job enqueued")
+				.expect("channel open");
+			keys.push(job.key);
+		}
+
+		let expected =
+			crate::lib::normalizers::generate_canonical_key("domain:example.com", "shared-salt")
+				.key;
+		assert_eq!(keys, vec![expected.clone(), expected.clone()]);
+		assert_eq!(state.record_key("domain", "example.com"), expected);
+	}
+
+	#[test]
+	fn unsalted_state_keeps_canonical_value() {
+		let state = crate::ingest::test_utils::create_test_app_state();
+		assert_eq!(state.canonical_key("example.com"), "example.com");
+	}
+}
+
+#[cfg(test)]
+mod records_tests {
+	use super::*;
+	use std::sync::Arc;
+
+	fn state_with_channel() -> (
+		crate::state::AppState,
+		tokio::sync::mpsc::Receiver<crate::persist::PersistJob>,
+	) {
+		crate::ingest::test_utils::app_state_with_receiver(Arc::new(
+			crate::ingest::test_utils::DummyRepo,
+		))
+	}
+
+	async fn body_text(resp: axum::response::Response) -> String {
+		let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+			.await
+			.unwrap();
+		String::from_utf8_lossy(&bytes).into_owned()
+	}
+
+	#[tokio::test]
+	async fn valid_batch_is_persisted() {
+		let (state, mut rx) = state_with_channel();
+		let batch = r#"[
+			{"field_type": "domain", "raw": " Example.COM. ", "canonical": "example.com"},
+			{"field_type": "ip", "canonical": "192.0.2.1"}
+		]"#;
+
+		let resp = records_upload(
+			State(state),
+			axum::extract::RawQuery(None),
+			axum::http::HeaderMap::new(),
+			None,
+			axum::body::Bytes::from(batch),
+		)
+		.await
+		.into_response();
+		assert_eq!(resp.status(), StatusCode::OK);
+		assert_eq!(
+			body_text(resp).await,
+			format!(
+				r#"{{"records_count":2,"content_sha256":"{}"}}"#,
+				content_sha256(batch.as_bytes())
+			)
+		);
+
+		let first = rx.try_recv().expect("first job");
+		assert_eq!(first.key, "domain:example.com");
+		assert_eq!(first.props["raw"], " Example.COM. ");
+		let second = rx.try_recv().expect("second job");
+		assert_eq!(second.key, "ip:192.0.2.1");
+		assert!(second.props.get("raw").is_none());
+		assert!(rx.try_recv().is_err());
+	}
+
+	#[tokio::test]
+	async fn records_are_stamped_with_the_ingest_source() {
+		let (state, mut rx) = state_with_channel();
+		let settings = crate::config::Settings {
+			ingest_source_header: "x-heimdall-source".to_string(),
+			..crate::config::Settings::default()
+		};
+		let state = state.with_settings(Arc::new(settings));
+		let body = "{\"field_type\":\"domain\",\"value\":\"example.com\"}\n";
+
+		let req = Request::builder()
+			.header("x-heimdall-source", "feedA")
+			.body(Body::from(body))
+			.unwrap();
+		let resp = ndjson_upload(State(state.clone()), req).await;
+		assert_eq!(resp.into_response().status(), StatusCode::OK);
+		assert_eq!(rx.try_recv().expect("job").props["source"], "feedA");
+
+		// Without the header nothing is stamped.
+		let req = Request::builder().body(Body::from(body)).unwrap();
+		let resp = ndjson_upload(State(state), req).await;
+		assert_eq!(resp.into_response().status(), StatusCode::OK);
+		assert!(rx.try_recv().expect("job").props.get("source").is_none());
+	}
+
+	#[tokio::test]
+	async fn mismatched_canonical_rejects_batch() {
+		let (state, mut rx) = state_with_channel();
+		let batch = r#"[
+			{"field_type": "domain", "raw": "example.com", "canonical": "example.com"},
+			{"field_type": "domain", "raw": "example.org", "canonical": "example.com"}
+		]"#;
+
+		let resp = records_upload(
+			State(state),
+			axum::extract::RawQuery(None),
+			axum::http::HeaderMap::new(),
+			None,
+			axum::body::Bytes::from(batch),
+		)
+		.await
+		.into_response();
+		assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+		assert_eq!(
+			body_text(resp).await,
+			"record 1 rejected: canonical does not match the normalized raw value"
+		);
+		assert!(
+			rx.try_recv().is_err(),
+			"nothing persisted from a rejected batch"
+		);
+	}
+
+	#[tokio::test]
+	async fn non_canonical_value_is_rejected() {
+		let (state, _rx) = state_with_channel();
+		let batch = r#"[{"field_type": "domain", "canonical": "Example.COM"}]"#;
+
+		let resp = records_upload(
+			State(state),
+			axum::extract::RawQuery(None),
+			axum::http::HeaderMap::new(),
+			None,
+			axum::body::Bytes::from(batch),
+		)
+		.await
+		.into_response();
+		assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+		assert_eq!(
+			body_text(resp).await,
+			"record 0 rejected: canonical is not in canonical form"
+		);
+	}
+
+	#[tokio::test]
+	async fn future_schema_version_is_rejected() {
+		let (state, mut rx) = state_with_channel();
+		let batch = r#"[{"schema_version": 99, "field_type": "ip", "canonical": "192.0.2.1"}]"#;
+
+		let resp = records_upload(
+			State(state),
+			axum::extract::RawQuery(None),
+			axum::http::HeaderMap::new(),
+			None,
+			axum::body::Bytes::from(batch),
+		)
+		.await
+		.into_response();
+		assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+		assert!(
+			body_text(resp)
+				.await
+				.contains("unsupported NormalizedRecord schema_version 99")
+		);
+		assert!(rx.try_recv().is_err());
+	}
+}
+
+#[cfg(test)]
+mod manifest_tests {
+	use super::*;
+	use crate::ingest::test_utils::{RecordingRepo, app_state_with_receiver};
+	use std::sync::Arc;
+
+	const FIXTURE: &[u8] = include_bytes!("../../tests/fixtures/bulk/sample.ndjson");
+	const FIXTURE_SHA256: &str = "3438a747f016b1ded04fa5d858ca3337704c4b67ea947d07c345f63ab59c70ce";
+
+	#[tokio::test]
+	async fn bulk_upload_returns_and_persists_manifest() {
+		let dir = tempfile::tempdir().unwrap();
+		let settings = crate::config::Settings {
+			upload_dir: dir.path().to_string_lossy().into_owned(),
+			..Default::default()
+		};
+		let repo = Arc::new(RecordingRepo::default());
+		let state = app_state_with_receiver(repo.clone())
+			.0
+			.with_settings(Arc::new(settings));
+
+		let req = Request::builder()
+			.uri("/ingest/bulk?filename=sample%20dump.ndjson")
+			.body(Body::from(FIXTURE))
+			.unwrap();
+		let resp = bulk_dump_upload(State(state), req).await.into_response();
+		assert_eq!(resp.status(), StatusCode::OK);
+		let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+			.await
+			.unwrap();
+		let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+		let manifest = &json["manifest"];
+		assert_eq!(manifest["format"], "ndjson");
+		assert_eq!(manifest["source_filename"], "sample dump.ndjson");
+		assert_eq!(manifest["bytes"], FIXTURE.len());
+		assert_eq!(manifest["row_count"], 3);
+		assert_eq!(manifest["record_count"], 3);
+		assert_eq!(manifest["content_sha256"], FIXTURE_SHA256);
+		assert!(manifest["compression"].is_null());
+
+		let dumps = repo.dumps.lock().unwrap();
+		assert_eq!(dumps.len(), 1);
+		assert_eq!(dumps[0].0, manifest["dump_id"].as_str().unwrap());
+		assert_eq!(dumps[0].1["content_sha256"], FIXTURE_SHA256);
+		assert_eq!(dumps[0].1["record_count"], 3);
+		assert!(dumps[0].1.get("dump_id").is_none());
+	}
+
+	#[tokio::test]
+	async fn bulk_upload_emits_lineage_for_the_dump() {
+		let dir = tempfile::tempdir().unwrap();
+		let settings = crate::config::Settings {
+			upload_dir: dir.path().to_string_lossy().into_owned(),
+			..Default::default()
+		};
+		let graph = settings.graph_name();
+		let lineage = Arc::new(crate::lineage::LineageEmitter::in_memory());
+		let state = app_state_with_receiver(Arc::new(RecordingRepo::default()))
+			.0
+			.with_settings(Arc::new(settings))
+			.with_lineage(lineage.clone());
+
+		let req = Request::builder()
+			.uri("/ingest/bulk")
+			.body(Body::from(FIXTURE))
+			.unwrap();
+		let resp = bulk_dump_upload(State(state), req).await.into_response();
+		assert_eq!(resp.status(), StatusCode::OK);
+		let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+			.await
+			.unwrap();
+		let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+		let events = lineage.events();
+		assert_eq!(events.len(), 1);
+		assert_eq!(events[0].job.name, crate::lineage::INGEST_JOB);
+		assert_eq!(events[0].inputs[0].name, json["manifest"]["dump_id"]);
+		assert_eq!(events[0].outputs[0].name, graph);
+		assert_eq!(events[0].outputs[0].facets["entity_count"], 3);
+	}
+}
+
+#[cfg(test)]
+mod db_health_tests {
+	use super::*;
+	use crate::health::DbHealth;
+	use std::sync::Arc;
+
+	const BATCH: &str = r#"[{"field_type": "domain", "canonical": "example.com"}]"#;
+
+	fn state_with_health(
+		healthy: bool,
+	) -> (
+		crate::state::AppState,
+		tokio::sync::mpsc::Receiver<crate::persist::PersistJob>,
+	) {
+		let (state, rx) = crate::ingest::test_utils::app_state_with_receiver(Arc::new(
+			crate::ingest::test_utils::DummyRepo,
+		));
+		let health = Arc::new(DbHealth::new(30));
+		health.set_healthy(healthy);
+		(state.with_db_health(health), rx)
+	}
+
+	#[tokio::test]
+	async fn ingest_is_rejected_while_db_is_down() {
+		let (state, mut rx) = state_with_health(false);
+
+		let resp = records_upload(
+			State(state.clone()),
+			axum::extract::RawQuery(None),
+			axum::http::HeaderMap::new(),
+			None,
+			axum::body::Bytes::from(BATCH),
+		)
+		.await
+		.into_response();
+		assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+		assert_eq!(resp.headers()[axum::http::header::RETRY_AFTER], "30");
+
+		let req = Request::builder()
+			.body(Body::from("{\"domain\": \"example.com\"}\n"))
+			.unwrap();
+		let resp = ndjson_upload(State(state), req).await.into_response();
+		assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+		assert!(rx.try_recv().is_err());
+	}
+
+	#[tokio::test]
+	async fn ingest_proceeds_once_db_recovers() {
+		let (state, mut rx) = state_with_health(false);
+		state.db_health.set_healthy(true);
+
+		let resp = records_upload(
+			State(state),
+			axum::extract::RawQuery(None),
+			axum::http::HeaderMap::new(),
+			None,
+			axum::body::Bytes::from(BATCH),
+		)
+		.await
+		.into_response();
+		assert_eq!(resp.status(), StatusCode::OK);
+		assert_eq!(
+			rx.try_recv().expect("job enqueued").key,
+			"domain:example.com"
+		);
+	}
+}
+
+#[cfg(test)]
+mod classifier_tests {
+	use super::*;
+	use crate::ingest::test_utils::{DummyRepo, app_state_with_receiver};
+	use crate::ingest::{FieldClassifier, FieldClassifiers, FieldKind};
+	use std::sync::Arc;
+
+	/// Tags JSON Web Tokens by their base64url-encoded `{"` prefix.
+	struct JwtClassifier;
+
+	impl FieldClassifier for JwtClassifier {
+		fn classify(&self, value: &str) -> Option<FieldKind> {
+			value
+				.starts_with("eyJ")
+				.then(|| FieldKind::Custom("jwt".to_string()))
+		}
+	}
+
+	#[tokio::test]
+	async fn registered_classifier_labels_ingested_values() {
+		let (state, mut rx) = app_state_with_receiver(Arc::new(DummyRepo));
+		let state = state.with_classifiers(FieldClassifiers::new().with_classifier(JwtClassifier));
+
+		let body = "{\"field_type\":\"username\",\"value\":\"eyJhbGciOiJIUzI1NiJ9.e30.sig\"}\n\
+			{\"field_type\":\"domain\",\"value\":\"Example.COM\"}\n";
+		let req = Request::builder().body(Body::from(body)).unwrap();
+		let resp = ndjson_upload(State(state), req).await.into_response();
+		assert_eq!(resp.status(), StatusCode::OK);
+
+		let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+			.await
+			.unwrap();
+		let records: Vec<crate::ingest::NormalizedRecord> = serde_json::from_slice(&bytes).unwrap();
+		assert_eq!(records[0].field_type, "jwt");
+		assert_eq!(records[1].field_type, "domain");
+
+		let job = rx.try_recv().expect("jwt job");
+		assert_eq!(job.props["field_type"], "jwt");
+		let job = rx.try_recv().expect("domain job");
+		assert_eq!(job.key, "domain:example.com");
+		assert_eq!(job.props["field_type"], "domain");
+	}
+
+	#[tokio::test]
+	async fn ingested_values_carry_detection_confidence() {
+		let (state, mut rx) = app_state_with_receiver(Arc::new(DummyRepo));
+
+		let body = "{\"field_type\":\"ip\",\"value\":\"192.0.2.1\"}\n\
+			{\"field_type\":\"timestamp\",\"value\":\"1705318200\"}\n";
+		let req = Request::builder().body(Body::from(body)).unwrap();
+		let resp = ndjson_upload(State(state), req).await.into_response();
+		assert_eq!(resp.status(), StatusCode::OK);
+
+		let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+			.await
+			.unwrap();
+		let records: Vec<crate::ingest::NormalizedRecord> = serde_json::from_slice(&bytes).unwrap();
+		assert_eq!(records[0].confidence, 1.0);
+		assert!(records[1].confidence < records[0].confidence);
+
+		let ip = rx.try_recv().expect("ip job");
+		assert_eq!(ip.props["confidence"], 1.0);
+		let timestamp = rx.try_recv().expect("timestamp job");
+		assert_eq!(
+			timestamp.props["confidence"].as_f64(),
+			Some(records[1].confidence as f64)
+		);
+	}
+}
+
+#[cfg(test)]
+mod sink_tests {
+	use super::*;
+	use crate::sink::test_utils::MemorySink;
+	use std::sync::Arc;
+
+	const BODY: &str = "{\"field_type\":\"domain\",\"value\":\"Example.COM\"}\n\
+		{\"field_type\":\"ip\",\"value\":\"10.0.0.1\"}\n\
+		not json\n";
+
+	fn state() -> (
+		crate::state::AppState,
+		tokio::sync::mpsc::Receiver<crate::persist::PersistJob>,
+	) {
+		crate::ingest::test_utils::app_state_with_receiver(Arc::new(
+			crate::ingest::test_utils::DummyRepo,
+		))
+	}
+
+	async fn upload(state: crate::state::AppState) -> Vec<crate::ingest::NormalizedRecord> {
+		let req = Request::builder().body(Body::from(BODY)).unwrap();
+		let resp = ndjson_upload(State(state), req).await.into_response();
+		assert_eq!(resp.status(), StatusCode::OK);
+		let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+			.await
+			.unwrap();
+		serde_json::from_slice(&bytes).unwrap()
+	}
+
+	#[tokio::test]
+	async fn every_accepted_record_reaches_the_sink() {
+		let (state, mut rx) = state();
+		let sink = Arc::new(MemorySink::default());
+		let state = state.without_age_sink().with_sink(sink.clone());
+
+		let accepted = upload(state).await;
+		assert_eq!(accepted.len(), 2);
+		assert_eq!(sink.records(), accepted);
+		assert!(rx.try_recv().is_err(), "graph sink was disabled");
+	}
+
+	#[tokio::test]
+	async fn records_fan_out_to_graph_and_sink() {
+		let (state, mut rx) = state();
+		let sink = Arc::new(MemorySink::default());
+		let state = state.with_sink(sink.clone());
+
+		let accepted = upload(state).await;
+		assert_eq!(sink.records(), accepted);
+		for rec in &accepted {
+			let job = rx.try_recv().expect("graph job");
+			assert_eq!(job.key, format!("{}:{}", rec.field_type, rec.canonical));
+		}
+		assert!(rx.try_recv().is_err());
+	}
+
+	async fn upload_recovery_email(
+		state: crate::state::AppState,
+	) -> crate::ingest::NormalizedRecord {
+		let body = "{\"recovery_email\":\"Backup@Example.COM\"}\n";
+		let req = Request::builder().body(Body::from(body)).unwrap();
+		let resp = ndjson_upload(State(state), req).await.into_response();
+		assert_eq!(resp.status(), StatusCode::OK);
+		let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+			.await
+			.unwrap();
+		let mut records: Vec<crate::ingest::NormalizedRecord> =
+			serde_json::from_slice(&bytes).unwrap();
+		assert_eq!(records.len(), 1);
+		records.remove(0)
+	}
+
+	#[tokio::test]
+	async fn pii_rules_for_the_source_field_take_precedence() {
+		use crate::pii::pii_policy::{PiiAction, PiiPolicyConfig, PiiPolicyEngine};
+
+		let config = PiiPolicyConfig {
+			rules: [("recovery_email".to_string(), PiiAction::Scrub)].into(),
+			default_action: PiiAction::Passthrough,
+		};
+		let engine = PiiPolicyEngine::new(config, vec![0x42; 32], "test-key".to_string()).unwrap();
+		let (state, mut rx) = state();
+		let sink = Arc::new(MemorySink::default());
+		let state = state
+			.with_pii_engine(Arc::new(engine))
+			.with_sink(sink.clone());
+
+		let accepted = upload_recovery_email(state).await;
+		assert_eq!(accepted.field_type, "email");
+		assert_eq!(accepted.source_field.as_deref(), Some("recovery_email"));
+
+		let delivered = sink.records();
+		assert_eq!(delivered[0].raw, "[REDACTED]");
+		assert_eq!(delivered[0].source_field.as_deref(), Some("recovery_email"));
+		let job = rx.try_recv().expect("graph job");
+		assert_eq!(job.props["raw"], "[REDACTED]");
+		assert_eq!(job.props["source_field"], "recovery_email");
+	}
+
+	#[tokio::test]
+	async fn source_field_can_be_left_off_delivered_records() {
+		let (state, _rx) = state();
+		let sink = Arc::new(MemorySink::default());
+		let settings = crate::config::Settings {
+			record_source_field: false,
+			..crate::config::Settings::default()
+		};
+		let state = state
+			.with_settings(Arc::new(settings))
+			.without_age_sink()
+			.with_sink(sink.clone());
+
+		let accepted = upload_recovery_email(state).await;
+		assert_eq!(accepted.source_field.as_deref(), Some("recovery_email"));
+		assert_eq!(sink.records()[0].source_field, None);
+	}
+
+	#[tokio::test]
+	async fn card_numbers_are_masked_without_any_pii_rule() {
+		let (state, mut rx) = state();
+		let sink = Arc::new(MemorySink::default());
+		let state = state.with_sink(sink.clone());
+
+		let body = "{\"field_type\":\"username\",\"value\":\"4111 1111 1111 1111\"}\n";
+		let req = Request::builder().body(Body::from(body)).unwrap();
+		let resp = ndjson_upload(State(state), req).await.into_response();
+		assert_eq!(resp.status(), StatusCode::OK);
+		let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+			.await
+			.unwrap();
+		let text = String::from_utf8(bytes.to_vec()).unwrap();
+		assert!(!text.contains("4111 1111 1111 1111"));
+
+		let delivered = sink.records();
+		assert_eq!(delivered[0].field_type, "pan");
+		assert_eq!(delivered[0].canonical, "411111...1111");
+		assert_eq!(delivered[0].raw, "[REDACTED]");
+		let job = rx.try_recv().expect("graph job");
+		assert_eq!(job.key, "pan:411111...1111");
+		assert!(!job.props.to_string().contains("4111111111111111"));
+	}
+
+	#[tokio::test]
+	async fn newlines_inside_string_values_keep_records_whole() {
+		let (state, _rx) = state();
+		let chunks = futures_util::stream::iter(vec![
+			Ok::<_, std::io::Error>(b"{\"field_type\":\"username\",\"value\":\"first\n".to_vec()),
+			Ok::<_, std::io::Error>(b"second\"}\n{\"field_type\":\"domain\",".to_vec()),
+			Ok::<_, std::io::Error>(b"\"value\":\"example.com\"}\n".to_vec()),
+		]);
+		let req = Request::builder().body(Body::from_stream(chunks)).unwrap();
+		let resp = ndjson_upload(State(state), req).await.into_response();
+		assert_eq!(resp.status(), StatusCode::OK);
+		let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+			.await
+			.unwrap();
+		let records: Vec<crate::ingest::NormalizedRecord> = serde_json::from_slice(&bytes).unwrap();
+		assert_eq!(records.len(), 2);
+		assert_eq!(records[0].field_type, "username");
+		assert!(records[0].raw.contains("first\nsecond"));
+		assert_eq!(records[1].canonical, "example.com");
+	}
+
+	#[tokio::test]
+	async fn truncated_record_does_not_swallow_the_records_after_it() {
+		let (state, _rx) = state();
+		let body = "{\"field_type\":\"username\",\"value\":\"cut\n\
+			{\"field_type\":\"domain\",\"value\":\"a.example\"}\n\
+			{\"field_type\":\"domain\",\"value\":\"b.example\"}\n\
+			{\"field_type\":\"domain\",\"value\":\"c.example\"}\n";
+		let req = Request::builder().body(Body::from(body)).unwrap();
+		let resp = ndjson_upload(State(state), req).await.into_response();
+		assert_eq!(resp.status(), StatusCode::OK);
+		let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+			.await
+			.unwrap();
+		let records: Vec<crate::ingest::NormalizedRecord> = serde_json::from_slice(&bytes).unwrap();
+		let domains: Vec<&str> = records
+			.iter()
+			.filter(|r| r.field_type == "domain")
+			.map(|r| r.canonical.as_str())
+			.collect();
+		assert_eq!(domains, ["a.example", "b.example", "c.example"]);
+	}
+}
+
+#[cfg(test)]
+mod utf8_tests {
+	use super::*;
+	use std::sync::Arc;
+
+	/// A valid line, then one whose value contains a lone 0xFF byte.
+	fn body() -> Vec<u8> {
+		let mut body = b"{\"field_type\":\"domain\",\"value\":\"ok.example\"}\n".to_vec();
+		body.extend_from_slice(b"{\"field_type\":\"username\",\"value\":\"al\xFFce\"}\n");
+		body
+	}
+
+	/// Upload `body()` to `uri`, returning the response headers, records and
+	/// the state's metrics.
+	async fn upload(
+		uri: &str,
+	) -> (
+		axum::http::HeaderMap,
+		Vec<crate::ingest::NormalizedRecord>,
+		Arc<crate::observability::MetricsRegistry>,
+	) {
+		let state = crate::ingest::test_utils::create_test_app_state();
+		let metrics = state.metrics.clone();
+		let req = Request::builder()
+			.uri(uri)
+			.body(Body::from(body()))
+			.unwrap();
+		let resp = ndjson_upload(State(state), req).await.into_response();
+		assert_eq!(resp.status(), StatusCode::OK);
+		let headers = resp.headers().clone();
+		let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+			.await
+			.unwrap();
+		(headers, serde_json::from_slice(&bytes).unwrap(), metrics)
+	}
+
+	#[tokio::test]
+	async fn lenient_mode_replaces_invalid_bytes() {
+		let (headers, records, metrics) = upload("/ingest/ndjson").await;
+		assert_eq!(records.len(), 2);
+		assert_eq!(records[1].raw, "al\u{FFFD}ce");
+		assert!(headers.get(INVALID_UTF8_LINES_HEADER).is_none());
+		assert_eq!(metrics.ingest_invalid_utf8_total.get(), 0);
+	}
+
+	#[tokio::test]
+	async fn strict_mode_rejects_and_reports_the_line() {
+		let (headers, records, metrics) = upload("/ingest/ndjson?strict_utf8=true").await;
+		assert_eq!(records.len(), 1);
+		assert_eq!(records[0].raw, "ok.example");
+		assert_eq!(headers[INVALID_UTF8_LINES_HEADER], "2");
+		assert_eq!(metrics.ingest_invalid_utf8_total.get(), 1);
+	}
+}
+
+#[cfg(test)]
+mod oversized_line_tests {
+	use super::*;
+	use axum::body::Bytes;
+
+	#[tokio::test]
+	async fn oversized_line_is_skipped_and_reported() {
+		let state = crate::ingest::test_utils::create_test_app_state();
+		let metrics = state.metrics.clone();
+
+		// A valid line, an 11 MiB line streamed in 1 MiB chunks, then
+		// another valid line.
+		let mut chunks: Vec<Result<Bytes, std::io::Error>> = vec![Ok(Bytes::from_static(
+			b"{\"field_type\":\"domain\",\"value\":\"first.example\"}\n{\"value\":\"",
+		))];
+		for _ in 0..11 {
+			chunks.push(Ok(Bytes::from(vec![b'a'; 1024 * 1024])));
+		}
+		chunks.push(Ok(Bytes::from_static(
+			b"\"}\n{\"field_type\":\"domain\",\"value\":\"second.example\"}\n",
+		)));
+		let body = Body::from_stream(futures_util::stream::iter(chunks));
+		let req = Request::builder().uri("/ingest/ndjson").body(body).unwrap();
+
+		let resp = ndjson_upload(State(state), req).await.into_response();
+		assert_eq!(resp.status(), StatusCode::OK);
+		assert_eq!(resp.headers()[OVERSIZED_LINES_HEADER], "2");
+		let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+			.await
+			.unwrap();
+		let records: Vec<crate::ingest::NormalizedRecord> = serde_json::from_slice(&bytes).unwrap();
+		let raws: Vec<&str> = records.iter().map(|r| r.raw.as_str()).collect();
+		assert_eq!(raws, vec!["first.example", "second.example"]);
+		assert_eq!(metrics.ingest_oversized_lines_total.get(), 1);
+	}
+}
+
+#[cfg(test)]
+mod parse_pool_tests {
+	use super::*;
+	use axum::{Router, routing::post};
+	use tower::ServiceExt;
+
+	async fn upload(app: Router) -> StatusCode {
+		let body = "--X\r\n\
+			Content-Disposition: form-data; name=\"format\"\r\n\r\n\
+			ndjson\r\n\
+			--X\r\n\
+			Content-Disposition: form-data; name=\"file\"; filename=\"a.ndjson\"\r\n\r\n\
+			{\"field_type\":\"domain\",\"value\":\"example.com\"}\n\r\n\
+			--X--\r\n";
+		let req = Request::builder()
+			.method("POST")
+			.uri("/ingest/multipart")
+			.header("content-type", "multipart/form-data; boundary=X")
+			.body(Body::from(body))
+			.unwrap();
+		app.oneshot(req).await.unwrap().status()
+	}
+
+	#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+	async fn saturated_parse_pool_rejects_uploads_with_503() {
+		let state = crate::ingest::test_utils::create_test_app_state().with_parse_workers(1);
+		let app = Router::new()
+			.route("/ingest/multipart", post(multipart_upload))
+			.with_state(state.clone());
+
+		assert_eq!(upload(app.clone()).await, StatusCode::OK);
+
+		// Occupy the only worker.
+		let (release, gate) = std::sync::mpsc::channel::<()>();
+		let (started_tx, started) = tokio::sync::oneshot::channel();
+		let pool = state.parse_pool.clone();
+		let busy = tokio::spawn(async move {
+			pool.run(move || {
+				started_tx.send(()).unwrap();
+				gate.recv().unwrap();
+			})
+			.await
+		});
+		started.await.unwrap();
+
+		assert_eq!(upload(app.clone()).await, StatusCode::SERVICE_UNAVAILABLE);
+
+		release.send(()).unwrap();
+		busy.await.unwrap().unwrap();
+		assert_eq!(upload(app).await, StatusCode::OK);
+	}
+}
+
+#[cfg(test)]
+mod multipart_tests {
+	use super::*;
+	use axum::{Router, routing::post};
+	use std::io::Write;
+	use tower::ServiceExt;
+
+	fn part(name: &str, filename: &str, content: &[u8]) -> Vec<u8> {
+		let mut out = format!(
+			"--X\r\nContent-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n\r\n",
+			name, filename
+		)
+		.into_bytes();
+		out.extend_from_slice(content);
+		out.extend_from_slice(b"\r\n");
+		out
+	}
+
+	fn field(name: &str, content: &[u8]) -> Vec<u8> {
+		let mut out = format!(
+			"--X\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n",
+			name
+		)
+		.into_bytes();
+		out.extend_from_slice(content);
+		out.extend_from_slice(b"\r\n");
+		out
+	}
+
+	async fn post_form(parts: Vec<Vec<u8>>) -> (StatusCode, serde_json::Value) {
+		post_form_with(crate::config::Settings::default(), parts).await
+	}
+
+	async fn post_form_with(
+		settings: crate::config::Settings,
+		parts: Vec<Vec<u8>>,
+	) -> (StatusCode, serde_json::Value) {
+		let state = crate::ingest::test_utils::create_test_app_state()
+			.with_settings(std::sync::Arc::new(settings));
+		let app = Router::new()
+			.route("/ingest/multipart", post(multipart_upload))
+			.with_state(state);
+		let mut body = parts.concat();
+		body.extend_from_slice(b"--X--\r\n");
+		let req = Request::builder()
+			.method("POST")
+			.uri("/ingest/multipart")
+			.header("content-type", "multipart/form-data; boundary=X")
+			.body(Body::from(body))
+			.unwrap();
+		let resp = app.oneshot(req).await.unwrap();
+		let status = resp.status();
+		let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+			.await
+			.unwrap();
+		(status, serde_json::from_slice(&bytes).unwrap_or_default())
+	}
+
+	#[tokio::test]
+	async fn each_file_part_is_parsed_and_reported_separately() {
+		let csv = b"field_type,value\ndomain,Example.COM\nip,192.0.2.1\n";
+		let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+		gz.write_all(b"{\"field_type\":\"email\",\"value\":\"USER@EXAMPLE.COM\"}\n")
+			.unwrap();
+		let gz = gz.finish().unwrap();
+
+		let (status, json) = post_form(vec![
+			part("file", "hosts.csv", csv),
+			part("file[]", "emails.ndjson.gz", &gz),
+			part("file[]", "notes.bin", &[0u8, 159, 146, 150, 0, 1]),
+		])
+		.await;
+		assert_eq!(status, StatusCode::OK);
+
+		let files = json.as_array().expect("array of per-file results");
+		assert_eq!(files.len(), 3);
+		assert_eq!(files[0]["filename"], "hosts.csv");
+		assert_eq!(files[0]["format"], "csv");
+		assert_eq!(files[0]["compressed"], false);
+		assert_eq!(files[0]["records"], 2);
+		assert_eq!(files[0]["errors"], serde_json::json!([]));
+		assert_eq!(files[1]["filename"], "emails.ndjson.gz");
+		assert_eq!(files[1]["format"], "ndjson");
+		assert_eq!(files[1]["compressed"], true);
+		assert_eq!(files[1]["records"], 1);
+		// A file that can't be parsed is reported without failing the rest.
+		assert_eq!(files[2]["records"], 0);
+		assert_eq!(files[2]["errors"].as_array().unwrap().len(), 1);
+	}
+
+	#[tokio::test]
+	async fn single_file_keeps_original_response() {
+		let (status, json) = post_form(vec![part(
+			"file",
+			"hosts.csv",
+			b"field_type,value\ndomain,example.com\n",
+		)])
+		.await;
+		assert_eq!(status, StatusCode::OK);
+		assert_eq!(json["format"], "csv");
+		assert_eq!(json["compressed"], false);
+		assert_eq!(json["records_count"], 1);
+	}
+
+	#[test]
+	fn parsing_dispatches_every_advertised_format() {
+		use crate::ingest::format_detection::FormatType;
+
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("part");
+		let csv = b"field_type,value\ndomain,example.com\n";
+		std::fs::write(&path, csv).unwrap();
+		for format in FormatType::PARSED {
+			let parsed = parse_part(&path, Some(format.as_str()), Default::default());
+			let error = parsed.error.unwrap_or_default();
+			assert!(
+				!error.starts_with("unsupported format"),
+				"{}: {}",
+				format.as_str(),
+				error
+			);
+			assert_eq!(parsed.format, Some(format));
+		}
+
+		// Codecs are unwrapped and their content parsed.
+		let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+		gz.write_all(csv).unwrap();
+		std::fs::write(&path, gz.finish().unwrap()).unwrap();
+		let parsed = parse_part(&path, None, Default::default());
+		assert!(parsed.compressed);
+		assert_eq!(parsed.records.len(), 1);
+
+		std::fs::write(&path, [0u8, 159, 146, 150, 0, 1]).unwrap();
+		let parsed = parse_part(&path, None, Default::default());
+		assert!(parsed.error.unwrap().starts_with("unsupported format"));
+	}
+
+	#[tokio::test]
+	async fn empty_cells_are_skipped_and_counted() {
+		let (state, mut rx) = crate::ingest::test_utils::app_state_with_receiver(
+			std::sync::Arc::new(crate::ingest::test_utils::DummyRepo),
+		);
+		let metrics = state.metrics.clone();
+		let app = Router::new()
+			.route("/ingest/multipart", post(multipart_upload))
+			.with_state(state);
+		let csv = b"field_type,value\ndomain,example.com\ndomain,\nip,   \n";
+		let mut body = part("file", "hosts.csv", csv);
+		body.extend_from_slice(b"--X--\r\n");
+		let req = Request::builder()
+			.method("POST")
+			.uri("/ingest/multipart")
+			.header("content-type", "multipart/form-data; boundary=X")
+			.body(Body::from(body))
+			.unwrap();
+		assert_eq!(app.oneshot(req).await.unwrap().status(), StatusCode::OK);
+
+		let job = rx.try_recv().expect("job for the non-empty cell");
+		assert_eq!(job.key, "domain:example.com");
+		assert!(rx.try_recv().is_err(), "no node for an empty value");
+		assert_eq!(metrics.ingest_empty_skipped_total.get(), 2);
+	}
+
+	#[tokio::test]
+	async fn field_count_and_size_are_capped() {
+		let mut settings = crate::config::Settings::default();
+		settings.multipart_max_fields = 2;
+		let csv = b"field_type,value\ndomain,example.com\n";
+
+		// A file and a format hint are within the cap.
+		let (status, json) = post_form_with(
+			settings.clone(),
+			vec![field("format", b"csv"), part("file", "hosts", csv)],
+		)
+		.await;
+		assert_eq!(status, StatusCode::OK);
+		assert_eq!(json["records_count"], 1);
+
+		// Tiny fields of any name count towards it.
+		let mut many = vec![part("file", "hosts.csv", csv)];
+		many.extend((0..100).map(|i| field(&format!("f{}", i), b"x")));
+		let (status, _) = post_form_with(settings.clone(), many).await;
+		assert_eq!(status, StatusCode::BAD_REQUEST);
+
+		let (status, _) = post_form(vec![
+			field("format", &[b'c'; FORMAT_HINT_MAX_BYTES + 1]),
+			part("file", "hosts.csv", csv),
+		])
+		.await;
+		assert_eq!(status, StatusCode::BAD_REQUEST);
+
+		settings.multipart_max_bytes = csv.len() - 1;
+		let (status, _) = post_form_with(settings, vec![part("file", "hosts.csv", csv)]).await;
+		assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+	}
+
+	#[tokio::test]
+	async fn same_file_hashes_alike_on_bulk_and_multipart() {
+		use crate::ingest::content_hash::CONTENT_SHA256_HEADER;
+
+		let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+		gz.write_all(b"{\"field_type\":\"domain\",\"value\":\"example.com\"}\n")
+			.unwrap();
+		let gz = gz.finish().unwrap();
+		// The compressed bytes as sent are hashed, not what they decode to.
+		let expected = content_sha256(&gz);
+
+		let dir = tempfile::tempdir().unwrap();
+		let settings = crate::config::Settings {
+			upload_dir: dir.path().to_string_lossy().into_owned(),
+			..Default::default()
+		};
+		let state = crate::ingest::test_utils::create_test_app_state()
+			.with_settings(std::sync::Arc::new(settings.clone()));
+		let req = Request::builder()
+			.uri("/ingest/bulk")
+			.body(Body::from(gz.clone()))
+			.unwrap();
+		let resp = bulk_dump_upload(State(state), req).await.into_response();
+		assert_eq!(resp.status(), StatusCode::OK);
+		assert_eq!(resp.headers()[CONTENT_SHA256_HEADER], expected.as_str());
+		let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+			.await
+			.unwrap();
+		let bulk: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+		assert_eq!(bulk["manifest"]["content_sha256"], expected);
+
+		let (status, single) =
+			post_form_with(settings.clone(), vec![part("file", "a.ndjson.gz", &gz)]).await;
+		assert_eq!(status, StatusCode::OK);
+		assert_eq!(single["compressed"], true);
+		assert_eq!(single["content_sha256"], expected);
+
+		let (status, files) = post_form_with(
+			settings,
+			vec![
+				part("file[]", "a.ndjson.gz", &gz),
+				part("file[]", "b.csv", b"field_type,value\nip,10.0.0.1\n"),
+			],
+		)
+		.await;
+		assert_eq!(status, StatusCode::OK);
+		assert_eq!(files[0]["content_sha256"], expected);
+		assert_eq!(
+			files[1]["content_sha256"],
+			content_sha256(b"field_type,value\nip,10.0.0.1\n")
+		);
+	}
+}
+
+#[cfg(test)]
+mod private_ip_tests {
+	use super::*;
+	use crate::ingest::ip_policy::PRIVATE_IPS_HEADER;
+
+	const BODY: &str = "{\"field_type\":\"ip\",\"value\":\"10.0.0.1\"}\n\
+{\"field_type\":\"ip\",\"value\":\"127.0.0.1\"}\n\
+{\"field_type\":\"ip\",\"value\":\"169.254.0.1\"}\n\
+{\"field_type\":\"ip\",\"value\":\"8.8.8.8\"}\n";
+
+	async fn upload(uri: &str) -> (axum::response::Response, crate::state::AppState) {
+		let state = crate::ingest::test_utils::create_test_app_state();
+		let req = Request::builder().uri(uri).body(Body::from(BODY)).unwrap();
+		let resp = ndjson_upload(State(state.clone()), req)
+			.await
+			.into_response();
+		(resp, state)
+	}
+
+	async fn canonical_values(resp: axum::response::Response) -> Vec<String> {
+		let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+			.await
+			.unwrap();
+		let records: Vec<crate::ingest::NormalizedRecord> = serde_json::from_slice(&bytes).unwrap();
+		records.into_iter().map(|r| r.canonical).collect()
+	}
+
+	#[tokio::test]
+	async fn drop_policy_keeps_only_global_addresses() {
+		let (resp, state) = upload("/ingest/ndjson?private_ips=drop").await;
+		assert_eq!(resp.status(), StatusCode::OK);
+		assert_eq!(resp.headers()[PRIVATE_IPS_HEADER], "3");
+		assert_eq!(canonical_values(resp).await, ["8.8.8.8"]);
+		assert_eq!(state.metrics.ingest_private_ip_filtered_total.get(), 3);
+	}
+
+	#[tokio::test]
+	async fn flag_policy_keeps_and_reports_private_addresses() {
+		let (resp, state) = upload("/ingest/ndjson?private_ips=flag").await;
+		assert_eq!(resp.headers()[PRIVATE_IPS_HEADER], "3");
+		assert_eq!(canonical_values(resp).await.len(), 4);
+		assert_eq!(state.metrics.ingest_private_ip_filtered_total.get(), 3);
+	}
+
+	#[tokio::test]
+	async fn default_policy_allows_everything() {
+		let (resp, state) = upload("/ingest/ndjson").await;
+		assert!(resp.headers().get(PRIVATE_IPS_HEADER).is_none());
+		assert_eq!(canonical_values(resp).await.len(), 4);
+		assert_eq!(state.metrics.ingest_private_ip_filtered_total.get(), 0);
+
+		let (resp, _) = upload("/ingest/ndjson?private_ips=reject").await;
+		assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+	}
+
+	#[tokio::test]
+	async fn records_endpoint_applies_the_configured_policy() {
+		let mut settings = crate::config::Settings::default();
+		settings.private_ip_policy = "drop".to_string();
+		let state = crate::ingest::test_utils::create_test_app_state()
+			.with_settings(std::sync::Arc::new(settings));
+		let batch = r#"[
+			{"field_type": "ip", "raw": "10.0.0.1", "canonical": "10.0.0.1"},
+			{"field_type": "ip", "raw": "8.8.8.8", "canonical": "8.8.8.8"}
+		]"#;
+		let resp = records_upload(
+			State(state),
+			axum::extract::RawQuery(None),
+			axum::http::HeaderMap::new(),
+			None,
+			axum::body::Bytes::from(batch),
+		)
+		.await
+		.into_response();
+		assert_eq!(resp.status(), StatusCode::OK);
+		assert_eq!(resp.headers()[PRIVATE_IPS_HEADER], "1");
+		let expected = content_sha256(batch.as_bytes());
+		assert_eq!(
+			resp.headers()[crate::ingest::CONTENT_SHA256_HEADER],
+			expected.as_str()
+		);
+		let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+			.await
+			.unwrap();
+		let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+		assert_eq!(body["records_count"], 1);
+		assert_eq!(body["content_sha256"], expected);
+	}
+}
+
+#[cfg(test)]
+mod sync_persist_tests {
+	use super::*;
+	use crate::ingest::test_utils::{RecordingRepo, app_state_with_receiver};
+
+	const BODY: &str = "{\"field_type\":\"domain\",\"value\":\"Example.COM\"}\n\
+		{\"field_type\":\"email\",\"value\":\"\"}\n\
+		{\"field_type\":\"ip\",\"value\":\"192.0.2.1\"}\n";
+
+	/// Repo that takes a while to write a batch.
+	fn slow_repo() -> RecordingRepo {
+		RecordingRepo {
+			delay: Some(std::time::Duration::from_millis(50)),
+			..RecordingRepo::default()
+		}
+	}
+
+	fn settings(max_bytes: usize) -> Arc<crate::config::Settings> {
+		Arc::new(crate::config::Settings {
+			sync_ingest_max_bytes: max_bytes,
+			..Default::default()
+		})
+	}
+
+	fn state(
+		max_bytes: usize,
+	) -> (
+		crate::state::AppState,
+		Arc<RecordingRepo>,
+		tokio::sync::mpsc::Receiver<crate::persist::PersistJob>,
+	) {
+		let repo = Arc::new(slow_repo());
+		// Nothing drains the batcher channel, so enqueued jobs stay there.
+		let (state, rx) = app_state_with_receiver(repo.clone());
+		(state.with_settings(settings(max_bytes)), repo, rx)
+	}
+
+	/// A state whose jobs go through a running batcher with `opts`.
+	fn batched_state(
+		opts: crate::persist::BatcherOptions,
+	) -> (crate::state::AppState, Arc<RecordingRepo>) {
+		let repo = Arc::new(slow_repo());
+		let metrics = Arc::new(crate::observability::MetricsRegistry::new());
+		let tx = crate::persist::start_batcher_with_options(repo.clone(), metrics.clone(), opts);
+		let state =
+			crate::state::AppState::new(repo.clone(), tx, metrics).with_settings(settings(1024));
+		(state, repo)
+	}
+
+	async fn persisted(resp: axum::response::Response) -> Vec<PersistedRecord> {
+		let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+			.await
+			.unwrap();
+		let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+		assert_eq!(body["records"].as_array().unwrap().len(), 3);
+		serde_json::from_value(body["persisted"].clone()).unwrap()
+	}
+
+	async fn upload(state: crate::state::AppState) -> axum::response::Response {
+		let req = Request::builder()
+			.uri("/ingest/ndjson?sync=true")
+			.body(Body::from(BODY))
+			.unwrap();
+		let resp = ndjson_upload(State(state), req).await.into_response();
+		assert_eq!(resp.status(), StatusCode::OK);
+		resp
+	}
+
+	#[tokio::test]
+	async fn sync_ingest_returns_after_the_repo_has_every_record() {
+		let (state, repo) = batched_state(crate::persist::BatcherOptions {
+			flush_interval_ms: 10,
+			..Default::default()
+		});
+
+		let resp = upload(state).await;
+
+		assert_eq!(repo.keys(), ["domain:example.com", "ip:192.0.2.1"]);
+		assert_eq!(resp.headers()[PERSIST_MODE_HEADER], "sync");
+		// The empty email was skipped, so it is not reported.
+		assert_eq!(
+			persisted(resp).await,
+			[
+				PersistedRecord {
+					index: 0,
+					key: "domain:example.com".to_string(),
+				},
+				PersistedRecord {
+					index: 2,
+					key: "ip:192.0.2.1".to_string(),
+				},
+			]
+		);
+	}
+
+	#[tokio::test]
+	async fn sync_ingest_reports_only_records_written_under_their_label() {
+		use crate::persist::schema::{LabelSchema, LabelSchemas, PropertyType, SchemaViolation};
+		let schemas = LabelSchemas::new(SchemaViolation::Quarantine).with_schema(
+			"FieldValue",
+			LabelSchema {
+				required: [("missing".to_string(), PropertyType::String)].into(),
+				optional: Default::default(),
+				additional: true,
+			},
+		);
+		let (state, repo) = batched_state(crate::persist::BatcherOptions {
+			flush_interval_ms: 10,
+			schemas: Some(Arc::new(schemas)),
+			..Default::default()
+		});
+
+		let resp = upload(state).await;
+
+		// Both were quarantined, so neither counts as persisted.
+		assert_eq!(repo.keys().len(), 2);
+		assert!(persisted(resp).await.is_empty());
+	}
+
+	#[tokio::test]
+	async fn large_sync_ingest_falls_back_to_the_batcher() {
+		let (state, repo, mut rx) = state(16);
+
+		let resp = upload(state).await;
+
+		assert_eq!(resp.headers()[PERSIST_MODE_HEADER], "async");
+		assert!(repo.keys().is_empty());
+		assert_eq!(rx.try_recv().unwrap().key, "domain:example.com");
+		assert_eq!(rx.try_recv().unwrap().key, "ip:192.0.2.1");
+		assert!(rx.try_recv().is_err());
 	}
 }
@@ -0,0 +1,155 @@
+//! Screening of private and reserved IP addresses at ingest.
+//!
+//! Some feeds should only ever carry publicly routable addresses; RFC 1918,
+//! loopback, link-local or other reserved addresses in them indicate bad
+//! data. A [`PrivateIpPolicy`], set globally with `Settings.private_ip_policy`
+//! or per request with `?private_ips=`, either lets such records through,
+//! flags them (keeps them and reports how many were seen) or drops them.
+
+use std::str::FromStr;
+
+use crate::ingest::{FieldKind, NormalizedRecord};
+use crate::lib::normalizers::normalize_ip;
+use crate::observability::MetricsRegistry;
+
+/// Query parameter selecting the policy for one request.
+pub const PRIVATE_IPS_PARAM: &str = "private_ips";
+
+/// Response header carrying how many private or reserved IP records the
+/// request's policy flagged or dropped.
+pub const PRIVATE_IPS_HEADER: &str = "x-private-ips";
+
+/// What ingest does with `ip` records that are not globally routable.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PrivateIpPolicy {
+	/// Accept them like any other record.
+	#[default]
+	Allow,
+	/// Accept them, counting and reporting them.
+	Flag,
+	/// Count and discard them.
+	Drop,
+}
+
+impl FromStr for PrivateIpPolicy {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.trim().to_ascii_lowercase().as_str() {
+			"allow" => Ok(Self::Allow),
+			"flag" => Ok(Self::Flag),
+			"drop" => Ok(Self::Drop),
+			other => Err(format!("unknown private IP policy '{}'", other)),
+		}
+	}
+}
+
+/// Whether `rec` is an `ip` record whose address is private, loopback,
+/// link-local or reserved. Values that do not parse as addresses are not.
+pub fn is_non_global_ip(rec: &NormalizedRecord) -> bool {
+	FieldKind::from_field_type(&rec.field_type) == FieldKind::Ip
+		&& normalize_ip(&rec.canonical).is_ok_and(|ip| !ip.is_global)
+}
+
+/// Applies a [`PrivateIpPolicy`] to the records of one request.
+pub struct PrivateIpFilter<'a> {
+	policy: PrivateIpPolicy,
+	metrics: &'a MetricsRegistry,
+	matched: usize,
+}
+
+impl<'a> PrivateIpFilter<'a> {
+	pub fn new(policy: PrivateIpPolicy, metrics: &'a MetricsRegistry) -> Self {
+		Self {
+			policy,
+			metrics,
+			matched: 0,
+		}
+	}
+
+	/// Whether `rec` should be ingested. Non-global addresses are counted
+	/// unless the policy allows them, and rejected under
+	/// [`PrivateIpPolicy::Drop`].
+	pub fn admit(&mut self, rec: &NormalizedRecord) -> bool {
+		if self.policy == PrivateIpPolicy::Allow || !is_non_global_ip(rec) {
+			return true;
+		}
+		self.matched += 1;
+		self.metrics.ingest_private_ip_filtered_total.inc();
+		self.policy == PrivateIpPolicy::Flag
+	}
+
+	/// Keep only the admitted records of `records`.
+	pub fn retain(&mut self, records: &mut Vec<NormalizedRecord>) {
+		records.retain(|rec| self.admit(rec));
+	}
+
+	/// Records flagged or dropped so far.
+	pub fn matched(&self) -> usize {
+		self.matched
+	}
+
+	/// Log and attach the [`PRIVATE_IPS_HEADER`] to `resp` when any record
+	/// was flagged or dropped.
+	pub fn report(&self, endpoint: &'static str, resp: &mut axum::response::Response) {
+		if self.matched == 0 {
+			return;
+		}
+		tracing::warn!(
+			endpoint,
+			policy = ?self.policy,
+			records = self.matched,
+			"private or reserved IP addresses in ingest"
+		);
+		resp.headers_mut().insert(
+			PRIVATE_IPS_HEADER,
+			axum::http::HeaderValue::from(self.matched),
+		);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn non_global_ip_records_are_filtered_by_policy() {
+		let records = || {
+			vec![
+				NormalizedRecord::new("ip", "10.0.0.1", "10.0.0.1"),
+				NormalizedRecord::new("ip", "127.0.0.1", "127.0.0.1"),
+				NormalizedRecord::new("ip", "169.254.0.1", "169.254.0.1"),
+				NormalizedRecord::new("ip", "8.8.8.8", "8.8.8.8"),
+				NormalizedRecord::new("domain", "10.0.0.1", "10.0.0.1"),
+			]
+		};
+		let metrics = MetricsRegistry::new();
+
+		let mut kept = records();
+		let mut filter = PrivateIpFilter::new(PrivateIpPolicy::Drop, &metrics);
+		filter.retain(&mut kept);
+		assert_eq!(filter.matched(), 3);
+		let canonical: Vec<_> = kept.iter().map(|r| r.canonical.as_str()).collect();
+		assert_eq!(canonical, ["8.8.8.8", "10.0.0.1"]);
+		assert_eq!(kept[1].field_type, "domain");
+
+		let mut flagged = records();
+		let mut filter = PrivateIpFilter::new(PrivateIpPolicy::Flag, &metrics);
+		filter.retain(&mut flagged);
+		assert_eq!((filter.matched(), flagged.len()), (3, 5));
+
+		let mut allowed = records();
+		let mut filter = PrivateIpFilter::new(PrivateIpPolicy::Allow, &metrics);
+		filter.retain(&mut allowed);
+		assert_eq!((filter.matched(), allowed.len()), (0, 5));
+
+		assert_eq!(metrics.ingest_private_ip_filtered_total.get(), 6);
+	}
+
+	#[test]
+	fn policy_parses_case_insensitively() {
+		assert_eq!("Drop".parse(), Ok(PrivateIpPolicy::Drop));
+		assert_eq!(" flag ".parse(), Ok(PrivateIpPolicy::Flag));
+		assert!("reject".parse::<PrivateIpPolicy>().is_err());
+	}
+}
@@ -0,0 +1,229 @@
+//! Graph keys for normalized records.
+//!
+//! A record's node key is its canonical value behind a prefix chosen by
+//! field kind, e.g. `email:user@example.com` or `ip:192.0.2.1`, so equal
+//! values of different kinds stay distinct nodes. Prefixes may contain
+//! `{algorithm}`, replaced by the hash algorithm detected from the value;
+//! hashes default to `hash:{algorithm}:`, giving keys like
+//! `hash:md5:d41d8cd98f00b204e9800998ecf8427e`. Such keys end in the
+//! algorithm-prefixed canonical form of the hash (`md5:<hex>`), so a value
+//! labelled `ntlm:<hex>` does not share a key with the bare MD5 digest.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::ingest::{FieldKind, NormalizedRecord};
+use crate::lib::normalizers::cache::NormalizationCache;
+use crate::lib::normalizers::{
+	NormalizeHashOptions, NormalizedHash, NormalizedValue, normalize_hash_with,
+};
+
+/// Version of the key scheme: how keys are built from a kind prefix and a
+/// canonical value, then salted. Bumped when existing keys would change.
+pub const CANONICAL_KEY_SCHEME_VERSION: u32 = 1;
+
+/// Placeholder replaced by the detected hash algorithm.
+const ALGORITHM_PLACEHOLDER: &str = "{algorithm}";
+
+/// Prefix for kinds without their own entry: the kind name and a colon.
+const KIND_PLACEHOLDER: &str = "{kind}";
+
+/// Field kind → key prefix mapping.
+#[derive(Debug, Clone)]
+pub struct KeyPrefixMap {
+	prefixes: HashMap<String, String>,
+	/// Prefix for kinds without an entry; `{kind}` expands to the kind.
+	fallback: String,
+	/// Memoizes hash algorithm detection; not part of the mapping.
+	cache: Option<Arc<NormalizationCache>>,
+}
+
+impl PartialEq for KeyPrefixMap {
+	fn eq(&self, other: &Self) -> bool {
+		self.prefixes == other.prefixes && self.fallback == other.fallback
+	}
+}
+
+impl Eq for KeyPrefixMap {}
+
+impl Default for KeyPrefixMap {
+	/// The `kind:` convention, with algorithm-qualified hash keys.
+	fn default() -> Self {
+		Self {
+			prefixes: HashMap::from([(
+				FieldKind::Hash.as_str().to_string(),
+				format!("hash:{}:", ALGORITHM_PLACEHOLDER),
+			)]),
+			fallback: format!("{}:", KIND_PLACEHOLDER),
+			cache: None,
+		}
+	}
+}
+
+impl KeyPrefixMap {
+	/// A map that adds no prefixes; keys are the bare canonical values.
+	pub fn none() -> Self {
+		Self {
+			prefixes: HashMap::new(),
+			fallback: String::new(),
+			cache: None,
+		}
+	}
+
+	/// Detect hash algorithms through `cache`.
+	pub fn with_cache(mut self, cache: Arc<NormalizationCache>) -> Self {
+		self.cache = Some(cache);
+		self
+	}
+
+	/// Cache shared with other users of the normalizers, if any.
+	pub fn cache(&self) -> Option<&Arc<NormalizationCache>> {
+		self.cache.as_ref()
+	}
+
+	/// Use `prefix` for records of `kind`. The kind `*` sets the prefix
+	/// for kinds without their own entry.
+	pub fn with_prefix(mut self, kind: &str, prefix: impl Into<String>) -> Self {
+		if kind.trim() == "*" {
+			self.fallback = prefix.into();
+		} else {
+			self.prefixes
+				.insert(FieldKind::from_field_type(kind).to_string(), prefix.into());
+		}
+		self
+	}
+
+	/// Parse comma-separated `kind=prefix` overrides of the default map,
+	/// e.g. `email=mail:,*=`. An empty spec yields the default map.
+	pub fn parse(spec: &str) -> Result<Self, String> {
+		let mut map = Self::default();
+		for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+			let (kind, prefix) = entry
+				.split_once('=')
+				.ok_or_else(|| format!("key prefix entry '{}' is not kind=prefix", entry))?;
+			if kind.trim().is_empty() {
+				return Err(format!("key prefix entry '{}' has no kind", entry));
+			}
+			map = map.with_prefix(kind, prefix.trim());
+		}
+		Ok(map)
+	}
+
+	/// Prefix for a value of `field_type` whose canonical form is `canonical`.
+	pub fn prefix(&self, field_type: &str, canonical: &str) -> String {
+		self.parts(field_type, canonical).0
+	}
+
+	/// Unsalted key for a value of `field_type`.
+	pub fn key(&self, field_type: &str, canonical: &str) -> String {
+		let (prefix, hash) = self.parts(field_type, canonical);
+		match hash {
+			Some(h) => format!("{}{}", prefix, h.hex),
+			None => format!("{}{}", prefix, canonical),
+		}
+	}
+
+	/// Expanded prefix, plus the detected hash when the prefix names its
+	/// algorithm; the key then carries the hex without any label.
+	fn parts(&self, field_type: &str, canonical: &str) -> (String, Option<NormalizedHash>) {
+		let kind = FieldKind::from_field_type(field_type).to_string();
+		let template = self.prefixes.get(&kind).unwrap_or(&self.fallback);
+		let prefix = template.replace(KIND_PLACEHOLDER, &kind);
+		if !prefix.contains(ALGORITHM_PLACEHOLDER) {
+			return (prefix, None);
+		}
+		let hash = match &self.cache {
+			Some(cache) => match cache.normalize("hash", canonical) {
+				Ok(NormalizedValue::Hash(h)) => Some(h),
+				_ => None,
+			},
+			None => normalize_hash_with(
+				canonical,
+				NormalizeHashOptions {
+					prefix_algorithm: true,
+				},
+			)
+			.ok(),
+		};
+		let algorithm = hash.as_ref().map_or("unknown", |h| h.algorithm.as_str());
+		(prefix.replace(ALGORITHM_PLACEHOLDER, algorithm), hash)
+	}
+
+	/// Unsalted key for `record`.
+	pub fn key_for(&self, record: &NormalizedRecord) -> String {
+		self.key(&record.field_type, &record.canonical)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn default_prefixes_follow_kind_convention() {
+		let map = KeyPrefixMap::default();
+		let email = NormalizedRecord::new("email", "User@Example.COM", "User@example.com");
+		assert_eq!(map.key_for(&email), "email:User@example.com");
+		assert_eq!(map.key("IP", "192.168.1.1"), "ip:192.168.1.1");
+		assert_eq!(map.key("asset_id", "A-1"), "asset_id:A-1");
+	}
+
+	#[test]
+	fn hash_keys_carry_detected_algorithm() {
+		let map = KeyPrefixMap::default();
+		let md5 = NormalizedRecord::new(
+			"hash",
+			"D41D8CD98F00B204E9800998ECF8427E",
+			"d41d8cd98f00b204e9800998ecf8427e",
+		);
+		assert_eq!(
+			map.key_for(&md5),
+			"hash:md5:d41d8cd98f00b204e9800998ecf8427e"
+		);
+		let sha256 = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+		assert_eq!(map.key("hash", sha256), format!("hash:sha256:{}", sha256));
+		assert_eq!(map.key("hash", "abc"), "hash:unknown:abc");
+	}
+
+	#[test]
+	fn same_length_hashes_of_different_algorithms_get_distinct_keys() {
+		let map = KeyPrefixMap::default();
+		let md5 = map.key("hash", "8846f7eaee8fb117ad06bdd830b7586c");
+		let ntlm = map.key("hash", "ntlm:8846f7eaee8fb117ad06bdd830b7586c");
+		assert_eq!(md5, "hash:md5:8846f7eaee8fb117ad06bdd830b7586c");
+		assert_eq!(ntlm, "hash:ntlm:8846f7eaee8fb117ad06bdd830b7586c");
+		assert_ne!(md5, ntlm);
+		assert_eq!(
+			map.prefix("hash", "ntlm:8846f7eaee8fb117ad06bdd830b7586c"),
+			"hash:ntlm:"
+		);
+	}
+
+	#[test]
+	fn overrides_parse_and_can_disable_prefixes() {
+		let map = KeyPrefixMap::parse("email=mail:, *=").unwrap();
+		assert_eq!(map.key("email", "a@example.com"), "mail:a@example.com");
+		assert_eq!(map.key("domain", "example.com"), "example.com");
+
+		assert_eq!(KeyPrefixMap::parse("").unwrap(), KeyPrefixMap::default());
+		assert_eq!(KeyPrefixMap::none().key("ip", "10.0.0.1"), "10.0.0.1");
+		assert!(KeyPrefixMap::parse("email").is_err());
+		assert!(KeyPrefixMap::parse("=x:").is_err());
+	}
+
+	#[test]
+	fn cached_hash_detection_matches_uncached() {
+		let cache = Arc::new(NormalizationCache::new(8));
+		let map = KeyPrefixMap::default().with_cache(cache.clone());
+		let md5 = "d41d8cd98f00b204e9800998ecf8427e";
+		for _ in 0..3 {
+			assert_eq!(
+				map.key("hash", md5),
+				KeyPrefixMap::default().key("hash", md5)
+			);
+		}
+		assert_eq!(map.key("hash", "abc"), "hash:unknown:abc");
+		assert_eq!((cache.hits(), cache.misses()), (2, 2));
+		assert_eq!(map, KeyPrefixMap::default());
+	}
+}
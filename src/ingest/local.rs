@@ -0,0 +1,380 @@
+//! Ingesting dumps already on the server's disk.
+//!
+//! `POST /ingest/local` takes `{"path": "..."}` naming a file under
+//! `Settings.local_ingest_dir` and runs it through the bulk pipeline in
+//! place, instead of uploading it over HTTP. The endpoint answers `404`
+//! unless a directory is configured, and only serves loopback peers and
+//! bearer-token subjects listed in `Settings.local_ingest_subjects`. The
+//! path is resolved against the directory, with symlinks followed, and must
+//! stay inside it.
+
+use std::io::Read;
+use std::net::SocketAddr;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::time::Instant;
+
+use axum::{
+	Json,
+	body::Body,
+	extract::{ConnectInfo, State},
+	http::{Request, StatusCode},
+	response::{IntoResponse, Response},
+};
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+
+use crate::auth::Subject;
+use crate::ingest::bulk_tasks::{BulkOutcome, run_bulk_task};
+use crate::ingest::handler::{
+	BulkDelivery, DETECT_PEEK_BYTES, detect_dump_type, process_bulk_reader,
+};
+use crate::ingest::source::ingest_source;
+use crate::state::AppState;
+
+/// Largest request body accepted; it only names a file.
+const MAX_REQUEST_BYTES: usize = 64 * 1024;
+
+/// Body of `POST /ingest/local`.
+#[derive(Debug, Deserialize)]
+pub struct LocalIngestRequest {
+	/// File to ingest, relative to the allowed directory or absolute
+	/// within it.
+	pub path: String,
+}
+
+#[derive(Debug, Serialize)]
+struct LocalIngestResponse {
+	path: String,
+	kind: String,
+	bytes: u64,
+	compressed: bool,
+	outcome: BulkOutcome,
+}
+
+/// Ingest a file from the allowed local directory through the bulk
+/// pipeline, answering once it has been processed.
+#[tracing::instrument(skip(state, req), fields(endpoint = "local"))]
+pub async fn local_ingest(State(state): State<AppState>, req: Request<Body>) -> Response {
+	let start_time = Instant::now();
+	if state.settings.local_ingest_dir.is_empty() {
+		return StatusCode::NOT_FOUND.into_response();
+	}
+	let peer = req
+		.extensions()
+		.get::<ConnectInfo<SocketAddr>>()
+		.map(|ConnectInfo(peer)| *peer);
+	if let Err(resp) = authorize(&state, req.headers(), peer).await {
+		return resp;
+	}
+	state.metrics.ingest_requests_total.inc();
+	if let Some(resp) = state.db_health.reject_if_down() {
+		return resp;
+	}
+
+	let source = ingest_source(
+		&state.settings,
+		req.headers(),
+		req.extensions().get::<Subject>(),
+	);
+	let body = match axum::body::to_bytes(req.into_body(), MAX_REQUEST_BYTES).await {
+		Ok(body) => body,
+		Err(e) => {
+			return (
+				StatusCode::BAD_REQUEST,
+				format!("failed to read request body: {}", e),
+			)
+				.into_response();
+		}
+	};
+	let request: LocalIngestRequest = match serde_json::from_slice(&body) {
+		Ok(request) => request,
+		Err(e) => {
+			return (StatusCode::BAD_REQUEST, format!("invalid request: {}", e)).into_response();
+		}
+	};
+	let path = match resolve(Path::new(&state.settings.local_ingest_dir), &request.path) {
+		Ok(path) => path,
+		Err(rejection) => {
+			tracing::warn!(
+				path = %request.path,
+				reason = rejection.reason(),
+				"local ingest path rejected"
+			);
+			return rejection.into_response();
+		}
+	};
+
+	let (peek, bytes) = match peek_file(&path).await {
+		Ok(peeked) => peeked,
+		Err(e) => {
+			state.metrics.ingest_errors_total.inc();
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				format!("failed to read file: {}", e),
+			)
+				.into_response();
+		}
+	};
+	state.metrics.ingest_bytes_total.inc_by(bytes as f64);
+	let (kind, _, compressed) = detect_dump_type(&peek, state.settings.json_detect_sample_bytes);
+
+	let delivery = BulkDelivery::from_state(&state, source);
+	let deadline = match state.settings.bulk_process_timeout_secs {
+		0 => None,
+		secs => Some(std::time::Duration::from_secs(secs)),
+	};
+	let task = format!("local_{}", crate::ingest::uploads::upload_filename("bin"));
+	let file = path.clone();
+	let outcome = run_bulk_task(state.bulk_tasks.clone(), task, deadline, move |cancel| {
+		process_local_file(&file, compressed, &delivery, cancel)
+	})
+	.await;
+
+	state
+		.metrics
+		.ingest_duration_seconds
+		.observe(start_time.elapsed().as_secs_f64());
+	let status = if outcome == BulkOutcome::Completed {
+		StatusCode::OK
+	} else {
+		state.metrics.ingest_errors_total.inc();
+		StatusCode::INTERNAL_SERVER_ERROR
+	};
+	let resp = LocalIngestResponse {
+		path: path.to_string_lossy().into_owned(),
+		kind,
+		bytes,
+		compressed,
+		outcome,
+	};
+	(status, Json(resp)).into_response()
+}
+
+/// Admit loopback peers, and others whose bearer token's subject is in
+/// `Settings.local_ingest_subjects`.
+async fn authorize(
+	state: &AppState,
+	headers: &axum::http::HeaderMap,
+	peer: Option<SocketAddr>,
+) -> Result<(), Response> {
+	if peer.is_some_and(|peer| peer.ip().is_loopback()) {
+		return Ok(());
+	}
+	if state.settings.local_ingest_subjects.is_empty() {
+		return Err((
+			StatusCode::FORBIDDEN,
+			"local ingest is only available from localhost",
+		)
+			.into_response());
+	}
+	let claims = crate::auth::authenticate(state, headers).await?;
+	if state.settings.local_ingest_subjects.contains(&claims.sub) {
+		Ok(())
+	} else {
+		Err((StatusCode::FORBIDDEN, "subject may not use local ingest").into_response())
+	}
+}
+
+/// Why a requested path was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PathRejection {
+	/// The path contains a `..` component.
+	Traversal,
+	/// The path resolves outside the allowed directory.
+	OutsideAllowedDir,
+	/// Nothing exists at the path.
+	NotFound,
+	/// The path is not a regular file.
+	NotAFile,
+	/// The allowed directory itself cannot be resolved.
+	DirUnavailable,
+}
+
+impl PathRejection {
+	fn reason(self) -> &'static str {
+		match self {
+			Self::Traversal => "path must not contain '..'",
+			Self::OutsideAllowedDir => "path is outside the allowed directory",
+			Self::NotFound => "file not found",
+			Self::NotAFile => "path is not a regular file",
+			Self::DirUnavailable => "allowed directory is unavailable",
+		}
+	}
+}
+
+impl IntoResponse for PathRejection {
+	fn into_response(self) -> Response {
+		let status = match self {
+			Self::Traversal | Self::OutsideAllowedDir => StatusCode::FORBIDDEN,
+			Self::NotFound => StatusCode::NOT_FOUND,
+			Self::NotAFile => StatusCode::BAD_REQUEST,
+			Self::DirUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+		};
+		(status, self.reason()).into_response()
+	}
+}
+
+/// Resolve `requested` against `dir`, following symlinks, and check the
+/// result is a regular file inside `dir`.
+fn resolve(dir: &Path, requested: &str) -> Result<PathBuf, PathRejection> {
+	let requested = Path::new(requested);
+	if requested.components().any(|c| c == Component::ParentDir) {
+		return Err(PathRejection::Traversal);
+	}
+	let root = dir
+		.canonicalize()
+		.map_err(|_| PathRejection::DirUnavailable)?;
+	let resolved = root
+		.join(requested)
+		.canonicalize()
+		.map_err(|_| PathRejection::NotFound)?;
+	if !resolved.starts_with(&root) {
+		return Err(PathRejection::OutsideAllowedDir);
+	}
+	if !resolved.is_file() {
+		return Err(PathRejection::NotAFile);
+	}
+	Ok(resolved)
+}
+
+/// The leading bytes of `path` used for type detection, and its size.
+async fn peek_file(path: &Path) -> std::io::Result<(Vec<u8>, u64)> {
+	use tokio::io::AsyncReadExt;
+
+	let file = tokio::fs::File::open(path).await?;
+	let bytes = file.metadata().await?.len();
+	let mut peek = Vec::with_capacity(DETECT_PEEK_BYTES.min(bytes as usize));
+	file.take(DETECT_PEEK_BYTES as u64)
+		.read_to_end(&mut peek)
+		.await?;
+	Ok((peek, bytes))
+}
+
+/// Deliver the records of `path` through `delivery`. Unlike uploads, the file is
+/// left untouched: no checkpoint is written next to it and it is never
+/// removed.
+fn process_local_file(
+	path: &Path,
+	compressed: bool,
+	delivery: &BulkDelivery,
+	cancel: &AtomicBool,
+) -> BulkOutcome {
+	let file = match std::fs::File::open(path) {
+		Ok(file) => file,
+		Err(e) => {
+			tracing::error!(path = %path.display(), error = %e, "failed to open local dump");
+			return BulkOutcome::Failed;
+		}
+	};
+	let reader: Box<dyn Read> = if compressed {
+		Box::new(GzDecoder::new(file))
+	} else {
+		Box::new(file)
+	};
+	process_bulk_reader(reader, path, compressed, delivery, cancel, None)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::sync::Arc;
+
+	/// State with local ingest allowed from `dir`, and the persist queue.
+	fn state_for(
+		dir: &Path,
+	) -> (
+		AppState,
+		tokio::sync::mpsc::Receiver<crate::persist::PersistJob>,
+	) {
+		let (state, rx) = crate::ingest::test_utils::app_state_with_receiver(Arc::new(
+			crate::ingest::test_utils::DummyRepo,
+		));
+		let settings = crate::config::Settings {
+			local_ingest_dir: dir.to_string_lossy().into_owned(),
+			..Default::default()
+		};
+		(state.with_settings(Arc::new(settings)), rx)
+	}
+
+	/// A request for `path` from `peer`.
+	fn request(path: &str, peer: &str) -> Request<Body> {
+		let mut req = Request::builder()
+			.uri("/ingest/local")
+			.body(Body::from(serde_json::json!({ "path": path }).to_string()))
+			.unwrap();
+		req.extensions_mut()
+			.insert(ConnectInfo(peer.parse::<SocketAddr>().unwrap()));
+		req
+	}
+
+	#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+	async fn a_file_in_the_allowed_directory_is_ingested() {
+		let dir = tempfile::tempdir().unwrap();
+		std::fs::write(
+			dir.path().join("dump.ndjson"),
+			"{\"field_type\":\"domain\",\"value\":\"a.example\"}\n\
+			 {\"field_type\":\"domain\",\"value\":\"b.example\"}\n",
+		)
+		.unwrap();
+		let (state, mut rx) = state_for(dir.path());
+
+		let resp = local_ingest(State(state), request("dump.ndjson", "127.0.0.1:5000")).await;
+		assert_eq!(resp.status(), StatusCode::OK);
+		let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+			.await
+			.unwrap();
+		let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+		assert_eq!(json["outcome"], "completed");
+		assert_eq!(json["compressed"], false);
+
+		let mut keys = Vec::new();
+		while let Ok(job) = rx.try_recv() {
+			keys.push(job.key);
+		}
+		assert_eq!(keys, vec!["domain:a.example", "domain:b.example"]);
+		// The source file is left in place.
+		assert!(dir.path().join("dump.ndjson").exists());
+	}
+
+	#[tokio::test]
+	async fn paths_outside_the_allowed_directory_are_rejected() {
+		let root = tempfile::tempdir().unwrap();
+		let allowed = root.path().join("dumps");
+		std::fs::create_dir(&allowed).unwrap();
+		std::fs::write(root.path().join("secret.ndjson"), "{}\n").unwrap();
+		let (state, mut rx) = state_for(&allowed);
+
+		for path in [
+			"../etc/passwd",
+			"../secret.ndjson",
+			"/etc/passwd",
+			root.path().join("secret.ndjson").to_str().unwrap(),
+		] {
+			let resp = local_ingest(State(state.clone()), request(path, "127.0.0.1:5000")).await;
+			assert_eq!(resp.status(), StatusCode::FORBIDDEN, "{}", path);
+		}
+		#[cfg(unix)]
+		{
+			std::os::unix::fs::symlink(root.path().join("secret.ndjson"), allowed.join("link"))
+				.unwrap();
+			let resp = local_ingest(State(state.clone()), request("link", "127.0.0.1:5000")).await;
+			assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+		}
+		assert!(rx.try_recv().is_err());
+	}
+
+	#[tokio::test]
+	async fn disabled_or_remote_requests_are_refused() {
+		let dir = tempfile::tempdir().unwrap();
+		std::fs::write(dir.path().join("dump.ndjson"), "{}\n").unwrap();
+
+		let state = crate::ingest::test_utils::create_test_app_state();
+		let resp = local_ingest(State(state), request("dump.ndjson", "127.0.0.1:5000")).await;
+		assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+		let (state, _rx) = state_for(dir.path());
+		let resp = local_ingest(State(state), request("dump.ndjson", "192.0.2.10:5000")).await;
+		assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+	}
+}
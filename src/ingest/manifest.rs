@@ -0,0 +1,119 @@
+//! Per-dump manifests describing what a bulk upload contained.
+//!
+//! A [`ManifestBuilder`] is fed each chunk as the upload is streamed to
+//! disk, hashing the content and counting lines without a second pass.
+
+use serde::Serialize;
+
+use crate::ingest::content_hash::ContentHasher;
+
+/// Metadata about a stored bulk dump, persisted on its `Dump` node.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DumpManifest {
+	/// Identifier of the dump; the stored upload's file name.
+	pub dump_id: String,
+	/// File name supplied by the client via `?filename=`, if any.
+	pub source_filename: Option<String>,
+	/// Detected format, e.g. `ndjson` or `csv`.
+	pub format: String,
+	/// Compression of the stored bytes, e.g. `gzip`.
+	pub compression: Option<String>,
+	/// Size of the upload as received.
+	pub bytes: u64,
+	/// Non-empty lines; `None` for compressed or binary uploads.
+	pub row_count: Option<u64>,
+	/// Records the rows represent (rows less the header for CSV); `None`
+	/// when the format has no line-per-record layout.
+	pub record_count: Option<u64>,
+	/// Lowercase hex SHA-256 of the upload as received; see
+	/// [`crate::ingest::content_hash`].
+	pub content_sha256: String,
+}
+
+/// Accumulates the hash and line counts of an upload chunk by chunk.
+#[derive(Default)]
+pub struct ManifestBuilder {
+	hasher: ContentHasher,
+	rows: u64,
+	/// Whether the current, unterminated line has non-whitespace content.
+	line_has_content: bool,
+}
+
+impl ManifestBuilder {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Account for the next chunk of the upload.
+	pub fn update(&mut self, chunk: &[u8]) {
+		self.hasher.update(chunk);
+		for &b in chunk {
+			if b == b'\n' {
+				if self.line_has_content {
+					self.rows += 1;
+				}
+				self.line_has_content = false;
+			} else if !b.is_ascii_whitespace() {
+				self.line_has_content = true;
+			}
+		}
+	}
+
+	/// Build the manifest for a dump detected as `format`.
+	pub fn finish(
+		self,
+		dump_id: impl Into<String>,
+		source_filename: Option<String>,
+		format: &str,
+		compressed: bool,
+	) -> DumpManifest {
+		let rows = self.rows + u64::from(self.line_has_content);
+		let row_count = (!compressed && format != "binary").then_some(rows);
+		let record_count = row_count.and_then(|rows| match format {
+			"csv" => Some(rows.saturating_sub(1)),
+			"ndjson" | "text" => Some(rows),
+			_ => None,
+		});
+		DumpManifest {
+			dump_id: dump_id.into(),
+			source_filename,
+			format: format.to_string(),
+			compression: compressed.then(|| format.to_string()),
+			bytes: self.hasher.bytes(),
+			row_count,
+			record_count,
+			content_sha256: self.hasher.finish(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::ingest::content_hash::content_sha256;
+
+	fn build(chunks: &[&[u8]], format: &str, compressed: bool) -> DumpManifest {
+		let mut builder = ManifestBuilder::new();
+		for chunk in chunks {
+			builder.update(chunk);
+		}
+		builder.finish("dump-1", None, format, compressed)
+	}
+
+	#[test]
+	fn counts_rows_across_chunk_boundaries() {
+		let m = build(&[b"a,b\n1,", b"2\n\n  \n3,4"], "csv", false);
+		assert_eq!(m.row_count, Some(3));
+		assert_eq!(m.record_count, Some(2));
+		assert_eq!(m.bytes, 15);
+		assert_eq!(m.content_sha256, content_sha256(b"a,b\n1,2\n\n  \n3,4"));
+	}
+
+	#[test]
+	fn compressed_dumps_have_no_counts() {
+		let m = build(&[&[0x1f, 0x8b, 0x08]], "gzip", true);
+		assert_eq!(m.compression.as_deref(), Some("gzip"));
+		assert_eq!(m.row_count, None);
+		assert_eq!(m.record_count, None);
+	}
+}
@@ -1,15 +1,38 @@
+pub mod admission;
 pub mod bulk_normalizer;
+pub mod bulk_tasks;
+pub mod classifier;
+pub mod content_encoding;
+pub mod content_hash;
 pub mod format_detection;
 pub mod handler;
+pub mod ip_policy;
+pub mod keys;
+pub mod local;
+pub mod manifest;
 pub mod ndjson;
+pub mod ndjson_splitter;
+pub mod offline;
+pub mod parse_pool;
 pub mod parsers;
+pub mod preview;
+pub mod quota;
+pub mod resumable;
+pub mod source;
+pub mod uploads;
 
 #[cfg(test)]
 pub mod test_utils;
 
-pub use bulk_normalizer::NormalizedRecord;
+pub use bulk_normalizer::{NormalizedRecord, TrimPolicy, TrimRules};
+pub use classifier::{FieldClassifier, FieldClassifiers, FieldKind, detection_confidence};
+pub use content_encoding::decompression_layer;
+pub use content_hash::{CONTENT_SHA256_HEADER, ContentHasher};
 pub use format_detection::{detect_format, FormatType};
-pub use handler::{bulk_dump_upload, multipart_upload, ndjson_upload};
+pub use handler::{bulk_dump_upload, detect_upload, multipart_upload, ndjson_upload, records_upload};
+pub use ip_policy::{PrivateIpFilter, PrivateIpPolicy};
+pub use keys::KeyPrefixMap;
+pub use manifest::{DumpManifest, ManifestBuilder};
 pub use ndjson::{normalize_ndjson, normalize_ndjson_line};
 
 #[cfg(feature = "unit-tests")]
@@ -1,8 +1,8 @@
 use anyhow::Result;
-use regex::Regex;
 use serde_json::Value;
 
 use crate::ingest::NormalizedRecord;
+use crate::ingest::bulk_normalizer::{TrimRules, canonicalize};
 
 /// Normalize a NDJSON (newline-delimited JSON) payload where each line is an object
 /// describing a single field/value pair. The function is intentionally permissive
@@ -12,7 +12,7 @@ use crate::ingest::NormalizedRecord;
 pub fn normalize_ndjson(input: &str) -> Result<Vec<NormalizedRecord>> {
 	let mut out = Vec::new();
 
-	let punct_re = Regex::new(r"^[\W_]+|[\W_]+$").unwrap();
+	let trim = TrimRules::default();
 
 	for line in input.lines() {
 		let line = line.trim();
@@ -28,12 +28,8 @@ pub fn normalize_ndjson(input: &str) -> Result<Vec<NormalizedRecord>> {
 				if let Some((ft, val)) = line.split_once(',') {
 					let ftype = ft.trim().to_lowercase();
 					let raw = val.trim().to_string();
-					let canonical = canonicalize(&ftype, &raw, &punct_re);
-					out.push(NormalizedRecord {
-						field_type: ftype,
-						raw,
-						canonical,
-					});
+					let canonical = canonicalize(&ftype, &raw, &trim);
+					out.push(NormalizedRecord::new(ftype, raw, canonical));
 					continue;
 				}
 				// skip unparseable line
@@ -42,13 +38,8 @@ pub fn normalize_ndjson(input: &str) -> Result<Vec<NormalizedRecord>> {
 		};
 
 		// Extract field_type and value from the JSON value
-		if let Some((ftype, raw)) = extract_field_and_value(&v) {
-			let canonical = canonicalize(&ftype, &raw, &punct_re);
-			out.push(NormalizedRecord {
-				field_type: ftype,
-				raw,
-				canonical,
-			});
+		if let Some(rec) = record_from_value(&v, &trim) {
+			out.push(rec);
 		}
 	}
 
@@ -58,7 +49,7 @@ pub fn normalize_ndjson(input: &str) -> Result<Vec<NormalizedRecord>> {
 /// Normalize a single NDJSON line (object/array/string/csv fallback) into
 /// a single `NormalizedRecord`. Returns `Some(NormalizedRecord)` if the line
 /// could be parsed and normalized, otherwise `None`.
-pub fn normalize_ndjson_line(line: &str, punct_re: &Regex) -> Option<NormalizedRecord> {
+pub fn normalize_ndjson_line(line: &str, trim: &TrimRules) -> Option<NormalizedRecord> {
 	let line = line.trim();
 	if line.is_empty() {
 		return None;
@@ -72,35 +63,50 @@ pub fn normalize_ndjson_line(line: &str, punct_re: &Regex) -> Option<NormalizedR
 			if let Some((ft, val)) = line.split_once(',') {
 				let ftype = ft.trim().to_lowercase();
 				let raw = val.trim().to_string();
-				let canonical = canonicalize(&ftype, &raw, punct_re);
-				return Some(NormalizedRecord {
-					field_type: ftype,
-					raw,
-					canonical,
-				});
+				let canonical = canonicalize(&ftype, &raw, trim);
+				return Some(NormalizedRecord::new(ftype, raw, canonical));
 			}
 			return None;
 		}
 	};
 
-	if let Some((ftype, raw)) = extract_field_and_value(&v) {
-		let canonical = canonicalize(&ftype, &raw, punct_re);
-		return Some(NormalizedRecord {
-			field_type: ftype,
-			raw,
-			canonical,
-		});
-	}
+	record_from_value(&v, trim)
+}
+
+/// Normalize the field/value pair in `v`, keeping the name of the field it
+/// was read from.
+fn record_from_value(v: &Value, trim: &TrimRules) -> Option<NormalizedRecord> {
+	let (ftype, raw, source_field) = extract_field_and_value(v)?;
+	let canonical = canonicalize(&ftype, &raw, trim);
+	let rec = NormalizedRecord::new(ftype, raw, canonical);
+	Some(match source_field {
+		Some(name) => rec.with_source_field(name),
+		None => rec,
+	})
+}
 
-	None
+/// Field kind of a value read from input field `name`: a known kind the
+/// name ends with (`recovery_email` is an `email`), otherwise the name
+/// itself.
+fn kind_for_field_name(name: &str) -> String {
+	const KINDS: [&str; 6] = ["domain", "ip", "email", "hash", "phone", "username"];
+	let name = name.trim().to_lowercase();
+	let last = name.rsplit(['_', '-', '.']).next().unwrap_or("");
+	match KINDS.iter().find(|kind| **kind == last) {
+		Some(kind) => kind.to_string(),
+		None => name,
+	}
 }
 
-fn extract_field_and_value(v: &Value) -> Option<(String, String)> {
+/// Field type, raw value and, when the input names one, source field of
+/// `v`.
+fn extract_field_and_value(v: &Value) -> Option<(String, String, Option<String>)> {
 	match v {
 		Value::Object(map) => {
 			// Prefer explicit keys
 			let mut ftype: Option<String> = None;
 			let mut raw: Option<String> = None;
+			let mut source_field: Option<String> = None;
 
 			for (k, val) in map.iter() {
 				match k.as_str() {
@@ -114,24 +120,34 @@ fn extract_field_and_value(v: &Value) -> Option<(String, String)> {
 							raw = Some(s.trim().to_string());
 						}
 					}
+					"source_field" => {
+						if let Some(s) = val.as_str() {
+							source_field = Some(s.trim().to_string());
+						}
+					}
 					_ => {}
 				}
 			}
 
-			if ftype.is_none() {
-				// Heuristic: if object has one key that looks like a type, and the other is value
-				if map.len() == 1 {
-					if let Some((_k, val)) = map.iter().next() {
-						if let Some(_s) = val.as_str() {
-							// not enough info
-							return None;
+			if ftype.is_none() && raw.is_none() && map.len() == 1 {
+				// A lone `{"<field name>": "<value>"}` pair: the field name
+				// is the source field and suggests the type.
+				if let Some((k, val)) = map.iter().next() {
+					if let Some(s) = val.as_str() {
+						let name = k.trim();
+						if !name.is_empty() {
+							return Some((
+								kind_for_field_name(name),
+								s.trim().to_string(),
+								Some(name.to_string()),
+							));
 						}
 					}
 				}
 			}
 
 			if let (Some(ft), Some(rv)) = (ftype, raw) {
-				return Some((ft, rv));
+				return Some((ft, rv, source_field.filter(|f| !f.is_empty())));
 			}
 
 			None
@@ -139,14 +155,14 @@ fn extract_field_and_value(v: &Value) -> Option<(String, String)> {
 		Value::Array(arr) => {
 			if arr.len() >= 2 {
 				if let (Some(ft), Some(vv)) = (arr[0].as_str(), arr[1].as_str()) {
-					return Some((ft.trim().to_lowercase(), vv.trim().to_string()));
+					return Some((ft.trim().to_lowercase(), vv.trim().to_string(), None));
 				}
 			}
 			None
 		}
 		Value::String(s) => {
 			if let Some((ft, val)) = s.split_once(',') {
-				return Some((ft.trim().to_lowercase(), val.trim().to_string()));
+				return Some((ft.trim().to_lowercase(), val.trim().to_string(), None));
 			}
 			None
 		}
@@ -154,22 +170,6 @@ fn extract_field_and_value(v: &Value) -> Option<(String, String)> {
 	}
 }
 
-fn canonicalize(ftype: &str, raw: &str, punct_re: &Regex) -> String {
-	match ftype {
-		"domain" => {
-			let mut v = raw.trim().to_lowercase();
-			if v.ends_with('.') {
-				v.pop();
-			}
-			v
-		}
-		"ip" => raw.trim().to_string(),
-		"hash" => punct_re.replace_all(&raw.to_lowercase(), "").to_string(),
-		"email" => raw.trim().to_lowercase().to_string(),
-		_ => raw.trim().to_lowercase().to_string(),
-	}
-}
-
 #[cfg(feature = "ingest-tests")]
 mod tests {
 	use super::*;
@@ -228,6 +228,57 @@ mod tests_default {
 		assert_eq!(got[3].canonical, "user@example.com");
 	}
 
+	#[test]
+	fn trim_policy_depends_on_field_kind() {
+		let ndjson = r#"{"field_type":"phone","value":" +15551234567 "}
+{"field_type":"hash","value":"-abcdef-"}
+{"field_type":"username","value":"  \"Alice_\"!! "}
+"#;
+		let got = normalize_ndjson(ndjson).expect("normalize");
+		assert_eq!(got[0].canonical, "+15551234567");
+		assert_eq!(got[1].canonical, "-abcdef-");
+		assert_eq!(got[2].canonical, "alice");
+	}
+
+	#[test]
+	fn trim_rules_can_be_overridden_per_kind() {
+		use crate::ingest::{TrimPolicy, TrimRules};
+
+		let trim = TrimRules::default().with_policy("phone", TrimPolicy::Punctuation);
+		let rec =
+			normalize_ndjson_line(r#"{"type":"phone","value":"+15551234567"}"#, &trim).unwrap();
+		assert_eq!(rec.canonical, "15551234567");
+
+		let trim = TrimRules::new(TrimPolicy::Whitespace);
+		let rec = normalize_ndjson_line("note, (draft) ", &trim).unwrap();
+		assert_eq!(rec.canonical, "(draft)");
+	}
+
+	#[test]
+	fn lone_field_value_pairs_keep_their_source_field() {
+		let trim = TrimRules::default();
+		let rec =
+			normalize_ndjson_line(r#"{"recovery_email":"Backup@Example.COM"}"#, &trim).unwrap();
+		assert_eq!(rec.source_field.as_deref(), Some("recovery_email"));
+		assert_eq!(rec.field_type, "email");
+		assert_eq!(rec.canonical, "backup@example.com");
+
+		let rec = normalize_ndjson_line(r#"{"nickname":"Alice"}"#, &trim).unwrap();
+		assert_eq!(rec.source_field.as_deref(), Some("nickname"));
+		assert_eq!(rec.field_type, "nickname");
+
+		let rec = normalize_ndjson_line(
+			r#"{"field_type":"email","value":"a@example.com","source_field":"email2"}"#,
+			&trim,
+		)
+		.unwrap();
+		assert_eq!(rec.source_field.as_deref(), Some("email2"));
+
+		let rec =
+			normalize_ndjson_line(r#"{"type":"domain","value":"example.com"}"#, &trim).unwrap();
+		assert_eq!(rec.source_field, None);
+	}
+
 	#[test]
 	fn supports_array_and_csv_line_default() {
 		let ndjson = "[\"domain\", \"Example.COM\"]\nemail,user@EXAMPLE.COM\n";
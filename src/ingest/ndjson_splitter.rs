@@ -0,0 +1,324 @@
+//! JSON-aware splitting of a streamed NDJSON body into records.
+//!
+//! Splitting on every `\n` byte cuts a record in two when one of its string
+//! values holds a literal newline, or when the record is spread over
+//! several lines. [`RecordSplitter`] tracks string, escape and nesting state
+//! across chunks and only reports a newline as a record boundary outside
+//! strings at nesting depth 0. Records that don't start like JSON (`{`, `[`
+//! or `"`), such as the `type,value` fallback, still end at the first
+//! newline.
+//!
+//! A record left unterminated, e.g. a truncated `{"a":"b`, would run on
+//! over the records after it. So a record spanning raw newlines that is
+//! not valid JSON once it closes, or that spans more than
+//! [`MAX_RECORD_LINES`] lines, ends at its first newline instead, and the
+//! lines after that are scanned again as records of their own.
+
+use std::borrow::Cow;
+
+/// Lines a record may span before it is taken to be a truncated one that
+/// ran on into the records after it.
+pub const MAX_RECORD_LINES: usize = 1024;
+
+/// Scan state of the record being read.
+#[derive(Debug, Default, Clone, Copy)]
+struct Scan {
+	/// Whether a non-blank byte of the record was seen.
+	started: bool,
+	/// Whether that byte opened a JSON value, so strings and nesting count.
+	json: bool,
+	depth: usize,
+	in_string: bool,
+	/// Whether the previous byte was a backslash escaping this one.
+	escaped: bool,
+}
+
+impl Scan {
+	/// Advance over `b`; true when `b` is the newline ending the record.
+	fn step(&mut self, b: u8) -> bool {
+		if !self.started {
+			if b == b'\n' {
+				return true;
+			}
+			if b.is_ascii_whitespace() {
+				return false;
+			}
+			self.started = true;
+			self.json = matches!(b, b'{' | b'[' | b'"');
+		}
+		if !self.json {
+			return b == b'\n';
+		}
+		if self.in_string {
+			if self.escaped {
+				self.escaped = false;
+			} else if b == b'\\' {
+				self.escaped = true;
+			} else if b == b'"' {
+				self.in_string = false;
+			}
+			return false;
+		}
+		match b {
+			b'"' => self.in_string = true,
+			b'{' | b'[' => self.depth += 1,
+			b'}' | b']' => self.depth = self.depth.saturating_sub(1),
+			b'\n' => return self.depth == 0,
+			_ => {}
+		}
+		false
+	}
+}
+
+/// Finds record boundaries in a growing NDJSON buffer.
+#[derive(Debug, Default)]
+pub struct RecordSplitter {
+	/// Bytes at the front of the buffer already scanned for the current
+	/// record.
+	scanned: usize,
+	scan: Scan,
+	/// Position in the buffer of the first raw newline inside the current
+	/// record.
+	first_newline: Option<usize>,
+	/// Raw newlines inside the current record so far.
+	newlines: usize,
+	/// Whether the front of the current record was discarded, so it can't
+	/// be checked as JSON.
+	partial: bool,
+}
+
+impl RecordSplitter {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Position in `buf` of the newline ending its first record, or `None`
+	/// while that record is incomplete. Bytes scanned by earlier calls are
+	/// not scanned again, so `buf` must only grow between calls, except
+	/// that after `Some(pos)` the caller drains `buf[..=pos]`.
+	pub fn next_boundary(&mut self, buf: &[u8]) -> Option<usize> {
+		for (pos, &b) in buf.iter().enumerate().skip(self.scanned) {
+			if self.scan.step(b) {
+				let end = match self.first_newline {
+					Some(first) if !self.partial && !is_json(&buf[..pos]) => first,
+					_ => pos,
+				};
+				*self = Self::default();
+				return Some(end);
+			}
+			if b == b'\n' {
+				let first = *self.first_newline.get_or_insert(pos);
+				self.newlines += 1;
+				if self.newlines >= MAX_RECORD_LINES {
+					*self = Self::default();
+					return Some(first);
+				}
+			}
+		}
+		self.scanned = buf.len();
+		None
+	}
+
+	/// At the end of the body, the newline ending the first record in the
+	/// rest of `buf`, when that record spans raw newlines but is not valid
+	/// JSON; `None` when `buf` is the last record. Call it once
+	/// [`next_boundary`](Self::next_boundary) finds no more boundaries.
+	pub fn finish(&mut self, buf: &[u8]) -> Option<usize> {
+		let first = self
+			.first_newline
+			.filter(|_| !self.partial && !is_json(buf))?;
+		*self = Self::default();
+		Some(first)
+	}
+
+	/// The caller dropped the buffered part of the current record, e.g. an
+	/// oversized one; the rest of it is still scanned in the same state.
+	pub fn discard_buffered(&mut self) {
+		self.scanned = 0;
+		self.first_newline = None;
+		self.partial = true;
+	}
+}
+
+/// Whether `record` parses as JSON once its embedded newlines are escaped.
+fn is_json(record: &[u8]) -> bool {
+	serde_json::from_slice::<serde::de::IgnoredAny>(&escape_embedded_newlines(record)).is_ok()
+}
+
+/// `record` with the newlines inside its JSON strings written as `\n` and
+/// `\r` escapes, which `serde_json` requires. Borrowed when there is none.
+pub fn escape_embedded_newlines(record: &[u8]) -> Cow<'_, [u8]> {
+	if !record.iter().any(|&b| b == b'\n' || b == b'\r') {
+		return Cow::Borrowed(record);
+	}
+	let mut scan = Scan::default();
+	let mut out = Vec::with_capacity(record.len() + 8);
+	for &b in record {
+		let in_string = scan.json && scan.in_string;
+		let escaped = scan.escaped;
+		scan.step(b);
+		match b {
+			b'\n' | b'\r' if in_string => {
+				// A backslash before the raw newline already starts the escape.
+				if !escaped {
+					out.push(b'\\');
+				}
+				out.push(if b == b'\n' { b'n' } else { b'r' });
+			}
+			_ => out.push(b),
+		}
+	}
+	Cow::Owned(out)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use proptest::prelude::*;
+	use serde_json::{Value, json};
+
+	/// Feed `chunks` through a splitter as the handler does and parse every
+	/// record.
+	fn split(chunks: &[&[u8]]) -> Vec<Value> {
+		let mut splitter = RecordSplitter::new();
+		let mut buf = Vec::new();
+		let mut out = Vec::new();
+		let mut parse = |record: &[u8]| {
+			let record = record.strip_suffix(b"\r").unwrap_or(record);
+			if !record.iter().all(u8::is_ascii_whitespace) {
+				out.push(serde_json::from_slice(&escape_embedded_newlines(record)).unwrap());
+			}
+		};
+		for chunk in chunks {
+			buf.extend_from_slice(chunk);
+			while let Some(pos) = splitter.next_boundary(&buf) {
+				let record: Vec<u8> = buf.drain(..=pos).collect();
+				parse(&record[..pos]);
+			}
+		}
+		parse(&buf);
+		out
+	}
+
+	/// The lines of `body` that parse as JSON on their own.
+	fn valid_lines(body: &[u8]) -> Vec<Value> {
+		body.split(|&b| b == b'\n')
+			.filter_map(|line| serde_json::from_slice(line).ok())
+			.collect()
+	}
+
+	#[test]
+	fn newlines_inside_strings_do_not_split_records() {
+		let body = b"{\"type\":\"note\",\"value\":\"line one\nline two\"}\n{\"value\":\"a\\\nb\"}\r\n[\"x\",\n \"y\"]\n";
+		assert_eq!(
+			split(&[body]),
+			vec![
+				json!({"type": "note", "value": "line one\nline two"}),
+				json!({"value": "a\nb"}),
+				json!(["x", "y"]),
+			]
+		);
+	}
+
+	#[test]
+	fn non_json_records_end_at_the_first_newline() {
+		let mut splitter = RecordSplitter::new();
+		let buf = b"ip,\"192.0.2.1\nnext";
+		assert_eq!(splitter.next_boundary(buf), Some(13));
+		assert_eq!(
+			escape_embedded_newlines(b"{\"a\":1}"),
+			Cow::Borrowed(b"{\"a\":1}".as_slice())
+		);
+	}
+
+	#[test]
+	fn records_after_a_truncated_one_are_split_again() {
+		let mut body = b"{\"value\":\"cut\n".to_vec();
+		for i in 0..3 {
+			body.extend_from_slice(format!("{{\"value\":\"v{}\"}}\n", i).as_bytes());
+		}
+		// The last record is cut too, so it only ends with the body.
+		body.extend_from_slice(b"{\"value\":\"end\n{\"value\":\"v3\"}\n");
+		let expected = valid_lines(&body);
+		assert_eq!(expected.len(), 4);
+
+		// Split as the handler does; the truncated records don't parse.
+		let mut splitter = RecordSplitter::new();
+		let mut buf = body.clone();
+		let mut records = Vec::new();
+		while let Some(pos) = splitter
+			.next_boundary(&buf)
+			.or_else(|| splitter.finish(&buf))
+		{
+			let record: Vec<u8> = buf.drain(..=pos).collect();
+			records.extend(serde_json::from_slice::<Value>(&record[..pos]).ok());
+		}
+		records.extend(serde_json::from_slice::<Value>(&buf).ok());
+		assert_eq!(records, expected);
+	}
+
+	#[test]
+	fn a_record_left_open_ends_after_max_record_lines() {
+		let mut body = b"{\"values\":[1,2\n".to_vec();
+		for i in 0..MAX_RECORD_LINES + 8 {
+			body.extend_from_slice(format!("{{\"value\":{}}}\n", i).as_bytes());
+		}
+		let mut splitter = RecordSplitter::new();
+		assert_eq!(splitter.next_boundary(&body), Some(14));
+		assert_eq!(&body[..14], b"{\"values\":[1,2");
+	}
+
+	#[test]
+	fn discarded_bytes_keep_the_string_state() {
+		let mut splitter = RecordSplitter::new();
+		let mut buf = b"{\"v\":\"one\n".to_vec();
+		assert_eq!(splitter.next_boundary(&buf), None);
+		buf.clear();
+		splitter.discard_buffered();
+		buf.extend_from_slice(b"two\"}\n{}\n");
+		assert_eq!(splitter.next_boundary(&buf), Some(5));
+	}
+
+	/// A string value with newlines and JSON punctuation in it. Backslashes
+	/// are left out so raw newlines can be restored by plain replacement.
+	fn value() -> impl Strategy<Value = String> {
+		r#"[a-z0-9 \n\r"{}\[\],:]{0,24}"#
+	}
+
+	proptest! {
+		#[test]
+		fn records_survive_any_chunking(
+			values in prop::collection::vec(value(), 1..8),
+			cuts in prop::collection::vec(any::<prop::sample::Index>(), 0..6),
+			crlf in any::<bool>(),
+		) {
+			let records: Vec<Value> = values
+				.iter()
+				.map(|v| json!({"type": "note", "value": v, "tags": [v]}))
+				.collect();
+			// Serialize, then unescape newlines so they appear raw inside
+			// the strings, as producers that skip escaping write them.
+			let terminator: &[u8] = if crlf { b"\r\n" } else { b"\n" };
+			let mut body = Vec::new();
+			for record in &records {
+				let line = serde_json::to_string(record)
+					.unwrap()
+					.replace("\\n", "\n")
+					.replace("\\r", "\r");
+				body.extend_from_slice(line.as_bytes());
+				body.extend_from_slice(terminator);
+			}
+			let mut cuts: Vec<usize> = cuts.iter().map(|i| i.index(body.len() + 1)).collect();
+			cuts.sort_unstable();
+			let mut chunks = Vec::new();
+			let mut start = 0;
+			for cut in cuts {
+				chunks.push(&body[start..cut]);
+				start = cut;
+			}
+			chunks.push(&body[start..]);
+
+			prop_assert_eq!(split(&chunks), records);
+		}
+	}
+}
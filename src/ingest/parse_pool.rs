@@ -0,0 +1,146 @@
+//! Bounded pool for CPU-bound upload parsing.
+//!
+//! Decompressing and parsing CSV, XLSX and JSON uploads runs on Tokio's
+//! blocking threads so request handlers never parse on the async runtime.
+//! At most `workers` parses run at once; an upload arriving while every
+//! worker is busy is turned away with `503` rather than queued, so load is
+//! pushed back to clients instead of piling up threads and buffers.
+
+use std::sync::Arc;
+
+use axum::{
+	http::{StatusCode, header},
+	response::{IntoResponse, Response},
+};
+use thiserror::Error;
+use tokio::sync::Semaphore;
+
+/// Why a job did not run to completion on the pool.
+#[derive(Debug, Error)]
+pub enum ParsePoolError {
+	#[error("all {0} parse workers are busy")]
+	Saturated(usize),
+	#[error("parse worker failed: {0}")]
+	Failed(String),
+}
+
+impl IntoResponse for ParsePoolError {
+	fn into_response(self) -> Response {
+		match self {
+			ParsePoolError::Saturated(_) => (
+				StatusCode::SERVICE_UNAVAILABLE,
+				[(header::RETRY_AFTER, "1")],
+				self.to_string(),
+			)
+				.into_response(),
+			ParsePoolError::Failed(_) => {
+				(StatusCode::INTERNAL_SERVER_ERROR, self.to_string()).into_response()
+			}
+		}
+	}
+}
+
+/// Runs parse jobs on at most `workers` blocking threads at once.
+#[derive(Debug)]
+pub struct ParsePool {
+	workers: usize,
+	permits: Arc<Semaphore>,
+}
+
+impl ParsePool {
+	pub fn new(workers: usize) -> Self {
+		Self {
+			workers,
+			permits: Arc::new(Semaphore::new(workers)),
+		}
+	}
+
+	/// One worker per available CPU.
+	pub fn default_workers() -> usize {
+		std::thread::available_parallelism()
+			.map(|n| n.get())
+			.unwrap_or(4)
+	}
+
+	pub fn workers(&self) -> usize {
+		self.workers
+	}
+
+	/// Run `job` on a blocking thread, or fail with
+	/// [`ParsePoolError::Saturated`] when every worker is busy.
+	pub async fn run<T, F>(&self, job: F) -> Result<T, ParsePoolError>
+	where
+		F: FnOnce() -> T + Send + 'static,
+		T: Send + 'static,
+	{
+		let permit = self
+			.permits
+			.clone()
+			.try_acquire_owned()
+			.map_err(|_| ParsePoolError::Saturated(self.workers))?;
+		tokio::task::spawn_blocking(move || {
+			let _permit = permit;
+			job()
+		})
+		.await
+		.map_err(|e| ParsePoolError::Failed(e.to_string()))
+	}
+}
+
+impl Default for ParsePool {
+	fn default() -> Self {
+		Self::new(Self::default_workers())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::sync::atomic::{AtomicUsize, Ordering};
+	use std::time::Duration;
+
+	#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+	async fn concurrent_jobs_share_a_bounded_pool() {
+		let pool = Arc::new(ParsePool::new(2));
+		let running = Arc::new(AtomicUsize::new(0));
+		let peak = Arc::new(AtomicUsize::new(0));
+		let (release, gate) = std::sync::mpsc::channel::<()>();
+		let gate = Arc::new(std::sync::Mutex::new(gate));
+
+		let mut jobs = Vec::new();
+		for _ in 0..2 {
+			let (pool, running, peak, gate) =
+				(pool.clone(), running.clone(), peak.clone(), gate.clone());
+			jobs.push(tokio::spawn(async move {
+				pool.run(move || {
+					let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+					peak.fetch_max(now, Ordering::SeqCst);
+					gate.lock().unwrap().recv().unwrap();
+					running.fetch_sub(1, Ordering::SeqCst);
+				})
+				.await
+			}));
+		}
+		while running.load(Ordering::SeqCst) < 2 {
+			tokio::time::sleep(Duration::from_millis(5)).await;
+		}
+
+		// Both workers are busy: a third upload is turned away.
+		let err = pool.run(|| ()).await.unwrap_err();
+		assert!(matches!(err, ParsePoolError::Saturated(2)));
+		assert_eq!(
+			err.into_response().status(),
+			StatusCode::SERVICE_UNAVAILABLE
+		);
+
+		release.send(()).unwrap();
+		release.send(()).unwrap();
+		for job in jobs {
+			job.await.unwrap().unwrap();
+		}
+		assert_eq!(peak.load(Ordering::SeqCst), 2);
+
+		// Freed workers accept new jobs.
+		assert_eq!(pool.run(|| 7).await.unwrap(), 7);
+	}
+}
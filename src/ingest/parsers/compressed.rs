@@ -1,11 +1,111 @@
 use anyhow::{anyhow, Result};
 use flate2::read::GzDecoder;
-use std::io::Read;
+use std::io::{self, Read};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use zip::ZipArchive;
 
-/// Decompress gzip data and return the uncompressed bytes.
-pub fn decompress_gzip<R: Read>(reader: R) -> Result<Vec<u8>> {
-	let mut decoder = GzDecoder::new(reader);
+/// Output below this size is never rejected for its compression ratio, so
+/// small, highly repetitive files still decompress.
+pub const RATIO_GRACE_BYTES: u64 = 1024 * 1024;
+
+/// Bounds on decompressed output that stop a small archive from expanding
+/// until memory runs out. See `Settings.archive_max_bytes` and
+/// `Settings.archive_max_ratio`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecompressionLimits {
+	/// Largest decompressed output; 0 is unlimited.
+	pub max_bytes: u64,
+	/// Largest ratio of decompressed to compressed bytes, enforced past
+	/// [`RATIO_GRACE_BYTES`]; 0 is unlimited.
+	pub max_ratio: u64,
+}
+
+impl Default for DecompressionLimits {
+	fn default() -> Self {
+		Self {
+			max_bytes: 1024 * 1024 * 1024,
+			max_ratio: 200,
+		}
+	}
+}
+
+impl DecompressionLimits {
+	/// No bounds at all.
+	pub const UNLIMITED: Self = Self {
+		max_bytes: 0,
+		max_ratio: 0,
+	};
+
+	/// Why `produced` bytes decompressed from `consumed` compressed bytes
+	/// break these limits, or `None` while they are within them.
+	fn exceeded(&self, produced: u64, consumed: u64) -> Option<String> {
+		if self.max_bytes > 0 && produced > self.max_bytes {
+			return Some(format!(
+				"decompressed size exceeds {} bytes",
+				self.max_bytes
+			));
+		}
+		if self.max_ratio > 0
+			&& produced > RATIO_GRACE_BYTES
+			&& produced > consumed.saturating_mul(self.max_ratio)
+		{
+			return Some(format!("compression ratio exceeds {}:1", self.max_ratio));
+		}
+		None
+	}
+}
+
+/// Reader counting the compressed bytes a decoder pulls from it.
+struct CountingReader<R> {
+	inner: R,
+	count: Arc<AtomicU64>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let n = self.inner.read(buf)?;
+		self.count.fetch_add(n as u64, Ordering::Relaxed);
+		Ok(n)
+	}
+}
+
+/// Decoder output that fails with `InvalidData` as soon as it breaks
+/// `limits`, before the rest of the stream is inflated.
+struct LimitedReader<R> {
+	inner: R,
+	limits: DecompressionLimits,
+	consumed: Arc<AtomicU64>,
+	produced: u64,
+}
+
+impl<R: Read> Read for LimitedReader<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let n = self.inner.read(buf)?;
+		self.produced += n as u64;
+		match self
+			.limits
+			.exceeded(self.produced, self.consumed.load(Ordering::Relaxed))
+		{
+			Some(reason) => Err(io::Error::new(io::ErrorKind::InvalidData, reason)),
+			None => Ok(n),
+		}
+	}
+}
+
+/// Decompress gzip data and return the uncompressed bytes, aborting once
+/// the output breaks `limits`.
+pub fn decompress_gzip<R: Read>(reader: R, limits: &DecompressionLimits) -> Result<Vec<u8>> {
+	let consumed = Arc::new(AtomicU64::new(0));
+	let mut decoder = LimitedReader {
+		inner: GzDecoder::new(CountingReader {
+			inner: reader,
+			count: consumed.clone(),
+		}),
+		limits: *limits,
+		consumed,
+		produced: 0,
+	};
 	let mut decompressed = Vec::new();
 	decoder
 		.read_to_end(&mut decompressed)
@@ -15,7 +115,12 @@ pub fn decompress_gzip<R: Read>(reader: R) -> Result<Vec<u8>> {
 
 /// Extract the first file from a ZIP archive and return its contents.
 /// If the archive contains multiple files, only the first one is extracted.
-pub fn extract_first_zip_entry<R: Read + std::io::Seek>(reader: R) -> Result<Vec<u8>> {
+/// The entry's ratio is measured against its compressed size, and
+/// extraction aborts once the output breaks `limits`.
+pub fn extract_first_zip_entry<R: Read + std::io::Seek>(
+	reader: R,
+	limits: &DecompressionLimits,
+) -> Result<Vec<u8>> {
 	let mut archive =
 		ZipArchive::new(reader).map_err(|e| anyhow!("failed to open zip archive: {}", e))?;
 
@@ -24,10 +129,17 @@ pub fn extract_first_zip_entry<R: Read + std::io::Seek>(reader: R) -> Result<Vec
 	}
 
 	// Get the first file
-	let mut file = archive
+	let file = archive
 		.by_index(0)
 		.map_err(|e| anyhow!("failed to read zip entry: {}", e))?;
 
+	let consumed = Arc::new(AtomicU64::new(file.compressed_size()));
+	let mut file = LimitedReader {
+		inner: file,
+		limits: *limits,
+		consumed,
+		produced: 0,
+	};
 	let mut contents = Vec::new();
 	file.read_to_end(&mut contents)
 		.map_err(|e| anyhow!("failed to read zip file contents: {}", e))?;
@@ -53,10 +165,91 @@ mod tests {
 		let compressed = encoder.finish().unwrap();
 
 		// Decompress
-		let decompressed = decompress_gzip(Cursor::new(compressed)).expect("decompress");
+		let decompressed =
+			decompress_gzip(Cursor::new(compressed), &DecompressionLimits::default())
+				.expect("decompress");
 		assert_eq!(&decompressed, test_data);
 	}
 
+	fn gzip(data: &[u8]) -> Vec<u8> {
+		let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+		encoder.write_all(data).unwrap();
+		encoder.finish().unwrap()
+	}
+
+	fn zip_of(data: &[u8]) -> Vec<u8> {
+		let mut zip_buf = Vec::new();
+		{
+			let mut zip = ZipWriter::new(Cursor::new(&mut zip_buf));
+			let options: FileOptions<()> = FileOptions::default();
+			zip.start_file("bomb.txt", options).unwrap();
+			zip.write_all(data).unwrap();
+			zip.finish().unwrap();
+		}
+		zip_buf
+	}
+
+	#[test]
+	fn decompression_aborts_at_size_cap() {
+		// 64 MiB of zeros compresses to roughly 64 KiB.
+		let bomb = vec![0u8; 64 * 1024 * 1024];
+		let limits = DecompressionLimits {
+			max_bytes: 4 * 1024 * 1024,
+			max_ratio: 0,
+		};
+		let err = decompress_gzip(Cursor::new(gzip(&bomb)), &limits).unwrap_err();
+		assert!(
+			err.to_string()
+				.contains("decompressed size exceeds 4194304 bytes"),
+			"{}",
+			err
+		);
+		let err = extract_first_zip_entry(Cursor::new(zip_of(&bomb)), &limits).unwrap_err();
+		assert!(
+			err.to_string()
+				.contains("decompressed size exceeds 4194304 bytes"),
+			"{}",
+			err
+		);
+
+		assert_eq!(
+			decompress_gzip(Cursor::new(gzip(&bomb)), &DecompressionLimits::UNLIMITED)
+				.unwrap()
+				.len(),
+			bomb.len()
+		);
+	}
+
+	#[test]
+	fn decompression_aborts_at_ratio_cap() {
+		let bomb = vec![0u8; 16 * 1024 * 1024];
+		let limits = DecompressionLimits {
+			max_bytes: 0,
+			max_ratio: 100,
+		};
+		let err = decompress_gzip(Cursor::new(gzip(&bomb)), &limits).unwrap_err();
+		assert!(
+			err.to_string().contains("compression ratio exceeds 100:1"),
+			"{}",
+			err
+		);
+		let err = extract_first_zip_entry(Cursor::new(zip_of(&bomb)), &limits).unwrap_err();
+		assert!(
+			err.to_string().contains("compression ratio exceeds 100:1"),
+			"{}",
+			err
+		);
+
+		// Small outputs are exempt however well they compress.
+		let small = vec![0u8; RATIO_GRACE_BYTES as usize];
+		assert_eq!(
+			decompress_gzip(Cursor::new(gzip(&small)), &limits)
+				.unwrap()
+				.len(),
+			small.len()
+		);
+	}
+
 	#[test]
 	fn extract_zip_basic() {
 		let test_data = b"Hello, ZIP!";
@@ -72,7 +265,9 @@ mod tests {
 		}
 
 		// Extract
-		let extracted = extract_first_zip_entry(Cursor::new(zip_buf)).expect("extract");
+		let extracted =
+			extract_first_zip_entry(Cursor::new(zip_buf), &DecompressionLimits::default())
+				.expect("extract");
 		assert_eq!(&extracted, test_data);
 	}
 }
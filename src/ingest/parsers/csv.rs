@@ -1,63 +1,68 @@
 use anyhow::Result;
-use regex::Regex;
 use std::io::Read;
 
 use crate::ingest::NormalizedRecord;
+use crate::ingest::bulk_normalizer::{TrimRules, canonicalize};
 
-/// Stream-parse CSV data from a reader and emit normalized records incrementally.
-/// Supports CSV and TSV (tab-separated) formats by auto-detecting the delimiter.
-pub fn parse_csv_stream<R: Read>(
-	reader: R,
-	delimiter: Option<u8>,
-) -> Result<Vec<NormalizedRecord>> {
-	let delim = delimiter.unwrap_or(b',');
-
-	let mut rdr = csv::ReaderBuilder::new()
-		.has_headers(true)
-		.delimiter(delim)
-		.trim(csv::Trim::All)
-		.from_reader(reader);
-
-	let mut out = Vec::new();
-	let punct_re = Regex::new(r"^[\W_]+|[\W_]+$").unwrap();
+/// Normalized records read incrementally from CSV input.
+///
+/// Parsing follows RFC 4180: a quoted field may hold delimiters, newlines
+/// and `""`-escaped quotes, and the reader's state machine carries an open
+/// quote across reads, so a record is assembled correctly however the input
+/// is chunked. Rows with fewer than two fields are skipped.
+pub struct CsvRecords<R: Read> {
+	rows: csv::StringRecordsIntoIter<R>,
+	trim: TrimRules,
+}
 
-	for result in rdr.records() {
-		let record = result?;
-		if record.len() < 2 {
-			continue;
+impl<R: Read> CsvRecords<R> {
+	/// Read `type,value` rows after a header row from `reader`. `delimiter`
+	/// defaults to `,`; use [`crate::ingest::format_detection::sniff_delimiter`]
+	/// for tab-, pipe- or semicolon-separated input.
+	pub fn new(reader: R, delimiter: Option<u8>) -> Self {
+		let rows = csv::ReaderBuilder::new()
+			.has_headers(true)
+			.delimiter(delimiter.unwrap_or(b','))
+			.trim(csv::Trim::All)
+			.from_reader(reader)
+			.into_records();
+		Self {
+			rows,
+			trim: TrimRules::default(),
 		}
-
-		let ftype = record.get(0).unwrap_or("").to_lowercase();
-		let raw = record.get(1).unwrap_or("").to_string();
-
-		let canonical = canonicalize(&ftype, &raw, &punct_re);
-
-		out.push(NormalizedRecord {
-			field_type: ftype,
-			raw,
-			canonical,
-		});
 	}
-
-	Ok(out)
 }
 
-fn canonicalize(ftype: &str, raw: &str, punct_re: &Regex) -> String {
-	match ftype {
-		"domain" => {
-			let mut v = raw.trim().to_lowercase();
-			if v.ends_with('.') {
-				v.pop();
+impl<R: Read> Iterator for CsvRecords<R> {
+	type Item = Result<NormalizedRecord>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			let record = match self.rows.next()? {
+				Ok(record) => record,
+				Err(e) => return Some(Err(e.into())),
+			};
+			if record.len() < 2 {
+				continue;
 			}
-			v
+
+			let ftype = record.get(0).unwrap_or("").to_lowercase();
+			let raw = record.get(1).unwrap_or("").to_string();
+			let canonical = canonicalize(&ftype, &raw, &self.trim);
+			return Some(Ok(NormalizedRecord::new(ftype, raw, canonical)));
 		}
-		"ip" => raw.trim().to_string(),
-		"hash" => punct_re.replace_all(&raw.to_lowercase(), "").to_string(),
-		"email" => raw.trim().to_lowercase().to_string(),
-		_ => raw.trim().to_lowercase().to_string(),
 	}
 }
 
+/// Stream-parse CSV data from a reader and collect the normalized records.
+/// See [`CsvRecords`] for quoting rules and the `delimiter` default.
+pub fn parse_csv_stream<R: Read>(
+	reader: R,
+	delimiter: Option<u8>,
+) -> Result<Vec<NormalizedRecord>> {
+	CsvRecords::new(reader, delimiter).collect()
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -79,4 +84,56 @@ mod tests {
 		assert_eq!(records[0].canonical, "example.com");
 		assert_eq!(records[1].canonical, "192.0.2.1");
 	}
+
+	/// A reader handing out at most `step` bytes per read, like a body
+	/// arriving in small chunks.
+	struct Chunked<'a> {
+		data: &'a [u8],
+		step: usize,
+	}
+
+	impl Read for Chunked<'_> {
+		fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+			let n = self.step.min(buf.len()).min(self.data.len());
+			buf[..n].copy_from_slice(&self.data[..n]);
+			self.data = &self.data[n..];
+			Ok(n)
+		}
+	}
+
+	#[test]
+	fn quoted_fields_span_lines_and_chunks() {
+		let csv = "field_type,value\nnote,\"line1\nline2, said \"\"hi\"\"\"\r\nip,192.0.2.1\n";
+		// Every chunk size cuts somewhere inside the quoted field, including
+		// between the two quotes of an escape.
+		for step in 1..=csv.len() {
+			let reader = Chunked {
+				data: csv.as_bytes(),
+				step,
+			};
+			let records = parse_csv_stream(reader, None).expect("parse csv");
+			assert_eq!(records.len(), 2, "chunk size {}", step);
+			assert_eq!(records[0].field_type, "note");
+			assert_eq!(records[0].raw, "line1\nline2, said \"hi\"");
+			assert_eq!(records[1].canonical, "192.0.2.1");
+		}
+	}
+
+	/// A reader that fails every read.
+	struct Broken;
+
+	impl Read for Broken {
+		fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+			Err(std::io::Error::other("connection reset"))
+		}
+	}
+
+	#[test]
+	fn records_are_yielded_before_the_input_ends() {
+		let csv = "field_type,value\ndomain,\"a.example\"\n";
+		let mut records = CsvRecords::new(csv.as_bytes().chain(Broken), None);
+		let first = records.next().unwrap().unwrap();
+		assert_eq!(first.canonical, "a.example");
+		assert!(records.next().unwrap().is_err());
+	}
 }
@@ -1,15 +1,15 @@
 use anyhow::Result;
-use regex::Regex;
 use serde_json::Value;
 use std::io::{BufRead, BufReader, Read};
 
 use crate::ingest::NormalizedRecord;
+use crate::ingest::bulk_normalizer::{TrimRules, canonicalize};
 
 /// Stream-parse NDJSON (newline-delimited JSON) from a reader and emit normalized records.
 pub fn parse_ndjson_stream<R: Read>(reader: R) -> Result<Vec<NormalizedRecord>> {
 	let buf_reader = BufReader::new(reader);
 	let mut out = Vec::new();
-	let punct_re = Regex::new(r"^[\W_]+|[\W_]+$").unwrap();
+	let trim = TrimRules::default();
 
 	for line_result in buf_reader.lines() {
 		let line = line_result?;
@@ -27,12 +27,8 @@ pub fn parse_ndjson_stream<R: Read>(reader: R) -> Result<Vec<NormalizedRecord>>
 				if let Some((ft, val)) = line.split_once(',') {
 					let ftype = ft.trim().to_lowercase();
 					let raw = val.trim().to_string();
-					let canonical = canonicalize(&ftype, &raw, &punct_re);
-					out.push(NormalizedRecord {
-						field_type: ftype,
-						raw,
-						canonical,
-					});
+					let canonical = canonicalize(&ftype, &raw, &trim);
+					out.push(NormalizedRecord::new(ftype, raw, canonical));
 					continue;
 				}
 				// skip unparseable line
@@ -41,12 +37,8 @@ pub fn parse_ndjson_stream<R: Read>(reader: R) -> Result<Vec<NormalizedRecord>>
 		};
 
 		if let Some((ftype, raw)) = extract_field_and_value(&v) {
-			let canonical = canonicalize(&ftype, &raw, &punct_re);
-			out.push(NormalizedRecord {
-				field_type: ftype,
-				raw,
-				canonical,
-			});
+			let canonical = canonicalize(&ftype, &raw, &trim);
+			out.push(NormalizedRecord::new(ftype, raw, canonical));
 		}
 	}
 
@@ -99,22 +91,6 @@ fn extract_field_and_value(v: &Value) -> Option<(String, String)> {
 	}
 }
 
-fn canonicalize(ftype: &str, raw: &str, punct_re: &Regex) -> String {
-	match ftype {
-		"domain" => {
-			let mut v = raw.trim().to_lowercase();
-			if v.ends_with('.') {
-				v.pop();
-			}
-			v
-		}
-		"ip" => raw.trim().to_string(),
-		"hash" => punct_re.replace_all(&raw.to_lowercase(), "").to_string(),
-		"email" => raw.trim().to_lowercase().to_string(),
-		_ => raw.trim().to_lowercase().to_string(),
-	}
-}
-
 #[cfg(test)]
 mod tests {
 	use super::*;
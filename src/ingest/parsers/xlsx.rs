@@ -1,9 +1,9 @@
 use anyhow::{anyhow, Result};
 use calamine::{open_workbook_auto_from_rs, Reader};
-use regex::Regex;
 use std::io::{Read, Seek};
 
 use crate::ingest::NormalizedRecord;
+use crate::ingest::bulk_normalizer::{TrimRules, canonicalize};
 
 /// Stream-parse Excel (XLSX) data from a reader and emit normalized records.
 /// Expects the first row to be headers (field_type, value) and subsequent rows to contain data.
@@ -23,7 +23,7 @@ pub fn parse_xlsx_stream<R: Read + Seek + Clone>(reader: R) -> Result<Vec<Normal
 		.map_err(|e| anyhow!("failed to read worksheet: {}", e))?;
 
 	let mut out = Vec::new();
-	let punct_re = Regex::new(r"^[\W_]+|[\W_]+$").unwrap();
+	let trim = TrimRules::default();
 
 	// Skip the header row (index 0) and process data rows
 	let mut rows = range.rows();
@@ -37,38 +37,19 @@ pub fn parse_xlsx_stream<R: Read + Seek + Clone>(reader: R) -> Result<Vec<Normal
 		let ftype = row[0].to_string().trim().to_lowercase();
 		let raw = row[1].to_string().trim().to_string();
 
-		if ftype.is_empty() || raw.is_empty() {
+		// Blank values are kept; the sink applies `Settings.empty_values`.
+		if ftype.is_empty() {
 			continue;
 		}
 
-		let canonical = canonicalize(&ftype, &raw, &punct_re);
+		let canonical = canonicalize(&ftype, &raw, &trim);
 
-		out.push(NormalizedRecord {
-			field_type: ftype,
-			raw,
-			canonical,
-		});
+		out.push(NormalizedRecord::new(ftype, raw, canonical));
 	}
 
 	Ok(out)
 }
 
-fn canonicalize(ftype: &str, raw: &str, punct_re: &Regex) -> String {
-	match ftype {
-		"domain" => {
-			let mut v = raw.trim().to_lowercase();
-			if v.ends_with('.') {
-				v.pop();
-			}
-			v
-		}
-		"ip" => raw.trim().to_string(),
-		"hash" => punct_re.replace_all(&raw.to_lowercase(), "").to_string(),
-		"email" => raw.trim().to_lowercase().to_string(),
-		_ => raw.trim().to_lowercase().to_string(),
-	}
-}
-
 #[cfg(test)]
 mod tests {
 	#[test]
@@ -0,0 +1,80 @@
+//! Previews of uploaded dumps returned to callers.
+//!
+//! A preview shows the first lines of a dump so callers can check what was
+//! detected. Values that look like emails, hashes or card numbers are
+//! masked first, since any caller able to upload sees the response.
+
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+
+use crate::ingest::format_detection::{DETECT_SCAN_BYTES, text_head};
+use crate::lib::normalizers::luhn_valid;
+
+static EMAIL_RE: Lazy<Regex> =
+	Lazy::new(|| Regex::new(r"(?i)[a-z0-9._%+-]+@[a-z0-9.-]+\.[a-z]{2,}").unwrap());
+static HASH_RE: Lazy<Regex> = Lazy::new(|| {
+	Regex::new(r"\b(?:[0-9a-fA-F]{128}|[0-9a-fA-F]{64}|[0-9a-fA-F]{40}|[0-9a-fA-F]{32})\b").unwrap()
+});
+static CARD_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\b(?:\d[ -]?){12,18}\d\b").unwrap());
+
+/// Preview for a dump of `kind` whose first bytes are `peek`: up to
+/// `max_lines` lines, within its first `DETECT_SCAN_BYTES`, with likely PII
+/// masked, or nothing when `max_lines` is 0. Binary and compressed dumps
+/// keep `detected`, the byte-level summary produced during type detection.
+pub fn bulk_preview(kind: &str, detected: String, peek: &[u8], max_lines: usize) -> String {
+	if max_lines == 0 {
+		return String::new();
+	}
+	match kind {
+		"binary" | "gzip" => detected,
+		_ => {
+			let text = text_head(peek, DETECT_SCAN_BYTES);
+			let lines: Vec<&str> = text.lines().take(max_lines).collect();
+			redact(&lines.join("\n"))
+		}
+	}
+}
+
+/// Mask emails, hex digests and Luhn-valid card numbers in `text`.
+pub fn redact(text: &str) -> String {
+	let text = EMAIL_RE.replace_all(text, "[email]");
+	let text = HASH_RE.replace_all(&text, "[hash]");
+	CARD_RE
+		.replace_all(&text, |caps: &Captures| {
+			if luhn_valid(&caps[0]) {
+				"[card]".to_string()
+			} else {
+				caps[0].to_string()
+			}
+		})
+		.into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn preview_masks_emails_hashes_and_cards() {
+		let peek = b"email,hash,card,note\n\
+			alice@example.com,5d41402abc4b2a76b9719d911017c592,4111 1111 1111 1111,order 1234567890123\n";
+		let preview = bulk_preview("csv", String::new(), peek, 8);
+		assert_eq!(
+			preview,
+			"email,hash,card,note\n[email],[hash],[card],order 1234567890123"
+		);
+		assert!(!preview.contains("alice"));
+	}
+
+	#[test]
+	fn preview_length_is_configurable() {
+		let peek = b"a\nb\nc\n";
+		assert_eq!(bulk_preview("text", String::new(), peek, 2), "a\nb");
+		assert_eq!(bulk_preview("text", String::new(), peek, 0), "");
+		assert_eq!(bulk_preview("binary", "ff 00".to_string(), peek, 0), "");
+		assert_eq!(
+			bulk_preview("binary", "ff 00".to_string(), peek, 8),
+			"ff 00"
+		);
+	}
+}
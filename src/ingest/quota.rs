@@ -0,0 +1,324 @@
+//! Per-subject ingest quotas.
+//!
+//! Requests to `/ingest/*` identified by [`crate::auth::identify`] are
+//! charged to the token's subject (`Claims.sub`); anonymous requests are
+//! charged to their peer address. Each subject may send a configured number
+//! of bytes and records per sliding window; requests over budget get `429`
+//! with `Retry-After`. Records are counted as newline-terminated lines of
+//! the request body.
+//!
+//! Admission reserves the request's announced `Content-Length` and one
+//! record, so concurrent requests cannot all pass the check before any of
+//! them is charged. The reservation is replaced by what the handler
+//! actually read once it returns.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+use std::time::{Duration, Instant};
+
+use axum::{
+	body::Body,
+	extract::{ConnectInfo, Request, State},
+	http::{StatusCode, header},
+	middleware::Next,
+	response::{IntoResponse, Response},
+};
+use futures_util::StreamExt;
+use sha2::{Digest, Sha256};
+
+use crate::auth::Subject;
+use crate::state::AppState;
+
+/// Budgets per subject over a sliding window. A zero budget is unlimited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuotaLimits {
+	pub max_bytes: u64,
+	pub max_records: u64,
+	pub window: Duration,
+}
+
+/// Usage charged at one instant; `id` identifies a reservation.
+struct Charge {
+	id: u64,
+	at: Instant,
+	bytes: u64,
+	records: u64,
+}
+
+/// Tracks each subject's usage over the last `window`.
+pub struct QuotaTracker {
+	limits: QuotaLimits,
+	usage: Mutex<HashMap<String, VecDeque<Charge>>>,
+	next_id: AtomicU64,
+}
+
+impl QuotaTracker {
+	pub fn new(limits: QuotaLimits) -> Self {
+		Self {
+			limits,
+			usage: Mutex::new(HashMap::new()),
+			next_id: AtomicU64::new(0),
+		}
+	}
+
+	pub fn limits(&self) -> QuotaLimits {
+		self.limits
+	}
+
+	/// Admit a request from `subject` announcing `incoming_bytes` and
+	/// reserve that many bytes and one record for it, returning the
+	/// reservation to [`Self::settle`]; or return how long to wait before
+	/// retrying.
+	pub fn reserve(&self, subject: &str, incoming_bytes: u64) -> Result<u64, Duration> {
+		let now = Instant::now();
+		let window = self.limits.window;
+		let mut usage = self.usage.lock().unwrap();
+		// Forget charges that left the window, and subjects with none left.
+		usage.retain(|_, charges| {
+			while charges
+				.front()
+				.is_some_and(|c| now.duration_since(c.at) >= window)
+			{
+				charges.pop_front();
+			}
+			!charges.is_empty()
+		});
+
+		let charges = usage.entry(subject.to_string()).or_default();
+		let bytes: u64 = charges.iter().map(|c| c.bytes).sum();
+		let records: u64 = charges.iter().map(|c| c.records).sum();
+		if over(self.limits.max_bytes, bytes, incoming_bytes)
+			|| over(self.limits.max_records, records, 1)
+		{
+			// Retry once the oldest charge has left the window.
+			let oldest = charges.front().map_or(now, |c| c.at);
+			let retry_after = window.saturating_sub(now.duration_since(oldest));
+			if charges.is_empty() {
+				usage.remove(subject);
+			}
+			return Err(retry_after);
+		}
+		let id = self.next_id.fetch_add(1, Relaxed);
+		charges.push_back(Charge {
+			id,
+			at: now,
+			bytes: incoming_bytes,
+			records: 1,
+		});
+		Ok(id)
+	}
+
+	/// Replace `subject`'s reservation `id` with the `bytes` and `records`
+	/// the request actually used.
+	pub fn settle(&self, subject: &str, id: u64, bytes: u64, records: u64) {
+		let mut usage = self.usage.lock().unwrap();
+		let charges = usage.entry(subject.to_string()).or_default();
+		charges.retain(|c| c.id != id);
+		charges.push_back(Charge {
+			id,
+			at: Instant::now(),
+			bytes,
+			records,
+		});
+	}
+}
+
+/// Whether using `incoming` more on top of `used` exceeds `budget`.
+fn over(budget: u64, used: u64, incoming: u64) -> bool {
+	budget > 0 && used.saturating_add(incoming) > budget
+}
+
+/// Metric label for `subject`: a truncated SHA-256, so subjects (often
+/// e-mail addresses) do not end up in metrics.
+pub fn subject_label(subject: &str) -> String {
+	let digest = Sha256::digest(subject.as_bytes());
+	digest[..8].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Bytes and lines seen while a request body is read.
+#[derive(Default)]
+struct BodyUsage {
+	bytes: AtomicU64,
+	lines: AtomicU64,
+}
+
+/// Middleware enforcing [`QuotaTracker`] budgets on `/ingest/*` requests.
+pub async fn enforce(State(state): State<AppState>, req: Request, next: Next) -> Response {
+	let Some(quota) = state.ingest_quota.clone() else {
+		return next.run(req).await;
+	};
+	if !req.uri().path().starts_with("/ingest/") {
+		return next.run(req).await;
+	}
+	let subject = quota_subject(&req);
+
+	let incoming = req
+		.headers()
+		.get(header::CONTENT_LENGTH)
+		.and_then(|v| v.to_str().ok())
+		.and_then(|v| v.parse::<u64>().ok())
+		.unwrap_or(0);
+	let reservation = match quota.reserve(&subject, incoming) {
+		Ok(reservation) => reservation,
+		Err(retry_after) => return reject(&state, &subject, retry_after),
+	};
+
+	// Count what the handler actually reads and charge it afterwards.
+	let usage = Arc::new(BodyUsage::default());
+	let counted = usage.clone();
+	let (parts, body) = req.into_parts();
+	let body = body.into_data_stream().inspect(move |chunk| {
+		if let Ok(chunk) = chunk {
+			let lines = chunk.iter().filter(|b| **b == b'\n').count();
+			counted.bytes.fetch_add(chunk.len() as u64, Relaxed);
+			counted.lines.fetch_add(lines as u64, Relaxed);
+		}
+	});
+	let req = Request::from_parts(parts, Body::from_stream(body));
+	let resp = next.run(req).await;
+	quota.settle(
+		&subject,
+		reservation,
+		usage.bytes.load(Relaxed),
+		usage.lines.load(Relaxed),
+	);
+	resp
+}
+
+/// Who `req` is charged to: its authenticated subject, else its peer
+/// address, else one budget shared by every anonymous caller.
+fn quota_subject(req: &Request) -> String {
+	if let Some(Subject(subject)) = req.extensions().get::<Subject>() {
+		return subject.clone();
+	}
+	match req.extensions().get::<ConnectInfo<SocketAddr>>() {
+		Some(ConnectInfo(peer)) => format!("anonymous@{}", peer.ip()),
+		None => "anonymous".to_string(),
+	}
+}
+
+/// `429 Too Many Requests` for `subject`, recording the rejection.
+fn reject(state: &AppState, subject: &str, retry_after: Duration) -> Response {
+	state
+		.metrics
+		.ingest_quota_rejections_total
+		.with_label_values(&[&subject_label(subject)])
+		.inc();
+	let secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+	(
+		StatusCode::TOO_MANY_REQUESTS,
+		[(header::RETRY_AFTER, secs.to_string())],
+		"ingest quota exceeded",
+	)
+		.into_response()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn tracker(max_bytes: u64, max_records: u64) -> QuotaTracker {
+		QuotaTracker::new(QuotaLimits {
+			max_bytes,
+			max_records,
+			window: Duration::from_secs(60),
+		})
+	}
+
+	#[test]
+	fn subject_over_byte_budget_is_throttled_others_are_not() {
+		let quota = tracker(1000, 0);
+		let id = quota.reserve("alice", 600).unwrap();
+		quota.settle("alice", id, 600, 6);
+
+		let retry_after = quota.reserve("alice", 600).unwrap_err();
+		assert!(retry_after > Duration::from_secs(55), "{:?}", retry_after);
+		assert!(retry_after <= Duration::from_secs(60));
+		// Requests that still fit are admitted.
+		assert!(quota.reserve("alice", 400).is_ok());
+		// Another subject has its own budget.
+		assert!(quota.reserve("bob", 1000).is_ok());
+		// A single request larger than the budget never fits.
+		assert!(quota.reserve("carol", 1001).is_err());
+	}
+
+	#[test]
+	fn record_budget_and_window_expiry() {
+		let quota = QuotaTracker::new(QuotaLimits {
+			max_bytes: 0,
+			max_records: 10,
+			window: Duration::from_millis(50),
+		});
+		let id = quota.reserve("alice", 0).unwrap();
+		quota.settle("alice", id, 1 << 30, 10);
+		assert!(quota.reserve("alice", 0).is_err());
+		std::thread::sleep(Duration::from_millis(60));
+		assert!(quota.reserve("alice", 0).is_ok());
+	}
+
+	#[test]
+	fn reservations_hold_the_budget_until_settled() {
+		let quota = tracker(1000, 0);
+		let first = quota.reserve("alice", 600).unwrap();
+		// A concurrent request cannot spend the bytes the first announced.
+		assert!(quota.reserve("alice", 600).is_err());
+
+		// The first read less than it announced; the rest is freed.
+		quota.settle("alice", first, 100, 1);
+		assert!(quota.reserve("alice", 600).is_ok());
+		assert!(quota.reserve("alice", 301).is_err());
+	}
+
+	#[tokio::test]
+	async fn anonymous_requests_are_budgeted_per_peer() {
+		use axum::{Router, routing::post};
+		use tower::ServiceExt;
+
+		let state =
+			crate::ingest::test_utils::create_test_app_state().with_ingest_quota(QuotaLimits {
+				max_bytes: 0,
+				max_records: 2,
+				window: Duration::from_secs(60),
+			});
+		let app = Router::new()
+			.route("/ingest/ndjson", post(|body: String| async move { body }))
+			.layer(axum::middleware::from_fn_with_state(state.clone(), enforce))
+			.with_state(state);
+		let send = |peer: &str| {
+			let req = Request::post("/ingest/ndjson")
+				.extension(ConnectInfo(peer.parse::<SocketAddr>().unwrap()))
+				.body(Body::from("a\nb\n"))
+				.unwrap();
+			app.clone().oneshot(req)
+		};
+
+		assert_eq!(
+			send("192.0.2.1:4000").await.unwrap().status(),
+			StatusCode::OK
+		);
+		// Another connection from the same host shares the budget.
+		let resp = send("192.0.2.1:4001").await.unwrap();
+		assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+		assert_eq!(
+			send("192.0.2.2:4000").await.unwrap().status(),
+			StatusCode::OK
+		);
+	}
+
+	#[test]
+	fn rejection_sets_retry_after_and_counts_hashed_subject() {
+		let state = crate::ingest::test_utils::create_test_app_state();
+		let resp = reject(&state, "alice@example.com", Duration::from_millis(1500));
+		assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+		assert_eq!(resp.headers()[header::RETRY_AFTER], "2");
+
+		let label = subject_label("alice@example.com");
+		assert_eq!(label.len(), 16);
+		assert!(!label.contains("alice"));
+		let rejections = &state.metrics.ingest_quota_rejections_total;
+		assert_eq!(rejections.with_label_values(&[&label]).get(), 1);
+	}
+}
@@ -0,0 +1,112 @@
+//! Resumable bulk uploads.
+//!
+//! A client sending [`UPLOAD_SESSION_HEADER`] uploads a dump in parts, each
+//! carrying a `Content-Range` and appended to the session's temp file.
+//! Bytes the session already holds are skipped, so a part can be retried
+//! after a dropped connection. Once every byte has arrived the dump is
+//! stored and processed like a single-request upload.
+
+use serde::Serialize;
+
+/// Request header naming the upload session a part belongs to.
+pub const UPLOAD_SESSION_HEADER: &str = "x-upload-session";
+
+/// Parsed `Content-Range: bytes <start>-<end>/<total>` of an upload part.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentRange {
+	/// First and last byte of the part; `None` for `bytes */<total>`,
+	/// which only asks how much the session holds.
+	pub range: Option<(u64, u64)>,
+	/// Size of the whole upload; `None` when the client sent `*`.
+	pub total: Option<u64>,
+}
+
+impl ContentRange {
+	/// Parse a `Content-Range` value, rejecting ranges that end before
+	/// they start or past `total`.
+	pub fn parse(value: &str) -> Option<Self> {
+		let (range, total) = value.trim().strip_prefix("bytes ")?.split_once('/')?;
+		let total = match total.trim() {
+			"*" => None,
+			t => Some(t.parse::<u64>().ok()?),
+		};
+		let range = match range.trim() {
+			"*" => None,
+			r => {
+				let (start, end) = r.split_once('-')?;
+				let (start, end) = (start.parse::<u64>().ok()?, end.parse::<u64>().ok()?);
+				if end < start || total.is_some_and(|t| end >= t) {
+					return None;
+				}
+				Some((start, end))
+			}
+		};
+		if range.is_none() && total.is_none() {
+			return None;
+		}
+		Some(Self { range, total })
+	}
+
+	/// Whether a session holding `received` bytes has the whole upload.
+	/// Without a range header the part is taken to be the last one.
+	pub fn is_complete(range: Option<&Self>, received: u64) -> bool {
+		range.is_none_or(|r| r.total == Some(received))
+	}
+}
+
+/// Body of the response to a part that did not complete its session.
+#[derive(Debug, Serialize)]
+pub struct SessionStatus<'a> {
+	pub session: &'a str,
+	/// Bytes the session holds; the next part starts here.
+	pub received: u64,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn content_ranges_are_parsed() {
+		assert_eq!(
+			ContentRange::parse("bytes 0-9/20"),
+			Some(ContentRange {
+				range: Some((0, 9)),
+				total: Some(20)
+			})
+		);
+		assert_eq!(
+			ContentRange::parse("bytes 10-19/*"),
+			Some(ContentRange {
+				range: Some((10, 19)),
+				total: None
+			})
+		);
+		assert_eq!(
+			ContentRange::parse("bytes */20"),
+			Some(ContentRange {
+				range: None,
+				total: Some(20)
+			})
+		);
+		for invalid in [
+			"bytes */*",
+			"bytes 9-0/20",
+			"bytes 0-20/20",
+			"items 0-9/20",
+			"0-9/20",
+		] {
+			assert_eq!(ContentRange::parse(invalid), None, "{}", invalid);
+		}
+	}
+
+	#[test]
+	fn completion_needs_every_byte() {
+		let first = ContentRange::parse("bytes 0-9/20").unwrap();
+		assert!(!ContentRange::is_complete(Some(&first), 10));
+		assert!(ContentRange::is_complete(Some(&first), 20));
+		let unknown = ContentRange::parse("bytes 0-9/*").unwrap();
+		assert!(!ContentRange::is_complete(Some(&unknown), 10));
+		assert!(ContentRange::is_complete(None, 10));
+	}
+}
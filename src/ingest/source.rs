@@ -0,0 +1,104 @@
+//! Which feed or tenant ingested a value.
+//!
+//! With an ingest source configured, every `FieldValue` node written by
+//! ingest carries it in [`SOURCE_PROP`] of its job, and the repository adds
+//! it to the node's [`SOURCES_PROP`], the list of every feed that reported
+//! the node. Nodes can then be scoped by feed in queries
+//! (`WHERE "feedA" IN n.sources`) and a feed's contribution withdrawn with
+//! [`crate::age_client::AgeClient::delete_by_source`], which deletes only
+//! the nodes no other feed reported.
+//!
+//! The source of a request is, in order of preference, the value of the
+//! configured `Settings.ingest_source_header`, the authenticated subject
+//! when `Settings.ingest_source_from_subject` is set, and the fixed
+//! `Settings.ingest_source`. All three are off by default.
+
+use axum::http::HeaderMap;
+
+use crate::auth::Subject;
+use crate::config::Settings;
+
+/// Job property holding the ingest source of a write.
+pub const SOURCE_PROP: &str = "source";
+
+/// Node property listing every ingest source that reported the node.
+pub const SOURCES_PROP: &str = "sources";
+
+/// Longest source identifier accepted; longer ones are ignored.
+pub const MAX_SOURCE_LEN: usize = 128;
+
+/// The ingest source of a request with `headers`, authenticated as
+/// `subject` if at all, or `None` when no source applies.
+pub fn ingest_source(
+	settings: &Settings,
+	headers: &HeaderMap,
+	subject: Option<&Subject>,
+) -> Option<String> {
+	let from_header = (!settings.ingest_source_header.is_empty())
+		.then(|| headers.get(settings.ingest_source_header.as_str()))
+		.flatten()
+		.and_then(|v| v.to_str().ok());
+	let from_subject = subject
+		.filter(|_| settings.ingest_source_from_subject)
+		.map(|Subject(sub)| sub.as_str());
+	let configured = Some(settings.ingest_source.as_str());
+	[from_header, from_subject, configured]
+		.into_iter()
+		.flatten()
+		.map(str::trim)
+		.find(|s| !s.is_empty() && s.len() <= MAX_SOURCE_LEN)
+		.map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use axum::http::HeaderValue;
+
+	#[test]
+	fn header_wins_over_subject_and_config() {
+		let mut settings = Settings {
+			ingest_source: "default-feed".to_string(),
+			..Settings::default()
+		};
+		let mut headers = HeaderMap::new();
+		headers.insert("x-feed", HeaderValue::from_static("feedA"));
+		let subject = Subject("ingest-bot".to_string());
+
+		// Header and subject are ignored until configured.
+		assert_eq!(
+			ingest_source(&settings, &headers, Some(&subject)).as_deref(),
+			Some("default-feed")
+		);
+
+		settings.ingest_source_from_subject = true;
+		assert_eq!(
+			ingest_source(&settings, &headers, Some(&subject)).as_deref(),
+			Some("ingest-bot")
+		);
+
+		settings.ingest_source_header = "x-feed".to_string();
+		assert_eq!(
+			ingest_source(&settings, &headers, Some(&subject)).as_deref(),
+			Some("feedA")
+		);
+
+		// Blank or oversized header values fall through.
+		headers.insert("x-feed", HeaderValue::from_static("  "));
+		assert_eq!(
+			ingest_source(&settings, &headers, None).as_deref(),
+			Some("default-feed")
+		);
+		let long = "f".repeat(MAX_SOURCE_LEN + 1);
+		headers.insert("x-feed", HeaderValue::from_str(&long).unwrap());
+		assert_eq!(
+			ingest_source(&settings, &headers, None).as_deref(),
+			Some("default-feed")
+		);
+
+		assert_eq!(
+			ingest_source(&Settings::default(), &headers, Some(&subject)),
+			None
+		);
+	}
+}
@@ -5,9 +5,17 @@
 
 #![cfg(test)]
 
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde_json::{Value, json};
 use tokio::sync::mpsc;
 
+use crate::age_client::{AgeError, AgeRepo, AgeResult, Edge, GraphNode};
+use crate::persist::PersistJob;
+
 /// A dummy repository implementation for testing that accepts all operations.
 pub struct DummyRepo;
 
@@ -18,28 +26,258 @@ impl crate::age_client::AgeRepo for DummyRepo {
 		_label: &str,
 		_key: &str,
 		_props: &serde_json::Value,
-	) -> anyhow::Result<()> {
+	) -> crate::age_client::AgeResult<()> {
 		Ok(())
 	}
 
-	async fn ping(&self) -> anyhow::Result<()> {
+	async fn ping(&self) -> crate::age_client::AgeResult<()> {
 		Ok(())
 	}
 
 	async fn merge_batch(
 		&self,
 		_items: &[(String, String, serde_json::Value)],
-	) -> anyhow::Result<()> {
+	) -> crate::age_client::AgeResult<()> {
+		Ok(())
+	}
+
+	async fn persist_row(
+		&self,
+		_dump_id: &str,
+		_row_index: i64,
+		_row_hash: Option<&str>,
+		_cells: &[(String, String, String, String)],
+		_timestamp: &str,
+	) -> crate::age_client::AgeResult<()> {
+		Ok(())
+	}
+
+	async fn increment_co_occurrence(
+		&self,
+		_a_key: &str,
+		_b_key: &str,
+		_timestamp: &str,
+	) -> crate::age_client::AgeResult<()> {
+		Ok(())
+	}
+
+	async fn persist_credential(
+		&self,
+		_from_key: &str,
+		_to_key: &str,
+		_timestamp: &str,
+	) -> crate::age_client::AgeResult<()> {
+		Ok(())
+	}
+
+	async fn apply_migration(&self, _sql_content: &str) -> crate::age_client::AgeResult<()> {
+		Ok(())
+	}
+}
+
+/// Repository double that records what is written through it.
+///
+/// Every knob is off by default: `down` fails `ping`, `failing` fails every
+/// write (nothing is recorded), `delay` holds each `merge_batch` for that
+/// long, `jitter` holds it a varying few milliseconds instead, and
+/// `fingerprint` is reported as the stored fingerprint of every key.
+#[derive(Default)]
+pub struct RecordingRepo {
+	/// `(label, key, props)` of every node merged, in order.
+	pub merged: Mutex<Vec<(String, String, Value)>>,
+	/// `(node key, edge)` of every `merge_edge`.
+	pub edges: Mutex<Vec<(String, Edge)>>,
+	/// `(from key, rel_type, to key)` of every `relate`.
+	pub relations: Mutex<Vec<(String, String, String)>>,
+	/// `(dump id, props)` of every `merge_dump`.
+	pub dumps: Mutex<Vec<(String, Value)>>,
+	/// Observations of each key through `observe_batch`.
+	pub seen_count: Mutex<HashMap<String, u64>>,
+	pub down: AtomicBool,
+	pub failing: AtomicBool,
+	pub delay: Option<Duration>,
+	pub jitter: bool,
+	pub fingerprint: Option<String>,
+	/// Most batch writes that overlapped.
+	pub max_in_flight: AtomicUsize,
+	/// Batch writes currently held by `delay` or `jitter`.
+	pub in_flight: AtomicUsize,
+	/// `merge_batch` calls so far, which seed the jitter.
+	pub calls: AtomicUsize,
+}
+
+impl RecordingRepo {
+	/// Keys of the nodes merged, in order.
+	pub fn keys(&self) -> Vec<String> {
+		let merged = self.merged.lock().unwrap();
+		merged.iter().map(|(_, key, _)| key.clone()).collect()
+	}
+
+	/// Labels of the nodes merged, in order.
+	pub fn labels(&self) -> Vec<String> {
+		let merged = self.merged.lock().unwrap();
+		merged.iter().map(|(label, _, _)| label.clone()).collect()
+	}
+
+	/// Resulting props of each `(label, key)`, with the props of later
+	/// merges set over earlier ones.
+	pub fn nodes(&self) -> HashMap<(String, String), Value> {
+		let mut nodes = HashMap::new();
+		for (label, key, props) in self.merged.lock().unwrap().iter() {
+			let node = nodes
+				.entry((label.clone(), key.clone()))
+				.or_insert_with(|| json!({}));
+			if let (Value::Object(existing), Value::Object(incoming)) = (node, props) {
+				existing.extend(incoming.clone());
+			}
+		}
+		nodes
+	}
+
+	fn write(&self) -> AgeResult<()> {
+		if self.failing.load(Ordering::SeqCst) {
+			return Err(AgeError::Connection("database down".to_string()));
+		}
+		Ok(())
+	}
+}
+
+#[async_trait::async_trait]
+impl AgeRepo for RecordingRepo {
+	async fn merge_entity(&self, label: &str, key: &str, props: &Value) -> AgeResult<()> {
+		self.write()?;
+		self.merged
+			.lock()
+			.unwrap()
+			.push((label.to_string(), key.to_string(), props.clone()));
+		Ok(())
+	}
+
+	async fn ping(&self) -> AgeResult<()> {
+		if self.down.load(Ordering::SeqCst) {
+			return Err(AgeError::Connection("database unavailable".to_string()));
+		}
+		Ok(())
+	}
+
+	async fn merge_batch(&self, items: &[(String, String, Value)]) -> AgeResult<()> {
+		let delay = if self.jitter {
+			let n = self.calls.fetch_add(1, Ordering::SeqCst);
+			Some(Duration::from_millis(1 + (n * 7 % 5) as u64))
+		} else {
+			self.delay
+		};
+		if let Some(delay) = delay {
+			let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+			self.max_in_flight.fetch_max(now, Ordering::SeqCst);
+			tokio::time::sleep(delay).await;
+			self.in_flight.fetch_sub(1, Ordering::SeqCst);
+		}
+		self.write()?;
+		self.merged.lock().unwrap().extend_from_slice(items);
+		Ok(())
+	}
+
+	async fn observe_batch(
+		&self,
+		items: &[(String, String, Value)],
+		_timestamp: &str,
+	) -> AgeResult<()> {
+		self.merge_batch(items).await?;
+		let mut seen = self.seen_count.lock().unwrap();
+		for (_, key, _) in items {
+			*seen.entry(key.clone()).or_insert(0) += 1;
+		}
+		Ok(())
+	}
+
+	async fn persist_row(
+		&self,
+		_dump_id: &str,
+		_row_index: i64,
+		_row_hash: Option<&str>,
+		_cells: &[(String, String, String, String)],
+		_timestamp: &str,
+	) -> AgeResult<()> {
+		self.write()
+	}
+
+	async fn increment_co_occurrence(
+		&self,
+		_a_key: &str,
+		_b_key: &str,
+		_timestamp: &str,
+	) -> AgeResult<()> {
+		self.write()
+	}
+
+	async fn persist_credential(
+		&self,
+		_from_key: &str,
+		_to_key: &str,
+		_timestamp: &str,
+	) -> AgeResult<()> {
+		self.write()
+	}
+
+	async fn merge_dump(&self, dump_id: &str, props: &Value, _timestamp: &str) -> AgeResult<()> {
+		self.write()?;
+		self.dumps
+			.lock()
+			.unwrap()
+			.push((dump_id.to_string(), props.clone()));
+		Ok(())
+	}
+
+	async fn relate(
+		&self,
+		from_key: &str,
+		to_key: &str,
+		rel_type: &str,
+		_props: &Value,
+	) -> AgeResult<()> {
+		self.write()?;
+		self.relations.lock().unwrap().push((
+			from_key.to_string(),
+			rel_type.to_string(),
+			to_key.to_string(),
+		));
+		Ok(())
+	}
+
+	async fn merge_edge(&self, node: &GraphNode, edge: &Edge, _timestamp: &str) -> AgeResult<()> {
+		self.write()?;
+		self.edges
+			.lock()
+			.unwrap()
+			.push((node.key.clone(), edge.clone()));
+		Ok(())
+	}
+
+	async fn key_fingerprint(&self, _label: &str, _key: &str) -> AgeResult<Option<String>> {
+		Ok(self.fingerprint.clone())
+	}
+
+	async fn apply_migration(&self, _sql_content: &str) -> AgeResult<()> {
 		Ok(())
 	}
 }
 
 /// Create a test AppState with a dummy repository and channel.
 pub fn create_test_app_state() -> crate::state::AppState {
-	let (tx, _rx) = mpsc::channel(16);
-	let repo: Arc<dyn crate::age_client::AgeRepo> = Arc::new(DummyRepo);
-	crate::state::AppState {
+	app_state_with_receiver(Arc::new(DummyRepo)).0
+}
+
+/// A test AppState over `repo`, with the receiving end of its persist
+/// channel so tests can inspect the jobs it enqueues.
+pub fn app_state_with_receiver(
+	repo: Arc<dyn AgeRepo>,
+) -> (crate::state::AppState, mpsc::Receiver<PersistJob>) {
+	let (tx, rx) = mpsc::channel(16);
+	let state = crate::state::AppState::new(
 		repo,
-		persist_sender: tx,
-	}
+		tx,
+		Arc::new(crate::observability::MetricsRegistry::new()),
+	);
+	(state, rx)
 }
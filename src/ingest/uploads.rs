@@ -0,0 +1,404 @@
+//! Temp-file placement and retention for bulk uploads.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+
+use crate::ingest::bulk_tasks::{BulkOutcome, BulkTaskRegistry};
+
+/// Filename prefix of every bulk upload temp file. The sweeper only ever
+/// touches files carrying this prefix.
+pub const UPLOAD_PREFIX: &str = "heimdall_dump_";
+
+/// Filename prefix of resumable upload sessions, which are swept after
+/// their own, usually shorter, idle TTL.
+pub const SESSION_PREFIX: &str = "heimdall_session_";
+
+/// Longest accepted upload session id.
+const MAX_SESSION_ID_LEN: usize = 64;
+
+static UPLOAD_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// Per-process sequence number that keeps upload filenames unique when
+/// several uploads start within the same millisecond.
+pub fn next_upload_seq() -> u64 {
+	UPLOAD_SEQ.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Unique temp filename for an upload, carrying [`UPLOAD_PREFIX`] so the
+/// sweeper reclaims it if it is left behind.
+pub fn upload_filename(ext: &str) -> String {
+	let now = SystemTime::now()
+		.duration_since(SystemTime::UNIX_EPOCH)
+		.unwrap_or_default();
+	format!(
+		"{}{}_{}_{}.{}",
+		UPLOAD_PREFIX,
+		std::process::id(),
+		now.as_millis(),
+		next_upload_seq(),
+		ext
+	)
+}
+
+/// Temp filename of resumable upload session `id`, or `None` unless the
+/// id is 1 to 64 ASCII letters, digits, `-` or `_`.
+pub fn session_filename(id: &str) -> Option<String> {
+	let valid = !id.is_empty()
+		&& id.len() <= MAX_SESSION_ID_LEN
+		&& id
+			.bytes()
+			.all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+	valid.then(|| format!("{}{}.bin", SESSION_PREFIX, id))
+}
+
+/// File recording how many bytes of the upload at `path` background
+/// processing has consumed. It shares the upload's prefix, so the sweeper
+/// removes it together with the upload.
+pub fn checkpoint_path(path: &Path) -> PathBuf {
+	let mut name = path.as_os_str().to_owned();
+	name.push(".offset");
+	PathBuf::from(name)
+}
+
+/// Byte offset checkpointed for `path`; 0 when there is none.
+pub fn read_checkpoint(path: &Path) -> u64 {
+	std::fs::read_to_string(checkpoint_path(path))
+		.ok()
+		.and_then(|s| s.trim().parse().ok())
+		.unwrap_or(0)
+}
+
+/// Record that the first `offset` bytes of `path` were processed. The
+/// checkpoint is replaced atomically.
+pub fn write_checkpoint(path: &Path, offset: u64) -> std::io::Result<()> {
+	let target = checkpoint_path(path);
+	let mut tmp = target.clone().into_os_string();
+	tmp.push(".tmp");
+	std::fs::write(&tmp, offset.to_string())?;
+	std::fs::rename(&tmp, &target)
+}
+
+/// Remove the checkpoint of `path`, if any.
+pub fn clear_checkpoint(path: &Path) {
+	let checkpoint = checkpoint_path(path);
+	match std::fs::remove_file(&checkpoint) {
+		Ok(()) => {}
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+		Err(e) => log::warn!(
+			"failed to remove checkpoint {}: {}",
+			checkpoint.display(),
+			e
+		),
+	}
+}
+
+/// Directory bulk uploads are written to: `upload_dir` when set, otherwise
+/// the system temp directory.
+pub fn upload_dir(settings: &crate::config::Settings) -> PathBuf {
+	if settings.upload_dir.is_empty() {
+		std::env::temp_dir()
+	} else {
+		PathBuf::from(&settings.upload_dir)
+	}
+}
+
+/// Remove upload temp files in `dir` whose modification time is older than
+/// `max_age`, leaving those `tasks` is still processing. Returns the number
+/// of files removed.
+pub fn sweep_uploads(
+	dir: &Path,
+	max_age: Duration,
+	tasks: &BulkTaskRegistry,
+) -> std::io::Result<usize> {
+	sweep_prefixed(dir, UPLOAD_PREFIX, max_age, tasks)
+}
+
+/// Remove resumable upload sessions in `dir` that received nothing for
+/// longer than `ttl`, leaving those `tasks` is still processing. Returns
+/// the number of files removed.
+pub fn sweep_sessions(
+	dir: &Path,
+	ttl: Duration,
+	tasks: &BulkTaskRegistry,
+) -> std::io::Result<usize> {
+	sweep_prefixed(dir, SESSION_PREFIX, ttl, tasks)
+}
+
+/// Temp filename of the upload a file in the upload directory belongs to:
+/// the file itself, or the upload it holds the checkpoint of.
+fn owning_upload(name: &str) -> &str {
+	name.strip_suffix(".offset.tmp")
+		.or_else(|| name.strip_suffix(".offset"))
+		.unwrap_or(name)
+}
+
+fn sweep_prefixed(
+	dir: &Path,
+	prefix: &str,
+	max_age: Duration,
+	tasks: &BulkTaskRegistry,
+) -> std::io::Result<usize> {
+	let now = SystemTime::now();
+	let mut removed = 0;
+	for entry in std::fs::read_dir(dir)? {
+		let entry = entry?;
+		let name = entry.file_name().to_string_lossy().into_owned();
+		if !name.starts_with(prefix) {
+			continue;
+		}
+		// Background processing keeps reading a file long after it was
+		// last written; it is not stale until its task has finished.
+		if tasks.outcome(owning_upload(&name)) == Some(BulkOutcome::Running) {
+			continue;
+		}
+		let meta = match entry.metadata() {
+			Ok(m) if m.is_file() => m,
+			_ => continue,
+		};
+		let age = meta
+			.modified()
+			.ok()
+			.and_then(|m| now.duration_since(m).ok())
+			.unwrap_or_default();
+		if age > max_age {
+			match std::fs::remove_file(entry.path()) {
+				Ok(()) => removed += 1,
+				Err(e) => {
+					log::warn!(
+						"failed to remove stale upload {}: {}",
+						entry.path().display(),
+						e
+					)
+				}
+			}
+		}
+	}
+	Ok(removed)
+}
+
+/// Spawn a background task that sweeps `dir` every `interval`, removing
+/// uploads older than `max_age` and sessions idle for longer than
+/// `session_ttl`. `None` leaves the respective files alone, as does a
+/// task in `tasks` still processing a file.
+pub fn spawn_upload_sweeper(
+	dir: PathBuf,
+	max_age: Option<Duration>,
+	session_ttl: Option<Duration>,
+	interval: Duration,
+	tasks: std::sync::Arc<BulkTaskRegistry>,
+) -> tokio::task::JoinHandle<()> {
+	tokio::spawn(async move {
+		let mut ticker = tokio::time::interval(interval);
+		loop {
+			ticker.tick().await;
+			let dir = dir.clone();
+			let tasks = tasks.clone();
+			let sweep = move || -> std::io::Result<usize> {
+				let uploads = max_age.map_or(Ok(0), |age| sweep_uploads(&dir, age, &tasks))?;
+				let sessions =
+					session_ttl.map_or(Ok(0), |ttl| sweep_sessions(&dir, ttl, &tasks))?;
+				Ok(uploads + sessions)
+			};
+			match tokio::task::spawn_blocking(sweep).await {
+				Ok(Ok(n)) if n > 0 => log::info!("removed {} stale upload file(s)", n),
+				Ok(Ok(_)) => {}
+				Ok(Err(e)) => log::warn!("upload sweep failed: {}", e),
+				Err(e) => log::warn!("upload sweep task failed: {}", e),
+			}
+		}
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn sweep_removes_only_old_upload_files() {
+		let dir = tempfile::tempdir().unwrap();
+		let old = dir.path().join(format!("{}1_1.bin", UPLOAD_PREFIX));
+		let fresh = dir.path().join(format!("{}1_2.bin", UPLOAD_PREFIX));
+		let other = dir.path().join("unrelated.bin");
+		for p in [&old, &fresh, &other] {
+			std::fs::write(p, b"x").unwrap();
+		}
+		let an_hour_ago = SystemTime::now() - Duration::from_secs(3600);
+		for p in [&old, &other] {
+			std::fs::File::options()
+				.write(true)
+				.open(p)
+				.unwrap()
+				.set_modified(an_hour_ago)
+				.unwrap();
+		}
+
+		let tasks = BulkTaskRegistry::new();
+		let removed = sweep_uploads(dir.path(), Duration::from_secs(60), &tasks).unwrap();
+		assert_eq!(removed, 1);
+		assert!(!old.exists());
+		assert!(fresh.exists());
+		assert!(other.exists());
+	}
+
+	#[test]
+	fn sweep_skips_uploads_still_being_processed() {
+		let dir = tempfile::tempdir().unwrap();
+		let name = format!("{}1_1.bin", UPLOAD_PREFIX);
+		let busy = dir.path().join(&name);
+		std::fs::write(&busy, b"x").unwrap();
+		write_checkpoint(&busy, 1).unwrap();
+		let an_hour_ago = SystemTime::now() - Duration::from_secs(3600);
+		for p in [busy.clone(), checkpoint_path(&busy)] {
+			std::fs::File::options()
+				.write(true)
+				.open(&p)
+				.unwrap()
+				.set_modified(an_hour_ago)
+				.unwrap();
+		}
+
+		let tasks = BulkTaskRegistry::new();
+		tasks.register(&name);
+		assert_eq!(
+			sweep_uploads(dir.path(), Duration::from_secs(60), &tasks).unwrap(),
+			0
+		);
+		assert!(busy.exists());
+		assert!(checkpoint_path(&busy).exists());
+
+		tasks.finish(&name, BulkOutcome::Failed);
+		assert_eq!(
+			sweep_uploads(dir.path(), Duration::from_secs(60), &tasks).unwrap(),
+			2
+		);
+		assert!(!busy.exists());
+	}
+
+	#[test]
+	fn session_ids_are_restricted() {
+		assert_eq!(
+			session_filename("a1-B_2").as_deref(),
+			Some("heimdall_session_a1-B_2.bin")
+		);
+		assert_eq!(session_filename(""), None);
+		assert_eq!(session_filename("../etc/passwd"), None);
+		assert_eq!(session_filename(&"x".repeat(65)), None);
+	}
+
+	#[test]
+	fn checkpoints_round_trip() {
+		let dir = tempfile::tempdir().unwrap();
+		let upload = dir.path().join(format!("{}1_1.bin", UPLOAD_PREFIX));
+		assert_eq!(read_checkpoint(&upload), 0);
+		write_checkpoint(&upload, 42).unwrap();
+		write_checkpoint(&upload, 4096).unwrap();
+		assert_eq!(read_checkpoint(&upload), 4096);
+		clear_checkpoint(&upload);
+		assert_eq!(read_checkpoint(&upload), 0);
+		clear_checkpoint(&upload);
+	}
+
+	#[test]
+	fn sessions_are_swept_separately() {
+		let dir = tempfile::tempdir().unwrap();
+		let session = dir.path().join(session_filename("abandoned").unwrap());
+		let upload = dir.path().join(format!("{}1_1.bin", UPLOAD_PREFIX));
+		for p in [&session, &upload] {
+			std::fs::write(p, b"x").unwrap();
+			std::fs::File::options()
+				.write(true)
+				.open(p)
+				.unwrap()
+				.set_modified(SystemTime::now() - Duration::from_secs(3600))
+				.unwrap();
+		}
+
+		let tasks = BulkTaskRegistry::new();
+		assert_eq!(
+			sweep_sessions(dir.path(), Duration::from_secs(60), &tasks).unwrap(),
+			1
+		);
+		assert!(!session.exists());
+		assert!(upload.exists());
+	}
+
+	/// Upload `body` through `bulk_dump_upload` with auto-processing on and
+	/// return the temp file path reported in the response.
+	async fn upload(dir: &Path, keep_raw_setting: bool, allow_query: bool, uri: &str) -> PathBuf {
+		use axum::body::Body;
+		use axum::http::Request;
+		use axum::response::IntoResponse;
+		use std::sync::Arc;
+
+		let settings = crate::config::Settings {
+			upload_dir: dir.to_string_lossy().into_owned(),
+			auto_process_bulk: true,
+			keep_raw_uploads: keep_raw_setting,
+			allow_keep_raw_query: allow_query,
+			..Default::default()
+		};
+		let state =
+			crate::ingest::test_utils::create_test_app_state().with_settings(Arc::new(settings));
+		let req = Request::builder()
+			.uri(uri)
+			.body(Body::from(
+				"{\"field_type\":\"domain\",\"value\":\"a.com\"}\n",
+			))
+			.unwrap();
+		let resp = crate::ingest::bulk_dump_upload(axum::extract::State(state), req)
+			.await
+			.into_response();
+		assert!(resp.status().is_success());
+		let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+			.await
+			.unwrap();
+		let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+		let path = PathBuf::from(json["filename"].as_str().unwrap());
+		assert!(path.starts_with(dir));
+		path
+	}
+
+	async fn wait_for_removal(path: &Path) -> bool {
+		for _ in 0..100 {
+			if !path.exists() {
+				return true;
+			}
+			tokio::time::sleep(Duration::from_millis(20)).await;
+		}
+		false
+	}
+
+	#[tokio::test]
+	async fn processed_upload_is_removed_by_default() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = upload(dir.path(), false, false, "/ingest/bulk").await;
+		assert!(
+			wait_for_removal(&path).await,
+			"{} was not removed",
+			path.display()
+		);
+	}
+
+	#[tokio::test]
+	async fn keep_raw_query_is_ignored_unless_allowed() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = upload(dir.path(), false, false, "/ingest/bulk?keep_raw=true").await;
+		assert!(
+			wait_for_removal(&path).await,
+			"{} was not removed",
+			path.display()
+		);
+	}
+
+	#[tokio::test]
+	async fn processed_upload_is_kept_with_keep_raw() {
+		let dir = tempfile::tempdir().unwrap();
+		let by_setting = upload(dir.path(), true, false, "/ingest/bulk").await;
+		let by_query = upload(dir.path(), false, true, "/ingest/bulk?keep_raw=true").await;
+		// Give background processing ample time to finish.
+		tokio::time::sleep(Duration::from_millis(500)).await;
+		assert!(by_setting.exists());
+		assert!(by_query.exists());
+	}
+}
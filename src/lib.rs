@@ -1,13 +1,22 @@
 pub mod age_client;
+pub mod audit;
+pub mod auth;
+pub mod capabilities;
 pub mod config;
 pub mod devops;
 pub mod enrich;
+pub mod entity;
 pub mod health;
 pub mod ingest;
+pub mod lineage;
 pub mod observability;
 pub mod persist;
 pub mod pii;
+pub mod preflight;
+pub mod query;
+pub mod sink;
 pub mod state;
+pub mod store;
 pub mod sync;
 pub mod tls_utils;
 
@@ -30,7 +39,7 @@ use hyper_util::rt::TokioExecutor;
 use hyper_util::rt::TokioIo;
 use hyper_util::server::conn::auto::Builder as AutoBuilder;
 use hyper_util::service::TowerToHyperService;
-use tokio::net::TcpListener;
+use tokio::net::TcpStream;
 use tokio_rustls::TlsAcceptor;
 use tower::ServiceBuilder;
 use tower_http::catch_panic::CatchPanicLayer;
@@ -40,7 +49,6 @@ use tower_http::sensitive_headers::{
 	SetSensitiveRequestHeadersLayer, SetSensitiveResponseHeadersLayer,
 };
 use tower_http::set_header::SetResponseHeaderLayer;
-use tower_http::timeout::RequestBodyTimeoutLayer;
 use tower_http::trace::TraceLayer;
 
 /// Start a hardened dev HTTP server exposing the ingest endpoints.
@@ -48,15 +56,6 @@ use tower_http::trace::TraceLayer;
 /// This function intentionally logs errors rather than returning them so
 /// the simple `main` runner can call it without changing its signature.
 pub async fn run() {
-	// Initialize observability: structured logging, metrics, and tracing
-	let obs_state = match crate::observability::init_observability().await {
-		Ok(s) => s,
-		Err(e) => {
-			eprintln!("warning: failed to initialize observability: {}", e);
-			crate::observability::ObservabilityState::default()
-		}
-	};
-
 	// Load settings (fall back to defaults on error)
 	let settings = match crate::config::load() {
 		Ok(s) => s,
@@ -66,18 +65,75 @@ pub async fn run() {
 		}
 	};
 
+	// Initialize observability: structured logging, metrics, and tracing
+	let obs_state =
+		match crate::observability::init_observability(settings.trace_sample_ratio).await {
+			Ok(s) => s,
+			Err(e) => {
+				eprintln!("warning: failed to initialize observability: {}", e);
+				crate::observability::ObservabilityState::default()
+			}
+		};
+
+	// Histogram buckets are configurable, so rebuild the metrics registry
+	// now that settings are loaded.
+	let metrics =
+		match crate::observability::MetricsRegistry::with_buckets(&settings.histogram_buckets()) {
+			Ok(m) => Arc::new(m),
+			Err(e) => {
+				eprintln!("warning: invalid histogram buckets, using defaults: {}", e);
+				obs_state.metrics.clone()
+			}
+		};
+
+	// Bulk uploads and queries get their own request and body timeouts:
+	// generous for the former, tight for the latter.
+	let bulk_routes = Router::new()
+		.route("/ingest/bulk", post(crate::ingest::bulk_dump_upload))
+		.route("/ingest/multipart", post(crate::ingest::multipart_upload))
+		.route("/ingest/local", post(crate::ingest::local::local_ingest));
+	let query_routes = Router::new().route("/query/stream", post(crate::query::query_stream));
+
 	// Build the router with ingest endpoints
 	let app = Router::new()
 		.route("/ingest/ndjson", post(crate::ingest::ndjson_upload))
-		.route("/ingest/bulk", post(crate::ingest::bulk_dump_upload))
-		.route("/ingest/multipart", post(crate::ingest::multipart_upload))
+		.route("/ingest/detect", post(crate::ingest::detect_upload))
+		.route("/ingest/records", post(crate::ingest::records_upload))
+		.route(
+			"/sync/changelog",
+			get(crate::sync::http::export_changelog).post(crate::sync::http::import_changelog),
+		)
+		.route(
+			"/admin/changelog/compact",
+			post(crate::sync::http::compact_changelog),
+		)
+		.route(
+			"/admin/changelog/stats",
+			get(crate::sync::http::changelog_stats),
+		)
+		.route(
+			"/admin/keys/rotate",
+			get(crate::persist::rekey::salt_rotation_status)
+				.post(crate::persist::rekey::rotate_salt),
+		)
+		.route(
+			"/entity/{label}/{key}/neighbors",
+			get(crate::entity::entity_neighbors),
+		)
 		.route("/health", get(|| async { "OK" }))
+		.route("/capabilities", get(crate::capabilities::capabilities))
 		.route("/health/db", get(crate::health::db_health))
-		.route("/metrics", get(|| async {
-			let mut metrics = crate::persist::metrics_text();
-			metrics.push_str(&crate::sync::global_sync_metrics().to_prometheus_text());
-			metrics
-		}))
+		.route("/enrich/status", get(crate::enrich::http::enrich_status))
+		.route("/metrics", get(crate::observability::metrics_handler))
+		.route(
+			"/metrics.json",
+			get(crate::observability::metrics_json_handler),
+		);
+	let app = settings
+		.default_timeouts()
+		.apply(app)
+		.merge(settings.bulk_timeouts().apply(bulk_routes))
+		.merge(settings.query_timeouts().apply(query_routes))
 		// Defense-in-depth: normalize paths and add conservative security headers
 		.layer(TraceLayer::new_for_http())
 		.layer(NormalizePathLayer::trim_trailing_slash())
@@ -115,36 +171,42 @@ pub async fn run() {
 		.ok()
 		.and_then(|s| s.parse::<u64>().ok())
 		.unwrap_or(1000);
-
-	let mut last_err: Option<anyhow::Error> = None;
-	let mut client_opt: Option<crate::age_client::AgeClient> = None;
-	for attempt in 1..=max_retries {
-		match crate::age_client::AgeClient::connect(
-			settings.database_url.as_str(),
-			&settings.age_graph,
-		)
-		.await
-		{
-			Ok(c) => {
-				client_opt = Some(c);
-				break;
-			}
-			Err(e) => {
-				eprintln!(
-					"DB connect attempt {}/{} failed: {}",
-					attempt, max_retries, e
-				);
-				last_err = Some(e);
-				if attempt < max_retries {
-					tokio::time::sleep(Duration::from_millis(backoff_ms)).await;
+	// Validated when settings load.
+	let prop_key_policy = settings.prop_key_policy.parse().unwrap_or_default();
+
+	// The graph backend: Apache AGE unless plain Postgres tables were
+	// selected, which need no extension, graph or Cypher checks.
+	let (store, age_info): (
+		std::sync::Arc<dyn crate::age_client::AgeRepo>,
+		Option<crate::age_client::AgeInfo>,
+	) = if settings.storage_backend == "postgres" {
+		let mut last_err: Option<crate::age_client::AgeError> = None;
+		let mut store_opt = None;
+		for attempt in 1..=max_retries {
+			match crate::store::postgres::PgGraphStore::connect(settings.database_url.as_str())
+				.await
+			{
+				Ok(s) => {
+					store_opt = Some(
+						s.with_key_property(&settings.graph_key_property)
+							.with_sighting_compaction(settings.sighting_compaction_secs)
+							.with_prop_key_policy(prop_key_policy),
+					);
+					break;
+				}
+				Err(e) => {
+					eprintln!(
+						"DB connect attempt {}/{} failed: {}",
+						attempt, max_retries, e
+					);
+					last_err = Some(e);
+					if attempt < max_retries {
+						tokio::time::sleep(Duration::from_millis(backoff_ms)).await;
+					}
 				}
 			}
 		}
-	}
-
-	let client = match client_opt {
-		Some(c) => c,
-		None => {
+		let Some(store) = store_opt else {
 			eprintln!(
 				"failed to connect to DB for persistence after {} attempts: {}",
 				max_retries,
@@ -154,141 +216,512 @@ pub async fn run() {
 					.unwrap_or_else(|| "unknown error".to_string())
 			);
 			return;
+		};
+		if let Err(e) = store.ensure_schema().await {
+			eprintln!("failed to create graph tables: {}; serving disabled", e);
+			return;
+		}
+		(std::sync::Arc::new(store), None)
+	} else {
+		let mut last_err: Option<anyhow::Error> = None;
+		let mut client_opt: Option<crate::age_client::AgeClient> = None;
+		for attempt in 1..=max_retries {
+			match crate::age_client::AgeClient::connect_with(
+				settings.database_url.as_str(),
+				&settings.graph_name(),
+				crate::age_client::AgeConnectOptions {
+					search_path: Some(settings.age_search_path.clone()),
+				},
+			)
+			.await
+			{
+				Ok(c) => {
+					let mut c = c.with_key_property(&settings.graph_key_property);
+					if settings.raw_samples_enabled {
+						c = c.with_raw_samples(settings.raw_samples_max);
+					}
+					if settings.age_strict_property_types {
+						c = c.with_strict_property_types();
+					}
+					c = c.with_hub_guard(crate::age_client::HubGuard {
+						max_degree: settings.cooccur_hub_max_degree,
+						flag: settings.cooccur_flag_hubs,
+						skipped: metrics.cooccur_hub_skipped_total.clone(),
+					});
+					c = c.with_sighting_compaction(settings.sighting_compaction_secs);
+					c = c.with_prop_key_policy(prop_key_policy);
+					if settings.auto_row_hash {
+						c = c.with_auto_row_hash(if settings.row_hash_unordered {
+							crate::age_client::RowHashOrder::Unordered
+						} else {
+							crate::age_client::RowHashOrder::Columns
+						});
+					}
+					client_opt = Some(c);
+					break;
+				}
+				Err(e) => {
+					eprintln!(
+						"DB connect attempt {}/{} failed: {}",
+						attempt, max_retries, e
+					);
+					last_err = Some(e.into());
+					if attempt < max_retries {
+						tokio::time::sleep(Duration::from_millis(backoff_ms)).await;
+					}
+				}
+			}
+		}
+
+		let client = match client_opt {
+			Some(c) => c,
+			None => {
+				eprintln!(
+					"failed to connect to DB for persistence after {} attempts: {}",
+					max_retries,
+					last_err
+						.as_ref()
+						.map(|e| e.to_string())
+						.unwrap_or_else(|| "unknown error".to_string())
+				);
+				return;
+			}
+		};
+
+		// Fail fast with an actionable message rather than on the first Cypher
+		// call when the AGE extension is missing or too old.
+		let mut age_info = None;
+		if settings.age_check_extension {
+			match client.check_extension().await {
+				Ok(info) => {
+					eprintln!("AGE extension {} detected", info.version);
+					age_info = Some(info);
+				}
+				Err(e) => {
+					eprintln!("{}; serving disabled", e);
+					return;
+				}
+			}
+		}
+
+		// Likewise for a missing graph, unless it may be created here.
+		let graph_ready = if settings.age_create_graph {
+			client.create_graph(client.graph()).await.map(|created| {
+				if created {
+					eprintln!("created AGE graph '{}'", client.graph());
+				}
+			})
+		} else {
+			client.verify_graph().await
+		};
+		if let Err(e) = graph_ready {
+			eprintln!("{}; serving disabled", e);
+			return;
 		}
+
+		(std::sync::Arc::new(client), age_info)
 	};
 
-	let repo: std::sync::Arc<dyn crate::age_client::AgeRepo> = std::sync::Arc::new(client);
+	// Writes still carrying keys a salt rotation has moved go to the new
+	// keys.
+	let salt_rotation = Arc::new(crate::persist::rekey::SaltRotation::new());
+	let repo: std::sync::Arc<dyn crate::age_client::AgeRepo> = std::sync::Arc::new(
+		crate::persist::rekey::RekeyingRepo::new(store, salt_rotation.keys()),
+	);
 
 	// Inject the shared repo into application state and attach it to the
 	// router so handlers can access it via Axum's `State` extractor.
 	// Start the background persistence batcher and attach the sender to the
 	// application state so handlers can enqueue jobs without blocking.
-	let persist_capacity: usize = std::env::var("HMD_PERSIST_CHANNEL_CAPACITY")
+	let mut persist_opts = crate::persist::BatcherOptions::default();
+	if let Some(v) = std::env::var("HMD_PERSIST_CHANNEL_CAPACITY")
 		.ok()
 		.and_then(|s| s.parse::<usize>().ok())
-		.unwrap_or(10_000);
-	let persist_batch_size: usize = std::env::var("HMD_PERSIST_BATCH_SIZE")
+	{
+		persist_opts.channel_capacity = v;
+	}
+	if let Some(v) = std::env::var("HMD_PERSIST_BATCH_SIZE")
 		.ok()
 		.and_then(|s| s.parse::<usize>().ok())
-		.unwrap_or(100);
-	let persist_flush_ms: u64 = std::env::var("HMD_PERSIST_FLUSH_MS")
+	{
+		persist_opts.batch_size = v;
+	}
+	if let Some(v) = std::env::var("HMD_PERSIST_FLUSH_MS")
 		.ok()
 		.and_then(|s| s.parse::<u64>().ok())
-		.unwrap_or(1000);
+	{
+		persist_opts.flush_interval_ms = v;
+	}
+	if let Some(v) = std::env::var("HMD_PERSIST_FLUSH_CONCURRENCY")
+		.ok()
+		.and_then(|s| s.parse::<usize>().ok())
+		.filter(|c| *c > 0)
+	{
+		persist_opts.flush_concurrency = v;
+	}
+	// Optional recent-merge filter: enabled by setting a non-zero capacity.
+	if let Some(capacity) = std::env::var("HMD_PERSIST_DEDUP_CAPACITY")
+		.ok()
+		.and_then(|s| s.parse::<usize>().ok())
+		.filter(|c| *c > 0)
+	{
+		let mut dedup = crate::persist::DedupOptions {
+			capacity,
+			..Default::default()
+		};
+		if let Some(rate) = std::env::var("HMD_PERSIST_DEDUP_FP_RATE")
+			.ok()
+			.and_then(|s| s.parse::<f64>().ok())
+			.filter(|r| *r > 0.0 && *r < 1.0)
+		{
+			dedup.false_positive_rate = rate;
+		}
+		persist_opts.dedup = Some(dedup);
+	}
+	let dead_letter_queue = || {
+		Arc::new(match std::env::var("HMD_PERSIST_DEAD_LETTER_PATH") {
+			Ok(path) if !path.is_empty() => {
+				crate::persist::dead_letter::DeadLetterQueue::open(path, 1000)
+			}
+			_ => crate::persist::dead_letter::DeadLetterQueue::in_memory(1000),
+		})
+	};
+	// Optional cap on serialized props size: enabled by a non-zero limit.
+	if let Some(max_bytes) = std::env::var("HMD_PERSIST_MAX_PROPS_BYTES")
+		.ok()
+		.and_then(|s| s.parse::<usize>().ok())
+		.filter(|b| *b > 0)
+	{
+		let policy = std::env::var("HMD_PERSIST_OVERSIZED_PROPS")
+			.ok()
+			.and_then(|s| s.parse().ok())
+			.unwrap_or(crate::persist::OversizedProps::Reject);
+		persist_opts.props_limit = Some(crate::persist::PropsLimit { max_bytes, policy });
+		persist_opts.dead_letter = Some(dead_letter_queue());
+	}
+	// Optional caps on props nesting depth and array length: each enabled
+	// by a non-zero limit, sharing the oversized props policy.
+	let shape_limit = |var: &str| {
+		std::env::var(var)
+			.ok()
+			.and_then(|s| s.parse::<usize>().ok())
+			.filter(|n| *n > 0)
+	};
+	let max_depth = shape_limit("HMD_PERSIST_MAX_PROPS_DEPTH");
+	let max_array_len = shape_limit("HMD_PERSIST_MAX_ARRAY_LEN");
+	if max_depth.is_some() || max_array_len.is_some() {
+		let policy = std::env::var("HMD_PERSIST_OVERSIZED_PROPS")
+			.ok()
+			.and_then(|s| s.parse().ok())
+			.unwrap_or(crate::persist::OversizedProps::Reject);
+		persist_opts.props_shape = Some(crate::persist::PropsShapeLimit {
+			max_depth: max_depth.unwrap_or(usize::MAX),
+			max_array_len: max_array_len.unwrap_or(usize::MAX),
+			policy,
+		});
+		if persist_opts.dead_letter.is_none() {
+			persist_opts.dead_letter = Some(dead_letter_queue());
+		}
+	}
+	// Optional per-label property schemas, validated when settings load.
+	if let Ok(Some(schemas)) = settings.label_schemas() {
+		if schemas.policy == crate::persist::schema::SchemaViolation::Reject
+			&& persist_opts.dead_letter.is_none()
+		{
+			persist_opts.dead_letter = Some(dead_letter_queue());
+		}
+		persist_opts.schemas = Some(Arc::new(schemas));
+	}
+	// Shared with the graph sink, which checks the records it writes itself.
+	let key_collisions = settings
+		.key_collision_detection
+		.then(|| Arc::new(crate::persist::collisions::KeyCollisionDetector::new()));
+	persist_opts.collisions = key_collisions.clone();
+
+	// Optional dead-man's switch: pause ingest while persist writes keep
+	// failing, dead-lettering whatever the batcher still holds.
+	let persist_circuit = settings.persist_circuit().map(|opts| {
+		Arc::new(crate::persist::circuit::PersistCircuit::new(
+			opts,
+			metrics.ingest_paused.clone(),
+		))
+	});
+	if let Some(circuit) = &persist_circuit {
+		if persist_opts.dead_letter.is_none() {
+			persist_opts.dead_letter = Some(dead_letter_queue());
+		}
+		persist_opts.circuit = Some(circuit.clone());
+	}
 
-	let sender = crate::persist::start_batcher(
+	// Change log used for replication and NDJSON export/import. Keep it on
+	// disk when a path is configured so exports survive restarts.
+	let changelog = Arc::new(if settings.sync_changelog_path.is_empty() {
+		crate::sync::ChangeLog::in_memory()
+	} else {
+		match crate::sync::ChangeLog::open(&settings.sync_changelog_path) {
+			Ok(log) => log,
+			Err(e) => {
+				eprintln!("failed to open change log: {}", e);
+				return;
+			}
+		}
+	});
+	if settings.sync_record_ingest {
+		persist_opts.changes = Some(crate::sync::ChangeRecorder::new(
+			changelog.clone(),
+			settings.sync_node_id.clone(),
+		));
+	}
+
+	let sender = crate::persist::start_batcher_with_options(
 		repo.clone(),
-		obs_state.metrics.clone(),
-		persist_capacity,
-		persist_batch_size,
-		persist_flush_ms,
+		metrics.clone(),
+		persist_opts,
 	);
 
-	// Initialize PII policy engine if master key is configured
-	let pii_engine = if let Some(key_hex) = &settings.pii_master_key {
-		match crate::pii::pii_policy::PiiPolicyEngine::parse_master_key_hex(key_hex) {
-			Ok(key) => {
-				// Create a default policy config (can be extended to load from file)
-				let config = crate::pii::pii_policy::PiiPolicyConfig::default();
-				match crate::pii::pii_policy::PiiPolicyEngine::new(
-					config,
-					key,
-					"default-key-v1".to_string(),
-				) {
-					Ok(engine) => {
-						eprintln!("PII policy engine initialized");
-						Some(Arc::new(engine))
-					}
-					Err(e) => {
-						eprintln!("warning: failed to create PII engine: {}", e);
-						None
-					}
-				}
+	let audit = Arc::new(settings.audit_log());
+
+	// Initialize PII policy engine if master key is configured. An unusable
+	// key stops startup unless `pii_fail_closed` is turned off.
+	let pii_engine =
+		match crate::pii::pii_policy::PiiPolicyEngine::from_settings(&settings, audit.clone()) {
+			Ok(Some(engine)) => {
+				eprintln!("PII policy engine initialized");
+				Some(Arc::new(engine))
 			}
+			Ok(None) => None,
 			Err(e) => {
-				eprintln!("warning: failed to parse PII master key: {}", e);
-				None
+				eprintln!("{}; refusing to start", e);
+				return;
 			}
-		}
-	} else {
-		None
-	};
-
-	let app_state = crate::state::AppState {
-		repo: repo.clone(),
-		persist_sender: sender,
-		metrics: obs_state.metrics.clone(),
-	};
-	let app = app.with_state(app_state);
+		};
 
-	// Load TLS material
-	let certs = match tls_utils::load_certs(Path::new(&settings.tls_cert)) {
-		Ok(c) => c,
+	// Encrypted raw-payload store. Payloads are only ever written sealed, so
+	// the store cannot be enabled without a PII engine.
+	let raw_store = match crate::pii::raw_store::RawPayloadStore::from_settings(
+		&settings,
+		pii_engine.clone(),
+	) {
+		Ok(store) => store.map(Arc::new),
 		Err(e) => {
-			eprintln!("failed to load TLS certs ({}). Serving disabled.", e);
+			eprintln!(
+				"failed to open raw payload store: {:#}; refusing to start",
+				e
+			);
 			return;
 		}
 	};
-	let key = match tls_utils::load_private_key(Path::new(&settings.tls_key)) {
-		Ok(k) => k,
-		Err(e) => {
-			eprintln!("failed to load TLS private key ({}). Serving disabled.", e);
-			return;
+
+	// OIDC provider for protected routes. Without one, protected routes
+	// reject every request.
+	let oidc = if settings.oidc_discovery_url.is_empty() {
+		None
+	} else {
+		let mut provider = crate::sync::OidcProvider::new(
+			settings.oidc_discovery_url.clone(),
+			settings.oidc_client_id.clone(),
+			settings.oidc_client_secret.clone(),
+		)
+		.with_min_refresh_interval(Duration::from_secs(settings.oidc_jwks_min_refresh_secs))
+		.with_token_expiry_skew(Duration::from_secs(settings.oidc_token_expiry_skew_secs))
+		.with_fetch_timeout(Duration::from_secs(settings.oidc_fetch_timeout_secs))
+		.with_fetch_retries(
+			settings.oidc_fetch_retries,
+			Duration::from_millis(settings.oidc_fetch_backoff_ms),
+		);
+		if !settings.oidc_audiences.is_empty() {
+			provider = provider.with_audiences(settings.oidc_audiences.clone());
+		}
+		if !settings.oidc_issuers.is_empty() {
+			provider = provider.with_issuers(settings.oidc_issuers.clone());
+		}
+		let initialized = match provider.initialize().await {
+			Ok(()) => true,
+			Err(e) if settings.oidc_validate_later => {
+				eprintln!(
+					"warning: failed to initialize OIDC provider, retrying in the background: {:#}",
+					e
+				);
+				false
+			}
+			Err(e) => {
+				eprintln!(
+					"failed to initialize OIDC provider: {:#}; refusing to start",
+					e
+				);
+				return;
+			}
+		};
+		let provider = Arc::new(provider);
+		if !initialized {
+			provider.spawn_initialize_retry(Duration::from_secs(
+				settings.oidc_jwks_min_refresh_secs.max(1),
+			));
+		}
+		if settings.oidc_jwks_refresh_secs > 0 {
+			provider.spawn_jwks_refresh(Duration::from_secs(settings.oidc_jwks_refresh_secs));
 		}
+		Some(provider)
 	};
 
-	// Basic X.509 policy checks on the leaf certificate.
-	if certs.is_empty() {
-		eprintln!("no TLS certificates loaded; serving disabled");
-		return;
+	// Ping the DB in the background so ingest can answer 503 during an
+	// outage instead of failing every record.
+	let mut db_health = crate::health::DbHealth::new(settings.db_retry_after_secs);
+	if let Some(circuit) = persist_circuit {
+		db_health = db_health.with_persist_circuit(circuit);
+	}
+	let db_health = Arc::new(db_health);
+	if let Some(info) = age_info {
+		db_health.set_age_version(info.version);
+	}
+	if settings.db_health_interval_secs > 0 {
+		db_health.spawn_monitor(
+			repo.clone(),
+			Duration::from_secs(settings.db_health_interval_secs),
+		);
 	}
 
-	let leaf = &certs[0];
-	// Expiry check
-	match tls_utils::is_cert_expired(leaf) {
-		Ok(true) => {
-			eprintln!("TLS certificate appears to be expired; serving disabled");
-			return;
+	// Already validated by `config::load`.
+	let mut key_prefixes =
+		crate::ingest::KeyPrefixMap::parse(&settings.key_prefixes).unwrap_or_default();
+	if settings.normalize_cache_size > 0 {
+		key_prefixes = key_prefixes.with_cache(Arc::new(
+			crate::lib::normalizers::cache::NormalizationCache::new(settings.normalize_cache_size),
+		));
+	}
+	let mut app_state =
+		crate::state::AppState::new(repo.clone(), sender, metrics.clone())
+			.with_settings(Arc::new(settings.clone()))
+			.with_db_health(db_health)
+			.with_canonical_salt(&settings.canonical_salt)
+			.with_salt_rotation(salt_rotation)
+			.with_key_prefixes(key_prefixes)
+			.with_label_allowlist(settings.label_allowlist())
+			.with_changelog(changelog);
+	if let Some(engine) = pii_engine {
+		app_state = app_state.with_pii_engine(engine);
+	}
+	if let Some(provider) = oidc {
+		app_state = app_state.with_oidc(provider);
+	}
+	if let Some(store) = raw_store {
+		app_state = app_state.with_raw_store(store);
+	}
+	if let Some(detector) = key_collisions {
+		app_state = app_state.with_key_collisions(detector);
+	}
+	// Record sinks: the graph unless deselected, plus any message buses.
+	if !settings.record_sinks.iter().any(|s| s == "age") {
+		app_state = app_state.without_age_sink();
+	}
+	if settings.record_sinks.iter().any(|s| s == "nats") {
+		match crate::sink::NatsSink::connect(&settings.nats_url, settings.nats_subject.clone())
+			.await
+		{
+			Ok(sink) => app_state = app_state.with_sink(Arc::new(sink)),
+			Err(e) => {
+				eprintln!(
+					"failed to connect to NATS at {} ({}); serving disabled",
+					settings.nats_url, e
+				);
+				return;
+			}
 		}
-		Err(e) => {
+	}
+	if let Some(limits) = settings.ingest_quota() {
+		app_state = app_state.with_ingest_quota(limits);
+	}
+	if let Some(limits) = settings.upload_admission() {
+		app_state = app_state.with_upload_admission(limits);
+	}
+	app_state = app_state
+		.with_audit_log(audit.clone())
+		.with_lineage(Arc::new(settings.lineage_emitter()))
+		.with_parse_workers(settings.parse_workers);
+
+	// One rate limiter shared by every connection, so a reload can retune
+	// it. SIGHUP reloads the config and applies the reloadable settings.
+	let rate_limit = crate::devops::SharedRateLimitLayer::new(
+		settings.rate_limit_burst as usize,
+		settings.rate_limit_rps,
+	);
+	#[cfg(unix)]
+	{
+		let reloader = crate::config::reload::ConfigReloader::new(
+			settings.clone(),
+			&app_state,
+			rate_limit.clone(),
+		);
+		if let Err(e) = Arc::new(reloader).spawn_on_sighup() {
 			eprintln!(
-				"failed to evaluate TLS certificate expiry ({}); serving disabled",
+				"warning: cannot watch SIGHUP, config reload disabled: {}",
 				e
 			);
-			return;
 		}
-		_ => {}
 	}
 
-	// Hostname (CN / SAN) check against configured host
-	let host_to_check = settings.host.clone();
-	if !host_to_check.is_empty() {
-		let mut matched = false;
-		if let Ok(sans) = tls_utils::dns_names_from_cert(leaf) {
-			if sans.iter().any(|s| s == &host_to_check) {
-				matched = true;
-			}
-		}
-		if !matched {
-			if let Ok(Some(cn)) = tls_utils::first_common_name(leaf) {
-				if cn == host_to_check {
-					matched = true;
-				}
-			}
-		}
-		if !matched {
-			eprintln!(
-				"TLS certificate does not contain configured host '{}' in CN or SAN; serving disabled",
-				host_to_check
+	// Outermost first: identify the caller, then audit, then meter, then
+	// wait for an upload slot.
+	let admission =
+		axum::middleware::from_fn_with_state(app_state.clone(), crate::ingest::admission::admit);
+	let quota = axum::middleware::from_fn_with_state(
+		app_state.clone(),
+		crate::ingest::quota::enforce,
+	);
+	let audit_requests =
+		axum::middleware::from_fn_with_state(app_state.clone(), crate::audit::audit_requests);
+	let identify = axum::middleware::from_fn_with_state(app_state.clone(), crate::auth::identify);
+	// The upload sweeper leaves files that are still being processed alone.
+	let bulk_tasks = app_state.bulk_tasks.clone();
+	let app = app
+		.layer(admission)
+		.layer(quota)
+		.layer(audit_requests)
+		.layer(identify)
+		.with_state(app_state);
+
+	// Sweep bulk upload temp files left behind (kept raw, failed or never
+	// processed) once they exceed the configured age, and resumable upload
+	// sessions abandoned for longer than their TTL.
+	let upload_max_age = (settings.upload_max_age_secs > 0)
+		.then(|| Duration::from_secs(settings.upload_max_age_secs));
+	let session_ttl = (settings.upload_session_ttl_secs > 0)
+		.then(|| Duration::from_secs(settings.upload_session_ttl_secs));
+	if upload_max_age.is_some() || session_ttl.is_some() {
+		crate::ingest::uploads::spawn_upload_sweeper(
+			crate::ingest::uploads::upload_dir(&settings),
+			upload_max_age,
+			session_ttl,
+			Duration::from_secs(settings.upload_sweep_interval_secs.max(1)),
+			bulk_tasks,
+		);
+	}
+
+	// Expire nodes whose label TTL has elapsed since they were last seen.
+	match settings.retention_policy() {
+		Ok(policy) if !policy.is_empty() => {
+			crate::persist::retention::spawn_retention_sweeper(
+				repo.clone(),
+				metrics.clone(),
+				policy,
+				Duration::from_secs(settings.retention_sweep_interval_secs.max(1)),
+				audit.clone(),
 			);
-			return;
 		}
+		Ok(_) => {}
+		Err(e) => eprintln!("warning: invalid retention settings, not expiring nodes: {}", e),
 	}
 
-	let server_cfg = match tls_utils::build_server_config_tls13(certs, key) {
+	// Load TLS material and apply the leaf certificate policy checks.
+	let server_cfg = match tls_utils::load_server_config(
+		Path::new(&settings.tls_cert),
+		Path::new(&settings.tls_key),
+		&settings.host,
+		settings.tls_min_rsa_bits,
+	) {
 		Ok(cfg) => cfg,
 		Err(e) => {
-			eprintln!("failed to build TLS server config: {}", e);
+			eprintln!("{:#}; serving disabled", e);
 			return;
 		}
 	};
@@ -304,7 +737,7 @@ pub async fn run() {
 	};
 
 	// Bind TCP listener
-	let listener = match TcpListener::bind(bind_addr).await {
+	let listener = match crate::devops::bind_with_backlog(bind_addr, settings.listen_backlog) {
 		Ok(l) => l,
 		Err(e) => {
 			eprintln!("failed to bind {}: {}", bind_addr, e);
@@ -317,27 +750,37 @@ pub async fn run() {
 		bind_addr
 	);
 
-	// Accept loop: perform TLS handshake and spawn a per-connection task that
-	// serves requests using hyper's connection serving utilities.
-	loop {
-		let (tcp_stream, peer_addr) = match listener.accept().await {
-			Ok(t) => t,
-			Err(e) => {
-				eprintln!("accept error: {}", e);
-				tokio::time::sleep(Duration::from_millis(100)).await;
-				continue;
-			}
-		};
-
+	// Accept loop: perform TLS handshake and serve each connection on its
+	// own task using hyper's connection serving utilities. Connections
+	// beyond `max_connections` are closed on accept.
+	let max_connections = settings.max_connections;
+	let handshake_timeout = Duration::from_millis(settings.tls_handshake_timeout_ms);
+	let serve = move |tcp_stream: TcpStream, peer_addr: SocketAddr| {
 		let acceptor = acceptor.clone();
 		let app = app.clone();
 		let settings = settings.clone();
+		let rate_limit = rate_limit.clone();
 
-		tokio::spawn(async move {
+		async move {
 			let _ = tcp_stream.set_nodelay(true);
 
-			let tls_stream = match acceptor.accept(tcp_stream).await {
+			// Drop clients that stall the handshake; request bodies are
+			// bounded separately by the per-route body timeouts.
+			let tls_stream = match tls_utils::accept_with_timeout(
+				&acceptor,
+				tcp_stream,
+				handshake_timeout,
+			)
+			.await
+			{
 				Ok(s) => s,
+				Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {
+					eprintln!(
+						"TLS handshake timed out ({}); dropping connection",
+						peer_addr
+					);
+					return;
+				}
 				Err(e) => {
 					eprintln!("TLS handshake failed ({}): {}", peer_addr, e);
 					return;
@@ -368,8 +811,8 @@ pub async fn run() {
 				.concurrency_limit(100)
 				// Load-shed when the service is saturated instead of queueing.
 				.load_shed()
-				// Overall request timeout to avoid slowloris-like resource use.
-				.timeout(Duration::from_secs(30))
+				// Request and body timeouts, which bound slowloris-like
+				// resource use, are set per route group on the router.
 				// (Optional) a simple rate limit was intentionally omitted
 				// here because some in-process rate limiters carry internal
 				// non-Clone state which interferes with the per-connection
@@ -378,17 +821,19 @@ pub async fn run() {
 				// implementation if you need in-process rate limits.
 				// Limit request body sizes to avoid memory/CPU exhaustion.
 				.layer(RequestBodyLimitLayer::new(10 * 1024 * 1024)) // 10MiB
-				// Also enforce timeouts while reading the request body.
-				.layer(RequestBodyTimeoutLayer::new(Duration::from_secs(30)))
-				// Shared in-process rate limiter (Clone-friendly layer)
-				.layer(crate::devops::SharedRateLimitLayer::new(
-					settings.rate_limit_burst as usize,
-					settings.rate_limit_rps,
+				// Decode gzip/deflate/zstd bodies before the ingest parsers
+				// see them, capping the decoded size against zip bombs.
+				.layer(crate::ingest::decompression_layer(
+					settings.max_decompressed_bytes,
 				))
+				// Shared in-process rate limiter (Clone-friendly layer)
+				.layer(rate_limit)
 				// Mark sensitive headers on both requests and responses so
 				// logging and tracing will avoid printing them.
 				.layer(SetSensitiveRequestHeadersLayer::from_shared(req_headers.clone()))
 				.layer(SetSensitiveResponseHeadersLayer::from_shared(res_headers.clone()))
+				// Expose the peer address to handlers that trust localhost.
+				.layer(axum::Extension(axum::extract::ConnectInfo(peer_addr)))
 				.service(app.into_service());
 
 			// Convert tower/axum service into a hyper-compatible service
@@ -403,6 +848,50 @@ pub async fn run() {
 			if let Err(err) = conn.await {
 				eprintln!("connection error ({}): {}", peer_addr, err);
 			}
-		});
+		}
+	};
+
+	// Serve until asked to stop, then flush telemetry before exiting.
+	tokio::select! {
+		_ = crate::devops::accept_with_limit(listener, max_connections, serve) => {}
+		_ = shutdown_signal() => {
+			tracing::info!("shutdown signal received; no longer accepting connections");
+		}
+	}
+	match tokio::task::spawn_blocking(move || obs_state.shutdown()).await {
+		Ok(Ok(())) => {}
+		Ok(Err(e)) => eprintln!("warning: failed to shut down observability: {}", e),
+		Err(e) => eprintln!("warning: observability shutdown task failed: {}", e),
+	}
+}
+
+/// Resolve on Ctrl-C, or on SIGTERM where supported.
+async fn shutdown_signal() {
+	let ctrl_c = async {
+		if let Err(e) = tokio::signal::ctrl_c().await {
+			eprintln!("failed to listen for Ctrl-C: {}", e);
+			std::future::pending::<()>().await;
+		}
+	};
+
+	#[cfg(unix)]
+	let terminate = async {
+		use tokio::signal::unix::{SignalKind, signal};
+		match signal(SignalKind::terminate()) {
+			Ok(mut sigterm) => {
+				sigterm.recv().await;
+			}
+			Err(e) => {
+				eprintln!("failed to listen for SIGTERM: {}", e);
+				std::future::pending::<()>().await;
+			}
+		}
+	};
+	#[cfg(not(unix))]
+	let terminate = std::future::pending::<()>();
+
+	tokio::select! {
+		_ = ctrl_c => {}
+		_ = terminate => {}
 	}
 }
@@ -0,0 +1,225 @@
+//! Bounded LRU cache of normalization results.
+//!
+//! Feeds repeat the same values (top domains, common password hashes)
+//! constantly. [`NormalizationCache`] keeps the most recently used results
+//! of [`normalize`] keyed by `(kind, version, raw)`, so a hot value is
+//! normalized once and a normalizer version bump never serves a result of
+//! the previous algorithm. Only successful results are cached.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::{NormalizedValue, NormalizerError, normalize, normalizer_version};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+	kind: String,
+	version: u32,
+	raw: String,
+}
+
+#[derive(Debug, Default)]
+struct Lru {
+	entries: HashMap<CacheKey, (u64, NormalizedValue)>,
+	/// Last use tick → key; the first entry is the least recently used.
+	order: BTreeMap<u64, CacheKey>,
+	tick: u64,
+}
+
+impl Lru {
+	fn get(&mut self, key: &CacheKey) -> Option<NormalizedValue> {
+		self.tick += 1;
+		let tick = self.tick;
+		let (used, value) = self.entries.get_mut(key)?;
+		let key = self.order.remove(used).expect("cached key has a tick");
+		*used = tick;
+		self.order.insert(tick, key);
+		Some(value.clone())
+	}
+
+	fn insert(&mut self, key: CacheKey, value: NormalizedValue, capacity: usize) {
+		self.tick += 1;
+		if let Some((used, _)) = self.entries.remove(&key) {
+			self.order.remove(&used);
+		}
+		while self.entries.len() >= capacity {
+			match self.order.pop_first() {
+				Some((_, oldest)) => {
+					self.entries.remove(&oldest);
+				}
+				None => break,
+			}
+		}
+		self.order.insert(self.tick, key.clone());
+		self.entries.insert(key, (self.tick, value));
+	}
+}
+
+/// Memoizes [`normalize`] for up to `capacity` distinct values, evicting the
+/// least recently used. A capacity of 0 disables caching.
+#[derive(Debug)]
+pub struct NormalizationCache {
+	capacity: usize,
+	lru: Mutex<Lru>,
+	hits: AtomicU64,
+	misses: AtomicU64,
+}
+
+impl NormalizationCache {
+	pub fn new(capacity: usize) -> Self {
+		Self {
+			capacity,
+			lru: Mutex::new(Lru::default()),
+			hits: AtomicU64::new(0),
+			misses: AtomicU64::new(0),
+		}
+	}
+
+	pub fn capacity(&self) -> usize {
+		self.capacity
+	}
+
+	pub fn is_enabled(&self) -> bool {
+		self.capacity > 0
+	}
+
+	/// Cached [`normalize`]: the result for `raw` under the current
+	/// normalizer version of `kind_hint`.
+	pub fn normalize(
+		&self,
+		kind_hint: &str,
+		raw: &str,
+	) -> Result<NormalizedValue, NormalizerError> {
+		let kind = kind_hint.trim().to_ascii_lowercase();
+		match normalizer_version(&kind) {
+			Some(version) => self.get_or_insert_with(&kind, version, raw, || normalize(&kind, raw)),
+			None => Err(NormalizerError::UnsupportedKind(kind_hint.to_string())),
+		}
+	}
+
+	/// The cached result for `(kind, version, raw)`, or the result of
+	/// `normalize`, cached when it succeeds.
+	pub fn get_or_insert_with<F>(
+		&self,
+		kind: &str,
+		version: u32,
+		raw: &str,
+		normalize: F,
+	) -> Result<NormalizedValue, NormalizerError>
+	where
+		F: FnOnce() -> Result<NormalizedValue, NormalizerError>,
+	{
+		if !self.is_enabled() {
+			return normalize();
+		}
+		let key = CacheKey {
+			kind: kind.to_string(),
+			version,
+			raw: raw.to_string(),
+		};
+		if let Some(value) = self.lru.lock().unwrap().get(&key) {
+			self.hits.fetch_add(1, Ordering::Relaxed);
+			return Ok(value);
+		}
+		self.misses.fetch_add(1, Ordering::Relaxed);
+		// Normalize outside the lock; concurrent misses on one value both
+		// compute it and the later insert wins.
+		let value = normalize()?;
+		self.lru
+			.lock()
+			.unwrap()
+			.insert(key, value.clone(), self.capacity);
+		Ok(value)
+	}
+
+	/// Lookups answered from the cache.
+	pub fn hits(&self) -> u64 {
+		self.hits.load(Ordering::Relaxed)
+	}
+
+	/// Lookups that ran the normalizer.
+	pub fn misses(&self) -> u64 {
+		self.misses.load(Ordering::Relaxed)
+	}
+
+	/// Values currently cached.
+	pub fn len(&self) -> usize {
+		self.lru.lock().unwrap().entries.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+}
+
+#[cfg(test)]
+#[cfg(feature = "unit-tests")]
+mod tests {
+	use super::*;
+	use std::sync::atomic::AtomicUsize;
+
+	#[test]
+	fn repeated_values_hit_the_cache() {
+		let cache = NormalizationCache::new(16);
+		let hot = ["Example.COM", "google.com.", "Example.COM"];
+		for _ in 0..1_000 {
+			for raw in hot {
+				let value = cache.normalize("domain", raw).unwrap();
+				assert_eq!(value, normalize("domain", raw).unwrap());
+			}
+		}
+		// Each distinct raw value is normalized once.
+		assert_eq!(cache.misses(), 2);
+		assert_eq!(cache.hits(), 2_998);
+		assert_eq!(cache.len(), 2);
+
+		// Failures are not cached.
+		assert!(cache.normalize("ip", "not-an-ip").is_err());
+		assert!(cache.normalize("ip", "not-an-ip").is_err());
+		assert_eq!(cache.misses(), 4);
+		assert!(cache.normalize("phone", "555-0100").is_err());
+	}
+
+	#[test]
+	fn version_change_invalidates_cached_results() {
+		let cache = NormalizationCache::new(16);
+		let runs = AtomicUsize::new(0);
+		let run = |version: u32| {
+			cache
+				.get_or_insert_with("domain", version, "Example.COM", || {
+					runs.fetch_add(1, Ordering::SeqCst);
+					normalize("domain", "Example.COM")
+				})
+				.unwrap()
+		};
+
+		run(1);
+		run(1);
+		assert_eq!(runs.load(Ordering::SeqCst), 1);
+		run(2);
+		assert_eq!(runs.load(Ordering::SeqCst), 2);
+		assert_eq!((cache.hits(), cache.misses()), (1, 2));
+	}
+
+	#[test]
+	fn least_recently_used_value_is_evicted() {
+		let cache = NormalizationCache::new(2);
+		cache.normalize("ip", "10.0.0.1").unwrap();
+		cache.normalize("ip", "10.0.0.2").unwrap();
+		cache.normalize("ip", "10.0.0.1").unwrap();
+		cache.normalize("ip", "10.0.0.3").unwrap();
+		assert_eq!(cache.len(), 2);
+
+		let misses = cache.misses();
+		cache.normalize("ip", "10.0.0.1").unwrap();
+		assert_eq!(cache.misses(), misses);
+		cache.normalize("ip", "10.0.0.2").unwrap();
+		assert_eq!(cache.misses(), misses + 1);
+
+		let disabled = NormalizationCache::new(0);
+		disabled.normalize("ip", "10.0.0.1").unwrap();
+		assert!(disabled.is_empty());
+		assert_eq!((disabled.hits(), disabled.misses()), (0, 0));
+	}
+}
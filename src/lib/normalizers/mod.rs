@@ -1,4 +1,5 @@
-//! Canonicalizers for IP addresses, domain names, hashes, emails, and timestamps.
+//! Canonicalizers for IP addresses, domain names, hashes, emails, timestamps,
+//! amounts, usernames, and payment card numbers.
 //!
 //! This module provides deterministic normalization functions that produce stable
 //! canonical forms for common data types found in telemetry dumps. Canonical forms
@@ -17,13 +18,23 @@
 //! - Hash normalization: v1
 //! - Email normalization: v1
 //! - Timestamp normalization: v1
+//! - Amount normalization: v1
+//! - Username normalization: v1
+//! - PAN normalization: v1
+//! - Text normalization: v1
 //! - Canonical key generation: v1
+//!
+//! [`normalize`] dispatches on a kind hint; [`cache::NormalizationCache`]
+//! memoizes its results for values that repeat across ingests.
+
+pub mod cache;
 
-use std::net::IpAddr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::str::FromStr;
 
-use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
 use thiserror::Error;
+use unicode_normalization::UnicodeNormalization;
 
 /// Errors that can occur during normalization.
 #[derive(Debug, Error, PartialEq, Eq)]
@@ -40,6 +51,15 @@ pub enum NormalizerError {
 	InvalidTimestamp(String),
 	#[error("invalid CIDR notation: {0}")]
 	InvalidCidr(String),
+	#[error("invalid amount: {0}")]
+	InvalidAmount(String),
+	#[error("invalid username: {0}")]
+	InvalidUsername(String),
+	/// Never carries the rejected number itself.
+	#[error("invalid card number: {0}")]
+	InvalidPan(String),
+	#[error("no normalizer for kind: {0}")]
+	UnsupportedKind(String),
 }
 
 /// Normalized IP address with version tracking.
@@ -51,6 +71,76 @@ pub struct NormalizedIp {
 	pub version: u32,
 	/// Whether this is a CIDR range
 	pub is_cidr: bool,
+	/// RFC 1918 (IPv4) or unique local `fc00::/7` (IPv6) address
+	pub is_private: bool,
+	/// Loopback address (`127.0.0.0/8`, `::1`)
+	pub is_loopback: bool,
+	/// Link-local address (`169.254.0.0/16`, `fe80::/10`)
+	pub is_link_local: bool,
+	/// Other special-purpose address not routed on the internet:
+	/// unspecified, `0.0.0.0/8`, shared (`100.64.0.0/10`), documentation,
+	/// benchmarking, multicast, `240.0.0.0/4` and broadcast
+	pub is_reserved: bool,
+	/// None of the above: a publicly routable address
+	pub is_global: bool,
+}
+
+impl NormalizedIp {
+	/// Classify `addr` (a CIDR range by its network address).
+	fn new(addr: IpAddr, canonical: String, is_cidr: bool) -> Self {
+		// IPv4-mapped IPv6 addresses are classified as their IPv4 address.
+		let addr = match addr {
+			IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(addr, IpAddr::V4),
+			v4 => v4,
+		};
+		let (is_private, is_loopback, is_link_local, is_reserved) = match addr {
+			IpAddr::V4(v4) => (
+				v4.is_private(),
+				v4.is_loopback(),
+				v4.is_link_local(),
+				is_reserved_v4(v4),
+			),
+			IpAddr::V6(v6) => (
+				v6.segments()[0] & 0xfe00 == 0xfc00,
+				v6.is_loopback(),
+				v6.segments()[0] & 0xffc0 == 0xfe80,
+				is_reserved_v6(v6),
+			),
+		};
+		Self {
+			canonical,
+			version: 1,
+			is_cidr,
+			is_private,
+			is_loopback,
+			is_link_local,
+			is_reserved,
+			is_global: !(is_private || is_loopback || is_link_local || is_reserved),
+		}
+	}
+}
+
+fn is_reserved_v4(addr: Ipv4Addr) -> bool {
+	let [a, b, c, _] = addr.octets();
+	addr.is_unspecified()
+		|| addr.is_broadcast()
+		|| addr.is_documentation()
+		|| addr.is_multicast()
+		|| a == 0
+		|| (a == 100 && b & 0xc0 == 64)
+		|| (a == 192 && b == 0 && c == 0)
+		|| (a == 198 && b & 0xfe == 18)
+		|| a >= 240
+}
+
+fn is_reserved_v6(addr: Ipv6Addr) -> bool {
+	let segments = addr.segments();
+	addr.is_unspecified()
+		|| addr.is_multicast()
+		// Documentation: 2001:db8::/32
+		|| (segments[0] == 0x2001 && segments[1] == 0x0db8)
+		// Discard-only: 100::/64
+		|| (segments[0] == 0x0100 && segments[1..4] == [0, 0, 0])
 }
 
 /// Normalized domain name with version tracking.
@@ -65,8 +155,11 @@ pub struct NormalizedDomain {
 /// Normalized hash with version tracking.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct NormalizedHash {
-	/// Canonical string representation (lowercase hex)
+	/// Canonical string representation: the lowercase hex, or
+	/// `algorithm:hex` when [`NormalizeHashOptions::prefix_algorithm`] is set
 	pub canonical: String,
+	/// Lowercase hex digest without any algorithm label
+	pub hex: String,
 	/// Detected hash algorithm (e.g., "md5", "sha1", "sha256")
 	pub algorithm: String,
 	/// Normalization algorithm version
@@ -89,8 +182,244 @@ pub struct NormalizedTimestamp {
 	pub canonical: String,
 	/// Normalization algorithm version
 	pub version: u32,
+	/// UTC offset the input was written in (e.g. `+05:30`), when requested
+	/// and the input carried one
+	pub original_offset: Option<String>,
+	/// Whether the input was a bare integer read as Unix seconds, which any
+	/// other numeric field would also parse as
+	pub unix_epoch: bool,
 }
 
+/// Sub-second precision of canonical timestamps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimestampPrecision {
+	/// Whole seconds; fractions are truncated.
+	#[default]
+	Seconds,
+	/// Three fractional digits.
+	Millis,
+	/// Nine fractional digits.
+	Nanos,
+}
+
+impl TimestampPrecision {
+	fn seconds_format(self) -> SecondsFormat {
+		match self {
+			TimestampPrecision::Seconds => SecondsFormat::Secs,
+			TimestampPrecision::Millis => SecondsFormat::Millis,
+			TimestampPrecision::Nanos => SecondsFormat::Nanos,
+		}
+	}
+}
+
+/// Options for [`normalize_timestamp_with`]. The default matches
+/// [`normalize_timestamp`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NormalizeTimestampOptions {
+	/// Precision of the canonical form.
+	pub precision: TimestampPrecision,
+	/// Record the input's UTC offset in `original_offset`.
+	pub preserve_offset: bool,
+}
+
+/// Normalized numeric or currency amount with version tracking.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NormalizedAmount {
+	/// Canonical decimal representation (e.g. `-1234.5`): no grouping, `.`
+	/// as decimal separator, no leading or trailing zeros
+	pub canonical: String,
+	/// ISO 4217 code of the currency symbol or code in the input, if any
+	pub currency: Option<String>,
+	/// Normalization algorithm version
+	pub version: u32,
+}
+
+/// Normalized username or handle with version tracking.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NormalizedUsername {
+	/// Canonical string representation (NFKC, without invisible characters)
+	pub canonical: String,
+	/// Normalization algorithm version
+	pub version: u32,
+}
+
+/// Options for [`normalize_username`]. The default is what [`normalize`]
+/// applies to `username` values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NormalizeUsernameOptions {
+	/// Lowercase the username, for services that treat names
+	/// case-insensitively.
+	pub case_fold: bool,
+	/// Drop one leading `@`, so `@handle` and `handle` are the same value.
+	pub strip_at: bool,
+}
+
+impl Default for NormalizeUsernameOptions {
+	fn default() -> Self {
+		Self {
+			case_fold: true,
+			strip_at: true,
+		}
+	}
+}
+
+/// Generic text reduced to a canonical form with version tracking.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NormalizedText {
+	/// Canonical string representation
+	pub canonical: String,
+	/// Normalization algorithm version
+	pub version: u32,
+}
+
+/// Options for [`normalize_text`]. The default, every step enabled, is
+/// what ingest applies to values of kinds without a normalizer of their
+/// own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextNormalizeOptions {
+	/// NFKC-normalize, so compatibility forms such as full-width letters
+	/// and spaces match their plain equivalents.
+	pub nfkc: bool,
+	/// Replace each run of whitespace with one space and trim the ends.
+	pub collapse_whitespace: bool,
+	/// Lowercase the text.
+	pub case_fold: bool,
+	/// Remove control and zero-width characters, except whitespace.
+	pub strip_control: bool,
+}
+
+impl Default for TextNormalizeOptions {
+	fn default() -> Self {
+		Self {
+			nfkc: true,
+			collapse_whitespace: true,
+			case_fold: true,
+			strip_control: true,
+		}
+	}
+}
+
+impl TextNormalizeOptions {
+	/// Every step disabled: text is kept as given.
+	pub fn none() -> Self {
+		Self {
+			nfkc: false,
+			collapse_whitespace: false,
+			case_fold: false,
+			strip_control: false,
+		}
+	}
+}
+
+impl FromStr for TextNormalizeOptions {
+	type Err = String;
+
+	/// Parse a comma-separated list of the steps to enable: `nfkc`,
+	/// `collapse_whitespace`, `case_fold` and `strip_control`, or `none`.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let mut opts = Self::none();
+		for step in s.split(',').map(|p| p.trim().to_ascii_lowercase()) {
+			match step.as_str() {
+				"nfkc" => opts.nfkc = true,
+				"collapse_whitespace" => opts.collapse_whitespace = true,
+				"case_fold" => opts.case_fold = true,
+				"strip_control" => opts.strip_control = true,
+				"none" | "" => {}
+				other => return Err(format!("unknown text normalization step '{}'", other)),
+			}
+		}
+		Ok(opts)
+	}
+}
+
+/// Payment card number (PAN) reduced to a masked form with version
+/// tracking. The full number is never kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NormalizedPan {
+	/// Masked canonical representation, the leading and trailing digits
+	/// around `...` (e.g. `411111...1111`)
+	pub canonical: String,
+	/// Card network detected from the issuer identification number (e.g.
+	/// "visa", "mastercard", "amex"), or "unknown"
+	pub network: String,
+	/// Normalization algorithm version
+	pub version: u32,
+}
+
+/// Options for [`normalize_pan_with`]. The default, also the most PCI DSS
+/// allows to be displayed, is what [`normalize_pan`] and [`normalize`]
+/// apply to `pan` values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NormalizePanOptions {
+	/// Leading digits kept in the canonical form, at most 6.
+	pub keep_first: usize,
+	/// Trailing digits kept in the canonical form, at most 4.
+	pub keep_last: usize,
+}
+
+impl Default for NormalizePanOptions {
+	fn default() -> Self {
+		Self {
+			keep_first: PAN_MAX_KEEP_FIRST,
+			keep_last: PAN_MAX_KEEP_LAST,
+		}
+	}
+}
+
+/// Leading digits of a PAN that may be kept unmasked.
+pub const PAN_MAX_KEEP_FIRST: usize = 6;
+
+/// Trailing digits of a PAN that may be kept unmasked.
+pub const PAN_MAX_KEEP_LAST: usize = 4;
+
+/// Card networks by issuer identification number range, as inclusive
+/// ranges of the leading digits. Earlier entries win.
+const PAN_NETWORKS: &[(&str, u32, u32)] = &[
+	("amex", 34, 34),
+	("amex", 37, 37),
+	("diners", 300, 305),
+	("diners", 36, 36),
+	("diners", 38, 39),
+	("jcb", 3528, 3589),
+	("visa", 4, 4),
+	("mastercard", 51, 55),
+	("mastercard", 2221, 2720),
+	("discover", 6011, 6011),
+	("discover", 622126, 622925),
+	("discover", 644, 649),
+	("discover", 65, 65),
+	("unionpay", 62, 62),
+	("maestro", 50, 50),
+	("maestro", 56, 69),
+];
+
+/// Options for [`normalize_hash_with`]. The default is what
+/// [`normalize_hash`] and [`normalize`] apply to `hash` values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NormalizeHashOptions {
+	/// Make the canonical form `algorithm:hex`, so digests of equal length
+	/// from different algorithms (NTLM and MD5) stay distinct.
+	pub prefix_algorithm: bool,
+}
+
+/// Algorithm labels accepted ahead of a digest, with their hex lengths.
+const HASH_ALGORITHMS: &[(&str, usize)] = &[
+	("md5", 32),
+	("ntlm", 32),
+	("sha1", 40),
+	("sha256", 64),
+	("sha384", 96),
+	("sha512", 128),
+];
+
+/// Currency symbols recognized by [`normalize_amount`].
+const CURRENCY_SYMBOLS: &[(&str, &str)] = &[("$", "USD"), ("€", "EUR"), ("£", "GBP"), ("¥", "JPY")];
+
+/// Languages whose locales write amounts as `1.234,56`.
+const COMMA_DECIMAL_LANGUAGES: &[&str] = &[
+	"cs", "da", "de", "es", "fi", "fr", "id", "it", "nb", "nl", "no", "pl", "pt", "ru", "sv", "tr",
+];
+
 /// Canonical key with salt and version tracking.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CanonicalKey {
@@ -151,24 +480,95 @@ pub fn normalize_ip(input: &str) -> Result<NormalizedIp, NormalizerError> {
 			)));
 		}
 
-		Ok(NormalizedIp {
-			canonical: format!("{}/{}", addr, prefix),
-			version: 1,
-			is_cidr: true,
-		})
+		Ok(NormalizedIp::new(
+			addr,
+			format!("{}/{}", addr, prefix),
+			true,
+		))
 	} else {
 		// Parse as regular IP address
 		let addr =
 			IpAddr::from_str(input).map_err(|_| NormalizerError::InvalidIp(input.to_string()))?;
 
-		Ok(NormalizedIp {
-			canonical: addr.to_string(),
-			version: 1,
-			is_cidr: false,
-		})
+		Ok(NormalizedIp::new(addr, addr.to_string(), false))
 	}
 }
 
+/// Fixed-width lowercase hex of `addr`: 8 digits for IPv4, 32 for IPv6.
+/// Addresses of one version compare as strings in numeric order, so ingest
+/// stores this as `ip_hex` on ip nodes for range queries.
+pub fn ip_hex(addr: IpAddr) -> String {
+	match addr {
+		IpAddr::V4(v4) => format!("{:08x}", u32::from(v4)),
+		IpAddr::V6(v6) => format!("{:032x}", u128::from(v6)),
+	}
+}
+
+/// The address an [`ip_hex`] string encodes, or `None` if it is not one.
+pub fn ip_from_hex(hex: &str) -> Option<IpAddr> {
+	match hex.len() {
+		8 => u32::from_str_radix(hex, 16)
+			.ok()
+			.map(|v| IpAddr::V4(Ipv4Addr::from(v))),
+		32 => u128::from_str_radix(hex, 16)
+			.ok()
+			.map(|v| IpAddr::V6(Ipv6Addr::from(v))),
+		_ => None,
+	}
+}
+
+/// Addresses of one IP version between two [`ip_hex`] bounds, inclusive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IpRange {
+	/// 4 or 6
+	pub version: u8,
+	pub first: String,
+	pub last: String,
+}
+
+/// The addresses `cidr` covers. Host bits are ignored, so `10.1.2.3/8`
+/// covers `10.0.0.0/8`; a bare address covers only itself.
+///
+/// # Examples
+///
+/// ```
+/// use vanopticon_heimdall::lib::normalizers::cidr_range;
+///
+/// let range = cidr_range("10.0.0.0/8").unwrap();
+/// assert_eq!((range.first.as_str(), range.last.as_str()), ("0a000000", "0affffff"));
+/// ```
+pub fn cidr_range(cidr: &str) -> Result<IpRange, NormalizerError> {
+	let ip = normalize_ip(cidr)?;
+	let (addr, prefix) = match ip.canonical.split_once('/') {
+		Some((addr, prefix)) => (addr, prefix.parse::<u32>().ok()),
+		None => (ip.canonical.as_str(), None),
+	};
+	let addr =
+		IpAddr::from_str(addr).map_err(|_| NormalizerError::InvalidCidr(cidr.to_string()))?;
+	Ok(match addr {
+		IpAddr::V4(v4) => {
+			let mask = u32::MAX.checked_shl(32 - prefix.unwrap_or(32)).unwrap_or(0);
+			let first = u32::from(v4) & mask;
+			IpRange {
+				version: 4,
+				first: format!("{:08x}", first),
+				last: format!("{:08x}", first | !mask),
+			}
+		}
+		IpAddr::V6(v6) => {
+			let mask = u128::MAX
+				.checked_shl(128 - prefix.unwrap_or(128))
+				.unwrap_or(0);
+			let first = u128::from(v6) & mask;
+			IpRange {
+				version: 6,
+				first: format!("{:032x}", first),
+				last: format!("{:032x}", first | !mask),
+			}
+		}
+	})
+}
+
 /// Normalize a domain name to its canonical form.
 ///
 /// Applies lowercase transformation, IDNA encoding, and removes trailing dots.
@@ -218,7 +618,8 @@ pub fn normalize_domain(input: &str) -> Result<NormalizedDomain, NormalizerError
 ///
 /// Converts hex to lowercase and validates hash length to detect algorithm.
 /// Supports MD5 (32 hex chars), SHA-1 (40 hex chars), SHA-256 (64 hex chars),
-/// SHA-384 (96 hex chars), and SHA-512 (128 hex chars).
+/// SHA-384 (96 hex chars), and SHA-512 (128 hex chars). A leading label such
+/// as `ntlm:` names the algorithm instead; its length must still match.
 ///
 /// # Examples
 ///
@@ -233,7 +634,34 @@ pub fn normalize_domain(input: &str) -> Result<NormalizedDomain, NormalizerError
 /// assert_eq!(sha256.algorithm, "sha256");
 /// ```
 pub fn normalize_hash(input: &str) -> Result<NormalizedHash, NormalizerError> {
+	normalize_hash_with(input, NormalizeHashOptions::default())
+}
+
+/// Normalize a hash as [`normalize_hash`] does, with `opts` choosing the
+/// shape of the canonical form.
+///
+/// # Examples
+///
+/// ```
+/// use vanopticon_heimdall::lib::normalizers::{NormalizeHashOptions, normalize_hash_with};
+///
+/// let opts = NormalizeHashOptions { prefix_algorithm: true };
+/// let md5 = normalize_hash_with("D41D8CD98F00B204E9800998ECF8427E", opts).unwrap();
+/// assert_eq!(md5.canonical, "md5:d41d8cd98f00b204e9800998ecf8427e");
+/// assert_eq!(md5.hex, "d41d8cd98f00b204e9800998ecf8427e");
+///
+/// let ntlm = normalize_hash_with("NTLM:D41D8CD98F00B204E9800998ECF8427E", opts).unwrap();
+/// assert_eq!(ntlm.canonical, "ntlm:d41d8cd98f00b204e9800998ecf8427e");
+/// ```
+pub fn normalize_hash_with(
+	input: &str,
+	opts: NormalizeHashOptions,
+) -> Result<NormalizedHash, NormalizerError> {
 	let input = input.trim();
+	let (label, input) = match input.split_once(':') {
+		Some((label, digest)) => (Some(label.trim().to_lowercase()), digest.trim()),
+		None => (None, input),
+	};
 
 	// Validate that the string contains only hex characters
 	if !input.chars().all(|c| c.is_ascii_hexdigit()) {
@@ -244,10 +672,27 @@ pub fn normalize_hash(input: &str) -> Result<NormalizedHash, NormalizerError> {
 	}
 
 	// Convert to lowercase
-	let canonical = input.to_lowercase();
+	let hex = input.to_lowercase();
+
+	// A label names the algorithm; its expected length must match
+	if let Some(label) = label {
+		return match HASH_ALGORITHMS.iter().find(|(name, _)| *name == label) {
+			Some(&(name, len)) if len == hex.len() => Ok(hashed(name, hex, opts)),
+			Some(&(name, len)) => Err(NormalizerError::InvalidHash(format!(
+				"{} hash must be {} hex chars, got {}",
+				name,
+				len,
+				hex.len()
+			))),
+			None => Err(NormalizerError::InvalidHash(format!(
+				"unknown hash algorithm: {}",
+				label
+			))),
+		};
+	}
 
 	// Detect algorithm based on length
-	let algorithm = match canonical.len() {
+	let algorithm = match hex.len() {
 		32 => "md5",
 		40 => "sha1",
 		64 => "sha256",
@@ -256,16 +701,26 @@ pub fn normalize_hash(input: &str) -> Result<NormalizedHash, NormalizerError> {
 		_ => {
 			return Err(NormalizerError::InvalidHash(format!(
 				"unrecognized hash length: {} (expected 32/40/64/96/128)",
-				canonical.len()
+				hex.len()
 			)));
 		}
 	};
 
-	Ok(NormalizedHash {
+	Ok(hashed(algorithm, hex, opts))
+}
+
+fn hashed(algorithm: &str, hex: String, opts: NormalizeHashOptions) -> NormalizedHash {
+	let canonical = if opts.prefix_algorithm {
+		format!("{}:{}", algorithm, hex)
+	} else {
+		hex.clone()
+	};
+	NormalizedHash {
 		canonical,
+		hex,
 		algorithm: algorithm.to_string(),
 		version: 1,
-	})
+	}
 }
 
 /// Normalize an email address to its canonical form.
@@ -330,10 +785,207 @@ pub fn normalize_email(input: &str) -> Result<NormalizedEmail, NormalizerError>
 	})
 }
 
+/// Normalize a username or handle to its canonical form.
+///
+/// Control and zero-width characters (joiners, direction marks, byte order
+/// marks) are removed, the rest is NFKC-normalized so that compatibility
+/// forms such as full-width letters match their plain equivalents, and
+/// surrounding whitespace is trimmed. `opts` controls case folding and
+/// stripping a leading `@`. Nothing left is an error.
+///
+/// # Examples
+///
+/// ```
+/// use vanopticon_heimdall::lib::normalizers::{NormalizeUsernameOptions, normalize_username};
+///
+/// let handle = normalize_username(" @Alice ", NormalizeUsernameOptions::default()).unwrap();
+/// assert_eq!(handle.canonical, "alice");
+///
+/// let opts = NormalizeUsernameOptions { case_fold: false, strip_at: false };
+/// assert_eq!(normalize_username("@Alice", opts).unwrap().canonical, "@Alice");
+/// ```
+pub fn normalize_username(
+	input: &str,
+	opts: NormalizeUsernameOptions,
+) -> Result<NormalizedUsername, NormalizerError> {
+	let visible: String = input.chars().filter(|&c| !is_invisible(c)).collect();
+	let nfkc: String = visible.nfkc().collect();
+	let mut name = nfkc.trim();
+	if opts.strip_at {
+		name = name.strip_prefix('@').unwrap_or(name);
+	}
+	let canonical = if opts.case_fold {
+		name.to_lowercase()
+	} else {
+		name.to_string()
+	};
+
+	if canonical.is_empty() {
+		return Err(NormalizerError::InvalidUsername(format!(
+			"empty after normalization: {:?}",
+			input
+		)));
+	}
+
+	Ok(NormalizedUsername {
+		canonical,
+		version: 1,
+	})
+}
+
+/// Normalize free text so values differing only in whitespace, case or
+/// Unicode form share one canonical form. Each step runs only when enabled
+/// in `opts`: control and zero-width characters other than whitespace are
+/// removed, the text is NFKC-normalized, runs of whitespace become one
+/// space with the ends trimmed, and the result is lowercased.
+///
+/// # Examples
+///
+/// ```
+/// use vanopticon_heimdall::lib::normalizers::{TextNormalizeOptions, normalize_text};
+///
+/// let text = normalize_text("  Foo \t Bar ", TextNormalizeOptions::default());
+/// assert_eq!(text.canonical, "foo bar");
+/// ```
+pub fn normalize_text(input: &str, opts: TextNormalizeOptions) -> NormalizedText {
+	let mut text: String = if opts.strip_control {
+		input
+			.chars()
+			.filter(|&c| c.is_whitespace() || !is_invisible(c))
+			.collect()
+	} else {
+		input.to_string()
+	};
+	if opts.nfkc {
+		text = text.nfkc().collect();
+	}
+	if opts.collapse_whitespace {
+		text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+	}
+	if opts.case_fold {
+		text = text.to_lowercase();
+	}
+	NormalizedText {
+		canonical: text,
+		version: 1,
+	}
+}
+
+/// Control characters and invisible formatting characters that make two
+/// otherwise identical usernames differ.
+fn is_invisible(c: char) -> bool {
+	c.is_control()
+		|| matches!(
+			c,
+			'\u{00AD}'
+				| '\u{180E}'
+				| '\u{200B}'..='\u{200F}'
+				| '\u{202A}'..='\u{202E}'
+				| '\u{2060}'..='\u{2064}'
+				| '\u{2066}'..='\u{2069}'
+				| '\u{FEFF}'
+		)
+}
+
+/// Normalize a payment card number (PAN) to its masked canonical form.
+///
+/// Spaces and dashes between digits are ignored. The 13 to 19 digits left
+/// must pass the Luhn check. The canonical form keeps the first six and
+/// last four digits around `...`; the network is detected from the leading
+/// digits. Errors never include the input.
+///
+/// # Examples
+///
+/// ```
+/// use vanopticon_heimdall::lib::normalizers::normalize_pan;
+///
+/// let pan = normalize_pan("4111 1111 1111 1111").unwrap();
+/// assert_eq!(pan.canonical, "411111...1111");
+/// assert_eq!(pan.network, "visa");
+/// assert!(normalize_pan("4111 1111 1111 1112").is_err());
+/// ```
+pub fn normalize_pan(input: &str) -> Result<NormalizedPan, NormalizerError> {
+	normalize_pan_with(input, NormalizePanOptions::default())
+}
+
+/// Normalize a payment card number like [`normalize_pan`], keeping
+/// `opts.keep_first` and `opts.keep_last` digits (capped at
+/// [`PAN_MAX_KEEP_FIRST`] and [`PAN_MAX_KEEP_LAST`]) in the canonical form.
+pub fn normalize_pan_with(
+	input: &str,
+	opts: NormalizePanOptions,
+) -> Result<NormalizedPan, NormalizerError> {
+	let input = input.trim();
+	if input.is_empty()
+		|| !input
+			.chars()
+			.all(|c| c.is_ascii_digit() || c == ' ' || c == '-')
+	{
+		return Err(NormalizerError::InvalidPan(
+			"expected digits separated only by spaces or dashes".to_string(),
+		));
+	}
+	let digits: String = input.chars().filter(char::is_ascii_digit).collect();
+	if !(13..=19).contains(&digits.len()) {
+		return Err(NormalizerError::InvalidPan(format!(
+			"expected 13 to 19 digits, got {}",
+			digits.len()
+		)));
+	}
+	if !luhn_valid(&digits) {
+		return Err(NormalizerError::InvalidPan(
+			"fails the Luhn check".to_string(),
+		));
+	}
+
+	let first = opts.keep_first.min(PAN_MAX_KEEP_FIRST);
+	let last = opts.keep_last.min(PAN_MAX_KEEP_LAST);
+	Ok(NormalizedPan {
+		canonical: format!("{}...{}", &digits[..first], &digits[digits.len() - last..]),
+		network: pan_network(&digits).to_string(),
+		version: 1,
+	})
+}
+
+/// Card network whose issuer identification number range `digits` starts
+/// in, or "unknown".
+fn pan_network(digits: &str) -> &'static str {
+	PAN_NETWORKS
+		.iter()
+		.find(|(_, low, high)| {
+			let width = low.to_string().len();
+			digits
+				.get(..width)
+				.and_then(|prefix| prefix.parse::<u32>().ok())
+				.is_some_and(|prefix| (*low..=*high).contains(&prefix))
+		})
+		.map(|(network, _, _)| *network)
+		.unwrap_or("unknown")
+}
+
+/// Luhn checksum over the digits of `number`, ignoring separators.
+pub fn luhn_valid(number: &str) -> bool {
+	let digits: Vec<u32> = number.chars().filter_map(|c| c.to_digit(10)).collect();
+	let sum: u32 = digits
+		.iter()
+		.rev()
+		.enumerate()
+		.map(|(i, &d)| {
+			if i % 2 == 1 {
+				let doubled = d * 2;
+				if doubled > 9 { doubled - 9 } else { doubled }
+			} else {
+				d
+			}
+		})
+		.sum();
+	!digits.is_empty() && sum.is_multiple_of(10)
+}
+
 /// Normalize a timestamp to its canonical form (ISO-8601 UTC).
 ///
 /// Parses various timestamp formats and converts them to a canonical
-/// ISO-8601 representation in UTC.
+/// ISO-8601 representation in UTC at seconds precision.
 ///
 /// # Examples
 ///
@@ -347,24 +999,55 @@ pub fn normalize_email(input: &str) -> Result<NormalizedEmail, NormalizerError>
 /// assert_eq!(unix.canonical, "2024-01-15T11:30:00Z");
 /// ```
 pub fn normalize_timestamp(input: &str) -> Result<NormalizedTimestamp, NormalizerError> {
+	normalize_timestamp_with(input, NormalizeTimestampOptions::default())
+}
+
+/// Normalize a timestamp to UTC with the given precision, optionally
+/// recording the offset it was written in.
+///
+/// Only RFC 3339 inputs carry an offset; Unix and naive formats are taken
+/// as UTC and never report one.
+///
+/// # Examples
+///
+/// ```
+/// use vanopticon_heimdall::lib::normalizers::{
+///     NormalizeTimestampOptions, TimestampPrecision, normalize_timestamp_with,
+/// };
+///
+/// let opts = NormalizeTimestampOptions {
+///     precision: TimestampPrecision::Millis,
+///     preserve_offset: true,
+/// };
+/// let ts = normalize_timestamp_with("2024-01-15T16:00:00.123+05:30", opts).unwrap();
+/// assert_eq!(ts.canonical, "2024-01-15T10:30:00.123Z");
+/// assert_eq!(ts.original_offset.as_deref(), Some("+05:30"));
+/// ```
+pub fn normalize_timestamp_with(
+	input: &str,
+	opts: NormalizeTimestampOptions,
+) -> Result<NormalizedTimestamp, NormalizerError> {
 	let input = input.trim();
+	let format = opts.precision.seconds_format();
+	let normalized = |dt: DateTime<Utc>, offset: Option<String>| NormalizedTimestamp {
+		canonical: dt.to_rfc3339_opts(format, true),
+		version: 1,
+		original_offset: offset.filter(|_| opts.preserve_offset),
+		unix_epoch: false,
+	};
 
 	// Try to parse as RFC3339/ISO-8601 first
 	if let Ok(dt) = DateTime::parse_from_rfc3339(input) {
-		return Ok(NormalizedTimestamp {
-			canonical: dt
-				.with_timezone(&Utc)
-				.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
-			version: 1,
-		});
+		let offset = dt.offset().to_string();
+		return Ok(normalized(dt.with_timezone(&Utc), Some(offset)));
 	}
 
 	// Try to parse as Unix timestamp (seconds since epoch)
 	if let Ok(secs) = input.parse::<i64>() {
 		if let Some(dt) = DateTime::from_timestamp(secs, 0) {
 			return Ok(NormalizedTimestamp {
-				canonical: dt.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
-				version: 1,
+				unix_epoch: true,
+				..normalized(dt, None)
 			});
 		}
 	}
@@ -381,10 +1064,7 @@ pub fn normalize_timestamp(input: &str) -> Result<NormalizedTimestamp, Normalize
 	for format in &formats {
 		if let Ok(naive) = NaiveDateTime::parse_from_str(input, format) {
 			let dt = DateTime::<Utc>::from_naive_utc_and_offset(naive, Utc);
-			return Ok(NormalizedTimestamp {
-				canonical: dt.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
-				version: 1,
-			});
+			return Ok(normalized(dt, None));
 		}
 	}
 
@@ -394,6 +1074,285 @@ pub fn normalize_timestamp(input: &str) -> Result<NormalizedTimestamp, Normalize
 	)))
 }
 
+/// Normalize a numeric or currency amount to a canonical decimal string.
+///
+/// Accepts thousands separators (`,`, `.`, spaces, `'`), a leading or
+/// trailing `-`, accounting-style parentheses for negatives, and a currency
+/// symbol (`$`, `€`, `£`, `¥`) or three-letter code on either side.
+///
+/// `locale_hint` (e.g. `de-DE`, `en_US`) decides the decimal separator by
+/// its language subtag. Without a hint, the last of `.` and `,` is the
+/// decimal separator when both appear; a lone separator followed by exactly
+/// three digits is ambiguous and read the US way (`1,234` is 1234, `1.234`
+/// is 1.234).
+///
+/// # Examples
+///
+/// ```
+/// use vanopticon_heimdall::lib::normalizers::normalize_amount;
+///
+/// let us = normalize_amount("$1,234.56", None).unwrap();
+/// assert_eq!(us.canonical, "1234.56");
+/// assert_eq!(us.currency.as_deref(), Some("USD"));
+///
+/// let eu = normalize_amount("1.234,56 €", Some("de-DE")).unwrap();
+/// assert_eq!(eu.canonical, "1234.56");
+/// assert_eq!(eu.currency.as_deref(), Some("EUR"));
+///
+/// let negative = normalize_amount("(1,000.00)", None).unwrap();
+/// assert_eq!(negative.canonical, "-1000");
+/// ```
+pub fn normalize_amount(
+	input: &str,
+	locale_hint: Option<&str>,
+) -> Result<NormalizedAmount, NormalizerError> {
+	let invalid = || NormalizerError::InvalidAmount(input.to_string());
+	let mut rest = input.trim();
+	let mut negative = false;
+	if let Some(inner) = rest.strip_prefix('(').and_then(|r| r.strip_suffix(')')) {
+		negative = true;
+		rest = inner;
+	}
+
+	// Sign and currency may appear in either order on either side.
+	let mut currency = None;
+	loop {
+		rest = rest.trim();
+		if let Some(r) = rest.strip_prefix('-').or_else(|| rest.strip_suffix('-')) {
+			if negative {
+				return Err(invalid());
+			}
+			negative = true;
+			rest = r;
+		} else if let Some((r, code)) = strip_currency(rest) {
+			if currency.is_some() {
+				return Err(invalid());
+			}
+			currency = Some(code);
+			rest = r;
+		} else {
+			break;
+		}
+	}
+
+	// Spaces and apostrophes only ever group digits.
+	let digits: String = rest
+		.chars()
+		.filter(|c| !matches!(c, ' ' | '\'' | '\u{a0}' | '\u{202f}'))
+		.collect();
+	if !digits.chars().any(|c| c.is_ascii_digit())
+		|| !digits
+			.chars()
+			.all(|c| c.is_ascii_digit() || c == '.' || c == ',')
+	{
+		return Err(invalid());
+	}
+
+	let decimal = match locale_hint.and_then(decimal_separator) {
+		Some(sep) => sep,
+		None => guess_decimal_separator(&digits),
+	};
+	let group = if decimal == '.' { ',' } else { '.' };
+	let (int_part, frac_part) = digits.split_once(decimal).unwrap_or((&digits, ""));
+	if frac_part.contains([decimal, group]) {
+		return Err(invalid());
+	}
+	if int_part.contains(group) && int_part.split(group).any(str::is_empty) {
+		return Err(invalid());
+	}
+
+	let int_digits = int_part.replace(group, "");
+	let int = match int_digits.trim_start_matches('0') {
+		"" => "0",
+		int => int,
+	};
+	let frac = frac_part.trim_end_matches('0');
+	let mut canonical = if frac.is_empty() {
+		int.to_string()
+	} else {
+		format!("{}.{}", int, frac)
+	};
+	if negative && canonical != "0" {
+		canonical.insert(0, '-');
+	}
+
+	Ok(NormalizedAmount {
+		canonical,
+		currency,
+		version: 1,
+	})
+}
+
+/// Strip a currency symbol or three-letter code from either end of `s`,
+/// returning the rest and the ISO 4217 code.
+fn strip_currency(s: &str) -> Option<(&str, String)> {
+	for (symbol, code) in CURRENCY_SYMBOLS {
+		if let Some(rest) = s.strip_prefix(symbol).or_else(|| s.strip_suffix(symbol)) {
+			return Some((rest, code.to_string()));
+		}
+	}
+	let is_code = |code: &[u8]| code.iter().all(u8::is_ascii_alphabetic);
+	let bytes = s.as_bytes();
+	if bytes.len() > 3 && is_code(&bytes[..3]) && !bytes[3].is_ascii_alphabetic() {
+		return Some((&s[3..], s[..3].to_ascii_uppercase()));
+	}
+	let at = bytes.len().checked_sub(3)?;
+	if at > 0 && is_code(&bytes[at..]) && !bytes[at - 1].is_ascii_alphabetic() {
+		return Some((&s[..at], s[at..].to_ascii_uppercase()));
+	}
+	None
+}
+
+/// Decimal separator used by `locale`, judged by its language subtag.
+fn decimal_separator(locale: &str) -> Option<char> {
+	let language = locale.split(['-', '_']).next()?.trim().to_ascii_lowercase();
+	if language.is_empty() {
+		None
+	} else if COMMA_DECIMAL_LANGUAGES.contains(&language.as_str()) {
+		Some(',')
+	} else {
+		Some('.')
+	}
+}
+
+/// Decimal separator of `digits` when no locale is known.
+fn guess_decimal_separator(digits: &str) -> char {
+	match (digits.rfind('.'), digits.rfind(',')) {
+		(Some(dot), Some(comma)) => {
+			if dot > comma {
+				'.'
+			} else {
+				','
+			}
+		}
+		(Some(_), None) if digits.matches('.').count() > 1 => ',',
+		(None, Some(comma)) if digits.matches(',').count() == 1 && digits.len() - comma != 4 => ',',
+		_ => '.',
+	}
+}
+
+/// Confidence of a value that parsed exactly as its kind.
+pub const CONFIDENCE_EXACT: f32 = 1.0;
+
+/// Confidence of a value that only a heuristic matched, such as a bare
+/// integer read as a Unix timestamp.
+pub const CONFIDENCE_HEURISTIC: f32 = 0.6;
+
+/// Result of [`normalize`]: the typed output of the normalizer for a kind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NormalizedValue {
+	Ip(NormalizedIp),
+	Domain(NormalizedDomain),
+	Hash(NormalizedHash),
+	Email(NormalizedEmail),
+	Timestamp(NormalizedTimestamp),
+	Amount(NormalizedAmount),
+	Username(NormalizedUsername),
+	Pan(NormalizedPan),
+}
+
+impl NormalizedValue {
+	/// Canonical string representation.
+	pub fn canonical(&self) -> &str {
+		match self {
+			NormalizedValue::Ip(v) => &v.canonical,
+			NormalizedValue::Domain(v) => &v.canonical,
+			NormalizedValue::Hash(v) => &v.canonical,
+			NormalizedValue::Email(v) => &v.canonical,
+			NormalizedValue::Timestamp(v) => &v.canonical,
+			NormalizedValue::Amount(v) => &v.canonical,
+			NormalizedValue::Username(v) => &v.canonical,
+			NormalizedValue::Pan(v) => &v.canonical,
+		}
+	}
+
+	/// Name of the normalizer that produced this value, the `kind_hint`
+	/// [`normalize`] dispatches on.
+	pub fn kind(&self) -> &'static str {
+		match self {
+			NormalizedValue::Ip(_) => "ip",
+			NormalizedValue::Domain(_) => "domain",
+			NormalizedValue::Hash(_) => "hash",
+			NormalizedValue::Email(_) => "email",
+			NormalizedValue::Timestamp(_) => "timestamp",
+			NormalizedValue::Amount(_) => "amount",
+			NormalizedValue::Username(_) => "username",
+			NormalizedValue::Pan(_) => "pan",
+		}
+	}
+
+	/// How certain it is that the input really was of this kind, from
+	/// [`CONFIDENCE_HEURISTIC`] to [`CONFIDENCE_EXACT`].
+	pub fn confidence(&self) -> f32 {
+		match self {
+			NormalizedValue::Timestamp(v) if v.unix_epoch => CONFIDENCE_HEURISTIC,
+			_ => CONFIDENCE_EXACT,
+		}
+	}
+
+	/// Version of the algorithm that produced this value.
+	pub fn version(&self) -> u32 {
+		match self {
+			NormalizedValue::Ip(v) => v.version,
+			NormalizedValue::Domain(v) => v.version,
+			NormalizedValue::Hash(v) => v.version,
+			NormalizedValue::Email(v) => v.version,
+			NormalizedValue::Timestamp(v) => v.version,
+			NormalizedValue::Amount(v) => v.version,
+			NormalizedValue::Username(v) => v.version,
+			NormalizedValue::Pan(v) => v.version,
+		}
+	}
+}
+
+/// Kinds [`normalize`] has a normalizer for.
+pub const NORMALIZER_KINDS: [&str; 8] = [
+	"ip",
+	"domain",
+	"hash",
+	"email",
+	"timestamp",
+	"amount",
+	"username",
+	"pan",
+];
+
+/// Version of the normalizer [`normalize`] applies to `kind_hint`, or
+/// `None` when no normalizer handles that kind. Must match the `version`
+/// the normalizer writes into its output.
+pub fn normalizer_version(kind_hint: &str) -> Option<u32> {
+	let kind = kind_hint.trim().to_ascii_lowercase();
+	NORMALIZER_KINDS.contains(&kind.as_str()).then_some(1)
+}
+
+/// Normalize `raw` with the normalizer for `kind_hint`: `ip`, `domain`,
+/// `hash`, `email`, `timestamp`, `amount` (without a locale hint),
+/// `username` or `pan` (with the default options).
+///
+/// # Examples
+///
+/// ```
+/// use vanopticon_heimdall::lib::normalizers::normalize;
+///
+/// let domain = normalize("domain", "Example.COM.").unwrap();
+/// assert_eq!(domain.canonical(), "example.com");
+/// assert!(normalize("phone", "555-0100").is_err());
+/// ```
+pub fn normalize(kind_hint: &str, raw: &str) -> Result<NormalizedValue, NormalizerError> {
+	match kind_hint.trim().to_ascii_lowercase().as_str() {
+		"ip" => normalize_ip(raw).map(NormalizedValue::Ip),
+		"domain" => normalize_domain(raw).map(NormalizedValue::Domain),
+		"hash" => normalize_hash(raw).map(NormalizedValue::Hash),
+		"email" => normalize_email(raw).map(NormalizedValue::Email),
+		"timestamp" => normalize_timestamp(raw).map(NormalizedValue::Timestamp),
+		"amount" => normalize_amount(raw, None).map(NormalizedValue::Amount),
+		"username" => normalize_username(raw, NormalizeUsernameOptions::default())
+			.map(NormalizedValue::Username),
+		"pan" => normalize_pan(raw).map(NormalizedValue::Pan),
+		_ => Err(NormalizerError::UnsupportedKind(kind_hint.to_string())),
+	}
+}
+
 /// Generate a canonical key from a normalized value with salt and versioning.
 ///
 /// The canonical key is a hash of the concatenation of:
@@ -404,12 +1363,9 @@ pub fn normalize_timestamp(input: &str) -> Result<NormalizedTimestamp, Normalize
 /// This ensures that keys are stable for identical inputs but change when
 /// the normalization algorithm or salt changes.
 ///
-/// # Security Note
-///
-/// The current implementation uses `DefaultHasher` for deterministic hashing.
-/// This is NOT cryptographically secure and should be upgraded to SHA-256
-/// for production deployments to ensure collision resistance and prevent
-/// predictable key generation.
+/// The key is the hex SHA-256 digest of that input. Version 1 keys were
+/// derived with `DefaultHasher`; version 2 replaced it with SHA-256, so
+/// nodes keyed before the change no longer match newly derived keys.
 ///
 /// # Examples
 ///
@@ -417,31 +1373,38 @@ pub fn normalize_timestamp(input: &str) -> Result<NormalizedTimestamp, Normalize
 /// use vanopticon_heimdall::lib::normalizers::generate_canonical_key;
 ///
 /// let key = generate_canonical_key("192.168.1.1", "my-salt");
-/// assert_eq!(key.version, 1);
+/// assert_eq!(key.version, 2);
 /// assert_eq!(key.salt, "my-salt");
+/// assert_eq!(key.key.len(), 64);
 /// ```
 pub fn generate_canonical_key(normalized_value: &str, salt: &str) -> CanonicalKey {
-	use std::collections::hash_map::DefaultHasher;
-	use std::hash::{Hash, Hasher};
+	use sha2::{Digest, Sha256};
 
-	let version = 1u32;
+	let version = 2u32;
 	let input = format!("{}:v{}:{}", salt, version, normalized_value);
-
-	// TODO: Upgrade to cryptographic hash (SHA-256) for production
-	// DefaultHasher is not cryptographically secure and may be vulnerable
-	// to collision attacks or predictable output. This is acceptable for
-	// initial development but MUST be replaced before production use.
-	let mut hasher = DefaultHasher::new();
-	input.hash(&mut hasher);
-	let hash_value = hasher.finish();
+	let digest = Sha256::digest(input.as_bytes());
 
 	CanonicalKey {
-		key: format!("{:016x}", hash_value),
+		key: digest.iter().map(|b| format!("{:02x}", b)).collect(),
 		salt: salt.to_string(),
 		version,
 	}
 }
 
+/// Graph key for `normalized_value`: the salted canonical key hash, or the
+/// value itself when `salt` is empty.
+///
+/// Ingest and sync must derive keys through this function with the same
+/// salt so that a value maps to the same node everywhere. Changing the salt
+/// invalidates every key derived with the previous one.
+pub fn salted_key(normalized_value: &str, salt: &str) -> String {
+	if salt.is_empty() {
+		normalized_value.to_string()
+	} else {
+		generate_canonical_key(normalized_value, salt).key
+	}
+}
+
 #[cfg(test)]
 #[cfg(feature = "unit-tests")]
 mod tests {
@@ -475,6 +1438,46 @@ mod tests {
 		assert_eq!(result.canonical, "::1");
 	}
 
+	#[test]
+	fn ip_hex_sorts_numerically_within_a_version() {
+		let hex = |s: &str| ip_hex(IpAddr::from_str(s).unwrap());
+		assert_eq!(hex("10.0.0.1"), "0a000001");
+		assert!(hex("9.255.255.255") < hex("10.0.0.0"));
+		assert_eq!(hex("2001:db8::1"), "20010db8000000000000000000000001");
+		assert_eq!(hex("::").len(), 32);
+		for s in ["10.0.0.1", "2001:db8::1", "::ffff:192.0.2.1"] {
+			assert_eq!(ip_from_hex(&hex(s)), Some(IpAddr::from_str(s).unwrap()));
+		}
+		assert_eq!(ip_from_hex("0a0001"), None);
+		assert_eq!(ip_from_hex("0a00000g"), None);
+	}
+
+	#[test]
+	fn cidr_range_covers_the_network() {
+		let range = cidr_range("10.1.2.3/8").unwrap();
+		assert_eq!(range.version, 4);
+		assert_eq!(
+			(range.first.as_str(), range.last.as_str()),
+			("0a000000", "0affffff")
+		);
+
+		let all = cidr_range("0.0.0.0/0").unwrap();
+		assert_eq!(
+			(all.first.as_str(), all.last.as_str()),
+			("00000000", "ffffffff")
+		);
+		let one = cidr_range("192.0.2.7").unwrap();
+		assert_eq!(one.first, one.last);
+
+		let v6 = cidr_range("2001:db8::/32").unwrap();
+		assert_eq!(v6.version, 6);
+		assert_eq!(v6.first, "20010db8000000000000000000000000");
+		assert_eq!(v6.last, "20010db8ffffffffffffffffffffffff");
+
+		assert!(cidr_range("10.0.0.0/33").is_err());
+		assert!(cidr_range("not-a-cidr").is_err());
+	}
+
 	#[test]
 	fn test_normalize_ipv4_cidr() {
 		let result = normalize_ip("10.0.0.0/8").unwrap();
@@ -501,6 +1504,34 @@ mod tests {
 		assert!(result.is_err());
 	}
 
+	#[test]
+	fn test_normalize_ip_classifies_scope() {
+		let scope = |input: &str| {
+			let ip = normalize_ip(input).unwrap();
+			(
+				ip.is_private,
+				ip.is_loopback,
+				ip.is_link_local,
+				ip.is_reserved,
+				ip.is_global,
+			)
+		};
+		assert_eq!(scope("10.0.0.1"), (true, false, false, false, false));
+		assert_eq!(scope("192.168.0.0/16"), (true, false, false, false, false));
+		assert_eq!(scope("127.0.0.1"), (false, true, false, false, false));
+		assert_eq!(scope("169.254.0.1"), (false, false, true, false, false));
+		assert_eq!(scope("100.64.0.1"), (false, false, false, true, false));
+		assert_eq!(scope("192.0.2.1"), (false, false, false, true, false));
+		assert_eq!(scope("8.8.8.8"), (false, false, false, false, true));
+
+		assert_eq!(scope("::1"), (false, true, false, false, false));
+		assert_eq!(scope("fd00::1"), (true, false, false, false, false));
+		assert_eq!(scope("fe80::1"), (false, false, true, false, false));
+		assert_eq!(scope("2001:db8::1"), (false, false, false, true, false));
+		assert_eq!(scope("::ffff:10.0.0.1"), (true, false, false, false, false));
+		assert_eq!(scope("2606:4700::1111"), (false, false, false, false, true));
+	}
+
 	// Domain normalization tests
 	#[test]
 	fn test_normalize_domain_lowercase() {
@@ -573,6 +1604,32 @@ mod tests {
 		assert!(result.is_err());
 	}
 
+	#[test]
+	fn test_normalize_hash_prefix_separates_same_length_algorithms() {
+		let opts = NormalizeHashOptions {
+			prefix_algorithm: true,
+		};
+		let digest = "8846F7EAEE8FB117AD06BDD830B7586C";
+		let md5 = normalize_hash_with(digest, opts).unwrap();
+		let ntlm = normalize_hash_with(&format!("ntlm:{}", digest), opts).unwrap();
+		assert_eq!(md5.canonical, "md5:8846f7eaee8fb117ad06bdd830b7586c");
+		assert_eq!(ntlm.canonical, "ntlm:8846f7eaee8fb117ad06bdd830b7586c");
+		assert_ne!(md5.canonical, ntlm.canonical);
+		assert_eq!(md5.hex, ntlm.hex);
+
+		// Without prefixing the canonical form stays the bare hex
+		let bare = normalize_hash(&format!("NTLM:{}", digest)).unwrap();
+		assert_eq!(bare.canonical, "8846f7eaee8fb117ad06bdd830b7586c");
+		assert_eq!(bare.algorithm, "ntlm");
+	}
+
+	#[test]
+	fn test_normalize_hash_rejects_bad_labels() {
+		assert!(normalize_hash("sha256:d41d8cd98f00b204e9800998ecf8427e").is_err());
+		assert!(normalize_hash("crc32:d41d8cd98f00b204e9800998ecf8427e").is_err());
+		assert!(normalize_hash("ntlm:not-hex").is_err());
+	}
+
 	// Email normalization tests
 	#[test]
 	fn test_normalize_email_basic() {
@@ -623,6 +1680,13 @@ mod tests {
 	fn test_normalize_timestamp_unix() {
 		let result = normalize_timestamp("1705318200").unwrap();
 		assert_eq!(result.canonical, "2024-01-15T11:30:00Z");
+		assert!(result.unix_epoch);
+
+		// Any integer reads as Unix seconds, so it is only a heuristic match.
+		let value = normalize("timestamp", "1705318200").unwrap();
+		assert_eq!(value.confidence(), CONFIDENCE_HEURISTIC);
+		let value = normalize("timestamp", "2024-01-15T11:30:00Z").unwrap();
+		assert_eq!(value.confidence(), CONFIDENCE_EXACT);
 	}
 
 	#[test]
@@ -631,19 +1695,165 @@ mod tests {
 		assert_eq!(result.canonical, "2024-01-15T10:30:00Z");
 	}
 
+	#[test]
+	fn test_normalize_timestamp_millis_with_offset() {
+		let opts = NormalizeTimestampOptions {
+			precision: TimestampPrecision::Millis,
+			preserve_offset: true,
+		};
+		let result = normalize_timestamp_with("2024-01-15T16:00:00.123+05:30", opts).unwrap();
+		assert_eq!(result.canonical, "2024-01-15T10:30:00.123Z");
+		assert_eq!(result.original_offset.as_deref(), Some("+05:30"));
+
+		let nanos = NormalizeTimestampOptions {
+			precision: TimestampPrecision::Nanos,
+			preserve_offset: false,
+		};
+		let result = normalize_timestamp_with("2024-01-15T16:00:00.123+05:30", nanos).unwrap();
+		assert_eq!(result.canonical, "2024-01-15T10:30:00.123000000Z");
+		assert_eq!(result.original_offset, None);
+
+		// Inputs without an offset never report one.
+		let result = normalize_timestamp_with("1705318200", opts).unwrap();
+		assert_eq!(result.canonical, "2024-01-15T11:30:00.000Z");
+		assert_eq!(result.original_offset, None);
+	}
+
+	#[test]
+	fn test_normalize_timestamp_default_is_unchanged() {
+		let result = normalize_timestamp("2024-01-15T16:00:00.123+05:30").unwrap();
+		assert_eq!(result.canonical, "2024-01-15T10:30:00Z");
+		assert_eq!(result.original_offset, None);
+		assert_eq!(
+			result,
+			normalize_timestamp_with(
+				"2024-01-15T16:00:00.123+05:30",
+				NormalizeTimestampOptions::default()
+			)
+			.unwrap()
+		);
+	}
+
 	#[test]
 	fn test_normalize_timestamp_invalid() {
 		let result = normalize_timestamp("not-a-timestamp");
 		assert!(result.is_err());
 	}
 
+	// Amount normalization tests
+	#[test]
+	fn test_normalize_amount_grouping() {
+		for (input, hint) in [
+			("1,234.56", None),
+			("1.234,56", None),
+			("1,234.56", Some("en-US")),
+			("1.234,56", Some("de_DE")),
+			("1 234,56", Some("fr")),
+			("1'234.56", None),
+		] {
+			let result = normalize_amount(input, hint).unwrap();
+			assert_eq!(result.canonical, "1234.56", "{} {:?}", input, hint);
+			assert_eq!(result.currency, None);
+			assert_eq!(result.version, 1);
+		}
+
+		assert_eq!(
+			normalize_amount("1,234,567", None).unwrap().canonical,
+			"1234567"
+		);
+		assert_eq!(
+			normalize_amount("1.234.567", None).unwrap().canonical,
+			"1234567"
+		);
+		// A lone separator before three digits is ambiguous without a hint.
+		assert_eq!(normalize_amount("1,234", None).unwrap().canonical, "1234");
+		assert_eq!(normalize_amount("1.234", None).unwrap().canonical, "1.234");
+		assert_eq!(
+			normalize_amount("1.234", Some("de")).unwrap().canonical,
+			"1234"
+		);
+		assert_eq!(normalize_amount("12,5", None).unwrap().canonical, "12.5");
+		assert_eq!(normalize_amount("007.50", None).unwrap().canonical, "7.5");
+	}
+
+	#[test]
+	fn test_normalize_amount_currency() {
+		let cases = [
+			("$1,234.56", "1234.56", "USD"),
+			("€1.234,56", "1234.56", "EUR"),
+			("1.234,56 €", "1234.56", "EUR"),
+			("USD 1,234.56", "1234.56", "USD"),
+			("1234.56 usd", "1234.56", "USD"),
+			("£10", "10", "GBP"),
+		];
+		for (input, canonical, currency) in cases {
+			let result = normalize_amount(input, None).unwrap();
+			assert_eq!(result.canonical, canonical, "{}", input);
+			assert_eq!(result.currency.as_deref(), Some(currency), "{}", input);
+		}
+	}
+
+	#[test]
+	fn test_normalize_amount_negatives() {
+		for input in [
+			"-1,234.56",
+			"1,234.56-",
+			"$-1,234.56",
+			"-$1,234.56",
+			"($1,234.56)",
+			"(1,234.56)",
+		] {
+			let result = normalize_amount(input, None).unwrap();
+			assert_eq!(result.canonical, "-1234.56", "{}", input);
+		}
+		assert_eq!(normalize_amount("(0.00)", None).unwrap().canonical, "0");
+	}
+
+	#[test]
+	fn test_normalize_amount_invalid() {
+		for input in [
+			"",
+			"$",
+			"abc",
+			"1,2,,3",
+			"1.234,56.7",
+			"--5",
+			"(-5)",
+			"$5€",
+			"12a",
+		] {
+			assert!(
+				matches!(
+					normalize_amount(input, None),
+					Err(NormalizerError::InvalidAmount(_))
+				),
+				"{}",
+				input
+			);
+		}
+	}
+
 	// Canonical key generation tests
+	#[test]
+	fn test_salted_key() {
+		assert_eq!(salted_key("example.com", ""), "example.com");
+		assert_eq!(
+			salted_key("example.com", "s1"),
+			generate_canonical_key("example.com", "s1").key
+		);
+		assert_ne!(
+			salted_key("example.com", "s1"),
+			salted_key("example.com", "s2")
+		);
+	}
+
 	#[test]
 	fn test_generate_canonical_key() {
 		let key1 = generate_canonical_key("192.168.1.1", "salt1");
-		assert_eq!(key1.version, 1);
+		assert_eq!(key1.version, 2);
 		assert_eq!(key1.salt, "salt1");
-		assert!(!key1.key.is_empty());
+		assert_eq!(key1.key.len(), 64);
+		assert!(key1.key.chars().all(|c| c.is_ascii_hexdigit()));
 
 		// Same input should produce same key
 		let key2 = generate_canonical_key("192.168.1.1", "salt1");
@@ -658,6 +1868,120 @@ mod tests {
 		assert_ne!(key1.key, key4.key);
 	}
 
+	#[test]
+	fn test_normalize_username() {
+		let opts = NormalizeUsernameOptions::default();
+		assert_eq!(
+			normalize_username("@Handle", opts).unwrap().canonical,
+			"handle"
+		);
+		assert_eq!(
+			normalize_username(
+				"@Handle",
+				NormalizeUsernameOptions {
+					strip_at: false,
+					..opts
+				}
+			)
+			.unwrap()
+			.canonical,
+			"@handle"
+		);
+		assert_eq!(
+			normalize_username(
+				"@Handle",
+				NormalizeUsernameOptions {
+					case_fold: false,
+					..opts
+				}
+			)
+			.unwrap()
+			.canonical,
+			"Handle"
+		);
+
+		// Zero-width joiners and other invisible characters are removed.
+		assert_eq!(
+			normalize_username("ad\u{200D}min\u{200B}\u{FEFF}", opts)
+				.unwrap()
+				.canonical,
+			"admin"
+		);
+
+		// Full-width compatibility forms fold to ASCII.
+		assert_eq!(
+			normalize_username("\u{FF20}\u{FF2A}\u{FF4F}\u{FF45}\u{FF11}", opts)
+				.unwrap()
+				.canonical,
+			"joe1"
+		);
+
+		for empty in ["", "@", " \u{200D} ", "\u{0007}"] {
+			assert!(matches!(
+				normalize_username(empty, opts),
+				Err(NormalizerError::InvalidUsername(_))
+			));
+		}
+	}
+
+	#[test]
+	fn test_normalize_pan_masks_known_networks() {
+		for (input, canonical, network) in [
+			("4111 1111 1111 1111", "411111...1111", "visa"),
+			("5555-5555-5555-4444", "555555...4444", "mastercard"),
+			("2223000048400011", "222300...0011", "mastercard"),
+			("3782 822463 10005", "378282...0005", "amex"),
+			("6011111111111117", "601111...1117", "discover"),
+			("3530111333300000", "353011...0000", "jcb"),
+		] {
+			let pan = normalize_pan(input).unwrap();
+			assert_eq!(pan.canonical, canonical, "{}", input);
+			assert_eq!(pan.network, network, "{}", input);
+			assert_eq!(pan.version, 1);
+		}
+
+		let opts = NormalizePanOptions {
+			keep_first: 0,
+			keep_last: 9,
+		};
+		assert_eq!(
+			normalize_pan_with("4111111111111111", opts)
+				.unwrap()
+				.canonical,
+			"...1111"
+		);
+	}
+
+	#[test]
+	fn test_normalize_pan_rejects_invalid_numbers() {
+		for input in [
+			"4111 1111 1111 1112",
+			"411111111111",
+			"41111111111111111111",
+			"4111.1111.1111.1111",
+			"",
+		] {
+			assert!(
+				matches!(normalize_pan(input), Err(NormalizerError::InvalidPan(_))),
+				"{}",
+				input
+			);
+		}
+	}
+
+	#[test]
+	fn test_normalize_pan_never_outputs_the_full_number() {
+		let full = "378282246310005";
+		let pan = normalize_pan("3782-822463-10005").unwrap();
+		assert!(!format!("{:?}", pan).contains(full));
+		assert!(!format!("{:?}", normalize("pan", full).unwrap()).contains(full));
+
+		let invalid = "378282246310006";
+		let err = normalize_pan(invalid).unwrap_err();
+		assert!(!err.to_string().contains(invalid));
+		assert!(!format!("{:?}", err).contains(invalid));
+	}
+
 	#[test]
 	fn test_canonical_key_deterministic() {
 		// Keys should be deterministic across multiple calls
@@ -669,4 +1993,82 @@ mod tests {
 			assert_eq!(keys[0].key, key.key);
 		}
 	}
+
+	#[test]
+	fn test_normalize_dispatches_by_kind() {
+		let ip = normalize("IP", "2001:0db8::0001").unwrap();
+		assert_eq!(
+			ip,
+			NormalizedValue::Ip(normalize_ip("2001:db8::1").unwrap())
+		);
+		let hash = normalize("hash", "D41D8CD98F00B204E9800998ECF8427E").unwrap();
+		assert_eq!(hash.canonical(), "d41d8cd98f00b204e9800998ecf8427e");
+
+		for (kind, raw) in [
+			("ip", "10.0.0.1"),
+			("domain", "example.com"),
+			("hash", "d41d8cd98f00b204e9800998ecf8427e"),
+			("email", "user@example.com"),
+			("timestamp", "2024-01-01T00:00:00Z"),
+			("amount", "1.5"),
+			("username", "alice"),
+			("pan", "4111111111111111"),
+		] {
+			let value = normalize(kind, raw).unwrap();
+			assert_eq!(Some(value.version()), normalizer_version(kind), "{}", kind);
+			assert_eq!(value.kind(), kind);
+			assert_eq!(value.confidence(), CONFIDENCE_EXACT, "{}", kind);
+		}
+
+		assert_eq!(
+			normalize("phone", "555-0100"),
+			Err(NormalizerError::UnsupportedKind("phone".to_string()))
+		);
+		assert_eq!(normalizer_version("phone"), None);
+	}
+
+	#[test]
+	fn test_normalize_text_folds_whitespace_case_and_width() {
+		let opts = TextNormalizeOptions::default();
+		let variants = [
+			"  Foo   Bar ",
+			"foo bar",
+			"\u{FF26}\u{FF4F}\u{FF4F}\u{3000}\u{FF22}\u{FF41}\u{FF52}",
+		];
+		for raw in variants {
+			assert_eq!(normalize_text(raw, opts).canonical, "foo bar", "{:?}", raw);
+		}
+		assert_eq!(
+			normalize_text("foo\u{200B}\tbar\u{0007}", opts).canonical,
+			"foo bar"
+		);
+		assert_eq!(normalize_text("foo bar", opts).version, 1);
+
+		// Without case folding the variants stay apart.
+		let unfolded = TextNormalizeOptions {
+			case_fold: false,
+			..opts
+		};
+		let canonical: Vec<String> = variants
+			.iter()
+			.map(|raw| normalize_text(raw, unfolded).canonical)
+			.collect();
+		assert_eq!(canonical, ["Foo Bar", "foo bar", "Foo Bar"]);
+		assert_ne!(canonical[0], canonical[1]);
+
+		assert_eq!(
+			normalize_text(" A  b ", TextNormalizeOptions::none()).canonical,
+			" A  b "
+		);
+		assert_eq!(
+			"nfkc, Case_Fold".parse(),
+			Ok(TextNormalizeOptions {
+				nfkc: true,
+				case_fold: true,
+				..TextNormalizeOptions::none()
+			})
+		);
+		assert_eq!("none".parse(), Ok(TextNormalizeOptions::none()));
+		assert!("soundex".parse::<TextNormalizeOptions>().is_err());
+	}
 }
@@ -0,0 +1,302 @@
+//! Lineage events for data governance.
+//!
+//! Each dump ingest and each enrichment step emits a [`LineageEvent`] in
+//! the OpenLineage run event shape: the job that ran, a run id, the input
+//! datasets it read and the output datasets it produced. A dump is the
+//! dataset named by its `dump_id`; graph nodes are datasets named by their
+//! key. Events go to the configured [`LineageEmitter`]: an append-only
+//! JSONL file, an HTTP endpoint accepting OpenLineage events, or nowhere
+//! (the default).
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Sink names accepted in `Settings.lineage_sink`.
+pub const LINEAGE_SINKS: &[&str] = &["off", "jsonl", "http"];
+
+/// Namespace of jobs and datasets Heimdall emits.
+pub const NAMESPACE: &str = "heimdall";
+
+/// `producer` of every event.
+pub const PRODUCER: &str = concat!(
+	"https://github.com/Vanopticon/Heimdall/tree/v",
+	env!("CARGO_PKG_VERSION")
+);
+
+/// Job name of dump ingests.
+pub const INGEST_JOB: &str = "ingest.bulk";
+
+/// An OpenLineage dataset: a dump or a graph node.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Dataset {
+	pub namespace: String,
+	/// `dump_id` of a dump, key of a node.
+	pub name: String,
+	/// What is known about the dataset, e.g. `label` or `entity_count`.
+	#[serde(default, skip_serializing_if = "Map::is_empty")]
+	pub facets: Map<String, Value>,
+}
+
+impl Dataset {
+	pub fn new(name: impl Into<String>) -> Self {
+		Self {
+			namespace: NAMESPACE.to_string(),
+			name: name.into(),
+			facets: Map::new(),
+		}
+	}
+
+	/// The same dataset with facet `name` set to `value`.
+	pub fn with_facet(mut self, name: &str, value: impl Into<Value>) -> Self {
+		self.facets.insert(name.to_string(), value.into());
+		self
+	}
+}
+
+/// The run an event belongs to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Run {
+	pub run_id: String,
+}
+
+/// The job that ran, e.g. [`INGEST_JOB`] or `enrich.<step>`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+	pub namespace: String,
+	pub name: String,
+}
+
+/// One OpenLineage run event.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LineageEvent {
+	/// Always `COMPLETE`: events are emitted once the outputs exist.
+	pub event_type: String,
+	/// RFC 3339 time the event was emitted.
+	pub event_time: String,
+	pub run: Run,
+	pub job: Job,
+	pub inputs: Vec<Dataset>,
+	pub outputs: Vec<Dataset>,
+	pub producer: String,
+}
+
+impl LineageEvent {
+	/// A completed run `run_id` of `job` with no datasets yet.
+	pub fn new(job: impl Into<String>, run_id: impl Into<String>) -> Self {
+		Self {
+			event_type: "COMPLETE".to_string(),
+			event_time: chrono::Utc::now().to_rfc3339(),
+			run: Run {
+				run_id: run_id.into(),
+			},
+			job: Job {
+				namespace: NAMESPACE.to_string(),
+				name: job.into(),
+			},
+			inputs: Vec::new(),
+			outputs: Vec::new(),
+			producer: PRODUCER.to_string(),
+		}
+	}
+
+	pub fn with_input(mut self, input: Dataset) -> Self {
+		self.inputs.push(input);
+		self
+	}
+
+	pub fn with_output(mut self, output: Dataset) -> Self {
+		self.outputs.push(output);
+		self
+	}
+
+	/// Ingest of dump `dump_id` into graph `graph`, which gained
+	/// `entities` records from it.
+	pub fn dump_ingested(dump_id: &str, graph: &str, entities: u64) -> Self {
+		Self::new(INGEST_JOB, new_run_id())
+			.with_input(Dataset::new(dump_id).with_facet("kind", "dump"))
+			.with_output(
+				Dataset::new(graph)
+					.with_facet("kind", "graph")
+					.with_facet("entity_count", entities),
+			)
+	}
+}
+
+/// A fresh random run id, formatted as a UUID (version 4).
+pub fn new_run_id() -> String {
+	let mut bytes: [u8; 16] = rand::random();
+	bytes[6] = (bytes[6] & 0x0f) | 0x40;
+	bytes[8] = (bytes[8] & 0x3f) | 0x80;
+	let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+	format!(
+		"{}-{}-{}-{}-{}",
+		&hex[..8],
+		&hex[8..12],
+		&hex[12..16],
+		&hex[16..20],
+		&hex[20..]
+	)
+}
+
+enum Sink {
+	Disabled,
+	Jsonl(PathBuf),
+	Http {
+		client: reqwest::Client,
+		url: String,
+	},
+	Memory(Mutex<Vec<LineageEvent>>),
+}
+
+/// Where lineage events go.
+pub struct LineageEmitter {
+	sink: Sink,
+	/// Serializes appends so concurrent events never interleave.
+	write: Mutex<()>,
+}
+
+impl LineageEmitter {
+	fn with_sink(sink: Sink) -> Self {
+		Self {
+			sink,
+			write: Mutex::new(()),
+		}
+	}
+
+	/// Drop every event.
+	pub fn disabled() -> Self {
+		Self::with_sink(Sink::Disabled)
+	}
+
+	/// Append events to `path` as JSON lines.
+	pub fn open(path: impl AsRef<Path>) -> Self {
+		Self::with_sink(Sink::Jsonl(path.as_ref().to_path_buf()))
+	}
+
+	/// POST each event as JSON to `url`, e.g. an OpenLineage collector's
+	/// `/api/v1/lineage`.
+	pub fn http(url: impl Into<String>) -> Self {
+		Self::with_sink(Sink::Http {
+			client: reqwest::Client::new(),
+			url: url.into(),
+		})
+	}
+
+	/// Keep events in memory; see [`LineageEmitter::events`].
+	pub fn in_memory() -> Self {
+		Self::with_sink(Sink::Memory(Mutex::new(Vec::new())))
+	}
+
+	pub fn is_enabled(&self) -> bool {
+		!matches!(self.sink, Sink::Disabled)
+	}
+
+	/// Emit `event`. Failing to deliver it is logged and does not fail the
+	/// operation it describes; HTTP delivery happens in the background and
+	/// needs a Tokio runtime.
+	pub fn emit(&self, event: LineageEvent) {
+		match &self.sink {
+			Sink::Disabled => {}
+			Sink::Jsonl(path) => {
+				let _guard = self.write.lock().unwrap();
+				if let Err(e) = append_line(path, &event) {
+					log::error!("failed to write lineage event to {}: {}", path.display(), e);
+				}
+			}
+			Sink::Http { client, url } => {
+				let request = client.post(url).json(&event);
+				let url = url.clone();
+				tokio::spawn(async move {
+					match request.send().await.and_then(|r| r.error_for_status()) {
+						Ok(_) => {}
+						Err(e) => log::error!("failed to send lineage event to {}: {}", url, e),
+					}
+				});
+			}
+			Sink::Memory(events) => events.lock().unwrap().push(event),
+		}
+	}
+
+	/// Events kept by an in-memory emitter, oldest first; empty for other
+	/// sinks.
+	pub fn events(&self) -> Vec<LineageEvent> {
+		match &self.sink {
+			Sink::Memory(events) => events.lock().unwrap().clone(),
+			_ => Vec::new(),
+		}
+	}
+}
+
+impl std::fmt::Debug for LineageEmitter {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let sink = match &self.sink {
+			Sink::Disabled => "off".to_string(),
+			Sink::Jsonl(path) => format!("jsonl:{}", path.display()),
+			Sink::Http { url, .. } => format!("http:{}", url),
+			Sink::Memory(_) => "memory".to_string(),
+		};
+		f.debug_struct("LineageEmitter")
+			.field("sink", &sink)
+			.finish()
+	}
+}
+
+fn append_line(path: &Path, event: &LineageEvent) -> std::io::Result<()> {
+	let mut line = serde_json::to_vec(event)?;
+	line.push(b'\n');
+	OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(path)?
+		.write_all(&line)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+
+	#[test]
+	fn events_serialize_in_the_openlineage_shape() {
+		let event = LineageEvent::dump_ingested("dump-1", "heimdall_graph", 3);
+		let value = serde_json::to_value(&event).unwrap();
+		assert_eq!(value["eventType"], "COMPLETE");
+		assert_eq!(value["run"]["runId"].as_str().unwrap().len(), 36);
+		assert_eq!(
+			value["job"],
+			json!({"namespace": "heimdall", "name": "ingest.bulk"})
+		);
+		assert_eq!(value["inputs"][0]["name"], "dump-1");
+		assert_eq!(value["outputs"][0]["facets"]["entity_count"], 3);
+		assert_eq!(
+			serde_json::from_value::<LineageEvent>(value).unwrap(),
+			event
+		);
+	}
+
+	#[test]
+	fn jsonl_sink_appends_one_line_per_event() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("lineage.jsonl");
+		let emitter = LineageEmitter::open(&path);
+		emitter.emit(LineageEvent::dump_ingested("dump-1", "g", 1));
+		emitter.emit(LineageEvent::dump_ingested("dump-2", "g", 2));
+
+		let written = std::fs::read_to_string(&path).unwrap();
+		let events: Vec<LineageEvent> = written
+			.lines()
+			.map(|l| serde_json::from_str(l).unwrap())
+			.collect();
+		assert_eq!(events.len(), 2);
+		assert_eq!(events[0].inputs[0].name, "dump-1");
+		assert_eq!(events[1].inputs[0].name, "dump-2");
+		assert!(!LineageEmitter::disabled().is_enabled());
+	}
+}
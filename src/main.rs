@@ -1,5 +1,11 @@
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
 use clap::{Parser, Subcommand};
-use vanopticon_heimdall::{config, devops, run};
+use vanopticon_heimdall::age_client::{AgeClient, AgeConnectOptions};
+use vanopticon_heimdall::ingest::offline::{InputFormat, normalize_file};
+use vanopticon_heimdall::persist::migrations::MIGRATIONS;
+use vanopticon_heimdall::{config, devops, preflight, run};
 
 #[derive(Parser)]
 #[command(name = "heimdall", about = "Heimdall - ETL and normalization hub")]
@@ -32,6 +38,54 @@ enum Commands {
 	StopDb,
 	/// Run the application (default)
 	Run,
+	/// Normalize a local file offline and write NDJSON records to stdout
+	Normalize {
+		/// File to normalize
+		#[arg(long)]
+		input: PathBuf,
+		/// Input format
+		#[arg(long, value_enum, default_value_t = InputFormat::Auto)]
+		format: InputFormat,
+		/// Drop records whose (field_type, canonical) pair was already emitted
+		#[arg(long)]
+		dedupe: bool,
+	},
+	/// Validate config, TLS, database and OIDC without serving; exits non-zero on failure
+	Check,
+	/// Apply the versioned schema migrations, skipping completed ones
+	Migrate {
+		/// Re-apply migrations an earlier run started but never completed
+		#[arg(long)]
+		force: bool,
+	},
+	/// Admin: bulk-load NDJSON `{"label", "key", "props"}` lines with the
+	/// property indexes dropped, then de-duplicate and rebuild them
+	BulkLoad {
+		/// NDJSON file to load
+		#[arg(long)]
+		input: PathBuf,
+		/// Nodes created per statement
+		#[arg(long, default_value_t = 1000)]
+		chunk_size: usize,
+		/// Confirm dropping indexes while the load runs
+		#[arg(long)]
+		yes: bool,
+	},
+}
+
+/// One line of bulk-load input.
+#[derive(serde::Deserialize)]
+struct BulkItem {
+	label: String,
+	key: String,
+	#[serde(default)]
+	props: serde_json::Value,
+}
+
+/// Parse a bulk-load input line into a `(label, key, props)` item.
+fn parse_bulk_item(line: &str) -> serde_json::Result<(String, String, serde_json::Value)> {
+	let item: BulkItem = serde_json::from_str(line)?;
+	Ok((item.label, item.key, item.props))
 }
 
 #[tokio::main]
@@ -66,13 +120,248 @@ async fn main() {
 		Commands::Run => {
 			match config::load() {
 				Ok(settings) => println!(
-					"Loaded settings: host={} port={}",
-					settings.host, settings.port
+					"Loaded settings: host={} port={} database={}",
+					settings.host,
+					settings.port,
+					settings.database_url.redacted()
 				),
 				Err(e) => eprintln!("Warning: failed to load config: {}", e),
 			}
 
 			run().await;
 		}
+		Commands::Normalize {
+			input,
+			format,
+			dedupe,
+		} => {
+			let stdout = std::io::stdout();
+			let mut out = std::io::BufWriter::new(stdout.lock());
+			let mut err = std::io::stderr();
+			let result = normalize_file(&input, format, dedupe, &mut out, &mut err);
+			let _ = out.flush();
+			match result {
+				Ok(summary) => eprintln!(
+					"normalized {} records ({} duplicates dropped, {} errors)",
+					summary.records, summary.duplicates, summary.errors
+				),
+				Err(e) => {
+					eprintln!("Failed to normalize {}: {}", input.display(), e);
+					std::process::exit(1);
+				}
+			}
+		}
+		Commands::Check => {
+			let results = preflight::run_checks().await;
+			print!("{}", preflight::render_table(&results));
+			if !preflight::all_passed(&results) {
+				std::process::exit(1);
+			}
+		}
+		Commands::Migrate { force } => {
+			let settings = match config::load() {
+				Ok(s) => s,
+				Err(e) => {
+					eprintln!("Failed to load config: {}", e);
+					std::process::exit(1);
+				}
+			};
+			let client = match AgeClient::connect_with(
+				settings.database_url.as_str(),
+				&settings.graph_name(),
+				AgeConnectOptions {
+					search_path: Some(settings.age_search_path.clone()),
+				},
+			)
+			.await
+			{
+				Ok(c) => c,
+				Err(e) => {
+					eprintln!("Failed to connect to database: {}", e);
+					std::process::exit(1);
+				}
+			};
+			let opts = settings.migration_options(force);
+			match client.run_migrations(MIGRATIONS, &opts).await {
+				Ok(report) => println!(
+					"applied migrations {:?}; {} already completed",
+					report.applied,
+					report.skipped.len()
+				),
+				Err(e) => {
+					eprintln!("{}", e);
+					std::process::exit(1);
+				}
+			}
+		}
+		Commands::BulkLoad {
+			input,
+			chunk_size,
+			yes,
+		} => {
+			if !yes {
+				eprintln!(
+					"bulk-load drops graph property indexes until it finishes; rerun with --yes to proceed"
+				);
+				std::process::exit(2);
+			}
+			let settings = match config::load() {
+				Ok(s) => s,
+				Err(e) => {
+					eprintln!("Failed to load config: {}", e);
+					std::process::exit(1);
+				}
+			};
+			let client = match AgeClient::connect_with(
+				settings.database_url.as_str(),
+				&settings.graph_name(),
+				AgeConnectOptions {
+					search_path: Some(settings.age_search_path.clone()),
+				},
+			)
+			.await
+			{
+				Ok(c) => c.with_key_property(&settings.graph_key_property),
+				Err(e) => {
+					eprintln!("Failed to connect to database: {}", e);
+					std::process::exit(1);
+				}
+			};
+			let file = match std::fs::File::open(&input) {
+				Ok(f) => f,
+				Err(e) => {
+					eprintln!("Failed to open {}: {}", input.display(), e);
+					std::process::exit(1);
+				}
+			};
+			let items = BufReader::new(file)
+				.lines()
+				.enumerate()
+				.filter_map(|(idx, line)| {
+					let line = line.ok()?;
+					if line.trim().is_empty() {
+						return None;
+					}
+					match parse_bulk_item(&line) {
+						Ok(item) => Some(item),
+						Err(e) => {
+							eprintln!("skipping line {}: {}", idx + 1, e);
+							None
+						}
+					}
+				});
+			match client.bulk_load(items, chunk_size).await {
+				Ok(report) => println!(
+					"bulk load created {} nodes, removed {} duplicates; indexes rebuilt",
+					report.created, report.duplicates_removed
+				),
+				Err(e) => {
+					eprintln!("Bulk load failed (indexes restored): {}", e);
+					std::process::exit(1);
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn normalize_subcommand_parses_arguments() {
+		let cli = Cli::try_parse_from([
+			"heimdall",
+			"normalize",
+			"--input",
+			"dump.csv",
+			"--format",
+			"csv",
+			"--dedupe",
+		])
+		.unwrap();
+
+		match cli.command {
+			Some(Commands::Normalize {
+				input,
+				format,
+				dedupe,
+			}) => {
+				assert_eq!(input, PathBuf::from("dump.csv"));
+				assert_eq!(format, InputFormat::Csv);
+				assert!(dedupe);
+			}
+			_ => panic!("expected normalize subcommand"),
+		}
+	}
+
+	#[test]
+	fn normalize_subcommand_defaults_to_auto_without_dedupe() {
+		let cli = Cli::try_parse_from(["heimdall", "normalize", "--input", "dump.ndjson"]).unwrap();
+		match cli.command {
+			Some(Commands::Normalize { format, dedupe, .. }) => {
+				assert_eq!(format, InputFormat::Auto);
+				assert!(!dedupe);
+			}
+			_ => panic!("expected normalize subcommand"),
+		}
+	}
+
+	#[test]
+	fn normalize_subcommand_rejects_unknown_format_and_missing_input() {
+		let bad_format =
+			Cli::try_parse_from(["heimdall", "normalize", "--input", "x", "--format", "pdf"]);
+		assert!(bad_format.is_err());
+		assert!(Cli::try_parse_from(["heimdall", "normalize"]).is_err());
+	}
+
+	#[test]
+	fn check_subcommand_parses() {
+		let cli = Cli::try_parse_from(["heimdall", "check"]).unwrap();
+		assert!(matches!(cli.command, Some(Commands::Check)));
+	}
+
+	#[test]
+	fn migrate_subcommand_parses_force() {
+		let cli = Cli::try_parse_from(["heimdall", "migrate"]).unwrap();
+		assert!(matches!(
+			cli.command,
+			Some(Commands::Migrate { force: false })
+		));
+		let cli = Cli::try_parse_from(["heimdall", "migrate", "--force"]).unwrap();
+		assert!(matches!(
+			cli.command,
+			Some(Commands::Migrate { force: true })
+		));
+	}
+
+	#[test]
+	fn bulk_load_subcommand_parses_and_defaults_to_unconfirmed() {
+		let cli =
+			Cli::try_parse_from(["heimdall", "bulk-load", "--input", "nodes.ndjson"]).unwrap();
+		match cli.command {
+			Some(Commands::BulkLoad {
+				input,
+				chunk_size,
+				yes,
+			}) => {
+				assert_eq!(input, PathBuf::from("nodes.ndjson"));
+				assert_eq!(chunk_size, 1000);
+				assert!(!yes);
+			}
+			_ => panic!("expected bulk-load subcommand"),
+		}
+	}
+
+	#[test]
+	fn bulk_items_parse_with_optional_props() {
+		let (label, key, props) =
+			parse_bulk_item(r#"{"label":"FieldValue","key":"example.com"}"#).unwrap();
+		assert_eq!(
+			(label.as_str(), key.as_str()),
+			("FieldValue", "example.com")
+		);
+		assert!(props.is_null());
+		assert!(parse_bulk_item(r#"{"key":"x"}"#).is_err());
 	}
 }
@@ -0,0 +1,239 @@
+//! `/metrics` endpoint with Prometheus text / OpenMetrics negotiation, and
+//! `/metrics.json` serving the same metrics as typed JSON.
+
+use axum::{
+	Json,
+	extract::State,
+	http::{HeaderMap, header::ACCEPT, header::CONTENT_TYPE},
+	response::{IntoResponse, Response},
+};
+use std::collections::HashSet;
+
+use crate::observability::metrics::MetricsSnapshot;
+use crate::state::AppState;
+
+/// Content type of the Prometheus text exposition format.
+pub const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Content type of the OpenMetrics text format.
+pub const OPENMETRICS_CONTENT_TYPE: &str =
+	"application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Serve all metrics, as OpenMetrics when the `Accept` header asks for it
+/// and as Prometheus text otherwise.
+pub async fn metrics_handler(State(state): State<AppState>, headers: HeaderMap) -> Response {
+	let mut text = state.metrics.encode();
+	text.push_str(&crate::sync::global_sync_metrics().to_prometheus_text());
+
+	let accept = headers.get(ACCEPT).and_then(|v| v.to_str().ok());
+	if wants_openmetrics(accept) {
+		(
+			[(CONTENT_TYPE, OPENMETRICS_CONTENT_TYPE)],
+			to_openmetrics(&text),
+		)
+			.into_response()
+	} else {
+		([(CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)], text).into_response()
+	}
+}
+
+/// Serve the metrics of [`metrics_handler`] as JSON, built from the gathered
+/// families rather than the text exposition.
+pub async fn metrics_json_handler(State(state): State<AppState>) -> Json<MetricsSnapshot> {
+	let mut families = state.metrics.snapshot();
+	families.extend(crate::sync::global_sync_metrics().snapshot());
+	Json(MetricsSnapshot { families })
+}
+
+/// Whether an `Accept` header lists `application/openmetrics-text` with a
+/// non-zero quality.
+fn wants_openmetrics(accept: Option<&str>) -> bool {
+	accept.unwrap_or("").split(',').any(|range| {
+		let mut parts = range.split(';').map(str::trim);
+		let media = parts.next().unwrap_or("");
+		if !media.eq_ignore_ascii_case("application/openmetrics-text") {
+			return false;
+		}
+		let q = parts
+			.filter_map(|p| p.strip_prefix("q="))
+			.find_map(|q| q.parse::<f32>().ok())
+			.unwrap_or(1.0);
+		q > 0.0
+	})
+}
+
+/// Convert Prometheus text exposition to OpenMetrics.
+///
+/// Counter families are named without the `_total` suffix in `# HELP` and
+/// `# TYPE` lines while their samples always carry it; blank lines are
+/// dropped and the mandatory `# EOF` terminator is appended.
+pub fn to_openmetrics(text: &str) -> String {
+	let counters: HashSet<&str> = text
+		.lines()
+		.filter_map(|l| l.strip_prefix("# TYPE "))
+		.filter_map(|rest| rest.strip_suffix(" counter"))
+		.collect();
+
+	let mut out = String::with_capacity(text.len() + 8);
+	for line in text.lines().filter(|l| !l.trim().is_empty()) {
+		if let Some((prefix, rest)) = split_metadata(line) {
+			let (name, tail) = rest.split_once(' ').unwrap_or((rest, ""));
+			let name = if counters.contains(name) {
+				name.strip_suffix("_total").unwrap_or(name)
+			} else {
+				name
+			};
+			out.push_str(prefix);
+			out.push_str(name);
+			if !tail.is_empty() {
+				out.push(' ');
+				out.push_str(tail);
+			}
+		} else if line.starts_with('#') {
+			out.push_str(line);
+		} else {
+			let end = line.find(['{', ' ']).unwrap_or(line.len());
+			let (name, tail) = line.split_at(end);
+			out.push_str(name);
+			if counters.contains(name) && !name.ends_with("_total") {
+				out.push_str("_total");
+			}
+			out.push_str(tail);
+		}
+		out.push('\n');
+	}
+	out.push_str("# EOF\n");
+	out
+}
+
+/// Split a `# HELP` or `# TYPE` line into its prefix and the remainder
+/// starting at the metric name.
+fn split_metadata(line: &str) -> Option<(&str, &str)> {
+	["# HELP ", "# TYPE "]
+		.into_iter()
+		.find_map(|prefix| line.strip_prefix(prefix).map(|rest| (prefix, rest)))
+}
+
+#[cfg(test)]
+#[cfg(feature = "unit-tests")]
+mod tests {
+	use super::*;
+	use axum::http::HeaderValue;
+
+	async fn scrape(accept: Option<&'static str>) -> (String, String) {
+		let state = crate::ingest::test_utils::create_test_app_state();
+		state.metrics.ingest_requests_total.inc();
+		let mut headers = HeaderMap::new();
+		if let Some(a) = accept {
+			headers.insert(ACCEPT, HeaderValue::from_static(a));
+		}
+		let resp = metrics_handler(State(state), headers).await;
+		let content_type = resp.headers()[CONTENT_TYPE].to_str().unwrap().to_string();
+		let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+			.await
+			.unwrap();
+		(content_type, String::from_utf8(body.to_vec()).unwrap())
+	}
+
+	#[tokio::test]
+	async fn default_negotiation_is_prometheus_text() {
+		let (content_type, body) = scrape(None).await;
+		assert_eq!(content_type, PROMETHEUS_CONTENT_TYPE);
+		assert!(body.contains("ingest_requests_total 1\n"));
+		assert!(!body.contains("# EOF"));
+
+		let (content_type, _) = scrape(Some("text/plain")).await;
+		assert_eq!(content_type, PROMETHEUS_CONTENT_TYPE);
+	}
+
+	#[tokio::test]
+	async fn openmetrics_is_served_when_accepted() {
+		let accept = "application/openmetrics-text;version=1.0.0,text/plain;version=0.0.4;q=0.5";
+		let (content_type, body) = scrape(Some(accept)).await;
+		assert_eq!(content_type, OPENMETRICS_CONTENT_TYPE);
+		assert!(body.ends_with("# EOF\n"));
+		assert!(body.contains("ingest_requests_total 1\n"));
+	}
+
+	#[tokio::test]
+	async fn json_snapshot_carries_current_values() {
+		let state = crate::ingest::test_utils::create_test_app_state();
+		state.metrics.ingest_requests_total.inc();
+		state.metrics.ingest_records_total.inc_by(3);
+		state.metrics.persist_jobs_submitted.inc_by(2);
+		state.metrics.persist_batch_latency_ms.observe(7.0);
+		let resp = metrics_json_handler(State(state)).await.into_response();
+		assert_eq!(resp.headers()[CONTENT_TYPE], "application/json");
+		let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+			.await
+			.unwrap();
+		let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+		let families = json["families"].as_array().unwrap();
+		let family = |suffix: &str| {
+			families
+				.iter()
+				.find(|f| f["name"].as_str().unwrap().ends_with(suffix))
+				.unwrap_or_else(|| panic!("no {} family", suffix))
+		};
+
+		let requests = family("ingest_requests_total");
+		assert_eq!(requests["type"], "counter");
+		assert_eq!(requests["samples"][0]["value"], 1.0);
+		assert_eq!(family("ingest_records_total")["samples"][0]["value"], 3.0);
+		assert_eq!(
+			family("persist_jobs_submitted_total")["samples"][0]["value"],
+			2.0
+		);
+
+		let latency = family("persist_batch_latency_ms");
+		assert_eq!(latency["type"], "histogram");
+		let distribution = &latency["samples"][0]["distribution"];
+		assert_eq!(distribution["count"], 1);
+		assert_eq!(distribution["sum"], 7.0);
+		assert_eq!(
+			distribution["buckets"][0],
+			serde_json::json!({"le": 1.0, "count": 0})
+		);
+		assert_eq!(
+			distribution["buckets"][2],
+			serde_json::json!({"le": 10.0, "count": 1})
+		);
+
+		assert_eq!(
+			family("heimdall_sync_push_attempts_total")["type"],
+			"counter"
+		);
+	}
+
+	#[test]
+	fn openmetrics_rejected_with_zero_quality() {
+		assert!(!wants_openmetrics(Some("application/openmetrics-text;q=0")));
+		assert!(!wants_openmetrics(None));
+	}
+
+	#[test]
+	fn counter_families_follow_total_suffix_convention() {
+		let text = "# HELP jobs_total Jobs seen\n\
+			# TYPE jobs_total counter\n\
+			jobs_total 3\n\
+			# HELP flushes Flushes\n\
+			# TYPE flushes counter\n\
+			flushes{peer=\"a\"} 2\n\
+			\n\
+			# TYPE queue gauge\n\
+			queue 1\n";
+
+		assert_eq!(
+			to_openmetrics(text),
+			"# HELP jobs Jobs seen\n\
+			 # TYPE jobs counter\n\
+			 jobs_total 3\n\
+			 # HELP flushes Flushes\n\
+			 # TYPE flushes counter\n\
+			 flushes_total{peer=\"a\"} 2\n\
+			 # TYPE queue gauge\n\
+			 queue 1\n\
+			 # EOF\n"
+		);
+	}
+}
@@ -1,4 +1,11 @@
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use std::sync::OnceLock;
+
+use tracing_subscriber::{
+	EnvFilter, Registry, layer::SubscriberExt, reload, util::SubscriberInitExt,
+};
+
+/// Handle replacing the filter installed by `init_logging`.
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
 
 /// Initialize structured JSON logging to stdout with contextual fields
 pub fn init_logging() -> anyhow::Result<()> {
@@ -6,6 +13,8 @@ pub fn init_logging() -> anyhow::Result<()> {
 	let env_filter = EnvFilter::try_from_default_env()
 		.or_else(|_| EnvFilter::try_new("info"))
 		.unwrap_or_else(|_| EnvFilter::new("info"));
+	// Reloadable so `set_log_level` can change it at runtime.
+	let (env_filter, handle) = reload::Layer::new(env_filter);
 
 	// Build JSON formatter for structured logging
 	let json_layer = tracing_subscriber::fmt::layer()
@@ -24,7 +33,20 @@ pub fn init_logging() -> anyhow::Result<()> {
 		.with(json_layer)
 		.try_init()
 		.map_err(|e| anyhow::anyhow!("Failed to initialize logging: {}", e))?;
+	let _ = FILTER.set(handle);
+
+	Ok(())
+}
 
+/// Log at `level` and above from now on, replacing the filter chosen at
+/// startup. A no-op before `init_logging`.
+pub fn set_log_level(level: log::Level) -> anyhow::Result<()> {
+	log::set_max_level(level.to_level_filter());
+	if let Some(handle) = FILTER.get() {
+		handle
+			.reload(EnvFilter::new(level.as_str().to_lowercase()))
+			.map_err(|e| anyhow::anyhow!("Failed to change log level: {}", e))?;
+	}
 	Ok(())
 }
 
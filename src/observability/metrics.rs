@@ -1,8 +1,197 @@
 use prometheus::{
-	Counter, Gauge, Histogram, HistogramOpts, IntCounter, IntGauge, Opts, Registry, TextEncoder,
+	Counter, Gauge, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+	TextEncoder,
 };
+use serde::Serialize;
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
+/// Default buckets for `persist_batch_latency_ms`.
+pub const DEFAULT_PERSIST_BATCH_LATENCY_MS_BUCKETS: [f64; 8] =
+	[1.0, 5.0, 10.0, 50.0, 100.0, 500.0, 1000.0, 5000.0];
+/// Default buckets for `ingest_duration_seconds`.
+pub const DEFAULT_INGEST_DURATION_SECONDS_BUCKETS: [f64; 9] =
+	[0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0];
+/// Default buckets for `enrichment_duration_seconds`.
+pub const DEFAULT_ENRICHMENT_DURATION_SECONDS_BUCKETS: [f64; 7] =
+	[0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0];
+
+/// Bucket boundaries for the configurable latency histograms.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistogramBuckets {
+	pub persist_batch_latency_ms: Vec<f64>,
+	pub ingest_duration_seconds: Vec<f64>,
+	pub enrichment_duration_seconds: Vec<f64>,
+}
+
+impl Default for HistogramBuckets {
+	fn default() -> Self {
+		Self {
+			persist_batch_latency_ms: DEFAULT_PERSIST_BATCH_LATENCY_MS_BUCKETS.to_vec(),
+			ingest_duration_seconds: DEFAULT_INGEST_DURATION_SECONDS_BUCKETS.to_vec(),
+			enrichment_duration_seconds: DEFAULT_ENRICHMENT_DURATION_SECONDS_BUCKETS.to_vec(),
+		}
+	}
+}
+
+impl HistogramBuckets {
+	/// Check every bucket vector is non-empty, finite and strictly
+	/// increasing.
+	pub fn validate(&self) -> Result<(), String> {
+		validate_buckets("persist_batch_latency_ms", &self.persist_batch_latency_ms)?;
+		validate_buckets("ingest_duration_seconds", &self.ingest_duration_seconds)?;
+		validate_buckets(
+			"enrichment_duration_seconds",
+			&self.enrichment_duration_seconds,
+		)
+	}
+}
+
+fn validate_buckets(name: &str, buckets: &[f64]) -> Result<(), String> {
+	if buckets.is_empty() {
+		return Err(format!("{} buckets are empty", name));
+	}
+	if buckets.iter().any(|b| !b.is_finite()) {
+		return Err(format!("{} buckets must be finite", name));
+	}
+	if buckets.windows(2).any(|w| w[0] >= w[1]) {
+		return Err(format!("{} buckets must be strictly increasing", name));
+	}
+	Ok(())
+}
+
+/// Metric families served by `/metrics.json`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MetricsSnapshot {
+	pub families: Vec<MetricFamilySnapshot>,
+}
+
+/// One metric family and its current samples.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MetricFamilySnapshot {
+	pub name: String,
+	pub help: String,
+	#[serde(rename = "type")]
+	pub kind: MetricKind,
+	pub samples: Vec<MetricSample>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MetricKind {
+	Counter,
+	Gauge,
+	Histogram,
+	Summary,
+	Untyped,
+}
+
+/// One labelled sample of a family: a plain value for counters, gauges
+/// and untyped metrics, a distribution for histograms and summaries.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MetricSample {
+	#[serde(skip_serializing_if = "BTreeMap::is_empty")]
+	pub labels: BTreeMap<String, String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub value: Option<f64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub distribution: Option<DistributionSnapshot>,
+}
+
+impl MetricSample {
+	/// A counter, gauge or untyped sample.
+	pub fn value(labels: BTreeMap<String, String>, value: f64) -> Self {
+		Self {
+			labels,
+			value: Some(value),
+			distribution: None,
+		}
+	}
+}
+
+/// Observations of a histogram or summary.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DistributionSnapshot {
+	pub count: u64,
+	pub sum: f64,
+	/// Cumulative histogram buckets; the implicit `+Inf` bucket is `count`.
+	/// Empty for summaries.
+	pub buckets: Vec<BucketSnapshot>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct BucketSnapshot {
+	pub le: f64,
+	pub count: u64,
+}
+
+impl MetricFamilySnapshot {
+	fn from_family(family: &prometheus::proto::MetricFamily) -> Self {
+		use prometheus::proto::MetricType;
+
+		let kind = match family.get_field_type() {
+			MetricType::COUNTER => MetricKind::Counter,
+			MetricType::GAUGE => MetricKind::Gauge,
+			MetricType::HISTOGRAM => MetricKind::Histogram,
+			MetricType::SUMMARY => MetricKind::Summary,
+			MetricType::UNTYPED => MetricKind::Untyped,
+		};
+		let samples = family
+			.get_metric()
+			.iter()
+			.map(|m| {
+				let labels = m
+					.get_label()
+					.iter()
+					.map(|l| (l.get_name().to_string(), l.get_value().to_string()))
+					.collect();
+				match kind {
+					MetricKind::Counter => MetricSample::value(labels, m.get_counter().get_value()),
+					MetricKind::Gauge => MetricSample::value(labels, m.get_gauge().get_value()),
+					MetricKind::Untyped => MetricSample::value(labels, m.get_untyped().get_value()),
+					MetricKind::Histogram => {
+						let h = m.get_histogram();
+						MetricSample {
+							labels,
+							value: None,
+							distribution: Some(DistributionSnapshot {
+								count: h.get_sample_count(),
+								sum: h.get_sample_sum(),
+								buckets: h
+									.get_bucket()
+									.iter()
+									.map(|b| BucketSnapshot {
+										le: b.get_upper_bound(),
+										count: b.get_cumulative_count(),
+									})
+									.collect(),
+							}),
+						}
+					}
+					MetricKind::Summary => {
+						let s = m.get_summary();
+						MetricSample {
+							labels,
+							value: None,
+							distribution: Some(DistributionSnapshot {
+								count: s.get_sample_count(),
+								sum: s.get_sample_sum(),
+								buckets: Vec::new(),
+							}),
+						}
+					}
+				}
+			})
+			.collect();
+		Self {
+			name: family.get_name().to_string(),
+			help: family.get_help().to_string(),
+			kind,
+			samples,
+		}
+	}
+}
+
 /// Central registry for all Prometheus metrics
 pub struct MetricsRegistry {
 	registry: Registry,
@@ -12,6 +201,18 @@ pub struct MetricsRegistry {
 	pub ingest_records_total: IntCounter,
 	pub ingest_errors_total: IntCounter,
 	pub ingest_bytes_total: Counter,
+	pub ingest_invalid_utf8_total: IntCounter,
+	pub ingest_oversized_lines_total: IntCounter,
+	/// Private or reserved IP records flagged or dropped at ingest.
+	pub ingest_private_ip_filtered_total: IntCounter,
+	/// Ingest requests over quota, labelled by hashed subject.
+	pub ingest_quota_rejections_total: IntCounterVec,
+	pub ingest_upload_shed_total: IntCounterVec,
+	/// 1 while ingest is paused because too many persist writes fail.
+	pub ingest_paused: IntGauge,
+	pub ingest_label_quarantined_total: IntCounter,
+	/// Empty or whitespace-only values not persisted.
+	pub ingest_empty_skipped_total: IntCounter,
 	pub ingest_duration_seconds: Histogram,
 
 	// Persistence metrics
@@ -21,20 +222,41 @@ pub struct MetricsRegistry {
 	pub persist_per_item_failures: IntCounter,
 	pub persist_queue_length: IntGauge,
 	pub persist_batch_latency_ms: Histogram,
+	pub ingest_to_persist_latency_ms: Histogram,
+	pub persist_skipped_duplicates_total: IntCounter,
+	pub persist_oversized_props_total: IntCounter,
+	pub persist_props_shape_limited_total: IntCounter,
+	pub persist_schema_violation_total: IntCounter,
+	/// Persist jobs quarantined because their key belongs to another value.
+	pub key_collision_total: IntCounter,
+	pub retention_expired_total: IntCounter,
+	/// Co-occurrences skipped because a value is a hub.
+	pub cooccur_hub_skipped_total: IntCounter,
 
 	// Sync metrics (for future multi-Heimdall sync)
 	pub sync_lag_seconds: Gauge,
 	pub sync_operations_total: IntCounter,
 	pub sync_errors_total: IntCounter,
+	/// Latency of each batch of sync-applied entries written to the graph.
+	pub sync_apply_batch_latency_ms: Histogram,
 
 	// Enrichment metrics
 	pub enrichment_requests_total: IntCounter,
 	pub enrichment_failures_total: IntCounter,
 	pub enrichment_duration_seconds: Histogram,
+	/// Enrichment work skipped by the fan-out limits, labelled by limit.
+	pub enrichment_shed_total: IntCounterVec,
 }
 
 impl MetricsRegistry {
 	pub fn new() -> Self {
+		Self::with_buckets(&HistogramBuckets::default()).expect("default buckets are valid")
+	}
+
+	/// Build the registry using `buckets` for the configurable latency
+	/// histograms. Fails if any bucket vector is invalid.
+	pub fn with_buckets(buckets: &HistogramBuckets) -> anyhow::Result<Self> {
+		buckets.validate().map_err(anyhow::Error::msg)?;
 		let registry = Registry::new();
 
 		// Ingest metrics
@@ -74,13 +296,78 @@ impl MetricsRegistry {
 		)
 		.unwrap();
 
+		let ingest_invalid_utf8_total = IntCounter::with_opts(
+			Opts::new(
+				"heimdall_ingest_invalid_utf8_total",
+				"NDJSON lines rejected for invalid UTF-8 under strict decoding",
+			)
+			.namespace("heimdall"),
+		)
+		.unwrap();
+
+		let ingest_oversized_lines_total = IntCounter::with_opts(
+			Opts::new(
+				"heimdall_ingest_oversized_lines_total",
+				"NDJSON lines skipped for exceeding the maximum line length",
+			)
+			.namespace("heimdall"),
+		)
+		.unwrap();
+
+		let ingest_private_ip_filtered_total = IntCounter::with_opts(
+			Opts::new(
+				"heimdall_ingest_private_ip_filtered_total",
+				"Private or reserved IP records flagged or dropped by the ingest policy",
+			)
+			.namespace("heimdall"),
+		)
+		.unwrap();
+
+		let ingest_label_quarantined_total = IntCounter::with_opts(
+			Opts::new(
+				"heimdall_ingest_label_quarantined_total",
+				"Labels and edge types outside the allowlist mapped to the quarantine label",
+			)
+			.namespace("heimdall"),
+		)
+		.unwrap();
+
+		let ingest_empty_skipped_total = IntCounter::with_opts(
+			Opts::new(
+				"heimdall_ingest_empty_skipped_total",
+				"Empty or whitespace-only values skipped instead of persisted",
+			)
+			.namespace("heimdall"),
+		)
+		.unwrap();
+
+		let ingest_quota_rejections_total = IntCounterVec::new(
+			Opts::new(
+				"heimdall_ingest_quota_rejections_total",
+				"Ingest requests rejected for exceeding the subject's quota",
+			)
+			.namespace("heimdall"),
+			&["subject"],
+		)
+		.unwrap();
+
+		let ingest_upload_shed_total = IntCounterVec::new(
+			Opts::new(
+				"heimdall_ingest_upload_shed_total",
+				"Uploads turned away because the endpoint's upload queue was full or too slow",
+			)
+			.namespace("heimdall"),
+			&["endpoint"],
+		)
+		.unwrap();
+
 		let ingest_duration_seconds = Histogram::with_opts(
 			HistogramOpts::new(
 				"heimdall_ingest_duration_seconds",
 				"Duration of ingest operations in seconds",
 			)
 			.namespace("heimdall")
-			.buckets(vec![0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0]),
+			.buckets(buckets.ingest_duration_seconds.clone()),
 		)
 		.unwrap();
 
@@ -121,6 +408,15 @@ impl MetricsRegistry {
 		)
 		.unwrap();
 
+		let ingest_paused = IntGauge::with_opts(
+			Opts::new(
+				"heimdall_ingest_paused",
+				"Whether ingest is paused because the persist failure rate is too high",
+			)
+			.namespace("heimdall"),
+		)
+		.unwrap();
+
 		let persist_queue_length = IntGauge::with_opts(
 			Opts::new(
 				"heimdall_persist_queue_length",
@@ -136,7 +432,7 @@ impl MetricsRegistry {
 				"Batch flush latency in milliseconds",
 			)
 			.namespace("heimdall")
-			.buckets(vec![1.0, 5.0, 10.0, 50.0, 100.0, 500.0, 1000.0, 5000.0]),
+			.buckets(buckets.persist_batch_latency_ms.clone()),
 		)
 		.unwrap();
 
@@ -168,6 +464,16 @@ impl MetricsRegistry {
 		)
 		.unwrap();
 
+		let sync_apply_batch_latency_ms = Histogram::with_opts(
+			HistogramOpts::new(
+				"heimdall_sync_apply_batch_latency_ms",
+				"Latency of writing a batch of applied change log entries in milliseconds",
+			)
+			.namespace("heimdall")
+			.buckets(DEFAULT_PERSIST_BATCH_LATENCY_MS_BUCKETS.to_vec()),
+		)
+		.unwrap();
+
 		// Enrichment metrics
 		let enrichment_requests_total = IntCounter::with_opts(
 			Opts::new(
@@ -193,7 +499,92 @@ impl MetricsRegistry {
 				"Duration of enrichment operations in seconds",
 			)
 			.namespace("heimdall")
-			.buckets(vec![0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0]),
+			.buckets(buckets.enrichment_duration_seconds.clone()),
+		)
+		.unwrap();
+
+		let enrichment_shed_total = IntCounterVec::new(
+			Opts::new(
+				"heimdall_enrichment_shed_total",
+				"Enrichment entities, lookups and hops skipped for exceeding the fan-out limits",
+			)
+			.namespace("heimdall"),
+			&["limit"],
+		)
+		.unwrap();
+
+		let persist_skipped_duplicates_total = IntCounter::with_opts(
+			Opts::new(
+				"heimdall_persist_skipped_duplicates_total",
+				"Persist jobs written as bare sightings because an identical merge was seen recently",
+			)
+			.namespace("heimdall"),
+		)
+		.unwrap();
+
+		let persist_oversized_props_total = IntCounter::with_opts(
+			Opts::new(
+				"heimdall_persist_oversized_props_total",
+				"Persist jobs whose props exceeded the configured size cap",
+			)
+			.namespace("heimdall"),
+		)
+		.unwrap();
+
+		let persist_props_shape_limited_total = IntCounter::with_opts(
+			Opts::new(
+				"heimdall_persist_props_shape_limited_total",
+				"Persist jobs whose props exceeded the configured nesting depth or array length",
+			)
+			.namespace("heimdall"),
+		)
+		.unwrap();
+
+		let persist_schema_violation_total = IntCounter::with_opts(
+			Opts::new(
+				"heimdall_persist_schema_violation_total",
+				"Persist jobs whose props violated their label's schema",
+			)
+			.namespace("heimdall"),
+		)
+		.unwrap();
+
+		let key_collision_total = IntCounter::with_opts(
+			Opts::new(
+				"heimdall_key_collision_total",
+				"Persist jobs quarantined because their canonical key collided with another value",
+			)
+			.namespace("heimdall"),
+		)
+		.unwrap();
+
+		let retention_expired_total = IntCounter::with_opts(
+			Opts::new(
+				"heimdall_retention_expired_total",
+				"Nodes tombstoned or deleted because their label TTL elapsed",
+			)
+			.namespace("heimdall"),
+		)
+		.unwrap();
+
+		let cooccur_hub_skipped_total = IntCounter::with_opts(
+			Opts::new(
+				"heimdall_cooccur_hub_skipped_total",
+				"Co-occurrences not recorded because a value reached the hub degree",
+			)
+			.namespace("heimdall"),
+		)
+		.unwrap();
+
+		let ingest_to_persist_latency_ms = Histogram::with_opts(
+			HistogramOpts::new(
+				"heimdall_ingest_to_persist_latency_ms",
+				"Latency from request arrival to batch flush in milliseconds",
+			)
+			.namespace("heimdall")
+			.buckets(vec![
+				1.0, 5.0, 10.0, 50.0, 100.0, 500.0, 1000.0, 5000.0, 10000.0, 30000.0, 60000.0,
+			]),
 		)
 		.unwrap();
 
@@ -210,6 +601,30 @@ impl MetricsRegistry {
 		registry
 			.register(Box::new(ingest_bytes_total.clone()))
 			.unwrap();
+		registry
+			.register(Box::new(ingest_invalid_utf8_total.clone()))
+			.unwrap();
+		registry
+			.register(Box::new(ingest_oversized_lines_total.clone()))
+			.unwrap();
+		registry
+			.register(Box::new(ingest_private_ip_filtered_total.clone()))
+			.unwrap();
+		registry
+			.register(Box::new(ingest_quota_rejections_total.clone()))
+			.unwrap();
+		registry
+			.register(Box::new(ingest_upload_shed_total.clone()))
+			.unwrap();
+		registry
+			.register(Box::new(ingest_paused.clone()))
+			.unwrap();
+		registry
+			.register(Box::new(ingest_label_quarantined_total.clone()))
+			.unwrap();
+		registry
+			.register(Box::new(ingest_empty_skipped_total.clone()))
+			.unwrap();
 		registry
 			.register(Box::new(ingest_duration_seconds.clone()))
 			.unwrap();
@@ -240,6 +655,9 @@ impl MetricsRegistry {
 		registry
 			.register(Box::new(sync_errors_total.clone()))
 			.unwrap();
+		registry
+			.register(Box::new(sync_apply_batch_latency_ms.clone()))
+			.unwrap();
 		registry
 			.register(Box::new(enrichment_requests_total.clone()))
 			.unwrap();
@@ -249,13 +667,48 @@ impl MetricsRegistry {
 		registry
 			.register(Box::new(enrichment_duration_seconds.clone()))
 			.unwrap();
+		registry
+			.register(Box::new(enrichment_shed_total.clone()))
+			.unwrap();
+		registry
+			.register(Box::new(persist_skipped_duplicates_total.clone()))
+			.unwrap();
+		registry
+			.register(Box::new(persist_oversized_props_total.clone()))
+			.unwrap();
+		registry
+			.register(Box::new(persist_props_shape_limited_total.clone()))
+			.unwrap();
+		registry
+			.register(Box::new(persist_schema_violation_total.clone()))
+			.unwrap();
+		registry
+			.register(Box::new(key_collision_total.clone()))
+			.unwrap();
+		registry
+			.register(Box::new(retention_expired_total.clone()))
+			.unwrap();
+		registry
+			.register(Box::new(cooccur_hub_skipped_total.clone()))
+			.unwrap();
+		registry
+			.register(Box::new(ingest_to_persist_latency_ms.clone()))
+			.unwrap();
 
-		Self {
+		Ok(Self {
 			registry,
 			ingest_requests_total,
 			ingest_records_total,
 			ingest_errors_total,
 			ingest_bytes_total,
+			ingest_invalid_utf8_total,
+			ingest_oversized_lines_total,
+			ingest_private_ip_filtered_total,
+			ingest_quota_rejections_total,
+			ingest_upload_shed_total,
+			ingest_paused,
+			ingest_label_quarantined_total,
+			ingest_empty_skipped_total,
 			ingest_duration_seconds,
 			persist_jobs_submitted,
 			persist_batch_flushes,
@@ -263,13 +716,23 @@ impl MetricsRegistry {
 			persist_per_item_failures,
 			persist_queue_length,
 			persist_batch_latency_ms,
+			ingest_to_persist_latency_ms,
+			persist_skipped_duplicates_total,
+			persist_oversized_props_total,
+			persist_props_shape_limited_total,
+			persist_schema_violation_total,
+			key_collision_total,
+			retention_expired_total,
+			cooccur_hub_skipped_total,
 			sync_lag_seconds,
 			sync_operations_total,
 			sync_errors_total,
+			sync_apply_batch_latency_ms,
 			enrichment_requests_total,
 			enrichment_failures_total,
 			enrichment_duration_seconds,
-		}
+			enrichment_shed_total,
+		})
 	}
 
 	/// Encode metrics in Prometheus text format
@@ -284,6 +747,15 @@ impl MetricsRegistry {
 			}
 		}
 	}
+
+	/// Current value of every registered metric family.
+	pub fn snapshot(&self) -> Vec<MetricFamilySnapshot> {
+		self.registry
+			.gather()
+			.iter()
+			.map(MetricFamilySnapshot::from_family)
+			.collect()
+	}
 }
 
 impl Default for MetricsRegistry {
@@ -312,4 +784,29 @@ mod tests {
 		registry.ingest_records_total.inc_by(10);
 		assert!(!registry.encode().is_empty());
 	}
+
+	#[test]
+	fn custom_buckets_are_applied() {
+		let buckets = super::HistogramBuckets {
+			persist_batch_latency_ms: vec![0.1, 0.5, 2.0],
+			..Default::default()
+		};
+		let registry = super::MetricsRegistry::with_buckets(&buckets).unwrap();
+		registry.persist_batch_latency_ms.observe(0.3);
+		let text = registry.encode();
+		assert!(text.contains("persist_batch_latency_ms_bucket{le=\"0.1\"} 0"));
+		assert!(text.contains("persist_batch_latency_ms_bucket{le=\"0.5\"} 1"));
+		assert!(!text.contains("persist_batch_latency_ms_bucket{le=\"5000\"}"));
+	}
+
+	#[test]
+	fn non_monotonic_buckets_are_rejected() {
+		for bad in [vec![1.0, 5.0, 5.0], vec![10.0, 1.0], vec![]] {
+			let buckets = super::HistogramBuckets {
+				ingest_duration_seconds: bad,
+				..Default::default()
+			};
+			assert!(super::MetricsRegistry::with_buckets(&buckets).is_err());
+		}
+	}
 }
@@ -1,23 +1,54 @@
+pub mod exposition;
 pub mod logging;
 pub mod metrics;
 pub mod tracing_setup;
 
-pub use logging::init_logging;
-pub use metrics::{MetricsRegistry, init_metrics};
+pub use exposition::{metrics_handler, metrics_json_handler};
+pub use logging::{init_logging, set_log_level};
+pub use metrics::{HistogramBuckets, MetricsRegistry, init_metrics};
 pub use tracing_setup::init_tracing;
 
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use opentelemetry_sdk::trace::TracerProvider;
 
 /// Global observability state
 pub struct ObservabilityState {
 	pub metrics: Arc<MetricsRegistry>,
+	/// Tracer provider installed by `init_tracing`, if it ran.
+	tracer_provider: Option<TracerProvider>,
+	shut_down: AtomicBool,
 }
 
 impl ObservabilityState {
 	pub fn new() -> Self {
 		Self {
 			metrics: Arc::new(MetricsRegistry::new()),
+			tracer_provider: None,
+			shut_down: AtomicBool::new(false),
+		}
+	}
+
+	/// Flush buffered spans and shut down the tracer provider and its
+	/// exporters.
+	///
+	/// Only the first call does any work, and it is a no-op when tracing was
+	/// never initialized. Metrics live in a Prometheus registry that is read
+	/// on scrape, so there is nothing buffered to flush for them. Exporter
+	/// shutdown may block, so call this from a blocking context.
+	pub fn shutdown(&self) -> anyhow::Result<()> {
+		if self.shut_down.swap(true, Ordering::SeqCst) {
+			return Ok(());
+		}
+		let Some(provider) = &self.tracer_provider else {
+			return Ok(());
+		};
+		for result in provider.force_flush() {
+			result?;
 		}
+		provider.shutdown()?;
+		Ok(())
 	}
 }
 
@@ -27,8 +58,9 @@ impl Default for ObservabilityState {
 	}
 }
 
-/// Initialize all observability components
-pub async fn init_observability() -> anyhow::Result<ObservabilityState> {
+/// Initialize all observability components. High-frequency spans are
+/// sampled at `trace_sample_ratio`.
+pub async fn init_observability(trace_sample_ratio: f64) -> anyhow::Result<ObservabilityState> {
 	// Initialize structured JSON logging
 	init_logging()?;
 
@@ -36,14 +68,18 @@ pub async fn init_observability() -> anyhow::Result<ObservabilityState> {
 	let metrics = init_metrics()?;
 
 	// Initialize OpenTelemetry tracing
-	init_tracing().await?;
+	let tracer_provider = init_tracing(trace_sample_ratio).await?;
 
 	tracing::info!(
 		component = "observability",
 		"Observability initialized: structured logging, metrics, and tracing enabled"
 	);
 
-	Ok(ObservabilityState { metrics })
+	Ok(ObservabilityState {
+		metrics,
+		tracer_provider: Some(tracer_provider),
+		shut_down: AtomicBool::new(false),
+	})
 }
 
 #[cfg(feature = "unit-tests")]
@@ -53,4 +89,22 @@ mod tests {
 		let state = super::ObservabilityState::new();
 		assert!(!state.metrics.encode().is_empty());
 	}
+
+	#[test]
+	fn shutdown_without_tracing_is_a_no_op() {
+		let state = super::ObservabilityState::new();
+		state.shutdown().unwrap();
+		state.shutdown().unwrap();
+	}
+
+	#[test]
+	fn shutdown_flushes_tracer_provider_once() {
+		let state = super::ObservabilityState {
+			tracer_provider: Some(opentelemetry_sdk::trace::TracerProvider::builder().build()),
+			..super::ObservabilityState::new()
+		};
+		state.shutdown().unwrap();
+		// A second shutdown would fail in the SDK; the guard makes it a no-op.
+		state.shutdown().unwrap();
+	}
 }
@@ -1,13 +1,72 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
 use opentelemetry::trace::TracerProvider as _;
 use opentelemetry_sdk::trace::TracerProvider;
-use tracing_subscriber::layer::SubscriberExt;
+use tracing::subscriber::Interest;
+use tracing::{Level, Metadata};
+use tracing_subscriber::Layer;
+use tracing_subscriber::layer::{Context, Filter, SubscriberExt};
+
+/// Spans opened for every persisted batch or ingested record. At high
+/// throughput these dominate the trace volume, so [`SpanSampler`] keeps
+/// only a share of them.
+pub const SAMPLED_SPANS: &[&str] = &["write_batch", "ingest_record"];
+
+/// Head-based sampler for the spans in [`SAMPLED_SPANS`].
+///
+/// Keeps `ratio` of those spans, deciding when each span is opened and
+/// spreading the kept ones evenly. `ERROR`-level spans, all other spans
+/// and all events are always kept.
+pub struct SpanSampler {
+	ratio: f64,
+	seen: AtomicU64,
+}
+
+impl SpanSampler {
+	/// Sampler keeping `ratio` (clamped to 0..=1) of the sampled spans.
+	pub fn new(ratio: f64) -> Self {
+		Self {
+			ratio: ratio.clamp(0.0, 1.0),
+			seen: AtomicU64::new(0),
+		}
+	}
+
+	fn applies_to(meta: &Metadata<'_>) -> bool {
+		meta.is_span() && *meta.level() != Level::ERROR && SAMPLED_SPANS.contains(&meta.name())
+	}
+
+	/// Whether to keep the next sampled span: the n-th one is kept when
+	/// `n * ratio` crosses an integer.
+	fn sample(&self) -> bool {
+		let n = self.seen.fetch_add(1, Ordering::Relaxed) as f64;
+		((n + 1.0) * self.ratio).floor() > (n * self.ratio).floor()
+	}
+}
+
+impl<S> Filter<S> for SpanSampler {
+	fn enabled(&self, meta: &Metadata<'_>, _cx: &Context<'_, S>) -> bool {
+		!Self::applies_to(meta) || self.sample()
+	}
+
+	fn callsite_enabled(&self, meta: &'static Metadata<'static>) -> Interest {
+		if Self::applies_to(meta) {
+			Interest::sometimes()
+		} else {
+			Interest::always()
+		}
+	}
+}
 
 /// Initialize OpenTelemetry tracing
 ///
 /// This sets up tracing spans for critical code paths. If OTEL_EXPORTER_OTLP_ENDPOINT
 /// is set, traces will be exported to that endpoint. Otherwise, traces are kept
 /// in-process for local debugging.
-pub async fn init_tracing() -> anyhow::Result<()> {
+///
+/// High-frequency spans are sampled at `sample_ratio`; see [`SpanSampler`].
+///
+/// Returns the tracer provider so it can be flushed and shut down on exit.
+pub async fn init_tracing(sample_ratio: f64) -> anyhow::Result<TracerProvider> {
 	// Check if OTLP endpoint is configured
 	let otlp_endpoint = std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").ok();
 
@@ -29,7 +88,9 @@ pub async fn init_tracing() -> anyhow::Result<()> {
 		.build();
 
 	let tracer = tracer_provider.tracer("heimdall");
-	let telemetry = tracing_opentelemetry::layer().with_tracer(tracer);
+	let telemetry = tracing_opentelemetry::layer()
+		.with_tracer(tracer)
+		.with_filter(SpanSampler::new(sample_ratio));
 
 	// Register the telemetry layer with the existing subscriber
 	// Use try_init to gracefully handle cases where a subscriber is already set
@@ -42,15 +103,76 @@ pub async fn init_tracing() -> anyhow::Result<()> {
 		);
 	}
 
-	Ok(())
+	Ok(tracer_provider)
 }
 
-#[cfg(feature = "unit-tests")]
+#[cfg(all(test, feature = "unit-tests"))]
 mod tests {
+	use super::*;
+	use std::sync::Arc;
+	use std::sync::atomic::AtomicUsize;
+
 	#[tokio::test]
 	async fn tracing_initialization() {
 		// Note: We can only initialize tracing once per process
 		// This test validates the function signature and error handling
-		let _ = super::init_tracing().await;
+		let _ = super::init_tracing(1.0).await;
+	}
+
+	/// Layer counting the spans it sees, by whether they are errors.
+	#[derive(Clone, Default)]
+	struct SpanCounter {
+		spans: Arc<AtomicUsize>,
+		errors: Arc<AtomicUsize>,
+	}
+
+	impl<S: tracing::Subscriber> Layer<S> for SpanCounter {
+		fn on_new_span(
+			&self,
+			attrs: &tracing::span::Attributes<'_>,
+			_id: &tracing::span::Id,
+			_cx: Context<'_, S>,
+		) {
+			let counter = if *attrs.metadata().level() == Level::ERROR {
+				&self.errors
+			} else {
+				&self.spans
+			};
+			counter.fetch_add(1, Ordering::Relaxed);
+		}
+	}
+
+	#[test]
+	fn hot_spans_are_sampled_and_error_spans_kept() {
+		let counter = SpanCounter::default();
+		let subscriber =
+			tracing_subscriber::registry().with(counter.clone().with_filter(SpanSampler::new(0.1)));
+		tracing::subscriber::with_default(subscriber, || {
+			for _ in 0..1000 {
+				let _span = tracing::info_span!("write_batch").entered();
+				let _record = tracing::debug_span!("ingest_record").entered();
+			}
+			for _ in 0..50 {
+				let _span = tracing::error_span!("write_batch").entered();
+			}
+		});
+
+		let kept = counter.spans.load(Ordering::Relaxed);
+		assert!((180..=220).contains(&kept), "kept {} of 2000 spans", kept);
+		assert_eq!(counter.errors.load(Ordering::Relaxed), 50);
+	}
+
+	#[test]
+	fn other_spans_are_not_sampled() {
+		let counter = SpanCounter::default();
+		let subscriber =
+			tracing_subscriber::registry().with(counter.clone().with_filter(SpanSampler::new(0.0)));
+		tracing::subscriber::with_default(subscriber, || {
+			for _ in 0..10 {
+				let _span = tracing::info_span!("ndjson_upload").entered();
+				let _dropped = tracing::info_span!("write_batch").entered();
+			}
+		});
+		assert_eq!(counter.spans.load(Ordering::Relaxed), 10);
 	}
 }
@@ -0,0 +1,112 @@
+//! Write acknowledgements for jobs whose submitter waits on the outcome.
+//!
+//! A [`WriteTracker`] hands out one [`JobAck`] per job. The batcher marks an
+//! ack written once its job is persisted under its own label; an ack that
+//! is dropped unmarked (the job was rejected, dead-lettered, quarantined or
+//! failed) resolves as not written. [`WriteTracker::wait`] returns once
+//! every ack handed out has resolved, with the ids of the written ones.
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::Notify;
+
+#[derive(Debug, Default)]
+struct TrackerInner {
+	next_id: AtomicUsize,
+	pending: AtomicUsize,
+	written: Mutex<HashSet<usize>>,
+	settled: Notify,
+}
+
+/// Collects the outcomes of a group of jobs. Cloning shares the group.
+#[derive(Debug, Clone, Default)]
+pub struct WriteTracker {
+	inner: Arc<TrackerInner>,
+}
+
+impl WriteTracker {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// A new ack in this group. Ids are handed out in order from 0.
+	pub fn ack(&self) -> Arc<JobAck> {
+		self.inner.pending.fetch_add(1, Ordering::SeqCst);
+		Arc::new(JobAck {
+			id: self.inner.next_id.fetch_add(1, Ordering::SeqCst),
+			written: AtomicBool::new(false),
+			tracker: self.inner.clone(),
+		})
+	}
+
+	/// Wait until every ack handed out so far has resolved and return the
+	/// ids of those whose jobs were written.
+	pub async fn wait(&self) -> HashSet<usize> {
+		while self.inner.pending.load(Ordering::SeqCst) > 0 {
+			self.inner.settled.notified().await;
+		}
+		self.inner.written.lock().unwrap().clone()
+	}
+}
+
+/// The outcome of one tracked job, resolved when the last reference to it
+/// is dropped.
+#[derive(Debug)]
+pub struct JobAck {
+	id: usize,
+	written: AtomicBool,
+	tracker: Arc<TrackerInner>,
+}
+
+impl JobAck {
+	pub fn id(&self) -> usize {
+		self.id
+	}
+
+	/// Mark the job as persisted.
+	pub fn written(&self) {
+		self.written.store(true, Ordering::SeqCst);
+	}
+}
+
+impl Drop for JobAck {
+	fn drop(&mut self) {
+		if self.written.load(Ordering::SeqCst) {
+			self.tracker.written.lock().unwrap().insert(self.id);
+		}
+		if self.tracker.pending.fetch_sub(1, Ordering::SeqCst) == 1 {
+			// A stored permit wakes a waiter that has not started waiting yet.
+			self.tracker.settled.notify_one();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[tokio::test]
+	async fn wait_returns_the_written_acks_once_all_resolve() {
+		let tracker = WriteTracker::new();
+		let written = tracker.ack();
+		let rejected = tracker.ack();
+		let pending = tracker.ack();
+		assert_eq!((written.id(), rejected.id(), pending.id()), (0, 1, 2));
+
+		written.written();
+		drop(written);
+		drop(rejected);
+		let waiter = tokio::spawn({
+			let tracker = tracker.clone();
+			async move { tracker.wait().await }
+		});
+		tokio::task::yield_now().await;
+		assert!(!waiter.is_finished());
+
+		pending.written();
+		drop(pending);
+		assert_eq!(waiter.await.unwrap(), HashSet::from([0, 2]));
+	}
+}
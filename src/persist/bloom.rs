@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use serde_json::Value;
+
+/// A fixed-size bloom filter over 128-bit fingerprints.
+///
+/// Bit positions are derived with double hashing (`h1 + i * h2`), so a
+/// single fingerprint computation serves all `k` probes.
+#[derive(Debug, Clone)]
+pub struct BloomFilter {
+	bits: Vec<u64>,
+	num_bits: u64,
+	num_hashes: u32,
+}
+
+impl BloomFilter {
+	/// Size a filter for `capacity` items at the given false-positive rate.
+	pub fn with_rate(capacity: usize, false_positive_rate: f64) -> Self {
+		let n = capacity.max(1) as f64;
+		let p = false_positive_rate.clamp(1e-9, 0.5);
+		let ln2 = std::f64::consts::LN_2;
+		let num_bits = (-(n * p.ln()) / (ln2 * ln2)).ceil().max(64.0) as u64;
+		let num_hashes = ((num_bits as f64 / n) * ln2).round().clamp(1.0, 32.0) as u32;
+		Self {
+			bits: vec![0; num_bits.div_ceil(64) as usize],
+			num_bits,
+			num_hashes,
+		}
+	}
+
+	fn positions(&self, fp: Fingerprint) -> impl Iterator<Item = u64> + '_ {
+		// Force h2 odd so successive probes never collapse onto one bit.
+		let (h1, h2) = (fp.0, fp.1 | 1);
+		(0..self.num_hashes as u64)
+			.map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % self.num_bits)
+	}
+
+	/// Record a fingerprint.
+	pub fn insert(&mut self, fp: Fingerprint) {
+		let positions: Vec<u64> = self.positions(fp).collect();
+		for bit in positions {
+			self.bits[(bit / 64) as usize] |= 1 << (bit % 64);
+		}
+	}
+
+	/// Whether a fingerprint may have been recorded (false positives are
+	/// possible, false negatives are not).
+	pub fn contains(&self, fp: Fingerprint) -> bool {
+		self.positions(fp)
+			.all(|bit| self.bits[(bit / 64) as usize] & (1 << (bit % 64)) != 0)
+	}
+}
+
+/// Two independent 64-bit hashes of a persist tuple.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Fingerprint(u64, u64);
+
+impl Fingerprint {
+	/// Fingerprint a `(label, key, props)` tuple. Props are hashed through
+	/// their serialized JSON form.
+	pub fn of(label: &str, key: &str, props: &Value) -> Self {
+		let props_json = serde_json::to_string(props).unwrap_or_default();
+		let hash_with = |seed: u64| {
+			let mut hasher = DefaultHasher::new();
+			seed.hash(&mut hasher);
+			label.hash(&mut hasher);
+			key.hash(&mut hasher);
+			props_json.hash(&mut hasher);
+			hasher.finish()
+		};
+		Self(
+			hash_with(0x9e37_79b9_7f4a_7c15),
+			hash_with(0xc2b2_ae3d_27d4_eb4f),
+		)
+	}
+
+	/// Hash of a `(label, key)` pair, identifying the node a tuple merges
+	/// into.
+	pub fn key_of(label: &str, key: &str) -> u64 {
+		let mut hasher = DefaultHasher::new();
+		label.hash(&mut hasher);
+		key.hash(&mut hasher);
+		hasher.finish()
+	}
+}
+
+/// Sliding-window membership test for recently merged tuples.
+///
+/// Two generations of bloom filters are kept. Once the current generation
+/// has taken `capacity` inserts it becomes the previous generation and a
+/// fresh one starts, so a tuple is remembered for between `capacity` and
+/// `2 * capacity` subsequent inserts. This bounds memory and lets stale
+/// entries age out without per-entry bookkeeping.
+///
+/// Each generation also remembers the last tuple merged into every node, so
+/// a tuple superseded by a later merge of the same node (`A`, `B`, `A`) is
+/// not reported as seen: skipping it would leave `B` stored.
+#[derive(Debug, Clone)]
+pub struct RecentMergeFilter {
+	current: BloomFilter,
+	previous: BloomFilter,
+	/// Node (see [`Fingerprint::key_of`]) → last tuple merged into it, per
+	/// generation.
+	current_latest: HashMap<u64, Fingerprint>,
+	previous_latest: HashMap<u64, Fingerprint>,
+	inserted: usize,
+	capacity: usize,
+	false_positive_rate: f64,
+}
+
+impl RecentMergeFilter {
+	pub fn new(capacity: usize, false_positive_rate: f64) -> Self {
+		let capacity = capacity.max(1);
+		Self {
+			current: BloomFilter::with_rate(capacity, false_positive_rate),
+			previous: BloomFilter::with_rate(capacity, false_positive_rate),
+			current_latest: HashMap::new(),
+			previous_latest: HashMap::new(),
+			inserted: 0,
+			capacity,
+			false_positive_rate,
+		}
+	}
+
+	/// Whether the tuple was merged into node `key` within the window and
+	/// no other tuple was merged into that node since.
+	pub fn seen(&self, key: u64, fp: Fingerprint) -> bool {
+		if !(self.current.contains(fp) || self.previous.contains(fp)) {
+			return false;
+		}
+		match self
+			.current_latest
+			.get(&key)
+			.or_else(|| self.previous_latest.get(&key))
+		{
+			Some(latest) => *latest == fp,
+			None => true,
+		}
+	}
+
+	/// Record a tuple successfully merged into node `key`.
+	pub fn record(&mut self, key: u64, fp: Fingerprint) {
+		if self.inserted >= self.capacity {
+			self.previous = std::mem::replace(
+				&mut self.current,
+				BloomFilter::with_rate(self.capacity, self.false_positive_rate),
+			);
+			self.previous_latest = std::mem::take(&mut self.current_latest);
+			self.inserted = 0;
+		}
+		self.current.insert(fp);
+		self.current_latest.insert(key, fp);
+		self.inserted += 1;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+
+	#[test]
+	fn identical_tuples_share_a_fingerprint() {
+		let a = Fingerprint::of("FieldValue", "k", &json!({"v": 1}));
+		let b = Fingerprint::of("FieldValue", "k", &json!({"v": 1}));
+		let c = Fingerprint::of("FieldValue", "k", &json!({"v": 2}));
+		assert_eq!(a, b);
+		assert_ne!(a, c);
+	}
+
+	#[test]
+	fn filter_has_no_false_negatives() {
+		let mut filter = RecentMergeFilter::new(1000, 0.01);
+		let fps: Vec<_> = (0..1000)
+			.map(|i| {
+				let key = format!("key-{}", i);
+				(
+					Fingerprint::key_of("FieldValue", &key),
+					Fingerprint::of("FieldValue", &key, &json!({})),
+				)
+			})
+			.collect();
+		for (key, fp) in &fps {
+			filter.record(*key, *fp);
+		}
+		assert!(fps.iter().all(|(key, fp)| filter.seen(*key, *fp)));
+	}
+
+	#[test]
+	fn superseded_tuples_are_not_seen() {
+		let mut filter = RecentMergeFilter::new(2, 0.01);
+		let node = Fingerprint::key_of("FieldValue", "k");
+		let a = Fingerprint::of("FieldValue", "k", &json!({"v": "a"}));
+		let b = Fingerprint::of("FieldValue", "k", &json!({"v": "b"}));
+		filter.record(node, a);
+		filter.record(node, b);
+		assert!(!filter.seen(node, a));
+		assert!(filter.seen(node, b));
+
+		// Still superseded after `b` moves to the previous generation.
+		filter.record(Fingerprint::key_of("FieldValue", "other"), a);
+		assert!(!filter.seen(node, a));
+		assert!(filter.seen(node, b));
+	}
+
+	#[test]
+	fn false_positive_rate_is_roughly_respected() {
+		let mut filter = RecentMergeFilter::new(1000, 0.01);
+		for i in 0..1000 {
+			let key = format!("in-{}", i);
+			filter.record(
+				Fingerprint::key_of("FieldValue", &key),
+				Fingerprint::of("FieldValue", &key, &json!({})),
+			);
+		}
+		let false_positives = (0..10_000)
+			.filter(|i| {
+				let key = format!("out-{}", i);
+				filter.seen(
+					Fingerprint::key_of("FieldValue", &key),
+					Fingerprint::of("FieldValue", &key, &json!({})),
+				)
+			})
+			.count();
+		// Two generations are consulted, so allow a comfortable margin.
+		assert!(
+			false_positives < 500,
+			"too many false positives: {}",
+			false_positives
+		);
+	}
+
+	#[test]
+	fn old_entries_age_out_after_two_generations() {
+		let mut filter = RecentMergeFilter::new(10, 0.01);
+		let node = Fingerprint::key_of("FieldValue", "old");
+		let old = Fingerprint::of("FieldValue", "old", &json!({}));
+		filter.record(node, old);
+		for i in 0..25 {
+			let key = format!("k{}", i);
+			filter.record(
+				Fingerprint::key_of("FieldValue", &key),
+				Fingerprint::of("FieldValue", &key, &json!({})),
+			);
+		}
+		assert!(!filter.seen(node, old));
+	}
+}
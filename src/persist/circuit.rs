@@ -0,0 +1,190 @@
+//! Pause persistence while too many writes fail.
+//!
+//! [`PersistCircuit`] counts batch and per-item write attempts, and how
+//! many of them failed, over a rolling window. Once the window holds at
+//! least `min_attempts` attempts and the failed share exceeds
+//! `failure_rate`, the circuit opens: ingest answers `503` and the batcher
+//! dead-letters jobs instead of writing them. With nothing written, the
+//! failures age out of the window and the circuit closes again, so the
+//! next batches probe the database; if they still fail it reopens.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use prometheus::IntGauge;
+
+/// The window is counted in this many slots, so attempts age out in
+/// steps of a tenth of it.
+const SLOTS: u32 = 10;
+
+/// When the circuit opens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CircuitOptions {
+	/// Share of failed attempts, in `(0, 1)`, above which it opens.
+	pub failure_rate: f64,
+	/// How long an attempt counts towards the rate.
+	pub window: Duration,
+	/// Attempts needed in the window before the rate is acted on.
+	pub min_attempts: u64,
+}
+
+/// Attempts started within one slot of the window.
+struct Slot {
+	start: Instant,
+	attempts: u64,
+	failures: u64,
+}
+
+/// Rolling persist failure rate and whether it has paused ingest.
+pub struct PersistCircuit {
+	opts: CircuitOptions,
+	slots: Mutex<VecDeque<Slot>>,
+	open: AtomicBool,
+	/// Mirrors `open` as `heimdall_ingest_paused`.
+	gauge: IntGauge,
+}
+
+impl PersistCircuit {
+	/// A closed circuit reporting its state through `gauge`.
+	pub fn new(opts: CircuitOptions, gauge: IntGauge) -> Self {
+		gauge.set(0);
+		Self {
+			opts,
+			slots: Mutex::new(VecDeque::new()),
+			open: AtomicBool::new(false),
+			gauge,
+		}
+	}
+
+	/// Count one write attempt and open or close the circuit accordingly.
+	pub fn record(&self, failed: bool) {
+		let now = Instant::now();
+		let width = self.opts.window / SLOTS;
+		let mut slots = self.slots.lock().unwrap();
+		let current = match slots.back() {
+			Some(slot) => now.duration_since(slot.start) < width,
+			None => false,
+		};
+		if !current {
+			slots.push_back(Slot {
+				start: now,
+				attempts: 0,
+				failures: 0,
+			});
+		}
+		let slot = slots.back_mut().expect("slot just ensured");
+		slot.attempts += 1;
+		if failed {
+			slot.failures += 1;
+		}
+		self.evaluate(&mut slots, now);
+	}
+
+	/// Whether persistence is paused. Re-evaluated while open, so the
+	/// circuit closes once its failures have aged out.
+	pub fn is_open(&self) -> bool {
+		if !self.open.load(Ordering::Relaxed) {
+			return false;
+		}
+		let mut slots = self.slots.lock().unwrap();
+		self.evaluate(&mut slots, Instant::now());
+		self.open.load(Ordering::Relaxed)
+	}
+
+	/// Drop slots older than the window and open the circuit iff the
+	/// remaining attempts fail too often.
+	fn evaluate(&self, slots: &mut VecDeque<Slot>, now: Instant) {
+		while slots
+			.front()
+			.is_some_and(|slot| now.duration_since(slot.start) >= self.opts.window)
+		{
+			slots.pop_front();
+		}
+		let (attempts, failures) = slots
+			.iter()
+			.fold((0, 0), |(a, f), slot| (a + slot.attempts, f + slot.failures));
+		let open = attempts >= self.opts.min_attempts.max(1)
+			&& failures as f64 > attempts as f64 * self.opts.failure_rate;
+		self.set_open(open, attempts, failures);
+	}
+
+	fn set_open(&self, open: bool, attempts: u64, failures: u64) {
+		if self.open.swap(open, Ordering::Relaxed) == open {
+			return;
+		}
+		self.gauge.set(i64::from(open));
+		if open {
+			tracing::warn!(
+				attempts,
+				failures,
+				"persist failure rate too high; pausing ingest"
+			);
+		} else {
+			tracing::info!("persist failures aged out; resuming ingest");
+		}
+	}
+}
+
+// Compared by identity: two options are equal when they share a circuit.
+impl PartialEq for PersistCircuit {
+	fn eq(&self, other: &Self) -> bool {
+		std::ptr::eq(self, other)
+	}
+}
+
+impl std::fmt::Debug for PersistCircuit {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("PersistCircuit")
+			.field("opts", &self.opts)
+			.field("open", &self.is_open())
+			.finish()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn circuit(window: Duration) -> PersistCircuit {
+		let gauge = IntGauge::new("test_ingest_paused", "test").unwrap();
+		PersistCircuit::new(
+			CircuitOptions {
+				failure_rate: 0.5,
+				window,
+				min_attempts: 4,
+			},
+			gauge,
+		)
+	}
+
+	#[test]
+	fn opens_only_past_the_rate_with_enough_attempts() {
+		let c = circuit(Duration::from_secs(60));
+		for _ in 0..3 {
+			c.record(true);
+		}
+		assert!(!c.is_open(), "too few attempts to judge");
+		c.record(false);
+		assert!(c.is_open());
+		assert_eq!(c.gauge.get(), 1);
+		for _ in 0..2 {
+			c.record(false);
+		}
+		assert!(!c.is_open(), "3 of 6 failed is not above half");
+		assert_eq!(c.gauge.get(), 0);
+	}
+
+	#[test]
+	fn closes_once_failures_age_out() {
+		let c = circuit(Duration::from_millis(50));
+		for _ in 0..4 {
+			c.record(true);
+		}
+		assert!(c.is_open());
+		std::thread::sleep(Duration::from_millis(60));
+		assert!(!c.is_open());
+		assert_eq!(c.gauge.get(), 0);
+	}
+}
@@ -0,0 +1,85 @@
+//! Detecting canonical key collisions.
+//!
+//! Salted canonical keys are hashes, so two different values could map
+//! to the same key and silently merge into one node. With detection
+//! enabled, the sink stores a fingerprint of the value a key was minted
+//! from (its field type and a SHA-256 prefix of the unsalted key) on every
+//! node. Before a batch is written, and before the sink writes a record
+//! itself because the batcher is full, each job's fingerprint is compared
+//! with the one already stored for its key; a mismatch is a collision,
+//! counted in `heimdall_key_collision_total`, and the job is written under
+//! [`QUARANTINE_LABEL`](super::labels::QUARANTINE_LABEL) instead of
+//! corrupting the existing node.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use sha2::{Digest, Sha256};
+
+use crate::age_client::AgeRepo;
+use crate::persist::PersistJob;
+
+/// Node property holding the key collision fingerprint.
+pub const KEY_FINGERPRINT_PROP: &str = "key_fingerprint";
+
+/// Fingerprints remembered before the cache is cleared.
+const MAX_TRACKED: usize = 100_000;
+
+/// The fingerprint of a value of `field_type` whose unsalted key is
+/// `unsalted_key`.
+pub fn key_fingerprint(field_type: &str, unsalted_key: &str) -> String {
+	let digest = Sha256::digest(unsalted_key.as_bytes());
+	let hash: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+	format!("{}:{}", field_type, hash)
+}
+
+/// Compares job fingerprints with the ones stored for their keys.
+///
+/// Fingerprints seen for each `(label, key)` are cached so a key is looked
+/// up in the repository at most once until the cache fills.
+#[derive(Debug, Default)]
+pub struct KeyCollisionDetector {
+	known: Mutex<HashMap<(String, String), String>>,
+}
+
+impl KeyCollisionDetector {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Whether `job` carries a fingerprint that differs from the one stored
+	/// for its key. Jobs without a fingerprint never collide, and the first
+	/// job seen for a key claims it.
+	pub async fn collides(&self, repo: &dyn AgeRepo, job: &PersistJob) -> bool {
+		let Some(fingerprint) = job.props.get(KEY_FINGERPRINT_PROP).and_then(|v| v.as_str()) else {
+			return false;
+		};
+		let id = (job.label.clone(), job.key.clone());
+		if let Some(known) = self.known.lock().unwrap().get(&id) {
+			return known != fingerprint;
+		}
+
+		let stored = match repo.key_fingerprint(&job.label, &job.key).await {
+			Ok(stored) => stored,
+			Err(e) => {
+				tracing::warn!(key = %job.key, error = %e, "failed to look up key fingerprint");
+				None
+			}
+		};
+		let mut known = self.known.lock().unwrap();
+		if known.len() >= MAX_TRACKED {
+			known.clear();
+		}
+		let claimed = known
+			.entry(id)
+			.or_insert_with(|| stored.unwrap_or_else(|| fingerprint.to_string()));
+		claimed != fingerprint
+	}
+}
+
+// Compared by identity: two options are equal when they share a detector.
+impl PartialEq for KeyCollisionDetector {
+	fn eq(&self, other: &Self) -> bool {
+		std::ptr::eq(self, other)
+	}
+}
@@ -0,0 +1,134 @@
+//! Dead-letter queue for persist jobs the batcher refuses to write.
+
+use std::collections::VecDeque;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::PersistJob;
+
+/// A rejected job and the reason it was rejected.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeadLetter {
+	pub label: String,
+	pub key: String,
+	pub props: Value,
+	pub reason: String,
+}
+
+impl DeadLetter {
+	pub fn new(job: &PersistJob, reason: impl Into<String>) -> Self {
+		Self {
+			label: job.label.clone(),
+			key: job.key.clone(),
+			props: job.props.clone(),
+			reason: reason.into(),
+		}
+	}
+}
+
+/// Holds the most recent dead letters in memory and, when opened with a
+/// path, appends every one to that file as NDJSON.
+pub struct DeadLetterQueue {
+	recent: Mutex<VecDeque<DeadLetter>>,
+	capacity: usize,
+	path: Option<PathBuf>,
+}
+
+impl DeadLetterQueue {
+	/// Keep up to `capacity` dead letters in memory, dropping the oldest.
+	pub fn in_memory(capacity: usize) -> Self {
+		Self {
+			recent: Mutex::new(VecDeque::new()),
+			capacity,
+			path: None,
+		}
+	}
+
+	/// Like `in_memory`, and also append every dead letter to `path`.
+	pub fn open(path: impl AsRef<Path>, capacity: usize) -> Self {
+		Self {
+			path: Some(path.as_ref().to_path_buf()),
+			..Self::in_memory(capacity)
+		}
+	}
+
+	/// Record `letter`. The in-memory copy is kept even when writing the
+	/// file fails.
+	pub fn push(&self, letter: DeadLetter) -> Result<()> {
+		let written = match &self.path {
+			Some(path) => append_line(path, &letter),
+			None => Ok(()),
+		};
+		let mut recent = self.recent.lock().unwrap();
+		if recent.len() >= self.capacity {
+			recent.pop_front();
+		}
+		if self.capacity > 0 {
+			recent.push_back(letter);
+		}
+		written
+	}
+
+	/// The dead letters still held in memory, oldest first.
+	pub fn recent(&self) -> Vec<DeadLetter> {
+		self.recent.lock().unwrap().iter().cloned().collect()
+	}
+}
+
+// Compared by identity: two options are equal when they share a queue.
+impl PartialEq for DeadLetterQueue {
+	fn eq(&self, other: &Self) -> bool {
+		std::ptr::eq(self, other)
+	}
+}
+
+impl std::fmt::Debug for DeadLetterQueue {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("DeadLetterQueue")
+			.field("capacity", &self.capacity)
+			.field("path", &self.path)
+			.finish_non_exhaustive()
+	}
+}
+
+fn append_line(path: &Path, letter: &DeadLetter) -> Result<()> {
+	let mut line = serde_json::to_vec(letter).context("failed to serialize dead letter")?;
+	line.push(b'\n');
+	OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(path)
+		.and_then(|mut f| f.write_all(&line))
+		.with_context(|| format!("failed to append to dead-letter file {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+
+	#[test]
+	fn keeps_the_most_recent_letters_and_appends_to_file() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("dead-letters.ndjson");
+		let queue = DeadLetterQueue::open(&path, 2);
+		for key in ["a", "b", "c"] {
+			let job = PersistJob::new("FieldValue", key, json!({}));
+			queue.push(DeadLetter::new(&job, "too big")).unwrap();
+		}
+
+		let keys: Vec<String> = queue.recent().into_iter().map(|l| l.key).collect();
+		assert_eq!(keys, vec!["b", "c"]);
+		let written = std::fs::read_to_string(&path).unwrap();
+		assert_eq!(written.lines().count(), 3);
+		let first: DeadLetter = serde_json::from_str(written.lines().next().unwrap()).unwrap();
+		assert_eq!(first.key, "a");
+		assert_eq!(first.reason, "too big");
+	}
+}
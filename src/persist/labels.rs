@@ -0,0 +1,108 @@
+//! Allowlist of node labels and edge types accepted from ingest and sync.
+//!
+//! Labels reach Cypher from data (field kinds, replicated entries, edge
+//! types discovered by enrichment). With an allowlist configured, anything
+//! outside it is written under [`QUARANTINE_LABEL`] or
+//! [`QUARANTINE_EDGE_TYPE`] instead of minting a new label, and counted in
+//! `heimdall_ingest_label_quarantined_total`.
+
+use std::collections::HashSet;
+
+use crate::observability::MetricsRegistry;
+
+/// Label given to nodes whose label is not allowed.
+pub const QUARANTINE_LABEL: &str = "UnclassifiedValue";
+
+/// Type given to edges whose type is not allowed.
+pub const QUARANTINE_EDGE_TYPE: &str = "UNCLASSIFIED";
+
+/// Permitted labels and edge types. An empty list permits everything of
+/// its kind, which is the default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LabelAllowlist {
+	labels: HashSet<String>,
+	edge_types: HashSet<String>,
+}
+
+impl LabelAllowlist {
+	pub fn new<L, E>(labels: L, edge_types: E) -> Self
+	where
+		L: IntoIterator,
+		L::Item: Into<String>,
+		E: IntoIterator,
+		E::Item: Into<String>,
+	{
+		Self {
+			labels: labels.into_iter().map(Into::into).collect(),
+			edge_types: edge_types.into_iter().map(Into::into).collect(),
+		}
+	}
+
+	/// Whether nodes may be labelled `label`.
+	pub fn permits_label(&self, label: &str) -> bool {
+		self.labels.is_empty() || label == QUARANTINE_LABEL || self.labels.contains(label)
+	}
+
+	/// Whether edges may have type `edge_type`.
+	pub fn permits_edge_type(&self, edge_type: &str) -> bool {
+		self.edge_types.is_empty()
+			|| edge_type == QUARANTINE_EDGE_TYPE
+			|| self.edge_types.contains(edge_type)
+	}
+
+	/// `label` when allowed, otherwise [`QUARANTINE_LABEL`].
+	pub fn node_label<'a>(&self, label: &'a str, metrics: &MetricsRegistry) -> &'a str {
+		if self.permits_label(label) {
+			return label;
+		}
+		log::debug!("quarantining node label {:?}", label);
+		metrics.ingest_label_quarantined_total.inc();
+		QUARANTINE_LABEL
+	}
+
+	/// `edge_type` when allowed, otherwise [`QUARANTINE_EDGE_TYPE`].
+	pub fn edge_type<'a>(&self, edge_type: &'a str, metrics: &MetricsRegistry) -> &'a str {
+		if self.permits_edge_type(edge_type) {
+			return edge_type;
+		}
+		log::debug!("quarantining edge type {:?}", edge_type);
+		metrics.ingest_label_quarantined_total.inc();
+		QUARANTINE_EDGE_TYPE
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn allowed_labels_pass_and_others_are_quarantined() {
+		let metrics = MetricsRegistry::new();
+		let allowlist = LabelAllowlist::new(["FieldValue", "Dump"], ["RESOLVES_TO"]);
+
+		assert_eq!(allowlist.node_label("FieldValue", &metrics), "FieldValue");
+		assert_eq!(allowlist.edge_type("RESOLVES_TO", &metrics), "RESOLVES_TO");
+		assert_eq!(metrics.ingest_label_quarantined_total.get(), 0);
+
+		assert_eq!(
+			allowlist.node_label("EvilLabel", &metrics),
+			QUARANTINE_LABEL
+		);
+		assert_eq!(allowlist.edge_type("OWNS", &metrics), QUARANTINE_EDGE_TYPE);
+		assert_eq!(metrics.ingest_label_quarantined_total.get(), 2);
+
+		// The quarantine label itself is always accepted.
+		assert_eq!(
+			allowlist.node_label(QUARANTINE_LABEL, &metrics),
+			QUARANTINE_LABEL
+		);
+		assert_eq!(metrics.ingest_label_quarantined_total.get(), 2);
+	}
+
+	#[test]
+	fn empty_allowlist_permits_everything() {
+		let allowlist = LabelAllowlist::default();
+		assert!(allowlist.permits_label("Anything"));
+		assert!(allowlist.permits_edge_type("ANY_EDGE"));
+	}
+}
@@ -0,0 +1,265 @@
+//! Versioned schema migrations.
+//!
+//! Each migration in [`MIGRATIONS`] is recorded in the
+//! `heimdall_schema_migrations` table, first as `started` and, once its SQL
+//! has run, as `completed`. Completed migrations are skipped on later runs.
+//! A migration left `started` was interrupted part-way, so the database is
+//! in an unknown state: the runner refuses to continue until an operator has
+//! inspected it and re-runs with `force`. Transient connection errors are
+//! retried with backoff before a migration is given up on.
+//!
+//! The runner itself is [`crate::age_client::AgeClient::run_migrations`].
+
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+
+use crate::age_client::{AgeError, AgeResult};
+
+/// Table the runner records migration progress in.
+pub const MIGRATIONS_TABLE: &str = "heimdall_schema_migrations";
+
+/// One versioned migration script.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Migration {
+	pub version: i64,
+	pub name: &'static str,
+	pub sql: &'static str,
+}
+
+/// The schema migrations shipped with this build, in the order they apply.
+pub const MIGRATIONS: &[Migration] = &[
+	Migration {
+		version: 1,
+		name: "create_graph",
+		sql: include_str!("../../sql/v1/001-create_graph.sql"),
+	},
+	Migration {
+		version: 2,
+		name: "property_indexes",
+		sql: include_str!("../../sql/v1/002-property-indexes.sql"),
+	},
+];
+
+/// Recorded progress of a migration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationStatus {
+	Started,
+	Completed,
+}
+
+impl MigrationStatus {
+	pub fn as_str(self) -> &'static str {
+		match self {
+			MigrationStatus::Started => "started",
+			MigrationStatus::Completed => "completed",
+		}
+	}
+
+	/// Parse a status stored in [`MIGRATIONS_TABLE`].
+	pub fn parse(s: &str) -> Option<Self> {
+		match s {
+			"started" => Some(MigrationStatus::Started),
+			"completed" => Some(MigrationStatus::Completed),
+			_ => None,
+		}
+	}
+}
+
+/// What the runner does with a migration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationStep {
+	/// Record it as started, run it, then record it as completed.
+	Apply,
+	/// Already completed; nothing to do.
+	Skip,
+	/// Interrupted on an earlier run; stop without touching it.
+	Blocked,
+}
+
+/// Step for a migration whose recorded status is `status` (`None` if it
+/// was never started). `force` re-applies an interrupted migration.
+pub fn next_step(status: Option<MigrationStatus>, force: bool) -> MigrationStep {
+	match status {
+		None => MigrationStep::Apply,
+		Some(MigrationStatus::Completed) => MigrationStep::Skip,
+		Some(MigrationStatus::Started) if force => MigrationStep::Apply,
+		Some(MigrationStatus::Started) => MigrationStep::Blocked,
+	}
+}
+
+/// How the runner treats interrupted migrations and transient errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MigrationOptions {
+	/// Re-apply migrations left `started` by an interrupted run.
+	pub force: bool,
+	/// Attempts per database call when it fails with a retryable error.
+	pub max_attempts: u32,
+	/// Delay before the second attempt, doubled for each one after it.
+	pub backoff: Duration,
+}
+
+impl Default for MigrationOptions {
+	fn default() -> Self {
+		Self {
+			force: false,
+			max_attempts: 3,
+			backoff: Duration::from_millis(500),
+		}
+	}
+}
+
+/// Versions applied and skipped by one run.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MigrationReport {
+	pub applied: Vec<i64>,
+	pub skipped: Vec<i64>,
+}
+
+/// Why migrations stopped.
+#[derive(Debug)]
+pub enum MigrationError {
+	/// The migration was started by an earlier run but never completed.
+	Interrupted { version: i64, name: &'static str },
+	/// A database call failed, after retries if it was retryable.
+	Database { version: i64, source: AgeError },
+}
+
+impl fmt::Display for MigrationError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			MigrationError::Interrupted { version, name } => write!(
+				f,
+				"migration {} ({}) was started but never completed; inspect the database, then re-run with --force",
+				version, name
+			),
+			MigrationError::Database { version, source } => {
+				write!(f, "migration {} failed: {}", version, source)
+			}
+		}
+	}
+}
+
+impl std::error::Error for MigrationError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			MigrationError::Interrupted { .. } => None,
+			MigrationError::Database { source, .. } => Some(source),
+		}
+	}
+}
+
+/// Run `op` until it succeeds, fails with a non-retryable error or has been
+/// attempted `opts.max_attempts` times.
+pub async fn with_retries<T, F, Fut>(opts: &MigrationOptions, mut op: F) -> AgeResult<T>
+where
+	F: FnMut() -> Fut,
+	Fut: Future<Output = AgeResult<T>>,
+{
+	let mut delay = opts.backoff;
+	let mut attempt = 1;
+	loop {
+		match op().await {
+			Err(e) if e.is_retryable() && attempt < opts.max_attempts => {
+				tracing::warn!(attempt, error = %e, "migration step failed; retrying");
+				tokio::time::sleep(delay).await;
+				delay = delay.saturating_mul(2);
+				attempt += 1;
+			}
+			res => return res,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::sync::atomic::{AtomicU32, Ordering};
+
+	#[test]
+	fn steps_follow_the_recorded_status() {
+		assert_eq!(next_step(None, false), MigrationStep::Apply);
+		assert_eq!(
+			next_step(Some(MigrationStatus::Completed), false),
+			MigrationStep::Skip
+		);
+		assert_eq!(
+			next_step(Some(MigrationStatus::Completed), true),
+			MigrationStep::Skip
+		);
+		assert_eq!(
+			next_step(Some(MigrationStatus::Started), false),
+			MigrationStep::Blocked
+		);
+		assert_eq!(
+			next_step(Some(MigrationStatus::Started), true),
+			MigrationStep::Apply
+		);
+	}
+
+	#[test]
+	fn statuses_round_trip() {
+		for status in [MigrationStatus::Started, MigrationStatus::Completed] {
+			assert_eq!(MigrationStatus::parse(status.as_str()), Some(status));
+		}
+		assert_eq!(MigrationStatus::parse("done"), None);
+	}
+
+	#[test]
+	fn migrations_are_ordered_by_version() {
+		assert!(MIGRATIONS.windows(2).all(|w| w[0].version < w[1].version));
+	}
+
+	fn fast(max_attempts: u32) -> MigrationOptions {
+		MigrationOptions {
+			force: false,
+			max_attempts,
+			backoff: Duration::from_millis(1),
+		}
+	}
+
+	#[tokio::test]
+	async fn transient_errors_are_retried() {
+		let calls = AtomicU32::new(0);
+		let res = with_retries(&fast(3), || async {
+			if calls.fetch_add(1, Ordering::SeqCst) < 2 {
+				Err(AgeError::Connection("reset".to_string()))
+			} else {
+				Ok(7)
+			}
+		})
+		.await;
+		assert_eq!(res.unwrap(), 7);
+		assert_eq!(calls.load(Ordering::SeqCst), 3);
+	}
+
+	#[tokio::test]
+	async fn retries_stop_at_the_attempt_limit_and_on_permanent_errors() {
+		let calls = AtomicU32::new(0);
+		let res: AgeResult<()> = with_retries(&fast(2), || async {
+			calls.fetch_add(1, Ordering::SeqCst);
+			Err(AgeError::Transient("busy".to_string()))
+		})
+		.await;
+		assert!(res.is_err());
+		assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+		calls.store(0, Ordering::SeqCst);
+		let res: AgeResult<()> = with_retries(&fast(5), || async {
+			calls.fetch_add(1, Ordering::SeqCst);
+			Err(AgeError::Query("syntax error".to_string()))
+		})
+		.await;
+		assert!(res.is_err());
+		assert_eq!(calls.load(Ordering::SeqCst), 1);
+	}
+
+	#[test]
+	fn interrupted_error_asks_for_force() {
+		let err = MigrationError::Interrupted {
+			version: 2,
+			name: "property_indexes",
+		};
+		assert!(err.to_string().contains("--force"));
+	}
+}
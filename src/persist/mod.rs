@@ -1,10 +1,32 @@
-use std::sync::Arc;
+pub mod ack;
+pub mod bloom;
+pub mod circuit;
+pub mod collisions;
+pub mod dead_letter;
+pub mod labels;
+pub mod migrations;
+pub mod rekey;
+pub mod retention;
+pub mod schema;
+pub mod sightings;
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
 use tokio::sync::mpsc::{self, Sender};
+use tokio::task::JoinHandle;
 use tokio::time::Duration;
 
-use crate::age_client::AgeRepo;
+use crate::age_client::{AgeRepo, Edge, GraphNode};
 use crate::observability::MetricsRegistry;
+use crate::sync::ChangeRecorder;
+use ack::JobAck;
+use bloom::{Fingerprint, RecentMergeFilter};
+use circuit::PersistCircuit;
+use collisions::KeyCollisionDetector;
+use dead_letter::{DeadLetter, DeadLetterQueue};
+use labels::QUARANTINE_LABEL;
+use schema::{LabelSchemas, SchemaViolation};
 use serde_json::Value;
 
 /// A single persistence job: represents a normalized and sanitized record
@@ -20,6 +42,56 @@ pub struct PersistJob {
 	/// (no raw PII). Prefer storing canonical values rather than original raw
 	/// payloads.
 	pub props: Value,
+	/// Edges from the node, merged in the same transaction as it.
+	pub edges: Vec<Edge>,
+	/// When the record entered Heimdall. Used only for the ingest-to-persist
+	/// latency metric; never persisted.
+	pub arrived_at: Instant,
+	/// Marked written once the job is persisted under its own label; see
+	/// [`ack`].
+	pub ack: Option<Arc<JobAck>>,
+}
+
+impl PersistJob {
+	/// Create a job stamped as arriving now.
+	pub fn new(label: impl Into<String>, key: impl Into<String>, props: Value) -> Self {
+		Self {
+			label: label.into(),
+			key: key.into(),
+			props,
+			edges: Vec::new(),
+			arrived_at: Instant::now(),
+			ack: None,
+		}
+	}
+
+	/// Override the arrival time, e.g. with the start of the request that
+	/// produced the job.
+	pub fn with_arrival(mut self, arrived_at: Instant) -> Self {
+		self.arrived_at = arrived_at;
+		self
+	}
+
+	/// Merge `edges` from the node along with it.
+	pub fn with_edges(mut self, edges: Vec<Edge>) -> Self {
+		self.edges = edges;
+		self
+	}
+
+	/// Report the job's outcome through `ack`.
+	pub fn with_ack(mut self, ack: Arc<JobAck>) -> Self {
+		self.ack = Some(ack);
+		self
+	}
+
+	/// Mark the job's ack, if any, written, unless it was quarantined.
+	fn acknowledge(&self) {
+		if let Some(ack) = &self.ack {
+			if self.label != QUARANTINE_LABEL {
+				ack.written();
+			}
+		}
+	}
 }
 
 /// Sender side exported type
@@ -38,13 +110,187 @@ pub fn submit_job(
 	sender.try_send(job)
 }
 
+/// Like [`submit_job`], but waits for room in the channel instead of
+/// failing when it is full.
+pub async fn submit_job_waiting(
+	sender: &PersistSender,
+	job: PersistJob,
+	metrics: &Arc<MetricsRegistry>,
+) -> Result<(), tokio::sync::mpsc::error::SendError<PersistJob>> {
+	metrics.persist_jobs_submitted.inc();
+	metrics.persist_queue_length.inc();
+	sender.send(job).await.inspect_err(|_| {
+		metrics.persist_queue_length.dec();
+	})
+}
+
+/// Settings for the recent-merge filter used to skip redundant props
+/// merges.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DedupOptions {
+	/// Number of distinct tuples per filter generation.
+	pub capacity: usize,
+	/// Target false-positive rate per generation. A false positive skips a
+	/// props merge that would have been a no-op only with this probability,
+	/// so keep it small.
+	pub false_positive_rate: f64,
+}
+
+impl Default for DedupOptions {
+	fn default() -> Self {
+		Self {
+			capacity: 100_000,
+			false_positive_rate: 0.001,
+		}
+	}
+}
+
+/// What the batcher does with a job whose props exceed the size cap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OversizedProps {
+	/// Move the job to the dead-letter queue instead of writing it.
+	Reject,
+	/// Shorten the longest string properties until the props fit; jobs
+	/// that still do not fit are rejected.
+	Truncate,
+}
+
+impl std::str::FromStr for OversizedProps {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.trim().to_ascii_lowercase().as_str() {
+			"reject" => Ok(Self::Reject),
+			"truncate" => Ok(Self::Truncate),
+			other => Err(format!("unknown oversized props policy '{}'", other)),
+		}
+	}
+}
+
+/// Cap on the serialized size of `PersistJob.props`. Oversized props can
+/// exceed AGE's statement limits and fail the whole batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PropsLimit {
+	pub max_bytes: usize,
+	pub policy: OversizedProps,
+}
+
+/// Caps on the shape of `PersistJob.props` values, which AGE stores poorly
+/// when deeply nested or holding huge arrays. `max_depth` counts the
+/// objects and arrays nested within one property, so `0` allows only
+/// scalars; `max_array_len` applies to arrays at any depth. Truncation
+/// replaces containers nested too deeply with `null` and keeps the first
+/// `max_array_len` elements of longer arrays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PropsShapeLimit {
+	pub max_depth: usize,
+	pub max_array_len: usize,
+	pub policy: OversizedProps,
+}
+
+impl PropsShapeLimit {
+	/// Why `props` exceed the limits, or `None` if they fit.
+	fn violation(&self, props: &Value) -> Option<String> {
+		let (depth, longest) = match props {
+			Value::Object(map) => map
+				.values()
+				.map(value_shape)
+				.fold((0, 0), |(d, l), (vd, vl)| (d.max(vd), l.max(vl))),
+			other => value_shape(other),
+		};
+		if depth > self.max_depth {
+			Some(format!(
+				"props nest {} deep, limit is {}",
+				depth, self.max_depth
+			))
+		} else if longest > self.max_array_len {
+			Some(format!(
+				"props hold an array of {} elements, limit is {}",
+				longest, self.max_array_len
+			))
+		} else {
+			None
+		}
+	}
+
+	/// Cut `props` down to the limits.
+	fn truncate(&self, props: &mut Value) {
+		match props {
+			Value::Object(map) => map
+				.values_mut()
+				.for_each(|v| cut_value(v, self.max_depth, self.max_array_len)),
+			other => cut_value(other, self.max_depth, self.max_array_len),
+		}
+	}
+}
+
+/// Options controlling the background persistence batcher.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatcherOptions {
+	pub channel_capacity: usize,
+	pub batch_size: usize,
+	pub flush_interval_ms: u64,
+	/// When set, jobs whose `(label, key, props)` tuple was merged recently
+	/// are written as bare sightings: they still bump `seen_count` and
+	/// `last_seen` but do not re-apply their props. Disabled by default.
+	pub dedup: Option<DedupOptions>,
+	/// Maximum number of batch writes in flight at once. Writes for the
+	/// same key never overlap, so per-key ordering is preserved.
+	pub flush_concurrency: usize,
+	/// When set, every successfully persisted job is recorded in the sync
+	/// change log. Disabled by default.
+	pub changes: Option<ChangeRecorder>,
+	/// When set, jobs whose props exceed the cap are truncated or
+	/// rejected. Disabled by default.
+	pub props_limit: Option<PropsLimit>,
+	/// When set, jobs whose props nest too deeply or hold too long an
+	/// array are truncated or rejected. Disabled by default.
+	pub props_shape: Option<PropsShapeLimit>,
+	/// Where rejected jobs go. Without a queue they are logged and dropped.
+	pub dead_letter: Option<Arc<DeadLetterQueue>>,
+	/// When set, jobs whose props violate their label's schema are
+	/// quarantined or rejected. Disabled by default.
+	pub schemas: Option<Arc<LabelSchemas>>,
+	/// When set, write outcomes feed the circuit, and while it is open
+	/// jobs are dead-lettered instead of written. Disabled by default.
+	pub circuit: Option<Arc<PersistCircuit>>,
+	/// When set, jobs whose key fingerprint differs from the one stored
+	/// for their key are quarantined. Disabled by default.
+	pub collisions: Option<Arc<KeyCollisionDetector>>,
+}
+
+impl Default for BatcherOptions {
+	fn default() -> Self {
+		Self {
+			channel_capacity: 10_000,
+			batch_size: 100,
+			flush_interval_ms: 1000,
+			dedup: None,
+			flush_concurrency: 1,
+			changes: None,
+			props_limit: None,
+			props_shape: None,
+			dead_letter: None,
+			schemas: None,
+			circuit: None,
+			collisions: None,
+		}
+	}
+}
+
 /// Start a background batcher task that collects persistence jobs and
 /// flushes them to the provided `repo` either when `batch_size` is
 /// reached or when `flush_interval_ms` elapses. Returns the Sender which
 /// can be used to submit `PersistJob`s.
 ///
+/// Jobs for the same canonical key are applied in submission order: the
+/// channel and buffer are FIFO, size- and interval-triggered flushes both
+/// run on the batcher task, concurrent writes never share a key (see
+/// `Flusher`), and dedup never strips the props of a job whose node was
+/// merged with different props since. The last job submitted for a key is
+/// the one left stored.
+///
 /// This function spawns a detached task and returns immediately.
-#[tracing::instrument(skip(repo, metrics))]
 pub fn start_batcher(
 	repo: Arc<dyn AgeRepo>,
 	metrics: Arc<MetricsRegistry>,
@@ -52,12 +298,51 @@ pub fn start_batcher(
 	batch_size: usize,
 	flush_interval_ms: u64,
 ) -> PersistSender {
-	let (tx, mut rx) = mpsc::channel::<PersistJob>(channel_capacity);
+	start_batcher_with_options(
+		repo,
+		metrics,
+		BatcherOptions {
+			channel_capacity,
+			batch_size,
+			flush_interval_ms,
+			..BatcherOptions::default()
+		},
+	)
+}
+
+/// Start the background batcher with explicit options. See `start_batcher`.
+#[tracing::instrument(skip(repo, metrics))]
+pub fn start_batcher_with_options(
+	repo: Arc<dyn AgeRepo>,
+	metrics: Arc<MetricsRegistry>,
+	opts: BatcherOptions,
+) -> PersistSender {
+	let (tx, mut rx) = mpsc::channel::<PersistJob>(opts.channel_capacity);
+	let batch_size = opts.batch_size.max(1);
+	let mut flusher = Flusher {
+		repo,
+		metrics: metrics.clone(),
+		filter: opts.dedup.map(|d| {
+			Arc::new(Mutex::new(RecentMergeFilter::new(
+				d.capacity,
+				d.false_positive_rate,
+			)))
+		}),
+		max_in_flight: opts.flush_concurrency.max(1),
+		in_flight: Vec::new(),
+		changes: opts.changes,
+		props_limit: opts.props_limit,
+		props_shape: opts.props_shape,
+		dead_letter: opts.dead_letter,
+		schemas: opts.schemas,
+		circuit: opts.circuit,
+		collisions: opts.collisions,
+	};
 
 	// Spawn the background worker
 	tokio::spawn(async move {
 		let mut buffer: Vec<PersistJob> = Vec::with_capacity(batch_size);
-		let flush_interval = Duration::from_millis(flush_interval_ms);
+		let flush_interval = Duration::from_millis(opts.flush_interval_ms);
 
 		loop {
 			tokio::select! {
@@ -68,21 +353,22 @@ pub fn start_batcher(
 							metrics.persist_queue_length.dec();
 							buffer.push(job);
 							if buffer.len() >= batch_size {
-								flush_buffer(&repo, &metrics, &mut buffer).await;
+								flusher.flush(&mut buffer).await;
 							}
 						}
 						None => {
 							// Channel closed; flush remaining and exit
 							if !buffer.is_empty() {
-								flush_buffer(&repo, &metrics, &mut buffer).await;
+								flusher.flush(&mut buffer).await;
 							}
+							flusher.wait_idle().await;
 							break;
 						}
 					}
 				}
 				_ = tokio::time::sleep(flush_interval) => {
 					if !buffer.is_empty() {
-						flush_buffer(&repo, &metrics, &mut buffer).await;
+						flusher.flush(&mut buffer).await;
 					}
 				}
 			}
@@ -92,128 +378,1167 @@ pub fn start_batcher(
 	tx
 }
 
-#[tracing::instrument(skip(repo, metrics, buffer), fields(batch_size = buffer.len()))]
-async fn flush_buffer(
-	repo: &Arc<dyn AgeRepo>,
-	metrics: &Arc<MetricsRegistry>,
-	buffer: &mut Vec<PersistJob>,
-) {
-	// Drain FIFO order
-	let jobs: Vec<PersistJob> = buffer.drain(..).collect();
-	if jobs.is_empty() {
-		return;
+/// Dispatches drained batches as concurrent writes.
+///
+/// At most `max_in_flight` writes run at once. A batch sharing a key with
+/// an in-flight write waits for that write first, so writes for one key
+/// never overlap and land in submission order. Within a batch, jobs are
+/// written in buffer order.
+///
+/// Batches are dispatched only from the batcher task, one at a time, so a
+/// batch flushed on the interval can never overtake one flushed on size.
+struct Flusher {
+	repo: Arc<dyn AgeRepo>,
+	metrics: Arc<MetricsRegistry>,
+	filter: Option<Arc<Mutex<RecentMergeFilter>>>,
+	max_in_flight: usize,
+	/// In-flight writes, oldest first, with the keys each one carries.
+	in_flight: Vec<(HashSet<String>, JoinHandle<()>)>,
+	changes: Option<ChangeRecorder>,
+	props_limit: Option<PropsLimit>,
+	props_shape: Option<PropsShapeLimit>,
+	dead_letter: Option<Arc<DeadLetterQueue>>,
+	schemas: Option<Arc<LabelSchemas>>,
+	circuit: Option<Arc<PersistCircuit>>,
+	collisions: Option<Arc<KeyCollisionDetector>>,
+}
+
+impl Flusher {
+	/// Drain `buffer` into a new write once ordering and the concurrency
+	/// limit allow it.
+	async fn flush(&mut self, buffer: &mut Vec<PersistJob>) {
+		// Drain FIFO order
+		let mut jobs = std::mem::take(buffer);
+		if self.circuit.as_ref().is_some_and(|c| c.is_open()) {
+			for j in &jobs {
+				let reason = "persistence paused: write failure rate too high".to_string();
+				dead_letter(self.dead_letter.as_deref(), j, reason);
+			}
+			return;
+		}
+		self.limit_shape(&mut jobs);
+		self.limit_props(&mut jobs);
+		self.check_schemas(&mut jobs);
+		self.check_collisions(&mut jobs).await;
+		if jobs.is_empty() {
+			return;
+		}
+		let keys: HashSet<String> = jobs.iter().map(|j| j.key.clone()).collect();
+
+		self.in_flight.retain(|(_, handle)| !handle.is_finished());
+		while let Some(pos) = self
+			.in_flight
+			.iter()
+			.position(|(in_flight_keys, _)| !in_flight_keys.is_disjoint(&keys))
+		{
+			let (_, handle) = self.in_flight.remove(pos);
+			let _ = handle.await;
+		}
+		while self.in_flight.len() >= self.max_in_flight {
+			let (_, handle) = self.in_flight.remove(0);
+			let _ = handle.await;
+		}
+
+		// Checked only now so earlier writes of these keys have recorded
+		// their tuples in the filter.
+		let fingerprints = self.skip_recent(&mut jobs);
+		let handle = tokio::spawn(write_batch(
+			self.repo.clone(),
+			self.metrics.clone(),
+			jobs,
+			fingerprints,
+			self.filter.clone(),
+			self.changes.clone(),
+			self.circuit.clone(),
+		));
+		self.in_flight.push((keys, handle));
+	}
+
+	/// Strip the props of jobs whose exact tuple is what their node already
+	/// holds: the node's previous job in this batch, or its last recent
+	/// merge. Merging those props would not change the stored node, but the
+	/// job is still a sighting, so it stays in the batch to bump
+	/// `seen_count` and `last_seen`. A tuple superseded by a different one
+	/// is never stripped, so the last job for a key wins.
+	///
+	/// Returns, per job in order, the fingerprint of the props it merges,
+	/// or `None` for a bare sighting. Empty when dedup is disabled.
+	fn skip_recent(&self, jobs: &mut [PersistJob]) -> Vec<Option<Fingerprint>> {
+		let mut fingerprints = Vec::new();
+		if let Some(f) = &self.filter {
+			let f = f.lock().unwrap();
+			// Node → fingerprint of its last props merged in this batch.
+			let mut in_batch: HashMap<u64, Fingerprint> = HashMap::new();
+			for j in jobs.iter_mut() {
+				let node = Fingerprint::key_of(&j.label, &j.key);
+				let fp = Fingerprint::of(&j.label, &j.key, &j.props);
+				let unchanged = match in_batch.get(&node) {
+					Some(last) => *last == fp,
+					None => f.seen(node, fp),
+				};
+				if unchanged {
+					self.metrics.persist_skipped_duplicates_total.inc();
+					j.props = Value::Object(Default::default());
+					fingerprints.push(None);
+				} else {
+					in_batch.insert(node, fp);
+					fingerprints.push(Some(fp));
+				}
+			}
+		}
+		fingerprints
 	}
 
+	/// Apply the props depth and array length caps, truncating or
+	/// dead-lettering jobs that exceed them according to the policy.
+	fn limit_shape(&self, jobs: &mut Vec<PersistJob>) {
+		let Some(limit) = self.props_shape else {
+			return;
+		};
+		jobs.retain_mut(|j| {
+			let Some(reason) = limit.violation(&j.props) else {
+				return true;
+			};
+			self.metrics.persist_props_shape_limited_total.inc();
+			if limit.policy == OversizedProps::Truncate {
+				limit.truncate(&mut j.props);
+				return true;
+			}
+			dead_letter(self.dead_letter.as_deref(), j, reason);
+			false
+		});
+	}
+
+	/// Apply the props size cap, truncating or dead-lettering oversized
+	/// jobs according to the policy.
+	fn limit_props(&self, jobs: &mut Vec<PersistJob>) {
+		let Some(limit) = self.props_limit else {
+			return;
+		};
+		jobs.retain_mut(|j| {
+			let size = props_size(&j.props);
+			if size <= limit.max_bytes {
+				return true;
+			}
+			self.metrics.persist_oversized_props_total.inc();
+			if limit.policy == OversizedProps::Truncate
+				&& truncate_props(&mut j.props, limit.max_bytes)
+			{
+				return true;
+			}
+			let reason = format!("props are {} bytes, limit is {}", size, limit.max_bytes);
+			dead_letter(self.dead_letter.as_deref(), j, reason);
+			false
+		});
+	}
+
+	/// Check jobs against their label's schema, relabelling or
+	/// dead-lettering violations according to the policy.
+	fn check_schemas(&self, jobs: &mut Vec<PersistJob>) {
+		let Some(schemas) = &self.schemas else {
+			return;
+		};
+		jobs.retain_mut(|j| {
+			let Some(reason) = schemas.violation(&j.label, &j.props) else {
+				return true;
+			};
+			self.metrics.persist_schema_violation_total.inc();
+			match schemas.policy {
+				SchemaViolation::Quarantine => {
+					tracing::debug!(key = %j.key, %reason, "quarantining node");
+					j.label = QUARANTINE_LABEL.to_string();
+					true
+				}
+				SchemaViolation::Reject => {
+					dead_letter(self.dead_letter.as_deref(), j, reason);
+					false
+				}
+			}
+		});
+	}
+
+	/// Quarantine jobs whose key already belongs to a different value.
+	async fn check_collisions(&self, jobs: &mut [PersistJob]) {
+		let Some(collisions) = &self.collisions else {
+			return;
+		};
+		for j in jobs.iter_mut() {
+			if j.label == QUARANTINE_LABEL || !collisions.collides(self.repo.as_ref(), j).await {
+				continue;
+			}
+			self.metrics.key_collision_total.inc();
+			tracing::warn!(label = %j.label, key = %j.key, "canonical key collision; quarantining node");
+			j.label = QUARANTINE_LABEL.to_string();
+		}
+	}
+
+	/// Wait for every in-flight write to finish.
+	async fn wait_idle(&mut self) {
+		for (_, handle) in self.in_flight.drain(..) {
+			let _ = handle.await;
+		}
+	}
+}
+
+#[tracing::instrument(skip_all, fields(batch_size = jobs.len()))]
+async fn write_batch(
+	repo: Arc<dyn AgeRepo>,
+	metrics: Arc<MetricsRegistry>,
+	jobs: Vec<PersistJob>,
+	fingerprints: Vec<Option<Fingerprint>>,
+	filter: Option<Arc<Mutex<RecentMergeFilter>>>,
+	changes: Option<ChangeRecorder>,
+	circuit: Option<Arc<PersistCircuit>>,
+) {
 	// Attempt a single batched merge for improved throughput. Implementations
-	// may fall back to individual merges when the batch fails.
+	// may fall back to individual merges when the batch fails. Jobs with
+	// edges are merged one at a time so each node commits with its edges.
 	let tuples: Vec<(String, String, Value)> = jobs
 		.iter()
+		.filter(|j| j.edges.is_empty())
 		.map(|j| (j.label.clone(), j.key.clone(), j.props.clone()))
 		.collect();
 
-	// Measure batch latency and record metrics
-	let start = Instant::now();
-	let res = repo.merge_batch(&tuples).await;
-	let elapsed_ms = start.elapsed().as_millis() as f64;
-	metrics.persist_batch_flushes.inc();
-	// Histogram expects milliseconds, as per metric name
-	metrics.persist_batch_latency_ms.observe(elapsed_ms);
-
-	if let Err(e) = res {
-		metrics.persist_batch_failures.inc();
-		eprintln!("persistence batch failed: {}", e);
-		// If merge_batch returns an error, try per-item merges as a last resort
-		for j in jobs {
-			if let Err(e2) = repo.merge_entity(&j.label, &j.key, &j.props).await {
+	// Every job in the batch is recorded as observed at flush time so
+	// repeated values bump `seen_count`/`last_seen` rather than overwrite.
+	let observed_at = chrono::Utc::now().to_rfc3339();
+
+	let record_attempt = |failed: bool| {
+		if let Some(c) = &circuit {
+			c.record(failed);
+		}
+	};
+	let mut batch_failed = false;
+	if !tuples.is_empty() {
+		// Measure batch latency and record metrics
+		let start = Instant::now();
+		let res = repo.observe_batch(&tuples, &observed_at).await;
+		let elapsed_ms = start.elapsed().as_millis() as f64;
+		metrics.persist_batch_flushes.inc();
+		// Histogram expects milliseconds, as per metric name
+		metrics.persist_batch_latency_ms.observe(elapsed_ms);
+		record_attempt(res.is_err());
+		if let Err(e) = res {
+			metrics.persist_batch_failures.inc();
+			eprintln!("persistence batch failed: {}", e);
+			batch_failed = true;
+		}
+	}
+
+	for (idx, j) in jobs.iter().enumerate() {
+		// If the batch failed, try per-item merges as a last resort.
+		if batch_failed || !j.edges.is_empty() {
+			let node = GraphNode::new(j.label.clone(), j.key.clone(), j.props.clone());
+			if let Err(e2) = repo.merge_record(&node, &j.edges, &observed_at).await {
+				record_attempt(true);
 				metrics.persist_per_item_failures.inc();
 				eprintln!("per-item persist failed for {}: {}", j.key, e2);
+				continue;
+			}
+			record_attempt(false);
+		}
+		observe_arrival_latency(&metrics, j);
+		j.acknowledge();
+		match fingerprints.get(idx) {
+			// A bare sighting changes nothing to replicate.
+			Some(None) => {}
+			Some(Some(fp)) => {
+				if let Some(f) = &filter {
+					f.lock()
+						.unwrap()
+						.record(Fingerprint::key_of(&j.label, &j.key), *fp);
+				}
+				record_change(changes.as_ref(), j).await;
 			}
+			None => record_change(changes.as_ref(), j).await,
 		}
 	}
 }
 
+/// Record a persisted job in the sync change log, if enabled. A failure
+/// only costs replication of this write, so it is logged and ignored.
+async fn record_change(changes: Option<&ChangeRecorder>, job: &PersistJob) {
+	if let Some(changes) = changes {
+		if let Err(e) = changes.record(&job.label, &job.key, &job.props).await {
+			tracing::warn!(key = %job.key, error = %e, "failed to record change log entry");
+		}
+	}
+}
+
+/// Send a rejected job to the dead-letter queue, or log and drop it when
+/// none is configured.
+fn dead_letter(queue: Option<&DeadLetterQueue>, job: &PersistJob, reason: String) {
+	match queue {
+		Some(queue) => {
+			if let Err(e) = queue.push(DeadLetter::new(job, reason)) {
+				tracing::warn!(key = %job.key, error = %e, "failed to write dead letter");
+			}
+		}
+		None => tracing::warn!(key = %job.key, %reason, "dropping persist job"),
+	}
+}
+
+fn props_size(props: &Value) -> usize {
+	serde_json::to_vec(props).map_or(usize::MAX, |v| v.len())
+}
+
+/// Shorten the longest top-level string properties until `props`
+/// serializes to at most `max_bytes`. Returns false when that is not
+/// possible, e.g. because the size is in nested values.
+fn truncate_props(props: &mut Value, max_bytes: usize) -> bool {
+	loop {
+		let size = props_size(props);
+		if size <= max_bytes {
+			return true;
+		}
+		let Value::Object(map) = props else {
+			return false;
+		};
+		let longest = map
+			.values_mut()
+			.filter_map(|v| match v {
+				Value::String(s) if !s.is_empty() => Some(s),
+				_ => None,
+			})
+			.max_by_key(|s| s.len());
+		let Some(longest) = longest else {
+			return false;
+		};
+		let mut cut = longest.len().saturating_sub(size - max_bytes);
+		while !longest.is_char_boundary(cut) {
+			cut -= 1;
+		}
+		longest.truncate(cut);
+	}
+}
+
+/// How deeply objects and arrays nest in `value`, and the length of its
+/// longest array.
+fn value_shape(value: &Value) -> (usize, usize) {
+	let (children, len): (Box<dyn Iterator<Item = &Value>>, usize) = match value {
+		Value::Array(items) => (Box::new(items.iter()), items.len()),
+		Value::Object(map) => (Box::new(map.values()), 0),
+		_ => return (0, 0),
+	};
+	let (depth, longest) = children
+		.map(value_shape)
+		.fold((0, len), |(d, l), (cd, cl)| (d.max(cd), l.max(cl)));
+	(depth + 1, longest)
+}
+
+/// Replace containers in `value` nested more than `depth` deep with `null`
+/// and shorten arrays to `max_len` elements.
+fn cut_value(value: &mut Value, depth: usize, max_len: usize) {
+	if !(value.is_array() || value.is_object()) {
+		return;
+	}
+	if depth == 0 {
+		*value = Value::Null;
+		return;
+	}
+	match value {
+		Value::Array(items) => {
+			items.truncate(max_len);
+			items
+				.iter_mut()
+				.for_each(|v| cut_value(v, depth - 1, max_len));
+		}
+		Value::Object(map) => map
+			.values_mut()
+			.for_each(|v| cut_value(v, depth - 1, max_len)),
+		_ => {}
+	}
+}
+
+fn observe_arrival_latency(metrics: &MetricsRegistry, job: &PersistJob) {
+	let ms = job.arrived_at.elapsed().as_secs_f64() * 1000.0;
+	metrics.ingest_to_persist_latency_ms.observe(ms);
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use crate::ingest::test_utils::RecordingRepo;
 	use serde_json::json;
+	use std::sync::atomic::Ordering;
 
 	#[test]
 	fn persist_job_creation() {
-		let job = PersistJob {
-			label: "TestLabel".to_string(),
-			key: "test-key".to_string(),
-			props: json!({"field": "value"}),
-		};
+		let job = PersistJob::new("TestLabel", "test-key", json!({"field": "value"}));
 		assert_eq!(job.label, "TestLabel");
 		assert_eq!(job.key, "test-key");
 	}
 
 	#[test]
 	fn metrics_text_format() {
-		let metrics = metrics_text();
+		let metrics = MetricsRegistry::new().encode();
 		assert!(metrics.contains("heimdall_persist_jobs_submitted_total"));
 		assert!(metrics.contains("heimdall_persist_batch_flushes_total"));
 		assert!(metrics.contains("heimdall_persist_batch_failures_total"));
 		assert!(metrics.contains("heimdall_persist_per_item_failures_total"));
-		assert!(metrics.contains("heimdall_persist_batch_flush_latency_ms_sum"));
+		assert!(metrics.contains("heimdall_persist_batch_latency_ms_sum"));
 		// Verify Prometheus format includes HELP and TYPE lines
 		assert!(metrics.contains("# HELP"));
 		assert!(metrics.contains("# TYPE"));
 	}
 
-	#[test]
-	fn metrics_text_includes_counter_type() {
-		let metrics = metrics_text();
-		// Verify all metrics are marked as counters
-		let type_count = metrics.matches("# TYPE").count();
-		let counter_count = metrics.matches("counter").count();
-		assert_eq!(type_count, counter_count);
-	}
-
 	#[tokio::test]
 	async fn submit_job_increments_metric() {
-		// Read initial value
-		let initial = PERSIST_JOBS_SUBMITTED.load(Ordering::Relaxed);
+		let metrics = Arc::new(MetricsRegistry::new());
 
 		// Create a channel with capacity 10
 		let (tx, _rx) = mpsc::channel::<PersistJob>(10);
 
-		let job = PersistJob {
-			label: "TestLabel".to_string(),
-			key: "test-key".to_string(),
-			props: json!({}),
-		};
+		let job = PersistJob::new("TestLabel", "test-key", json!({}));
 
 		// Submit job
-		let result = submit_job(&tx, job);
+		let result = submit_job(&tx, job, &metrics);
 		assert!(result.is_ok());
 
 		// Verify metric incremented
-		let after = PERSIST_JOBS_SUBMITTED.load(Ordering::Relaxed);
-		assert_eq!(after, initial + 1);
+		assert_eq!(metrics.persist_jobs_submitted.get(), 1);
+		assert_eq!(metrics.persist_queue_length.get(), 1);
 	}
 
 	#[tokio::test]
 	async fn submit_job_fails_when_channel_full() {
+		let metrics = Arc::new(MetricsRegistry::new());
 		// Create a channel with capacity 1, fill it, then try to send another
 		let (tx, _rx) = mpsc::channel::<PersistJob>(1);
 
 		// Fill the channel
-		let job1 = PersistJob {
-			label: "TestLabel".to_string(),
-			key: "test-key-1".to_string(),
-			props: json!({}),
-		};
-		let result1 = submit_job(&tx, job1);
+		let job1 = PersistJob::new("TestLabel", "test-key-1", json!({}));
+		let result1 = submit_job(&tx, job1, &metrics);
 		assert!(result1.is_ok());
 
 		// Try to send another job - should fail because channel is full
-		let job2 = PersistJob {
-			label: "TestLabel".to_string(),
-			key: "test-key-2".to_string(),
-			props: json!({}),
-		};
-		let result2 = submit_job(&tx, job2);
+		let job2 = PersistJob::new("TestLabel", "test-key-2", json!({}));
+		let result2 = submit_job(&tx, job2, &metrics);
 		assert!(result2.is_err());
 	}
+
+	async fn wait_until(mut cond: impl FnMut() -> bool) {
+		for _ in 0..200 {
+			if cond() {
+				return;
+			}
+			tokio::time::sleep(Duration::from_millis(5)).await;
+		}
+		panic!("condition not reached in time");
+	}
+
+	#[tokio::test]
+	async fn dedup_skips_identical_jobs_but_not_changed_props() {
+		let repo = Arc::new(RecordingRepo::default());
+		let metrics = Arc::new(MetricsRegistry::new());
+		let tx = start_batcher_with_options(
+			repo.clone(),
+			metrics.clone(),
+			BatcherOptions {
+				batch_size: 1,
+				flush_interval_ms: 10,
+				dedup: Some(DedupOptions::default()),
+				..BatcherOptions::default()
+			},
+		);
+
+		let job = |v: &str| {
+			PersistJob::new(
+				"FieldValue",
+				"example.com",
+				json!({"field_type": "domain", "value": v}),
+			)
+		};
+
+		submit_job(&tx, job("a"), &metrics).unwrap();
+		wait_until(|| repo.merged.lock().unwrap().len() == 1).await;
+
+		// Identical tuple: only a sighting.
+		submit_job(&tx, job("a"), &metrics).unwrap();
+		wait_until(|| repo.merged.lock().unwrap().len() == 2).await;
+		assert_eq!(metrics.persist_skipped_duplicates_total.get(), 1);
+		assert_eq!(repo.merged.lock().unwrap()[1].2, json!({}));
+
+		// Same key, changed props: merged.
+		submit_job(&tx, job("b"), &metrics).unwrap();
+		wait_until(|| repo.merged.lock().unwrap().len() == 3).await;
+
+		assert_eq!(metrics.persist_skipped_duplicates_total.get(), 1);
+		assert_eq!(repo.merged.lock().unwrap()[2].2["value"], "b");
+		assert_eq!(repo.seen_count.lock().unwrap()["example.com"], 3);
+	}
+
+	#[tokio::test]
+	async fn jobs_with_edges_are_merged_with_them() {
+		let repo = Arc::new(RecordingRepo::default());
+		let metrics = Arc::new(MetricsRegistry::new());
+		let tx = start_batcher_with_options(
+			repo.clone(),
+			metrics.clone(),
+			BatcherOptions {
+				batch_size: 2,
+				flush_interval_ms: 10,
+				..BatcherOptions::default()
+			},
+		);
+
+		let edge = Edge::new("USES_PASSWORD", "hash:abc").with_to_label("Credential");
+		let job = PersistJob::new("FieldValue", "email:a@example.com", json!({}))
+			.with_edges(vec![edge.clone()]);
+		submit_job(&tx, job, &metrics).unwrap();
+		let job = PersistJob::new("FieldValue", "domain:example.com", json!({}));
+		submit_job(&tx, job, &metrics).unwrap();
+		wait_until(|| repo.merged.lock().unwrap().len() == 2).await;
+
+		assert_eq!(
+			*repo.edges.lock().unwrap(),
+			[("email:a@example.com".to_string(), edge)]
+		);
+		// Only the job without edges went through the batched write.
+		assert_eq!(repo.seen_count.lock().unwrap().len(), 1);
+	}
+
+	#[tokio::test]
+	async fn every_identical_job_counts_as_a_sighting() {
+		let repo = Arc::new(RecordingRepo::default());
+		let metrics = Arc::new(MetricsRegistry::new());
+		let log = Arc::new(crate::sync::ChangeLog::in_memory());
+		let tx = start_batcher_with_options(
+			repo.clone(),
+			metrics.clone(),
+			BatcherOptions {
+				batch_size: 3,
+				flush_interval_ms: 10,
+				dedup: Some(DedupOptions::default()),
+				changes: Some(ChangeRecorder::new(log.clone(), "node-a")),
+				..BatcherOptions::default()
+			},
+		);
+
+		// Duplicates within a batch and across batches.
+		for _ in 0..7 {
+			let job = PersistJob::new("FieldValue", "example.com", json!({"field_type": "domain"}));
+			submit_job(&tx, job, &metrics).unwrap();
+		}
+		wait_until(|| repo.seen_count.lock().unwrap().get("example.com") == Some(&7)).await;
+
+		assert_eq!(metrics.persist_skipped_duplicates_total.get(), 6);
+		// Only the first carried props to replicate.
+		assert_eq!(log.len().await, 1);
+	}
+
+	fn props_limited_batcher(
+		repo: Arc<RecordingRepo>,
+		metrics: Arc<MetricsRegistry>,
+		policy: OversizedProps,
+		dead_letter: Arc<DeadLetterQueue>,
+	) -> PersistSender {
+		start_batcher_with_options(
+			repo,
+			metrics,
+			BatcherOptions {
+				batch_size: 2,
+				flush_interval_ms: 10,
+				props_limit: Some(PropsLimit {
+					max_bytes: 64,
+					policy,
+				}),
+				dead_letter: Some(dead_letter),
+				..BatcherOptions::default()
+			},
+		)
+	}
+
+	#[tokio::test]
+	async fn oversized_props_are_rejected_to_the_dead_letter_queue() {
+		let repo = Arc::new(RecordingRepo::default());
+		let metrics = Arc::new(MetricsRegistry::new());
+		let dlq = Arc::new(DeadLetterQueue::in_memory(10));
+		let tx = props_limited_batcher(
+			repo.clone(),
+			metrics.clone(),
+			OversizedProps::Reject,
+			dlq.clone(),
+		);
+
+		let big = PersistJob::new("FieldValue", "big", json!({"raw": "x".repeat(200)}));
+		let small = PersistJob::new("FieldValue", "small", json!({"raw": "x"}));
+		submit_job(&tx, big, &metrics).unwrap();
+		submit_job(&tx, small, &metrics).unwrap();
+		wait_until(|| repo.merged.lock().unwrap().len() == 1).await;
+
+		assert_eq!(repo.merged.lock().unwrap()[0].1, "small");
+		assert_eq!(metrics.persist_oversized_props_total.get(), 1);
+		let letters = dlq.recent();
+		assert_eq!(letters.len(), 1);
+		assert_eq!(letters[0].key, "big");
+		assert!(letters[0].reason.contains("limit is 64"));
+	}
+
+	#[tokio::test]
+	async fn oversized_props_are_truncated_to_fit() {
+		let repo = Arc::new(RecordingRepo::default());
+		let metrics = Arc::new(MetricsRegistry::new());
+		let dlq = Arc::new(DeadLetterQueue::in_memory(10));
+		let tx = props_limited_batcher(
+			repo.clone(),
+			metrics.clone(),
+			OversizedProps::Truncate,
+			dlq.clone(),
+		);
+
+		let props = json!({"field_type": "domain", "raw": "é".repeat(100)});
+		submit_job(&tx, PersistJob::new("FieldValue", "big", props), &metrics).unwrap();
+		let small = PersistJob::new("FieldValue", "small", json!({"raw": "x"}));
+		submit_job(&tx, small, &metrics).unwrap();
+		wait_until(|| repo.merged.lock().unwrap().len() == 2).await;
+
+		let merged = repo.merged.lock().unwrap().clone();
+		assert!(props_size(&merged[0].2) <= 64);
+		assert_eq!(merged[0].2["field_type"], "domain");
+		assert!(merged[0].2["raw"].as_str().unwrap().starts_with('é'));
+		assert_eq!(merged[1].2["raw"], "x");
+		assert_eq!(metrics.persist_oversized_props_total.get(), 1);
+		assert!(dlq.recent().is_empty());
+	}
+
+	#[test]
+	fn truncation_fails_when_no_string_can_absorb_the_overflow() {
+		let mut props = json!({"values": vec![1; 100]});
+		assert!(!truncate_props(&mut props, 64));
+		assert_eq!("truncate".parse(), Ok(OversizedProps::Truncate));
+		assert!("drop".parse::<OversizedProps>().is_err());
+	}
+
+	fn shape_limited_batcher(
+		repo: Arc<RecordingRepo>,
+		metrics: Arc<MetricsRegistry>,
+		policy: OversizedProps,
+		dead_letter: Arc<DeadLetterQueue>,
+	) -> PersistSender {
+		start_batcher_with_options(
+			repo,
+			metrics,
+			BatcherOptions {
+				batch_size: 3,
+				flush_interval_ms: 10,
+				props_shape: Some(PropsShapeLimit {
+					max_depth: 2,
+					max_array_len: 100,
+					policy,
+				}),
+				dead_letter: Some(dead_letter),
+				..BatcherOptions::default()
+			},
+		)
+	}
+
+	fn shape_jobs() -> Vec<PersistJob> {
+		vec![
+			PersistJob::new(
+				"FieldValue",
+				"deep",
+				json!({"field_type": "domain", "nested": {"a": {"b": {"c": 1}}, "x": 1}}),
+			),
+			PersistJob::new("FieldValue", "long", json!({"values": vec![7; 10_000]})),
+			PersistJob::new(
+				"FieldValue",
+				"normal",
+				json!({"raw": "x", "tags": ["a", "b"], "meta": {"k": [1, 2]}}),
+			),
+		]
+	}
+
+	#[tokio::test]
+	async fn deep_and_long_props_are_truncated_to_the_limits() {
+		let repo = Arc::new(RecordingRepo::default());
+		let metrics = Arc::new(MetricsRegistry::new());
+		let dlq = Arc::new(DeadLetterQueue::in_memory(10));
+		let tx = shape_limited_batcher(
+			repo.clone(),
+			metrics.clone(),
+			OversizedProps::Truncate,
+			dlq.clone(),
+		);
+		for job in shape_jobs() {
+			submit_job(&tx, job, &metrics).unwrap();
+		}
+		wait_until(|| repo.merged.lock().unwrap().len() == 3).await;
+
+		let merged = repo.merged.lock().unwrap().clone();
+		assert_eq!(
+			merged[0].2,
+			json!({"field_type": "domain", "nested": {"a": {"b": null}, "x": 1}})
+		);
+		assert_eq!(merged[1].2["values"], json!(vec![7; 100]));
+		assert_eq!(merged[2].2, shape_jobs()[2].props);
+		assert_eq!(metrics.persist_props_shape_limited_total.get(), 2);
+		assert!(dlq.recent().is_empty());
+	}
+
+	#[tokio::test]
+	async fn deep_and_long_props_are_rejected_to_the_dead_letter_queue() {
+		let repo = Arc::new(RecordingRepo::default());
+		let metrics = Arc::new(MetricsRegistry::new());
+		let dlq = Arc::new(DeadLetterQueue::in_memory(10));
+		let tx = shape_limited_batcher(
+			repo.clone(),
+			metrics.clone(),
+			OversizedProps::Reject,
+			dlq.clone(),
+		);
+		for job in shape_jobs() {
+			submit_job(&tx, job, &metrics).unwrap();
+		}
+		wait_until(|| repo.merged.lock().unwrap().len() == 1).await;
+
+		assert_eq!(repo.merged.lock().unwrap()[0].1, "normal");
+		assert_eq!(metrics.persist_props_shape_limited_total.get(), 2);
+		let letters = dlq.recent();
+		assert_eq!(letters.len(), 2);
+		assert!(letters[0].reason.contains("nest 3 deep, limit is 2"));
+		assert!(letters[1].reason.contains("10000 elements, limit is 100"));
+	}
+
+	fn schema_checked_batcher(
+		repo: Arc<RecordingRepo>,
+		metrics: Arc<MetricsRegistry>,
+		policy: SchemaViolation,
+		dead_letter: Arc<DeadLetterQueue>,
+	) -> PersistSender {
+		let schemas = LabelSchemas::new(policy)
+			.parse(r#"{"FieldValue": {"required": {"field_type": "string"}, "optional": {"confidence": "number"}}}"#)
+			.unwrap();
+		start_batcher_with_options(
+			repo,
+			metrics,
+			BatcherOptions {
+				batch_size: 3,
+				flush_interval_ms: 10,
+				schemas: Some(Arc::new(schemas)),
+				dead_letter: Some(dead_letter),
+				..BatcherOptions::default()
+			},
+		)
+	}
+
+	fn schema_jobs() -> [PersistJob; 3] {
+		[
+			PersistJob::new(
+				"FieldValue",
+				"ok",
+				json!({"field_type": "ip", "confidence": 1.0}),
+			),
+			PersistJob::new("FieldValue", "missing", json!({"raw": "10.0.0.1"})),
+			PersistJob::new(
+				"FieldValue",
+				"mistyped",
+				json!({"field_type": "ip", "confidence": "high"}),
+			),
+		]
+	}
+
+	#[tokio::test]
+	async fn schema_violations_are_quarantined() {
+		let repo = Arc::new(RecordingRepo::default());
+		let metrics = Arc::new(MetricsRegistry::new());
+		let dlq = Arc::new(DeadLetterQueue::in_memory(10));
+		let tx = schema_checked_batcher(
+			repo.clone(),
+			metrics.clone(),
+			SchemaViolation::Quarantine,
+			dlq.clone(),
+		);
+
+		for job in schema_jobs() {
+			submit_job(&tx, job, &metrics).unwrap();
+		}
+		wait_until(|| repo.merged.lock().unwrap().len() == 3).await;
+
+		let labels: Vec<(String, String)> = repo
+			.merged
+			.lock()
+			.unwrap()
+			.iter()
+			.map(|(label, key, _)| (key.clone(), label.clone()))
+			.collect();
+		assert_eq!(
+			labels,
+			vec![
+				("ok".to_string(), "FieldValue".to_string()),
+				("missing".to_string(), QUARANTINE_LABEL.to_string()),
+				("mistyped".to_string(), QUARANTINE_LABEL.to_string()),
+			]
+		);
+		assert_eq!(metrics.persist_schema_violation_total.get(), 2);
+		assert!(dlq.recent().is_empty());
+	}
+
+	#[tokio::test]
+	async fn schema_violations_are_rejected_to_the_dead_letter_queue() {
+		let repo = Arc::new(RecordingRepo::default());
+		let metrics = Arc::new(MetricsRegistry::new());
+		let dlq = Arc::new(DeadLetterQueue::in_memory(10));
+		let tx = schema_checked_batcher(
+			repo.clone(),
+			metrics.clone(),
+			SchemaViolation::Reject,
+			dlq.clone(),
+		);
+
+		for job in schema_jobs() {
+			submit_job(&tx, job, &metrics).unwrap();
+		}
+		wait_until(|| dlq.recent().len() == 2).await;
+		wait_until(|| repo.merged.lock().unwrap().len() == 1).await;
+
+		assert_eq!(repo.merged.lock().unwrap()[0].1, "ok");
+		assert_eq!(metrics.persist_schema_violation_total.get(), 2);
+		let letters = dlq.recent();
+		assert_eq!(letters[0].key, "missing");
+		assert!(
+			letters[0]
+				.reason
+				.contains("missing required property 'field_type'")
+		);
+		assert_eq!(letters[1].key, "mistyped");
+		assert!(
+			letters[1]
+				.reason
+				.contains("'confidence' must be of type number")
+		);
+	}
+
+	#[tokio::test]
+	async fn values_sharing_a_key_are_quarantined_as_collisions() {
+		use collisions::{KEY_FINGERPRINT_PROP, key_fingerprint};
+
+		// A hasher that sends every value to the same key.
+		fn colliding_key(_unsalted: &str) -> String {
+			"0000000000000000".to_string()
+		}
+		let job = |unsalted: &str| {
+			PersistJob::new(
+				"FieldValue",
+				colliding_key(unsalted),
+				json!({
+					"field_type": "domain",
+					KEY_FINGERPRINT_PROP: key_fingerprint("domain", unsalted),
+				}),
+			)
+		};
+
+		let repo = Arc::new(RecordingRepo::default());
+		let metrics = Arc::new(MetricsRegistry::new());
+		let tx = start_batcher_with_options(
+			repo.clone(),
+			metrics.clone(),
+			BatcherOptions {
+				batch_size: 3,
+				flush_interval_ms: 10,
+				collisions: Some(Arc::new(KeyCollisionDetector::new())),
+				..BatcherOptions::default()
+			},
+		);
+
+		for unsalted in ["domain:a.example", "domain:b.example", "domain:a.example"] {
+			submit_job(&tx, job(unsalted), &metrics).unwrap();
+		}
+		wait_until(|| repo.merged.lock().unwrap().len() == 3).await;
+
+		let labels: Vec<String> = repo
+			.merged
+			.lock()
+			.unwrap()
+			.iter()
+			.map(|(label, _, _)| label.clone())
+			.collect();
+		assert_eq!(
+			labels,
+			vec![
+				"FieldValue".to_string(),
+				QUARANTINE_LABEL.to_string(),
+				"FieldValue".to_string(),
+			]
+		);
+		assert_eq!(metrics.key_collision_total.get(), 1);
+	}
+
+	#[tokio::test]
+	async fn dedup_disabled_merges_every_job() {
+		let repo = Arc::new(RecordingRepo::default());
+		let metrics = Arc::new(MetricsRegistry::new());
+		let tx = start_batcher_with_options(
+			repo.clone(),
+			metrics.clone(),
+			BatcherOptions {
+				batch_size: 1,
+				flush_interval_ms: 10,
+				..BatcherOptions::default()
+			},
+		);
+
+		for _ in 0..3 {
+			let job = PersistJob::new("FieldValue", "example.com", json!({"value": "a"}));
+			submit_job(&tx, job, &metrics).unwrap();
+		}
+		wait_until(|| repo.merged.lock().unwrap().len() == 3).await;
+		assert_eq!(metrics.persist_skipped_duplicates_total.get(), 0);
+	}
+
+	#[tokio::test]
+	async fn persisted_jobs_are_recorded_in_the_change_log() {
+		let repo = Arc::new(RecordingRepo::default());
+		let metrics = Arc::new(MetricsRegistry::new());
+		let log = Arc::new(crate::sync::ChangeLog::in_memory());
+		let tx = start_batcher_with_options(
+			repo.clone(),
+			metrics.clone(),
+			BatcherOptions {
+				batch_size: 1,
+				flush_interval_ms: 10,
+				changes: Some(ChangeRecorder::new(log.clone(), "node-a")),
+				..BatcherOptions::default()
+			},
+		);
+
+		let mut versions = Vec::new();
+		for n in 1..=2 {
+			let job = PersistJob::new("FieldValue", "example.com", json!({"n": n}));
+			submit_job(&tx, job, &metrics).unwrap();
+			for _ in 0..200 {
+				if log.last_seq().await == n {
+					break;
+				}
+				tokio::time::sleep(Duration::from_millis(5)).await;
+			}
+			let entry = log
+				.latest_for("FieldValue", "example.com")
+				.await
+				.expect("change log entry");
+			assert_eq!(entry.origin, "node-a");
+			assert_eq!(entry.props, json!({"n": n}));
+			versions.push(entry.version_vector["node-a"]);
+		}
+		assert_eq!(log.len().await, 2);
+		assert!(versions[1] > versions[0], "{:?}", versions);
+	}
+
+	#[tokio::test]
+	async fn flush_records_ingest_to_persist_latency() {
+		let repo = Arc::new(RecordingRepo::default());
+		let metrics = Arc::new(MetricsRegistry::new());
+		let tx = start_batcher_with_options(
+			repo.clone(),
+			metrics.clone(),
+			BatcherOptions {
+				batch_size: 1,
+				flush_interval_ms: 10,
+				..BatcherOptions::default()
+			},
+		);
+
+		let arrived = Instant::now() - Duration::from_millis(50);
+		let job = PersistJob::new("FieldValue", "example.com", json!({"field_type": "domain"}))
+			.with_arrival(arrived);
+		submit_job(&tx, job, &metrics).unwrap();
+		wait_until(|| metrics.ingest_to_persist_latency_ms.get_sample_count() == 1).await;
+
+		assert!(metrics.ingest_to_persist_latency_ms.get_sample_sum() >= 50.0);
+		// The arrival stamp is bookkeeping only and never reaches the graph.
+		let merged = repo.merged.lock().unwrap();
+		assert_eq!(merged[0].2, json!({"field_type": "domain"}));
+	}
+
+	/// Push 16 jobs through a batcher of batches of 4 over a repo taking
+	/// 100ms per write. Returns the elapsed time and peak overlapping writes.
+	async fn run_slow_batches(
+		concurrency: usize,
+		key: impl Fn(usize) -> String,
+	) -> (Duration, usize) {
+		let repo = Arc::new(RecordingRepo {
+			delay: Some(Duration::from_millis(100)),
+			..RecordingRepo::default()
+		});
+		let metrics = Arc::new(MetricsRegistry::new());
+		let tx = start_batcher_with_options(
+			repo.clone(),
+			metrics.clone(),
+			BatcherOptions {
+				batch_size: 4,
+				flush_interval_ms: 10,
+				flush_concurrency: concurrency,
+				..BatcherOptions::default()
+			},
+		);
+
+		let started = Instant::now();
+		for i in 0..16 {
+			let job = PersistJob::new("FieldValue", key(i), json!({"n": i}));
+			submit_job(&tx, job, &metrics).unwrap();
+		}
+		for _ in 0..400 {
+			if repo.merged.lock().unwrap().len() == 16 {
+				break;
+			}
+			tokio::time::sleep(Duration::from_millis(5)).await;
+		}
+		assert_eq!(repo.merged.lock().unwrap().len(), 16);
+		(started.elapsed(), repo.max_in_flight.load(Ordering::SeqCst))
+	}
+
+	#[tokio::test]
+	async fn concurrent_flushes_overlap_and_improve_throughput() {
+		let (serial, serial_peak) = run_slow_batches(1, |i| format!("k{}", i)).await;
+		let (parallel, parallel_peak) = run_slow_batches(2, |i| format!("k{}", i)).await;
+
+		assert_eq!(serial_peak, 1);
+		assert_eq!(parallel_peak, 2);
+		assert!(
+			parallel < serial,
+			"concurrency 2 took {:?}, concurrency 1 took {:?}",
+			parallel,
+			serial
+		);
+	}
+
+	#[tokio::test]
+	async fn writes_for_the_same_key_never_overlap() {
+		let (_, peak) = run_slow_batches(2, |_| "example.com".to_string()).await;
+		assert_eq!(peak, 1);
+	}
+
+	/// Last props merged for each key, in merge order, ignoring bare
+	/// sightings.
+	fn stored(repo: &RecordingRepo) -> HashMap<String, Value> {
+		repo.merged
+			.lock()
+			.unwrap()
+			.iter()
+			.filter(|(_, _, props)| *props != json!({}))
+			.map(|(_, key, props)| (key.clone(), props.clone()))
+			.collect()
+	}
+
+	#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+	async fn last_submitted_props_win_under_concurrent_flushes() {
+		let repo = Arc::new(RecordingRepo {
+			jitter: true,
+			..RecordingRepo::default()
+		});
+		let metrics = Arc::new(MetricsRegistry::new());
+		let tx = start_batcher_with_options(
+			repo.clone(),
+			metrics.clone(),
+			BatcherOptions {
+				batch_size: 1,
+				flush_interval_ms: 1,
+				flush_concurrency: 4,
+				dedup: Some(DedupOptions::default()),
+				..BatcherOptions::default()
+			},
+		);
+
+		// Four keys interleaved, each flipping a, b, a, ...: a reordered or
+		// wrongly skipped write leaves a stale value stored.
+		let mut expected = HashMap::new();
+		for i in 0..62 {
+			let key = format!("k{}", i % 4);
+			let props = json!({ "value": (["a", "b"][(i / 4) % 2]) });
+			expected.insert(key.clone(), props.clone());
+			submit_job(&tx, PersistJob::new("FieldValue", key, props), &metrics).unwrap();
+		}
+		assert_eq!(expected["k0"]["value"], "b");
+		assert_eq!(expected["k2"]["value"], "a");
+
+		// Every value differs from the key's previous one, so all are
+		// written; the stored values can match early while some are queued.
+		wait_until(|| repo.merged.lock().unwrap().len() == 62).await;
+		assert_eq!(stored(&repo), expected);
+		assert!(repo.max_in_flight.load(Ordering::SeqCst) > 1);
+	}
+
+	#[tokio::test]
+	async fn values_flipping_back_within_a_batch_are_kept() {
+		let repo = Arc::new(RecordingRepo::default());
+		let metrics = Arc::new(MetricsRegistry::new());
+		let tx = start_batcher_with_options(
+			repo.clone(),
+			metrics.clone(),
+			BatcherOptions {
+				batch_size: 4,
+				flush_interval_ms: 60_000,
+				dedup: Some(DedupOptions::default()),
+				..BatcherOptions::default()
+			},
+		);
+
+		for value in ["a", "b", "a", "a"] {
+			let job = PersistJob::new("FieldValue", "example.com", json!({ "value": value }));
+			submit_job(&tx, job, &metrics).unwrap();
+		}
+		wait_until(|| repo.merged.lock().unwrap().len() == 4).await;
+		assert_eq!(metrics.persist_skipped_duplicates_total.get(), 1);
+		let merged: Vec<Value> = repo
+			.merged
+			.lock()
+			.unwrap()
+			.iter()
+			.map(|(_, _, props)| props["value"].clone())
+			.collect();
+		assert_eq!(
+			merged,
+			vec![json!("a"), json!("b"), json!("a"), Value::Null]
+		);
+	}
+
+	#[tokio::test]
+	async fn failing_writes_pause_ingest_until_they_age_out() {
+		let repo = Arc::new(RecordingRepo::default());
+		repo.failing.store(true, Ordering::SeqCst);
+		let metrics = Arc::new(MetricsRegistry::new());
+		let dlq = Arc::new(DeadLetterQueue::in_memory(10));
+		let circuit = Arc::new(PersistCircuit::new(
+			circuit::CircuitOptions {
+				failure_rate: 0.5,
+				window: Duration::from_millis(200),
+				min_attempts: 2,
+			},
+			metrics.ingest_paused.clone(),
+		));
+		let health = crate::health::DbHealth::new(3).with_persist_circuit(circuit.clone());
+		let tx = start_batcher_with_options(
+			repo.clone(),
+			metrics.clone(),
+			BatcherOptions {
+				batch_size: 1,
+				flush_interval_ms: 10,
+				dead_letter: Some(dlq.clone()),
+				circuit: Some(circuit.clone()),
+				..BatcherOptions::default()
+			},
+		);
+		let job = |key: &str| PersistJob::new("FieldValue", key, json!({"value": key}));
+
+		// The failed batch and its failed per-item retry trip the circuit.
+		submit_job(&tx, job("a"), &metrics).unwrap();
+		wait_until(|| circuit.is_open()).await;
+		assert_eq!(metrics.ingest_paused.get(), 1);
+		let resp = health.reject_if_down().expect("ingest is paused");
+		assert_eq!(resp.status(), axum::http::StatusCode::SERVICE_UNAVAILABLE);
+
+		// While open, queued jobs are dead-lettered rather than written.
+		submit_job(&tx, job("b"), &metrics).unwrap();
+		wait_until(|| dlq.recent().len() == 1).await;
+		assert_eq!(dlq.recent()[0].key, "b");
+
+		// Once the database recovers and the failures age out, writes resume.
+		repo.failing.store(false, Ordering::SeqCst);
+		wait_until(|| !circuit.is_open()).await;
+		assert!(health.reject_if_down().is_none());
+		assert_eq!(metrics.ingest_paused.get(), 0);
+		submit_job(&tx, job("c"), &metrics).unwrap();
+		wait_until(|| repo.merged.lock().unwrap().len() == 1).await;
+		assert!(!circuit.is_open());
+	}
 }
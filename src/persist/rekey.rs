@@ -0,0 +1,761 @@
+//! Canonical-key salt rotation.
+//!
+//! Changing `canonical_salt` changes every graph key. A [`Rekeyer`] walks
+//! the `FieldValue` nodes in key order, recovers each node's unsalted key
+//! from its stored `field_type` and `value` or `raw`, checks it against
+//! the old salt and moves the node to its key under the new salt. When a
+//! node already holds the new key, e.g. one written by a peer that switched
+//! salts first, the old node's edges are moved onto it and the old node is
+//! removed.
+//!
+//! Nodes are processed in batches, and progress is checkpointed on a
+//! `KeyRotation` node after each one, so an interrupted rotation resumes
+//! where it stopped. Moved keys are recorded in a [`KeyMap`], which
+//! [`RekeyingRepo`] applies to writes that still carry old keys while the
+//! rotation runs.
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+use async_trait::async_trait;
+use axum::{
+	Json,
+	extract::State,
+	http::{HeaderMap, StatusCode},
+	response::{IntoResponse, Response},
+};
+use futures_util::stream::BoxStream;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::age_client::{
+	AgeRepo, AgeResult, Edge, GraphFragment, GraphNode, RekeyCandidate, RekeyOutcome,
+	RetentionAction,
+};
+use crate::audit::{AuditAction, AuditEvent};
+use crate::ingest::KeyPrefixMap;
+use crate::lib::normalizers::{generate_canonical_key, normalize, salted_key};
+use crate::state::AppState;
+
+/// Nodes re-keyed per batch unless the request sets `batch_size`.
+pub const DEFAULT_REKEY_BATCH: usize = 500;
+
+/// Label of the nodes holding rotation checkpoints.
+const CHECKPOINT_LABEL: &str = "KeyRotation";
+
+/// Label whose nodes are re-keyed.
+const REKEY_LABEL: &str = "FieldValue";
+
+/// Old key → new key for nodes a rotation has moved.
+#[derive(Debug, Default)]
+pub struct KeyMap {
+	inner: RwLock<KeyMapInner>,
+}
+
+#[derive(Debug, Default)]
+struct KeyMapInner {
+	moved: HashMap<String, String>,
+	new_keys: HashSet<String>,
+}
+
+impl KeyMap {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Record that the node keyed `old_key` now has `new_key`.
+	pub fn insert(&self, old_key: &str, new_key: &str) {
+		let mut inner = self.inner.write().unwrap();
+		inner.moved.insert(old_key.to_string(), new_key.to_string());
+		inner.new_keys.insert(new_key.to_string());
+	}
+
+	/// The key `key` was moved to, or `key` itself.
+	pub fn translate<'a>(&self, key: &'a str) -> Cow<'a, str> {
+		match self.inner.read().unwrap().moved.get(key) {
+			Some(new_key) => Cow::Owned(new_key.clone()),
+			None => Cow::Borrowed(key),
+		}
+	}
+
+	/// Whether `key` is one a node was moved to.
+	pub fn is_new_key(&self, key: &str) -> bool {
+		self.inner.read().unwrap().new_keys.contains(key)
+	}
+
+	/// Number of moved keys.
+	pub fn len(&self) -> usize {
+		self.inner.read().unwrap().moved.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+}
+
+/// How far a rotation has got; stored on its checkpoint node.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RekeyProgress {
+	/// Key of the last node visited; the walk resumes after it.
+	#[serde(default)]
+	pub after: String,
+	#[serde(default)]
+	pub scanned: u64,
+	/// Nodes given their new key.
+	#[serde(default)]
+	pub renamed: u64,
+	/// Nodes folded into an existing node with the new key.
+	#[serde(default)]
+	pub merged: u64,
+	/// Nodes whose unsalted key could not be recovered; they keep their key.
+	#[serde(default)]
+	pub unresolved: u64,
+	#[serde(default)]
+	pub done: bool,
+}
+
+/// Where a node's key stands relative to the rotation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Resolution {
+	/// Keyed under the old salt; holds the new key.
+	Move(String),
+	/// Already keyed under the new salt.
+	Moved,
+	/// No recoverable unsalted key matches.
+	Unresolved,
+}
+
+/// Re-keys `FieldValue` nodes from one salt to another.
+pub struct Rekeyer {
+	repo: Arc<dyn AgeRepo>,
+	key_prefixes: Arc<KeyPrefixMap>,
+	old_salt: String,
+	new_salt: String,
+	batch_size: usize,
+	keys: Arc<KeyMap>,
+}
+
+impl Rekeyer {
+	/// Rotate keys derived with `old_salt` to `new_salt`, using the default
+	/// key prefixes and batch size.
+	pub fn new(repo: Arc<dyn AgeRepo>, old_salt: &str, new_salt: &str) -> Self {
+		Self {
+			repo,
+			key_prefixes: Arc::new(KeyPrefixMap::default()),
+			old_salt: old_salt.to_string(),
+			new_salt: new_salt.to_string(),
+			batch_size: DEFAULT_REKEY_BATCH,
+			keys: Arc::new(KeyMap::new()),
+		}
+	}
+
+	/// Recover unsalted keys with the prefixes ingest used.
+	pub fn with_key_prefixes(mut self, key_prefixes: Arc<KeyPrefixMap>) -> Self {
+		self.key_prefixes = key_prefixes;
+		self
+	}
+
+	/// Re-key up to `batch_size` nodes per batch.
+	pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+		self.batch_size = batch_size.max(1);
+		self
+	}
+
+	/// Record moved keys in `keys`.
+	pub fn with_key_map(mut self, keys: Arc<KeyMap>) -> Self {
+		self.keys = keys;
+		self
+	}
+
+	/// Identifies this salt pair's checkpoint without revealing either salt.
+	pub fn rotation_id(&self) -> String {
+		generate_canonical_key(&self.new_salt, &self.old_salt).key
+	}
+
+	/// The stored checkpoint, or a fresh start when there is none.
+	pub async fn checkpoint(&self) -> AgeResult<RekeyProgress> {
+		let fragment = self
+			.repo
+			.neighbors(CHECKPOINT_LABEL, &self.rotation_id(), &[], 0)
+			.await?;
+		let progress = fragment
+			.and_then(|f| f.nodes.into_iter().next())
+			.and_then(|node| serde_json::from_value(node["properties"].clone()).ok());
+		Ok(progress.unwrap_or_default())
+	}
+
+	async fn save(&self, progress: &RekeyProgress) -> AgeResult<()> {
+		let props = serde_json::to_value(progress)?;
+		self.repo
+			.merge_entity(CHECKPOINT_LABEL, &self.rotation_id(), &props)
+			.await
+	}
+
+	/// Re-key the next batch after `progress.after` and checkpoint.
+	pub async fn step(&self, progress: &mut RekeyProgress) -> AgeResult<()> {
+		let batch = self
+			.repo
+			.rekey_candidates(REKEY_LABEL, &progress.after, self.batch_size)
+			.await?;
+		for candidate in &batch {
+			progress.scanned += 1;
+			match self.resolve(candidate) {
+				Resolution::Move(new_key) => {
+					match self
+						.repo
+						.rekey(REKEY_LABEL, &candidate.key, &new_key)
+						.await?
+					{
+						RekeyOutcome::Renamed => progress.renamed += 1,
+						RekeyOutcome::Merged => progress.merged += 1,
+						RekeyOutcome::Missing => {}
+					}
+					self.keys.insert(&candidate.key, &new_key);
+				}
+				Resolution::Moved => {}
+				Resolution::Unresolved => progress.unresolved += 1,
+			}
+		}
+		if let Some(last) = batch.last() {
+			progress.after = last.key.clone();
+		}
+		progress.done = batch.len() < self.batch_size;
+		self.save(progress).await
+	}
+
+	/// Run from the checkpoint to completion, calling `on_batch` after
+	/// each batch. A finished rotation starts a new pass, which only moves
+	/// nodes written with the old salt since.
+	pub async fn run<F>(&self, mut on_batch: F) -> AgeResult<RekeyProgress>
+	where
+		F: FnMut(&RekeyProgress),
+	{
+		let mut progress = self.checkpoint().await?;
+		if progress.done {
+			progress = RekeyProgress::default();
+		}
+		while !progress.done {
+			self.step(&mut progress).await?;
+			on_batch(&progress);
+		}
+		Ok(progress)
+	}
+
+	/// Unsalted keys the candidate may have been derived from.
+	fn unsalted_keys(&self, candidate: &RekeyCandidate) -> Vec<String> {
+		let mut keys = Vec::new();
+		if let Some(field_type) = &candidate.field_type {
+			if let Some(value) = &candidate.value {
+				keys.push(self.key_prefixes.key(field_type, value));
+			}
+			if let Some(Ok(normalized)) = candidate.raw.as_ref().map(|r| normalize(field_type, r)) {
+				keys.push(self.key_prefixes.key(field_type, normalized.canonical()));
+			}
+		}
+		if let Some(value) = &candidate.value {
+			keys.push(value.clone());
+		}
+		keys
+	}
+
+	fn resolve(&self, candidate: &RekeyCandidate) -> Resolution {
+		if self.keys.is_new_key(&candidate.key) {
+			return Resolution::Moved;
+		}
+		let unsalted = self.unsalted_keys(candidate);
+		if unsalted
+			.iter()
+			.any(|k| salted_key(k, &self.new_salt) == candidate.key)
+		{
+			return Resolution::Moved;
+		}
+		if let Some(k) = unsalted
+			.iter()
+			.find(|k| salted_key(k, &self.old_salt) == candidate.key)
+		{
+			return Resolution::Move(salted_key(k, &self.new_salt));
+		}
+		// Without an old salt the stored key is the unsalted key itself.
+		if self.old_salt.is_empty() {
+			return Resolution::Move(salted_key(&candidate.key, &self.new_salt));
+		}
+		Resolution::Unresolved
+	}
+}
+
+/// Repository wrapper that sends writes for moved keys to their new keys.
+pub struct RekeyingRepo {
+	inner: Arc<dyn AgeRepo>,
+	keys: Arc<KeyMap>,
+}
+
+impl RekeyingRepo {
+	pub fn new(inner: Arc<dyn AgeRepo>, keys: Arc<KeyMap>) -> Self {
+		Self { inner, keys }
+	}
+
+	fn items(&self, items: &[(String, String, Value)]) -> Vec<(String, String, Value)> {
+		items
+			.iter()
+			.map(|(label, key, props)| {
+				(
+					label.clone(),
+					self.keys.translate(key).into_owned(),
+					props.clone(),
+				)
+			})
+			.collect()
+	}
+}
+
+#[async_trait]
+impl AgeRepo for RekeyingRepo {
+	async fn merge_entity(&self, label: &str, key: &str, props: &Value) -> AgeResult<()> {
+		self.inner
+			.merge_entity(label, &self.keys.translate(key), props)
+			.await
+	}
+
+	async fn ping(&self) -> AgeResult<()> {
+		self.inner.ping().await
+	}
+
+	async fn merge_batch(&self, items: &[(String, String, Value)]) -> AgeResult<()> {
+		if self.keys.is_empty() {
+			return self.inner.merge_batch(items).await;
+		}
+		self.inner.merge_batch(&self.items(items)).await
+	}
+
+	async fn observe_value(
+		&self,
+		label: &str,
+		key: &str,
+		props: &Value,
+		timestamp: &str,
+	) -> AgeResult<()> {
+		self.inner
+			.observe_value(label, &self.keys.translate(key), props, timestamp)
+			.await
+	}
+
+	async fn observe_batch(
+		&self,
+		items: &[(String, String, Value)],
+		timestamp: &str,
+	) -> AgeResult<()> {
+		if self.keys.is_empty() {
+			return self.inner.observe_batch(items, timestamp).await;
+		}
+		self.inner
+			.observe_batch(&self.items(items), timestamp)
+			.await
+	}
+
+	async fn persist_row(
+		&self,
+		dump_id: &str,
+		row_index: i64,
+		row_hash: Option<&str>,
+		cells: &[(String, String, String, String)],
+		timestamp: &str,
+	) -> AgeResult<()> {
+		let cells: Vec<_> = cells
+			.iter()
+			.map(|(column, raw, key, value)| {
+				(
+					column.clone(),
+					raw.clone(),
+					self.keys.translate(key).into_owned(),
+					value.clone(),
+				)
+			})
+			.collect();
+		self.inner
+			.persist_row(dump_id, row_index, row_hash, &cells, timestamp)
+			.await
+	}
+
+	async fn increment_co_occurrence(
+		&self,
+		a_key: &str,
+		b_key: &str,
+		timestamp: &str,
+	) -> AgeResult<()> {
+		self.inner
+			.increment_co_occurrence(
+				&self.keys.translate(a_key),
+				&self.keys.translate(b_key),
+				timestamp,
+			)
+			.await
+	}
+
+	async fn persist_credential(
+		&self,
+		from_key: &str,
+		to_key: &str,
+		timestamp: &str,
+	) -> AgeResult<()> {
+		self.inner
+			.persist_credential(
+				&self.keys.translate(from_key),
+				&self.keys.translate(to_key),
+				timestamp,
+			)
+			.await
+	}
+
+	async fn merge_dump(&self, dump_id: &str, props: &Value, timestamp: &str) -> AgeResult<()> {
+		self.inner.merge_dump(dump_id, props, timestamp).await
+	}
+
+	async fn relate(
+		&self,
+		from_key: &str,
+		to_key: &str,
+		rel_type: &str,
+		props: &Value,
+	) -> AgeResult<()> {
+		self.inner
+			.relate(
+				&self.keys.translate(from_key),
+				&self.keys.translate(to_key),
+				rel_type,
+				props,
+			)
+			.await
+	}
+
+	async fn merge_edge(&self, node: &GraphNode, edge: &Edge, timestamp: &str) -> AgeResult<()> {
+		let mut node = node.clone();
+		node.key = self.keys.translate(&node.key).into_owned();
+		let edge = Edge {
+			to_key: self.keys.translate(&edge.to_key).into_owned(),
+			..edge.clone()
+		};
+		self.inner.merge_edge(&node, &edge, timestamp).await
+	}
+
+	async fn merge_record(
+		&self,
+		node: &GraphNode,
+		edges: &[Edge],
+		timestamp: &str,
+	) -> AgeResult<()> {
+		let mut node = node.clone();
+		node.key = self.keys.translate(&node.key).into_owned();
+		let edges: Vec<Edge> = edges
+			.iter()
+			.map(|edge| Edge {
+				to_key: self.keys.translate(&edge.to_key).into_owned(),
+				..edge.clone()
+			})
+			.collect();
+		self.inner.merge_record(&node, &edges, timestamp).await
+	}
+
+	async fn expire(
+		&self,
+		label: &str,
+		cutoff: &str,
+		action: RetentionAction,
+		limit: usize,
+	) -> AgeResult<u64> {
+		self.inner.expire(label, cutoff, action, limit).await
+	}
+
+	async fn neighbors(
+		&self,
+		label: &str,
+		key: &str,
+		edge_types: &[&str],
+		limit: usize,
+	) -> AgeResult<Option<GraphFragment>> {
+		self.inner
+			.neighbors(label, &self.keys.translate(key), edge_types, limit)
+			.await
+	}
+
+	async fn delete_by_source(&self, source: &str) -> AgeResult<u64> {
+		self.inner.delete_by_source(source).await
+	}
+
+	async fn key_fingerprint(&self, label: &str, key: &str) -> AgeResult<Option<String>> {
+		self.inner
+			.key_fingerprint(label, &self.keys.translate(key))
+			.await
+	}
+
+	async fn rekey_candidates(
+		&self,
+		label: &str,
+		after: &str,
+		limit: usize,
+	) -> AgeResult<Vec<RekeyCandidate>> {
+		self.inner.rekey_candidates(label, after, limit).await
+	}
+
+	async fn rekey(&self, label: &str, old_key: &str, new_key: &str) -> AgeResult<RekeyOutcome> {
+		self.inner.rekey(label, old_key, new_key).await
+	}
+
+	fn query_stream(
+		&self,
+		cypher: &str,
+		timeout: std::time::Duration,
+	) -> BoxStream<'static, AgeResult<Value>> {
+		self.inner.query_stream(cypher, timeout)
+	}
+
+	async fn apply_migration(&self, sql_content: &str) -> AgeResult<()> {
+		self.inner.apply_migration(sql_content).await
+	}
+
+	fn key_property(&self) -> &str {
+		self.inner.key_property()
+	}
+}
+
+/// Status of the server's salt rotation, as served by
+/// `GET /admin/keys/rotate`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RotationStatus {
+	pub running: bool,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub rotation_id: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub progress: Option<RekeyProgress>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub error: Option<String>,
+}
+
+/// The server's salt rotation: at most one runs at a time, and its moved
+/// keys stay mapped until the process restarts.
+#[derive(Debug, Default)]
+pub struct SaltRotation {
+	keys: Arc<KeyMap>,
+	running: AtomicBool,
+	status: Mutex<RotationStatus>,
+}
+
+impl SaltRotation {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Keys moved by rotations on this server.
+	pub fn keys(&self) -> Arc<KeyMap> {
+		self.keys.clone()
+	}
+
+	pub fn status(&self) -> RotationStatus {
+		self.status.lock().unwrap().clone()
+	}
+
+	/// Claim the rotation slot; `false` when one is already running.
+	fn try_start(&self, rotation_id: String) -> bool {
+		if self.running.swap(true, Ordering::SeqCst) {
+			return false;
+		}
+		*self.status.lock().unwrap() = RotationStatus {
+			running: true,
+			rotation_id: Some(rotation_id),
+			..Default::default()
+		};
+		true
+	}
+
+	fn report(&self, progress: &RekeyProgress) {
+		self.status.lock().unwrap().progress = Some(progress.clone());
+	}
+
+	fn finish(&self, result: AgeResult<RekeyProgress>) {
+		let mut status = self.status.lock().unwrap();
+		status.running = false;
+		match result {
+			Ok(progress) => status.progress = Some(progress),
+			Err(e) => status.error = Some(e.to_string()),
+		}
+		self.running.store(false, Ordering::SeqCst);
+	}
+}
+
+/// Body of `POST /admin/keys/rotate`.
+#[derive(Debug, Deserialize)]
+pub struct RotateSaltRequest {
+	pub old_salt: String,
+	pub new_salt: String,
+	/// Nodes re-keyed per batch; [`DEFAULT_REKEY_BATCH`] when unset.
+	#[serde(default)]
+	pub batch_size: Option<usize>,
+}
+
+/// Start re-keying the graph from `old_salt` to `new_salt` in the
+/// background, resuming an earlier run for the same pair. Responds 202
+/// with the rotation status, or 409 while another rotation runs. Requires
+/// a valid bearer token.
+pub async fn rotate_salt(
+	State(state): State<AppState>,
+	headers: HeaderMap,
+	Json(req): Json<RotateSaltRequest>,
+) -> Response {
+	let claims = match crate::auth::authenticate(&state, &headers).await {
+		Ok(claims) => claims,
+		Err(resp) => return resp,
+	};
+	if req.old_salt == req.new_salt {
+		return (StatusCode::BAD_REQUEST, "old_salt and new_salt are equal").into_response();
+	}
+
+	let rotation = state.salt_rotation.clone();
+	let rekeyer = Rekeyer::new(state.repo.clone(), &req.old_salt, &req.new_salt)
+		.with_key_prefixes(state.key_prefixes.load_full())
+		.with_batch_size(req.batch_size.unwrap_or(DEFAULT_REKEY_BATCH))
+		.with_key_map(rotation.keys());
+	if !rotation.try_start(rekeyer.rotation_id()) {
+		return (StatusCode::CONFLICT, "a salt rotation is already running").into_response();
+	}
+
+	let audit = state.audit.clone();
+	let request_id = crate::audit::request_id(&headers);
+	let status = rotation.status();
+	tokio::spawn(async move {
+		let result = rekeyer.run(|progress| rotation.report(progress)).await;
+		match &result {
+			Ok(progress) => {
+				log::info!(
+					"salt rotation {} finished: {} renamed, {} merged, {} unresolved",
+					rekeyer.rotation_id(),
+					progress.renamed,
+					progress.merged,
+					progress.unresolved
+				);
+				audit.emit(
+					AuditEvent::new(AuditAction::Rekey, request_id)
+						.with_subject(claims.sub)
+						.with_endpoint("/admin/keys/rotate")
+						.with_records(progress.renamed + progress.merged),
+				);
+			}
+			Err(e) => log::error!("salt rotation {} failed: {}", rekeyer.rotation_id(), e),
+		}
+		rotation.finish(result);
+	});
+	(StatusCode::ACCEPTED, Json(status)).into_response()
+}
+
+/// Report the salt rotation's progress. Requires a valid bearer token.
+pub async fn salt_rotation_status(State(state): State<AppState>, headers: HeaderMap) -> Response {
+	if let Err(resp) = crate::auth::authenticate(&state, &headers).await {
+		return resp;
+	}
+	(StatusCode::OK, Json(state.salt_rotation.status())).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn candidate(key: &str, field_type: &str, value: &str) -> RekeyCandidate {
+		RekeyCandidate {
+			key: key.to_string(),
+			field_type: Some(field_type.to_string()),
+			value: Some(value.to_string()),
+			raw: None,
+		}
+	}
+
+	fn rekeyer(old_salt: &str, new_salt: &str) -> Rekeyer {
+		let repo = crate::ingest::test_utils::create_test_app_state().repo;
+		Rekeyer::new(repo, old_salt, new_salt)
+	}
+
+	#[test]
+	fn resolves_keys_under_the_old_salt() {
+		let rekeyer = rekeyer("old", "new");
+		let old_key = salted_key("domain:example.com", "old");
+		let new_key = salted_key("domain:example.com", "new");
+		assert_eq!(
+			rekeyer.resolve(&candidate(&old_key, "domain", "example.com")),
+			Resolution::Move(new_key.clone())
+		);
+		assert_eq!(
+			rekeyer.resolve(&candidate(&new_key, "domain", "example.com")),
+			Resolution::Moved
+		);
+		assert_eq!(
+			rekeyer.resolve(&candidate("0123456789abcdef", "domain", "example.com")),
+			Resolution::Unresolved
+		);
+	}
+
+	#[test]
+	fn recovers_unsalted_keys_from_raw_values() {
+		let rekeyer = rekeyer("old", "");
+		let raw = RekeyCandidate {
+			key: salted_key("domain:example.com", "old"),
+			field_type: Some("domain".to_string()),
+			value: None,
+			raw: Some("Example.COM.".to_string()),
+		};
+		assert_eq!(
+			rekeyer.resolve(&raw),
+			Resolution::Move("domain:example.com".to_string())
+		);
+	}
+
+	#[test]
+	fn unsalted_keys_are_moved_as_is() {
+		let rekeyer = rekeyer("", "new");
+		let bare = RekeyCandidate {
+			key: "ip:10.0.0.1".to_string(),
+			..Default::default()
+		};
+		let new_key = salted_key("ip:10.0.0.1", "new");
+		assert_eq!(rekeyer.resolve(&bare), Resolution::Move(new_key.clone()));
+
+		rekeyer.keys.insert("ip:10.0.0.1", &new_key);
+		let moved = RekeyCandidate {
+			key: new_key,
+			..Default::default()
+		};
+		assert_eq!(rekeyer.resolve(&moved), Resolution::Moved);
+	}
+
+	#[test]
+	fn key_map_translates_moved_keys_only() {
+		let keys = KeyMap::new();
+		assert!(keys.is_empty());
+		keys.insert("old", "new");
+		assert_eq!(keys.translate("old"), "new");
+		assert_eq!(keys.translate("other"), "other");
+		assert!(keys.is_new_key("new"));
+		assert!(!keys.is_new_key("old"));
+		assert_eq!(keys.len(), 1);
+	}
+
+	#[test]
+	fn rotation_id_depends_on_both_salts() {
+		let id = rekeyer("a", "b").rotation_id();
+		assert_eq!(id, rekeyer("a", "b").rotation_id());
+		assert_ne!(id, rekeyer("b", "a").rotation_id());
+	}
+
+	#[test]
+	fn only_one_rotation_runs_at_a_time() {
+		let rotation = SaltRotation::new();
+		assert!(rotation.try_start("r1".to_string()));
+		assert!(!rotation.try_start("r2".to_string()));
+		rotation.finish(Ok(RekeyProgress {
+			done: true,
+			..Default::default()
+		}));
+		let status = rotation.status();
+		assert!(!status.running);
+		assert_eq!(status.rotation_id.as_deref(), Some("r1"));
+		assert!(rotation.try_start("r2".to_string()));
+	}
+}
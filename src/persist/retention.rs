@@ -0,0 +1,141 @@
+//! Per-label retention for graph nodes.
+//!
+//! Labels with a TTL are swept periodically: nodes whose `last_seen` is
+//! older than the TTL are tombstoned (or deleted, see [`RetentionAction`]).
+//! Each pass expires at most `limit` nodes per label so a large backlog is
+//! worked off over several passes instead of one long-locking statement.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::age_client::{AgeRepo, RetentionAction};
+use crate::audit::{AuditAction, AuditEvent, AuditLog};
+use crate::observability::MetricsRegistry;
+
+/// Label → TTL mapping plus what to do with expired nodes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetentionPolicy {
+	ttls: Vec<(String, Duration)>,
+	action: RetentionAction,
+	limit: usize,
+}
+
+impl RetentionPolicy {
+	/// A policy with no TTLs that expires up to `limit` nodes per label and
+	/// pass with `action`.
+	pub fn new(action: RetentionAction, limit: usize) -> Self {
+		Self {
+			ttls: Vec::new(),
+			action,
+			limit: limit.max(1),
+		}
+	}
+
+	/// Expire `label` nodes not seen for `ttl`.
+	pub fn with_ttl(mut self, label: impl Into<String>, ttl: Duration) -> Self {
+		let label = label.into();
+		self.ttls.retain(|(l, _)| *l != label);
+		self.ttls.push((label, ttl));
+		self
+	}
+
+	/// Add comma-separated `label=seconds` TTLs, e.g. `Token=3600,IP=86400`.
+	pub fn parse_ttls(mut self, spec: &str) -> Result<Self, String> {
+		for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+			let (label, secs) = entry
+				.split_once('=')
+				.ok_or_else(|| format!("retention entry '{}' is not label=seconds", entry))?;
+			let label = label.trim();
+			if label.is_empty() {
+				return Err(format!("retention entry '{}' has no label", entry));
+			}
+			let secs = secs
+				.trim()
+				.parse::<u64>()
+				.ok()
+				.filter(|s| *s > 0)
+				.ok_or_else(|| format!("retention entry '{}' needs a positive TTL", entry))?;
+			self = self.with_ttl(label, Duration::from_secs(secs));
+		}
+		Ok(self)
+	}
+
+	/// Whether any label has a TTL.
+	pub fn is_empty(&self) -> bool {
+		self.ttls.is_empty()
+	}
+
+	/// TTL configured for `label`, if any.
+	pub fn ttl(&self, label: &str) -> Option<Duration> {
+		self.ttls.iter().find(|(l, _)| l == label).map(|(_, t)| *t)
+	}
+
+	/// Run one pass: expire up to `limit` nodes per label whose TTL has
+	/// elapsed. Returns the number of nodes expired. A failing label is
+	/// logged and does not stop the others.
+	pub async fn sweep(&self, repo: &dyn AgeRepo, metrics: &MetricsRegistry) -> u64 {
+		let now = chrono::Utc::now();
+		let mut expired = 0;
+		for (label, ttl) in &self.ttls {
+			let Ok(ttl) = chrono::Duration::from_std(*ttl) else {
+				continue;
+			};
+			let cutoff = (now - ttl).to_rfc3339();
+			match repo.expire(label, &cutoff, self.action, self.limit).await {
+				Ok(n) => {
+					metrics.retention_expired_total.inc_by(n);
+					expired += n;
+				}
+				Err(e) => log::warn!("retention sweep of {} failed: {}", label, e),
+			}
+		}
+		expired
+	}
+}
+
+/// Spawn a background task running `policy.sweep` every `interval`.
+pub fn spawn_retention_sweeper(
+	repo: Arc<dyn AgeRepo>,
+	metrics: Arc<MetricsRegistry>,
+	policy: RetentionPolicy,
+	interval: Duration,
+	audit: Arc<AuditLog>,
+) -> tokio::task::JoinHandle<()> {
+	tokio::spawn(async move {
+		let mut ticker = tokio::time::interval(interval);
+		loop {
+			ticker.tick().await;
+			let n = policy.sweep(repo.as_ref(), &metrics).await;
+			if n > 0 {
+				log::info!("retention expired {} node(s)", n);
+				audit.emit(
+					AuditEvent::new(AuditAction::Expire, crate::audit::new_request_id())
+						.with_subject("retention")
+						.with_records(n),
+				);
+			}
+		}
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_ttls_reads_label_seconds_pairs() {
+		let policy = RetentionPolicy::new(RetentionAction::Tombstone, 100)
+			.parse_ttls("Token=3600, IPAddress=86400,")
+			.unwrap();
+		assert_eq!(policy.ttl("Token"), Some(Duration::from_secs(3600)));
+		assert_eq!(policy.ttl("IPAddress"), Some(Duration::from_secs(86400)));
+		assert_eq!(policy.ttl("FieldValue"), None);
+
+		let empty = RetentionPolicy::new(RetentionAction::Delete, 100).parse_ttls("");
+		assert!(empty.unwrap().is_empty());
+		for bad in ["Token", "=60", "Token=0", "Token=soon"] {
+			let parsed = RetentionPolicy::new(RetentionAction::Delete, 100).parse_ttls(bad);
+			assert!(parsed.is_err(), "{}", bad);
+		}
+	}
+}
@@ -0,0 +1,277 @@
+//! Per-label property schemas checked before nodes are persisted.
+//!
+//! Ingest and sync can write any property set to a node, so nodes of one
+//! label drift apart and queries over them become unreliable. A schema
+//! names the properties a label requires and allows, with their types.
+//! Jobs that violate it are quarantined under
+//! [`QUARANTINE_LABEL`](super::labels::QUARANTINE_LABEL) or rejected to the
+//! dead-letter queue, and counted in `heimdall_persist_schema_violation_total`.
+//!
+//! Schemas are configured as a JSON object keyed by label:
+//!
+//! ```json
+//! {"FieldValue": {"required": {"field_type": "string"},
+//!                 "optional": {"raw": "string", "confidence": "number"},
+//!                 "additional": false}}
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Deserialize;
+use serde_json::Value;
+
+/// JSON type a property must have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PropertyType {
+	String,
+	/// Any number, integral or not.
+	Number,
+	Integer,
+	Boolean,
+	Array,
+	Object,
+}
+
+impl PropertyType {
+	/// Whether `value` has this type. `null` has none.
+	pub fn matches(self, value: &Value) -> bool {
+		match self {
+			PropertyType::String => value.is_string(),
+			PropertyType::Number => value.is_number(),
+			PropertyType::Integer => value.is_i64() || value.is_u64(),
+			PropertyType::Boolean => value.is_boolean(),
+			PropertyType::Array => value.is_array(),
+			PropertyType::Object => value.is_object(),
+		}
+	}
+
+	fn name(self) -> &'static str {
+		match self {
+			PropertyType::String => "string",
+			PropertyType::Number => "number",
+			PropertyType::Integer => "integer",
+			PropertyType::Boolean => "boolean",
+			PropertyType::Array => "array",
+			PropertyType::Object => "object",
+		}
+	}
+}
+
+/// Properties of one label.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LabelSchema {
+	/// Properties every node must carry, with their types.
+	#[serde(default)]
+	pub required: BTreeMap<String, PropertyType>,
+	/// Properties a node may carry, with their types.
+	#[serde(default)]
+	pub optional: BTreeMap<String, PropertyType>,
+	/// Whether properties listed in neither map are accepted, with any
+	/// type.
+	#[serde(default = "LabelSchema::additional_default")]
+	pub additional: bool,
+}
+
+impl LabelSchema {
+	fn additional_default() -> bool {
+		true
+	}
+
+	/// Why `props` violate this schema, or `None` if they conform.
+	/// Messages name properties but never include their values.
+	pub fn violation(&self, props: &Value) -> Option<String> {
+		let empty = serde_json::Map::new();
+		let props = match props {
+			Value::Object(map) => map,
+			Value::Null => &empty,
+			_ => return Some("props are not an object".to_string()),
+		};
+		for (name, ty) in &self.required {
+			match props.get(name) {
+				None | Some(Value::Null) => {
+					return Some(format!("missing required property '{}'", name));
+				}
+				Some(value) if !ty.matches(value) => {
+					return Some(format!("property '{}' must be of type {}", name, ty.name()));
+				}
+				Some(_) => {}
+			}
+		}
+		for (name, value) in props {
+			if self.required.contains_key(name) || value.is_null() {
+				continue;
+			}
+			match self.optional.get(name) {
+				Some(ty) if !ty.matches(value) => {
+					return Some(format!("property '{}' must be of type {}", name, ty.name()));
+				}
+				None if !self.additional => {
+					return Some(format!("property '{}' is not allowed", name));
+				}
+				_ => {}
+			}
+		}
+		None
+	}
+}
+
+/// What the batcher does with a job that violates its label's schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaViolation {
+	/// Write the node under [`QUARANTINE_LABEL`](super::labels::QUARANTINE_LABEL) instead.
+	Quarantine,
+	/// Move the job to the dead-letter queue instead of writing it.
+	Reject,
+}
+
+impl std::str::FromStr for SchemaViolation {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.trim().to_ascii_lowercase().as_str() {
+			"quarantine" => Ok(Self::Quarantine),
+			"reject" => Ok(Self::Reject),
+			other => Err(format!("unknown schema violation policy '{}'", other)),
+		}
+	}
+}
+
+/// Schemas by label and the policy for violations. Labels without a
+/// schema accept any properties.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelSchemas {
+	schemas: HashMap<String, LabelSchema>,
+	pub policy: SchemaViolation,
+}
+
+impl LabelSchemas {
+	pub fn new(policy: SchemaViolation) -> Self {
+		Self {
+			schemas: HashMap::new(),
+			policy,
+		}
+	}
+
+	/// Check nodes labelled `label` against `schema`.
+	pub fn with_schema(mut self, label: impl Into<String>, schema: LabelSchema) -> Self {
+		self.schemas.insert(label.into(), schema);
+		self
+	}
+
+	/// Add the schemas in `spec`, a JSON object keyed by label. An empty
+	/// spec adds none.
+	pub fn parse(mut self, spec: &str) -> Result<Self, String> {
+		if spec.trim().is_empty() {
+			return Ok(self);
+		}
+		let schemas: HashMap<String, LabelSchema> =
+			serde_json::from_str(spec).map_err(|e| format!("invalid label schemas: {}", e))?;
+		for (label, schema) in schemas {
+			if let Some(name) = schema
+				.required
+				.keys()
+				.find(|n| schema.optional.contains_key(*n))
+			{
+				return Err(format!(
+					"label '{}' lists property '{}' as both required and optional",
+					label, name
+				));
+			}
+			self.schemas.insert(label, schema);
+		}
+		Ok(self)
+	}
+
+	/// Whether any label has a schema.
+	pub fn is_empty(&self) -> bool {
+		self.schemas.is_empty()
+	}
+
+	/// Why `props` violate the schema of `label`, or `None` if they conform
+	/// or the label has no schema.
+	pub fn violation(&self, label: &str, props: &Value) -> Option<String> {
+		self.schemas
+			.get(label)
+			.and_then(|schema| schema.violation(props))
+			.map(|reason| format!("label {}: {}", label, reason))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+
+	fn schemas() -> LabelSchemas {
+		LabelSchemas::new(SchemaViolation::Quarantine)
+			.parse(
+				r#"{"FieldValue": {
+					"required": {"field_type": "string"},
+					"optional": {"raw": "string", "confidence": "number", "normalizer_version": "integer"},
+					"additional": false
+				}}"#,
+			)
+			.unwrap()
+	}
+
+	#[test]
+	fn conforming_props_pass() {
+		let schemas = schemas();
+		let props = json!({"field_type": "ip", "raw": "10.0.0.1", "confidence": 1.0});
+		assert_eq!(schemas.violation("FieldValue", &props), None);
+		assert_eq!(
+			schemas.violation("FieldValue", &json!({"field_type": "ip", "raw": null})),
+			None
+		);
+		// Labels without a schema accept anything.
+		assert_eq!(schemas.violation("Dump", &json!({"x": [1]})), None);
+	}
+
+	#[test]
+	fn missing_mistyped_and_unknown_properties_are_violations() {
+		let schemas = schemas();
+		for (props, reason) in [
+			(
+				json!({"raw": "a"}),
+				"missing required property 'field_type'",
+			),
+			(json!(null), "missing required property 'field_type'"),
+			(
+				json!({"field_type": 7}),
+				"property 'field_type' must be of type string",
+			),
+			(
+				json!({"field_type": "ip", "confidence": "high"}),
+				"property 'confidence' must be of type number",
+			),
+			(
+				json!({"field_type": "ip", "normalizer_version": 1.5}),
+				"property 'normalizer_version' must be of type integer",
+			),
+			(
+				json!({"field_type": "ip", "secret": "x"}),
+				"property 'secret' is not allowed",
+			),
+		] {
+			let got = schemas.violation("FieldValue", &props).unwrap();
+			assert_eq!(got, format!("label FieldValue: {}", reason));
+		}
+	}
+
+	#[test]
+	fn invalid_specs_are_rejected() {
+		let parse = |spec: &str| LabelSchemas::new(SchemaViolation::Reject).parse(spec);
+		assert!(parse("").unwrap().is_empty());
+		assert!(parse("{\"A\": {\"required\": {\"x\": \"date\"}}}").is_err());
+		assert!(parse("{\"A\": {\"requird\": {}}}").is_err());
+		let err = parse(
+			"{\"A\": {\"required\": {\"x\": \"string\"}, \"optional\": {\"x\": \"string\"}}}",
+		)
+		.unwrap_err();
+		assert!(err.contains("both required and optional"), "{}", err);
+		assert_eq!("REJECT".parse(), Ok(SchemaViolation::Reject));
+		assert!("drop".parse::<SchemaViolation>().is_err());
+	}
+}
@@ -0,0 +1,199 @@
+//! Compaction of identical consecutive sightings.
+//!
+//! A high-frequency feed can report the same value in the same column many
+//! times a second, and each report would otherwise become its own
+//! `Sighting` node. [`SightingCompactor`] remembers the latest sighting of
+//! each `(value, column)` pair; a repeat within the window folds into it,
+//! combining `count`, `first_seen` and `last_seen` with the sync module's
+//! [`MergeStrategy::MergeSightings`], instead of creating a node.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use serde_json::{Value, json};
+
+use crate::sync::{
+	EntityVersion, MergeConfig, MergeResolver, MergeRule, MergeStrategy, VersionVector,
+};
+
+/// Pairs remembered at once; past it, pairs whose window has passed are
+/// forgotten.
+const MAX_TRACKED: usize = 100_000;
+
+/// What to do with one sighting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Compaction {
+	/// Create a `Sighting` with this `sighting_id` and `count` 1.
+	New(String),
+	/// Update the existing `Sighting` `id` to `count` and `last_seen`.
+	Coalesced {
+		id: String,
+		count: u64,
+		last_seen: String,
+	},
+}
+
+struct Latest {
+	id: String,
+	/// `count`, `first_seen` and `last_seen` (Unix milliseconds).
+	props: Value,
+}
+
+/// Decides which sightings coalesce; shared by every row persisted.
+pub struct SightingCompactor {
+	window_ms: u64,
+	resolver: MergeResolver,
+	latest: Mutex<HashMap<(String, String), Latest>>,
+}
+
+impl SightingCompactor {
+	/// Coalesce sightings of a pair at most `window_secs` after its previous
+	/// one.
+	pub fn new(window_secs: u64) -> Self {
+		let rule =
+			MergeRule::new("Sighting", MergeStrategy::MergeSightings).with_merge_fields(vec![
+				"count".to_string(),
+				"first_seen".to_string(),
+				"last_seen".to_string(),
+			]);
+		Self {
+			window_ms: window_secs.saturating_mul(1000),
+			resolver: MergeResolver::new(MergeConfig::new().add_rule(rule)),
+			latest: Mutex::new(HashMap::new()),
+		}
+	}
+
+	/// Place a sighting of `value_key` in `column` at RFC 3339 `timestamp`.
+	/// Sightings with an unparseable timestamp are never coalesced.
+	pub fn admit(&self, value_key: &str, column: &str, timestamp: &str) -> Compaction {
+		let Some(at) = parse_ms(timestamp) else {
+			return Compaction::New(new_sighting_id());
+		};
+		let sighting = json!({"count": 1, "first_seen": at, "last_seen": at});
+		let pair = (value_key.to_string(), column.to_string());
+		let mut latest = self.latest.lock().unwrap();
+
+		if let Some(prev) = latest.get_mut(&pair) {
+			let last_seen = prev.props["last_seen"].as_u64().unwrap_or(0);
+			if at.abs_diff(last_seen) <= self.window_ms {
+				let version = |props: &Value, at: u64| {
+					EntityVersion::new(
+						"Sighting",
+						&prev.id,
+						props.clone(),
+						VersionVector::new("", at),
+					)
+				};
+				let merged = self
+					.resolver
+					.merge(&version(&prev.props, last_seen), &version(&sighting, at));
+				if let Ok(merged) = merged {
+					prev.props = merged.props;
+					let last_seen = prev.props["last_seen"].as_u64().unwrap_or(at);
+					return Compaction::Coalesced {
+						id: prev.id.clone(),
+						count: prev.props["count"].as_u64().unwrap_or(1),
+						last_seen: format_ms(last_seen).unwrap_or_else(|| timestamp.to_string()),
+					};
+				}
+			}
+		}
+
+		if latest.len() >= MAX_TRACKED {
+			let window_ms = self.window_ms;
+			latest.retain(|_, l| {
+				at.abs_diff(l.props["last_seen"].as_u64().unwrap_or(0)) <= window_ms
+			});
+			if latest.len() >= MAX_TRACKED {
+				latest.clear();
+			}
+		}
+		let id = new_sighting_id();
+		latest.insert(
+			pair,
+			Latest {
+				id: id.clone(),
+				props: sighting,
+			},
+		);
+		Compaction::New(id)
+	}
+}
+
+impl std::fmt::Debug for SightingCompactor {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("SightingCompactor")
+			.field("window_ms", &self.window_ms)
+			.finish()
+	}
+}
+
+fn parse_ms(timestamp: &str) -> Option<u64> {
+	let at = DateTime::parse_from_rfc3339(timestamp).ok()?;
+	u64::try_from(at.timestamp_millis()).ok()
+}
+
+fn format_ms(ms: u64) -> Option<String> {
+	DateTime::<Utc>::from_timestamp_millis(i64::try_from(ms).ok()?).map(|t| t.to_rfc3339())
+}
+
+fn new_sighting_id() -> String {
+	format!("{:032x}", rand::random::<u128>())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn at(secs: u64) -> String {
+		format_ms(1_700_000_000_000 + secs * 1000).unwrap()
+	}
+
+	#[test]
+	fn repeats_within_the_window_coalesce() {
+		let compactor = SightingCompactor::new(60);
+		let Compaction::New(id) = compactor.admit("ip:192.0.2.1", "ip", &at(0)) else {
+			panic!("first sighting is new");
+		};
+		for i in 1..10 {
+			assert_eq!(
+				compactor.admit("ip:192.0.2.1", "ip", &at(i)),
+				Compaction::Coalesced {
+					id: id.clone(),
+					count: i + 1,
+					last_seen: at(i),
+				}
+			);
+		}
+		// The window follows the latest sighting, not the first.
+		assert!(matches!(
+			compactor.admit("ip:192.0.2.1", "ip", &at(65)),
+			Compaction::Coalesced { count: 11, .. }
+		));
+	}
+
+	#[test]
+	fn other_pairs_and_late_sightings_are_new() {
+		let compactor = SightingCompactor::new(60);
+		let Compaction::New(first) = compactor.admit("ip:192.0.2.1", "ip", &at(0)) else {
+			panic!("first sighting is new");
+		};
+		assert!(matches!(
+			compactor.admit("ip:192.0.2.1", "src_ip", &at(1)),
+			Compaction::New(_)
+		));
+		assert!(matches!(
+			compactor.admit("ip:192.0.2.2", "ip", &at(1)),
+			Compaction::New(_)
+		));
+		match compactor.admit("ip:192.0.2.1", "ip", &at(61)) {
+			Compaction::New(id) => assert_ne!(id, first),
+			other => panic!("expected a new sighting, got {:?}", other),
+		}
+		assert!(matches!(
+			compactor.admit("ip:192.0.2.1", "ip", "yesterday"),
+			Compaction::New(_)
+		));
+	}
+}
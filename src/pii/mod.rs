@@ -1 +1,2 @@
 pub mod pii_policy;
+pub mod raw_store;
@@ -7,6 +7,8 @@ use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use crate::audit::{AuditAction, AuditEvent, AuditLog};
+
 /// PII field action: how to handle sensitive data
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -78,6 +80,7 @@ pub struct PiiPolicyEngine {
 	master_key: Arc<Vec<u8>>,
 	key_id: String,
 	rng: SystemRandom,
+	audit: Option<Arc<AuditLog>>,
 }
 
 /// Nonce generator for AES-GCM (generates random nonces)
@@ -116,9 +119,51 @@ impl PiiPolicyEngine {
 			master_key: Arc::new(master_key),
 			key_id,
 			rng: SystemRandom::new(),
+			audit: None,
 		})
 	}
 
+	/// Record decrypts in `audit` instead of on stderr
+	pub fn with_audit_log(mut self, audit: Arc<AuditLog>) -> Self {
+		self.audit = Some(audit);
+		self
+	}
+
+	/// Build the engine for `settings.pii_master_key`, or `None` when no key
+	/// is configured.
+	///
+	/// A configured key that cannot be used is an error when
+	/// `settings.pii_fail_closed` is set, so the server refuses to start
+	/// rather than store protected fields in plaintext. Otherwise a warning
+	/// is printed and no engine is built.
+	pub fn from_settings(
+		settings: &crate::config::Settings,
+		audit: Arc<AuditLog>,
+	) -> Result<Option<Self>> {
+		let Some(key_hex) = &settings.pii_master_key else {
+			return Ok(None);
+		};
+		// Create a default policy config (can be extended to load from file)
+		let built = Self::parse_master_key_hex(key_hex)
+			.map_err(|e| anyhow!("failed to parse PII master key: {}", e))
+			.and_then(|key| {
+				Self::new(
+					PiiPolicyConfig::default(),
+					key,
+					"default-key-v1".to_string(),
+				)
+				.map_err(|e| anyhow!("failed to create PII engine: {}", e))
+			});
+		match built {
+			Ok(engine) => Ok(Some(engine.with_audit_log(audit))),
+			Err(e) if settings.pii_fail_closed => Err(e),
+			Err(e) => {
+				eprintln!("warning: {}; PII will not be protected", e);
+				Ok(None)
+			}
+		}
+	}
+
 	/// Parse master key from hex string
 	pub fn parse_master_key_hex(hex: &str) -> Result<Vec<u8>> {
 		if hex.len() != 64 {
@@ -144,6 +189,16 @@ impl PiiPolicyEngine {
 			.unwrap_or(self.config.default_action)
 	}
 
+	/// Name whose rule applies to a value of `field_type` read from input
+	/// field `source_field`: the source field when a rule names it,
+	/// otherwise the field type
+	pub fn rule_field<'a>(&self, source_field: Option<&'a str>, field_type: &'a str) -> &'a str {
+		match source_field {
+			Some(name) if self.config.rules.contains_key(name) => name,
+			_ => field_type,
+		}
+	}
+
 	/// Apply PII policy to a field value
 	pub fn apply_policy(&self, field_name: &str, value: &str) -> Result<String> {
 		let action = self.get_action(field_name);
@@ -263,8 +318,15 @@ impl PiiPolicyEngine {
 			key_id: envelope.key_id.clone(),
 		};
 
-		// Log to stderr for now (in production, this should go to a secure audit log)
-		eprintln!("AUDIT: decrypt {}", serde_json::to_string(&audit)?);
+		match &self.audit {
+			Some(log) => log.emit(
+				AuditEvent::new(AuditAction::Decrypt, crate::audit::new_request_id())
+					.with_subject(audit.actor)
+					.with_reason(audit.reason)
+					.with_target(audit.key_id),
+			),
+			None => eprintln!("AUDIT: decrypt {}", serde_json::to_string(&audit)?),
+		}
 
 		String::from_utf8(plaintext_bytes.to_vec())
 			.map_err(|_| anyhow!("decrypted data is not valid UTF-8"))
@@ -397,6 +459,47 @@ mod tests {
 		assert!(engine.is_ok());
 	}
 
+	#[test]
+	fn test_invalid_master_key_fails_closed() {
+		let audit = Arc::new(AuditLog::disabled());
+		let settings = crate::config::Settings {
+			pii_master_key: Some("not-a-key".to_string()),
+			..Default::default()
+		};
+		assert!(settings.pii_fail_closed);
+		let Err(err) = PiiPolicyEngine::from_settings(&settings, audit.clone()) else {
+			panic!("an unusable master key was accepted");
+		};
+		assert!(err.to_string().contains("failed to parse PII master key"));
+
+		// Only an explicit opt-out degrades to no protection.
+		let lenient = crate::config::Settings {
+			pii_fail_closed: false,
+			..settings
+		};
+		assert!(
+			PiiPolicyEngine::from_settings(&lenient, audit.clone())
+				.unwrap()
+				.is_none()
+		);
+
+		let valid = crate::config::Settings {
+			pii_master_key: Some("42".repeat(32)),
+			..Default::default()
+		};
+		assert!(
+			PiiPolicyEngine::from_settings(&valid, audit.clone())
+				.unwrap()
+				.is_some()
+		);
+		let unset = crate::config::Settings::default();
+		assert!(
+			PiiPolicyEngine::from_settings(&unset, audit)
+				.unwrap()
+				.is_none()
+		);
+	}
+
 	#[test]
 	fn test_policy_engine_rejects_invalid_key() {
 		let config = test_config();
@@ -0,0 +1,237 @@
+//! Encrypted side store for original ingest payloads.
+//!
+//! Raw lines are sealed with the PII engine's envelope encryption and
+//! appended to an NDJSON file keyed by the record's canonical key. Nothing
+//! is written in plaintext; reading a payload back goes through
+//! `PiiPolicyEngine::decrypt`, which emits an audit record.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::config::Settings;
+use crate::pii::pii_policy::{EncryptedEnvelope, PiiPolicyEngine};
+
+/// One line of the raw-payload store.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RawPayloadEntry {
+	/// Canonical key of the record the payload belongs to.
+	pub key: String,
+	/// RFC 3339 time the payload was stored.
+	pub stored_at: String,
+	/// The encrypted original payload.
+	pub envelope: EncryptedEnvelope,
+}
+
+/// Append-only, encrypted store of raw ingest payloads.
+pub struct RawPayloadStore {
+	path: PathBuf,
+	engine: Arc<PiiPolicyEngine>,
+	// Serializes appends so concurrent uploads never interleave lines.
+	writer: Mutex<File>,
+}
+
+impl RawPayloadStore {
+	/// Open (creating if needed) the store at `path`.
+	pub fn open(path: impl AsRef<Path>, engine: Arc<PiiPolicyEngine>) -> Result<Self> {
+		let path = path.as_ref().to_path_buf();
+		if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+			std::fs::create_dir_all(parent)
+				.with_context(|| format!("failed to create {}", parent.display()))?;
+		}
+
+		let mut options = OpenOptions::new();
+		options.create(true).append(true);
+		#[cfg(unix)]
+		{
+			use std::os::unix::fs::OpenOptionsExt;
+			options.mode(0o600);
+		}
+		let file = options
+			.open(&path)
+			.with_context(|| format!("failed to open raw payload store {}", path.display()))?;
+
+		Ok(Self {
+			path,
+			engine,
+			writer: Mutex::new(file),
+		})
+	}
+
+	/// The store `settings` configure, or `None` while it is disabled.
+	/// Payloads are only ever written sealed, so an enabled store without
+	/// a PII `engine` is an error.
+	pub fn from_settings(
+		settings: &Settings,
+		engine: Option<Arc<PiiPolicyEngine>>,
+	) -> Result<Option<Self>> {
+		if !settings.raw_store_enabled {
+			return Ok(None);
+		}
+		let engine =
+			engine.context("raw payload store is enabled but no PII master key is configured")?;
+		Self::open(&settings.raw_store_path, engine).map(Some)
+	}
+
+	/// Encrypt `raw` and append it under `key`.
+	pub fn store(&self, key: &str, raw: &str) -> Result<()> {
+		self.store_many(std::iter::once((key, raw)))
+	}
+
+	/// Encrypt and append several `(key, raw)` pairs with a single write.
+	pub fn store_many<'a>(
+		&self,
+		items: impl IntoIterator<Item = (&'a str, &'a str)>,
+	) -> Result<()> {
+		let stored_at = chrono::Utc::now().to_rfc3339();
+		let mut buf = Vec::new();
+		for (key, raw) in items {
+			let entry = RawPayloadEntry {
+				key: key.to_string(),
+				stored_at: stored_at.clone(),
+				envelope: self.engine.encrypt(raw)?,
+			};
+			serde_json::to_writer(&mut buf, &entry)?;
+			buf.push(b'\n');
+		}
+		if buf.is_empty() {
+			return Ok(());
+		}
+
+		let mut file = self
+			.writer
+			.lock()
+			.map_err(|_| anyhow::anyhow!("raw payload store lock poisoned"))?;
+		file.write_all(&buf)?;
+		file.flush()?;
+		Ok(())
+	}
+
+	/// All encrypted envelopes stored under `key`, oldest first.
+	pub fn envelopes(&self, key: &str) -> Result<Vec<EncryptedEnvelope>> {
+		let file = File::open(&self.path)
+			.with_context(|| format!("failed to open raw payload store {}", self.path.display()))?;
+		let mut out = Vec::new();
+		for line in BufReader::new(file).lines() {
+			let line = line?;
+			if line.trim().is_empty() {
+				continue;
+			}
+			match serde_json::from_str::<RawPayloadEntry>(&line) {
+				Ok(entry) if entry.key == key => out.push(entry.envelope),
+				Ok(_) => {}
+				Err(e) => log::warn!("skipping corrupt raw payload entry: {}", e),
+			}
+		}
+		Ok(out)
+	}
+
+	/// Decrypt every payload stored under `key`. Each decryption is audited
+	/// against `actor` and `reason`.
+	pub fn retrieve(&self, key: &str, actor: &str, reason: &str) -> Result<Vec<String>> {
+		self.envelopes(key)?
+			.iter()
+			.map(|envelope| self.engine.decrypt(envelope, actor, reason))
+			.collect()
+	}
+}
+
+#[cfg(test)]
+#[cfg(feature = "unit-tests")]
+mod tests {
+	use super::*;
+	use crate::pii::pii_policy::PiiPolicyConfig;
+
+	fn engine() -> Arc<PiiPolicyEngine> {
+		Arc::new(
+			PiiPolicyEngine::new(
+				PiiPolicyConfig::default(),
+				vec![0x42; 32],
+				"test-key-1".to_string(),
+			)
+			.unwrap(),
+		)
+	}
+
+	#[test]
+	fn stored_payload_decrypts_to_original() {
+		let dir = tempfile::tempdir().unwrap();
+		let store = RawPayloadStore::open(dir.path().join("raw.ndjson"), engine()).unwrap();
+
+		let raw = r#"{"field_type":"email","value":"Alice@Example.com"}"#;
+		store.store("alice@example.com", raw).unwrap();
+		store.store("bob@example.com", "other").unwrap();
+
+		let payloads = store
+			.retrieve("alice@example.com", "tester", "unit test")
+			.unwrap();
+		assert_eq!(payloads, vec![raw.to_string()]);
+
+		let on_disk = std::fs::read_to_string(dir.path().join("raw.ndjson")).unwrap();
+		assert!(!on_disk.contains("Alice@Example.com"));
+	}
+
+	#[test]
+	fn unknown_key_has_no_payloads() {
+		let dir = tempfile::tempdir().unwrap();
+		let store = RawPayloadStore::open(dir.path().join("raw.ndjson"), engine()).unwrap();
+		assert!(store.envelopes("missing").unwrap().is_empty());
+	}
+
+	#[tokio::test]
+	async fn ndjson_upload_stores_raw_lines_only_when_enabled() {
+		use axum::body::Body;
+		use axum::http::Request;
+		use axum::response::IntoResponse;
+
+		let line = r#"{"field_type":"domain","value":"Example.COM."}"#;
+		let request = || {
+			Request::builder()
+				.uri("/ingest/ndjson")
+				.body(Body::from(format!("{}\n", line)))
+				.unwrap()
+		};
+
+		// The store the settings configure, at a path in a fresh directory.
+		let configured = |enabled: bool| {
+			let dir = tempfile::tempdir().unwrap();
+			let settings = crate::config::Settings {
+				raw_store_enabled: enabled,
+				raw_store_path: dir.path().join("raw.ndjson").display().to_string(),
+				..crate::config::Settings::default()
+			};
+			let store = RawPayloadStore::from_settings(&settings, Some(engine()))
+				.unwrap()
+				.map(Arc::new);
+			let mut state = crate::ingest::test_utils::create_test_app_state();
+			if let Some(store) = &store {
+				state = state.with_raw_store(store.clone());
+			}
+			(dir, store, state)
+		};
+
+		// Enabled: the original line is recoverable under the canonical key.
+		let (_dir, store, state) = configured(true);
+		let resp = crate::ingest::ndjson_upload(axum::extract::State(state), request())
+			.await
+			.into_response();
+		assert!(resp.status().is_success());
+		let payloads = store
+			.unwrap()
+			.retrieve("example.com", "tester", "unit test")
+			.unwrap();
+		assert_eq!(payloads, vec![line.to_string()]);
+
+		// Disabled: nothing is written to the configured directory.
+		let (dir, store, state) = configured(false);
+		assert!(store.is_none());
+		let resp = crate::ingest::ndjson_upload(axum::extract::State(state), request())
+			.await
+			.into_response();
+		assert!(resp.status().is_success());
+		assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+	}
+}
@@ -0,0 +1,332 @@
+//! Preflight checks behind `heimdall check`.
+//!
+//! Each check exercises the same code path `run()` uses at startup (config
+//! loading, TLS material, the AGE connection, OIDC discovery) so a deployment
+//! can be validated end-to-end without starting the server.
+
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::age_client::{AgeClient, AgeConnectOptions, AgeRepo};
+use crate::config::{self, Settings};
+use crate::sync::auth::OidcProvider;
+use crate::tls_utils;
+
+/// Outcome of a single check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+	Pass,
+	Fail,
+	/// The check did not apply, or a check it depends on failed.
+	Skip,
+}
+
+impl fmt::Display for CheckStatus {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			CheckStatus::Pass => "PASS",
+			CheckStatus::Fail => "FAIL",
+			CheckStatus::Skip => "SKIP",
+		})
+	}
+}
+
+/// A named check and what it found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+	pub name: &'static str,
+	pub status: CheckStatus,
+	pub detail: String,
+}
+
+impl CheckResult {
+	fn new(name: &'static str, status: CheckStatus, detail: impl Into<String>) -> Self {
+		Self {
+			name,
+			status,
+			detail: detail.into(),
+		}
+	}
+}
+
+/// Problems with loaded settings that `config::load` accepts but that would
+/// stop the server from starting or a feature from working.
+pub fn settings_problems(settings: &Settings) -> Vec<String> {
+	let mut problems = Vec::new();
+	if !matches!(settings.database_url.scheme(), "postgres" | "postgresql") {
+		problems.push(format!(
+			"database_url scheme '{}' is not postgres",
+			settings.database_url.scheme()
+		));
+	}
+	if settings.tls_cert.is_empty() || settings.tls_key.is_empty() {
+		problems.push("tls_cert and tls_key must both be set".to_string());
+	}
+	match &settings.pii_master_key {
+		Some(key) => {
+			if let Err(e) = crate::pii::pii_policy::PiiPolicyEngine::parse_master_key_hex(key) {
+				problems.push(format!("pii_master_key: {}", e));
+			}
+		}
+		None if settings.raw_store_enabled => {
+			problems.push("raw_store_enabled requires pii_master_key".to_string());
+		}
+		None => {}
+	}
+	if settings.sync_enabled && settings.oidc_discovery_url.is_empty() {
+		problems.push("sync_enabled requires oidc_discovery_url".to_string());
+	}
+	problems
+}
+
+/// Load and validate settings. The settings are returned when they loaded,
+/// even if `settings_problems` reported something, so later checks can run.
+pub fn check_config() -> (CheckResult, Option<Settings>) {
+	match config::load() {
+		Ok(settings) => {
+			let problems = settings_problems(&settings);
+			let result = if problems.is_empty() {
+				CheckResult::new("config", CheckStatus::Pass, "settings loaded")
+			} else {
+				CheckResult::new("config", CheckStatus::Fail, problems.join("; "))
+			};
+			(result, Some(settings))
+		}
+		Err(e) => (
+			CheckResult::new("config", CheckStatus::Fail, e.to_string()),
+			None,
+		),
+	}
+}
+
+/// Load the TLS certificate and key and apply the startup policy checks
+/// (expiry, hostname, key strength, no self-signed leaf).
+pub fn check_tls(settings: &Settings) -> CheckResult {
+	match tls_utils::load_server_config(
+		Path::new(&settings.tls_cert),
+		Path::new(&settings.tls_key),
+		&settings.host,
+		settings.tls_min_rsa_bits,
+	) {
+		Ok(_) => CheckResult::new(
+			"tls",
+			CheckStatus::Pass,
+			format!("certificate {} is valid", settings.tls_cert),
+		),
+		Err(e) => CheckResult::new("tls", CheckStatus::Fail, format!("{:#}", e)),
+	}
+}
+
+/// Connect to the database, ping it and check the tenant's graph exists.
+pub async fn check_db(settings: &Settings) -> CheckResult {
+	let graph = settings.graph_name();
+	let client = match AgeClient::connect_with(
+		settings.database_url.as_str(),
+		&graph,
+		AgeConnectOptions {
+			search_path: Some(settings.age_search_path.clone()),
+		},
+	)
+	.await
+	{
+		Ok(c) => c,
+		Err(e) => return CheckResult::new("database", CheckStatus::Fail, e.to_string()),
+	};
+	if let Err(e) = client.ping().await {
+		return CheckResult::new("database", CheckStatus::Fail, e.to_string());
+	}
+	match client.verify_graph().await {
+		Ok(()) => CheckResult::new(
+			"database",
+			CheckStatus::Pass,
+			format!("connected; graph '{}' exists", graph),
+		),
+		Err(e) => CheckResult::new("database", CheckStatus::Fail, e.to_string()),
+	}
+}
+
+/// Fetch the OIDC discovery document when sync is configured.
+pub async fn check_oidc(settings: &Settings) -> CheckResult {
+	if !settings.sync_enabled || settings.oidc_discovery_url.is_empty() {
+		return CheckResult::new("oidc", CheckStatus::Skip, "sync not configured");
+	}
+	let provider = OidcProvider::new(
+		settings.oidc_discovery_url.clone(),
+		settings.oidc_client_id.clone(),
+		settings.oidc_client_secret.clone(),
+	)
+	.with_fetch_timeout(Duration::from_secs(settings.oidc_fetch_timeout_secs))
+	.with_fetch_retries(
+		settings.oidc_fetch_retries,
+		Duration::from_millis(settings.oidc_fetch_backoff_ms),
+	);
+	match provider.fetch_discovery().await {
+		Ok(doc) => CheckResult::new(
+			"oidc",
+			CheckStatus::Pass,
+			format!("discovered issuer {}", doc.issuer),
+		),
+		Err(e) => CheckResult::new("oidc", CheckStatus::Fail, format!("{:#}", e)),
+	}
+}
+
+/// Run every check in order. Checks that need settings are skipped when
+/// they could not be loaded.
+pub async fn run_checks() -> Vec<CheckResult> {
+	let (config_result, settings) = check_config();
+	let mut results = vec![config_result];
+	match settings {
+		Some(settings) => {
+			results.push(check_tls(&settings));
+			results.push(check_db(&settings).await);
+			results.push(check_oidc(&settings).await);
+		}
+		None => {
+			for name in ["tls", "database", "oidc"] {
+				results.push(CheckResult::new(
+					name,
+					CheckStatus::Skip,
+					"settings not loaded",
+				));
+			}
+		}
+	}
+	results
+}
+
+/// True when no check failed; skipped checks do not count as failures.
+pub fn all_passed(results: &[CheckResult]) -> bool {
+	results.iter().all(|r| r.status != CheckStatus::Fail)
+}
+
+/// Render `results` as an aligned plain-text table.
+pub fn render_table(results: &[CheckResult]) -> String {
+	let width = results
+		.iter()
+		.map(|r| r.name.len())
+		.chain(std::iter::once("CHECK".len()))
+		.max()
+		.unwrap_or(0);
+	let mut out = format!("{:<width$}  STATUS  DETAIL\n", "CHECK");
+	for r in results {
+		out.push_str(&format!(
+			"{:<width$}  {:<6}  {}\n",
+			r.name,
+			r.status.to_string(),
+			r.detail
+		));
+	}
+	out
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rcgen::{
+		BasicConstraints, Certificate, CertificateParams, DistinguishedName, DnType, IsCa,
+	};
+	use std::time::{Duration, SystemTime};
+
+	/// Write a leaf for `host`, signed by a throwaway CA, and its key into
+	/// `dir`; returns settings pointing at them.
+	fn write_leaf(dir: &Path, host: &str, not_after: SystemTime) -> Settings {
+		let mut ca_params = CertificateParams::new(Vec::new());
+		ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+		let mut ca_dn = DistinguishedName::new();
+		ca_dn.push(DnType::CommonName, "preflight test CA");
+		ca_params.distinguished_name = ca_dn;
+		let ca = Certificate::from_params(ca_params).unwrap();
+
+		let mut params = CertificateParams::new(vec![host.to_string()]);
+		params.not_before = (SystemTime::now() - Duration::from_secs(2 * 86_400)).into();
+		params.not_after = not_after.into();
+		let leaf = Certificate::from_params(params).unwrap();
+
+		let cert_path = dir.join("tls.crt");
+		let key_path = dir.join("tls.key");
+		std::fs::write(&cert_path, leaf.serialize_pem_with_signer(&ca).unwrap()).unwrap();
+		std::fs::write(&key_path, leaf.serialize_private_key_pem()).unwrap();
+
+		Settings {
+			host: host.to_string(),
+			tls_cert: cert_path.display().to_string(),
+			tls_key: key_path.display().to_string(),
+			..Settings::default()
+		}
+	}
+
+	fn next_year() -> SystemTime {
+		SystemTime::now() + Duration::from_secs(365 * 86_400)
+	}
+
+	#[test]
+	fn default_settings_have_no_problems() {
+		assert!(settings_problems(&Settings::default()).is_empty());
+	}
+
+	#[test]
+	fn inconsistent_settings_are_reported() {
+		let settings = Settings {
+			database_url: "mysql://localhost/heimdall".parse().unwrap(),
+			tls_key: String::new(),
+			raw_store_enabled: true,
+			sync_enabled: true,
+			..Settings::default()
+		};
+		let problems = settings_problems(&settings);
+		assert_eq!(problems.len(), 4, "{:?}", problems);
+
+		let settings = Settings {
+			pii_master_key: Some("not-hex".to_string()),
+			..Settings::default()
+		};
+		assert_eq!(settings_problems(&settings).len(), 1);
+	}
+
+	#[test]
+	fn tls_passes_for_valid_ca_signed_leaf() {
+		let dir = tempfile::tempdir().unwrap();
+		let settings = write_leaf(dir.path(), "heimdall.example", next_year());
+		let result = check_tls(&settings);
+		assert_eq!(result.status, CheckStatus::Pass, "{}", result.detail);
+	}
+
+	#[test]
+	fn tls_fails_for_expired_wrong_host_or_missing_files() {
+		let dir = tempfile::tempdir().unwrap();
+		let yesterday = SystemTime::now() - Duration::from_secs(86_400);
+		let expired = write_leaf(dir.path(), "heimdall.example", yesterday);
+		let result = check_tls(&expired);
+		assert_eq!(result.status, CheckStatus::Fail);
+		assert!(result.detail.contains("expired"), "{}", result.detail);
+
+		let mut wrong_host = write_leaf(dir.path(), "heimdall.example", next_year());
+		wrong_host.host = "other.example".to_string();
+		let result = check_tls(&wrong_host);
+		assert_eq!(result.status, CheckStatus::Fail);
+		assert!(result.detail.contains("other.example"), "{}", result.detail);
+
+		let missing = Settings {
+			tls_cert: dir.path().join("absent.crt").display().to_string(),
+			..wrong_host
+		};
+		assert_eq!(check_tls(&missing).status, CheckStatus::Fail);
+	}
+
+	#[test]
+	fn table_lists_each_check_and_failures_are_detected() {
+		let results = vec![
+			CheckResult::new("config", CheckStatus::Pass, "settings loaded"),
+			CheckResult::new("oidc", CheckStatus::Skip, "sync not configured"),
+		];
+		assert!(all_passed(&results));
+		let table = render_table(&results);
+		assert_eq!(table.lines().count(), 3);
+		assert!(table.contains("config  PASS    settings loaded"));
+
+		let mut results = results;
+		results.push(CheckResult::new("tls", CheckStatus::Fail, "expired"));
+		assert!(!all_passed(&results));
+	}
+}
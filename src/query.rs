@@ -0,0 +1,218 @@
+//! Read-only Cypher queries streamed to analysts as NDJSON.
+
+use std::convert::Infallible;
+use std::time::Duration;
+
+use axum::{
+	Json,
+	body::{Body, Bytes},
+	extract::State,
+	http::{HeaderMap, HeaderValue, StatusCode, header},
+	response::{IntoResponse, Response},
+};
+use futures_util::stream::{self, BoxStream, Stream, StreamExt};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::age_client::AgeResult;
+use crate::state::AppState;
+
+/// Response header carrying the row cap applied to a streamed query; a
+/// response with exactly that many rows may have been cut short.
+pub const MAX_ROWS_HEADER: &str = "x-query-max-rows";
+
+/// Cypher clauses that write to the graph.
+const WRITE_CLAUSES: [&str; 6] = ["CREATE", "MERGE", "SET", "DELETE", "DETACH", "REMOVE"];
+
+/// Body of `POST /query/stream`.
+#[derive(Debug, Deserialize)]
+pub struct QueryRequest {
+	/// Cypher whose `RETURN` yields a single value per row.
+	pub cypher: String,
+	/// Rows to return at most; capped at `Settings.query_max_rows`.
+	pub limit: Option<usize>,
+}
+
+/// Whether `cypher` is free of clauses that write to the graph. Keywords
+/// inside string literals are ignored.
+pub fn is_read_only(cypher: &str) -> bool {
+	let mut words = Vec::new();
+	let mut word = String::new();
+	let mut quote = None;
+	for c in cypher.chars() {
+		match quote {
+			Some(q) if c == q => quote = None,
+			Some(_) => {}
+			None if c == '\'' || c == '"' || c == '`' => quote = Some(c),
+			None if c.is_ascii_alphanumeric() || c == '_' => word.push(c),
+			None => words.push(std::mem::take(&mut word)),
+		}
+	}
+	words.push(word);
+	!words.iter().any(|w| {
+		WRITE_CLAUSES
+			.iter()
+			.any(|clause| w.eq_ignore_ascii_case(clause))
+	})
+}
+
+/// NDJSON body for `rows`: one line per row until `max_rows` rows were
+/// sent, the rows end or `timeout` passes. A failure or timeout after
+/// the response has started is reported as a final `{"error": ...}` line.
+pub fn ndjson_rows(
+	rows: BoxStream<'static, AgeResult<Value>>,
+	max_rows: usize,
+	timeout: Duration,
+) -> impl Stream<Item = Result<Bytes, Infallible>> + Send + 'static {
+	let deadline = tokio::time::Instant::now() + timeout;
+	stream::unfold(
+		(rows, 0usize, false),
+		move |(mut rows, sent, done)| async move {
+			if done || sent >= max_rows {
+				return None;
+			}
+			let error = match tokio::time::timeout_at(deadline, rows.next()).await {
+				Ok(Some(Ok(row))) => {
+					let line = format!("{}\n", row);
+					return Some((Ok(Bytes::from(line)), (rows, sent + 1, false)));
+				}
+				Ok(None) => return None,
+				Ok(Some(Err(e))) => {
+					log::warn!("streamed query failed after {} rows: {}", sent, e);
+					e.to_string()
+				}
+				Err(_) => "query timed out".to_string(),
+			};
+			let line = format!("{}\n", serde_json::json!({ "error": error }));
+			Some((Ok(Bytes::from(line)), (rows, sent, true)))
+		},
+	)
+}
+
+/// Streaming NDJSON response for `rows`; see [`ndjson_rows`].
+pub fn ndjson_response(
+	rows: BoxStream<'static, AgeResult<Value>>,
+	max_rows: usize,
+	timeout: Duration,
+) -> Response {
+	let mut resp = Body::from_stream(ndjson_rows(rows, max_rows, timeout)).into_response();
+	resp.headers_mut().insert(
+		header::CONTENT_TYPE,
+		HeaderValue::from_static("application/x-ndjson"),
+	);
+	resp.headers_mut()
+		.insert(MAX_ROWS_HEADER, HeaderValue::from(max_rows));
+	resp
+}
+
+/// Run a read-only Cypher query and stream its rows as NDJSON while the
+/// database produces them. Returns at most `Settings.query_max_rows` rows
+/// and stops after `Settings.query_timeout_secs`. Queries with write
+/// clauses are rejected with 400. Requires a valid bearer token.
+pub async fn query_stream(
+	State(state): State<AppState>,
+	headers: HeaderMap,
+	Json(req): Json<QueryRequest>,
+) -> Response {
+	if let Err(resp) = crate::auth::authenticate(&state, &headers).await {
+		return resp;
+	}
+	if !is_read_only(&req.cypher) {
+		return (StatusCode::BAD_REQUEST, "query must not write to the graph").into_response();
+	}
+
+	let max_rows = req
+		.limit
+		.unwrap_or(state.settings.query_max_rows)
+		.min(state.settings.query_max_rows);
+	let timeout = Duration::from_secs(state.settings.query_timeout_secs);
+	let rows = state.repo.query_stream(&req.cypher, timeout);
+	ndjson_response(rows, max_rows, timeout)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::age_client::AgeError;
+
+	async fn collect(
+		rows: BoxStream<'static, AgeResult<Value>>,
+		max_rows: usize,
+		timeout: Duration,
+	) -> Vec<Value> {
+		let chunks: Vec<_> = ndjson_rows(rows, max_rows, timeout).collect().await;
+		chunks
+			.into_iter()
+			.map(|c| {
+				let c = c.unwrap();
+				assert!(c.ends_with(b"\n"), "one line per chunk");
+				serde_json::from_slice(&c).unwrap()
+			})
+			.collect()
+	}
+
+	#[tokio::test]
+	async fn rows_are_streamed_up_to_the_cap() {
+		let rows = stream::iter((0..10).map(|i| Ok(serde_json::json!({ "n": i })))).boxed();
+		let lines = collect(rows, 3, Duration::from_secs(5)).await;
+		assert_eq!(
+			lines,
+			vec![
+				serde_json::json!({"n": 0}),
+				serde_json::json!({"n": 1}),
+				serde_json::json!({"n": 2})
+			]
+		);
+	}
+
+	#[tokio::test]
+	async fn failures_and_timeouts_end_with_an_error_line() {
+		let rows = stream::iter(vec![
+			Ok(serde_json::json!(1)),
+			Err(AgeError::Query("boom".to_string())),
+			Ok(serde_json::json!(2)),
+		])
+		.boxed();
+		let lines = collect(rows, 10, Duration::from_secs(5)).await;
+		assert_eq!(lines.len(), 2);
+		assert_eq!(lines[1]["error"], "query failed: boom");
+
+		let stalled = stream::iter(vec![Ok(serde_json::json!(1))])
+			.chain(stream::pending())
+			.boxed();
+		let lines = collect(stalled, 10, Duration::from_millis(50)).await;
+		assert_eq!(
+			lines,
+			vec![
+				serde_json::json!(1),
+				serde_json::json!({"error": "query timed out"})
+			]
+		);
+	}
+
+	#[test]
+	fn write_clauses_are_detected() {
+		assert!(is_read_only("MATCH (n:FieldValue) RETURN n LIMIT 10"));
+		assert!(is_read_only(
+			"MATCH (n) WHERE n.note = 'set by DELETE job' RETURN n.offset"
+		));
+		assert!(!is_read_only("MATCH (n) DETACH DELETE n"));
+		assert!(!is_read_only("match (n) set n.x = 1 return n"));
+		assert!(!is_read_only("MERGE (n:FieldValue {canonical_key: 'x'})"));
+	}
+
+	#[tokio::test]
+	async fn query_stream_requires_authentication() {
+		let state = crate::ingest::test_utils::create_test_app_state();
+		let resp = query_stream(
+			State(state),
+			HeaderMap::new(),
+			Json(QueryRequest {
+				cypher: "MATCH (n) RETURN n".to_string(),
+				limit: None,
+			}),
+		)
+		.await;
+		assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+	}
+}
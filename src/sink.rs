@@ -0,0 +1,622 @@
+//! Destinations for normalized records.
+//!
+//! Ingest hands every accepted record to each configured [`RecordSink`].
+//! [`AgeSink`] persists to the graph through the batcher and is selected by
+//! default; [`NatsSink`] publishes to a NATS subject for downstream
+//! consumers. Records reach sinks after the PII policy has been applied.
+
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use async_trait::async_trait;
+use tokio::sync::mpsc::error::TrySendError;
+
+use crate::age_client::{AgeRepo, GraphNode};
+use crate::ingest::source::SOURCE_PROP;
+use crate::ingest::{KeyPrefixMap, NormalizedRecord};
+use crate::lib::normalizers::{NormalizedValue, NormalizerError, ip_hex, normalize, salted_key};
+use crate::observability::MetricsRegistry;
+use crate::persist::ack::WriteTracker;
+use crate::persist::collisions::{KEY_FINGERPRINT_PROP, KeyCollisionDetector, key_fingerprint};
+use crate::persist::labels::{LabelAllowlist, QUARANTINE_LABEL};
+use crate::persist::{PersistJob, PersistSender, submit_job, submit_job_waiting};
+
+/// Sink names accepted in `Settings.record_sinks`.
+pub const SINK_NAMES: &[&str] = &["age", "nats"];
+
+/// A destination for normalized records.
+#[async_trait]
+pub trait RecordSink: Send + Sync {
+	/// Deliver `record`. An error means the record did not reach this sink.
+	async fn send(&self, record: &NormalizedRecord) -> anyhow::Result<()>;
+
+	/// Finish delivering what `send` buffered. Sinks that deliver on `send`
+	/// have nothing to do.
+	async fn flush(&self) -> anyhow::Result<()> {
+		Ok(())
+	}
+
+	/// The records the last `flush` confirmed persisted, as `(index, key)`
+	/// where `index` counts the records sent before it. `None` for sinks
+	/// that do not confirm what they persist.
+	fn persisted(&self) -> Option<Vec<(usize, String)>> {
+		None
+	}
+}
+
+/// Deliver `record` to every sink in order, stopping at the first failure.
+///
+/// Its span is one of the high-frequency spans sampled by
+/// [`crate::observability::tracing_setup::SpanSampler`].
+#[tracing::instrument(name = "ingest_record", skip_all, fields(field_type = %record.field_type))]
+pub async fn deliver(
+	sinks: &[Arc<dyn RecordSink>],
+	record: &NormalizedRecord,
+) -> anyhow::Result<()> {
+	for sink in sinks {
+		sink.send(record).await?;
+	}
+	Ok(())
+}
+
+/// Flush every sink in order, stopping at the first failure. Once this
+/// returns `Ok`, every record delivered before it has reached every sink.
+pub async fn flush(sinks: &[Arc<dyn RecordSink>]) -> anyhow::Result<()> {
+	for sink in sinks {
+		sink.flush().await?;
+	}
+	Ok(())
+}
+
+/// Canonical value stored in place of an empty one under
+/// [`EmptyValues::Sentinel`].
+pub const EMPTY_VALUE_KEY: &str = "<empty>";
+
+/// What [`AgeSink`] does with records whose value is empty or only
+/// whitespace, e.g. a blank CSV or XLSX cell or an NDJSON `""`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EmptyValues {
+	/// Count them in `heimdall_ingest_empty_skipped_total` and persist
+	/// nothing.
+	#[default]
+	Skip,
+	/// Persist them under [`EMPTY_VALUE_KEY`], one node per field type.
+	Sentinel,
+}
+
+impl FromStr for EmptyValues {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.trim().to_ascii_lowercase().as_str() {
+			"skip" => Ok(Self::Skip),
+			"sentinel" => Ok(Self::Sentinel),
+			other => Err(format!("unknown empty value behavior '{}'", other)),
+		}
+	}
+}
+
+/// Persists records as `FieldValue` nodes through the persistence batcher.
+///
+/// When the batcher's channel is full or closed the record is written
+/// synchronously instead so it is not lost. With direct writes every job
+/// still goes through the batcher, waiting for room in its channel, and
+/// [`RecordSink::flush`] waits until the batcher has resolved each one;
+/// [`RecordSink::persisted`] then lists the records written under their
+/// own label.
+///
+/// With provenance enabled, nodes whose value a typed normalizer accepts
+/// carry `normalizer` and `normalizer_version` so values written by an
+/// outdated algorithm can be found and migrated later.
+///
+/// With an ingest source set, nodes carry it in
+/// [`SOURCE_PROP`](crate::ingest::source::SOURCE_PROP).
+///
+/// Records with an empty value are skipped or stored under a sentinel key
+/// according to [`EmptyValues`].
+///
+/// With key fingerprints enabled, nodes carry
+/// [`KEY_FINGERPRINT_PROP`] so the batcher can tell when two values share a
+/// canonical key (see [`crate::persist::collisions`]). Records written
+/// synchronously are checked here against the batcher's detector, set with
+/// [`AgeSink::with_collision_detector`].
+///
+/// Single addresses of kind `ip` also carry `ip_version` and `ip_hex` (see
+/// [`ip_hex`]) so [`crate::age_client::AgeClient::ips_in_cidr`] can find
+/// them by range.
+pub struct AgeSink {
+	repo: Arc<dyn AgeRepo>,
+	sender: PersistSender,
+	metrics: Arc<MetricsRegistry>,
+	salt: Arc<str>,
+	key_prefixes: Arc<KeyPrefixMap>,
+	label_allowlist: Arc<LabelAllowlist>,
+	arrived_at: Instant,
+	provenance: bool,
+	key_fingerprints: bool,
+	collisions: Option<Arc<KeyCollisionDetector>>,
+	source: Option<String>,
+	empty_values: EmptyValues,
+	direct: Option<DirectWrites>,
+}
+
+/// What an [`AgeSink`] writing directly tracks between flushes.
+#[derive(Default)]
+struct DirectWrites {
+	tracker: WriteTracker,
+	/// Per record sent, the ack id and key of its job, or `None` when the
+	/// record was skipped.
+	sent: Mutex<Vec<Option<(usize, String)>>>,
+	/// What the last flush confirmed; see [`RecordSink::persisted`].
+	persisted: Mutex<Vec<(usize, String)>>,
+}
+
+impl AgeSink {
+	pub fn new(
+		repo: Arc<dyn AgeRepo>,
+		sender: PersistSender,
+		metrics: Arc<MetricsRegistry>,
+	) -> Self {
+		Self {
+			repo,
+			sender,
+			metrics,
+			salt: Arc::from(""),
+			key_prefixes: Arc::new(KeyPrefixMap::default()),
+			label_allowlist: Arc::new(LabelAllowlist::default()),
+			arrived_at: Instant::now(),
+			provenance: false,
+			key_fingerprints: false,
+			collisions: None,
+			source: None,
+			empty_values: EmptyValues::Skip,
+			direct: None,
+		}
+	}
+
+	/// Salt canonical keys with `salt`.
+	pub fn with_canonical_salt(mut self, salt: Arc<str>) -> Self {
+		self.salt = salt;
+		self
+	}
+
+	/// Prefix keys by field kind with `key_prefixes` instead of the default
+	/// `kind:` convention.
+	pub fn with_key_prefixes(mut self, key_prefixes: Arc<KeyPrefixMap>) -> Self {
+		self.key_prefixes = key_prefixes;
+		self
+	}
+
+	/// Quarantine node labels outside `allowlist`.
+	pub fn with_label_allowlist(mut self, allowlist: Arc<LabelAllowlist>) -> Self {
+		self.label_allowlist = allowlist;
+		self
+	}
+
+	/// Stamp jobs as arriving at `arrived_at`, e.g. the start of the request.
+	pub fn with_arrival(mut self, arrived_at: Instant) -> Self {
+		self.arrived_at = arrived_at;
+		self
+	}
+
+	/// Record which normalizer, and which version of it, produced each
+	/// node's canonical value.
+	pub fn with_provenance(mut self, enabled: bool) -> Self {
+		self.provenance = enabled;
+		self
+	}
+
+	/// Store a fingerprint of each node's unsalted key for key collision
+	/// detection.
+	pub fn with_key_fingerprints(mut self, enabled: bool) -> Self {
+		self.key_fingerprints = enabled;
+		self
+	}
+
+	/// Check records written synchronously for key collisions with
+	/// `detector`, which should be the one the batcher checks with.
+	pub fn with_collision_detector(mut self, detector: Arc<KeyCollisionDetector>) -> Self {
+		self.collisions = Some(detector);
+		self
+	}
+
+	/// Stamp nodes with the feed or tenant `source` that ingested them.
+	pub fn with_ingest_source(mut self, source: Option<String>) -> Self {
+		self.source = source;
+		self
+	}
+
+	/// Skip or sentinel-key records with empty values per `empty_values`.
+	pub fn with_empty_values(mut self, empty_values: EmptyValues) -> Self {
+		self.empty_values = empty_values;
+		self
+	}
+
+	/// Track every job through the batcher so `flush` returns once they
+	/// are resolved, letting a caller know what was persisted.
+	pub fn with_direct_writes(mut self) -> Self {
+		self.direct = Some(DirectWrites::default());
+		self
+	}
+
+	/// The typed normalization of `record`, through the key prefixes'
+	/// cache when they have one.
+	fn normalized(&self, record: &NormalizedRecord) -> Result<NormalizedValue, NormalizerError> {
+		match self.key_prefixes.cache() {
+			Some(cache) => cache.normalize(&record.field_type, &record.canonical),
+			None => normalize(&record.field_type, &record.canonical),
+		}
+	}
+}
+
+#[async_trait]
+impl RecordSink for AgeSink {
+	async fn send(&self, record: &NormalizedRecord) -> anyhow::Result<()> {
+		let sentinel;
+		let record = if record.canonical.trim().is_empty() {
+			match self.empty_values {
+				EmptyValues::Skip => {
+					self.metrics.ingest_empty_skipped_total.inc();
+					if let Some(direct) = &self.direct {
+						direct.sent.lock().unwrap().push(None);
+					}
+					return Ok(());
+				}
+				EmptyValues::Sentinel => {
+					sentinel = NormalizedRecord {
+						canonical: EMPTY_VALUE_KEY.to_string(),
+						..record.clone()
+					};
+					&sentinel
+				}
+			}
+		} else {
+			record
+		};
+
+		// Store the (salted) kind-prefixed canonical key as the merge key;
+		// records without a raw value only carry their field type.
+		let mut props = serde_json::json!({
+			"field_type": record.field_type,
+			"confidence": record.confidence,
+		});
+		if !record.raw.is_empty() {
+			props["raw"] = serde_json::Value::String(record.raw.clone());
+		}
+		if let Some(field) = &record.source_field {
+			props["source_field"] = serde_json::Value::String(field.clone());
+		}
+		if self.provenance || record.field_type == "ip" {
+			let normalized = self.normalized(record);
+			if self.provenance {
+				if let Ok(value) = &normalized {
+					props["normalizer"] = value.kind().into();
+					props["normalizer_version"] = value.version().into();
+				}
+			}
+			if let Ok(NormalizedValue::Ip(ip)) = &normalized {
+				if let Ok(addr) = ip.canonical.parse::<std::net::IpAddr>() {
+					props["ip_version"] = if addr.is_ipv4() { 4 } else { 6 }.into();
+					props["ip_hex"] = ip_hex(addr).into();
+				}
+			}
+		}
+		if let Some(source) = &self.source {
+			props[SOURCE_PROP] = source.as_str().into();
+		}
+		let key = self.key_prefixes.key_for(record);
+		if self.key_fingerprints {
+			props[KEY_FINGERPRINT_PROP] = key_fingerprint(&record.field_type, &key).into();
+		}
+		let label = self.label_allowlist.node_label("FieldValue", &self.metrics);
+		let job = PersistJob::new(label, salted_key(&key, &self.salt), props)
+			.with_arrival(self.arrived_at);
+
+		if let Some(direct) = &self.direct {
+			let ack = direct.tracker.ack();
+			direct
+				.sent
+				.lock()
+				.unwrap()
+				.push(Some((ack.id(), job.key.clone())));
+			submit_job_waiting(&self.sender, job.with_ack(ack), &self.metrics)
+				.await
+				.map_err(|_| anyhow::anyhow!("persistence batcher is not running"))?;
+			return Ok(());
+		}
+		match submit_job(&self.sender, job, &self.metrics) {
+			Ok(()) => Ok(()),
+			Err(TrySendError::Full(mut returned)) | Err(TrySendError::Closed(mut returned)) => {
+				if let Some(collisions) = &self.collisions {
+					if returned.label != QUARANTINE_LABEL
+						&& collisions.collides(self.repo.as_ref(), &returned).await
+					{
+						self.metrics.key_collision_total.inc();
+						tracing::warn!(label = %returned.label, key = %returned.key, "canonical key collision; quarantining node");
+						returned.label = QUARANTINE_LABEL.to_string();
+					}
+				}
+				// Persist synchronously; a record's node and edges commit together.
+				let node = GraphNode::new(returned.label, returned.key, returned.props);
+				self.repo
+					.merge_record(&node, &returned.edges, &chrono::Utc::now().to_rfc3339())
+					.await?;
+				Ok(())
+			}
+		}
+	}
+
+	async fn flush(&self) -> anyhow::Result<()> {
+		let Some(direct) = &self.direct else {
+			return Ok(());
+		};
+		let written = direct.tracker.wait().await;
+		let sent = std::mem::take(&mut *direct.sent.lock().unwrap());
+		*direct.persisted.lock().unwrap() = sent
+			.into_iter()
+			.enumerate()
+			.filter_map(|(index, job)| {
+				job.filter(|(id, _)| written.contains(id))
+					.map(|(_, key)| (index, key))
+			})
+			.collect();
+		Ok(())
+	}
+
+	fn persisted(&self) -> Option<Vec<(usize, String)>> {
+		self.direct
+			.as_ref()
+			.map(|direct| direct.persisted.lock().unwrap().clone())
+	}
+}
+
+/// Publishes each record as JSON to a NATS subject.
+pub struct NatsSink {
+	client: async_nats::Client,
+	subject: String,
+}
+
+impl NatsSink {
+	/// Connect to the NATS server at `url`, publishing to `subject`.
+	pub async fn connect(url: &str, subject: impl Into<String>) -> anyhow::Result<Self> {
+		let client = async_nats::connect(url).await?;
+		Ok(Self {
+			client,
+			subject: subject.into(),
+		})
+	}
+}
+
+#[async_trait]
+impl RecordSink for NatsSink {
+	async fn send(&self, record: &NormalizedRecord) -> anyhow::Result<()> {
+		let payload = serde_json::to_vec(record)?;
+		self.client
+			.publish(self.subject.clone(), payload.into())
+			.await?;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+pub(crate) mod test_utils {
+	use super::*;
+	use std::sync::Mutex;
+
+	/// Sink that keeps every record it is sent.
+	#[derive(Default)]
+	pub struct MemorySink {
+		records: Mutex<Vec<NormalizedRecord>>,
+	}
+
+	impl MemorySink {
+		pub fn records(&self) -> Vec<NormalizedRecord> {
+			self.records.lock().unwrap().clone()
+		}
+	}
+
+	#[async_trait]
+	impl RecordSink for MemorySink {
+		async fn send(&self, record: &NormalizedRecord) -> anyhow::Result<()> {
+			self.records.lock().unwrap().push(record.clone());
+			Ok(())
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::test_utils::MemorySink;
+	use super::*;
+	use crate::ingest::test_utils::RecordingRepo;
+
+	#[tokio::test]
+	async fn age_sink_enqueues_salted_jobs() {
+		let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+		let sink = AgeSink::new(
+			Arc::new(crate::ingest::test_utils::DummyRepo),
+			tx,
+			Arc::new(MetricsRegistry::new()),
+		)
+		.with_canonical_salt(Arc::from("salt"));
+
+		let rec = NormalizedRecord::new("domain", "Example.COM", "example.com");
+		sink.send(&rec).await.unwrap();
+
+		let job = rx.try_recv().unwrap();
+		assert_eq!(job.key, salted_key("domain:example.com", "salt"));
+		assert_eq!(job.props["field_type"], "domain");
+		assert_eq!(job.props["raw"], "Example.COM");
+		assert_eq!(job.props["confidence"], 1.0);
+		// Provenance is opt-in.
+		assert!(job.props.get("normalizer").is_none());
+	}
+
+	#[tokio::test]
+	async fn age_sink_quarantines_labels_outside_the_allowlist() {
+		use crate::persist::labels::QUARANTINE_LABEL;
+
+		let metrics = Arc::new(MetricsRegistry::new());
+		let rec = NormalizedRecord::new("domain", "example.com", "example.com");
+		for (allowed, expected) in [("FieldValue", "FieldValue"), ("Dump", QUARANTINE_LABEL)] {
+			let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+			let allowlist = LabelAllowlist::new([allowed], Vec::<String>::new());
+			let sink = AgeSink::new(
+				Arc::new(crate::ingest::test_utils::DummyRepo),
+				tx,
+				metrics.clone(),
+			)
+			.with_label_allowlist(Arc::new(allowlist));
+
+			sink.send(&rec).await.unwrap();
+			assert_eq!(rx.try_recv().unwrap().label, expected);
+		}
+		assert_eq!(metrics.ingest_label_quarantined_total.get(), 1);
+	}
+
+	#[tokio::test]
+	async fn age_sink_stamps_the_ingest_source() {
+		let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+		let sink = AgeSink::new(
+			Arc::new(crate::ingest::test_utils::DummyRepo),
+			tx,
+			Arc::new(MetricsRegistry::new()),
+		)
+		.with_ingest_source(Some("feedA".to_string()));
+
+		let rec = NormalizedRecord::new("domain", "example.com", "example.com");
+		sink.send(&rec).await.unwrap();
+		assert_eq!(rx.try_recv().unwrap().props["source"], "feedA");
+	}
+
+	#[tokio::test]
+	async fn synchronous_writes_quarantine_key_collisions() {
+		// Every key already carries another value's fingerprint.
+		let repo = Arc::new(RecordingRepo {
+			fingerprint: Some(key_fingerprint("domain", "domain:other.example")),
+			..RecordingRepo::default()
+		});
+		let metrics = Arc::new(MetricsRegistry::new());
+		let (tx, rx) = tokio::sync::mpsc::channel(1);
+		drop(rx);
+		let sink = AgeSink::new(repo.clone(), tx, metrics.clone())
+			.with_key_fingerprints(true)
+			.with_collision_detector(Arc::new(KeyCollisionDetector::new()));
+
+		let rec = NormalizedRecord::new("domain", "example.com", "example.com");
+		sink.send(&rec).await.unwrap();
+		assert_eq!(repo.labels(), [QUARANTINE_LABEL]);
+		assert_eq!(metrics.key_collision_total.get(), 1);
+	}
+
+	#[tokio::test]
+	async fn age_sink_skips_or_sentinel_keys_empty_values() {
+		let metrics = Arc::new(MetricsRegistry::new());
+		let blank = NormalizedRecord::new("domain", " ", " ");
+
+		let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+		let sink = AgeSink::new(
+			Arc::new(crate::ingest::test_utils::DummyRepo),
+			tx,
+			metrics.clone(),
+		);
+		let empty = NormalizedRecord::new("domain", "", "");
+		sink.send(&blank).await.unwrap();
+		sink.send(&empty).await.unwrap();
+		assert!(rx.try_recv().is_err());
+		assert_eq!(metrics.ingest_empty_skipped_total.get(), 2);
+
+		let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+		let sink = AgeSink::new(
+			Arc::new(crate::ingest::test_utils::DummyRepo),
+			tx,
+			metrics.clone(),
+		)
+		.with_empty_values(EmptyValues::Sentinel);
+		sink.send(&blank).await.unwrap();
+		assert_eq!(rx.try_recv().unwrap().key, "domain:<empty>");
+		assert_eq!(metrics.ingest_empty_skipped_total.get(), 2);
+
+		assert_eq!("Sentinel".parse(), Ok(EmptyValues::Sentinel));
+		assert!("drop".parse::<EmptyValues>().is_err());
+	}
+
+	#[tokio::test]
+	async fn age_sink_records_normalizer_provenance() {
+		let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+		let cache = Arc::new(crate::lib::normalizers::cache::NormalizationCache::new(8));
+		let sink = AgeSink::new(
+			Arc::new(crate::ingest::test_utils::DummyRepo),
+			tx,
+			Arc::new(MetricsRegistry::new()),
+		)
+		.with_key_prefixes(Arc::new(KeyPrefixMap::default().with_cache(cache)))
+		.with_provenance(true);
+
+		sink.send(&NormalizedRecord::new("ip", "10.0.0.1", "10.0.0.1"))
+			.await
+			.unwrap();
+		let job = rx.try_recv().unwrap();
+		assert_eq!(job.props["normalizer"], "ip");
+		assert_eq!(
+			job.props["normalizer_version"],
+			crate::lib::normalizers::normalizer_version("ip").unwrap()
+		);
+
+		// Kinds without a typed normalizer, and values it rejects, carry none.
+		for rec in [
+			NormalizedRecord::new("url", "https://example.com/", "https://example.com/"),
+			NormalizedRecord::new("ip", "not-an-ip", "not-an-ip"),
+		] {
+			sink.send(&rec).await.unwrap();
+			let job = rx.try_recv().unwrap();
+			assert!(job.props.get("normalizer").is_none());
+			assert!(job.props.get("normalizer_version").is_none());
+		}
+	}
+
+	#[tokio::test]
+	async fn ip_nodes_carry_their_numeric_form() {
+		let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+		let sink = AgeSink::new(
+			Arc::new(crate::ingest::test_utils::DummyRepo),
+			tx,
+			Arc::new(MetricsRegistry::new()),
+		);
+		for (ip, version, hex) in [
+			("10.0.0.1", 4, "0a000001"),
+			("2001:db8::1", 6, "20010db8000000000000000000000001"),
+		] {
+			sink.send(&NormalizedRecord::new("ip", ip, ip))
+				.await
+				.unwrap();
+			let job = rx.try_recv().unwrap();
+			assert_eq!(job.props["ip_version"], version);
+			assert_eq!(job.props["ip_hex"], hex);
+		}
+
+		// Ranges and other kinds carry none.
+		for rec in [
+			NormalizedRecord::new("ip", "10.0.0.0/8", "10.0.0.0/8"),
+			NormalizedRecord::new("domain", "example.com", "example.com"),
+		] {
+			sink.send(&rec).await.unwrap();
+			let job = rx.try_recv().unwrap();
+			assert!(job.props.get("ip_hex").is_none());
+		}
+	}
+
+	#[tokio::test]
+	async fn deliver_fans_out_to_every_sink() {
+		let a = Arc::new(MemorySink::default());
+		let b = Arc::new(MemorySink::default());
+		let sinks: Vec<Arc<dyn RecordSink>> = vec![a.clone(), b.clone()];
+
+		let rec = NormalizedRecord::new("ip", "10.0.0.1", "10.0.0.1");
+		deliver(&sinks, &rec).await.unwrap();
+
+		assert_eq!(a.records(), vec![rec.clone()]);
+		assert_eq!(b.records(), vec![rec]);
+	}
+}
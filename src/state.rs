@@ -1,14 +1,312 @@
 use std::sync::Arc;
+use std::time::Instant;
+
+use arc_swap::{ArcSwap, ArcSwapOption};
 
 use crate::age_client::AgeRepo;
+use crate::audit::AuditLog;
+use crate::config::Settings;
+use crate::enrich::ProviderRegistry;
+use crate::health::DbHealth;
+use crate::ingest::admission::{AdmissionLimits, UploadGates};
+use crate::ingest::bulk_tasks::BulkTaskRegistry;
+use crate::ingest::classifier::FieldClassifiers;
+use crate::ingest::keys::KeyPrefixMap;
+use crate::ingest::parse_pool::ParsePool;
+use crate::ingest::quota::{QuotaLimits, QuotaTracker};
+use crate::lib::normalizers::salted_key;
+use crate::lineage::LineageEmitter;
 use crate::observability::MetricsRegistry;
+use crate::persist::collisions::KeyCollisionDetector;
+use crate::persist::labels::LabelAllowlist;
+use crate::persist::rekey::SaltRotation;
+use crate::pii::pii_policy::PiiPolicyEngine;
+use crate::pii::raw_store::RawPayloadStore;
+use crate::sink::{AgeSink, RecordSink};
+use crate::sync::auth::OidcProvider;
+use crate::sync::changelog::ChangeLog;
 
 /// Application state passed to handlers via Axum's `State` extractor.
 ///
 /// Holds a shared `AgeRepo` and a sender to the persistence batcher so
 /// handlers can enqueue records without blocking on DB round-trips.
+/// Optional components are attached with the `with_*` methods.
 #[derive(Clone)]
 pub struct AppState {
 	pub repo: Arc<dyn AgeRepo>,
 	pub persist_sender: tokio::sync::mpsc::Sender<crate::persist::PersistJob>,
+	pub metrics: Arc<MetricsRegistry>,
+	/// Runtime configuration consulted by handlers.
+	pub settings: Arc<Settings>,
+	/// PII policy engine applied to raw values before persistence; swapped
+	/// on config reload.
+	pub pii_engine: Arc<ArcSwapOption<PiiPolicyEngine>>,
+	/// Local change log used for replication and export.
+	pub changelog: Arc<ChangeLog>,
+	/// OIDC provider used to authenticate protected routes.
+	pub oidc: Option<Arc<OidcProvider>>,
+	/// Encrypted store for original payloads; `None` keeps no raw copies.
+	pub raw_store: Option<Arc<RawPayloadStore>>,
+	/// Background bulk processing tasks and their outcomes.
+	pub bulk_tasks: Arc<BulkTaskRegistry>,
+	/// Salt mixed into canonical keys; empty leaves keys unsalted. See
+	/// `Settings.canonical_salt`.
+	pub canonical_salt: Arc<str>,
+	/// Field kind → key prefix mapping applied before salting; swapped on
+	/// config reload.
+	pub key_prefixes: Arc<ArcSwap<KeyPrefixMap>>,
+	/// Database reachability; ingest is rejected with 503 while it is down.
+	pub db_health: Arc<DbHealth>,
+	/// Field-kind classifiers consulted before a record's declared type.
+	pub classifiers: Arc<FieldClassifiers>,
+	/// Deliver records to the graph through the persistence batcher.
+	pub age_sink: bool,
+	/// Further sinks records are delivered to, e.g. a NATS publisher.
+	pub sinks: Vec<Arc<dyn RecordSink>>,
+	/// Labels and edge types accepted from ingest and sync; swapped on
+	/// config reload.
+	pub label_allowlist: Arc<ArcSwap<LabelAllowlist>>,
+	/// Key collision detector shared with the batcher; `None` leaves
+	/// collisions unchecked.
+	pub key_collisions: Option<Arc<KeyCollisionDetector>>,
+	/// Per-subject ingest budgets; `None` leaves ingest unmetered.
+	pub ingest_quota: Option<Arc<QuotaTracker>>,
+	/// Concurrent uploads admitted per endpoint; `None` admits all.
+	pub upload_admission: Option<Arc<UploadGates>>,
+	/// Where audit events for mutating operations go.
+	pub audit: Arc<AuditLog>,
+	/// Where lineage events for dump ingests go.
+	pub lineage: Arc<LineageEmitter>,
+	/// Bounded workers parsing uploads off the async runtime.
+	pub parse_pool: Arc<ParsePool>,
+	/// Canonical-key salt rotation and the keys it has moved.
+	pub salt_rotation: Arc<SaltRotation>,
+	/// Rate-limit and circuit-breaker state of enrichment providers,
+	/// reported by `/enrich/status` and `/health/db`.
+	pub enrich_providers: ProviderRegistry,
+}
+
+impl AppState {
+	/// Create state with the required components, default settings and an
+	/// in-memory change log.
+	pub fn new(
+		repo: Arc<dyn AgeRepo>,
+		persist_sender: tokio::sync::mpsc::Sender<crate::persist::PersistJob>,
+		metrics: Arc<MetricsRegistry>,
+	) -> Self {
+		Self {
+			repo,
+			persist_sender,
+			metrics,
+			settings: Arc::new(Settings::default()),
+			pii_engine: Arc::new(ArcSwapOption::empty()),
+			changelog: Arc::new(ChangeLog::in_memory()),
+			oidc: None,
+			raw_store: None,
+			bulk_tasks: Arc::new(BulkTaskRegistry::new()),
+			canonical_salt: Arc::from(""),
+			key_prefixes: Arc::new(ArcSwap::from_pointee(KeyPrefixMap::default())),
+			db_health: Arc::new(DbHealth::default()),
+			classifiers: Arc::new(FieldClassifiers::default()),
+			age_sink: true,
+			sinks: Vec::new(),
+			label_allowlist: Arc::new(ArcSwap::from_pointee(LabelAllowlist::default())),
+			key_collisions: None,
+			ingest_quota: None,
+			upload_admission: None,
+			audit: Arc::new(AuditLog::log()),
+			lineage: Arc::new(LineageEmitter::disabled()),
+			parse_pool: Arc::new(ParsePool::default()),
+			salt_rotation: Arc::new(SaltRotation::new()),
+			enrich_providers: ProviderRegistry::new(),
+		}
+	}
+
+	/// Replace the default settings.
+	pub fn with_settings(mut self, settings: Arc<Settings>) -> Self {
+		self.settings = settings;
+		self
+	}
+
+	/// Attach a PII policy engine.
+	pub fn with_pii_engine(mut self, engine: Arc<PiiPolicyEngine>) -> Self {
+		self.pii_engine = Arc::new(ArcSwapOption::new(Some(engine)));
+		self
+	}
+
+	/// Replace the change log (e.g. with a file-backed one).
+	pub fn with_changelog(mut self, changelog: Arc<ChangeLog>) -> Self {
+		self.changelog = changelog;
+		self
+	}
+
+	/// Attach an OIDC provider for bearer-token authentication.
+	pub fn with_oidc(mut self, provider: Arc<OidcProvider>) -> Self {
+		self.oidc = Some(provider);
+		self
+	}
+
+	/// Attach an encrypted raw-payload store.
+	pub fn with_raw_store(mut self, store: Arc<RawPayloadStore>) -> Self {
+		self.raw_store = Some(store);
+		self
+	}
+
+	/// Salt canonical keys with `salt`.
+	pub fn with_canonical_salt(mut self, salt: &str) -> Self {
+		self.canonical_salt = Arc::from(salt);
+		self
+	}
+
+	/// Share `rotation`, e.g. the one whose key map wraps `repo`.
+	pub fn with_salt_rotation(mut self, rotation: Arc<SaltRotation>) -> Self {
+		self.salt_rotation = rotation;
+		self
+	}
+
+	/// Prefix keys by field kind with `key_prefixes`.
+	pub fn with_key_prefixes(mut self, key_prefixes: KeyPrefixMap) -> Self {
+		self.key_prefixes = Arc::new(ArcSwap::from_pointee(key_prefixes));
+		self
+	}
+
+	/// Check synchronous graph writes for key collisions with `detector`.
+	pub fn with_key_collisions(mut self, detector: Arc<KeyCollisionDetector>) -> Self {
+		self.key_collisions = Some(detector);
+		self
+	}
+
+	/// Meter ingest per authenticated subject against `limits`.
+	pub fn with_ingest_quota(mut self, limits: QuotaLimits) -> Self {
+		self.ingest_quota = Some(Arc::new(QuotaTracker::new(limits)));
+		self
+	}
+
+	/// Admit uploads to each ingest endpoint within `limits`.
+	pub fn with_upload_admission(mut self, limits: AdmissionLimits) -> Self {
+		self.upload_admission = Some(Arc::new(UploadGates::new(limits)));
+		self
+	}
+
+	/// Restrict the labels and edge types ingest and sync may write.
+	pub fn with_label_allowlist(mut self, allowlist: LabelAllowlist) -> Self {
+		self.label_allowlist = Arc::new(ArcSwap::from_pointee(allowlist));
+		self
+	}
+
+	/// Send audit events to `audit`.
+	pub fn with_audit_log(mut self, audit: Arc<AuditLog>) -> Self {
+		self.audit = audit;
+		self
+	}
+
+	/// Send lineage events to `lineage`.
+	pub fn with_lineage(mut self, lineage: Arc<LineageEmitter>) -> Self {
+		self.lineage = lineage;
+		self
+	}
+
+	/// Parse uploads on at most `workers` threads at once.
+	pub fn with_parse_workers(mut self, workers: usize) -> Self {
+		self.parse_pool = Arc::new(ParsePool::new(workers));
+		self
+	}
+
+	/// Share `health` with the monitor that keeps it current.
+	pub fn with_db_health(mut self, health: Arc<DbHealth>) -> Self {
+		self.db_health = health;
+		self
+	}
+
+	/// Report the providers registered in `registry`; share it with the
+	/// enrichment clients built for them.
+	pub fn with_enrich_providers(mut self, registry: ProviderRegistry) -> Self {
+		self.enrich_providers = registry;
+		self
+	}
+
+	/// Classify ingested values with `classifiers`.
+	pub fn with_classifiers(mut self, classifiers: FieldClassifiers) -> Self {
+		self.classifiers = Arc::new(classifiers);
+		self
+	}
+
+	/// Also deliver records to `sink`.
+	pub fn with_sink(mut self, sink: Arc<dyn RecordSink>) -> Self {
+		self.sinks.push(sink);
+		self
+	}
+
+	/// Stop delivering records to the graph; only the attached sinks
+	/// receive them.
+	pub fn without_age_sink(mut self) -> Self {
+		self.age_sink = false;
+		self
+	}
+
+	/// The sinks a request's records go to, the graph first. `arrived_at`
+	/// stamps graph jobs for the ingest-to-persist latency metric, and
+	/// `source` is stamped on graph nodes (see [`crate::ingest::source`]).
+	pub fn record_sinks(
+		&self,
+		arrived_at: Instant,
+		source: Option<String>,
+	) -> Vec<Arc<dyn RecordSink>> {
+		self.sinks_for(arrived_at, source, false)
+	}
+
+	/// Like [`Self::record_sinks`], but flushing the graph sink waits for
+	/// the batcher to resolve its writes; see [`AgeSink::with_direct_writes`].
+	pub fn direct_record_sinks(
+		&self,
+		arrived_at: Instant,
+		source: Option<String>,
+	) -> Vec<Arc<dyn RecordSink>> {
+		self.sinks_for(arrived_at, source, true)
+	}
+
+	fn sinks_for(
+		&self,
+		arrived_at: Instant,
+		source: Option<String>,
+		direct: bool,
+	) -> Vec<Arc<dyn RecordSink>> {
+		let mut sinks: Vec<Arc<dyn RecordSink>> = Vec::with_capacity(self.sinks.len() + 1);
+		if self.age_sink {
+			let mut age = AgeSink::new(
+				self.repo.clone(),
+				self.persist_sender.clone(),
+				self.metrics.clone(),
+			)
+			.with_canonical_salt(self.canonical_salt.clone())
+			.with_key_prefixes(self.key_prefixes.load_full())
+			.with_label_allowlist(self.label_allowlist.load_full())
+			.with_provenance(self.settings.normalizer_provenance)
+			.with_key_fingerprints(self.settings.key_collision_detection)
+			.with_ingest_source(source)
+			.with_empty_values(self.settings.empty_values.parse().unwrap_or_default())
+			.with_arrival(arrived_at);
+			if let Some(detector) = &self.key_collisions {
+				age = age.with_collision_detector(detector.clone());
+			}
+			if direct {
+				age = age.with_direct_writes();
+			}
+			sinks.push(Arc::new(age));
+		}
+		sinks.extend(self.sinks.iter().cloned());
+		sinks
+	}
+
+	/// Graph key for a normalized value under this state's salt.
+	pub fn canonical_key(&self, normalized_value: &str) -> String {
+		salted_key(normalized_value, &self.canonical_salt)
+	}
+
+	/// Graph key for a `field_type` value: prefixed by kind, then salted.
+	/// Keys passed to `increment_co_occurrence` and `persist_credential`
+	/// must be built this way to reach the nodes ingest created.
+	pub fn record_key(&self, field_type: &str, canonical: &str) -> String {
+		self.canonical_key(&self.key_prefixes.load().key(field_type, canonical))
+	}
 }
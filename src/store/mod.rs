@@ -0,0 +1,184 @@
+//! Backend-neutral graph storage.
+//!
+//! [`GraphStore`] is the handful of operations Heimdall's data model needs
+//! from a graph: merge a value, observe it, relate two values and read a
+//! node back with its edges. Every [`AgeRepo`] is a `GraphStore`, so the
+//! two backends selected by `Settings.storage_backend` are interchangeable
+//! behind it:
+//!
+//! - `age` (default): [`crate::age_client::AgeClient`], Cypher on Apache AGE.
+//! - `postgres`: [`postgres::PgGraphStore`], plain `nodes`/`edges` tables
+//!   for databases without the AGE extension.
+
+pub mod postgres;
+
+use async_trait::async_trait;
+use serde_json::{Map, Value};
+
+use crate::age_client::{AgeRepo, AgeResult, GraphFragment, MAX_NEIGHBORS};
+
+/// Backend names accepted in `Settings.storage_backend`.
+pub const BACKEND_NAMES: &[&str] = &["age", "postgres"];
+
+/// A node as read back from a store, without backend-specific ids.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredNode {
+	pub label: String,
+	pub key: String,
+	/// Properties other than the key property.
+	pub props: Map<String, Value>,
+}
+
+/// A directed edge between two keyed nodes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredEdge {
+	pub rel_type: String,
+	pub from_key: String,
+	pub to_key: String,
+	pub props: Map<String, Value>,
+}
+
+/// A node with the edges touching it, in either direction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeView {
+	pub node: StoredNode,
+	pub edges: Vec<StoredEdge>,
+}
+
+/// Storage operations shared by every backend.
+#[async_trait]
+pub trait GraphStore: Send + Sync {
+	/// Merge the `label` node keyed `key`, overwriting `props` on it.
+	async fn merge_value(&self, label: &str, key: &str, props: &Value) -> AgeResult<()>;
+	/// Observe the `label` node keyed `key`: keep `first_seen`, move
+	/// `last_seen` to `timestamp` and increment `seen_count`.
+	async fn observe(
+		&self,
+		label: &str,
+		key: &str,
+		props: &Value,
+		timestamp: &str,
+	) -> AgeResult<()>;
+	/// Relate the nodes keyed `from_key` and `to_key` with a directed
+	/// `rel_type` edge carrying `props`.
+	async fn link(
+		&self,
+		from_key: &str,
+		to_key: &str,
+		rel_type: &str,
+		props: &Value,
+	) -> AgeResult<()>;
+	/// The `label` node keyed `key` with up to [`MAX_NEIGHBORS`] of its
+	/// edges, or `None` when it does not exist.
+	async fn lookup(&self, label: &str, key: &str) -> AgeResult<Option<NodeView>>;
+}
+
+#[async_trait]
+impl<R: AgeRepo + ?Sized> GraphStore for R {
+	async fn merge_value(&self, label: &str, key: &str, props: &Value) -> AgeResult<()> {
+		self.merge_entity(label, key, props).await
+	}
+
+	async fn observe(
+		&self,
+		label: &str,
+		key: &str,
+		props: &Value,
+		timestamp: &str,
+	) -> AgeResult<()> {
+		self.observe_value(label, key, props, timestamp).await
+	}
+
+	async fn link(
+		&self,
+		from_key: &str,
+		to_key: &str,
+		rel_type: &str,
+		props: &Value,
+	) -> AgeResult<()> {
+		self.relate(from_key, to_key, rel_type, props).await
+	}
+
+	async fn lookup(&self, label: &str, key: &str) -> AgeResult<Option<NodeView>> {
+		let fragment = self.neighbors(label, key, &[], MAX_NEIGHBORS).await?;
+		Ok(fragment.and_then(|f| node_view(&f, self.key_property())))
+	}
+}
+
+/// Read a [`GraphFragment`] of AGE-shaped vertices (`id`, `label`,
+/// `properties`) and edges (`label`, `start_id`, `end_id`, `properties`),
+/// whose first node is the one looked up, as a [`NodeView`].
+fn node_view(fragment: &GraphFragment, key_prop: &str) -> Option<NodeView> {
+	let stored = |v: &Value| {
+		let mut props = v["properties"].as_object().cloned().unwrap_or_default();
+		let key = match props.remove(key_prop) {
+			Some(Value::String(k)) => k,
+			Some(other) => other.to_string(),
+			None => String::new(),
+		};
+		StoredNode {
+			label: v["label"].as_str().unwrap_or_default().to_string(),
+			key,
+			props,
+		}
+	};
+	let node = stored(fragment.nodes.first()?);
+	let key_of = |id: &Value| {
+		fragment
+			.nodes
+			.iter()
+			.find(|n| n["id"] == *id)
+			.map(|n| stored(n).key)
+			.unwrap_or_default()
+	};
+	let edges = fragment
+		.edges
+		.iter()
+		.map(|e| StoredEdge {
+			rel_type: e["label"].as_str().unwrap_or_default().to_string(),
+			from_key: key_of(&e["start_id"]),
+			to_key: key_of(&e["end_id"]),
+			props: e["properties"].as_object().cloned().unwrap_or_default(),
+		})
+		.collect();
+	Some(NodeView { node, edges })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+
+	#[test]
+	fn fragments_read_as_keyed_nodes_and_edges() {
+		let fragment = GraphFragment {
+			nodes: vec![
+				json!({"id": 1, "label": "IPAddress", "properties": {"canonical_key": "ip:192.0.2.1", "asn": 64500}}),
+				json!({"id": 2, "label": "Domain", "properties": {"canonical_key": "domain:example.com"}}),
+			],
+			edges: vec![json!({
+				"id": 3,
+				"label": "RESOLVES_TO",
+				"start_id": 2,
+				"end_id": 1,
+				"properties": {"source": "dns"},
+			})],
+		};
+		let view = node_view(&fragment, "canonical_key").unwrap();
+		assert_eq!(view.node.label, "IPAddress");
+		assert_eq!(view.node.key, "ip:192.0.2.1");
+		assert_eq!(
+			view.node.props,
+			json!({"asn": 64500}).as_object().cloned().unwrap()
+		);
+		assert_eq!(
+			view.edges,
+			vec![StoredEdge {
+				rel_type: "RESOLVES_TO".to_string(),
+				from_key: "domain:example.com".to_string(),
+				to_key: "ip:192.0.2.1".to_string(),
+				props: json!({"source": "dns"}).as_object().cloned().unwrap(),
+			}]
+		);
+	}
+}
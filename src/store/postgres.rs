@@ -0,0 +1,667 @@
+//! Graph storage on plain Postgres tables.
+//!
+//! [`PgGraphStore`] keeps nodes in `heimdall_nodes` (unique per label and
+//! key, properties as `jsonb`) and edges in `heimdall_edges` (unique per
+//! type and endpoints), so Heimdall can be evaluated on a database without
+//! the AGE extension. It implements [`AgeRepo`] with the same semantics as
+//! [`crate::age_client::AgeClient`] for merges, observations, relations,
+//! rows, co-occurrences and credentials, and returns neighbors in the shape
+//! AGE does. Cypher queries, retention, rekeying and `raw_samples` are not
+//! supported.
+
+use async_trait::async_trait;
+use serde_json::{Map, Value, json};
+use sqlx::{Executor, PgConnection, PgPool};
+
+use crate::age_client::{
+	AgeError, AgeRepo, AgeResult, DEFAULT_KEY_PROPERTY, Edge, GraphFragment, GraphNode,
+	MAX_NEIGHBORS, OBSERVATION_PROPS, PropKeyPolicy, sanitize_label, sanitize_prop_key,
+};
+use crate::ingest::source::{SOURCE_PROP, SOURCES_PROP};
+use crate::persist::collisions::KEY_FINGERPRINT_PROP;
+use crate::persist::sightings::{Compaction, SightingCompactor};
+
+/// Tables created by [`PgGraphStore::ensure_schema`].
+const SCHEMA: &str = "\
+CREATE TABLE IF NOT EXISTS heimdall_nodes (
+	id BIGSERIAL PRIMARY KEY,
+	label TEXT NOT NULL,
+	key TEXT NOT NULL,
+	props JSONB NOT NULL DEFAULT '{}',
+	UNIQUE (label, key)
+);
+CREATE INDEX IF NOT EXISTS heimdall_nodes_key ON heimdall_nodes (key);
+CREATE TABLE IF NOT EXISTS heimdall_edges (
+	id BIGSERIAL PRIMARY KEY,
+	rel_type TEXT NOT NULL,
+	from_id BIGINT NOT NULL REFERENCES heimdall_nodes (id) ON DELETE CASCADE,
+	to_id BIGINT NOT NULL REFERENCES heimdall_nodes (id) ON DELETE CASCADE,
+	props JSONB NOT NULL DEFAULT '{}',
+	UNIQUE (rel_type, from_id, to_id)
+);
+CREATE INDEX IF NOT EXISTS heimdall_edges_to ON heimdall_edges (to_id);";
+
+/// Which properties win when a merged node already exists.
+#[derive(Clone, Copy)]
+enum OnExisting {
+	/// Incoming properties overwrite stored ones (`SET n += props`).
+	Overwrite,
+	/// Stored properties are kept (`ON CREATE SET`).
+	Keep,
+}
+
+/// Graph store on `heimdall_nodes` and `heimdall_edges`.
+pub struct PgGraphStore {
+	pool: PgPool,
+	key_property: String,
+	/// Coalesce identical consecutive sightings; `None` creates every one.
+	sightings: Option<SightingCompactor>,
+	/// What writes do with property keys that are not plain identifiers.
+	prop_keys: PropKeyPolicy,
+}
+
+impl PgGraphStore {
+	pub fn new(pool: PgPool) -> Self {
+		Self {
+			pool,
+			key_property: DEFAULT_KEY_PROPERTY.to_string(),
+			sightings: None,
+			prop_keys: PropKeyPolicy::default(),
+		}
+	}
+
+	/// Connect to `database_url`.
+	pub async fn connect(database_url: &str) -> AgeResult<Self> {
+		Ok(Self::new(PgPool::connect(database_url).await?))
+	}
+
+	/// Store keys in the `key_prop` property instead of `canonical_key`.
+	pub fn with_key_property(mut self, key_prop: &str) -> Self {
+		self.key_property = sanitize_prop_key(key_prop);
+		self
+	}
+
+	/// Coalesce sightings like [`AgeClient::with_sighting_compaction`].
+	///
+	/// [`AgeClient::with_sighting_compaction`]: crate::age_client::AgeClient::with_sighting_compaction
+	pub fn with_sighting_compaction(mut self, window_secs: u64) -> Self {
+		self.sightings = (window_secs > 0).then(|| SightingCompactor::new(window_secs));
+		self
+	}
+
+	/// Encode or reject property keys like
+	/// [`AgeClient::with_prop_key_policy`].
+	///
+	/// [`AgeClient::with_prop_key_policy`]: crate::age_client::AgeClient::with_prop_key_policy
+	pub fn with_prop_key_policy(mut self, policy: PropKeyPolicy) -> Self {
+		self.prop_keys = policy;
+		self
+	}
+
+	/// Create the tables if they do not exist yet.
+	pub async fn ensure_schema(&self) -> AgeResult<()> {
+		// Unprepared, so the statements can be sent as one batch.
+		self.pool.execute(SCHEMA).await?;
+		Ok(())
+	}
+
+	/// `props` with sanitized keys and the key property set to `key`.
+	fn node_props(&self, key: &str, props: &Value, skip: &[&str]) -> Map<String, Value> {
+		let mut out = Map::new();
+		if let Value::Object(map) = props {
+			for (k, v) in map {
+				let k = sanitize_prop_key(k);
+				if !skip.contains(&k.as_str()) {
+					out.insert(k, v.clone());
+				}
+			}
+		}
+		out.insert(self.key_property.clone(), Value::String(key.to_string()));
+		out
+	}
+}
+
+/// Merge the `(label, key)` node, returning its id.
+async fn upsert_node(
+	conn: &mut PgConnection,
+	label: &str,
+	key: &str,
+	props: &Map<String, Value>,
+	on_existing: OnExisting,
+) -> AgeResult<i64> {
+	let update = match on_existing {
+		OnExisting::Overwrite => "heimdall_nodes.props || EXCLUDED.props",
+		OnExisting::Keep => "heimdall_nodes.props",
+	};
+	let sql = format!(
+		"INSERT INTO heimdall_nodes (label, key, props) VALUES ($1, $2, $3::jsonb) \
+		 ON CONFLICT (label, key) DO UPDATE SET props = {} RETURNING id",
+		update
+	);
+	Ok(sqlx::query_scalar(&sql)
+		.bind(sanitize_label(label))
+		.bind(key)
+		.bind(serde_json::to_string(props)?)
+		.fetch_one(conn)
+		.await?)
+}
+
+/// Merge the `rel_type` edge between two node ids and apply `props`.
+async fn upsert_edge(
+	conn: &mut PgConnection,
+	rel_type: &str,
+	from_id: i64,
+	to_id: i64,
+	props: &Value,
+) -> AgeResult<()> {
+	sqlx::query(
+		"INSERT INTO heimdall_edges (rel_type, from_id, to_id, props) \
+		 VALUES ($1, $2, $3, $4::jsonb) \
+		 ON CONFLICT (rel_type, from_id, to_id) \
+		 DO UPDATE SET props = heimdall_edges.props || EXCLUDED.props",
+	)
+	.bind(rel_type)
+	.bind(from_id)
+	.bind(to_id)
+	.bind(props.to_string())
+	.execute(conn)
+	.await?;
+	Ok(())
+}
+
+/// Merge the `rel_type` edge between two node ids, incrementing its
+/// `count` and moving `last_seen` to `timestamp`.
+async fn count_edge(
+	conn: &mut PgConnection,
+	rel_type: &str,
+	from_id: i64,
+	to_id: i64,
+	timestamp: &str,
+) -> AgeResult<()> {
+	sqlx::query(
+		"INSERT INTO heimdall_edges (rel_type, from_id, to_id, props) \
+		 VALUES ($1, $2, $3, jsonb_build_object('count', 1, 'last_seen', $4::text)) \
+		 ON CONFLICT (rel_type, from_id, to_id) DO UPDATE SET props = heimdall_edges.props \
+		 || jsonb_build_object('count', COALESCE((heimdall_edges.props->>'count')::bigint, 0) + 1, \
+		 'last_seen', $4::text)",
+	)
+	.bind(rel_type)
+	.bind(from_id)
+	.bind(to_id)
+	.bind(timestamp)
+	.execute(conn)
+	.await?;
+	Ok(())
+}
+
+/// `rel_type` sanitized like a label; one with no usable characters is
+/// rejected, as `AgeClient::relate` does.
+fn edge_type(rel_type: &str) -> AgeResult<String> {
+	if !rel_type
+		.chars()
+		.any(|c| c.is_ascii_alphanumeric() || c == '_')
+	{
+		return Err(AgeError::Query(format!(
+			"invalid relationship type: {:?}",
+			rel_type
+		)));
+	}
+	Ok(sanitize_label(rel_type))
+}
+
+/// A stored node as an AGE vertex.
+fn vertex(id: i64, label: &str, props: &str) -> AgeResult<Value> {
+	let props: Value = serde_json::from_str(props)?;
+	Ok(json!({"id": id, "label": label, "properties": props}))
+}
+
+#[async_trait]
+impl AgeRepo for PgGraphStore {
+	async fn merge_entity(&self, label: &str, key: &str, props: &Value) -> AgeResult<()> {
+		self.prop_keys.check(props)?;
+		let mut conn = self.pool.acquire().await?;
+		let props = self.node_props(key, props, &[]);
+		upsert_node(&mut conn, label, key, &props, OnExisting::Overwrite).await?;
+		Ok(())
+	}
+
+	async fn ping(&self) -> AgeResult<()> {
+		sqlx::query("SELECT 1").execute(&self.pool).await?;
+		Ok(())
+	}
+
+	async fn merge_batch(&self, items: &[(String, String, Value)]) -> AgeResult<()> {
+		for (_, _, props) in items {
+			self.prop_keys.check(props)?;
+		}
+		let mut tx = self.pool.begin().await?;
+		for (label, key, props) in items {
+			let props = self.node_props(key, props, &[]);
+			upsert_node(&mut tx, label, key, &props, OnExisting::Overwrite).await?;
+		}
+		tx.commit().await?;
+		Ok(())
+	}
+
+	async fn observe_value(
+		&self,
+		label: &str,
+		key: &str,
+		props: &Value,
+		timestamp: &str,
+	) -> AgeResult<()> {
+		self.prop_keys.check(props)?;
+		let source = props.get(SOURCE_PROP).and_then(Value::as_str);
+		let mut skip = OBSERVATION_PROPS.to_vec();
+		skip.push(SOURCE_PROP);
+		let props = self.node_props(key, props, &skip);
+		// The source joins the node's sources; $6 is null without one.
+		sqlx::query(
+			"INSERT INTO heimdall_nodes (label, key, props) \
+			 VALUES ($1, $2, $3::jsonb || jsonb_build_object('first_seen', $4::text, \
+			 'last_seen', $4::text, 'seen_count', 1) \
+			 || CASE WHEN $6::text IS NULL THEN '{}'::jsonb \
+			 ELSE jsonb_build_object($5::text, jsonb_build_array($6::text)) END) \
+			 ON CONFLICT (label, key) DO UPDATE SET props = heimdall_nodes.props || $3::jsonb \
+			 || jsonb_build_object('last_seen', $4::text, 'seen_count', \
+			 COALESCE((heimdall_nodes.props->>'seen_count')::bigint, 0) + 1) \
+			 || CASE WHEN $6::text IS NULL OR heimdall_nodes.props->$5::text ? $6::text \
+			 THEN '{}'::jsonb \
+			 ELSE jsonb_build_object($5::text, \
+			 COALESCE(heimdall_nodes.props->$5, '[]'::jsonb) || to_jsonb($6::text)) END",
+		)
+		.bind(sanitize_label(label))
+		.bind(key)
+		.bind(serde_json::to_string(&props)?)
+		.bind(timestamp)
+		.bind(SOURCES_PROP)
+		.bind(source)
+		.execute(&self.pool)
+		.await?;
+		Ok(())
+	}
+
+	async fn observe_batch(
+		&self,
+		items: &[(String, String, Value)],
+		timestamp: &str,
+	) -> AgeResult<()> {
+		for (label, key, props) in items {
+			self.observe_value(label, key, props, timestamp).await?;
+		}
+		Ok(())
+	}
+
+	async fn persist_row(
+		&self,
+		dump_id: &str,
+		row_index: i64,
+		row_hash: Option<&str>,
+		cells: &[(String, String, String, String)],
+		timestamp: &str,
+	) -> AgeResult<()> {
+		let mut tx = self.pool.begin().await?;
+		let dump = upsert_node(
+			&mut tx,
+			"Dump",
+			dump_id,
+			json!({"id": dump_id, "received_at": timestamp})
+				.as_object()
+				.unwrap(),
+			OnExisting::Keep,
+		)
+		.await?;
+
+		// Hashed rows are claimed once per dump; repeats only count.
+		let (row_key, row_props) = match row_hash {
+			Some(hash) => (
+				format!("{}/{}", dump_id, hash),
+				json!({"dump_id": dump_id, "row_hash": hash, "index": row_index, "seen_count": 1}),
+			),
+			None => (
+				format!("{}/#{}", dump_id, row_index),
+				json!({"dump_id": dump_id, "index": row_index}),
+			),
+		};
+		let (row, seen): (i64, i64) = sqlx::query_as(
+			"INSERT INTO heimdall_nodes (label, key, props) VALUES ('Row', $1, $2::jsonb) \
+			 ON CONFLICT (label, key) DO UPDATE SET props = heimdall_nodes.props \
+			 || jsonb_build_object('seen_count', \
+			 COALESCE((heimdall_nodes.props->>'seen_count')::bigint, 1) + 1) \
+			 RETURNING id, COALESCE((props->>'seen_count')::bigint, 1)",
+		)
+		.bind(&row_key)
+		.bind(row_props.to_string())
+		.fetch_one(&mut *tx)
+		.await?;
+		upsert_edge(&mut tx, "HAS_ROW", dump, row, &json!({})).await?;
+		if row_hash.is_some() && seen > 1 {
+			tx.commit().await?;
+			return Ok(());
+		}
+
+		for (i, (column, raw, canonical_key, canonical_value)) in cells.iter().enumerate() {
+			let mut value_props = Map::new();
+			value_props.insert("value".to_string(), json!(canonical_value));
+			value_props.insert("created_at".to_string(), json!(timestamp));
+			value_props.insert(self.key_property.clone(), json!(canonical_key));
+			let value = upsert_node(
+				&mut tx,
+				"FieldValue",
+				canonical_key,
+				&value_props,
+				OnExisting::Keep,
+			)
+			.await?;
+			let field = upsert_node(
+				&mut tx,
+				"Field",
+				column,
+				json!({"name": column}).as_object().unwrap(),
+				OnExisting::Keep,
+			)
+			.await?;
+			upsert_edge(&mut tx, "VALUE_OF", value, field, &json!({})).await?;
+			let mut props = json!({"column": column, "raw": raw, "timestamp": timestamp});
+			let sighting_key = match self
+				.sightings
+				.as_ref()
+				.map(|c| c.admit(canonical_key, column, timestamp))
+			{
+				None => format!("{}/{}", row_key, i),
+				Some(Compaction::New(id)) => {
+					props["sighting_id"] = json!(id);
+					props["count"] = json!(1);
+					props["first_seen"] = json!(timestamp);
+					props["last_seen"] = json!(timestamp);
+					id
+				}
+				Some(Compaction::Coalesced {
+					id,
+					count,
+					last_seen,
+				}) => {
+					props = json!({
+						"sighting_id": id,
+						"column": column,
+						"count": count,
+						"last_seen": last_seen,
+					});
+					id
+				}
+			};
+			let sighting = upsert_node(
+				&mut tx,
+				"Sighting",
+				&sighting_key,
+				props.as_object().unwrap(),
+				OnExisting::Overwrite,
+			)
+			.await?;
+			upsert_edge(&mut tx, "HAS_SIGHTING", row, sighting, &json!({})).await?;
+			upsert_edge(&mut tx, "OBSERVED_VALUE", sighting, value, &json!({})).await?;
+		}
+		tx.commit().await?;
+		Ok(())
+	}
+
+	async fn increment_co_occurrence(
+		&self,
+		a_key: &str,
+		b_key: &str,
+		timestamp: &str,
+	) -> AgeResult<()> {
+		// Deterministic ordering keeps one edge per pair.
+		let (first, second) = if a_key < b_key {
+			(a_key, b_key)
+		} else {
+			(b_key, a_key)
+		};
+		self.count_values("CO_OCCURS", first, second, timestamp)
+			.await
+	}
+
+	async fn persist_credential(
+		&self,
+		from_key: &str,
+		to_key: &str,
+		timestamp: &str,
+	) -> AgeResult<()> {
+		self.count_values("CREDENTIAL", from_key, to_key, timestamp)
+			.await
+	}
+
+	async fn relate(
+		&self,
+		from_key: &str,
+		to_key: &str,
+		rel_type: &str,
+		props: &Value,
+	) -> AgeResult<()> {
+		let rel_type = edge_type(rel_type)?;
+		self.prop_keys.check(props)?;
+		let mut sanitized = Map::new();
+		if let Value::Object(map) = props {
+			for (k, v) in map {
+				sanitized.insert(sanitize_prop_key(k), v.clone());
+			}
+		}
+		// Like AGE's MATCH, nodes of any label with the keys are related,
+		// and missing nodes relate nothing.
+		sqlx::query(
+			"INSERT INTO heimdall_edges (rel_type, from_id, to_id, props) \
+			 SELECT $1, a.id, b.id, $4::jsonb \
+			 FROM heimdall_nodes a, heimdall_nodes b WHERE a.key = $2 AND b.key = $3 \
+			 ON CONFLICT (rel_type, from_id, to_id) \
+			 DO UPDATE SET props = heimdall_edges.props || EXCLUDED.props",
+		)
+		.bind(rel_type)
+		.bind(from_key)
+		.bind(to_key)
+		.bind(serde_json::to_string(&sanitized)?)
+		.execute(&self.pool)
+		.await?;
+		Ok(())
+	}
+
+	async fn merge_edge(&self, node: &GraphNode, edge: &Edge, timestamp: &str) -> AgeResult<()> {
+		let rel_type = edge_type(&edge.rel_type)?;
+		self.prop_keys.check(&edge.props)?;
+		let mut props = Map::new();
+		if let Value::Object(map) = &edge.props {
+			for (k, v) in map {
+				let k = sanitize_prop_key(k);
+				if k != "count" && k != "last_seen" {
+					props.insert(k, v.clone());
+				}
+			}
+		}
+		let mut tx = self.pool.begin().await?;
+		let from = upsert_node(
+			&mut tx,
+			&node.label,
+			&node.key,
+			&self.node_props(&node.key, &Value::Null, &[]),
+			OnExisting::Keep,
+		)
+		.await?;
+		let to = upsert_node(
+			&mut tx,
+			&edge.to_label,
+			&edge.to_key,
+			&self.node_props(&edge.to_key, &Value::Null, &[]),
+			OnExisting::Keep,
+		)
+		.await?;
+		count_edge(&mut tx, &rel_type, from, to, timestamp).await?;
+		if !props.is_empty() {
+			upsert_edge(&mut tx, &rel_type, from, to, &Value::Object(props)).await?;
+		}
+		tx.commit().await?;
+		Ok(())
+	}
+
+	async fn delete_by_source(&self, source: &str) -> AgeResult<u64> {
+		let mut tx = self.pool.begin().await?;
+		// Edges go with their nodes through `ON DELETE CASCADE`.
+		let deleted = sqlx::query(
+			"DELETE FROM heimdall_nodes WHERE props -> $1 = jsonb_build_array($2::text)",
+		)
+		.bind(SOURCES_PROP)
+		.bind(source)
+		.execute(&mut *tx)
+		.await?;
+		sqlx::query(
+			"UPDATE heimdall_nodes SET props = jsonb_set(props, ARRAY[$1::text], \
+			 (props -> $1) - $2::text) WHERE props -> $1 ? $2",
+		)
+		.bind(SOURCES_PROP)
+		.bind(source)
+		.execute(&mut *tx)
+		.await?;
+		tx.commit().await?;
+		Ok(deleted.rows_affected())
+	}
+
+	async fn key_fingerprint(&self, label: &str, key: &str) -> AgeResult<Option<String>> {
+		let fingerprint: Option<Option<String>> = sqlx::query_scalar(
+			"SELECT props ->> $3 FROM heimdall_nodes WHERE label = $1 AND key = $2",
+		)
+		.bind(sanitize_label(label))
+		.bind(key)
+		.bind(KEY_FINGERPRINT_PROP)
+		.fetch_optional(&self.pool)
+		.await?;
+		Ok(fingerprint.flatten())
+	}
+
+	async fn neighbors(
+		&self,
+		label: &str,
+		key: &str,
+		edge_types: &[&str],
+		limit: usize,
+	) -> AgeResult<Option<GraphFragment>> {
+		let types = edge_types
+			.iter()
+			.map(|t| edge_type(t))
+			.collect::<AgeResult<Vec<String>>>()?;
+		let node: Option<(i64, String, String)> = sqlx::query_as(
+			"SELECT id, label, props::text FROM heimdall_nodes WHERE label = $1 AND key = $2",
+		)
+		.bind(sanitize_label(label))
+		.bind(key)
+		.fetch_optional(&self.pool)
+		.await?;
+		let Some((id, label, props)) = node else {
+			return Ok(None);
+		};
+
+		let rows: Vec<(i64, String, i64, i64, String, i64, String, String)> = sqlx::query_as(
+			"SELECT e.id, e.rel_type, e.from_id, e.to_id, e.props::text, m.id, m.label, m.props::text \
+			 FROM heimdall_edges e JOIN heimdall_nodes m \
+			 ON m.id = CASE WHEN e.from_id = $1 THEN e.to_id ELSE e.from_id END \
+			 WHERE (e.from_id = $1 OR e.to_id = $1) \
+			 AND (cardinality($2::text[]) = 0 OR e.rel_type = ANY($2)) \
+			 ORDER BY e.id LIMIT $3",
+		)
+		.bind(id)
+		.bind(types)
+		.bind(limit.min(MAX_NEIGHBORS) as i64)
+		.fetch_all(&self.pool)
+		.await?;
+
+		// A neighbor reached through several edges is listed once.
+		let mut seen = std::collections::HashSet::from([id]);
+		let mut fragment = GraphFragment {
+			nodes: vec![vertex(id, &label, &props)?],
+			edges: Vec::with_capacity(rows.len()),
+		};
+		for (edge_id, rel_type, from_id, to_id, edge_props, other_id, other_label, other_props) in
+			rows
+		{
+			let edge_props: Value = serde_json::from_str(&edge_props)?;
+			fragment.edges.push(json!({
+				"id": edge_id,
+				"label": rel_type,
+				"start_id": from_id,
+				"end_id": to_id,
+				"properties": edge_props,
+			}));
+			if seen.insert(other_id) {
+				fragment
+					.nodes
+					.push(vertex(other_id, &other_label, &other_props)?);
+			}
+		}
+		Ok(Some(fragment))
+	}
+
+	async fn apply_migration(&self, sql_content: &str) -> AgeResult<()> {
+		self.pool.execute(sql_content).await?;
+		Ok(())
+	}
+
+	fn key_property(&self) -> &str {
+		&self.key_property
+	}
+}
+
+impl PgGraphStore {
+	/// Merge `FieldValue` nodes for both keys and count a `rel_type` edge
+	/// between them.
+	async fn count_values(
+		&self,
+		rel_type: &str,
+		from_key: &str,
+		to_key: &str,
+		timestamp: &str,
+	) -> AgeResult<()> {
+		let mut tx = self.pool.begin().await?;
+		let from = upsert_node(
+			&mut tx,
+			"FieldValue",
+			from_key,
+			&self.node_props(from_key, &Value::Null, &[]),
+			OnExisting::Keep,
+		)
+		.await?;
+		let to = upsert_node(
+			&mut tx,
+			"FieldValue",
+			to_key,
+			&self.node_props(to_key, &Value::Null, &[]),
+			OnExisting::Keep,
+		)
+		.await?;
+		count_edge(&mut tx, rel_type, from, to, timestamp).await?;
+		tx.commit().await?;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[tokio::test]
+	async fn node_props_set_the_key_and_skip_observation_props() {
+		let store = PgGraphStore::new(PgPool::connect_lazy("postgres://localhost/none").unwrap())
+			.with_key_property("uid");
+		let props = store.node_props(
+			"example.com",
+			&json!({"field-type": "domain", "seen_count": 9}),
+			&OBSERVATION_PROPS,
+		);
+		assert_eq!(
+			Value::Object(props),
+			json!({"_enc_field_2dtype": "domain", "uid": "example.com"})
+		);
+	}
+
+	#[test]
+	fn edge_types_are_sanitized_or_rejected() {
+		assert_eq!(edge_type("ENRICHED-BY").unwrap(), "ENRICHEDBY");
+		assert!(edge_type("--").is_err());
+	}
+}
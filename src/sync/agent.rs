@@ -1,21 +1,37 @@
 use anyhow::{Context, Result};
 use log::{debug, error, info};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 use tokio::sync::RwLock;
-use tokio::time::{interval, sleep};
+use tokio::time::{interval, sleep, timeout};
 use tokio_rustls::rustls::{ClientConfig, RootCertStore, ServerName};
 use tokio_rustls::TlsConnector;
 
+use crate::observability::metrics::{MetricFamilySnapshot, MetricKind, MetricSample};
 use crate::sync::auth::OidcProvider;
+use crate::sync::changelog::ChangeLog;
 
 /// Maximum size for a single change log entry (10MB)
 const MAX_ENTRY_SIZE: usize = 10 * 1024 * 1024;
 
+/// Default limit on a single read from a peer
+pub const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default limit on a whole sync cycle with a peer
+pub const DEFAULT_CYCLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Default cap on change log entries held in memory awaiting push
+pub const DEFAULT_MAX_PENDING: usize = 100_000;
+
+/// Default number of change log entries asked for per pull
+pub const DEFAULT_PULL_PAGE_SIZE: usize = 1_000;
+
 /// Global sync metrics instance
 static GLOBAL_SYNC_METRICS: once_cell::sync::Lazy<SyncMetrics> =
 	once_cell::sync::Lazy::new(|| SyncMetrics::default());
@@ -60,10 +76,26 @@ pub enum SyncMessage {
 	Push { entries: Vec<ChangeLogEntry> },
 	/// Acknowledge receipt of push
 	PushAck { count: usize },
-	/// Pull change logs since a given timestamp
-	Pull { since_timestamp: u64 },
-	/// Response to pull with change log entries
-	PullResponse { entries: Vec<ChangeLogEntry> },
+	/// Pull change logs since a given timestamp, or after `cursor` (the
+	/// peer's change log sequence number from a previous response) when
+	/// set. The peer returns at most `limit` entries, and never more than
+	/// its own cap.
+	Pull {
+		since_timestamp: u64,
+		#[serde(default)]
+		cursor: Option<u64>,
+		#[serde(default)]
+		limit: Option<usize>,
+	},
+	/// Response to pull with change log entries. When `has_more` is set,
+	/// pull again from `next_cursor` for the rest.
+	PullResponse {
+		entries: Vec<ChangeLogEntry>,
+		#[serde(default)]
+		has_more: bool,
+		#[serde(default)]
+		next_cursor: Option<u64>,
+	},
 	/// Heartbeat to keep connection alive
 	Ping,
 	/// Heartbeat response
@@ -72,7 +104,22 @@ pub enum SyncMessage {
 	Error { message: String },
 }
 
+/// Counters tracked per peer, keyed by `host:port`.
+#[derive(Default)]
+pub struct PeerSyncMetrics {
+	pub push_attempts: AtomicU64,
+	pub push_failures: AtomicU64,
+	pub pull_attempts: AtomicU64,
+	pub pull_failures: AtomicU64,
+	pub reconnections: AtomicU64,
+	pub auth_failures: AtomicU64,
+}
+
 /// Metrics for sync operations
+///
+/// The plain counters are the aggregate across all peers. The `record_*`
+/// helpers update both the aggregate and the per-peer counters so the
+/// Prometheus output can carry a `peer` label.
 pub struct SyncMetrics {
 	pub push_attempts: AtomicU64,
 	pub push_successes: AtomicU64,
@@ -84,6 +131,9 @@ pub struct SyncMetrics {
 	pub entries_received: AtomicU64,
 	pub reconnections: AtomicU64,
 	pub auth_failures: AtomicU64,
+	/// Pending entries evicted from memory (spilled or dropped) over the cap
+	pub pending_overflow: AtomicU64,
+	peers: std::sync::RwLock<BTreeMap<String, Arc<PeerSyncMetrics>>>,
 }
 
 impl Default for SyncMetrics {
@@ -99,89 +149,253 @@ impl Default for SyncMetrics {
 			entries_received: AtomicU64::new(0),
 			reconnections: AtomicU64::new(0),
 			auth_failures: AtomicU64::new(0),
+			pending_overflow: AtomicU64::new(0),
+			peers: std::sync::RwLock::new(BTreeMap::new()),
 		}
 	}
 }
 
 impl SyncMetrics {
-	/// Generate Prometheus-compatible metrics text
-	pub fn to_prometheus_text(&self) -> String {
-		let mut out = String::new();
+	/// Get (or create) the counters for a peer identified by `host:port`.
+	pub fn peer(&self, peer: &str) -> Arc<PeerSyncMetrics> {
+		if let Some(m) = self.peers.read().unwrap_or_else(|e| e.into_inner()).get(peer) {
+			return Arc::clone(m);
+		}
+		let mut peers = self.peers.write().unwrap_or_else(|e| e.into_inner());
+		Arc::clone(peers.entry(peer.to_string()).or_default())
+	}
 
-		out.push_str("# HELP heimdall_sync_push_attempts_total Total sync push attempts\n");
-		out.push_str("# TYPE heimdall_sync_push_attempts_total counter\n");
-		out.push_str(&format!(
-			"heimdall_sync_push_attempts_total {}\n",
-			self.push_attempts.load(Ordering::Relaxed)
-		));
+	fn peer_snapshot(&self, counter: fn(&PeerSyncMetrics) -> &AtomicU64) -> Vec<(String, u64)> {
+		self.peers
+			.read()
+			.unwrap_or_else(|e| e.into_inner())
+			.iter()
+			.map(|(peer, m)| (peer.clone(), counter(m).load(Ordering::Relaxed)))
+			.collect()
+	}
 
-		out.push_str("# HELP heimdall_sync_push_successes_total Successful sync pushes\n");
-		out.push_str("# TYPE heimdall_sync_push_successes_total counter\n");
-		out.push_str(&format!(
-			"heimdall_sync_push_successes_total {}\n",
-			self.push_successes.load(Ordering::Relaxed)
-		));
+	/// Record a push attempt against a peer.
+	pub fn record_push_attempt(&self, peer: &str) {
+		self.push_attempts.fetch_add(1, Ordering::Relaxed);
+		self.peer(peer).push_attempts.fetch_add(1, Ordering::Relaxed);
+	}
 
-		out.push_str("# HELP heimdall_sync_push_failures_total Failed sync pushes\n");
-		out.push_str("# TYPE heimdall_sync_push_failures_total counter\n");
-		out.push_str(&format!(
-			"heimdall_sync_push_failures_total {}\n",
-			self.push_failures.load(Ordering::Relaxed)
-		));
+	/// Record a failed push to a peer.
+	pub fn record_push_failure(&self, peer: &str) {
+		self.push_failures.fetch_add(1, Ordering::Relaxed);
+		self.peer(peer).push_failures.fetch_add(1, Ordering::Relaxed);
+	}
 
-		out.push_str("# HELP heimdall_sync_pull_attempts_total Total sync pull attempts\n");
-		out.push_str("# TYPE heimdall_sync_pull_attempts_total counter\n");
-		out.push_str(&format!(
-			"heimdall_sync_pull_attempts_total {}\n",
-			self.pull_attempts.load(Ordering::Relaxed)
-		));
+	/// Record a pull attempt against a peer.
+	pub fn record_pull_attempt(&self, peer: &str) {
+		self.pull_attempts.fetch_add(1, Ordering::Relaxed);
+		self.peer(peer).pull_attempts.fetch_add(1, Ordering::Relaxed);
+	}
 
-		out.push_str("# HELP heimdall_sync_pull_successes_total Successful sync pulls\n");
-		out.push_str("# TYPE heimdall_sync_pull_successes_total counter\n");
-		out.push_str(&format!(
-			"heimdall_sync_pull_successes_total {}\n",
-			self.pull_successes.load(Ordering::Relaxed)
-		));
+	/// Record a failed pull from a peer.
+	pub fn record_pull_failure(&self, peer: &str) {
+		self.pull_failures.fetch_add(1, Ordering::Relaxed);
+		self.peer(peer).pull_failures.fetch_add(1, Ordering::Relaxed);
+	}
 
-		out.push_str("# HELP heimdall_sync_pull_failures_total Failed sync pulls\n");
-		out.push_str("# TYPE heimdall_sync_pull_failures_total counter\n");
-		out.push_str(&format!(
-			"heimdall_sync_pull_failures_total {}\n",
-			self.pull_failures.load(Ordering::Relaxed)
-		));
+	/// Record a reconnection to a peer after a failed sync cycle.
+	pub fn record_reconnection(&self, peer: &str) {
+		self.reconnections.fetch_add(1, Ordering::Relaxed);
+		self.peer(peer).reconnections.fetch_add(1, Ordering::Relaxed);
+	}
 
-		out.push_str("# HELP heimdall_sync_entries_sent_total Total change log entries sent\n");
-		out.push_str("# TYPE heimdall_sync_entries_sent_total counter\n");
-		out.push_str(&format!(
-			"heimdall_sync_entries_sent_total {}\n",
-			self.entries_sent.load(Ordering::Relaxed)
-		));
+	/// Record an authentication failure against a peer.
+	pub fn record_auth_failure(&self, peer: &str) {
+		self.auth_failures.fetch_add(1, Ordering::Relaxed);
+		self.peer(peer).auth_failures.fetch_add(1, Ordering::Relaxed);
+	}
 
-		out.push_str("# HELP heimdall_sync_entries_received_total Total change log entries received\n");
-		out.push_str("# TYPE heimdall_sync_entries_received_total counter\n");
-		out.push_str(&format!(
-			"heimdall_sync_entries_received_total {}\n",
-			self.entries_received.load(Ordering::Relaxed)
-		));
+	/// Every counter family: the aggregates, then the per-peer breakdowns.
+	fn counter_families(&self) -> Vec<CounterFamily> {
+		let mut families = vec![
+			CounterFamily::total(
+				"heimdall_sync_push_attempts_total",
+				"Total sync push attempts",
+				self.push_attempts.load(Ordering::Relaxed),
+			),
+			CounterFamily::total(
+				"heimdall_sync_push_successes_total",
+				"Successful sync pushes",
+				self.push_successes.load(Ordering::Relaxed),
+			),
+			CounterFamily::total(
+				"heimdall_sync_push_failures_total",
+				"Failed sync pushes",
+				self.push_failures.load(Ordering::Relaxed),
+			),
+			CounterFamily::total(
+				"heimdall_sync_pull_attempts_total",
+				"Total sync pull attempts",
+				self.pull_attempts.load(Ordering::Relaxed),
+			),
+			CounterFamily::total(
+				"heimdall_sync_pull_successes_total",
+				"Successful sync pulls",
+				self.pull_successes.load(Ordering::Relaxed),
+			),
+			CounterFamily::total(
+				"heimdall_sync_pull_failures_total",
+				"Failed sync pulls",
+				self.pull_failures.load(Ordering::Relaxed),
+			),
+			CounterFamily::total(
+				"heimdall_sync_entries_sent_total",
+				"Total change log entries sent",
+				self.entries_sent.load(Ordering::Relaxed),
+			),
+			CounterFamily::total(
+				"heimdall_sync_entries_received_total",
+				"Total change log entries received",
+				self.entries_received.load(Ordering::Relaxed),
+			),
+			CounterFamily::total(
+				"heimdall_sync_reconnections_total",
+				"Total reconnection attempts",
+				self.reconnections.load(Ordering::Relaxed),
+			),
+			CounterFamily::total(
+				"heimdall_sync_auth_failures_total",
+				"Total authentication failures",
+				self.auth_failures.load(Ordering::Relaxed),
+			),
+			CounterFamily::total(
+				"heimdall_sync_pending_overflow_total",
+				"Pending change log entries evicted from memory over the cap",
+				self.pending_overflow.load(Ordering::Relaxed),
+			),
+		];
+		families.extend([
+			CounterFamily::per_peer(
+				"heimdall_sync_peer_push_attempts_total",
+				"Sync push attempts per peer",
+				self.peer_snapshot(|m| &m.push_attempts),
+			),
+			CounterFamily::per_peer(
+				"heimdall_sync_peer_push_failures_total",
+				"Failed sync pushes per peer",
+				self.peer_snapshot(|m| &m.push_failures),
+			),
+			CounterFamily::per_peer(
+				"heimdall_sync_peer_pull_attempts_total",
+				"Sync pull attempts per peer",
+				self.peer_snapshot(|m| &m.pull_attempts),
+			),
+			CounterFamily::per_peer(
+				"heimdall_sync_peer_pull_failures_total",
+				"Failed sync pulls per peer",
+				self.peer_snapshot(|m| &m.pull_failures),
+			),
+			CounterFamily::per_peer(
+				"heimdall_sync_peer_reconnections_total",
+				"Reconnection attempts per peer",
+				self.peer_snapshot(|m| &m.reconnections),
+			),
+			CounterFamily::per_peer(
+				"heimdall_sync_peer_auth_failures_total",
+				"Authentication failures per peer",
+				self.peer_snapshot(|m| &m.auth_failures),
+			),
+		]);
+		families
+	}
 
-		out.push_str("# HELP heimdall_sync_reconnections_total Total reconnection attempts\n");
-		out.push_str("# TYPE heimdall_sync_reconnections_total counter\n");
-		out.push_str(&format!(
-			"heimdall_sync_reconnections_total {}\n",
-			self.reconnections.load(Ordering::Relaxed)
-		));
+	/// Generate Prometheus-compatible metrics text
+	///
+	/// Aggregates are unlabeled families of their own; the per-peer
+	/// breakdowns live in separate `heimdall_sync_peer_*` families with one
+	/// `peer="host:port"` sample per known peer, so no family mixes the two.
+	pub fn to_prometheus_text(&self) -> String {
+		let mut out = String::new();
+		for family in self.counter_families() {
+			write_counter(&mut out, &family);
+		}
+		out
+	}
 
-		out.push_str("# HELP heimdall_sync_auth_failures_total Total authentication failures\n");
-		out.push_str("# TYPE heimdall_sync_auth_failures_total counter\n");
-		out.push_str(&format!(
-			"heimdall_sync_auth_failures_total {}\n",
-			self.auth_failures.load(Ordering::Relaxed)
-		));
+	/// The same families as [`Self::to_prometheus_text`], typed for
+	/// `/metrics.json`.
+	pub fn snapshot(&self) -> Vec<MetricFamilySnapshot> {
+		self.counter_families()
+			.into_iter()
+			.map(|family| MetricFamilySnapshot {
+				name: family.name.to_string(),
+				help: family.help.to_string(),
+				kind: MetricKind::Counter,
+				samples: family
+					.samples
+					.into_iter()
+					.map(|(peer, value)| {
+						let labels = peer
+							.map(|peer| BTreeMap::from([("peer".to_string(), peer)]))
+							.unwrap_or_default();
+						MetricSample::value(labels, value as f64)
+					})
+					.collect(),
+			})
+			.collect()
+	}
+}
 
-		out
+/// One sync counter family and its current values: either a single
+/// unlabeled aggregate or one sample per peer.
+struct CounterFamily {
+	name: &'static str,
+	help: &'static str,
+	samples: Vec<(Option<String>, u64)>,
+}
+
+impl CounterFamily {
+	fn total(name: &'static str, help: &'static str, value: u64) -> Self {
+		Self {
+			name,
+			help,
+			samples: vec![(None, value)],
+		}
+	}
+
+	fn per_peer(name: &'static str, help: &'static str, peers: Vec<(String, u64)>) -> Self {
+		Self {
+			name,
+			help,
+			samples: peers
+				.into_iter()
+				.map(|(peer, value)| (Some(peer), value))
+				.collect(),
+		}
 	}
 }
 
+/// Append one counter family (HELP, TYPE and its samples).
+fn write_counter(out: &mut String, family: &CounterFamily) {
+	let name = family.name;
+	out.push_str(&format!("# HELP {} {}\n", name, family.help));
+	out.push_str(&format!("# TYPE {} counter\n", name));
+	for (peer, value) in &family.samples {
+		match peer {
+			None => out.push_str(&format!("{} {}\n", name, value)),
+			Some(peer) => out.push_str(&format!(
+				"{}{{peer=\"{}\"}} {}\n",
+				name,
+				escape_label_value(peer),
+				value
+			)),
+		}
+	}
+}
+
+/// Escape a Prometheus label value (backslash, double quote and newline).
+fn escape_label_value(value: &str) -> String {
+	value
+		.replace('\\', "\\\\")
+		.replace('"', "\\\"")
+		.replace('\n', "\\n")
+}
+
 /// Configuration for a sync peer
 #[derive(Debug, Clone)]
 pub struct PeerConfig {
@@ -193,6 +407,40 @@ pub struct PeerConfig {
 	pub sni_hostname: String,
 	/// Sync interval in seconds
 	pub sync_interval_secs: u64,
+	/// Certificate presented to the peer for mutual TLS; `None` connects
+	/// without client authentication
+	pub client_cert: Option<ClientCertConfig>,
+}
+
+/// PEM files of the certificate chain and private key a sync agent
+/// presents to a peer that requires client certificates.
+#[derive(Debug, Clone)]
+pub struct ClientCertConfig {
+	pub cert_path: PathBuf,
+	pub key_path: PathBuf,
+}
+
+/// TLS1.3 client config trusting `roots`, presenting the peer's client
+/// certificate when one is configured.
+fn client_config(roots: RootCertStore, peer: &PeerConfig) -> Result<ClientConfig> {
+	let builder = ClientConfig::builder()
+		.with_safe_default_cipher_suites()
+		.with_safe_default_kx_groups()
+		.with_protocol_versions(&[&tokio_rustls::rustls::version::TLS13])
+		.context("failed to configure TLS protocol versions")?
+		.with_root_certificates(roots);
+	match &peer.client_cert {
+		None => Ok(builder.with_no_client_auth()),
+		Some(client) => {
+			let certs = crate::tls_utils::load_certs(&client.cert_path)
+				.context("failed to load sync client certificate")?;
+			let key = crate::tls_utils::load_private_key(&client.key_path)
+				.context("failed to load sync client key")?;
+			builder
+				.with_single_cert(certs, key)
+				.context("invalid sync client certificate or key")
+		}
+	}
 }
 
 /// Sync agent for push/pull replication over TLS
@@ -205,12 +453,22 @@ pub struct SyncAgent {
 	peers: Vec<PeerConfig>,
 	/// Metrics
 	metrics: Arc<SyncMetrics>,
-	/// TLS connector
-	tls_connector: TlsConnector,
+	/// TLS connector per peer, keyed by `host:port`
+	tls_connectors: std::collections::HashMap<String, TlsConnector>,
 	/// Pending change log entries to push
 	pending_entries: Arc<RwLock<Vec<ChangeLogEntry>>>,
-	/// Last pull timestamp per peer
-	last_pull_timestamps: Arc<RwLock<std::collections::HashMap<String, u64>>>,
+	/// Last pull cursor per peer
+	last_pull_cursors: Arc<RwLock<std::collections::HashMap<String, u64>>>,
+	/// Entries asked for per pull request
+	pull_page_size: usize,
+	/// Limit on each read of a message length prefix or body
+	read_timeout: Duration,
+	/// Limit on a whole sync cycle (connect, auth, push and pull)
+	cycle_timeout: Duration,
+	/// Most entries kept in `pending_entries`
+	max_pending: usize,
+	/// Durable log receiving entries evicted over `max_pending`
+	spill: Option<Arc<ChangeLog>>,
 }
 
 impl SyncAgent {
@@ -242,32 +500,92 @@ impl SyncAgent {
 
 		debug!("Loaded {} valid root certificates", valid_certs);
 
-		let client_config = ClientConfig::builder()
-			.with_safe_default_cipher_suites()
-			.with_safe_default_kx_groups()
-			.with_protocol_versions(&[&tokio_rustls::rustls::version::TLS13])
-			.context("failed to configure TLS protocol versions")?
-			.with_root_certificates(root_store)
-			.with_no_client_auth();
-
-		let tls_connector = TlsConnector::from(Arc::new(client_config));
+		let mut tls_connectors = std::collections::HashMap::new();
+		for peer in &peers {
+			let config = client_config(root_store.clone(), peer)
+				.with_context(|| format!("peer {}:{}", peer.host, peer.port))?;
+			tls_connectors.insert(
+				format!("{}:{}", peer.host, peer.port),
+				TlsConnector::from(Arc::new(config)),
+			);
+		}
 
 		Ok(Self {
 			node_id,
 			oidc_provider,
 			peers,
 			metrics: Arc::new(SyncMetrics::default()),
-			tls_connector,
+			tls_connectors,
 			pending_entries: Arc::new(RwLock::new(Vec::new())),
-			last_pull_timestamps: Arc::new(RwLock::new(std::collections::HashMap::new())),
+			last_pull_cursors: Arc::new(RwLock::new(std::collections::HashMap::new())),
+			pull_page_size: DEFAULT_PULL_PAGE_SIZE,
+			read_timeout: DEFAULT_READ_TIMEOUT,
+			cycle_timeout: DEFAULT_CYCLE_TIMEOUT,
+			max_pending: DEFAULT_MAX_PENDING,
+			spill: None,
 		})
 	}
 
+	/// Keep at most `max_pending` entries in memory awaiting push. Older
+	/// entries beyond the cap are spilled to the log set with
+	/// [`SyncAgent::with_spill`], or dropped without one; tombstones are
+	/// always kept.
+	pub fn with_max_pending(mut self, max_pending: usize) -> Self {
+		self.max_pending = max_pending;
+		self
+	}
+
+	/// Spill entries evicted over the pending cap to `changelog`, from which
+	/// peers can still pull them.
+	pub fn with_spill(mut self, changelog: Arc<ChangeLog>) -> Self {
+		self.spill = Some(changelog);
+		self
+	}
+
+	/// Ask peers for at most `page_size` entries per pull response; a larger
+	/// backlog is pulled in several requests within one cycle.
+	pub fn with_pull_page_size(mut self, page_size: usize) -> Self {
+		self.pull_page_size = page_size.max(1);
+		self
+	}
+
+	/// Replace the default per-read and per-cycle timeouts. A peer that
+	/// stalls past either fails the cycle, which is retried after backoff.
+	pub fn with_timeouts(mut self, read_timeout: Duration, cycle_timeout: Duration) -> Self {
+		self.read_timeout = read_timeout;
+		self.cycle_timeout = cycle_timeout;
+		self
+	}
+
 	/// Add a change log entry to the pending queue
 	pub async fn enqueue_change(&self, entry: ChangeLogEntry) {
 		let mut entries = self.pending_entries.write().await;
 		entries.push(entry);
+		let evicted = evict_over_cap(&mut entries, self.max_pending);
 		debug!("Enqueued change log entry, queue size: {}", entries.len());
+		drop(entries);
+
+		if evicted.is_empty() {
+			return;
+		}
+		self.metrics
+			.pending_overflow
+			.fetch_add(evicted.len() as u64, Ordering::Relaxed);
+		match &self.spill {
+			Some(log) => {
+				for entry in evicted {
+					if let Err(e) = log.append(entry).await {
+						error!("Failed to spill pending change log entry: {:#}", e);
+					}
+				}
+			}
+			None => debug!("Dropped {} pending entries over the cap", evicted.len()),
+		}
+	}
+
+	/// Number of entries held in memory awaiting push
+	pub async fn pending_len(&self) -> usize {
+		self.pending_entries.read().await.len()
 	}
 
 	/// Get the metrics for this sync agent
@@ -297,13 +615,21 @@ impl SyncAgent {
 
 			debug!("Starting sync cycle with peer: {}", peer_addr);
 
-			match self.sync_with_peer(&peer).await {
+			let cycle = timeout(self.cycle_timeout, self.sync_with_peer(&peer))
+				.await
+				.unwrap_or_else(|_| {
+					Err(anyhow::anyhow!(
+						"sync cycle timed out after {:?}",
+						self.cycle_timeout
+					))
+				});
+			match cycle {
 				Ok(_) => {
 					debug!("Sync cycle completed successfully with peer: {}", peer_addr);
 				}
 				Err(e) => {
 					error!("Sync cycle failed with peer {}: {}", peer_addr, e);
-					self.metrics.reconnections.fetch_add(1, Ordering::Relaxed);
+					self.metrics.record_reconnection(&peer_addr);
 					// Exponential backoff with jitter to avoid thundering herd
 					let jitter = (std::time::SystemTime::now()
 						.duration_since(std::time::UNIX_EPOCH)
@@ -318,15 +644,17 @@ impl SyncAgent {
 
 	/// Perform a sync operation with a peer
 	async fn sync_with_peer(&self, peer: &PeerConfig) -> Result<()> {
+		let peer_id = format!("{}:{}", peer.host, peer.port);
+
 		// Connect to peer over TLS
 		let stream = self.connect_tls(peer).await?;
 		let (mut reader, mut writer) = tokio::io::split(stream);
 
 		// Authenticate with OIDC token
-		self.authenticate(&mut reader, &mut writer).await?;
+		self.authenticate(&mut reader, &mut writer, &peer_id).await?;
 
 		// Push pending changes
-		self.push_changes(&mut reader, &mut writer).await?;
+		self.push_changes(&mut reader, &mut writer, &peer_id).await?;
 
 		// Pull remote changes
 		self.pull_changes(&mut reader, &mut writer, peer).await?;
@@ -349,8 +677,11 @@ impl SyncAgent {
 		let server_name = ServerName::try_from(peer.sni_hostname.as_str())
 			.context("invalid SNI hostname")?;
 
-		let tls_stream = self
-			.tls_connector
+		let connector = self
+			.tls_connectors
+			.get(&addr)
+			.context("no TLS connector for peer")?;
+		let tls_stream = connector
 			.connect(server_name, tcp_stream)
 			.await
 			.context("TLS handshake failed")?;
@@ -364,6 +695,7 @@ impl SyncAgent {
 		&self,
 		reader: &mut R,
 		writer: &mut W,
+		peer_id: &str,
 	) -> Result<()> {
 		debug!("Authenticating with peer using OIDC");
 
@@ -387,11 +719,13 @@ impl SyncAgent {
 				Ok(())
 			}
 			SyncMessage::AuthFailed { reason } => {
-				self.metrics.auth_failures.fetch_add(1, Ordering::Relaxed);
+				self.metrics.record_auth_failure(peer_id);
+				// Fetch a fresh token next cycle rather than resending this one.
+				self.oidc_provider.clear_token_cache().await;
 				anyhow::bail!("authentication failed: {}", reason)
 			}
 			_ => {
-				self.metrics.auth_failures.fetch_add(1, Ordering::Relaxed);
+				self.metrics.record_auth_failure(peer_id);
 				anyhow::bail!("unexpected response to auth: {:?}", response)
 			}
 		}
@@ -402,8 +736,9 @@ impl SyncAgent {
 		&self,
 		reader: &mut R,
 		writer: &mut W,
+		peer_id: &str,
 	) -> Result<()> {
-		self.metrics.push_attempts.fetch_add(1, Ordering::Relaxed);
+		self.metrics.record_push_attempt(peer_id);
 
 		let mut pending = self.pending_entries.write().await;
 		if pending.is_empty() {
@@ -434,72 +769,90 @@ impl SyncAgent {
 					self.metrics.entries_sent.fetch_add(ack_count as u64, Ordering::Relaxed);
 					Ok(())
 				} else {
-					self.metrics.push_failures.fetch_add(1, Ordering::Relaxed);
+					self.metrics.record_push_failure(peer_id);
 					anyhow::bail!("push ack count mismatch: expected {}, got {}", count, ack_count)
 				}
 			}
 			SyncMessage::Error { message } => {
-				self.metrics.push_failures.fetch_add(1, Ordering::Relaxed);
+				self.metrics.record_push_failure(peer_id);
 				anyhow::bail!("push failed: {}", message)
 			}
 			_ => {
-				self.metrics.push_failures.fetch_add(1, Ordering::Relaxed);
+				self.metrics.record_push_failure(peer_id);
 				anyhow::bail!("unexpected response to push: {:?}", response)
 			}
 		}
 	}
 
-	/// Pull changes from a peer
+	/// Pull changes from a peer, page by page until it has no more
 	async fn pull_changes<R: AsyncReadExt + Unpin, W: AsyncWriteExt + Unpin>(
 		&self,
 		reader: &mut R,
 		writer: &mut W,
 		peer: &PeerConfig,
 	) -> Result<()> {
-		self.metrics.pull_attempts.fetch_add(1, Ordering::Relaxed);
-
 		let peer_id = format!("{}:{}", peer.host, peer.port);
-		let timestamps = self.last_pull_timestamps.read().await;
-		let since_timestamp = timestamps.get(&peer_id).copied().unwrap_or(0);
-		drop(timestamps);
-
-		debug!("Pulling changes since timestamp: {}", since_timestamp);
-
-		let pull_msg = SyncMessage::Pull { since_timestamp };
-		self.send_message(writer, &pull_msg).await?;
+		self.metrics.record_pull_attempt(&peer_id);
+		let cursors = self.last_pull_cursors.read().await;
+		let mut cursor = cursors.get(&peer_id).copied().unwrap_or(0);
+		drop(cursors);
 
-		// Wait for pull response
-		let response = self.receive_message(reader).await?;
-
-		match response {
-			SyncMessage::PullResponse { entries } => {
-				let count = entries.len();
-				info!("Received {} change log entries from peer", count);
-
-				// Update last pull timestamp
-				if let Some(last_entry) = entries.last() {
-					let mut timestamps = self.last_pull_timestamps.write().await;
-					timestamps.insert(peer_id, last_entry.timestamp);
+		loop {
+			debug!("Pulling changes after cursor: {}", cursor);
+
+			let pull_msg = SyncMessage::Pull {
+				since_timestamp: 0,
+				cursor: Some(cursor),
+				limit: Some(self.pull_page_size),
+			};
+			self.send_message(writer, &pull_msg).await?;
+
+			// Wait for pull response
+			let response = self.receive_message(reader).await?;
+
+			match response {
+				SyncMessage::PullResponse {
+					entries,
+					has_more,
+					next_cursor,
+				} => {
+					let count = entries.len();
+					info!("Received {} change log entries from peer", count);
+					self.metrics.entries_received.fetch_add(count as u64, Ordering::Relaxed);
+
+					// Process received entries (in a real implementation, this would
+					// apply merge rules and update the local database)
+					debug!("Processing {} received entries", count);
+
+					// Advance the cursor only once the page is processed, so a
+					// failed cycle pulls it again.
+					let advanced = next_cursor.filter(|next| *next > cursor);
+					if let Some(next) = advanced {
+						cursor = next;
+						let mut cursors = self.last_pull_cursors.write().await;
+						cursors.insert(peer_id.clone(), cursor);
+					}
+					if !has_more {
+						break;
+					}
+					if advanced.is_none() {
+						self.metrics.record_pull_failure(&peer_id);
+						anyhow::bail!("pull response has more entries but no new cursor");
+					}
+				}
+				SyncMessage::Error { message } => {
+					self.metrics.record_pull_failure(&peer_id);
+					anyhow::bail!("pull failed: {}", message)
+				}
+				_ => {
+					self.metrics.record_pull_failure(&peer_id);
+					anyhow::bail!("unexpected response to pull: {:?}", response)
 				}
-
-				self.metrics.pull_successes.fetch_add(1, Ordering::Relaxed);
-				self.metrics.entries_received.fetch_add(count as u64, Ordering::Relaxed);
-
-				// Process received entries (in a real implementation, this would
-				// apply merge rules and update the local database)
-				debug!("Processing {} received entries", count);
-
-				Ok(())
-			}
-			SyncMessage::Error { message } => {
-				self.metrics.pull_failures.fetch_add(1, Ordering::Relaxed);
-				anyhow::bail!("pull failed: {}", message)
-			}
-			_ => {
-				self.metrics.pull_failures.fetch_add(1, Ordering::Relaxed);
-				anyhow::bail!("unexpected response to pull: {:?}", response)
 			}
 		}
+
+		self.metrics.pull_successes.fetch_add(1, Ordering::Relaxed);
+		Ok(())
 	}
 
 	/// Send a sync message over the wire
@@ -508,57 +861,93 @@ impl SyncAgent {
 		writer: &mut W,
 		msg: &SyncMessage,
 	) -> Result<()> {
-		let json = serde_json::to_vec(msg).context("failed to serialize message")?;
-		let len = json.len();
+		write_message(writer, msg).await
+	}
+
+	/// Receive a sync message from the wire
+	async fn receive_message<R: AsyncReadExt + Unpin>(&self, reader: &mut R) -> Result<SyncMessage> {
+		read_message(reader, self.read_timeout).await
+	}
+}
 
-		if len > MAX_ENTRY_SIZE {
-			anyhow::bail!("message size {} exceeds maximum {}", len, MAX_ENTRY_SIZE);
+/// Remove the oldest non-tombstone entries until at most `max` remain (or
+/// only tombstones are left), returning them oldest first.
+fn evict_over_cap(pending: &mut Vec<ChangeLogEntry>, max: usize) -> Vec<ChangeLogEntry> {
+	let mut excess = pending.len().saturating_sub(max);
+	if excess == 0 {
+		return Vec::new();
+	}
+	let mut evicted = Vec::new();
+	pending.retain(|entry| {
+		if excess > 0 && !entry.tombstone {
+			excess -= 1;
+			evicted.push(entry.clone());
+			false
+		} else {
+			true
 		}
+	});
+	evicted
+}
 
-		// Write length prefix (4 bytes, big-endian)
-		writer
-			.write_all(&(len as u32).to_be_bytes())
-			.await
-			.context("failed to write message length")?;
+/// Write one length-prefixed message.
+pub(crate) async fn write_message<W: AsyncWriteExt + Unpin>(
+	writer: &mut W,
+	msg: &SyncMessage,
+) -> Result<()> {
+	let json = serde_json::to_vec(msg).context("failed to serialize message")?;
+	let len = json.len();
 
-		// Write message body
-		writer
-			.write_all(&json)
-			.await
-			.context("failed to write message body")?;
+	if len > MAX_ENTRY_SIZE {
+		anyhow::bail!("message size {} exceeds maximum {}", len, MAX_ENTRY_SIZE);
+	}
 
-		writer.flush().await.context("failed to flush writer")?;
+	// Write length prefix (4 bytes, big-endian)
+	writer
+		.write_all(&(len as u32).to_be_bytes())
+		.await
+		.context("failed to write message length")?;
 
-		Ok(())
-	}
+	// Write message body
+	writer
+		.write_all(&json)
+		.await
+		.context("failed to write message body")?;
 
-	/// Receive a sync message from the wire
-	async fn receive_message<R: AsyncReadExt + Unpin>(&self, reader: &mut R) -> Result<SyncMessage> {
-		// Read length prefix (4 bytes, big-endian)
-		let mut len_bytes = [0u8; 4];
-		reader
-			.read_exact(&mut len_bytes)
-			.await
-			.context("failed to read message length")?;
+	writer.flush().await.context("failed to flush writer")?;
 
-		let len = u32::from_be_bytes(len_bytes) as usize;
+	Ok(())
+}
 
-		if len > MAX_ENTRY_SIZE {
-			anyhow::bail!("message size {} exceeds maximum {}", len, MAX_ENTRY_SIZE);
-		}
+/// Read one length-prefixed message, failing if either the prefix or the
+/// body takes longer than `read_timeout` to arrive.
+pub(crate) async fn read_message<R: AsyncReadExt + Unpin>(
+	reader: &mut R,
+	read_timeout: Duration,
+) -> Result<SyncMessage> {
+	// Read length prefix (4 bytes, big-endian)
+	let mut len_bytes = [0u8; 4];
+	timeout(read_timeout, reader.read_exact(&mut len_bytes))
+		.await
+		.context("timed out reading message length")?
+		.context("failed to read message length")?;
+
+	let len = u32::from_be_bytes(len_bytes) as usize;
+
+	if len > MAX_ENTRY_SIZE {
+		anyhow::bail!("message size {} exceeds maximum {}", len, MAX_ENTRY_SIZE);
+	}
 
-		// Read message body
-		let mut buf = vec![0u8; len];
-		reader
-			.read_exact(&mut buf)
-			.await
-			.context("failed to read message body")?;
+	// Read message body
+	let mut buf = vec![0u8; len];
+	timeout(read_timeout, reader.read_exact(&mut buf))
+		.await
+		.context("timed out reading message body")?
+		.context("failed to read message body")?;
 
-		let msg: SyncMessage =
-			serde_json::from_slice(&buf).context("failed to deserialize message")?;
+	let msg: SyncMessage = serde_json::from_slice(&buf).context("failed to deserialize message")?;
 
-		Ok(msg)
-	}
+	Ok(msg)
 }
 
 #[cfg(test)]
@@ -575,6 +964,30 @@ mod tests {
 		assert!(matches!(deserialized, SyncMessage::Ping));
 	}
 
+	#[test]
+	fn test_pull_without_cursor_still_parses() {
+		let pull: SyncMessage =
+			serde_json::from_str(r#"{"type":"Pull","since_timestamp":5}"#).unwrap();
+		assert!(matches!(
+			pull,
+			SyncMessage::Pull {
+				since_timestamp: 5,
+				cursor: None,
+				limit: None
+			}
+		));
+		let response: SyncMessage =
+			serde_json::from_str(r#"{"type":"PullResponse","entries":[]}"#).unwrap();
+		assert!(matches!(
+			response,
+			SyncMessage::PullResponse {
+				has_more: false,
+				next_cursor: None,
+				..
+			}
+		));
+	}
+
 	#[test]
 	fn test_change_log_entry_serialization() {
 		let mut version_vector = std::collections::HashMap::new();
@@ -600,6 +1013,48 @@ mod tests {
 		assert_eq!(deserialized.tombstone, false);
 	}
 
+	#[test]
+	fn test_client_cert_is_presented_when_configured() {
+		let dir = tempfile::tempdir().unwrap();
+		let cert =
+			rcgen::generate_simple_self_signed(vec!["node-a.heimdall.test".to_string()]).unwrap();
+		let cert_path = dir.path().join("client.crt");
+		let key_path = dir.path().join("client.key");
+		// Each serialization signs anew, so keep the one written out.
+		let cert_pem = cert.serialize_pem().unwrap();
+		std::fs::write(&cert_path, &cert_pem).unwrap();
+		std::fs::write(&key_path, cert.serialize_private_key_pem()).unwrap();
+
+		let mut peer = PeerConfig {
+			host: "peer.heimdall.test".to_string(),
+			port: 8443,
+			sni_hostname: "peer.heimdall.test".to_string(),
+			sync_interval_secs: 60,
+			client_cert: None,
+		};
+		let plain = client_config(RootCertStore::empty(), &peer).unwrap();
+		assert!(!plain.client_auth_cert_resolver.has_certs());
+
+		peer.client_cert = Some(ClientCertConfig {
+			cert_path: cert_path.clone(),
+			key_path,
+		});
+		let mtls = client_config(RootCertStore::empty(), &peer).unwrap();
+		assert!(mtls.client_auth_cert_resolver.has_certs());
+		let presented = mtls
+			.client_auth_cert_resolver
+			.resolve(&[], &[])
+			.expect("client certificate");
+		let written = rustls_pemfile::certs(&mut cert_pem.as_bytes()).unwrap();
+		assert_eq!(presented.cert[0].0, written[0]);
+
+		peer.client_cert = Some(ClientCertConfig {
+			cert_path,
+			key_path: dir.path().join("missing.key"),
+		});
+		assert!(client_config(RootCertStore::empty(), &peer).is_err());
+	}
+
 	#[test]
 	fn test_sync_metrics_default() {
 		let metrics = SyncMetrics::default();
@@ -617,4 +1072,145 @@ mod tests {
 		assert!(text.contains("heimdall_sync_push_attempts_total 5"));
 		assert!(text.contains("heimdall_sync_entries_sent_total 100"));
 	}
+
+	#[test]
+	fn test_sync_metrics_per_peer_labels() {
+		let metrics = SyncMetrics::default();
+		metrics.record_push_failure("peer-a.example:7443");
+		metrics.record_push_failure("peer-a.example:7443");
+		metrics.record_push_failure("peer-b.example:7443");
+		metrics.record_auth_failure("peer-b.example:7443");
+		metrics.record_reconnection("peer-a.example:7443");
+
+		let text = metrics.to_prometheus_text();
+
+		// Aggregates stay unlabeled in their own families.
+		assert!(text.contains("heimdall_sync_push_failures_total 3\n"));
+		assert!(!text.contains("heimdall_sync_push_failures_total{"));
+		assert!(text.contains(
+			"heimdall_sync_peer_push_failures_total{peer=\"peer-a.example:7443\"} 2\n"
+		));
+		assert!(text.contains(
+			"heimdall_sync_peer_push_failures_total{peer=\"peer-b.example:7443\"} 1\n"
+		));
+		assert!(text.contains(
+			"heimdall_sync_peer_auth_failures_total{peer=\"peer-b.example:7443\"} 1\n"
+		));
+		assert!(text.contains(
+			"heimdall_sync_peer_auth_failures_total{peer=\"peer-a.example:7443\"} 0\n"
+		));
+		assert!(text.contains(
+			"heimdall_sync_peer_reconnections_total{peer=\"peer-a.example:7443\"} 1\n"
+		));
+
+		// HELP/TYPE appear once per family, and no per-peer family carries
+		// an unlabeled sample.
+		assert_eq!(
+			text.matches("# TYPE heimdall_sync_push_failures_total counter").count(),
+			1
+		);
+		assert_eq!(
+			text.matches("# TYPE heimdall_sync_peer_push_failures_total counter")
+				.count(),
+			1
+		);
+		assert!(!text.contains("\nheimdall_sync_peer_push_failures_total "));
+	}
+
+	#[test]
+	fn test_sync_metrics_snapshot_matches_text() {
+		let metrics = SyncMetrics::default();
+		metrics.record_push_failure("peer-a.example:7443");
+		metrics.entries_sent.store(4, Ordering::Relaxed);
+
+		let families = metrics.snapshot();
+		assert_eq!(families.len(), metrics.to_prometheus_text().matches("# TYPE").count());
+		let failures = families
+			.iter()
+			.find(|f| f.name == "heimdall_sync_push_failures_total")
+			.unwrap();
+		assert_eq!(failures.kind, MetricKind::Counter);
+		assert_eq!(failures.samples.len(), 1);
+		assert_eq!(failures.samples[0].value, Some(1.0));
+		assert!(failures.samples[0].labels.is_empty());
+		let per_peer = families
+			.iter()
+			.find(|f| f.name == "heimdall_sync_peer_push_failures_total")
+			.unwrap();
+		assert_eq!(per_peer.samples.len(), 1);
+		assert_eq!(
+			per_peer.samples[0].labels.get("peer").map(String::as_str),
+			Some("peer-a.example:7443")
+		);
+		let sent = families
+			.iter()
+			.find(|f| f.name == "heimdall_sync_entries_sent_total")
+			.unwrap();
+		assert_eq!(sent.samples.len(), 1);
+		assert_eq!(sent.samples[0].value, Some(4.0));
+	}
+
+	fn entry(id: &str, tombstone: bool) -> ChangeLogEntry {
+		ChangeLogEntry {
+			id: id.to_string(),
+			timestamp: 0,
+			label: "FieldValue".to_string(),
+			key: id.to_string(),
+			props: serde_json::json!({}),
+			origin: "node-a".to_string(),
+			version_vector: std::collections::HashMap::new(),
+			tombstone,
+		}
+	}
+
+	#[test]
+	fn test_evict_over_cap_keeps_newest_and_tombstones() {
+		let mut pending = Vec::new();
+		for i in 0..10 {
+			pending.push(entry(&format!("e{}", i), i == 1));
+			let evicted = evict_over_cap(&mut pending, 4);
+			assert!(pending.len() <= 4);
+			assert!(evicted.iter().all(|e| !e.tombstone));
+		}
+		let ids: Vec<&str> = pending.iter().map(|e| e.id.as_str()).collect();
+		assert_eq!(ids, ["e1", "e7", "e8", "e9"]);
+
+		// A queue of only tombstones is never trimmed.
+		let mut tombstones: Vec<_> = (0..3).map(|i| entry(&format!("t{}", i), true)).collect();
+		assert!(evict_over_cap(&mut tombstones, 1).is_empty());
+		assert_eq!(tombstones.len(), 3);
+	}
+
+	#[tokio::test]
+	async fn test_read_message_times_out_on_stalled_body() {
+		let (mut peer, mut local) = tokio::io::duplex(64);
+		peer.write_all(&16u32.to_be_bytes()).await.unwrap();
+
+		// The peer stays connected but never sends the body.
+		let result = tokio::time::timeout(
+			Duration::from_secs(5),
+			read_message(&mut local, Duration::from_millis(50)),
+		)
+		.await
+		.expect("read_message blocked past its read timeout");
+		let err = result.unwrap_err();
+		assert!(err.to_string().contains("timed out reading message body"));
+		drop(peer);
+	}
+
+	#[tokio::test]
+	async fn test_read_message_reads_framed_message() {
+		let (mut peer, mut local) = tokio::io::duplex(64);
+		let body = serde_json::to_vec(&SyncMessage::AuthOk).unwrap();
+		peer.write_all(&(body.len() as u32).to_be_bytes()).await.unwrap();
+		peer.write_all(&body).await.unwrap();
+
+		let msg = read_message(&mut local, Duration::from_secs(1)).await.unwrap();
+		assert!(matches!(msg, SyncMessage::AuthOk));
+	}
+
+	#[test]
+	fn test_peer_label_value_is_escaped() {
+		assert_eq!(escape_label_value("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+	}
 }
@@ -2,11 +2,30 @@ use anyhow::{Context, Result};
 use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
 use log::{debug, info, warn};
 use reqwest::Client;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::HashMap;
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+
+/// Minimum time between JWKS fetches triggered by unknown key IDs.
+pub const DEFAULT_MIN_JWKS_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long before expiry a cached client-credentials token is replaced.
+pub const DEFAULT_TOKEN_EXPIRY_SKEW: Duration = Duration::from_secs(30);
+
+/// Timeout of one discovery or JWKS request.
+pub const DEFAULT_FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Retries of a failed discovery or JWKS request.
+pub const DEFAULT_FETCH_RETRIES: u32 = 3;
+
+/// Delay before the first retry; it doubles with every further attempt.
+pub const DEFAULT_FETCH_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Upper bound on the delay between retries.
+const MAX_FETCH_BACKOFF: Duration = Duration::from_secs(30);
 
 /// OIDC discovery document structure as defined by OpenID Connect Discovery 1.0
 #[derive(Debug, Deserialize, Clone)]
@@ -31,8 +50,69 @@ pub struct Jwk {
 	pub kty: String,
 	pub kid: Option<String>,
 	pub alg: Option<String>,
+	/// RSA modulus and exponent
 	pub n: Option<String>,
 	pub e: Option<String>,
+	/// Curve of an `EC` or `OKP` key, e.g. `P-256` or `Ed25519`
+	#[serde(default)]
+	pub crv: Option<String>,
+	/// Public point coordinates of an `EC` key; `x` alone for `OKP`
+	#[serde(default)]
+	pub x: Option<String>,
+	#[serde(default)]
+	pub y: Option<String>,
+}
+
+impl Jwk {
+	/// Signature algorithms this key verifies: the RSA family for `RSA`
+	/// keys, the curve's ECDSA algorithm for `EC` keys and EdDSA for
+	/// Ed25519 `OKP` keys.
+	fn algorithms(&self) -> Result<&'static [Algorithm]> {
+		let crv = self.crv.as_deref().unwrap_or_default();
+		Ok(match (self.kty.as_str(), crv) {
+			("RSA", _) => &[
+				Algorithm::RS256,
+				Algorithm::RS384,
+				Algorithm::RS512,
+				Algorithm::PS256,
+				Algorithm::PS384,
+				Algorithm::PS512,
+			],
+			("EC", "P-256") => &[Algorithm::ES256],
+			("EC", "P-384") => &[Algorithm::ES384],
+			("OKP", "Ed25519") => &[Algorithm::EdDSA],
+			(kty, "") => anyhow::bail!("unsupported key type: {}", kty),
+			(kty, crv) => anyhow::bail!("unsupported key type: {} ({})", kty, crv),
+		})
+	}
+
+	/// Decoding key for a token signed with `alg`, refusing algorithms the
+	/// key (or its `alg`, when set) was not made for.
+	fn decoding_key(&self, alg: Algorithm) -> Result<DecodingKey> {
+		if !self.algorithms()?.contains(&alg) {
+			anyhow::bail!("token algorithm {:?} does not match {} key", alg, self.kty);
+		}
+		if let Some(key_alg) = self.alg.as_deref() {
+			if key_alg != format!("{:?}", alg) {
+				anyhow::bail!(
+					"token algorithm {:?} does not match key algorithm {}",
+					alg,
+					key_alg
+				);
+			}
+		}
+		fn param<'a>(value: &'a Option<String>, name: &str) -> anyhow::Result<&'a str> {
+			value
+				.as_deref()
+				.with_context(|| format!("JWK missing '{}' parameter", name))
+		}
+		let key = match self.kty.as_str() {
+			"RSA" => DecodingKey::from_rsa_components(param(&self.n, "n")?, param(&self.e, "e")?),
+			"EC" => DecodingKey::from_ec_components(param(&self.x, "x")?, param(&self.y, "y")?),
+			_ => DecodingKey::from_ed_components(param(&self.x, "x")?),
+		};
+		key.context("failed to construct decoding key")
+	}
 }
 
 /// Claims structure for JWT validation
@@ -40,13 +120,39 @@ pub struct Jwk {
 pub struct Claims {
 	pub sub: String,
 	pub iss: String,
-	pub aud: String,
+	/// Intended audiences; a single-string `aud` becomes a one-element list.
+	#[serde(deserialize_with = "one_or_many")]
+	pub aud: Vec<String>,
 	pub exp: u64,
 	pub iat: u64,
 	pub azp: Option<String>,
 	pub scope: Option<String>,
 }
 
+/// Deserialize a string or an array of strings into a list.
+fn one_or_many<'de, D>(deserializer: D) -> std::result::Result<Vec<String>, D::Error>
+where
+	D: Deserializer<'de>,
+{
+	#[derive(Deserialize)]
+	#[serde(untagged)]
+	enum OneOrMany {
+		One(String),
+		Many(Vec<String>),
+	}
+
+	Ok(match OneOrMany::deserialize(deserializer)? {
+		OneOrMany::One(s) => vec![s],
+		OneOrMany::Many(v) => v,
+	})
+}
+
+/// Client-credentials access token and when it stops being reused.
+struct CachedToken {
+	access_token: String,
+	refresh_at: Instant,
+}
+
 /// OIDC provider configuration and validation state
 pub struct OidcProvider {
 	discovery_url: String,
@@ -55,6 +161,22 @@ pub struct OidcProvider {
 	discovery_doc: Arc<RwLock<Option<OidcDiscoveryDocument>>>,
 	jwks: Arc<RwLock<Option<Jwks>>>,
 	client: Client,
+	/// When an unknown `kid` last triggered a JWKS fetch, successful or not.
+	last_on_demand_refresh: Mutex<Option<Instant>>,
+	/// Serializes on-demand refreshes so concurrent callers share one fetch.
+	refresh_lock: tokio::sync::Mutex<()>,
+	min_refresh_interval: Duration,
+	/// Accepted `aud` values; a token must name at least one.
+	audiences: Vec<String>,
+	/// Accepted `iss` values; the discovered issuer when empty.
+	issuers: Vec<String>,
+	/// Client-credentials tokens by requested scope. Held across the token
+	/// request so concurrent callers share one fetch.
+	token_cache: tokio::sync::Mutex<HashMap<Option<String>, CachedToken>>,
+	token_expiry_skew: Duration,
+	fetch_timeout: Duration,
+	fetch_retries: u32,
+	fetch_backoff: Duration,
 }
 
 impl OidcProvider {
@@ -66,12 +188,117 @@ impl OidcProvider {
 			.expect("failed to build HTTP client for OIDC");
 
 		Self {
+			audiences: vec![client_id.clone()],
+			issuers: Vec::new(),
 			discovery_url,
 			client_id,
 			client_secret,
 			discovery_doc: Arc::new(RwLock::new(None)),
 			jwks: Arc::new(RwLock::new(None)),
 			client,
+			last_on_demand_refresh: Mutex::new(None),
+			refresh_lock: tokio::sync::Mutex::new(()),
+			min_refresh_interval: DEFAULT_MIN_JWKS_REFRESH_INTERVAL,
+			token_cache: tokio::sync::Mutex::new(HashMap::new()),
+			token_expiry_skew: DEFAULT_TOKEN_EXPIRY_SKEW,
+			fetch_timeout: DEFAULT_FETCH_TIMEOUT,
+			fetch_retries: DEFAULT_FETCH_RETRIES,
+			fetch_backoff: DEFAULT_FETCH_BACKOFF,
+		}
+	}
+
+	/// Give up on a single discovery or JWKS request after `timeout`.
+	pub fn with_fetch_timeout(mut self, timeout: Duration) -> Self {
+		self.fetch_timeout = timeout;
+		self
+	}
+
+	/// Retry a failed discovery or JWKS request up to `retries` times,
+	/// waiting `backoff` before the first retry and doubling the wait for
+	/// each one after it.
+	pub fn with_fetch_retries(mut self, retries: u32, backoff: Duration) -> Self {
+		self.fetch_retries = retries;
+		self.fetch_backoff = backoff;
+		self
+	}
+
+	/// How long before its `expires_in` elapses a cached client-credentials
+	/// token is replaced, covering clock drift and request latency.
+	pub fn with_token_expiry_skew(mut self, skew: Duration) -> Self {
+		self.token_expiry_skew = skew;
+		self
+	}
+
+	/// Accept tokens whose `aud` names any of `audiences` instead of only
+	/// the client ID.
+	pub fn with_audiences<I, S>(mut self, audiences: I) -> Self
+	where
+		I: IntoIterator<Item = S>,
+		S: Into<String>,
+	{
+		self.audiences = audiences.into_iter().map(Into::into).collect();
+		self
+	}
+
+	/// Accept tokens issued by any of `issuers` instead of only the issuer
+	/// named in the discovery document, e.g. in a federation.
+	pub fn with_issuers<I, S>(mut self, issuers: I) -> Self
+	where
+		I: IntoIterator<Item = S>,
+		S: Into<String>,
+	{
+		self.issuers = issuers.into_iter().map(Into::into).collect();
+		self
+	}
+
+	/// Minimum time between JWKS fetches triggered by tokens with an
+	/// unknown `kid`. Protects the provider from refresh storms when many
+	/// tokens signed with unknown keys arrive at once.
+	pub fn with_min_refresh_interval(mut self, interval: Duration) -> Self {
+		self.min_refresh_interval = interval;
+		self
+	}
+
+	/// GET `url` as JSON, retrying failures with backoff. The error after
+	/// the last attempt names `what` and how many attempts were made.
+	async fn get_json_with_retries<T>(&self, url: &str, what: &str) -> Result<T>
+	where
+		T: serde::de::DeserializeOwned,
+	{
+		let mut backoff = self.fetch_backoff;
+		let mut attempt = 0;
+		loop {
+			attempt += 1;
+			let result = async {
+				self.client
+					.get(url)
+					.timeout(self.fetch_timeout)
+					.send()
+					.await
+					.with_context(|| format!("failed to fetch {}", what))?
+					.error_for_status()
+					.with_context(|| format!("failed to fetch {}", what))?
+					.json::<T>()
+					.await
+					.with_context(|| format!("failed to parse {}", what))
+			}
+			.await;
+			match result {
+				Ok(value) => return Ok(value),
+				Err(e) if attempt > self.fetch_retries => {
+					return Err(
+						e.context(format!("giving up on {} after {} attempts", what, attempt))
+					);
+				}
+				Err(e) => {
+					warn!(
+						"Fetching {} failed (attempt {}): {:#}; retrying in {:?}",
+						what, attempt, e, backoff
+					);
+					tokio::time::sleep(backoff).await;
+					backoff = (backoff * 2).min(MAX_FETCH_BACKOFF);
+				}
+			}
 		}
 	}
 
@@ -79,15 +306,9 @@ impl OidcProvider {
 	pub async fn fetch_discovery(&self) -> Result<OidcDiscoveryDocument> {
 		info!("Fetching OIDC discovery document from {}", self.discovery_url);
 
-		let doc = self
-			.client
-			.get(&self.discovery_url)
-			.send()
-			.await
-			.context("failed to fetch OIDC discovery document")?
-			.json::<OidcDiscoveryDocument>()
-			.await
-			.context("failed to parse OIDC discovery document")?;
+		let doc: OidcDiscoveryDocument = self
+			.get_json_with_retries(&self.discovery_url, "OIDC discovery document")
+			.await?;
 
 		debug!("OIDC issuer: {}", doc.issuer);
 		debug!("OIDC jwks_uri: {}", doc.jwks_uri);
@@ -98,24 +319,18 @@ impl OidcProvider {
 		Ok(doc)
 	}
 
-	/// Fetch the JSON Web Key Set (JWKS) from the provider
+	/// Fetch the JSON Web Key Set (JWKS) from the provider, fetching the
+	/// discovery document first if it is not loaded yet.
 	pub async fn fetch_jwks(&self) -> Result<Jwks> {
-		let discovery = self.discovery_doc.read().await;
-		let doc = discovery
-			.as_ref()
-			.context("discovery document not loaded; call fetch_discovery first")?;
+		let loaded = self.discovery_doc.read().await.clone();
+		let doc = match loaded {
+			Some(doc) => doc,
+			None => self.fetch_discovery().await?,
+		};
 
 		info!("Fetching JWKS from {}", doc.jwks_uri);
 
-		let jwks = self
-			.client
-			.get(&doc.jwks_uri)
-			.send()
-			.await
-			.context("failed to fetch JWKS")?
-			.json::<Jwks>()
-			.await
-			.context("failed to parse JWKS")?;
+		let jwks: Jwks = self.get_json_with_retries(&doc.jwks_uri, "JWKS").await?;
 
 		debug!("Fetched {} JWKs", jwks.keys.len());
 
@@ -125,7 +340,8 @@ impl OidcProvider {
 		Ok(jwks)
 	}
 
-	/// Initialize the OIDC provider by fetching discovery and JWKS
+	/// Initialize the OIDC provider by fetching discovery and JWKS, each
+	/// retried as configured with [`Self::with_fetch_retries`].
 	pub async fn initialize(&self) -> Result<()> {
 		self.fetch_discovery().await?;
 		self.fetch_jwks().await?;
@@ -133,6 +349,28 @@ impl OidcProvider {
 		Ok(())
 	}
 
+	/// Whether discovery and JWKS have been loaded.
+	pub async fn is_initialized(&self) -> bool {
+		self.discovery_doc.read().await.is_some() && self.jwks.read().await.is_some()
+	}
+
+	/// Keep calling [`Self::initialize`] every `interval` in the background
+	/// until it succeeds, for a provider that failed to initialize at
+	/// startup. Tokens are rejected until then, unless a validation's own
+	/// JWKS refresh loads the keys first.
+	pub fn spawn_initialize_retry(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+		let provider = Arc::clone(self);
+		tokio::spawn(async move {
+			while !provider.is_initialized().await {
+				tokio::time::sleep(interval).await;
+				match provider.initialize().await {
+					Ok(()) => break,
+					Err(e) => warn!("OIDC initialization failed: {:#}", e),
+				}
+			}
+		})
+	}
+
 	/// Validate a JWT token and return the claims if valid
 	pub async fn validate_token(&self, token: &str) -> Result<Claims> {
 		// Decode header to get the key ID (kid)
@@ -140,39 +378,35 @@ impl OidcProvider {
 
 		let kid = header.kid.context("JWT header missing 'kid' field")?;
 
-		// Find the matching key in the JWKS
-		let jwks = self.jwks.read().await;
-		let keys = jwks
-			.as_ref()
-			.context("JWKS not loaded; call initialize first")?;
-
-		let jwk = keys
-			.keys
-			.iter()
-			.find(|k| k.kid.as_ref() == Some(&kid))
-			.context("no matching key found in JWKS")?;
-
-		// Validate that the key is RSA
-		if jwk.kty != "RSA" {
-			anyhow::bail!("unsupported key type: {}", jwk.kty);
-		}
-
-		let n = jwk.n.as_ref().context("JWK missing 'n' parameter")?;
-		let e = jwk.e.as_ref().context("JWK missing 'e' parameter")?;
+		// Find the matching key in the JWKS. An unknown kid usually means
+		// the provider rotated its keys, so refresh once and retry.
+		let jwk = match self.find_jwk(&kid).await? {
+			Some(jwk) => jwk,
+			None => {
+				self.refresh_jwks_debounced().await?;
+				self.find_jwk(&kid)
+					.await?
+					.context("no matching key found in JWKS")?
+			}
+		};
 
-		// Construct the decoding key from RSA components
-		let decoding_key =
-			DecodingKey::from_rsa_components(n, e).context("failed to construct decoding key")?;
+		// The header names the algorithm, but only one the key supports
+		// is accepted.
+		let decoding_key = jwk.decoding_key(header.alg)?;
 
 		// Set validation parameters
-		let discovery = self.discovery_doc.read().await;
-		let doc = discovery
-			.as_ref()
-			.context("discovery document not loaded")?;
-
-		let mut validation = Validation::new(Algorithm::RS256);
-		validation.set_issuer(&[&doc.issuer]);
-		validation.set_audience(&[&self.client_id]);
+		let mut validation = Validation::new(header.alg);
+		if self.issuers.is_empty() {
+			let discovery = self.discovery_doc.read().await;
+			let doc = discovery
+				.as_ref()
+				.context("discovery document not loaded")?;
+			validation.set_issuer(&[&doc.issuer]);
+		} else {
+			validation.set_issuer(&self.issuers);
+		}
+		// A token is accepted when any of its audiences is in this set.
+		validation.set_audience(&self.audiences);
 
 		// Decode and validate the token (includes expiration check)
 		let token_data = decode::<Claims>(token, &decoding_key, &validation)
@@ -184,7 +418,46 @@ impl OidcProvider {
 	}
 
 	/// Obtain a machine-to-machine (M2M) access token using client credentials
+	///
+	/// Tokens are cached per scope and reused until they are within the
+	/// expiry skew of their `expires_in`. Tokens without `expires_in` are
+	/// not cached.
 	pub async fn get_client_credentials_token(&self, scope: Option<&str>) -> Result<String> {
+		let key = scope.map(str::to_string);
+		let mut cache = self.token_cache.lock().await;
+		if let Some(cached) = cache.get(&key) {
+			if Instant::now() < cached.refresh_at {
+				debug!("Using cached client credentials token");
+				return Ok(cached.access_token.clone());
+			}
+		}
+		cache.remove(&key);
+
+		let (access_token, expires_in) = self.request_client_credentials_token(scope).await?;
+		if let Some(secs) = expires_in {
+			let lifetime = Duration::from_secs(secs).saturating_sub(self.token_expiry_skew);
+			cache.insert(
+				key,
+				CachedToken {
+					access_token: access_token.clone(),
+					refresh_at: Instant::now() + lifetime,
+				},
+			);
+		}
+		Ok(access_token)
+	}
+
+	/// Drop cached client-credentials tokens, e.g. after a peer rejected one.
+	pub async fn clear_token_cache(&self) {
+		self.token_cache.lock().await.clear();
+	}
+
+	/// Request a token from the token endpoint, returning it with its
+	/// `expires_in` in seconds when the provider sent one.
+	async fn request_client_credentials_token(
+		&self,
+		scope: Option<&str>,
+	) -> Result<(String, Option<u64>)> {
 		let discovery = self.discovery_doc.read().await;
 		let doc = discovery
 			.as_ref()
@@ -224,10 +497,11 @@ impl OidcProvider {
 			.get("access_token")
 			.and_then(|v| v.as_str())
 			.context("token response missing 'access_token' field")?;
+		let expires_in = token_response.get("expires_in").and_then(|v| v.as_u64());
 
 		debug!("Successfully obtained client credentials token");
 
-		Ok(access_token.to_string())
+		Ok((access_token.to_string(), expires_in))
 	}
 
 	/// Refresh JWKS if needed (e.g., after a validation failure)
@@ -236,6 +510,59 @@ impl OidcProvider {
 		self.fetch_jwks().await?;
 		Ok(())
 	}
+
+	/// Refresh JWKS on behalf of a token with an unknown `kid`, unless such
+	/// a refresh already ran within the minimum refresh interval. Returns
+	/// whether a fetch was made.
+	///
+	/// Concurrent callers wait for an in-flight refresh and then skip their
+	/// own, so a burst of tokens with unknown keys causes at most one fetch.
+	pub async fn refresh_jwks_debounced(&self) -> Result<bool> {
+		let _guard = self.refresh_lock.lock().await;
+		{
+			let mut last = self.last_on_demand_refresh.lock().unwrap();
+			if last.is_some_and(|at| at.elapsed() < self.min_refresh_interval) {
+				debug!("Skipping JWKS refresh; last refresh was too recent");
+				return Ok(false);
+			}
+			// Stamped before fetching so failures are rate-limited too.
+			*last = Some(Instant::now());
+		}
+		self.refresh_jwks().await?;
+		Ok(true)
+	}
+
+	/// Refresh JWKS every `interval` in the background so rotated keys are
+	/// picked up without waiting for an unknown `kid`. Failures are logged
+	/// and retried at the next tick.
+	pub fn spawn_jwks_refresh(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+		let provider = Arc::clone(self);
+		tokio::spawn(async move {
+			let mut ticker = tokio::time::interval(interval);
+			// The first tick completes immediately; JWKS was just loaded.
+			ticker.tick().await;
+			loop {
+				ticker.tick().await;
+				if let Err(e) = provider.refresh_jwks().await {
+					warn!("Periodic JWKS refresh failed: {:#}", e);
+				}
+			}
+		})
+	}
+
+	/// Clone the key with ID `kid` out of the loaded JWKS. `None` also when
+	/// no JWKS is loaded yet, so validation's refresh loads it.
+	async fn find_jwk(&self, kid: &str) -> Result<Option<Jwk>> {
+		let jwks = self.jwks.read().await;
+		let Some(keys) = jwks.as_ref() else {
+			return Ok(None);
+		};
+		Ok(keys
+			.keys
+			.iter()
+			.find(|k| k.kid.as_deref() == Some(kid))
+			.cloned())
+	}
 }
 
 #[cfg(test)]
@@ -259,7 +586,7 @@ mod tests {
 		let claims = Claims {
 			sub: "user123".to_string(),
 			iss: "https://issuer.example.com".to_string(),
-			aud: "client-id".to_string(),
+			aud: vec!["client-id".to_string()],
 			exp: 1234567890,
 			iat: 1234567800,
 			azp: Some("azp-value".to_string()),
@@ -270,4 +597,58 @@ mod tests {
 		assert!(json.contains("user123"));
 		assert!(json.contains("https://issuer.example.com"));
 	}
+
+	#[test]
+	fn test_claims_aud_string_or_array() {
+		let single: Claims = serde_json::from_value(serde_json::json!({
+			"sub": "s", "iss": "i", "aud": "client-id", "exp": 2, "iat": 1,
+		}))
+		.unwrap();
+		assert_eq!(single.aud, vec!["client-id"]);
+
+		let many: Claims = serde_json::from_value(serde_json::json!({
+			"sub": "s", "iss": "i", "aud": ["other", "client-id"], "exp": 2, "iat": 1,
+		}))
+		.unwrap();
+		assert_eq!(many.aud, vec!["other", "client-id"]);
+	}
+
+	#[test]
+	fn jwk_accepts_only_algorithms_of_its_key_type() {
+		let jwk = |value: serde_json::Value| serde_json::from_value::<Jwk>(value).unwrap();
+		let ec = jwk(serde_json::json!({
+			"kty": "EC", "crv": "P-256",
+			"x": "aCX8RuqkYNGSrcOQSrmtAJmXP3y0Irkf0XsrlI8rUiI",
+			"y": "uYZgaLtZFJHNP2puDP5WrtNY0Egwt8iLbll9jDzV5_M",
+		}));
+		assert!(ec.decoding_key(Algorithm::ES256).is_ok());
+		for alg in [
+			Algorithm::RS256,
+			Algorithm::HS256,
+			Algorithm::ES384,
+			Algorithm::EdDSA,
+		] {
+			let err = ec.decoding_key(alg).err().unwrap();
+			assert!(err.to_string().contains("does not match EC key"), "{}", err);
+		}
+
+		let ed = jwk(serde_json::json!({
+			"kty": "OKP", "crv": "Ed25519", "alg": "EdDSA",
+			"x": "rQFJ3zRcPoqABGY5-YLfYMzQKR8qGF2mWkVlb_v3YOU",
+		}));
+		assert!(ed.decoding_key(Algorithm::EdDSA).is_ok());
+		assert!(ed.decoding_key(Algorithm::ES256).is_err());
+
+		let rsa = jwk(serde_json::json!({"kty": "RSA", "alg": "RS256", "n": "AQAB", "e": "AQAB"}));
+		let err = rsa.decoding_key(Algorithm::PS256).err().unwrap();
+		assert!(err.to_string().contains("key algorithm RS256"), "{}", err);
+		assert!(rsa.decoding_key(Algorithm::HS256).is_err());
+
+		let x25519 = jwk(serde_json::json!({"kty": "OKP", "crv": "X25519", "x": "AA"}));
+		let err = x25519.decoding_key(Algorithm::EdDSA).err().unwrap();
+		assert!(
+			err.to_string()
+				.contains("unsupported key type: OKP (X25519)")
+		);
+	}
 }
@@ -0,0 +1,957 @@
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use prometheus::Histogram;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::RwLock;
+
+use crate::age_client::AgeRepo;
+use crate::sync::agent::ChangeLogEntry;
+use crate::sync::merge::{EntityVersion, MergeResolver, VersionVector};
+
+/// A change log entry together with its local sequence number.
+///
+/// Sequence numbers are assigned on append, start at 1 and are strictly
+/// increasing. They are local to this node: an entry imported from a peer
+/// receives a fresh sequence number here.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SequencedEntry {
+	pub seq: u64,
+	#[serde(flatten)]
+	pub entry: ChangeLogEntry,
+}
+
+#[derive(Default)]
+struct ChangeLogInner {
+	entries: Vec<SequencedEntry>,
+	ids: HashSet<String>,
+	/// Index of the newest entry per (label, key), used as the local version
+	/// when merging incoming entries.
+	latest: HashMap<(String, String), usize>,
+}
+
+/// Outcome of [`ChangeLog::compact`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CompactReport {
+	/// Entries in the log before compaction.
+	pub before: usize,
+	/// Entries left after compaction.
+	pub after: usize,
+	/// Bytes freed on disk (0 for an in-memory log).
+	pub reclaimed_bytes: u64,
+}
+
+/// Size and sequence range of a change log, see [`ChangeLog::stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ChangeLogStats {
+	pub entries: usize,
+	pub oldest_seq: Option<u64>,
+	pub newest_seq: Option<u64>,
+	/// Size of the backing file (0 for an in-memory log).
+	pub disk_bytes: u64,
+}
+
+impl ChangeLogInner {
+	fn next_seq(&self) -> u64 {
+		self.entries.last().map(|e| e.seq + 1).unwrap_or(1)
+	}
+
+	fn index(&mut self, sequenced: SequencedEntry) {
+		let ident = (sequenced.entry.label.clone(), sequenced.entry.key.clone());
+		let idx = self.entries.len();
+		let newer = match self.latest.get(&ident) {
+			Some(&current) => {
+				let (new, old) = (
+					version_of(&sequenced.entry),
+					version_of(&self.entries[current].entry),
+				);
+				// One origin's writes are ordered by its own counter; its
+				// timestamps only have second resolution.
+				if new.origin == old.origin {
+					new.version > old.version
+				} else {
+					new.is_newer_than(&old)
+				}
+			}
+			None => true,
+		};
+		if newer {
+			self.latest.insert(ident, idx);
+		}
+		self.ids.insert(sequenced.entry.id.clone());
+		self.entries.push(sequenced);
+	}
+}
+
+/// Sequenced, append-only change log.
+///
+/// Entries are de-duplicated by their `id`, which makes re-applying the same
+/// export a no-op. When opened with a path the log is persisted as NDJSON
+/// (one `SequencedEntry` per line) and reloaded on open.
+pub struct ChangeLog {
+	inner: RwLock<ChangeLogInner>,
+	path: Option<PathBuf>,
+}
+
+impl ChangeLog {
+	/// Create a change log that only lives in memory.
+	pub fn in_memory() -> Self {
+		Self {
+			inner: RwLock::new(ChangeLogInner::default()),
+			path: None,
+		}
+	}
+
+	/// Open (or create) a file-backed change log, replaying existing entries.
+	pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+		let path = path.as_ref().to_path_buf();
+		let mut inner = ChangeLogInner::default();
+
+		if path.exists() {
+			let file = File::open(&path)
+				.with_context(|| format!("failed to open change log {}", path.display()))?;
+			for (lineno, line) in BufReader::new(file).lines().enumerate() {
+				let line = line.context("failed to read change log")?;
+				if line.trim().is_empty() {
+					continue;
+				}
+				match serde_json::from_str::<SequencedEntry>(&line) {
+					Ok(entry) => inner.index(entry),
+					Err(e) => warn!(
+						"skipping corrupt change log line {} in {}: {}",
+						lineno + 1,
+						path.display(),
+						e
+					),
+				}
+			}
+		}
+
+		Ok(Self {
+			inner: RwLock::new(inner),
+			path: Some(path),
+		})
+	}
+
+	/// Append an entry, returning its sequence number. Returns `None` when an
+	/// entry with the same id is already present.
+	pub async fn append(&self, entry: ChangeLogEntry) -> Result<Option<u64>> {
+		let mut inner = self.inner.write().await;
+		if inner.ids.contains(&entry.id) {
+			return Ok(None);
+		}
+
+		let sequenced = SequencedEntry {
+			seq: inner.next_seq(),
+			entry,
+		};
+
+		if let Some(path) = &self.path {
+			let mut line =
+				serde_json::to_vec(&sequenced).context("failed to serialize change log entry")?;
+			line.push(b'\n');
+			let mut file = OpenOptions::new()
+				.create(true)
+				.append(true)
+				.open(path)
+				.with_context(|| format!("failed to open change log {}", path.display()))?;
+			file.write_all(&line)
+				.context("failed to append to change log")?;
+		}
+
+		let seq = sequenced.seq;
+		inner.index(sequenced);
+		Ok(Some(seq))
+	}
+
+	/// Return all entries with a sequence number greater than `since_seq`.
+	pub async fn since(&self, since_seq: u64) -> Vec<SequencedEntry> {
+		let inner = self.inner.read().await;
+		let start = inner.entries.partition_point(|e| e.seq <= since_seq);
+		inner.entries[start..].to_vec()
+	}
+
+	/// Return at most `limit` entries with a sequence number greater than
+	/// `since_seq`, and whether more follow them.
+	pub async fn page(&self, since_seq: u64, limit: usize) -> (Vec<SequencedEntry>, bool) {
+		let inner = self.inner.read().await;
+		let start = inner.entries.partition_point(|e| e.seq <= since_seq);
+		let end = start.saturating_add(limit).min(inner.entries.len());
+		(
+			inner.entries[start..end].to_vec(),
+			end < inner.entries.len(),
+		)
+	}
+
+	/// The sequence number to page after to start at the first entry
+	/// stamped at or after `timestamp` (the last one when there is none).
+	pub async fn seq_before_timestamp(&self, timestamp: u64) -> u64 {
+		let inner = self.inner.read().await;
+		match inner
+			.entries
+			.iter()
+			.find(|e| e.entry.timestamp >= timestamp)
+		{
+			Some(first) => first.seq - 1,
+			None => inner.entries.last().map(|e| e.seq).unwrap_or(0),
+		}
+	}
+
+	/// Whether an entry with the given id has already been recorded.
+	pub async fn contains(&self, id: &str) -> bool {
+		self.inner.read().await.ids.contains(id)
+	}
+
+	/// The newest recorded entry for a (label, key) pair, if any.
+	pub async fn latest_for(&self, label: &str, key: &str) -> Option<ChangeLogEntry> {
+		let inner = self.inner.read().await;
+		inner
+			.latest
+			.get(&(label.to_string(), key.to_string()))
+			.map(|&idx| inner.entries[idx].entry.clone())
+	}
+
+	/// Sequence number of the most recent entry (0 when empty).
+	pub async fn last_seq(&self) -> u64 {
+		self.inner
+			.read()
+			.await
+			.entries
+			.last()
+			.map(|e| e.seq)
+			.unwrap_or(0)
+	}
+
+	/// Number of entries in the log.
+	pub async fn len(&self) -> usize {
+		self.inner.read().await.entries.len()
+	}
+
+	/// Whether the log holds no entries.
+	pub async fn is_empty(&self) -> bool {
+		self.len().await == 0
+	}
+
+	/// Entry count, sequence range and on-disk size of the log.
+	pub async fn stats(&self) -> Result<ChangeLogStats> {
+		let inner = self.inner.read().await;
+		Ok(ChangeLogStats {
+			entries: inner.entries.len(),
+			oldest_seq: inner.entries.first().map(|e| e.seq),
+			newest_seq: inner.entries.last().map(|e| e.seq),
+			disk_bytes: self.disk_bytes()?,
+		})
+	}
+
+	/// Drop superseded entries with a sequence number up to `watermark`.
+	///
+	/// Among those entries only the newest per (label, key) is kept, along
+	/// with every tombstone so deletes still reach peers that sync later.
+	/// Entries above the watermark are untouched, and the most recent entry
+	/// is always kept so sequence numbers are never reused. Sequence numbers
+	/// of surviving entries do not change; a file-backed log is rewritten
+	/// atomically.
+	pub async fn compact(&self, watermark: u64) -> Result<CompactReport> {
+		let mut inner = self.inner.write().await;
+		let before = inner.entries.len();
+		let before_bytes = self.disk_bytes()?;
+
+		let last = inner.entries.len().checked_sub(1);
+		let latest: HashSet<usize> = inner.latest.values().copied().collect();
+		let kept: Vec<SequencedEntry> = inner
+			.entries
+			.iter()
+			.enumerate()
+			.filter(|(idx, e)| {
+				e.seq > watermark || e.entry.tombstone || latest.contains(idx) || Some(*idx) == last
+			})
+			.map(|(_, e)| e.clone())
+			.collect();
+
+		if kept.len() == before {
+			return Ok(CompactReport {
+				before,
+				after: before,
+				reclaimed_bytes: 0,
+			});
+		}
+
+		if let Some(path) = &self.path {
+			let tmp = path.with_extension("compact");
+			let mut buf = Vec::new();
+			for entry in &kept {
+				buf.extend(encode_ndjson_line(entry)?);
+			}
+			std::fs::write(&tmp, &buf)
+				.with_context(|| format!("failed to write {}", tmp.display()))?;
+			std::fs::rename(&tmp, path)
+				.with_context(|| format!("failed to replace change log {}", path.display()))?;
+		}
+
+		// Remember the ids of dropped entries so re-imports stay no-ops for
+		// the lifetime of this process.
+		let ids = std::mem::take(&mut inner.ids);
+		let mut rebuilt = ChangeLogInner::default();
+		for entry in kept {
+			rebuilt.index(entry);
+		}
+		rebuilt.ids.extend(ids);
+		*inner = rebuilt;
+
+		let after = inner.entries.len();
+		debug!("compacted change log from {} to {} entries", before, after);
+		Ok(CompactReport {
+			before,
+			after,
+			reclaimed_bytes: before_bytes.saturating_sub(self.disk_bytes()?),
+		})
+	}
+
+	fn disk_bytes(&self) -> Result<u64> {
+		match &self.path {
+			Some(path) if path.exists() => Ok(std::fs::metadata(path)
+				.with_context(|| format!("failed to stat change log {}", path.display()))?
+				.len()),
+			_ => Ok(0),
+		}
+	}
+}
+
+/// Records local writes in a change log so they become eligible for sync.
+///
+/// Each recorded write advances this origin's component of the node's
+/// version vector, starting from the newest logged entry for the same
+/// (label, key).
+#[derive(Clone)]
+pub struct ChangeRecorder {
+	log: Arc<ChangeLog>,
+	origin: String,
+	counter: Arc<AtomicU64>,
+}
+
+impl ChangeRecorder {
+	/// Record into `log` on behalf of the node `origin`.
+	pub fn new(log: Arc<ChangeLog>, origin: impl Into<String>) -> Self {
+		Self {
+			log,
+			origin: origin.into(),
+			counter: Arc::new(AtomicU64::new(0)),
+		}
+	}
+
+	/// Append an entry for a write of `props` to (`label`, `key`),
+	/// returning its sequence number.
+	pub async fn record(&self, label: &str, key: &str, props: &serde_json::Value) -> Result<u64> {
+		let mut version_vector = self
+			.log
+			.latest_for(label, key)
+			.await
+			.map(|e| e.version_vector)
+			.unwrap_or_default();
+		*version_vector.entry(self.origin.clone()).or_insert(0) += 1;
+
+		let now = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.unwrap_or_default();
+		// Unique across restarts even when an in-memory log starts empty.
+		let id = format!(
+			"{}-{}-{}",
+			self.origin,
+			now.as_nanos(),
+			self.counter.fetch_add(1, Ordering::Relaxed)
+		);
+		let entry = ChangeLogEntry {
+			id,
+			timestamp: now.as_secs(),
+			label: label.to_string(),
+			key: key.to_string(),
+			props: props.clone(),
+			origin: self.origin.clone(),
+			version_vector,
+			tombstone: false,
+		};
+		self.log
+			.append(entry)
+			.await?
+			.context("change log entry id collided")
+	}
+}
+
+impl fmt::Debug for ChangeRecorder {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("ChangeRecorder")
+			.field("origin", &self.origin)
+			.finish_non_exhaustive()
+	}
+}
+
+impl PartialEq for ChangeRecorder {
+	fn eq(&self, other: &Self) -> bool {
+		Arc::ptr_eq(&self.log, &other.log) && self.origin == other.origin
+	}
+}
+
+/// Outcome of importing a batch of change log entries.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ImportReport {
+	/// Entries recorded in the local log (and merged into the graph when
+	/// they changed the resolved state).
+	pub applied: usize,
+	/// Entries skipped because they were already present.
+	pub skipped: usize,
+	/// Lines that could not be parsed.
+	pub rejected: usize,
+}
+
+/// A change log entry an import refuses: it lacks an id, label or key, or
+/// cannot be merged with the local state of its node.
+#[derive(Debug, thiserror::Error)]
+#[error("invalid change log entry {id:?}: {reason}")]
+pub struct InvalidEntry {
+	pub id: String,
+	pub reason: String,
+}
+
+/// Check that `entry` names the entry and node it applies to.
+pub fn validate_entry(entry: &ChangeLogEntry) -> std::result::Result<(), InvalidEntry> {
+	let missing = if entry.id.is_empty() {
+		"id"
+	} else if entry.label.is_empty() {
+		"label"
+	} else if entry.key.is_empty() {
+		"key"
+	} else {
+		return Ok(());
+	};
+	Err(InvalidEntry {
+		id: entry.id.clone(),
+		reason: format!("empty {}", missing),
+	})
+}
+
+/// Encode a single sequenced entry as one NDJSON line (newline-terminated).
+pub fn encode_ndjson_line(entry: &SequencedEntry) -> Result<Vec<u8>> {
+	let mut line = serde_json::to_vec(entry).context("failed to serialize change log entry")?;
+	line.push(b'\n');
+	Ok(line)
+}
+
+/// Parse an NDJSON change log export. Blank lines are ignored; lines that
+/// do not parse are counted and skipped.
+pub fn parse_ndjson(body: &[u8]) -> (Vec<ChangeLogEntry>, usize) {
+	let mut entries = Vec::new();
+	let mut rejected = 0;
+	for line in body.split(|b| *b == b'\n') {
+		if line.iter().all(|b| b.is_ascii_whitespace()) {
+			continue;
+		}
+		match serde_json::from_slice::<ChangeLogEntry>(line) {
+			Ok(entry) => entries.push(entry),
+			Err(e) => {
+				debug!("rejecting change log line: {}", e);
+				rejected += 1;
+			}
+		}
+	}
+	(entries, rejected)
+}
+
+fn version_of(entry: &ChangeLogEntry) -> VersionVector {
+	let mut version = VersionVector::new(entry.origin.clone(), entry.timestamp);
+	version.version = entry
+		.version_vector
+		.get(&entry.origin)
+		.copied()
+		.unwrap_or(0);
+	version
+}
+
+fn entity_of(entry: &ChangeLogEntry) -> EntityVersion {
+	EntityVersion::new(
+		entry.label.clone(),
+		entry.key.clone(),
+		entry.props.clone(),
+		version_of(entry),
+	)
+	.with_tombstone(entry.tombstone)
+}
+
+/// Default number of graph writes grouped into one `merge_batch` when
+/// applying change log entries.
+pub const DEFAULT_APPLY_BATCH_SIZE: usize = 500;
+
+/// Default age at which a partial batch of graph writes is flushed.
+pub const DEFAULT_APPLY_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How [`import_entries_batched`] groups the graph writes of applied
+/// entries.
+#[derive(Debug, Clone)]
+pub struct ApplyBatching {
+	max_entries: usize,
+	flush_interval: Duration,
+	latency_ms: Option<Histogram>,
+}
+
+impl Default for ApplyBatching {
+	fn default() -> Self {
+		Self::new(DEFAULT_APPLY_BATCH_SIZE, DEFAULT_APPLY_FLUSH_INTERVAL)
+	}
+}
+
+impl ApplyBatching {
+	/// Write at most `max_entries` entries per batch, flushing a partial
+	/// batch once its oldest write has waited `flush_interval`.
+	pub fn new(max_entries: usize, flush_interval: Duration) -> Self {
+		Self {
+			max_entries: max_entries.max(1),
+			flush_interval,
+			latency_ms: None,
+		}
+	}
+
+	/// Observe each batch write's latency in `histogram`.
+	pub fn with_latency(mut self, histogram: Histogram) -> Self {
+		self.latency_ms = Some(histogram);
+		self
+	}
+}
+
+/// Entries resolved but not yet written, in arrival order.
+#[derive(Default)]
+struct PendingApply {
+	writes: Vec<(String, String, Value)>,
+	entries: Vec<ChangeLogEntry>,
+	keys: HashSet<(String, String)>,
+	ids: HashSet<String>,
+	since: Option<Instant>,
+}
+
+impl PendingApply {
+	/// Whether `entry` must wait for the pending batch: resolving it
+	/// against the log is only correct once earlier entries for the same
+	/// (label, key), or with the same id, have been appended.
+	fn holds(&self, entry: &ChangeLogEntry) -> bool {
+		self.ids.contains(&entry.id)
+			|| self
+				.keys
+				.contains(&(entry.label.clone(), entry.key.clone()))
+	}
+
+	fn is_due(&self, batching: &ApplyBatching) -> bool {
+		self.entries.len() >= batching.max_entries
+			|| self
+				.since
+				.is_some_and(|since| since.elapsed() >= batching.flush_interval)
+	}
+
+	/// Write the batch, then append its entries to `log`.
+	async fn flush(
+		&mut self,
+		repo: &dyn AgeRepo,
+		log: &ChangeLog,
+		batching: &ApplyBatching,
+		report: &mut ImportReport,
+	) -> Result<()> {
+		if !self.writes.is_empty() {
+			let started = Instant::now();
+			repo.merge_batch(&self.writes).await.with_context(|| {
+				format!(
+					"failed to apply change log entries {}..{}",
+					self.entries.first().map_or("", |e| e.id.as_str()),
+					self.entries.last().map_or("", |e| e.id.as_str()),
+				)
+			})?;
+			if let Some(histogram) = &batching.latency_ms {
+				histogram.observe(started.elapsed().as_secs_f64() * 1000.0);
+			}
+		}
+		for entry in self.entries.drain(..) {
+			if log.append(entry).await?.is_some() {
+				report.applied += 1;
+			} else {
+				report.skipped += 1;
+			}
+		}
+		*self = Self::default();
+		Ok(())
+	}
+}
+
+/// Apply change log entries to the graph through the merge resolver.
+///
+/// Each entry is resolved against the newest local entry for the same
+/// (label, key); the graph is only written when the resolved properties
+/// differ from what is already stored. Entries whose id is already in the
+/// local log are skipped, so importing the same export twice is a no-op.
+/// Tombstones are recorded in the log but not applied to the graph.
+/// Writes are batched with the default [`ApplyBatching`].
+pub async fn import_entries(
+	repo: &dyn AgeRepo,
+	log: &ChangeLog,
+	resolver: &MergeResolver,
+	entries: impl IntoIterator<Item = ChangeLogEntry>,
+) -> Result<ImportReport> {
+	import_entries_batched(repo, log, resolver, entries, &ApplyBatching::default()).await
+}
+
+/// [`import_entries`], grouping graph writes into `merge_batch` calls as
+/// `batching` allows.
+///
+/// A batch holds at most one entry per (label, key), so entries for a key
+/// are written and logged in the order they arrive. Entries are appended
+/// to the log only after their batch is written; when a write fails, the
+/// entries of that batch are left out of the log and a later import
+/// applies them again.
+pub async fn import_entries_batched(
+	repo: &dyn AgeRepo,
+	log: &ChangeLog,
+	resolver: &MergeResolver,
+	entries: impl IntoIterator<Item = ChangeLogEntry>,
+	batching: &ApplyBatching,
+) -> Result<ImportReport> {
+	let mut report = ImportReport::default();
+	let mut pending = PendingApply::default();
+
+	for entry in entries {
+		if pending.holds(&entry) {
+			pending.flush(repo, log, batching, &mut report).await?;
+		}
+		if log.contains(&entry.id).await {
+			report.skipped += 1;
+			continue;
+		}
+
+		let remote = entity_of(&entry);
+		let local = log.latest_for(&entry.label, &entry.key).await;
+		let (resolved, changed) = match &local {
+			Some(local_entry) => {
+				let local_version = entity_of(local_entry);
+				let resolved =
+					resolver
+						.merge(&local_version, &remote)
+						.map_err(|e| InvalidEntry {
+							id: entry.id.clone(),
+							reason: format!("{:#}", e),
+						})?;
+				let changed = resolved.props != local_version.props
+					|| resolved.tombstone != local_version.tombstone;
+				(resolved, changed)
+			}
+			None => (remote, true),
+		};
+
+		if changed && !resolved.tombstone {
+			pending
+				.writes
+				.push((resolved.entity_type, resolved.key, resolved.props));
+		}
+		pending
+			.keys
+			.insert((entry.label.clone(), entry.key.clone()));
+		pending.ids.insert(entry.id.clone());
+		pending.since.get_or_insert_with(Instant::now);
+		pending.entries.push(entry);
+
+		if pending.is_due(batching) {
+			pending.flush(repo, log, batching, &mut report).await?;
+		}
+	}
+	pending.flush(repo, log, batching, &mut report).await?;
+
+	Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::ingest::test_utils::RecordingRepo;
+	use crate::sync::merge::MergeConfig;
+	use serde_json::{Value, json};
+
+	fn entry(id: &str, ts: u64, key: &str, props: Value) -> ChangeLogEntry {
+		let mut version_vector = HashMap::new();
+		version_vector.insert("node-a".to_string(), ts);
+		ChangeLogEntry {
+			id: id.to_string(),
+			timestamp: ts,
+			label: "FieldValue".to_string(),
+			key: key.to_string(),
+			props,
+			origin: "node-a".to_string(),
+			version_vector,
+			tombstone: false,
+		}
+	}
+
+	fn resolver() -> MergeResolver {
+		MergeResolver::new(MergeConfig::new())
+	}
+
+	#[tokio::test]
+	async fn since_returns_entries_after_sequence() {
+		let log = ChangeLog::in_memory();
+		for i in 1..=3 {
+			log.append(entry(&format!("e{}", i), i, "k", json!({"i": i})))
+				.await
+				.unwrap();
+		}
+
+		let tail = log.since(1).await;
+		assert_eq!(tail.iter().map(|e| e.seq).collect::<Vec<_>>(), vec![2, 3]);
+		assert!(log.since(3).await.is_empty());
+		assert_eq!(log.since(0).await.len(), 3);
+	}
+
+	#[tokio::test]
+	async fn append_ignores_duplicate_ids() {
+		let log = ChangeLog::in_memory();
+		assert_eq!(
+			log.append(entry("e1", 1, "k", json!({}))).await.unwrap(),
+			Some(1)
+		);
+		assert_eq!(
+			log.append(entry("e1", 1, "k", json!({}))).await.unwrap(),
+			None
+		);
+		assert_eq!(log.len().await, 1);
+	}
+
+	#[tokio::test]
+	async fn file_backed_log_reloads_entries() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("changelog.ndjson");
+
+		let log = ChangeLog::open(&path).unwrap();
+		log.append(entry("e1", 1, "a", json!({"v": 1})))
+			.await
+			.unwrap();
+		log.append(entry("e2", 2, "b", json!({"v": 2})))
+			.await
+			.unwrap();
+		drop(log);
+
+		let reopened = ChangeLog::open(&path).unwrap();
+		assert_eq!(reopened.last_seq().await, 2);
+		assert!(reopened.contains("e2").await);
+		assert_eq!(
+			reopened
+				.append(entry("e3", 3, "c", json!({})))
+				.await
+				.unwrap(),
+			Some(3)
+		);
+	}
+
+	#[tokio::test]
+	async fn compact_drops_superseded_entries_and_keeps_tombstones() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("changelog.ndjson");
+		let log = ChangeLog::open(&path).unwrap();
+		for i in 1..=4 {
+			log.append(entry(&format!("a{}", i), i, "a", json!({"v": i})))
+				.await
+				.unwrap();
+		}
+		let mut deleted = entry("b1", 5, "b", json!({}));
+		deleted.tombstone = true;
+		log.append(deleted).await.unwrap();
+		log.append(entry("b2", 6, "b", json!({"v": 6})))
+			.await
+			.unwrap();
+		log.append(entry("a5", 7, "a", json!({"v": 7})))
+			.await
+			.unwrap();
+
+		let before = log.stats().await.unwrap();
+		assert_eq!(before.entries, 7);
+		assert_eq!((before.oldest_seq, before.newest_seq), (Some(1), Some(7)));
+
+		let report = log.compact(6).await.unwrap();
+		// a1..a4 are superseded by a5; the tombstone, b2 and a5 stay.
+		assert_eq!((report.before, report.after), (7, 3));
+		assert!(report.reclaimed_bytes > 0);
+
+		let after = log.stats().await.unwrap();
+		assert_eq!(after.entries, 3);
+		assert_eq!((after.oldest_seq, after.newest_seq), (Some(5), Some(7)));
+		assert_eq!(after.disk_bytes, before.disk_bytes - report.reclaimed_bytes);
+		assert_eq!(after.disk_bytes, std::fs::metadata(&path).unwrap().len());
+		assert_eq!(log.latest_for("FieldValue", "a").await.unwrap().id, "a5");
+		assert!(log.contains("a1").await);
+
+		let reopened = ChangeLog::open(&path).unwrap();
+		let seqs: Vec<u64> = reopened.since(0).await.iter().map(|e| e.seq).collect();
+		assert_eq!(seqs, vec![5, 6, 7]);
+		assert_eq!(
+			reopened
+				.append(entry("c1", 8, "c", json!({})))
+				.await
+				.unwrap(),
+			Some(8)
+		);
+	}
+
+	#[tokio::test]
+	async fn compact_keeps_last_entry_so_sequences_are_not_reused() {
+		let log = ChangeLog::in_memory();
+		log.append(entry("new", 200, "k", json!({}))).await.unwrap();
+		log.append(entry("old", 100, "k", json!({}))).await.unwrap();
+
+		let report = log.compact(2).await.unwrap();
+		assert_eq!(
+			(report.before, report.after, report.reclaimed_bytes),
+			(2, 2, 0)
+		);
+		assert_eq!(log.stats().await.unwrap().disk_bytes, 0);
+	}
+
+	#[tokio::test]
+	async fn export_then_import_reproduces_graph_state() {
+		// Source node: apply local changes through the import path so the
+		// graph and the log agree.
+		let source_repo = RecordingRepo::default();
+		let source_log = ChangeLog::in_memory();
+		let local_changes = vec![
+			entry(
+				"e1",
+				100,
+				"alice@example.com",
+				json!({"field_type": "email", "n": 1}),
+			),
+			entry("e2", 101, "10.0.0.1", json!({"field_type": "ip"})),
+			entry(
+				"e3",
+				102,
+				"alice@example.com",
+				json!({"field_type": "email", "n": 2}),
+			),
+		];
+		import_entries(&source_repo, &source_log, &resolver(), local_changes)
+			.await
+			.unwrap();
+
+		// Export as NDJSON.
+		let mut export = Vec::new();
+		for e in source_log.since(0).await {
+			export.extend(encode_ndjson_line(&e).unwrap());
+		}
+
+		// Import into a fresh store.
+		let target_repo = RecordingRepo::default();
+		let target_log = ChangeLog::in_memory();
+		let (entries, rejected) = parse_ndjson(&export);
+		assert_eq!(rejected, 0);
+		let report = import_entries(&target_repo, &target_log, &resolver(), entries)
+			.await
+			.unwrap();
+
+		assert_eq!(report.applied, 3);
+		assert_eq!(target_repo.nodes(), source_repo.nodes());
+		assert_eq!(
+			target_repo.nodes()[&("FieldValue".to_string(), "alice@example.com".to_string())]["n"],
+			2
+		);
+	}
+
+	#[tokio::test]
+	async fn reimporting_the_same_export_is_idempotent() {
+		let repo = RecordingRepo::default();
+		let log = ChangeLog::in_memory();
+		let export = b"{\"id\":\"e1\",\"timestamp\":1,\"label\":\"FieldValue\",\"key\":\"k\",\"props\":{\"v\":1},\"origin\":\"node-a\",\"version_vector\":{\"node-a\":1},\"tombstone\":false}\n\nnot json\n";
+
+		let (entries, rejected) = parse_ndjson(export);
+		assert_eq!(rejected, 1);
+		let first = import_entries(&repo, &log, &resolver(), entries.clone())
+			.await
+			.unwrap();
+		let second = import_entries(&repo, &log, &resolver(), entries)
+			.await
+			.unwrap();
+
+		assert_eq!(first.applied, 1);
+		assert_eq!(second.applied, 0);
+		assert_eq!(second.skipped, 1);
+		assert_eq!(repo.merged.lock().unwrap().len(), 1);
+		assert_eq!(log.len().await, 1);
+	}
+
+	#[tokio::test]
+	async fn a_key_repeated_within_a_batch_applies_in_order() {
+		let repo = RecordingRepo::default();
+		let log = ChangeLog::in_memory();
+		let mut deleted = entry("a3", 300, "a", json!({}));
+		deleted.tombstone = true;
+		let entries = vec![
+			entry("a1", 100, "a", json!({"v": 1})),
+			entry("b1", 100, "b", json!({"v": 1})),
+			entry("a2", 200, "a", json!({"v": 2})),
+			deleted,
+			entry("a1", 100, "a", json!({"v": 1})),
+		];
+		let batching = ApplyBatching::new(100, Duration::from_secs(60));
+		let report = import_entries_batched(&repo, &log, &resolver(), entries, &batching)
+			.await
+			.unwrap();
+
+		assert_eq!((report.applied, report.skipped), (4, 1));
+		assert_eq!(
+			repo.nodes()[&("FieldValue".to_string(), "a".to_string())]["v"],
+			2
+		);
+		let ids: Vec<String> = log.since(0).await.into_iter().map(|e| e.entry.id).collect();
+		assert_eq!(ids, vec!["a1", "b1", "a2", "a3"]);
+		assert!(log.latest_for("FieldValue", "a").await.unwrap().tombstone);
+	}
+
+	#[tokio::test]
+	async fn older_remote_entry_does_not_overwrite_newer_local_state() {
+		let repo = RecordingRepo::default();
+		let log = ChangeLog::in_memory();
+		import_entries(
+			&repo,
+			&log,
+			&resolver(),
+			vec![entry("new", 200, "k", json!({"v": "new"}))],
+		)
+		.await
+		.unwrap();
+		import_entries(
+			&repo,
+			&log,
+			&resolver(),
+			vec![entry("old", 100, "k", json!({"v": "old"}))],
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(
+			repo.nodes()[&("FieldValue".to_string(), "k".to_string())]["v"],
+			"new"
+		);
+		assert_eq!(log.latest_for("FieldValue", "k").await.unwrap().id, "new");
+	}
+
+	#[test]
+	fn entries_without_an_id_label_or_key_are_invalid() {
+		let valid = entry("e1", 1, "k", json!({}));
+		assert!(validate_entry(&valid).is_ok());
+		let keyless = ChangeLogEntry {
+			key: String::new(),
+			..valid.clone()
+		};
+		assert_eq!(validate_entry(&keyless).unwrap_err().reason, "empty key");
+		let anonymous = ChangeLogEntry {
+			id: String::new(),
+			..valid
+		};
+		assert_eq!(validate_entry(&anonymous).unwrap_err().reason, "empty id");
+	}
+}
@@ -0,0 +1,311 @@
+use axum::{
+	Json,
+	body::{Body, Bytes},
+	extract::{Query, State},
+	http::{HeaderMap, StatusCode, header::CONTENT_TYPE},
+	response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+
+use crate::audit::{AuditAction, AuditEvent};
+use crate::state::AppState;
+use crate::sync::ChangeLogEntry;
+use crate::sync::changelog::{
+	InvalidEntry, encode_ndjson_line, import_entries_batched, parse_ndjson, validate_entry,
+};
+use crate::sync::merge::{MergeConfig, MergeResolver};
+
+/// Query parameters for `GET /sync/changelog`.
+#[derive(Debug, Default, Deserialize)]
+pub struct ExportParams {
+	/// Only entries with a sequence number greater than this are returned.
+	#[serde(default)]
+	pub since_seq: u64,
+}
+
+/// Export the local change log as NDJSON (one sequenced entry per line).
+///
+/// Read-only: exporting never modifies the log. Requires a valid bearer
+/// token.
+pub async fn export_changelog(
+	State(state): State<AppState>,
+	headers: HeaderMap,
+	Query(params): Query<ExportParams>,
+) -> Response {
+	if let Err(resp) = crate::auth::authenticate(&state, &headers).await {
+		return resp;
+	}
+
+	let entries = state.changelog.since(params.since_seq).await;
+	let lines = entries
+		.into_iter()
+		.filter_map(|entry| match encode_ndjson_line(&entry) {
+			Ok(line) => Some(Ok::<_, std::convert::Infallible>(Bytes::from(line))),
+			Err(e) => {
+				log::error!("skipping change log entry {} in export: {}", entry.seq, e);
+				None
+			}
+		});
+
+	(
+		[(CONTENT_TYPE, "application/x-ndjson")],
+		Body::from_stream(futures_util::stream::iter(lines)),
+	)
+		.into_response()
+}
+
+/// Body of the `400` returned for an import that does not parse or
+/// validate; the details are only logged.
+const INVALID_IMPORT: &str = "invalid change log import";
+
+/// Import an NDJSON change log export, applying entries through the merge
+/// path. Entries already present locally are skipped, so re-posting the same
+/// file is safe. Requires a valid bearer token.
+///
+/// A body with no parseable line, or with an entry that fails validation,
+/// is refused with `400` before anything is applied.
+pub async fn import_changelog(
+	State(state): State<AppState>,
+	headers: HeaderMap,
+	body: Bytes,
+) -> Response {
+	let claims = match crate::auth::authenticate(&state, &headers).await {
+		Ok(claims) => claims,
+		Err(resp) => return resp,
+	};
+
+	let (mut entries, rejected) = parse_ndjson(&body);
+	if entries.is_empty() && rejected > 0 {
+		log::warn!(
+			"change log import rejected: none of {} line(s) parsed",
+			rejected
+		);
+		return (StatusCode::BAD_REQUEST, INVALID_IMPORT).into_response();
+	}
+	if let Some(invalid) = entries.iter().find_map(|e| validate_entry(e).err()) {
+		log::warn!("change log import rejected: {}", invalid);
+		return (StatusCode::BAD_REQUEST, INVALID_IMPORT).into_response();
+	}
+	quarantine_labels(&state, &mut entries);
+	let resolver = MergeResolver::new(MergeConfig::default());
+	let batching = state
+		.settings
+		.apply_batching()
+		.with_latency(state.metrics.sync_apply_batch_latency_ms.clone());
+	match import_entries_batched(
+		state.repo.as_ref(),
+		&state.changelog,
+		&resolver,
+		entries,
+		&batching,
+	)
+	.await
+	{
+		Ok(mut report) => {
+			report.rejected = rejected;
+			state.audit.emit(
+				AuditEvent::new(AuditAction::Import, crate::audit::request_id(&headers))
+					.with_subject(claims.sub)
+					.with_endpoint("/sync/changelog")
+					.with_records(report.applied as u64),
+			);
+			(StatusCode::OK, Json(report)).into_response()
+		}
+		Err(e) => import_error(&e),
+	}
+}
+
+/// Log a failed import and answer without its details: `400` when an
+/// entry could not be merged, `500` otherwise.
+fn import_error(err: &anyhow::Error) -> Response {
+	if err.chain().any(|cause| cause.is::<InvalidEntry>()) {
+		log::warn!("change log import rejected: {:#}", err);
+		(StatusCode::BAD_REQUEST, INVALID_IMPORT).into_response()
+	} else {
+		log::error!("change log import failed: {:#}", err);
+		(StatusCode::INTERNAL_SERVER_ERROR, "import failed").into_response()
+	}
+}
+
+/// Map entry labels outside the state's allowlist to the quarantine label.
+fn quarantine_labels(state: &AppState, entries: &mut [ChangeLogEntry]) {
+	for entry in entries {
+		entry.label = state
+			.label_allowlist
+			.load()
+			.node_label(&entry.label, &state.metrics)
+			.to_string();
+	}
+}
+
+/// Query parameters for `POST /admin/changelog/compact`.
+#[derive(Debug, Default, Deserialize)]
+pub struct CompactParams {
+	/// Only entries with a sequence number up to this are compacted.
+	/// Defaults to the newest entry, compacting the whole log.
+	pub watermark: Option<u64>,
+}
+
+/// Compact the change log up to a watermark, keeping the newest entry per
+/// (label, key) and every tombstone. Responds with the entry counts before
+/// and after and the bytes reclaimed on disk. Requires a valid bearer token.
+pub async fn compact_changelog(
+	State(state): State<AppState>,
+	headers: HeaderMap,
+	Query(params): Query<CompactParams>,
+) -> Response {
+	let claims = match crate::auth::authenticate(&state, &headers).await {
+		Ok(claims) => claims,
+		Err(resp) => return resp,
+	};
+
+	let watermark = match params.watermark {
+		Some(w) => w,
+		None => state.changelog.last_seq().await,
+	};
+	match state.changelog.compact(watermark).await {
+		Ok(report) => {
+			state.audit.emit(
+				AuditEvent::new(AuditAction::Compact, crate::audit::request_id(&headers))
+					.with_subject(claims.sub)
+					.with_endpoint("/admin/changelog/compact")
+					.with_records(report.before.saturating_sub(report.after) as u64),
+			);
+			(StatusCode::OK, Json(report)).into_response()
+		}
+		Err(e) => {
+			log::error!("change log compaction failed: {:#}", e);
+			(StatusCode::INTERNAL_SERVER_ERROR, "compaction failed").into_response()
+		}
+	}
+}
+
+/// Report the change log's entry count, sequence range and disk size.
+/// Requires a valid bearer token.
+pub async fn changelog_stats(State(state): State<AppState>, headers: HeaderMap) -> Response {
+	if let Err(resp) = crate::auth::authenticate(&state, &headers).await {
+		return resp;
+	}
+
+	match state.changelog.stats().await {
+		Ok(stats) => (StatusCode::OK, Json(stats)).into_response(),
+		Err(e) => {
+			log::error!("failed to read change log stats: {:#}", e);
+			(StatusCode::INTERNAL_SERVER_ERROR, "stats failed").into_response()
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use axum::http::header::AUTHORIZATION;
+
+	#[tokio::test]
+	async fn export_requires_authentication() {
+		let state = crate::ingest::test_utils::create_test_app_state();
+		let resp = export_changelog(
+			State(state),
+			HeaderMap::new(),
+			Query(ExportParams::default()),
+		)
+		.await;
+		assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+	}
+
+	#[tokio::test]
+	async fn import_requires_authentication_and_leaves_log_untouched() {
+		let state = crate::ingest::test_utils::create_test_app_state();
+		let mut headers = HeaderMap::new();
+		headers.insert(AUTHORIZATION, "Bearer not-a-jwt".parse().unwrap());
+		let body = Bytes::from_static(
+			b"{\"id\":\"e1\",\"timestamp\":1,\"label\":\"FieldValue\",\"key\":\"k\",\"props\":{},\"origin\":\"n\",\"version_vector\":{},\"tombstone\":false}\n",
+		);
+
+		let resp = import_changelog(State(state.clone()), headers, body).await;
+		assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+		assert!(state.changelog.is_empty().await);
+	}
+
+	#[tokio::test]
+	async fn admin_changelog_endpoints_require_authentication() {
+		let state = crate::ingest::test_utils::create_test_app_state();
+		state
+			.changelog
+			.append(crate::sync::ChangeLogEntry {
+				id: "e1".to_string(),
+				timestamp: 1,
+				label: "FieldValue".to_string(),
+				key: "k".to_string(),
+				props: serde_json::json!({}),
+				origin: "n".to_string(),
+				version_vector: Default::default(),
+				tombstone: false,
+			})
+			.await
+			.unwrap();
+
+		let resp = compact_changelog(
+			State(state.clone()),
+			HeaderMap::new(),
+			Query(CompactParams::default()),
+		)
+		.await;
+		assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+		assert_eq!(state.changelog.len().await, 1);
+
+		let resp = changelog_stats(State(state), HeaderMap::new()).await;
+		assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+	}
+
+	#[tokio::test]
+	async fn import_errors_hide_their_details() {
+		async fn reply(err: anyhow::Error) -> (StatusCode, Bytes) {
+			let resp = import_error(&err);
+			let status = resp.status();
+			(
+				status,
+				axum::body::to_bytes(resp.into_body(), usize::MAX)
+					.await
+					.unwrap(),
+			)
+		}
+
+		let invalid = anyhow::Error::new(InvalidEntry {
+			id: "e1".to_string(),
+			reason: "cannot merge entities with different keys: a vs b".to_string(),
+		})
+		.context("failed to import");
+		assert_eq!(
+			reply(invalid).await,
+			(
+				StatusCode::BAD_REQUEST,
+				Bytes::from_static(INVALID_IMPORT.as_bytes())
+			)
+		);
+
+		let failed = anyhow::anyhow!("connection refused to db.internal:5432");
+		assert_eq!(
+			reply(failed).await,
+			(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Bytes::from_static(b"import failed")
+			)
+		);
+	}
+
+	#[test]
+	fn imported_labels_outside_the_allowlist_are_quarantined() {
+		use crate::persist::labels::{LabelAllowlist, QUARANTINE_LABEL};
+
+		let state = crate::ingest::test_utils::create_test_app_state()
+			.with_label_allowlist(LabelAllowlist::new(["FieldValue"], Vec::<String>::new()));
+		let body = b"{\"id\":\"e1\",\"timestamp\":1,\"label\":\"FieldValue\",\"key\":\"k\",\"props\":{},\"origin\":\"n\",\"version_vector\":{},\"tombstone\":false}\n{\"id\":\"e2\",\"timestamp\":1,\"label\":\"Evil\",\"key\":\"k\",\"props\":{},\"origin\":\"n\",\"version_vector\":{},\"tombstone\":false}\n";
+		let (mut entries, _) = parse_ndjson(body);
+
+		quarantine_labels(&state, &mut entries);
+		assert_eq!(entries[0].label, "FieldValue");
+		assert_eq!(entries[1].label, QUARANTINE_LABEL);
+		assert_eq!(state.metrics.ingest_label_quarantined_total.get(), 1);
+	}
+}
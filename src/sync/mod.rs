@@ -1,5 +1,20 @@
 pub mod agent;
 pub mod auth;
+pub mod changelog;
+pub mod http;
+pub mod merge;
+pub mod server;
 
-pub use agent::{global_sync_metrics, ChangeLogEntry, PeerConfig, SyncAgent, SyncMetrics, SyncMessage};
+pub use agent::{
+	global_sync_metrics, ChangeLogEntry, ClientCertConfig, PeerConfig, SyncAgent, SyncMetrics,
+	SyncMessage,
+};
 pub use auth::{Claims, OidcProvider};
+pub use changelog::{
+	ApplyBatching, ChangeLog, ChangeLogStats, ChangeRecorder, CompactReport, ImportReport,
+	SequencedEntry,
+};
+pub use merge::{
+	EntityVersion, MergeConfig, MergeResolver, MergeRule, MergeStrategy, VersionVector,
+};
+pub use server::SyncServer;
@@ -0,0 +1,348 @@
+//! Serving the sync protocol to peer agents.
+//!
+//! A [`SyncServer`] answers the length-prefixed [`SyncMessage`]s a
+//! [`SyncAgent`](crate::sync::SyncAgent) sends: the first message must
+//! authenticate with an OIDC token, after which pushed entries are appended
+//! to the local change log and pulls are answered from it. Pull responses
+//! carry at most [`SyncServer::with_max_pull_entries`] entries and a cursor
+//! (the change log sequence number of the last one), so a large backlog is
+//! delivered over several pulls instead of one oversized frame.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use log::{debug, error, info, warn};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+
+use crate::sync::agent::{DEFAULT_READ_TIMEOUT, SyncMessage, read_message, write_message};
+use crate::sync::auth::OidcProvider;
+use crate::sync::changelog::ChangeLog;
+
+/// Default cap on change log entries returned per pull response
+pub const DEFAULT_MAX_PULL_ENTRIES: usize = 1_000;
+
+/// Answers sync requests from peers out of the local change log.
+pub struct SyncServer {
+	changelog: Arc<ChangeLog>,
+	oidc_provider: Arc<OidcProvider>,
+	max_pull_entries: usize,
+	read_timeout: Duration,
+}
+
+impl SyncServer {
+	/// Serve `changelog` to peers whose tokens `oidc_provider` accepts.
+	pub fn new(changelog: Arc<ChangeLog>, oidc_provider: Arc<OidcProvider>) -> Self {
+		Self {
+			changelog,
+			oidc_provider,
+			max_pull_entries: DEFAULT_MAX_PULL_ENTRIES,
+			read_timeout: DEFAULT_READ_TIMEOUT,
+		}
+	}
+
+	/// Return at most `max` entries per pull response, whatever limit the
+	/// peer asks for.
+	pub fn with_max_pull_entries(mut self, max: usize) -> Self {
+		self.max_pull_entries = max.max(1);
+		self
+	}
+
+	/// Drop a connection whose peer stalls a read for longer than `timeout`.
+	pub fn with_read_timeout(mut self, timeout: Duration) -> Self {
+		self.read_timeout = timeout;
+		self
+	}
+
+	/// Accept TLS connections on `listener` and serve each on its own task.
+	pub async fn serve(self: Arc<Self>, listener: TcpListener, acceptor: TlsAcceptor) {
+		loop {
+			let (tcp, addr) = match listener.accept().await {
+				Ok(conn) => conn,
+				Err(e) => {
+					error!("Failed to accept sync connection: {}", e);
+					continue;
+				}
+			};
+			let server = Arc::clone(&self);
+			let acceptor = acceptor.clone();
+			tokio::spawn(async move {
+				let stream = match acceptor.accept(tcp).await {
+					Ok(stream) => stream,
+					Err(e) => {
+						warn!("TLS handshake with sync peer {} failed: {}", addr, e);
+						return;
+					}
+				};
+				if let Err(e) = server.serve_connection(stream).await {
+					warn!("Sync connection from {} ended: {:#}", addr, e);
+				}
+			});
+		}
+	}
+
+	/// Authenticate the peer on `stream`, then answer its requests until it
+	/// disconnects.
+	pub async fn serve_connection<S: AsyncRead + AsyncWrite + Unpin>(
+		&self,
+		stream: S,
+	) -> Result<()> {
+		let (mut reader, mut writer) = tokio::io::split(stream);
+
+		let token = match read_message(&mut reader, self.read_timeout).await? {
+			SyncMessage::Auth { token } => token,
+			other => {
+				let reason = format!("expected Auth, got {:?}", other);
+				write_message(
+					&mut writer,
+					&SyncMessage::AuthFailed {
+						reason: reason.clone(),
+					},
+				)
+				.await?;
+				anyhow::bail!(reason);
+			}
+		};
+		match self.oidc_provider.validate_token(&token).await {
+			Ok(claims) => {
+				info!("Sync peer authenticated as {}", claims.sub);
+				write_message(&mut writer, &SyncMessage::AuthOk).await?;
+			}
+			Err(e) => {
+				write_message(
+					&mut writer,
+					&SyncMessage::AuthFailed {
+						reason: "invalid token".to_string(),
+					},
+				)
+				.await?;
+				return Err(e.context("sync peer failed authentication"));
+			}
+		}
+
+		self.serve_authenticated(&mut reader, &mut writer).await
+	}
+
+	/// Answer requests from an authenticated peer until it disconnects.
+	async fn serve_authenticated<R, W>(&self, reader: &mut R, writer: &mut W) -> Result<()>
+	where
+		R: AsyncRead + Unpin,
+		W: AsyncWrite + Unpin,
+	{
+		loop {
+			let request = match read_message(reader, self.read_timeout).await {
+				Ok(request) => request,
+				Err(e) if is_disconnect(&e) => return Ok(()),
+				Err(e) => return Err(e),
+			};
+			let response = self.respond(request).await;
+			write_message(writer, &response)
+				.await
+				.context("failed to answer sync peer")?;
+		}
+	}
+
+	/// The answer to one request from an authenticated peer.
+	pub async fn respond(&self, request: SyncMessage) -> SyncMessage {
+		match request {
+			SyncMessage::Push { entries } => {
+				let count = entries.len();
+				for entry in entries {
+					if let Err(e) = self.changelog.append(entry).await {
+						error!("Failed to append pushed change log entry: {:#}", e);
+						return SyncMessage::Error {
+							message: "failed to record pushed entries".to_string(),
+						};
+					}
+				}
+				debug!("Accepted {} pushed change log entries", count);
+				SyncMessage::PushAck { count }
+			}
+			SyncMessage::Pull {
+				since_timestamp,
+				cursor,
+				limit,
+			} => {
+				let limit = limit
+					.unwrap_or(self.max_pull_entries)
+					.clamp(1, self.max_pull_entries);
+				// Peers without a cursor start at their timestamp instead.
+				let after = match cursor {
+					Some(cursor) => cursor,
+					None => self.changelog.seq_before_timestamp(since_timestamp).await,
+				};
+				let (page, has_more) = self.changelog.page(after, limit).await;
+				let next_cursor = page.last().map(|e| e.seq).unwrap_or(after);
+				let entries = page.into_iter().map(|e| e.entry).collect();
+				SyncMessage::PullResponse {
+					entries,
+					has_more,
+					next_cursor: Some(next_cursor),
+				}
+			}
+			SyncMessage::Ping => SyncMessage::Pong,
+			other => SyncMessage::Error {
+				message: format!("unexpected request: {:?}", other),
+			},
+		}
+	}
+}
+
+impl std::fmt::Debug for SyncServer {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("SyncServer")
+			.field("max_pull_entries", &self.max_pull_entries)
+			.field("read_timeout", &self.read_timeout)
+			.finish_non_exhaustive()
+	}
+}
+
+/// Whether `err` is the peer closing the connection between messages.
+fn is_disconnect(err: &anyhow::Error) -> bool {
+	err.chain().any(|cause| {
+		cause
+			.downcast_ref::<std::io::Error>()
+			.is_some_and(|e| e.kind() == std::io::ErrorKind::UnexpectedEof)
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::sync::ChangeLogEntry;
+
+	fn entry(i: u64) -> ChangeLogEntry {
+		ChangeLogEntry {
+			id: format!("e{}", i),
+			timestamp: 1_700_000_000 + i,
+			label: "FieldValue".to_string(),
+			key: format!("k{}", i),
+			props: serde_json::json!({}),
+			origin: "node-b".to_string(),
+			version_vector: Default::default(),
+			tombstone: false,
+		}
+	}
+
+	#[tokio::test]
+	async fn a_backlog_over_the_limit_is_pulled_in_pages() {
+		let changelog = Arc::new(ChangeLog::in_memory());
+		for i in 0..25 {
+			changelog.append(entry(i)).await.unwrap();
+		}
+		let provider = Arc::new(OidcProvider::new(
+			"https://example.com/.well-known/openid-configuration".to_string(),
+			"test-client".to_string(),
+			"test-secret".to_string(),
+		));
+		let server = SyncServer::new(changelog, provider).with_max_pull_entries(10);
+
+		let (client, peer) = tokio::io::duplex(64 * 1024);
+		let serving = tokio::spawn(async move {
+			let (mut reader, mut writer) = tokio::io::split(peer);
+			server.serve_authenticated(&mut reader, &mut writer).await
+		});
+
+		// Pull as the agent does: from the last cursor until drained, asking
+		// for more than the server hands out.
+		let (mut reader, mut writer) = tokio::io::split(client);
+		let mut cursor = 0;
+		let mut pages = Vec::new();
+		let mut ids = Vec::new();
+		loop {
+			let pull = SyncMessage::Pull {
+				since_timestamp: 0,
+				cursor: Some(cursor),
+				limit: Some(100),
+			};
+			write_message(&mut writer, &pull).await.unwrap();
+			let SyncMessage::PullResponse {
+				entries,
+				has_more,
+				next_cursor,
+			} = read_message(&mut reader, Duration::from_secs(5))
+				.await
+				.unwrap()
+			else {
+				panic!("expected a pull response");
+			};
+			pages.push(entries.len());
+			ids.extend(entries.into_iter().map(|e| e.id));
+			cursor = next_cursor.expect("cursor");
+			if !has_more {
+				break;
+			}
+		}
+
+		assert_eq!(pages, vec![10, 10, 5]);
+		assert_eq!(ids, (0..25).map(|i| format!("e{}", i)).collect::<Vec<_>>());
+		assert_eq!(cursor, 25);
+
+		// Drained: pulling from the final cursor returns nothing more.
+		let pull = SyncMessage::Pull {
+			since_timestamp: 0,
+			cursor: Some(cursor),
+			limit: None,
+		};
+		write_message(&mut writer, &pull).await.unwrap();
+		assert!(matches!(
+			read_message(&mut reader, Duration::from_secs(5)).await.unwrap(),
+			SyncMessage::PullResponse { entries, has_more: false, next_cursor: Some(25) }
+				if entries.is_empty()
+		));
+
+		drop((reader, writer));
+		serving.await.unwrap().unwrap();
+	}
+
+	#[tokio::test]
+	async fn a_pull_without_cursor_pages_from_its_timestamp() {
+		let changelog = Arc::new(ChangeLog::in_memory());
+		for i in 0..25 {
+			changelog.append(entry(i)).await.unwrap();
+		}
+		let provider = Arc::new(OidcProvider::new(
+			"https://example.com/.well-known/openid-configuration".to_string(),
+			"test-client".to_string(),
+			"test-secret".to_string(),
+		));
+		let server = SyncServer::new(changelog, provider).with_max_pull_entries(10);
+
+		// A whole page of entries is older than the timestamp.
+		let response = server
+			.respond(SyncMessage::Pull {
+				since_timestamp: 1_700_000_015,
+				cursor: None,
+				limit: None,
+			})
+			.await;
+		let SyncMessage::PullResponse {
+			entries,
+			has_more,
+			next_cursor,
+		} = response
+		else {
+			panic!("expected a pull response");
+		};
+		let ids: Vec<_> = entries.into_iter().map(|e| e.id).collect();
+		assert_eq!(ids, (15..25).map(|i| format!("e{}", i)).collect::<Vec<_>>());
+		assert!(!has_more);
+		assert_eq!(next_cursor, Some(25));
+
+		// Past the newest entry there is nothing to page through.
+		let response = server
+			.respond(SyncMessage::Pull {
+				since_timestamp: 1_800_000_000,
+				cursor: None,
+				limit: None,
+			})
+			.await;
+		assert!(matches!(
+			response,
+			SyncMessage::PullResponse { entries, has_more: false, next_cursor: Some(25) }
+				if entries.is_empty()
+		));
+	}
+}
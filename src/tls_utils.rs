@@ -4,7 +4,11 @@ use std::fs::File;
 use std::io::BufReader;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_rustls::TlsAcceptor;
 use tokio_rustls::rustls::{self, Certificate, PrivateKey, server::ServerConfig};
+use tokio_rustls::server::TlsStream;
 
 /// Load PEM-encoded certificates from `path` and return them as `rustls::Certificate`.
 pub fn load_certs(path: &Path) -> Result<Vec<Certificate>> {
@@ -51,6 +55,54 @@ pub fn parse_first_cert_x509<'a>(
 	Ok(res.1)
 }
 
+/// Default for the smallest RSA key accepted by [`check_key_strength`].
+pub const DEFAULT_MIN_RSA_BITS: usize = 2048;
+
+/// Signature algorithms (OID, name) no longer considered collision
+/// resistant: MD2, MD5 and SHA-1 with RSA, DSA or ECDSA.
+const DEPRECATED_SIGNATURE_ALGORITHMS: &[(&str, &str)] = &[
+	("1.2.840.113549.1.1.2", "md2WithRSAEncryption"),
+	("1.2.840.113549.1.1.4", "md5WithRSAEncryption"),
+	("1.2.840.113549.1.1.5", "sha1WithRSAEncryption"),
+	("1.3.14.3.2.29", "sha1WithRSASignature"),
+	("1.2.840.10040.4.3", "dsa-with-sha1"),
+	("1.2.840.10045.4.1", "ecdsa-with-SHA1"),
+];
+
+/// Reject a certificate signed with a deprecated algorithm or carrying an
+/// RSA key shorter than `min_rsa_bits`. Non-RSA keys are not size-checked.
+pub fn check_key_strength(cert: &Certificate, min_rsa_bits: usize) -> Result<()> {
+	use x509_parser::public_key::PublicKey;
+
+	let parsed = parse_first_cert_x509(cert)?;
+	let oid = parsed.signature_algorithm.algorithm.to_id_string();
+	if let Some((_, name)) = DEPRECATED_SIGNATURE_ALGORITHMS
+		.iter()
+		.find(|(deprecated, _)| *deprecated == oid)
+	{
+		anyhow::bail!(
+			"TLS certificate is signed with deprecated algorithm {}",
+			name
+		);
+	}
+
+	let key = parsed
+		.public_key()
+		.parsed()
+		.map_err(|e| anyhow::anyhow!("failed to parse certificate public key: {:?}", e))?;
+	if let PublicKey::RSA(rsa) = key {
+		let bits = rsa.key_size();
+		if bits < min_rsa_bits {
+			anyhow::bail!(
+				"TLS certificate RSA key is {} bits; at least {} are required",
+				bits,
+				min_rsa_bits
+			);
+		}
+	}
+	Ok(())
+}
+
 /// Return true if the certificate appears to be self-signed (subject == issuer).
 pub fn is_self_signed(cert: &Certificate) -> Result<bool> {
 	let parsed = parse_first_cert_x509(cert)?;
@@ -106,6 +158,41 @@ pub fn is_cert_expired(cert: &Certificate) -> Result<bool> {
 	Ok(not_after <= now)
 }
 
+/// Return true if `host` appears in the certificate's SAN DNS names or is its
+/// common name. Unparseable extensions count as no match.
+pub fn cert_matches_host(cert: &Certificate, host: &str) -> bool {
+	let in_san = dns_names_from_cert(cert).is_ok_and(|sans| sans.iter().any(|s| s == host));
+	in_san || matches!(first_common_name(cert), Ok(Some(cn)) if cn == host)
+}
+
+/// Load the certificate chain and key at `cert_path`/`key_path`, check the
+/// leaf is unexpired, issued for `host` (skipped when empty) and passes
+/// [`check_key_strength`], and build the TLS1.3 server config from them.
+pub fn load_server_config(
+	cert_path: &Path,
+	key_path: &Path,
+	host: &str,
+	min_rsa_bits: usize,
+) -> Result<Arc<ServerConfig>> {
+	let certs = load_certs(cert_path).context("failed to load TLS certs")?;
+	let key = load_private_key(key_path).context("failed to load TLS private key")?;
+
+	// Basic X.509 policy checks on the leaf certificate.
+	let leaf = &certs[0];
+	if is_cert_expired(leaf).context("failed to evaluate TLS certificate expiry")? {
+		anyhow::bail!("TLS certificate appears to be expired");
+	}
+	if !host.is_empty() && !cert_matches_host(leaf, host) {
+		anyhow::bail!(
+			"TLS certificate does not contain configured host '{}' in CN or SAN",
+			host
+		);
+	}
+	check_key_strength(leaf, min_rsa_bits)?;
+
+	build_server_config_tls13(certs, key)
+}
+
 /// Build a rustls `ServerConfig` restricted to TLS1.3. Returns an `Arc<ServerConfig>` suitable for `tokio_rustls::TlsAcceptor::from(...)`.
 pub fn build_server_config_tls13(
 	certs: Vec<Certificate>,
@@ -139,9 +226,30 @@ pub fn build_server_config_tls13(
 	Ok(Arc::new(cfg))
 }
 
+/// Perform the server side of a TLS handshake, failing with
+/// `ErrorKind::TimedOut` if it does not complete within `timeout`.
+///
+/// Without a deadline a client that opens a TCP connection and never sends
+/// a ClientHello would hold its connection task forever (slowloris).
+pub async fn accept_with_timeout(
+	acceptor: &TlsAcceptor,
+	stream: TcpStream,
+	timeout: Duration,
+) -> std::io::Result<TlsStream<TcpStream>> {
+	tokio::time::timeout(timeout, acceptor.accept(stream))
+		.await
+		.unwrap_or_else(|_| {
+			Err(std::io::Error::new(
+				std::io::ErrorKind::TimedOut,
+				"TLS handshake timed out",
+			))
+		})
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use tokio::io::AsyncReadExt;
 	// PathBuf not needed in these simple tests
 
 	#[test]
@@ -155,4 +263,98 @@ mod tests {
 		let p = Path::new("/this/path/does/not/exist/key.pem");
 		assert!(load_private_key(p).is_err());
 	}
+
+	/// Key pair exposing only a fixed RSA public key; enough for a leaf
+	/// that is signed by a separate CA.
+	struct RsaPublicKey(&'static [u8]);
+
+	impl rcgen::RemoteKeyPair for RsaPublicKey {
+		fn public_key(&self) -> &[u8] {
+			self.0
+		}
+
+		fn sign(&self, _msg: &[u8]) -> std::result::Result<Vec<u8>, rcgen::RcgenError> {
+			Err(rcgen::RcgenError::KeyGenerationUnavailable)
+		}
+
+		fn algorithm(&self) -> &'static rcgen::SignatureAlgorithm {
+			&rcgen::PKCS_RSA_SHA256
+		}
+	}
+
+	/// A CA-signed leaf carrying the PKCS#1 RSA public key `public_key`.
+	fn rsa_leaf(public_key: &'static [u8]) -> Certificate {
+		let mut ca_params = rcgen::CertificateParams::new(Vec::new());
+		ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+		let ca = rcgen::Certificate::from_params(ca_params).unwrap();
+
+		let mut params = rcgen::CertificateParams::new(vec!["heimdall.test".to_string()]);
+		params.alg = &rcgen::PKCS_RSA_SHA256;
+		let key_pair = rcgen::KeyPair::from_remote(Box::new(RsaPublicKey(public_key))).unwrap();
+		params.key_pair = Some(key_pair);
+		let leaf = rcgen::Certificate::from_params(params).unwrap();
+		Certificate(leaf.serialize_der_with_signer(&ca).unwrap())
+	}
+
+	#[test]
+	fn weak_rsa_keys_are_rejected() {
+		let weak = rsa_leaf(include_bytes!("../tests/fixtures/tls/rsa1024-public.der"));
+		let err = check_key_strength(&weak, DEFAULT_MIN_RSA_BITS).unwrap_err();
+		assert!(err.to_string().contains("1024 bits"), "{}", err);
+		// The minimum is configurable.
+		assert!(check_key_strength(&weak, 1024).is_ok());
+	}
+
+	#[test]
+	fn strong_keys_are_accepted() {
+		let strong = rsa_leaf(include_bytes!("../tests/fixtures/tls/rsa2048-public.der"));
+		assert!(check_key_strength(&strong, DEFAULT_MIN_RSA_BITS).is_ok());
+
+		let ecdsa = rcgen::generate_simple_self_signed(vec!["heimdall.test".to_string()]).unwrap();
+		let ecdsa = Certificate(ecdsa.serialize_der().unwrap());
+		assert!(check_key_strength(&ecdsa, DEFAULT_MIN_RSA_BITS).is_ok());
+	}
+
+	#[test]
+	fn sha1_signed_certificates_are_rejected() {
+		// rcgen cannot sign with SHA-1, so this one was made with openssl.
+		let pem = include_bytes!("../tests/fixtures/tls/sha1-signed.pem");
+		let der = pem_certs(&mut &pem[..]).unwrap().remove(0);
+		let err = check_key_strength(&Certificate(der), DEFAULT_MIN_RSA_BITS).unwrap_err();
+		assert!(err.to_string().contains("sha1WithRSAEncryption"), "{}", err);
+	}
+
+	#[tokio::test]
+	async fn silent_client_is_dropped_after_handshake_timeout() {
+		let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+		let cfg = ServerConfig::builder()
+			.with_safe_defaults()
+			.with_no_client_auth()
+			.with_single_cert(
+				vec![Certificate(cert.serialize_der().unwrap())],
+				PrivateKey(cert.serialize_private_key_der()),
+			)
+			.unwrap();
+		let acceptor = TlsAcceptor::from(Arc::new(cfg));
+
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let addr = listener.local_addr().unwrap();
+		let server = tokio::spawn(async move {
+			let (stream, _) = listener.accept().await.unwrap();
+			accept_with_timeout(&acceptor, stream, Duration::from_millis(100)).await
+		});
+
+		// Connect but never send a ClientHello.
+		let mut client = TcpStream::connect(addr).await.unwrap();
+		let err = server.await.unwrap().err().expect("handshake must fail");
+		assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+
+		// The server side has been dropped, so the client sees EOF.
+		let mut buf = [0u8; 1];
+		let n = tokio::time::timeout(Duration::from_secs(2), client.read(&mut buf))
+			.await
+			.expect("connection closed")
+			.unwrap_or(0);
+		assert_eq!(n, 0);
+	}
 }
@@ -0,0 +1,32 @@
+mod common;
+
+use vanopticon_heimdall::age_client::{AgeClient, MIN_AGE_VERSION, version_at_least};
+
+#[tokio::test]
+async fn check_extension_reports_installed_version() {
+	// This integration test is gated behind an env var to avoid running Docker in CI by default.
+	if !common::check_docker_enabled() {
+		return;
+	}
+
+	vanopticon_heimdall::devops::start_dev_db()
+		.await
+		.expect("start db");
+
+	common::with_ephemeral_graph(|_pool, graph| async move {
+		let client = AgeClient::connect(&common::database_url(), &graph)
+			.await
+			.expect("connect");
+		let info = client
+			.check_extension()
+			.await
+			.expect("age extension present");
+		assert!(!info.version.is_empty());
+		assert!(
+			version_at_least(&info.version, MIN_AGE_VERSION),
+			"dev database runs AGE {}",
+			info.version
+		);
+	})
+	.await;
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use serde_json::json;
+use vanopticon_heimdall::age_client::{AgeClient, BulkLoadReport};
+
+/// Count `FieldValue` nodes whose key starts with `prefix`.
+async fn count_nodes(pool: &sqlx::PgPool, graph: &str, prefix: &str) -> i64 {
+	let cypher = format!(
+		"MATCH (n:FieldValue) WHERE n.canonical_key STARTS WITH {} RETURN count(n)",
+		serde_json::to_string(prefix).unwrap()
+	);
+	let count: String =
+		sqlx::query_scalar("SELECT c::text FROM cypher($1::text, $2::text) as (c agtype);")
+			.bind(graph)
+			.bind(&cypher)
+			.fetch_one(pool)
+			.await
+			.expect("count nodes");
+	count.parse().expect("numeric count")
+}
+
+#[tokio::test]
+async fn integration_bulk_load_dedupes_and_restores_indexes() {
+	// This integration test is gated behind an env var to avoid running Docker in CI by default.
+	if !common::check_docker_enabled() {
+		return;
+	}
+
+	vanopticon_heimdall::devops::start_dev_db()
+		.await
+		.expect("start db");
+
+	common::with_ephemeral_graph(|pool, graph| async move {
+		let client = AgeClient::new(pool.clone(), graph.clone());
+		client
+			.create_property_indexes()
+			.await
+			.expect("create property indexes");
+
+		let prefix = format!("bulk-{}-", std::process::id());
+		client
+			.merge_entity(
+				"FieldValue",
+				&format!("{}0", prefix),
+				&json!({"field_type": "domain"}),
+			)
+			.await
+			.expect("seed existing node");
+
+		// 100 distinct keys, each loaded twice; key 0 already exists.
+		let items: Vec<(String, String, serde_json::Value)> = (0..200)
+			.map(|i| {
+				(
+					"FieldValue".to_string(),
+					format!("{}{}", prefix, i % 100),
+					json!({"field_type": "domain"}),
+				)
+			})
+			.collect();
+		let report = client.bulk_load(items, 32).await.expect("bulk load");
+
+		assert_eq!(
+			report,
+			BulkLoadReport {
+				created: 200,
+				duplicates_removed: 101,
+			}
+		);
+		assert_eq!(count_nodes(&pool, &graph, &prefix).await, 100);
+
+		let indexes = client.property_indexes().await.expect("list indexes");
+		assert_eq!(
+			indexes,
+			vec![
+				"idx_dump_id",
+				"idx_field_name",
+				"idx_fieldvalue_canonical_key",
+				"idx_row_dump_id",
+				"idx_rowhash_hash",
+			]
+		);
+	})
+	.await;
+
+	vanopticon_heimdall::devops::stop_dev_db()
+		.await
+		.expect("stop db");
+}
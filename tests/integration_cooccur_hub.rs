@@ -0,0 +1,86 @@
+mod common;
+
+use serde_json::json;
+use vanopticon_heimdall::age_client::{AgeClient, AgeRepo, HubGuard};
+use vanopticon_heimdall::observability::MetricsRegistry;
+
+#[tokio::test]
+async fn integration_cooccurrence_stops_at_hub_values() {
+	// This integration test is gated behind an env var to avoid running Docker in CI by default.
+	if !common::check_docker_enabled() {
+		return;
+	}
+
+	vanopticon_heimdall::devops::start_dev_db()
+		.await
+		.expect("start db");
+
+	common::with_ephemeral_graph(|pool, graph| async move {
+		let metrics = MetricsRegistry::new();
+		let client = AgeClient::new(pool.clone(), graph.clone()).with_hub_guard(HubGuard {
+			max_degree: 3,
+			flag: true,
+			skipped: metrics.cooccur_hub_skipped_total.clone(),
+		});
+
+		// A constant `source` column co-occurs with every other value.
+		let hub = "source:feed";
+		client
+			.merge_entity("FieldValue", hub, &json!({}))
+			.await
+			.expect("merge hub");
+		for i in 0..10 {
+			let other = format!("domain:host{}.example", i);
+			client
+				.merge_entity("FieldValue", &other, &json!({}))
+				.await
+				.expect("merge value");
+			AgeRepo::increment_co_occurrence(&client, hub, &other, "2024-01-01T00:00:00Z")
+				.await
+				.expect("co-occur");
+		}
+
+		let degree = format!(
+			"MATCH (n:FieldValue {{canonical_key: {}}})-[e:CO_OCCURS]-() RETURN count(e)",
+			serde_json::to_string(hub).unwrap()
+		);
+		let count: String =
+			sqlx::query_scalar("SELECT c::text FROM cypher($1::text, $2::text) as (c agtype);")
+				.bind(&graph)
+				.bind(&degree)
+				.fetch_one(&pool)
+				.await
+				.expect("count hub edges");
+		assert_eq!(count, "3", "no edges past the threshold");
+		assert_eq!(metrics.cooccur_hub_skipped_total.get(), 7);
+
+		let flag = format!(
+			"MATCH (n:FieldValue {{canonical_key: {}}}) RETURN n.is_hub",
+			serde_json::to_string(hub).unwrap()
+		);
+		let is_hub: String =
+			sqlx::query_scalar("SELECT h::text FROM cypher($1::text, $2::text) as (h agtype);")
+				.bind(&graph)
+				.bind(&flag)
+				.fetch_one(&pool)
+				.await
+				.expect("read hub flag");
+		assert_eq!(is_hub, "true");
+
+		// Values that are not hubs still co-occur.
+		AgeRepo::increment_co_occurrence(
+			&client,
+			"domain:host0.example",
+			"domain:host1.example",
+			"2024-01-01T00:00:00Z",
+		)
+		.await
+		.expect("co-occur non-hubs");
+		assert_eq!(metrics.cooccur_hub_skipped_total.get(), 7);
+	})
+	.await;
+
+	vanopticon_heimdall::devops::stop_dev_db()
+		.await
+		.expect("stop db");
+}
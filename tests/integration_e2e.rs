@@ -39,11 +39,8 @@ async fn e2e_ndjson_upload_persists() {
 		100,
 	);
 
-	let app_state = vanopticon_heimdall::state::AppState {
-		repo: repo.clone(),
-		persist_sender: sender.clone(),
-		metrics: metrics.clone(),
-	};
+	let app_state =
+		vanopticon_heimdall::state::AppState::new(repo.clone(), sender.clone(), metrics.clone());
 
 	// Build NDJSON payload and call handler directly
 	let payload = r#"{"field_type":"domain","value":"Example.COM"}
@@ -64,11 +61,11 @@ async fn e2e_ndjson_upload_persists() {
 	// Allow a short time for the batcher to flush
 	sleep(Duration::from_millis(500)).await;
 
-	// Verify DB contains the merged node (canonical: example.com)
+	// Verify DB contains the merged node (key: domain:example.com)
 	let sql = "SELECT * FROM cypher($1::text, $2::text) as (v agtype);";
 	let cypher = format!(
 		"MATCH (n:FieldValue {{canonical_key: \"{}\"}}) RETURN n LIMIT 1",
-		"example.com"
+		"domain:example.com"
 	);
 	let row = sqlx::query(sql)
 		.bind("heimdall_graph")
@@ -124,6 +124,8 @@ mod tests {
 			max_backoff_ms: 2000,
 			circuit_breaker_threshold: 5,
 			circuit_breaker_timeout_ms: 30_000,
+			circuit_breaker_half_open_max_probes: 1,
+			circuit_breaker_success_threshold: 1,
 		};
 
 		let client = ResilientClientBuilder::new(config).build();
@@ -29,29 +29,36 @@ async fn e2e_enrichment_mock_workflow() {
 	let repo: Arc<dyn vanopticon_heimdall::age_client::AgeRepo> = Arc::new(client);
 
 	// Start a batcher that flushes immediately for testing (batch_size=1)
-	let sender = vanopticon_heimdall::persist::start_batcher(repo.clone(), 1024, 1, 100);
+	let metrics = Arc::new(vanopticon_heimdall::observability::MetricsRegistry::new());
+	let sender = vanopticon_heimdall::persist::start_batcher(
+		repo.clone(),
+		metrics.clone(),
+		1024,
+		1,
+		100,
+	);
 
 	// Scenario: Ingest an IP address, then enrich it with GeoIP and ASN data
 	
 	// Step 1: Create base entity (IP address)
 	let ip_address = "8.8.8.8";
-	let ip_job = vanopticon_heimdall::persist::PersistJob {
-		label: "IPAddress".to_string(),
-		key: ip_address.to_string(),
-		props: json!({
+	let ip_job = vanopticon_heimdall::persist::PersistJob::new(
+		"IPAddress",
+		ip_address.to_string(),
+		json!({
 			"canonical_key": ip_address,
 			"field_type": "ip",
 		}),
-	};
+	);
 	
-	vanopticon_heimdall::persist::submit_job(&sender, ip_job)
+	vanopticon_heimdall::persist::submit_job(&sender, ip_job, &metrics)
 		.expect("submit IP job");
 
 	// Step 2: Mock enrichment - add GeoIP data
-	let geoip_job = vanopticon_heimdall::persist::PersistJob {
-		label: "GeoIPEnrichment".to_string(),
-		key: format!("geoip_{}", ip_address),
-		props: json!({
+	let geoip_job = vanopticon_heimdall::persist::PersistJob::new(
+		"GeoIPEnrichment",
+		format!("geoip_{}", ip_address),
+		json!({
 			"canonical_key": format!("geoip_{}", ip_address),
 			"ip_address": ip_address,
 			"country": "US",
@@ -61,16 +68,16 @@ async fn e2e_enrichment_mock_workflow() {
 			"enrichment_source": "mock_geoip",
 			"enriched_at": "2024-01-01T00:00:00Z",
 		}),
-	};
+	);
 	
-	vanopticon_heimdall::persist::submit_job(&sender, geoip_job)
+	vanopticon_heimdall::persist::submit_job(&sender, geoip_job, &metrics)
 		.expect("submit GeoIP enrichment job");
 
 	// Step 3: Mock enrichment - add ASN data
-	let asn_job = vanopticon_heimdall::persist::PersistJob {
-		label: "ASNEnrichment".to_string(),
-		key: format!("asn_{}", ip_address),
-		props: json!({
+	let asn_job = vanopticon_heimdall::persist::PersistJob::new(
+		"ASNEnrichment",
+		format!("asn_{}", ip_address),
+		json!({
 			"canonical_key": format!("asn_{}", ip_address),
 			"ip_address": ip_address,
 			"asn": 15169,
@@ -78,9 +85,9 @@ async fn e2e_enrichment_mock_workflow() {
 			"enrichment_source": "mock_asn",
 			"enriched_at": "2024-01-01T00:00:00Z",
 		}),
-	};
+	);
 	
-	vanopticon_heimdall::persist::submit_job(&sender, asn_job)
+	vanopticon_heimdall::persist::submit_job(&sender, asn_job, &metrics)
 		.expect("submit ASN enrichment job");
 
 	// Allow time for the batcher to flush
@@ -159,58 +166,65 @@ async fn e2e_chained_enrichment() {
 	let repo: Arc<dyn vanopticon_heimdall::age_client::AgeRepo> = Arc::new(client);
 
 	// Start a batcher
-	let sender = vanopticon_heimdall::persist::start_batcher(repo.clone(), 1024, 1, 100);
+	let metrics = Arc::new(vanopticon_heimdall::observability::MetricsRegistry::new());
+	let sender = vanopticon_heimdall::persist::start_batcher(
+		repo.clone(),
+		metrics.clone(),
+		1024,
+		1,
+		100,
+	);
 
 	// Scenario: Domain -> DNS Resolution -> IP -> GeoIP
 	let domain = "example.com";
 	
 	// Step 1: Domain entity
-	let domain_job = vanopticon_heimdall::persist::PersistJob {
-		label: "Domain".to_string(),
-		key: domain.to_string(),
-		props: json!({
+	let domain_job = vanopticon_heimdall::persist::PersistJob::new(
+		"Domain",
+		domain.to_string(),
+		json!({
 			"canonical_key": domain,
 			"field_type": "domain",
 		}),
-	};
-	vanopticon_heimdall::persist::submit_job(&sender, domain_job)
+	);
+	vanopticon_heimdall::persist::submit_job(&sender, domain_job, &metrics)
 		.expect("submit domain job");
 
 	// Step 2: DNS resolution enrichment (mock)
-	let dns_job = vanopticon_heimdall::persist::PersistJob {
-		label: "DNSEnrichment".to_string(),
-		key: format!("dns_{}", domain),
-		props: json!({
+	let dns_job = vanopticon_heimdall::persist::PersistJob::new(
+		"DNSEnrichment",
+		format!("dns_{}", domain),
+		json!({
 			"canonical_key": format!("dns_{}", domain),
 			"domain": domain,
 			"resolved_ips": ["93.184.216.34"],
 			"enrichment_source": "mock_dns",
 			"enriched_at": "2024-01-01T00:00:00Z",
 		}),
-	};
-	vanopticon_heimdall::persist::submit_job(&sender, dns_job)
+	);
+	vanopticon_heimdall::persist::submit_job(&sender, dns_job, &metrics)
 		.expect("submit DNS enrichment job");
 
 	// Step 3: Discovered IP from DNS resolution
 	let discovered_ip = "93.184.216.34";
-	let ip_job = vanopticon_heimdall::persist::PersistJob {
-		label: "IPAddress".to_string(),
-		key: discovered_ip.to_string(),
-		props: json!({
+	let ip_job = vanopticon_heimdall::persist::PersistJob::new(
+		"IPAddress",
+		discovered_ip.to_string(),
+		json!({
 			"canonical_key": discovered_ip,
 			"field_type": "ip",
 			"discovered_via": "dns_resolution",
 			"source_domain": domain,
 		}),
-	};
-	vanopticon_heimdall::persist::submit_job(&sender, ip_job)
+	);
+	vanopticon_heimdall::persist::submit_job(&sender, ip_job, &metrics)
 		.expect("submit IP job");
 
 	// Step 4: GeoIP enrichment for discovered IP
-	let geoip_job = vanopticon_heimdall::persist::PersistJob {
-		label: "GeoIPEnrichment".to_string(),
-		key: format!("geoip_{}", discovered_ip),
-		props: json!({
+	let geoip_job = vanopticon_heimdall::persist::PersistJob::new(
+		"GeoIPEnrichment",
+		format!("geoip_{}", discovered_ip),
+		json!({
 			"canonical_key": format!("geoip_{}", discovered_ip),
 			"ip_address": discovered_ip,
 			"country": "US",
@@ -218,8 +232,8 @@ async fn e2e_chained_enrichment() {
 			"enrichment_source": "mock_geoip",
 			"enriched_at": "2024-01-01T00:00:00Z",
 		}),
-	};
-	vanopticon_heimdall::persist::submit_job(&sender, geoip_job)
+	);
+	vanopticon_heimdall::persist::submit_job(&sender, geoip_job, &metrics)
 		.expect("submit GeoIP enrichment job");
 
 	// Allow time for the batcher to flush
@@ -289,3 +303,60 @@ async fn e2e_chained_enrichment() {
 		.await
 		.expect("stop db");
 }
+
+/// Enrichment results are stored as a node linked from the enriched entity
+/// by an `ENRICHED_BY` edge.
+#[tokio::test]
+async fn e2e_enrichment_result_is_linked_by_enriched_by() {
+	use vanopticon_heimdall::age_client::AgeClient;
+	use vanopticon_heimdall::enrich::{EnrichmentResult, Entity};
+
+	if !common::check_docker_enabled() {
+		return;
+	}
+
+	vanopticon_heimdall::devops::start_dev_db()
+		.await
+		.expect("start db");
+
+	common::with_ephemeral_graph(|pool, graph| async move {
+		let client = AgeClient::new(pool.clone(), graph.clone());
+		let ip = Entity::new("IPAddress", "8.8.8.8", json!({"field_type": "ip"}));
+		client
+			.merge_entity(&ip.label, &ip.key, &ip.props)
+			.await
+			.expect("merge IP");
+
+		let result = EnrichmentResult::new(
+			"GeoIPEnrichment",
+			"mock_geoip",
+			&ip,
+			json!({"country": "US", "city": "Mountain View"}),
+		)
+		.with_enriched_at("2024-01-01T00:00:00Z");
+		// Persisting twice updates the same node and edge.
+		for _ in 0..2 {
+			result.persist(&client).await.expect("persist enrichment");
+		}
+
+		let cypher = "MATCH (i:IPAddress {canonical_key: \"8.8.8.8\"})-[e:ENRICHED_BY]->(g:GeoIPEnrichment) \
+		              RETURN [g.country, g.enrichment_source, g.enriched_at, e.enrichment_source]";
+		let rows: Vec<String> =
+			sqlx::query_scalar("SELECT v::text FROM cypher($1::text, $2::text) as (v agtype);")
+				.bind(&graph)
+				.bind(cypher)
+				.fetch_all(&pool)
+				.await
+				.expect("query enrichment");
+		assert_eq!(rows.len(), 1);
+		assert_eq!(
+			serde_json::from_str::<serde_json::Value>(&rows[0]).unwrap(),
+			json!(["US", "mock_geoip", "2024-01-01T00:00:00Z", "mock_geoip"])
+		);
+	})
+	.await;
+
+	vanopticon_heimdall::devops::stop_dev_db()
+		.await
+		.expect("stop db");
+}
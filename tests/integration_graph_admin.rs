@@ -0,0 +1,41 @@
+mod common;
+
+use vanopticon_heimdall::age_client::{AgeClient, AgeError};
+
+#[tokio::test]
+async fn create_and_drop_graph_are_idempotent() {
+	// This integration test is gated behind an env var to avoid running Docker in CI by default.
+	if !common::check_docker_enabled() {
+		return;
+	}
+
+	vanopticon_heimdall::devops::start_dev_db()
+		.await
+		.expect("start db");
+
+	let pool = common::wait_for_postgres(&common::database_url(), 30)
+		.await
+		.expect("connect to postgres");
+	let graph = common::unique_graph_name("admin_graph");
+	let client = AgeClient::new(pool.clone(), graph.clone());
+
+	assert!(matches!(
+		client.verify_graph().await,
+		Err(AgeError::GraphMissing(_))
+	));
+	assert!(client.create_graph(&graph).await.expect("create graph"));
+	assert!(common::graph_exists(&pool, &graph).await.unwrap());
+	client.verify_graph().await.expect("graph exists");
+
+	// Creating it again leaves it in place.
+	assert!(!client.create_graph(&graph).await.expect("re-create graph"));
+	client.verify_graph().await.expect("graph still exists");
+
+	assert!(client.drop_graph(&graph, true).await.expect("drop graph"));
+	assert!(!common::graph_exists(&pool, &graph).await.unwrap());
+	assert!(!client.drop_graph(&graph, true).await.expect("drop again"));
+
+	vanopticon_heimdall::devops::stop_dev_db()
+		.await
+		.expect("stop db");
+}
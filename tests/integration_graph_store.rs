@@ -0,0 +1,103 @@
+mod common;
+
+use serde_json::json;
+use vanopticon_heimdall::age_client::AgeClient;
+use vanopticon_heimdall::store::postgres::PgGraphStore;
+use vanopticon_heimdall::store::{GraphStore, NodeView};
+
+/// Merge, observe and link the same values, then read the domain back.
+async fn scenario(store: &dyn GraphStore) -> NodeView {
+	store
+		.merge_value("Domain", "store.example", &json!({"tld": "example"}))
+		.await
+		.expect("merge domain");
+	store
+		.merge_value("IPAddress", "192.0.2.10", &json!({"version": 4}))
+		.await
+		.expect("merge ip");
+	for ts in ["2026-01-01T00:00:00Z", "2026-01-02T00:00:00Z"] {
+		store
+			.observe("Domain", "store.example", &json!({"source": "feed"}), ts)
+			.await
+			.expect("observe domain");
+	}
+	store
+		.link(
+			"store.example",
+			"192.0.2.10",
+			"RESOLVES_TO",
+			&json!({"ttl": 60}),
+		)
+		.await
+		.expect("link");
+	// Linking again updates the one edge.
+	store
+		.link(
+			"store.example",
+			"192.0.2.10",
+			"RESOLVES_TO",
+			&json!({"ttl": 300}),
+		)
+		.await
+		.expect("relink");
+
+	assert!(
+		store
+			.lookup("Domain", "missing.example")
+			.await
+			.expect("lookup missing")
+			.is_none()
+	);
+	let mut view = store
+		.lookup("Domain", "store.example")
+		.await
+		.expect("lookup")
+		.expect("domain exists");
+	view.edges
+		.sort_by(|a, b| (&a.rel_type, &a.to_key).cmp(&(&b.rel_type, &b.to_key)));
+	view
+}
+
+#[tokio::test]
+async fn integration_backends_store_the_same_graph() {
+	// This integration test is gated behind an env var to avoid running Docker in CI by default.
+	if !common::check_docker_enabled() {
+		return;
+	}
+
+	vanopticon_heimdall::devops::start_dev_db()
+		.await
+		.expect("start db");
+
+	common::with_ephemeral_graph(|pool, graph| async move {
+		let age = AgeClient::new(pool.clone(), graph);
+		let age_view = scenario(&age).await;
+
+		let pg = PgGraphStore::new(pool.clone());
+		pg.ensure_schema().await.expect("create tables");
+		sqlx::query("TRUNCATE heimdall_nodes, heimdall_edges")
+			.execute(&pool)
+			.await
+			.expect("truncate tables");
+		let pg_view = scenario(&pg).await;
+
+		assert_eq!(pg_view, age_view);
+		assert_eq!(pg_view.node.props["seen_count"], json!(2));
+		assert_eq!(
+			pg_view.node.props["first_seen"],
+			json!("2026-01-01T00:00:00Z")
+		);
+		assert_eq!(pg_view.edges.len(), 1);
+		assert_eq!(pg_view.edges[0].props["ttl"], json!(300));
+
+		sqlx::query("DROP TABLE heimdall_edges, heimdall_nodes")
+			.execute(&pool)
+			.await
+			.expect("drop tables");
+	})
+	.await;
+
+	vanopticon_heimdall::devops::stop_dev_db()
+		.await
+		.expect("stop db");
+}
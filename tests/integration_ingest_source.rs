@@ -0,0 +1,90 @@
+mod common;
+
+use std::sync::Arc;
+
+use vanopticon_heimdall::age_client::{AgeClient, AgeRepo};
+use vanopticon_heimdall::ingest::NormalizedRecord;
+use vanopticon_heimdall::observability::MetricsRegistry;
+use vanopticon_heimdall::sink::{AgeSink, RecordSink};
+
+/// `sources` of every `FieldValue` node, ordered by key.
+async fn sources(pool: &sqlx::PgPool, graph: &str) -> Vec<(String, Vec<String>)> {
+	let read = "MATCH (n:FieldValue) RETURN [n.canonical_key, n.sources]";
+	let rows: Vec<String> =
+		sqlx::query_scalar("SELECT v::text FROM cypher($1::text, $2::text) as (v agtype);")
+			.bind(graph)
+			.bind(read)
+			.fetch_all(pool)
+			.await
+			.expect("read sources");
+	let mut rows: Vec<(String, Vec<String>)> = rows
+		.iter()
+		.map(|row| serde_json::from_str(row).expect("agtype pair"))
+		.collect();
+	rows.sort();
+	rows
+}
+
+#[tokio::test]
+async fn integration_delete_by_source_keeps_nodes_other_feeds_reported() {
+	// This integration test is gated behind an env var to avoid running Docker in CI by default.
+	if !common::check_docker_enabled() {
+		return;
+	}
+
+	vanopticon_heimdall::devops::start_dev_db()
+		.await
+		.expect("start db");
+
+	common::with_ephemeral_graph(|pool, graph| async move {
+		let client = Arc::new(AgeClient::new(pool.clone(), graph.clone()));
+		for (source, domains) in [
+			("feedA", &["a1.example", "a2.example", "shared.example"][..]),
+			("feedB", &["b1.example", "shared.example"][..]),
+		] {
+			// A closed batcher channel makes the sink write synchronously.
+			let (tx, rx) = tokio::sync::mpsc::channel(1);
+			drop(rx);
+			let repo: Arc<dyn AgeRepo> = client.clone();
+			let sink = AgeSink::new(repo, tx, Arc::new(MetricsRegistry::new()))
+				.with_ingest_source(Some(source.to_string()));
+			for domain in domains {
+				sink.send(&NormalizedRecord::new("domain", *domain, *domain))
+					.await
+					.expect("ingest domain");
+			}
+		}
+
+		let feed_a = || vec!["feedA".to_string()];
+		let feed_b = || vec!["feedB".to_string()];
+		let both = || vec!["feedA".to_string(), "feedB".to_string()];
+		assert_eq!(
+			sources(&pool, &graph).await,
+			vec![
+				("domain:a1.example".to_string(), feed_a()),
+				("domain:a2.example".to_string(), feed_a()),
+				("domain:b1.example".to_string(), feed_b()),
+				("domain:shared.example".to_string(), both()),
+			]
+		);
+
+		// The shared node stays, reported by feedB alone.
+		assert_eq!(client.delete_by_source("feedA").await.expect("delete"), 2);
+		assert_eq!(
+			sources(&pool, &graph).await,
+			vec![
+				("domain:b1.example".to_string(), feed_b()),
+				("domain:shared.example".to_string(), feed_b()),
+			]
+		);
+		assert_eq!(client.delete_by_source("feedA").await.expect("delete"), 0);
+
+		assert_eq!(client.delete_by_source("feedB").await.expect("delete"), 2);
+		assert!(sources(&pool, &graph).await.is_empty());
+	})
+	.await;
+
+	vanopticon_heimdall::devops::stop_dev_db()
+		.await
+		.expect("stop db");
+}
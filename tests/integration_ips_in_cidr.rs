@@ -0,0 +1,70 @@
+mod common;
+
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use vanopticon_heimdall::age_client::{AgeClient, AgeRepo};
+use vanopticon_heimdall::ingest::NormalizedRecord;
+use vanopticon_heimdall::observability::MetricsRegistry;
+use vanopticon_heimdall::sink::{AgeSink, RecordSink};
+
+#[tokio::test]
+async fn integration_ips_in_cidr_returns_ingested_addresses_in_range() {
+	// This integration test is gated behind an env var to avoid running Docker in CI by default.
+	if !common::check_docker_enabled() {
+		return;
+	}
+
+	vanopticon_heimdall::devops::start_dev_db()
+		.await
+		.expect("start db");
+
+	common::with_ephemeral_graph(|pool, graph| async move {
+		let client = Arc::new(AgeClient::new(pool.clone(), graph.clone()));
+		// A closed batcher channel makes the sink write synchronously.
+		let (tx, rx) = tokio::sync::mpsc::channel(1);
+		drop(rx);
+		let repo: Arc<dyn AgeRepo> = client.clone();
+		let sink = AgeSink::new(repo, tx, Arc::new(MetricsRegistry::new()));
+		for ip in [
+			"10.255.255.255",
+			"10.0.0.1",
+			"9.255.255.255",
+			"11.0.0.0",
+			"10.20.30.40",
+			"192.168.1.1",
+			"2001:db8::1",
+			"2001:db9::1",
+			// IPv4-mapped IPv6 is IPv6 and outside IPv4 ranges.
+			"::ffff:10.0.0.2",
+		] {
+			sink.send(&NormalizedRecord::new("ip", ip, ip))
+				.await
+				.expect("ingest ip");
+		}
+
+		let ips =
+			|addrs: &[&str]| -> Vec<IpAddr> { addrs.iter().map(|a| a.parse().unwrap()).collect() };
+		assert_eq!(
+			client.ips_in_cidr("10.0.0.0/8").await.expect("query v4"),
+			ips(&["10.0.0.1", "10.20.30.40", "10.255.255.255"])
+		);
+		assert_eq!(
+			client.ips_in_cidr("2001:db8::/32").await.expect("query v6"),
+			ips(&["2001:db8::1"])
+		);
+		assert!(
+			client
+				.ips_in_cidr("172.16.0.0/12")
+				.await
+				.expect("query empty")
+				.is_empty()
+		);
+		assert!(client.ips_in_cidr("10.0.0.0/40").await.is_err());
+	})
+	.await;
+
+	vanopticon_heimdall::devops::stop_dev_db()
+		.await
+		.expect("stop db");
+}
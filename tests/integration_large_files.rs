@@ -70,7 +70,11 @@ mod large_file_tests {
 		let compressed = encoder.finish().unwrap();
 
 		// Decompress
-		let decompressed = parsers::decompress_gzip(Cursor::new(&compressed)).expect("decompress");
+		let decompressed = parsers::decompress_gzip(
+			Cursor::new(&compressed),
+			&parsers::DecompressionLimits::default(),
+		)
+		.expect("decompress");
 		assert_eq!(
 			String::from_utf8_lossy(&decompressed),
 			test_data,
@@ -0,0 +1,93 @@
+mod common;
+
+use serde_json::json;
+use vanopticon_heimdall::age_client::{AgeClient, Edge, GraphNode};
+
+async fn count(pool: &sqlx::PgPool, graph: &str, cypher: &str) -> i64 {
+	let count: String =
+		sqlx::query_scalar("SELECT c::text FROM cypher($1::text, $2::text) as (c agtype);")
+			.bind(graph)
+			.bind(cypher)
+			.fetch_one(pool)
+			.await
+			.expect("count query");
+	count.parse().expect("integer count")
+}
+
+#[tokio::test]
+async fn integration_merge_record_tx_rolls_back_node_and_edges() {
+	// This integration test is gated behind an env var to avoid running Docker in CI by default.
+	if !common::check_docker_enabled() {
+		return;
+	}
+
+	vanopticon_heimdall::devops::start_dev_db()
+		.await
+		.expect("start db");
+
+	common::with_ephemeral_graph(|pool, graph| async move {
+		let client = AgeClient::new(pool.clone(), graph.clone());
+
+		// Inject a failure after the node merge: any insert into the BROKEN
+		// edge table raises.
+		sqlx::query("SELECT ag_catalog.create_elabel($1::name, 'BROKEN'::name);")
+			.bind(&graph)
+			.execute(&pool)
+			.await
+			.expect("create BROKEN edge label");
+		sqlx::query(
+			"CREATE OR REPLACE FUNCTION heimdall_test_reject_insert() RETURNS trigger \
+			 LANGUAGE plpgsql AS $$ BEGIN RAISE EXCEPTION 'injected failure'; END $$;",
+		)
+		.execute(&pool)
+		.await
+		.expect("create trigger function");
+		sqlx::query(&format!(
+			"CREATE TRIGGER reject_insert BEFORE INSERT ON \"{}\".\"BROKEN\" \
+			 FOR EACH ROW EXECUTE FUNCTION heimdall_test_reject_insert();",
+			graph
+		))
+		.execute(&pool)
+		.await
+		.expect("create trigger");
+
+		let node = GraphNode::new(
+			"FieldValue",
+			"email:tx@example.com",
+			json!({"field_type": "email"}),
+		);
+		let edges = [
+			Edge::new("CO_OCCURS", "domain:tx.example"),
+			Edge::new("BROKEN", "domain:tx.example"),
+		];
+		let ts = "2024-01-01T00:00:00Z";
+		client
+			.merge_record_tx(&node, &edges, ts)
+			.await
+			.expect_err("injected failure must abort the record");
+
+		let nodes = "MATCH (n:FieldValue) RETURN count(n)";
+		assert_eq!(count(&pool, &graph, nodes).await, 0, "no node committed");
+		let co = "MATCH ()-[e:CO_OCCURS]->() RETURN count(e)";
+		assert_eq!(count(&pool, &graph, co).await, 0, "no edge committed");
+
+		// Without the failing edge the node and edge commit together.
+		client
+			.merge_record_tx(&node, &edges[..1], ts)
+			.await
+			.expect("merge record");
+		assert_eq!(count(&pool, &graph, nodes).await, 2);
+		let edge_count = format!(
+			"MATCH (a {{canonical_key: {}}})-[e:CO_OCCURS]->(b {{canonical_key: {}}}) \
+			 RETURN e.count",
+			serde_json::to_string(&node.key).unwrap(),
+			serde_json::to_string(&edges[0].to_key).unwrap()
+		);
+		assert_eq!(count(&pool, &graph, &edge_count).await, 1);
+	})
+	.await;
+
+	vanopticon_heimdall::devops::stop_dev_db()
+		.await
+		.expect("stop db");
+}
@@ -5,6 +5,8 @@
 
 use std::io::Cursor;
 
+mod common;
+
 #[cfg(feature = "ingest-tests")]
 mod multipart_tests {
 	use super::*;
@@ -27,42 +29,16 @@ mod multipart_tests {
 		);
 
 		// Build a simple AppState for testing
-		// Note: We would use vanopticon_heimdall::ingest::test_utils here, but test_utils
-		// is only compiled in test mode within the library, not for integration tests.
-		// For integration tests, we create a minimal inline implementation.
 		use std::sync::Arc;
 		use tokio::sync::mpsc;
 
-		struct DummyRepo;
-		#[async_trait::async_trait]
-		impl vanopticon_heimdall::age_client::AgeRepo for DummyRepo {
-			async fn merge_entity(
-				&self,
-				_label: &str,
-				_key: &str,
-				_props: &serde_json::Value,
-			) -> anyhow::Result<()> {
-				Ok(())
-			}
-
-			async fn ping(&self) -> anyhow::Result<()> {
-				Ok(())
-			}
-
-			async fn merge_batch(
-				&self,
-				_items: &[(String, String, serde_json::Value)],
-			) -> anyhow::Result<()> {
-				Ok(())
-			}
-		}
-
 		let (tx, _rx) = mpsc::channel(16);
-		let repo: Arc<dyn vanopticon_heimdall::age_client::AgeRepo> = Arc::new(DummyRepo);
-		let _app_state = vanopticon_heimdall::state::AppState {
+		let repo: Arc<dyn vanopticon_heimdall::age_client::AgeRepo> = Arc::new(common::DummyRepo);
+		let _app_state = vanopticon_heimdall::state::AppState::new(
 			repo,
-			persist_sender: tx,
-		};
+			tx,
+			Arc::new(vanopticon_heimdall::observability::MetricsRegistry::new()),
+		);
 
 		// Note: Full integration test would require setting up an actual HTTP server
 		// and making requests. For now, we verify that the parsers and format detection
@@ -135,7 +111,11 @@ mod multipart_tests {
 		encoder.write_all(test_data).unwrap();
 		let compressed = encoder.finish().unwrap();
 
-		let decompressed = parsers::decompress_gzip(Cursor::new(&compressed)).expect("decompress");
+		let decompressed = parsers::decompress_gzip(
+			Cursor::new(&compressed),
+			&parsers::DecompressionLimits::default(),
+		)
+		.expect("decompress");
 		assert_eq!(&decompressed, test_data);
 	}
 }
@@ -0,0 +1,98 @@
+mod common;
+
+use serde_json::json;
+use vanopticon_heimdall::age_client::AgeRepo;
+
+#[tokio::test]
+async fn integration_neighbors_returns_node_edges_and_adjacent_nodes() {
+	// This integration test is gated behind an env var to avoid running Docker in CI by default.
+	if !common::check_docker_enabled() {
+		return;
+	}
+
+	vanopticon_heimdall::devops::start_dev_db()
+		.await
+		.expect("start db");
+
+	common::with_ephemeral_graph(|pool, graph| async move {
+		let client = vanopticon_heimdall::age_client::AgeClient::new(pool.clone(), graph.clone());
+
+		for (label, key) in [
+			("Domain", "domain:pivot.example"),
+			("IPAddress", "ip:192.0.2.1"),
+			("IPAddress", "ip:192.0.2.2"),
+			("Email", "email:admin@pivot.example"),
+			("Domain", "domain:unrelated.example"),
+		] {
+			client
+				.merge_entity(label, key, &json!({}))
+				.await
+				.expect("merge node");
+		}
+		let edges = [
+			("domain:pivot.example", "ip:192.0.2.1", "RESOLVES_TO"),
+			("domain:pivot.example", "ip:192.0.2.2", "RESOLVES_TO"),
+			(
+				"email:admin@pivot.example",
+				"domain:pivot.example",
+				"REGISTERED",
+			),
+		];
+		for (from, to, rel) in edges {
+			AgeRepo::relate(&client, from, to, rel, &json!({"source": "test"}))
+				.await
+				.expect("relate");
+		}
+
+		// Both directions are followed.
+		let all = AgeRepo::neighbors(&client, "Domain", "domain:pivot.example", &[], 100)
+			.await
+			.expect("neighbors")
+			.expect("node exists");
+		assert_eq!(
+			all.nodes[0]["properties"]["canonical_key"],
+			"domain:pivot.example"
+		);
+		assert_eq!(all.nodes.len(), 4);
+		assert_eq!(all.edges.len(), 3);
+		let mut keys: Vec<String> = all.nodes[1..]
+			.iter()
+			.map(|n| {
+				n["properties"]["canonical_key"]
+					.as_str()
+					.unwrap()
+					.to_string()
+			})
+			.collect();
+		keys.sort();
+		assert_eq!(
+			keys,
+			vec!["email:admin@pivot.example", "ip:192.0.2.1", "ip:192.0.2.2"]
+		);
+
+		// Edge type filter and limit are applied.
+		let resolved = AgeRepo::neighbors(
+			&client,
+			"Domain",
+			"domain:pivot.example",
+			&["RESOLVES_TO"],
+			1,
+		)
+		.await
+		.expect("filtered neighbors")
+		.expect("node exists");
+		assert_eq!(resolved.edges.len(), 1);
+		assert_eq!(resolved.edges[0]["type"], "RESOLVES_TO");
+		assert_eq!(resolved.edges[0]["properties"]["source"], "test");
+
+		let missing = AgeRepo::neighbors(&client, "Domain", "domain:absent.example", &[], 10)
+			.await
+			.expect("lookup missing");
+		assert!(missing.is_none());
+	})
+	.await;
+
+	vanopticon_heimdall::devops::stop_dev_db()
+		.await
+		.expect("stop db");
+}
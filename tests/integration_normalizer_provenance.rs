@@ -0,0 +1,82 @@
+mod common;
+
+use std::sync::Arc;
+
+use vanopticon_heimdall::age_client::{AgeClient, AgeRepo};
+use vanopticon_heimdall::ingest::NormalizedRecord;
+use vanopticon_heimdall::lib::normalizers::normalizer_version;
+use vanopticon_heimdall::observability::MetricsRegistry;
+use vanopticon_heimdall::sink::{AgeSink, RecordSink};
+
+#[tokio::test]
+async fn integration_ip_nodes_record_normalizer_provenance() {
+	// This integration test is gated behind an env var to avoid running Docker in CI by default.
+	if !common::check_docker_enabled() {
+		return;
+	}
+
+	vanopticon_heimdall::devops::start_dev_db()
+		.await
+		.expect("start db");
+
+	common::with_ephemeral_graph(|pool, graph| async move {
+		let client = Arc::new(AgeClient::new(pool.clone(), graph.clone()));
+		// A closed batcher channel makes the sink write synchronously.
+		let (tx, rx) = tokio::sync::mpsc::channel(1);
+		drop(rx);
+		let repo: Arc<dyn AgeRepo> = client.clone();
+		let sink = AgeSink::new(repo, tx, Arc::new(MetricsRegistry::new())).with_provenance(true);
+		for ip in ["10.0.0.1", "10.0.0.2"] {
+			sink.send(&NormalizedRecord::new("ip", ip, ip))
+				.await
+				.expect("ingest ip");
+		}
+
+		let current = normalizer_version("ip").unwrap();
+		let read = "MATCH (n:FieldValue {canonical_key: \"ip:10.0.0.1\"}) \
+		            RETURN [n.normalizer, n.normalizer_version]";
+		let stamped: String =
+			sqlx::query_scalar("SELECT v::text FROM cypher($1::text, $2::text) as (v agtype);")
+				.bind(&graph)
+				.bind(read)
+				.fetch_one(&pool)
+				.await
+				.expect("read provenance");
+		assert_eq!(
+			serde_json::from_str::<serde_json::Value>(&stamped).unwrap(),
+			serde_json::json!(["ip", current])
+		);
+
+		// Nothing is outdated until a node predates the current version.
+		assert!(
+			client
+				.outdated_normalizations("ip", 10)
+				.await
+				.expect("find outdated")
+				.is_empty()
+		);
+		let age = format!(
+			"MATCH (n:FieldValue {{canonical_key: \"ip:10.0.0.2\"}}) \
+			 SET n.normalizer_version = {} RETURN n",
+			current - 1
+		);
+		sqlx::query("SELECT * FROM cypher($1::text, $2::text) as (v agtype);")
+			.bind(&graph)
+			.bind(&age)
+			.execute(&pool)
+			.await
+			.expect("age node");
+		assert_eq!(
+			client
+				.outdated_normalizations("ip", 10)
+				.await
+				.expect("find outdated"),
+			vec!["ip:10.0.0.2".to_string()]
+		);
+	})
+	.await;
+
+	vanopticon_heimdall::devops::stop_dev_db()
+		.await
+		.expect("stop db");
+}
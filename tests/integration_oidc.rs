@@ -0,0 +1,431 @@
+//! Token validation and JWKS refresh tests against a mock OIDC provider.
+//!
+//! The provider serves signing key `k1` until it is rotated, after which it
+//! only serves `k2`. Keys are fixtures in `tests/fixtures/oidc`.
+
+#![cfg(feature = "integration-tests")]
+
+use axum::{
+	Json, Router,
+	extract::State,
+	http::StatusCode,
+	routing::{get, post},
+};
+use jsonwebtoken::{Algorithm, EncodingKey, Header, encode};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use vanopticon_heimdall::sync::OidcProvider;
+
+const CLIENT_ID: &str = "heimdall-test";
+const JWKS: &str = include_str!("fixtures/oidc/jwks.json");
+const K1_PEM: &str = include_str!("fixtures/oidc/k1.pem");
+const K2_PEM: &str = include_str!("fixtures/oidc/k2.pem");
+const EC1_PEM: &str = include_str!("fixtures/oidc/ec1.pem");
+const ED1_PEM: &str = include_str!("fixtures/oidc/ed1.pem");
+
+struct MockIdp {
+	issuer: String,
+	rotated: AtomicBool,
+	jwks_fetches: AtomicUsize,
+	/// `expires_in` of issued access tokens.
+	token_ttl: AtomicU64,
+	token_fetches: AtomicUsize,
+	discovery_fetches: AtomicUsize,
+	/// Discovery requests still to be answered with 503.
+	discovery_failures: AtomicUsize,
+}
+
+async fn discovery(State(idp): State<Arc<MockIdp>>) -> Result<Json<Value>, StatusCode> {
+	idp.discovery_fetches.fetch_add(1, Ordering::SeqCst);
+	let failing = idp
+		.discovery_failures
+		.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+		.is_ok();
+	if failing {
+		return Err(StatusCode::SERVICE_UNAVAILABLE);
+	}
+	Ok(Json(json!({
+		"issuer": idp.issuer,
+		"authorization_endpoint": format!("{}/authorize", idp.issuer),
+		"token_endpoint": format!("{}/token", idp.issuer),
+		"jwks_uri": format!("{}/jwks", idp.issuer),
+	})))
+}
+
+async fn jwks(State(idp): State<Arc<MockIdp>>) -> Json<Value> {
+	idp.jwks_fetches.fetch_add(1, Ordering::SeqCst);
+	let kid = if idp.rotated.load(Ordering::SeqCst) {
+		"k2"
+	} else {
+		"k1"
+	};
+	let all: Value = serde_json::from_str(JWKS).unwrap();
+	let keys: Vec<Value> = all["keys"]
+		.as_array()
+		.unwrap()
+		.iter()
+		.filter(|k| k["kid"] == kid || k["kty"] != "RSA")
+		.cloned()
+		.collect();
+	Json(json!({ "keys": keys }))
+}
+
+/// Issue a distinct access token per request.
+async fn issue_token(State(idp): State<Arc<MockIdp>>) -> Json<Value> {
+	let n = idp.token_fetches.fetch_add(1, Ordering::SeqCst) + 1;
+	Json(json!({
+		"access_token": format!("token-{n}"),
+		"token_type": "Bearer",
+		"expires_in": idp.token_ttl.load(Ordering::SeqCst),
+	}))
+}
+
+/// Start the mock provider and return it with an initialized client.
+async fn start(min_refresh: Duration) -> (Arc<MockIdp>, Arc<OidcProvider>) {
+	start_with(|p| p.with_min_refresh_interval(min_refresh)).await
+}
+
+/// Start the mock provider with a client customized by `configure`.
+async fn start_with(
+	configure: impl FnOnce(OidcProvider) -> OidcProvider,
+) -> (Arc<MockIdp>, Arc<OidcProvider>) {
+	let (idp, provider) = serve(0, configure).await;
+	provider.initialize().await.expect("initialize provider");
+	(idp, Arc::new(provider))
+}
+
+/// Start the mock provider, failing the first `discovery_failures`
+/// discovery requests, and return it with an uninitialized client.
+async fn serve(
+	discovery_failures: usize,
+	configure: impl FnOnce(OidcProvider) -> OidcProvider,
+) -> (Arc<MockIdp>, OidcProvider) {
+	let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let issuer = format!("http://{}", listener.local_addr().unwrap());
+	let idp = Arc::new(MockIdp {
+		issuer: issuer.clone(),
+		rotated: AtomicBool::new(false),
+		jwks_fetches: AtomicUsize::new(0),
+		token_ttl: AtomicU64::new(300),
+		token_fetches: AtomicUsize::new(0),
+		discovery_fetches: AtomicUsize::new(0),
+		discovery_failures: AtomicUsize::new(discovery_failures),
+	});
+	let app = Router::new()
+		.route("/.well-known/openid-configuration", get(discovery))
+		.route("/jwks", get(jwks))
+		.route("/token", post(issue_token))
+		.with_state(idp.clone());
+	tokio::spawn(async move {
+		axum::serve(listener, app).await.unwrap();
+	});
+
+	let provider = configure(OidcProvider::new(
+		format!("{}/.well-known/openid-configuration", issuer),
+		CLIENT_ID.to_string(),
+		"secret".to_string(),
+	));
+	(idp, provider)
+}
+
+fn token(issuer: &str, kid: &str, pem: &str) -> String {
+	token_with(issuer, json!(CLIENT_ID), kid, pem)
+}
+
+/// Sign a token with an arbitrary `aud` claim, string or array.
+fn token_with(issuer: &str, aud: Value, kid: &str, pem: &str) -> String {
+	let key = EncodingKey::from_rsa_pem(pem.as_bytes()).unwrap();
+	signed(issuer, aud, kid, Algorithm::RS256, &key)
+}
+
+/// Sign a token with any algorithm and key.
+fn signed(issuer: &str, aud: Value, kid: &str, alg: Algorithm, key: &EncodingKey) -> String {
+	let now = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.unwrap()
+		.as_secs();
+	let claims = json!({
+		"sub": "peer-node",
+		"iss": issuer,
+		"aud": aud,
+		"exp": now + 300,
+		"iat": now,
+	});
+	let mut header = Header::new(alg);
+	header.kid = Some(kid.to_string());
+	encode(&header, &claims, key).unwrap()
+}
+
+#[tokio::test]
+async fn rotated_key_validates_after_automatic_refresh() {
+	let (idp, provider) = start(Duration::ZERO).await;
+	assert_eq!(idp.jwks_fetches.load(Ordering::SeqCst), 1);
+
+	let old = token(&idp.issuer, "k1", K1_PEM);
+	assert_eq!(
+		provider.validate_token(&old).await.unwrap().sub,
+		"peer-node"
+	);
+
+	idp.rotated.store(true, Ordering::SeqCst);
+	let new = token(&idp.issuer, "k2", K2_PEM);
+	let claims = provider
+		.validate_token(&new)
+		.await
+		.expect("token signed with rotated key validates");
+	assert_eq!(claims.sub, "peer-node");
+	assert_eq!(idp.jwks_fetches.load(Ordering::SeqCst), 2);
+
+	// The old key is gone after rotation.
+	assert!(provider.validate_token(&old).await.is_err());
+}
+
+#[tokio::test]
+async fn unknown_kid_refreshes_are_rate_limited() {
+	let (idp, provider) = start(Duration::from_secs(3600)).await;
+	idp.rotated.store(true, Ordering::SeqCst);
+
+	// A burst of tokens signed with the new key shares a single refresh.
+	let new = token(&idp.issuer, "k2", K2_PEM);
+	let mut tasks = Vec::new();
+	for _ in 0..8 {
+		let provider = provider.clone();
+		let new = new.clone();
+		tasks.push(tokio::spawn(
+			async move { provider.validate_token(&new).await },
+		));
+	}
+	for task in tasks {
+		task.await.unwrap().expect("token validates");
+	}
+	assert_eq!(idp.jwks_fetches.load(Ordering::SeqCst), 2);
+
+	// Further unknown kids within the interval do not hit the provider.
+	for _ in 0..5 {
+		let bogus = token(&idp.issuer, "k3", K1_PEM);
+		assert!(provider.validate_token(&bogus).await.is_err());
+	}
+	assert_eq!(idp.jwks_fetches.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn periodic_refresh_picks_up_rotated_keys() {
+	let (idp, provider) = start(Duration::from_secs(3600)).await;
+	idp.rotated.store(true, Ordering::SeqCst);
+	let handle = provider.spawn_jwks_refresh(Duration::from_millis(50));
+
+	let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+	while idp.jwks_fetches.load(Ordering::SeqCst) < 2 {
+		assert!(
+			tokio::time::Instant::now() < deadline,
+			"no periodic refresh"
+		);
+		tokio::time::sleep(Duration::from_millis(10)).await;
+	}
+	handle.abort();
+}
+
+#[tokio::test]
+async fn audience_array_containing_client_id_validates() {
+	let (idp, provider) = start(Duration::ZERO).await;
+
+	let tok = token_with(
+		&idp.issuer,
+		json!(["other-service", CLIENT_ID]),
+		"k1",
+		K1_PEM,
+	);
+	let claims = provider
+		.validate_token(&tok)
+		.await
+		.expect("array audience naming the client validates");
+	assert_eq!(claims.aud, ["other-service", CLIENT_ID]);
+}
+
+#[tokio::test]
+async fn token_without_accepted_audience_is_rejected() {
+	let (idp, provider) = start(Duration::ZERO).await;
+
+	let tok = token_with(&idp.issuer, json!(["a", "b"]), "k1", K1_PEM);
+	assert!(provider.validate_token(&tok).await.is_err());
+	let tok = token_with(&idp.issuer, json!("a"), "k1", K1_PEM);
+	assert!(provider.validate_token(&tok).await.is_err());
+}
+
+#[tokio::test]
+async fn configured_audiences_and_issuers_are_accepted() {
+	let (idp, provider) = start_with(|p| {
+		p.with_audiences(["heimdall-federation"])
+			.with_issuers(["https://idp-a.example", "https://idp-b.example"])
+	})
+	.await;
+
+	let tok = token_with(
+		"https://idp-b.example",
+		json!("heimdall-federation"),
+		"k1",
+		K1_PEM,
+	);
+	assert!(provider.validate_token(&tok).await.is_ok());
+
+	// The configured lists replace the defaults.
+	let tok = token_with("https://idp-a.example", json!(CLIENT_ID), "k1", K1_PEM);
+	assert!(provider.validate_token(&tok).await.is_err());
+	let tok = token_with(&idp.issuer, json!("heimdall-federation"), "k1", K1_PEM);
+	assert!(provider.validate_token(&tok).await.is_err());
+}
+
+#[tokio::test]
+async fn client_credentials_token_is_cached_until_near_expiry() {
+	let (idp, provider) = start_with(|p| p.with_token_expiry_skew(Duration::from_secs(30))).await;
+
+	let first = provider
+		.get_client_credentials_token(Some("sync"))
+		.await
+		.unwrap();
+	let second = provider
+		.get_client_credentials_token(Some("sync"))
+		.await
+		.unwrap();
+	assert_eq!(first, second);
+	assert_eq!(idp.token_fetches.load(Ordering::SeqCst), 1);
+
+	// Scopes are cached separately.
+	provider.get_client_credentials_token(None).await.unwrap();
+	assert_eq!(idp.token_fetches.load(Ordering::SeqCst), 2);
+
+	provider.clear_token_cache().await;
+	let third = provider
+		.get_client_credentials_token(Some("sync"))
+		.await
+		.unwrap();
+	assert_ne!(first, third);
+	assert_eq!(idp.token_fetches.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn expiring_client_credentials_token_is_refetched() {
+	let (idp, provider) = start_with(|p| p.with_token_expiry_skew(Duration::ZERO)).await;
+	idp.token_ttl.store(1, Ordering::SeqCst);
+
+	let first = provider
+		.get_client_credentials_token(Some("sync"))
+		.await
+		.unwrap();
+	assert_eq!(
+		provider
+			.get_client_credentials_token(Some("sync"))
+			.await
+			.unwrap(),
+		first
+	);
+	tokio::time::sleep(Duration::from_millis(1100)).await;
+	let second = provider
+		.get_client_credentials_token(Some("sync"))
+		.await
+		.unwrap();
+	assert_ne!(first, second);
+	assert_eq!(idp.token_fetches.load(Ordering::SeqCst), 2);
+
+	// A token whose lifetime is within the skew is never reused.
+	let (idp, provider) = start_with(|p| p.with_token_expiry_skew(Duration::from_secs(300))).await;
+	provider.get_client_credentials_token(None).await.unwrap();
+	provider.get_client_credentials_token(None).await.unwrap();
+	assert_eq!(idp.token_fetches.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn transient_discovery_failures_are_retried() {
+	let (idp, provider) = serve(2, |p| p.with_fetch_retries(3, Duration::from_millis(10))).await;
+	provider
+		.initialize()
+		.await
+		.expect("initialize after retries");
+	assert!(provider.is_initialized().await);
+	assert_eq!(idp.discovery_fetches.load(Ordering::SeqCst), 3);
+
+	let issuer = idp.issuer.clone();
+	let claims = provider
+		.validate_token(&token(&issuer, "k1", K1_PEM))
+		.await
+		.expect("token validates");
+	assert_eq!(claims.sub, "peer-node");
+}
+
+#[tokio::test]
+async fn persistent_discovery_failure_gives_up_after_retries() {
+	let (idp, provider) = serve(usize::MAX, |p| {
+		p.with_fetch_retries(2, Duration::from_millis(10))
+			.with_fetch_timeout(Duration::from_secs(1))
+	})
+	.await;
+	let err = provider.initialize().await.unwrap_err();
+	let message = format!("{:#}", err);
+	assert!(
+		message.contains("giving up on OIDC discovery document after 3 attempts"),
+		"{}",
+		message
+	);
+	assert!(message.contains("503"), "{}", message);
+	assert_eq!(idp.discovery_fetches.load(Ordering::SeqCst), 3);
+	assert!(!provider.is_initialized().await);
+}
+
+#[tokio::test]
+async fn failed_initialization_is_retried_in_the_background() {
+	let (idp, provider) = serve(2, |p| p.with_fetch_retries(0, Duration::ZERO)).await;
+	assert!(provider.initialize().await.is_err());
+	let provider = Arc::new(provider);
+
+	provider
+		.spawn_initialize_retry(Duration::from_millis(20))
+		.await
+		.expect("retry task");
+	assert!(provider.is_initialized().await);
+	assert_eq!(idp.discovery_fetches.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn ec_and_ed25519_signed_tokens_validate() {
+	let (idp, provider) = start(Duration::ZERO).await;
+
+	let ec = EncodingKey::from_ec_pem(EC1_PEM.as_bytes()).unwrap();
+	let es256 = signed(&idp.issuer, json!(CLIENT_ID), "ec1", Algorithm::ES256, &ec);
+	let claims = provider
+		.validate_token(&es256)
+		.await
+		.expect("ES256 token validates");
+	assert_eq!(claims.sub, "peer-node");
+
+	let ed = EncodingKey::from_ed_pem(ED1_PEM.as_bytes()).unwrap();
+	let eddsa = signed(&idp.issuer, json!(CLIENT_ID), "ed1", Algorithm::EdDSA, &ed);
+	let claims = provider
+		.validate_token(&eddsa)
+		.await
+		.expect("EdDSA token validates");
+	assert_eq!(claims.sub, "peer-node");
+	assert_eq!(idp.jwks_fetches.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn algorithm_not_matching_key_type_is_rejected() {
+	let (idp, provider) = start(Duration::ZERO).await;
+
+	// An RSA signature presented under the EC key's kid.
+	let rsa = EncodingKey::from_rsa_pem(K1_PEM.as_bytes()).unwrap();
+	let forged = signed(&idp.issuer, json!(CLIENT_ID), "ec1", Algorithm::RS256, &rsa);
+	let err = provider.validate_token(&forged).await.unwrap_err();
+	assert!(err.to_string().contains("does not match EC key"), "{}", err);
+
+	// An EC signature presented under an RSA kid.
+	let ec = EncodingKey::from_ec_pem(EC1_PEM.as_bytes()).unwrap();
+	let forged = signed(&idp.issuer, json!(CLIENT_ID), "k1", Algorithm::ES256, &ec);
+	let err = provider.validate_token(&forged).await.unwrap_err();
+	assert!(
+		err.to_string().contains("does not match RSA key"),
+		"{}",
+		err
+	);
+}
@@ -1,3 +1,5 @@
+mod common;
+
 use serde_json::json;
 use std::collections::HashMap;
 use vanopticon_heimdall::pii::pii_policy::{PiiAction, PiiPolicyConfig, PiiPolicyEngine};
@@ -140,39 +142,15 @@ async fn test_ingest_with_pii_protection() {
 			.expect("engine creation")
 	);
 
-	// Create a dummy repo for testing
-	struct DummyRepo;
-	#[async_trait::async_trait]
-	impl vanopticon_heimdall::age_client::AgeRepo for DummyRepo {
-		async fn merge_entity(
-			&self,
-			_label: &str,
-			_key: &str,
-			_props: &serde_json::Value,
-		) -> anyhow::Result<()> {
-			Ok(())
-		}
-
-		async fn ping(&self) -> anyhow::Result<()> {
-			Ok(())
-		}
-
-		async fn merge_batch(
-			&self,
-			_items: &[(String, String, serde_json::Value)],
-		) -> anyhow::Result<()> {
-			Ok(())
-		}
-	}
-
 	let (tx, _rx) = mpsc::channel(16);
-	let repo: Arc<dyn vanopticon_heimdall::age_client::AgeRepo> = Arc::new(DummyRepo);
+	let repo: Arc<dyn vanopticon_heimdall::age_client::AgeRepo> = Arc::new(common::DummyRepo);
 	
-	let _app_state = AppState {
+	let _app_state = AppState::new(
 		repo,
-		persist_sender: tx,
-		pii_engine: Some(engine.clone()),
-	};
+		tx,
+		Arc::new(vanopticon_heimdall::observability::MetricsRegistry::new()),
+	)
+	.with_pii_engine(engine.clone());
 
 	// Test that PII policies are applied
 	let email_protected = engine.apply_policy("email", "user@example.com")
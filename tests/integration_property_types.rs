@@ -0,0 +1,76 @@
+mod common;
+
+use serde_json::json;
+use vanopticon_heimdall::age_client::{AgeClient, AgeError};
+
+#[tokio::test]
+async fn integration_numeric_properties_compare_as_numbers() {
+	// This integration test is gated behind an env var to avoid running Docker in CI by default.
+	if !common::check_docker_enabled() {
+		return;
+	}
+
+	vanopticon_heimdall::devops::start_dev_db()
+		.await
+		.expect("start db");
+
+	common::with_ephemeral_graph(|pool, graph| async move {
+		let client = AgeClient::new(pool.clone(), graph.clone()).with_strict_property_types();
+		for (key, count) in [("domain:busy.example", 9), ("domain:quiet.example", 3)] {
+			client
+				.merge_entity(
+					"FieldValue",
+					key,
+					&json!({ "seen_count": count, "score": 0.5 }),
+				)
+				.await
+				.expect("merge");
+		}
+
+		let keys: Vec<String> =
+			sqlx::query_scalar("SELECT k::text FROM cypher($1::text, $2::text) as (k agtype);")
+				.bind(&graph)
+				.bind(
+					"MATCH (n:FieldValue) WHERE n.seen_count > 5 AND n.score < 1 RETURN n.canonical_key",
+				)
+				.fetch_all(&pool)
+				.await
+				.expect("numeric query");
+		assert_eq!(keys, vec!["\"domain:busy.example\"".to_string()]);
+
+		// Strict typing refuses to turn the stored number into a string.
+		let err = client
+			.merge_entity(
+				"FieldValue",
+				"domain:busy.example",
+				&json!({ "seen_count": "12" }),
+			)
+			.await
+			.unwrap_err();
+		assert!(matches!(err, AgeError::PropertyType(_)), "{}", err);
+		assert!(err.to_string().contains("seen_count: number, got string"));
+
+		// Same-typed writes, and new nodes, still go through.
+		client
+			.merge_entity(
+				"FieldValue",
+				"domain:busy.example",
+				&json!({ "seen_count": 12 }),
+			)
+			.await
+			.expect("same type");
+		client
+			.merge_entity(
+				"FieldValue",
+				"domain:new.example",
+				&json!({ "seen_count": "1" }),
+			)
+			.await
+			.expect("new node");
+	})
+	.await;
+
+	vanopticon_heimdall::devops::stop_dev_db()
+		.await
+		.expect("stop db");
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use serde_json::{Value, json};
+use vanopticon_heimdall::age_client::{AgeClient, AgeRepo};
+use vanopticon_heimdall::query::{MAX_ROWS_HEADER, ndjson_response};
+
+#[tokio::test]
+async fn integration_query_stream_emits_rows_incrementally_up_to_the_cap() {
+	// This integration test is gated behind an env var to avoid running Docker in CI by default.
+	if !common::check_docker_enabled() {
+		return;
+	}
+
+	vanopticon_heimdall::devops::start_dev_db()
+		.await
+		.expect("start db");
+
+	common::with_ephemeral_graph(|pool, graph| async move {
+		let client = AgeClient::new(pool.clone(), graph.clone());
+		let items: Vec<(String, String, Value)> = (0..500)
+			.map(|i| {
+				(
+					"FieldValue".to_string(),
+					format!("stream:{:04}", i),
+					json!({"n": i}),
+				)
+			})
+			.collect();
+		client.merge_batch(&items).await.expect("seed nodes");
+
+		let cypher = "MATCH (n:FieldValue) RETURN n.canonical_key ORDER BY n.canonical_key";
+		let resp = ndjson_response(
+			AgeRepo::query_stream(&client, cypher, Duration::from_secs(30)),
+			200,
+			Duration::from_secs(30),
+		);
+		assert_eq!(resp.headers()[MAX_ROWS_HEADER], "200");
+		assert_eq!(resp.headers()["content-type"], "application/x-ndjson");
+
+		// Each row arrives as its own chunk of the body.
+		let mut body = resp.into_body().into_data_stream();
+		let mut keys = Vec::new();
+		while let Some(chunk) = body.next().await {
+			let chunk = chunk.expect("body chunk");
+			assert_eq!(chunk.iter().filter(|b| **b == b'\n').count(), 1);
+			let key: Value = serde_json::from_slice(&chunk).expect("row is JSON");
+			keys.push(key.as_str().expect("key").to_string());
+		}
+		assert_eq!(keys.len(), 200, "row cap applied");
+		assert_eq!(keys[0], "stream:0000");
+		assert_eq!(keys[199], "stream:0199");
+
+		// Below the cap every row is returned.
+		let all: Vec<_> = AgeRepo::query_stream(&client, cypher, Duration::from_secs(30))
+			.collect()
+			.await;
+		assert_eq!(all.len(), 500);
+
+		// The transaction is read-only.
+		let write = AgeRepo::query_stream(
+			&client,
+			"CREATE (n:FieldValue {canonical_key: 'sneaky'}) RETURN n",
+			Duration::from_secs(30),
+		)
+		.collect::<Vec<_>>()
+		.await;
+		assert!(matches!(write.as_slice(), [Err(_)]));
+	})
+	.await;
+
+	vanopticon_heimdall::devops::stop_dev_db()
+		.await
+		.expect("stop db");
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use serde_json::json;
+
+#[tokio::test]
+async fn integration_raw_samples_stay_bounded_and_varied() {
+	// This integration test is gated behind an env var to avoid running Docker in CI by default.
+	if !common::check_docker_enabled() {
+		return;
+	}
+
+	vanopticon_heimdall::devops::start_dev_db()
+		.await
+		.expect("start db");
+
+	common::with_ephemeral_graph(|pool, graph| async move {
+		let client = vanopticon_heimdall::age_client::AgeClient::new(pool.clone(), graph.clone())
+			.with_raw_samples(4);
+
+		// 200 sightings cycling through 20 casings of the same domain.
+		let key = format!("samples-{}.example", std::process::id());
+		for i in 0..200 {
+			let raw: String = key
+				.chars()
+				.enumerate()
+				.map(|(j, c)| {
+					if (i % 20) & (1 << (j % 5)) != 0 {
+						c.to_ascii_uppercase()
+					} else {
+						c
+					}
+				})
+				.collect();
+			client
+				.observe_value(
+					"SampleTest",
+					&key,
+					&json!({"field_type": "domain", "raw": raw}),
+					"2024-01-01T00:00:00Z",
+				)
+				.await
+				.expect("observe");
+		}
+
+		let cypher = format!(
+			"MATCH (n:SampleTest {{canonical_key: {}}}) RETURN n.raw_samples, n.seen_count",
+			serde_json::to_string(&key).unwrap()
+		);
+		let (samples, seen_count) = sqlx::query_as::<_, (String, String)>(
+			"SELECT s::text, c::text FROM cypher($1::text, $2::text) as (s agtype, c agtype);",
+		)
+		.bind(&graph)
+		.bind(&cypher)
+		.fetch_one(&pool)
+		.await
+		.expect("read samples");
+		let samples: Vec<String> = serde_json::from_str(&samples).expect("samples array");
+
+		assert_eq!(seen_count, "200");
+		assert!(samples.len() <= 4, "{:?}", samples);
+		let mut distinct = samples.clone();
+		distinct.sort();
+		distinct.dedup();
+		assert_eq!(distinct.len(), samples.len(), "{:?}", samples);
+		assert!(distinct.len() >= 2, "{:?}", samples);
+	})
+	.await;
+
+	vanopticon_heimdall::devops::stop_dev_db()
+		.await
+		.expect("stop db");
+}
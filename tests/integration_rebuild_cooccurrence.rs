@@ -0,0 +1,134 @@
+mod common;
+
+use std::collections::BTreeMap;
+
+use vanopticon_heimdall::age_client::AgeClient;
+
+type Cell = (String, String, String, String);
+
+fn cell(column: &str, key: &str) -> Cell {
+	let value = key.split_once(':').map_or(key, |(_, v)| v);
+	(
+		column.to_string(),
+		value.to_string(),
+		key.to_string(),
+		value.to_string(),
+	)
+}
+
+/// Every `CO_OCCURS` edge as `(lower key, higher key) -> count`.
+async fn cooccurrence_counts(pool: &sqlx::PgPool, graph: &str) -> BTreeMap<(String, String), i64> {
+	let cypher = "MATCH (a:FieldValue)-[e:CO_OCCURS]-(b:FieldValue) \
+	              WHERE a.canonical_key < b.canonical_key \
+	              RETURN [a.canonical_key, b.canonical_key, e.count]";
+	let rows: Vec<String> =
+		sqlx::query_scalar("SELECT v::text FROM cypher($1::text, $2::text) as (v agtype);")
+			.bind(graph)
+			.bind(cypher)
+			.fetch_all(pool)
+			.await
+			.expect("read co-occurrences");
+	rows.iter()
+		.map(|row| {
+			let (a, b, count): (String, String, i64) = serde_json::from_str(row).expect("edge row");
+			((a, b), count)
+		})
+		.collect()
+}
+
+#[tokio::test]
+async fn integration_rebuild_cooccurrence_matches_incremental_counts() {
+	// This integration test is gated behind an env var to avoid running Docker in CI by default.
+	if !common::check_docker_enabled() {
+		return;
+	}
+
+	vanopticon_heimdall::devops::start_dev_db()
+		.await
+		.expect("start db");
+
+	common::with_ephemeral_graph(|pool, graph| async move {
+		let client = AgeClient::new(pool.clone(), graph.clone());
+		let ts = "2024-01-01T00:00:00Z";
+		let rows: Vec<(&str, Vec<Cell>)> = vec![
+			(
+				"dump-1",
+				vec![
+					cell("email", "email:a@example.com"),
+					cell("domain", "domain:example.com"),
+					cell("ip", "ip:10.0.0.1"),
+				],
+			),
+			(
+				"dump-1",
+				vec![
+					cell("email", "email:a@example.com"),
+					cell("domain", "domain:example.com"),
+				],
+			),
+			(
+				"dump-2",
+				vec![
+					cell("email", "email:a@example.com"),
+					cell("ip", "ip:10.0.0.1"),
+				],
+			),
+		];
+		for (i, (dump, cells)) in rows.iter().enumerate() {
+			client
+				.persist_row(dump, i as i64, None, cells, ts)
+				.await
+				.expect("persist row");
+		}
+		assert!(cooccurrence_counts(&pool, &graph).await.is_empty());
+
+		// Scoped to dump-2, only its pair is rebuilt, counting rows of both dumps.
+		assert_eq!(
+			client.rebuild_cooccurrence(Some("dump-2")).await.unwrap(),
+			1
+		);
+		let scoped = cooccurrence_counts(&pool, &graph).await;
+		assert_eq!(
+			scoped.get(&("email:a@example.com".to_string(), "ip:10.0.0.1".to_string())),
+			Some(&2)
+		);
+		assert_eq!(scoped.len(), 1);
+
+		// The whole-graph rebuild is idempotent.
+		assert_eq!(client.rebuild_cooccurrence(None).await.unwrap(), 3);
+		assert_eq!(client.rebuild_cooccurrence(None).await.unwrap(), 3);
+		let rebuilt = cooccurrence_counts(&pool, &graph).await;
+
+		// Recording the same rows incrementally gives the same counts.
+		sqlx::query("SELECT * FROM cypher($1::text, $2::text) as (v agtype);")
+			.bind(&graph)
+			.bind("MATCH ()-[e:CO_OCCURS]->() DELETE e")
+			.execute(&pool)
+			.await
+			.expect("drop co-occurrences");
+		for (_, cells) in &rows {
+			for (i, a) in cells.iter().enumerate() {
+				for b in &cells[i + 1..] {
+					client
+						.increment_co_occurrence(&a.2, &b.2, ts)
+						.await
+						.expect("co-occur");
+				}
+			}
+		}
+		let incremental = cooccurrence_counts(&pool, &graph).await;
+		assert_eq!(rebuilt, incremental);
+		assert_eq!(
+			rebuilt.get(&(
+				"domain:example.com".to_string(),
+				"email:a@example.com".to_string()
+			)),
+			Some(&2)
+		);
+	})
+	.await;
+
+	vanopticon_heimdall::devops::stop_dev_db()
+		.await
+		.expect("stop db");
+}
@@ -0,0 +1,156 @@
+mod common;
+
+use std::sync::Arc;
+
+use serde_json::json;
+use vanopticon_heimdall::age_client::{AgeClient, AgeRepo};
+use vanopticon_heimdall::lib::normalizers::salted_key;
+use vanopticon_heimdall::persist::rekey::{KeyMap, RekeyProgress, Rekeyer};
+
+const OLD_SALT: &str = "old-salt";
+const NEW_SALT: &str = "new-salt";
+
+fn neighbor_keys(fragment: &vanopticon_heimdall::age_client::GraphFragment) -> Vec<String> {
+	let mut keys: Vec<String> = fragment.nodes[1..]
+		.iter()
+		.map(|n| {
+			n["properties"]["canonical_key"]
+				.as_str()
+				.unwrap()
+				.to_string()
+		})
+		.collect();
+	keys.sort();
+	keys
+}
+
+#[tokio::test]
+async fn integration_rekey_moves_nodes_to_new_salt_and_keeps_edges() {
+	// This integration test is gated behind an env var to avoid running Docker in CI by default.
+	if !common::check_docker_enabled() {
+		return;
+	}
+
+	vanopticon_heimdall::devops::start_dev_db()
+		.await
+		.expect("start db");
+
+	common::with_ephemeral_graph(|pool, graph| async move {
+		let client = AgeClient::new(pool.clone(), graph.clone());
+		let old = |k: &str| salted_key(k, OLD_SALT);
+		let new = |k: &str| salted_key(k, NEW_SALT);
+
+		// Values written under the old salt: one by the ingest sink (raw),
+		// one by row persistence (value), and one that a peer has already
+		// written under the new salt.
+		let nodes = [
+			(
+				old("domain:example.com"),
+				json!({"field_type": "domain", "raw": "Example.com"}),
+			),
+			(
+				old("ip:192.0.2.1"),
+				json!({"field_type": "ip", "value": "192.0.2.1"}),
+			),
+			(
+				old("email:a@example.com"),
+				json!({"field_type": "email", "raw": "a@example.com"}),
+			),
+			(
+				new("email:a@example.com"),
+				json!({"field_type": "email", "raw": "a@example.com"}),
+			),
+			("opaque-key".to_string(), json!({"field_type": "domain"})),
+		];
+		for (key, props) in &nodes {
+			client
+				.merge_entity("FieldValue", key, props)
+				.await
+				.expect("merge node");
+		}
+		let edges = [
+			(
+				old("domain:example.com"),
+				old("ip:192.0.2.1"),
+				"RESOLVES_TO",
+			),
+			(
+				old("email:a@example.com"),
+				old("domain:example.com"),
+				"REGISTERED",
+			),
+			(
+				new("email:a@example.com"),
+				old("ip:192.0.2.1"),
+				"LOGGED_IN_FROM",
+			),
+		];
+		for (from, to, rel) in &edges {
+			AgeRepo::relate(&client, from, to, rel, &json!({}))
+				.await
+				.expect("relate");
+		}
+
+		// Stop after two single-node batches, then resume with a fresh
+		// rekeyer from the stored checkpoint.
+		let repo: Arc<dyn AgeRepo> = Arc::new(AgeClient::new(pool.clone(), graph.clone()));
+		let keys = Arc::new(KeyMap::new());
+		let first = Rekeyer::new(repo.clone(), OLD_SALT, NEW_SALT)
+			.with_batch_size(1)
+			.with_key_map(keys.clone());
+		let mut progress = first.checkpoint().await.expect("checkpoint");
+		assert_eq!(progress, RekeyProgress::default());
+		first.step(&mut progress).await.expect("batch 1");
+		first.step(&mut progress).await.expect("batch 2");
+		assert_eq!(progress.scanned, 2);
+
+		let resumed = Rekeyer::new(repo.clone(), OLD_SALT, NEW_SALT)
+			.with_batch_size(2)
+			.with_key_map(keys.clone());
+		assert_eq!(resumed.checkpoint().await.expect("checkpoint"), progress);
+		let done = resumed.run(|_| {}).await.expect("run");
+		assert!(done.done);
+		assert_eq!((done.renamed, done.merged, done.unresolved), (2, 1, 1));
+		assert_eq!(keys.len(), 3);
+		assert_eq!(keys.translate(&old("ip:192.0.2.1")), new("ip:192.0.2.1"));
+
+		// Old keys are gone; new keys reach the nodes with their edges.
+		for key in ["domain:example.com", "ip:192.0.2.1", "email:a@example.com"] {
+			let stale = AgeRepo::neighbors(&client, "FieldValue", &old(key), &[], 10)
+				.await
+				.expect("neighbors");
+			assert!(stale.is_none(), "{} still has its old key", key);
+		}
+		let domain = AgeRepo::neighbors(&client, "FieldValue", &new("domain:example.com"), &[], 10)
+			.await
+			.expect("neighbors")
+			.expect("domain re-keyed");
+		assert_eq!(domain.edges.len(), 2);
+		let mut expected = vec![new("email:a@example.com"), new("ip:192.0.2.1")];
+		expected.sort();
+		assert_eq!(neighbor_keys(&domain), expected);
+
+		// The merged email node carries its own edge and the moved one.
+		let email = AgeRepo::neighbors(&client, "FieldValue", &new("email:a@example.com"), &[], 10)
+			.await
+			.expect("neighbors")
+			.expect("email merged");
+		assert_eq!(email.edges.len(), 2);
+		let mut expected = vec![new("domain:example.com"), new("ip:192.0.2.1")];
+		expected.sort();
+		assert_eq!(neighbor_keys(&email), expected);
+
+		// Unrecoverable nodes keep their key.
+		assert!(
+			AgeRepo::neighbors(&client, "FieldValue", "opaque-key", &[], 10)
+				.await
+				.expect("neighbors")
+				.is_some()
+		);
+	})
+	.await;
+
+	vanopticon_heimdall::devops::stop_dev_db()
+		.await
+		.expect("stop db");
+}
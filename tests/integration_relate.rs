@@ -0,0 +1,72 @@
+mod common;
+
+use serde_json::json;
+use vanopticon_heimdall::age_client::AgeRepo;
+
+#[tokio::test]
+async fn integration_relate_creates_directed_edge_with_props() {
+	// This integration test is gated behind an env var to avoid running Docker in CI by default.
+	if !common::check_docker_enabled() {
+		return;
+	}
+
+	vanopticon_heimdall::devops::start_dev_db()
+		.await
+		.expect("start db");
+
+	common::with_ephemeral_graph(|pool, graph| async move {
+		let client = vanopticon_heimdall::age_client::AgeClient::new(pool.clone(), graph.clone());
+
+		let domain = "relate.example";
+		let ip = "relate-ip";
+		client
+			.merge_entity("Domain", domain, &json!({}))
+			.await
+			.expect("merge domain");
+		client
+			.merge_entity("IPAddress", ip, &json!({}))
+			.await
+			.expect("merge ip");
+
+		// Relating twice merges one edge and updates its properties.
+		AgeRepo::relate(&client, domain, ip, "RESOLVES_TO", &json!({"ttl": 60}))
+			.await
+			.expect("first relate");
+		AgeRepo::relate(&client, domain, ip, "RESOLVES_TO", &json!({"ttl": 300}))
+			.await
+			.expect("second relate");
+
+		let cypher = format!(
+			"MATCH (a {{canonical_key: {}}})-[e:RESOLVES_TO]->(b {{canonical_key: {}}}) RETURN e.ttl",
+			serde_json::to_string(domain).unwrap(),
+			serde_json::to_string(ip).unwrap()
+		);
+		let ttls = sqlx::query_scalar::<_, String>(
+			"SELECT t::text FROM cypher($1::text, $2::text) as (t agtype);",
+		)
+		.bind(&graph)
+		.bind(&cypher)
+		.fetch_all(&pool)
+		.await
+		.expect("read edge");
+		assert_eq!(ttls, vec!["300".to_string()]);
+
+		// Direction is explicit: nothing points from the IP back to the domain.
+		let reverse = format!(
+			"MATCH (a {{canonical_key: {}}})-[e:RESOLVES_TO]->(b) RETURN e",
+			serde_json::to_string(ip).unwrap()
+		);
+		let rows = sqlx::query("SELECT * FROM cypher($1::text, $2::text) as (e agtype);")
+			.bind(&graph)
+			.bind(&reverse)
+			.fetch_all(&pool)
+			.await
+			.expect("read reverse edge");
+		assert!(rows.is_empty());
+	})
+	.await;
+
+	vanopticon_heimdall::devops::stop_dev_db()
+		.await
+		.expect("stop db");
+}
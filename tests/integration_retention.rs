@@ -0,0 +1,75 @@
+mod common;
+
+use std::time::Duration;
+
+use serde_json::json;
+use vanopticon_heimdall::age_client::{AgeClient, RetentionAction};
+use vanopticon_heimdall::observability::MetricsRegistry;
+use vanopticon_heimdall::persist::retention::RetentionPolicy;
+
+/// `tombstone` of the `RetentionTest` node keyed `key`, as agtype text.
+async fn tombstone(pool: &sqlx::PgPool, graph: &str, key: &str) -> Option<String> {
+	let cypher = format!(
+		"MATCH (n:RetentionTest {{canonical_key: {}}}) RETURN n.tombstone",
+		serde_json::to_string(key).unwrap()
+	);
+	sqlx::query_scalar::<_, Option<String>>(
+		"SELECT t::text FROM cypher($1::text, $2::text) as (t agtype);",
+	)
+	.bind(graph)
+	.bind(&cypher)
+	.fetch_one(pool)
+	.await
+	.expect("read tombstone")
+}
+
+#[tokio::test]
+async fn integration_retention_expires_only_nodes_past_their_ttl() {
+	// This integration test is gated behind an env var to avoid running Docker in CI by default.
+	if !common::check_docker_enabled() {
+		return;
+	}
+
+	vanopticon_heimdall::devops::start_dev_db()
+		.await
+		.expect("start db");
+
+	common::with_ephemeral_graph(|pool, graph| async move {
+		let client = AgeClient::new(pool.clone(), graph.clone());
+		let props = json!({"field_type": "token"});
+		let two_days_ago = (chrono::Utc::now() - chrono::Duration::days(2)).to_rfc3339();
+		let now = chrono::Utc::now().to_rfc3339();
+		client
+			.observe_value("RetentionTest", "token:stale", &props, &two_days_ago)
+			.await
+			.expect("observe stale");
+		client
+			.observe_value("RetentionTest", "token:fresh", &props, &now)
+			.await
+			.expect("observe fresh");
+
+		let metrics = MetricsRegistry::new();
+		let policy = RetentionPolicy::new(RetentionAction::Tombstone, 10)
+			.with_ttl("RetentionTest", Duration::from_secs(24 * 60 * 60));
+		assert_eq!(policy.sweep(&client, &metrics).await, 1);
+		assert_eq!(metrics.retention_expired_total.get(), 1);
+		assert_eq!(
+			tombstone(&pool, &graph, "token:stale").await.as_deref(),
+			Some("true")
+		);
+		assert_eq!(tombstone(&pool, &graph, "token:fresh").await, None);
+
+		// Tombstoned nodes are not expired again; deletion removes them.
+		assert_eq!(policy.sweep(&client, &metrics).await, 0);
+		let delete = RetentionPolicy::new(RetentionAction::Delete, 10)
+			.with_ttl("RetentionTest", Duration::from_secs(24 * 60 * 60));
+		assert_eq!(delete.sweep(&client, &metrics).await, 1);
+		assert_eq!(metrics.retention_expired_total.get(), 2);
+		assert_eq!(tombstone(&pool, &graph, "token:fresh").await, None);
+	})
+	.await;
+
+	vanopticon_heimdall::devops::stop_dev_db()
+		.await
+		.expect("stop db");
+}
@@ -53,6 +53,53 @@ async fn test_schema_migration() {
 		.expect("stop db");
 }
 
+#[tokio::test]
+#[cfg(feature = "integration-tests")]
+async fn test_rerunning_completed_migrations_is_a_noop() {
+	use vanopticon_heimdall::persist::migrations::{MIGRATIONS, MigrationOptions};
+
+	// Skip unless explicitly enabled
+	if env::var("RUN_DOCKER_INTEGRATION_TESTS").is_err() {
+		eprintln!("Skipping Docker integration test; set RUN_DOCKER_INTEGRATION_TESTS=1");
+		return;
+	}
+
+	// Start dev DB
+	vanopticon_heimdall::devops::start_dev_db()
+		.await
+		.expect("start db");
+
+	let pool = wait_for_postgres().await;
+	let client = AgeClient::new(pool.clone(), "heimdall_graph");
+	let opts = MigrationOptions::default();
+
+	// The first run may find some already applied by an earlier test.
+	let first = client
+		.run_migrations(MIGRATIONS, &opts)
+		.await
+		.expect("run migrations");
+	assert_eq!(first.applied.len() + first.skipped.len(), MIGRATIONS.len());
+
+	let second = client
+		.run_migrations(MIGRATIONS, &opts)
+		.await
+		.expect("re-run migrations");
+	assert!(second.applied.is_empty());
+	assert_eq!(second.skipped.len(), MIGRATIONS.len());
+
+	let statuses: Vec<String> =
+		sqlx::query_scalar("SELECT status FROM heimdall_schema_migrations ORDER BY version")
+			.fetch_all(&pool)
+			.await
+			.expect("read migration status");
+	assert!(statuses.iter().all(|s| s == "completed"));
+
+	// Clean up
+	vanopticon_heimdall::devops::stop_dev_db()
+		.await
+		.expect("stop db");
+}
+
 #[tokio::test]
 #[cfg(feature = "integration-tests")]
 async fn test_persist_row_workflow() {
@@ -294,3 +341,134 @@ async fn test_multiple_rows_same_dump() {
 		.await
 		.expect("stop db");
 }
+
+#[tokio::test]
+#[cfg(feature = "integration-tests")]
+async fn test_duplicate_row_hash_is_merged() {
+	// Skip unless explicitly enabled
+	if env::var("RUN_DOCKER_INTEGRATION_TESTS").is_err() {
+		eprintln!("Skipping Docker integration test; set RUN_DOCKER_INTEGRATION_TESTS=1");
+		return;
+	}
+
+	// Start dev DB
+	vanopticon_heimdall::devops::start_dev_db()
+		.await
+		.expect("start db");
+
+	let pool = wait_for_postgres().await;
+	let client = AgeClient::new(pool.clone(), "heimdall_graph");
+
+	// Apply schema
+	let schema_sql = include_str!("../sql/v1/001-create_graph.sql");
+	client
+		.apply_migration(schema_sql)
+		.await
+		.expect("apply migration");
+
+	let dump_id = format!("test-dump-dedup-{}", std::process::id());
+	let timestamp = "2024-01-15T10:30:00Z";
+	let cells = vec![(
+		"username".to_string(),
+		"carol".to_string(),
+		"username:carol".to_string(),
+		"carol".to_string(),
+	)];
+
+	// The same row uploaded twice (e.g. a re-uploaded dump)
+	let first = client
+		.persist_row(&dump_id, 0, Some("hash_dup"), &cells, timestamp)
+		.await
+		.expect("persist row");
+	let second = client
+		.persist_row(&dump_id, 0, Some("hash_dup"), &cells, timestamp)
+		.await
+		.expect("persist duplicate row");
+	assert!(first);
+	assert!(!second);
+
+	let cypher = format!(
+		"MATCH (r:Row {{dump_id: {}, row_hash: \"hash_dup\"}}) \
+		 OPTIONAL MATCH (r)-[:HAS_SIGHTING]->(s) \
+		 RETURN count(DISTINCT r), count(s)",
+		serde_json::to_string(&dump_id).unwrap()
+	);
+	let (rows, sightings) = sqlx::query_as::<_, (String, String)>(
+		"SELECT r::text, s::text FROM cypher($1::text, $2::text) as (r agtype, s agtype);",
+	)
+	.bind("heimdall_graph")
+	.bind(&cypher)
+	.fetch_one(&pool)
+	.await
+	.expect("count rows");
+	assert_eq!(rows, "1");
+	assert_eq!(sightings, "1");
+
+	// Clean up
+	vanopticon_heimdall::devops::stop_dev_db()
+		.await
+		.expect("stop db");
+}
+
+#[tokio::test]
+#[cfg(feature = "integration-tests")]
+async fn test_persist_row_with_row_hash() {
+	// Skip unless explicitly enabled
+	if env::var("RUN_DOCKER_INTEGRATION_TESTS").is_err() {
+		eprintln!("Skipping Docker integration test; set RUN_DOCKER_INTEGRATION_TESTS=1");
+		return;
+	}
+
+	// Start dev DB
+	vanopticon_heimdall::devops::start_dev_db()
+		.await
+		.expect("start db");
+
+	let pool = wait_for_postgres().await;
+	let client = AgeClient::new(pool.clone(), "heimdall_graph");
+
+	// Apply schema
+	let schema_sql = include_str!("../sql/v1/001-create_graph.sql");
+	client
+		.apply_migration(schema_sql)
+		.await
+		.expect("apply migration");
+
+	// A hashed row must produce valid Cypher and store the hash on the Row
+	let dump_id = format!("test-dump-hash-{}", std::process::id());
+	let cells = vec![(
+		"domain".to_string(),
+		"Example.COM".to_string(),
+		"domain:example.com".to_string(),
+		"example.com".to_string(),
+	)];
+	client
+		.persist_row(
+			&dump_id,
+			0,
+			Some("hash_valid"),
+			&cells,
+			"2024-01-15T10:30:00Z",
+		)
+		.await
+		.expect("persist row with row_hash");
+
+	let cypher = format!(
+		"MATCH (:Dump {{id: {}}})-[:HAS_ROW]->(r:Row) RETURN r.row_hash",
+		serde_json::to_string(&dump_id).unwrap()
+	);
+	let hash = sqlx::query_scalar::<_, String>(
+		"SELECT h::text FROM cypher($1::text, $2::text) as (h agtype);",
+	)
+	.bind("heimdall_graph")
+	.bind(&cypher)
+	.fetch_one(&pool)
+	.await
+	.expect("read row hash");
+	assert_eq!(hash, "\"hash_valid\"");
+
+	// Clean up
+	vanopticon_heimdall::devops::stop_dev_db()
+		.await
+		.expect("stop db");
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use serde_json::json;
+use vanopticon_heimdall::age_client::{AgeClient, AgeConnectOptions};
+
+#[tokio::test]
+async fn operations_use_configured_search_path() {
+	// This integration test is gated behind an env var to avoid running Docker in CI by default.
+	if !common::check_docker_enabled() {
+		return;
+	}
+
+	vanopticon_heimdall::devops::start_dev_db()
+		.await
+		.expect("start db");
+
+	common::with_ephemeral_graph(|pool, graph| async move {
+		// A tenant schema alongside the graph's own schema, ahead of `public`.
+		let tenant = format!("tenant_{}", std::process::id());
+		sqlx::query(&format!("CREATE SCHEMA IF NOT EXISTS {}", tenant))
+			.execute(&pool)
+			.await
+			.expect("create tenant schema");
+
+		let options = AgeConnectOptions {
+			search_path: Some(format!("ag_catalog, {}, {}, public", tenant, graph)),
+		};
+		let client = AgeClient::connect_with(&common::database_url(), &graph, options)
+			.await
+			.expect("connect with search_path");
+		client
+			.merge_entity(
+				"Tenanted",
+				"tenant.example",
+				&json!({"field_type": "domain"}),
+			)
+			.await
+			.expect("merge under configured search_path");
+		client
+			.increment_co_occurrence("tenant.example", "other.example", "2024-01-01T00:00:00Z")
+			.await
+			.expect("co-occurrence under configured search_path");
+
+		// Read back through a connection on the default search path.
+		let count: String = sqlx::query_scalar(
+			"SELECT c::text FROM ag_catalog.cypher($1::text, $2::text) as (c ag_catalog.agtype);",
+		)
+		.bind(&graph)
+		.bind("MATCH (n:Tenanted {canonical_key: \"tenant.example\"}) RETURN count(n)")
+		.fetch_one(&pool)
+		.await
+		.expect("read back node");
+		assert_eq!(count, "1");
+
+		// Without `ag_catalog` on the path the unqualified `cypher` call
+		// can't resolve, so the hook is what makes the calls above work.
+		let options = AgeConnectOptions {
+			search_path: Some("public".to_string()),
+		};
+		let client = AgeClient::connect_with(&common::database_url(), &graph, options)
+			.await
+			.expect("connect with public-only search_path");
+		assert!(
+			client
+				.merge_entity("Tenanted", "tenant.example", &json!({}))
+				.await
+				.is_err()
+		);
+
+		sqlx::query(&format!("DROP SCHEMA IF EXISTS {} CASCADE", tenant))
+			.execute(&pool)
+			.await
+			.expect("drop tenant schema");
+	})
+	.await;
+
+	vanopticon_heimdall::devops::stop_dev_db()
+		.await
+		.expect("stop db");
+}
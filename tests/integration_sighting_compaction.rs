@@ -0,0 +1,70 @@
+mod common;
+
+use vanopticon_heimdall::age_client::AgeClient;
+
+/// `count` of every `Sighting` of `ip:192.0.2.1`, oldest first.
+async fn sighting_counts(pool: &sqlx::PgPool, graph: &str) -> Vec<i64> {
+	let cypher = "MATCH (s:Sighting)-[:OBSERVED_VALUE]->(v:FieldValue {canonical_key: 'ip:192.0.2.1'}) \
+	              RETURN s.count ORDER BY s.first_seen";
+	let rows: Vec<String> =
+		sqlx::query_scalar("SELECT v::text FROM cypher($1::text, $2::text) as (v agtype);")
+			.bind(graph)
+			.bind(cypher)
+			.fetch_all(pool)
+			.await
+			.expect("read sightings");
+	rows.iter()
+		.map(|row| row.parse().expect("sighting count"))
+		.collect()
+}
+
+#[tokio::test]
+async fn integration_identical_sightings_within_the_window_coalesce() {
+	// This integration test is gated behind an env var to avoid running Docker in CI by default.
+	if !common::check_docker_enabled() {
+		return;
+	}
+
+	vanopticon_heimdall::devops::start_dev_db()
+		.await
+		.expect("start db");
+
+	common::with_ephemeral_graph(|pool, graph| async move {
+		let client = AgeClient::new(pool.clone(), graph.clone()).with_sighting_compaction(60);
+		let cells = vec![(
+			"ip".to_string(),
+			"192.0.2.1".to_string(),
+			"ip:192.0.2.1".to_string(),
+			"192.0.2.1".to_string(),
+		)];
+
+		// Ten sightings a few seconds apart, then two more each past the window.
+		let mut timestamps: Vec<String> = (0..10)
+			.map(|i| format!("2024-01-01T00:00:{:02}Z", i * 5))
+			.collect();
+		timestamps.push("2024-01-01T00:05:00Z".to_string());
+		timestamps.push("2024-01-01T00:10:00Z".to_string());
+		for (i, ts) in timestamps.iter().enumerate() {
+			client
+				.persist_row("feed", i as i64, None, &cells, ts)
+				.await
+				.expect("persist row");
+		}
+
+		assert_eq!(sighting_counts(&pool, &graph).await, vec![10, 1, 1]);
+
+		let last_seen: String =
+			sqlx::query_scalar("SELECT v::text FROM cypher($1::text, $2::text) as (v agtype);")
+				.bind(&graph)
+				.bind("MATCH (s:Sighting) WHERE s.count = 10 RETURN s.last_seen")
+				.fetch_one(&pool)
+				.await
+				.expect("read last_seen");
+		assert_eq!(last_seen, "\"2024-01-01T00:00:45+00:00\"");
+	})
+	.await;
+
+	vanopticon_heimdall::devops::stop_dev_db()
+		.await
+		.expect("stop db");
+}
@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
-use vanopticon_heimdall::sync::{ChangeLogEntry, OidcProvider, PeerConfig, SyncAgent};
+use vanopticon_heimdall::sync::{ChangeLog, ChangeLogEntry, OidcProvider, PeerConfig, SyncAgent};
 
 /// Test that the sync agent can be created with valid configuration
 #[tokio::test]
@@ -18,6 +18,7 @@ async fn test_sync_agent_creation() -> Result<(), Box<dyn std::error::Error>> {
 		port: 8443,
 		sni_hostname: "localhost".to_string(),
 		sync_interval_secs: 60,
+		client_cert: None,
 	}];
 
 	let agent = SyncAgent::new("test-node".to_string(), oidc_provider, peers)?;
@@ -51,6 +52,7 @@ async fn test_enqueue_change() -> Result<(), Box<dyn std::error::Error>> {
 		port: 8443,
 		sni_hostname: "localhost".to_string(),
 		sync_interval_secs: 60,
+		client_cert: None,
 	}];
 
 	let agent = SyncAgent::new("test-node".to_string(), oidc_provider, peers)?;
@@ -77,6 +79,65 @@ async fn test_enqueue_change() -> Result<(), Box<dyn std::error::Error>> {
 	Ok(())
 }
 
+/// Test that entries enqueued beyond the pending cap are spilled to the
+/// durable change log while tombstones stay queued
+#[tokio::test]
+#[cfg(feature = "integration-tests")]
+async fn test_enqueue_beyond_cap_spills() -> Result<(), Box<dyn std::error::Error>> {
+	let oidc_provider = Arc::new(OidcProvider::new(
+		"https://example.com/.well-known/openid-configuration".to_string(),
+		"test-client".to_string(),
+		"test-secret".to_string(),
+	));
+
+	let peers = vec![PeerConfig {
+		host: "localhost".to_string(),
+		port: 8443,
+		sni_hostname: "localhost".to_string(),
+		sync_interval_secs: 60,
+		client_cert: None,
+	}];
+
+	let spill = Arc::new(ChangeLog::in_memory());
+	let agent = SyncAgent::new("test-node".to_string(), oidc_provider, peers)?
+		.with_max_pending(3)
+		.with_spill(spill.clone());
+
+	for i in 0..10 {
+		agent
+			.enqueue_change(ChangeLogEntry {
+				id: format!("entry-{}", i),
+				timestamp: i,
+				label: "Entity".to_string(),
+				key: format!("key-{}", i),
+				props: serde_json::json!({}),
+				origin: "test-node".to_string(),
+				version_vector: HashMap::new(),
+				tombstone: i == 0,
+			})
+			.await;
+	}
+
+	assert_eq!(agent.pending_len().await, 3);
+	assert_eq!(spill.len().await, 7);
+	assert!(!spill.contains("entry-0").await, "tombstone was spilled");
+	assert_eq!(
+		agent
+			.metrics()
+			.pending_overflow
+			.load(std::sync::atomic::Ordering::Relaxed),
+		7
+	);
+	assert!(
+		agent
+			.metrics()
+			.to_prometheus_text()
+			.contains("heimdall_sync_pending_overflow_total 7")
+	);
+
+	Ok(())
+}
+
 /// Test metrics generation
 #[tokio::test]
 #[cfg(feature = "integration-tests")]
@@ -92,6 +153,7 @@ async fn test_sync_metrics_prometheus_format() -> Result<(), Box<dyn std::error:
 		port: 8443,
 		sni_hostname: "localhost".to_string(),
 		sync_interval_secs: 60,
+		client_cert: None,
 	}];
 
 	let agent = SyncAgent::new("test-node".to_string(), oidc_provider, peers)?;
@@ -143,6 +205,7 @@ async fn test_sync_network_partition_resilience() -> Result<(), Box<dyn std::err
 		port: 9999,
 		sni_hostname: "nonexistent.local".to_string(),
 		sync_interval_secs: 1,
+		client_cert: None,
 	}];
 
 	let agent = Arc::new(SyncAgent::new("test-node".to_string(), oidc_provider, peers)?);
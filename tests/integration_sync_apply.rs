@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde_json::{Value, json};
+use vanopticon_heimdall::age_client::{AgeRepo, AgeResult};
+use vanopticon_heimdall::sync::changelog::import_entries_batched;
+use vanopticon_heimdall::sync::{
+	ApplyBatching, ChangeLog, ChangeLogEntry, MergeConfig, MergeResolver,
+};
+
+/// Repo double that counts graph queries and keeps the merged node state.
+#[derive(Default)]
+struct CountingRepo {
+	nodes: Mutex<HashMap<(String, String), Value>>,
+	queries: Mutex<usize>,
+}
+
+impl CountingRepo {
+	fn merge(&self, label: &str, key: &str, props: &Value) {
+		let mut nodes = self.nodes.lock().unwrap();
+		let node = nodes
+			.entry((label.to_string(), key.to_string()))
+			.or_insert_with(|| json!({}));
+		if let (Value::Object(existing), Value::Object(incoming)) = (node, props) {
+			for (k, v) in incoming {
+				existing.insert(k.clone(), v.clone());
+			}
+		}
+	}
+
+	fn queries(&self) -> usize {
+		*self.queries.lock().unwrap()
+	}
+}
+
+#[async_trait::async_trait]
+impl AgeRepo for CountingRepo {
+	async fn merge_entity(&self, label: &str, key: &str, props: &Value) -> AgeResult<()> {
+		*self.queries.lock().unwrap() += 1;
+		self.merge(label, key, props);
+		Ok(())
+	}
+
+	async fn ping(&self) -> AgeResult<()> {
+		Ok(())
+	}
+
+	async fn merge_batch(&self, items: &[(String, String, Value)]) -> AgeResult<()> {
+		*self.queries.lock().unwrap() += 1;
+		for (label, key, props) in items {
+			self.merge(label, key, props);
+		}
+		Ok(())
+	}
+
+	async fn persist_row(
+		&self,
+		_dump_id: &str,
+		_row_index: i64,
+		_row_hash: Option<&str>,
+		_cells: &[(String, String, String, String)],
+		_timestamp: &str,
+	) -> AgeResult<()> {
+		Ok(())
+	}
+
+	async fn increment_co_occurrence(
+		&self,
+		_a_key: &str,
+		_b_key: &str,
+		_timestamp: &str,
+	) -> AgeResult<()> {
+		Ok(())
+	}
+
+	async fn persist_credential(
+		&self,
+		_from_key: &str,
+		_to_key: &str,
+		_timestamp: &str,
+	) -> AgeResult<()> {
+		Ok(())
+	}
+
+	async fn apply_migration(&self, _sql_content: &str) -> AgeResult<()> {
+		Ok(())
+	}
+}
+
+/// 1,000 pulled entries: four rounds of updates to 250 keys, with some
+/// keys deleted along the way.
+fn pulled_entries() -> Vec<ChangeLogEntry> {
+	let mut entries = Vec::new();
+	for round in 0..4u64 {
+		for key in 0..250u64 {
+			let timestamp = 1_700_000_000 + round * 1_000 + key;
+			entries.push(ChangeLogEntry {
+				id: format!("r{}-k{}", round, key),
+				timestamp,
+				label: "FieldValue".to_string(),
+				key: format!("ip:10.0.{}.{}", key / 100, key % 100),
+				props: json!({"round": round, "seen": timestamp}),
+				origin: "node-b".to_string(),
+				version_vector: HashMap::from([("node-b".to_string(), round + 1)]),
+				tombstone: round == 2 && key % 25 == 0,
+			});
+		}
+	}
+	entries
+}
+
+#[tokio::test]
+#[cfg(feature = "integration-tests")]
+async fn batched_apply_matches_per_entry_apply_with_fewer_queries() {
+	let resolver = MergeResolver::new(MergeConfig::new());
+
+	let per_entry_repo = CountingRepo::default();
+	let per_entry_log = ChangeLog::in_memory();
+	let per_entry = import_entries_batched(
+		&per_entry_repo,
+		&per_entry_log,
+		&resolver,
+		pulled_entries(),
+		&ApplyBatching::new(1, Duration::ZERO),
+	)
+	.await
+	.expect("per-entry apply");
+
+	let batched_repo = CountingRepo::default();
+	let batched_log = ChangeLog::in_memory();
+	let batched = import_entries_batched(
+		&batched_repo,
+		&batched_log,
+		&resolver,
+		pulled_entries(),
+		&ApplyBatching::new(100, Duration::from_secs(60)),
+	)
+	.await
+	.expect("batched apply");
+
+	assert_eq!(per_entry.applied, 1_000);
+	assert_eq!(batched.applied, per_entry.applied);
+	assert_eq!(
+		*batched_repo.nodes.lock().unwrap(),
+		*per_entry_repo.nodes.lock().unwrap()
+	);
+	let ids = |log: Vec<vanopticon_heimdall::sync::SequencedEntry>| {
+		log.into_iter().map(|e| e.entry.id).collect::<Vec<_>>()
+	};
+	assert_eq!(
+		ids(batched_log.since(0).await),
+		ids(per_entry_log.since(0).await)
+	);
+
+	assert!(per_entry_repo.queries() > 900);
+	assert!(
+		batched_repo.queries() <= 10,
+		"expected at most 10 batched writes, got {}",
+		batched_repo.queries()
+	);
+}
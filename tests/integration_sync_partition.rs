@@ -29,34 +29,43 @@ async fn e2e_sync_partition_basic() {
 	let graph_a = "heimdall_graph"; // default graph (already created)
 	let graph_b = "heimdall_graph_b"; // second instance
 
-	// Create second graph
-	let create_graph_sql = "SELECT ag_catalog.create_graph($1::text);";
-	sqlx::query(create_graph_sql)
-		.bind(graph_b)
-		.execute(&pool)
-		.await
-		.expect("create graph B");
-
-	// Build clients for both graphs
+	// Build clients for both graphs and create the second one
 	let client_a = vanopticon_heimdall::age_client::AgeClient::new(pool.clone(), graph_a);
 	let client_b = vanopticon_heimdall::age_client::AgeClient::new(pool.clone(), graph_b);
+	client_b
+		.create_graph(graph_b)
+		.await
+		.expect("create graph B");
 
 	let repo_a: Arc<dyn vanopticon_heimdall::age_client::AgeRepo> = Arc::new(client_a);
 	let repo_b: Arc<dyn vanopticon_heimdall::age_client::AgeRepo> = Arc::new(client_b);
 
 	// Start batchers for both instances
-	let sender_a = vanopticon_heimdall::persist::start_batcher(repo_a.clone(), 1024, 1, 100);
-	let sender_b = vanopticon_heimdall::persist::start_batcher(repo_b.clone(), 1024, 1, 100);
+	let metrics = Arc::new(vanopticon_heimdall::observability::MetricsRegistry::new());
+	let sender_a = vanopticon_heimdall::persist::start_batcher(
+		repo_a.clone(),
+		metrics.clone(),
+		1024,
+		1,
+		100,
+	);
+	let sender_b = vanopticon_heimdall::persist::start_batcher(
+		repo_b.clone(),
+		metrics.clone(),
+		1024,
+		1,
+		100,
+	);
 
 	// Scenario: Instance A receives data from source "sensor_1"
 	// Instance B receives data from source "sensor_2"
 	// Both instances should be able to sync and merge data
 
 	// Instance A: Ingest data from sensor_1
-	let job_a1 = vanopticon_heimdall::persist::PersistJob {
-		label: "Sighting".to_string(),
-		key: "sighting_sensor1_001".to_string(),
-		props: json!({
+	let job_a1 = vanopticon_heimdall::persist::PersistJob::new(
+		"Sighting",
+		"sighting_sensor1_001",
+		json!({
 			"canonical_key": "sighting_sensor1_001",
 			"source": "sensor_1",
 			"instance": "heimdall_a",
@@ -64,14 +73,14 @@ async fn e2e_sync_partition_basic() {
 			"timestamp": "2024-01-01T10:00:00Z",
 			"partition_key": "sensor_1",
 		}),
-	};
-	vanopticon_heimdall::persist::submit_job(&sender_a, job_a1)
+	);
+	vanopticon_heimdall::persist::submit_job(&sender_a, job_a1, &metrics)
 		.expect("submit job to instance A");
 
-	let job_a2 = vanopticon_heimdall::persist::PersistJob {
-		label: "Sighting".to_string(),
-		key: "sighting_sensor1_002".to_string(),
-		props: json!({
+	let job_a2 = vanopticon_heimdall::persist::PersistJob::new(
+		"Sighting",
+		"sighting_sensor1_002",
+		json!({
 			"canonical_key": "sighting_sensor1_002",
 			"source": "sensor_1",
 			"instance": "heimdall_a",
@@ -79,15 +88,15 @@ async fn e2e_sync_partition_basic() {
 			"timestamp": "2024-01-01T10:01:00Z",
 			"partition_key": "sensor_1",
 		}),
-	};
-	vanopticon_heimdall::persist::submit_job(&sender_a, job_a2)
+	);
+	vanopticon_heimdall::persist::submit_job(&sender_a, job_a2, &metrics)
 		.expect("submit second job to instance A");
 
 	// Instance B: Ingest data from sensor_2
-	let job_b1 = vanopticon_heimdall::persist::PersistJob {
-		label: "Sighting".to_string(),
-		key: "sighting_sensor2_001".to_string(),
-		props: json!({
+	let job_b1 = vanopticon_heimdall::persist::PersistJob::new(
+		"Sighting",
+		"sighting_sensor2_001",
+		json!({
 			"canonical_key": "sighting_sensor2_001",
 			"source": "sensor_2",
 			"instance": "heimdall_b",
@@ -95,14 +104,14 @@ async fn e2e_sync_partition_basic() {
 			"timestamp": "2024-01-01T10:00:00Z",
 			"partition_key": "sensor_2",
 		}),
-	};
-	vanopticon_heimdall::persist::submit_job(&sender_b, job_b1)
+	);
+	vanopticon_heimdall::persist::submit_job(&sender_b, job_b1, &metrics)
 		.expect("submit job to instance B");
 
-	let job_b2 = vanopticon_heimdall::persist::PersistJob {
-		label: "Sighting".to_string(),
-		key: "sighting_sensor2_002".to_string(),
-		props: json!({
+	let job_b2 = vanopticon_heimdall::persist::PersistJob::new(
+		"Sighting",
+		"sighting_sensor2_002",
+		json!({
 			"canonical_key": "sighting_sensor2_002",
 			"source": "sensor_2",
 			"instance": "heimdall_b",
@@ -110,8 +119,8 @@ async fn e2e_sync_partition_basic() {
 			"timestamp": "2024-01-01T10:01:00Z",
 			"partition_key": "sensor_2",
 		}),
-	};
-	vanopticon_heimdall::persist::submit_job(&sender_b, job_b2)
+	);
+	vanopticon_heimdall::persist::submit_job(&sender_b, job_b2, &metrics)
 		.expect("submit second job to instance B");
 
 	// Allow time for batchers to flush
@@ -142,10 +151,10 @@ async fn e2e_sync_partition_basic() {
 
 	// Simulate sync: Copy instance B's data to instance A
 	// In a real sync scenario, this would be done via API or message queue
-	let sync_job_b1 = vanopticon_heimdall::persist::PersistJob {
-		label: "Sighting".to_string(),
-		key: "sighting_sensor2_001".to_string(),
-		props: json!({
+	let sync_job_b1 = vanopticon_heimdall::persist::PersistJob::new(
+		"Sighting",
+		"sighting_sensor2_001",
+		json!({
 			"canonical_key": "sighting_sensor2_001",
 			"source": "sensor_2",
 			"instance": "heimdall_b",
@@ -155,14 +164,14 @@ async fn e2e_sync_partition_basic() {
 			"synced_from": "heimdall_b",
 			"synced_at": "2024-01-01T10:05:00Z",
 		}),
-	};
-	vanopticon_heimdall::persist::submit_job(&sender_a, sync_job_b1)
+	);
+	vanopticon_heimdall::persist::submit_job(&sender_a, sync_job_b1, &metrics)
 		.expect("sync job from B to A");
 
-	let sync_job_b2 = vanopticon_heimdall::persist::PersistJob {
-		label: "Sighting".to_string(),
-		key: "sighting_sensor2_002".to_string(),
-		props: json!({
+	let sync_job_b2 = vanopticon_heimdall::persist::PersistJob::new(
+		"Sighting",
+		"sighting_sensor2_002",
+		json!({
 			"canonical_key": "sighting_sensor2_002",
 			"source": "sensor_2",
 			"instance": "heimdall_b",
@@ -172,8 +181,8 @@ async fn e2e_sync_partition_basic() {
 			"synced_from": "heimdall_b",
 			"synced_at": "2024-01-01T10:05:00Z",
 		}),
-	};
-	vanopticon_heimdall::persist::submit_job(&sender_a, sync_job_b2)
+	);
+	vanopticon_heimdall::persist::submit_job(&sender_a, sync_job_b2, &metrics)
 		.expect("sync second job from B to A");
 
 	sleep(Duration::from_millis(500)).await;
@@ -216,10 +225,8 @@ async fn e2e_sync_partition_basic() {
 	);
 
 	// Cleanup: Drop the second graph
-	let drop_graph_sql = "SELECT ag_catalog.drop_graph($1::text, true);";
-	sqlx::query(drop_graph_sql)
-		.bind(graph_b)
-		.execute(&pool)
+	vanopticon_heimdall::age_client::AgeClient::new(pool.clone(), graph_a)
+		.drop_graph(graph_b, true)
 		.await
 		.expect("drop graph B");
 
@@ -253,7 +260,14 @@ async fn e2e_sync_partition_deduplication() {
 	let graph_name = "heimdall_graph";
 	let client = vanopticon_heimdall::age_client::AgeClient::new(pool.clone(), graph_name);
 	let repo: Arc<dyn vanopticon_heimdall::age_client::AgeRepo> = Arc::new(client);
-	let sender = vanopticon_heimdall::persist::start_batcher(repo.clone(), 1024, 1, 100);
+	let metrics = Arc::new(vanopticon_heimdall::observability::MetricsRegistry::new());
+	let sender = vanopticon_heimdall::persist::start_batcher(
+		repo.clone(),
+		metrics.clone(),
+		1024,
+		1,
+		100,
+	);
 
 	// Scenario: Same entity appears in multiple partitions
 	// The sync process should deduplicate based on canonical_key
@@ -261,36 +275,36 @@ async fn e2e_sync_partition_deduplication() {
 	let shared_ip = "192.168.1.100";
 
 	// Partition 1: Entity from sensor_1
-	let job1 = vanopticon_heimdall::persist::PersistJob {
-		label: "IPAddress".to_string(),
-		key: shared_ip.to_string(),
-		props: json!({
+	let job1 = vanopticon_heimdall::persist::PersistJob::new(
+		"IPAddress",
+		shared_ip.to_string(),
+		json!({
 			"canonical_key": shared_ip,
 			"field_type": "ip",
 			"first_seen": "2024-01-01T10:00:00Z",
 			"partition_key": "sensor_1",
 			"seen_count": 1,
 		}),
-	};
-	vanopticon_heimdall::persist::submit_job(&sender, job1)
+	);
+	vanopticon_heimdall::persist::submit_job(&sender, job1, &metrics)
 		.expect("submit first partition job");
 
 	sleep(Duration::from_millis(200)).await;
 
 	// Partition 2: Same entity from sensor_2 (simulating sync from another instance)
 	// MERGE should update the existing node rather than creating a duplicate
-	let job2 = vanopticon_heimdall::persist::PersistJob {
-		label: "IPAddress".to_string(),
-		key: shared_ip.to_string(),
-		props: json!({
+	let job2 = vanopticon_heimdall::persist::PersistJob::new(
+		"IPAddress",
+		shared_ip.to_string(),
+		json!({
 			"canonical_key": shared_ip,
 			"field_type": "ip",
 			"last_seen": "2024-01-01T11:00:00Z",
 			"partition_key": "sensor_2",
 			"seen_count": 2,
 		}),
-	};
-	vanopticon_heimdall::persist::submit_job(&sender, job2)
+	);
+	vanopticon_heimdall::persist::submit_job(&sender, job2, &metrics)
 		.expect("submit second partition job");
 
 	sleep(Duration::from_millis(500)).await;
@@ -0,0 +1,41 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+#[tokio::test]
+async fn ephemeral_graph_is_dropped_when_test_panics() {
+	// This integration test is gated behind an env var to avoid running Docker in CI by default.
+	if !common::check_docker_enabled() {
+		return;
+	}
+
+	vanopticon_heimdall::devops::start_dev_db()
+		.await
+		.expect("start db");
+
+	let seen: Arc<Mutex<Option<String>>> = Arc::default();
+	let captured = seen.clone();
+	let outcome = tokio::spawn(common::with_ephemeral_graph(|pool, graph| async move {
+		assert!(
+			common::graph_exists(&pool, &graph).await.unwrap(),
+			"graph exists while the test runs"
+		);
+		*captured.lock().unwrap() = Some(graph);
+		panic!("simulated test failure");
+	}))
+	.await;
+
+	// The panic is propagated to the caller...
+	assert!(outcome.unwrap_err().is_panic());
+
+	// ...after the graph has been dropped.
+	let graph = seen.lock().unwrap().clone().expect("closure ran");
+	let pool = common::wait_for_postgres(&common::database_url(), 30)
+		.await
+		.expect("connect to postgres");
+	assert!(!common::graph_exists(&pool, &graph).await.unwrap());
+
+	vanopticon_heimdall::devops::stop_dev_db()
+		.await
+		.expect("stop db");
+}
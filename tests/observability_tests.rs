@@ -1,3 +1,5 @@
+mod common;
+
 use vanopticon_heimdall::observability::{MetricsRegistry, init_metrics};
 
 #[cfg(feature = "unit-tests")]
@@ -90,32 +92,7 @@ async fn test_metrics_in_persist_workflow() {
 	use vanopticon_heimdall::persist::{PersistJob, submit_job, start_batcher};
 	use serde_json::json;
 
-	// Create a dummy repo
-	struct DummyRepo;
-	#[async_trait::async_trait]
-	impl vanopticon_heimdall::age_client::AgeRepo for DummyRepo {
-		async fn merge_entity(
-			&self,
-			_label: &str,
-			_key: &str,
-			_props: &serde_json::Value,
-		) -> anyhow::Result<()> {
-			Ok(())
-		}
-
-		async fn ping(&self) -> anyhow::Result<()> {
-			Ok(())
-		}
-
-		async fn merge_batch(
-			&self,
-			_items: &[(String, String, serde_json::Value)],
-		) -> anyhow::Result<()> {
-			Ok(())
-		}
-	}
-
-	let repo: Arc<dyn vanopticon_heimdall::age_client::AgeRepo> = Arc::new(DummyRepo);
+	let repo: Arc<dyn vanopticon_heimdall::age_client::AgeRepo> = Arc::new(common::DummyRepo);
 	let metrics = Arc::new(MetricsRegistry::new());
 
 	// Start batcher
@@ -123,11 +100,7 @@ async fn test_metrics_in_persist_workflow() {
 
 	// Submit some jobs
 	for i in 0..5 {
-		let job = PersistJob {
-			label: "TestNode".to_string(),
-			key: format!("key_{}", i),
-			props: json!({ "test": true }),
-		};
+		let job = PersistJob::new("TestNode", format!("key_{}", i), json!({ "test": true }));
 		let _ = submit_job(&sender, job, &metrics);
 	}
 
@@ -148,40 +121,11 @@ async fn test_tracing_spans_in_handler() {
 	use vanopticon_heimdall::ingest::ndjson_upload;
 	use vanopticon_heimdall::state::AppState;
 
-	// Setup dummy repo and metrics
-	struct DummyRepo;
-	#[async_trait::async_trait]
-	impl vanopticon_heimdall::age_client::AgeRepo for DummyRepo {
-		async fn merge_entity(
-			&self,
-			_label: &str,
-			_key: &str,
-			_props: &serde_json::Value,
-		) -> anyhow::Result<()> {
-			Ok(())
-		}
-
-		async fn ping(&self) -> anyhow::Result<()> {
-			Ok(())
-		}
-
-		async fn merge_batch(
-			&self,
-			_items: &[(String, String, serde_json::Value)],
-		) -> anyhow::Result<()> {
-			Ok(())
-		}
-	}
-
-	let repo: Arc<dyn vanopticon_heimdall::age_client::AgeRepo> = Arc::new(DummyRepo);
+	let repo: Arc<dyn vanopticon_heimdall::age_client::AgeRepo> = Arc::new(common::DummyRepo);
 	let metrics = Arc::new(MetricsRegistry::new());
 	let (tx, _rx) = tokio::sync::mpsc::channel(16);
 
-	let app_state = AppState {
-		repo,
-		persist_sender: tx,
-		metrics: metrics.clone(),
-	};
+	let app_state = AppState::new(repo, tx, metrics.clone());
 
 	// Create a test request
 	let payload = r#"{"field_type":"domain","value":"example.com"}"#;